@@ -10,8 +10,16 @@ export function getDashboardValueSize(value: unknown): number {
   ]).size
 }
 
-export function getDashboardValueKind(value: unknown): string {
-  return typeof value === 'string' && value.startsWith('0x')
-    ? 'BINARY DATA'
-    : 'UTF-8 TEXT'
+const CONTENT_TYPE_LABELS: Record<string, string> = {
+  json: 'JSON',
+  text: 'UTF-8 TEXT',
+  binary: 'BINARY DATA',
+  msgpack: 'MESSAGEPACK',
+  protobuf: 'PROTOBUF (HEX)',
+  blob: 'BLOB REFERENCE',
+}
+
+// Content type as reported by the server snapshot (`content_type`)
+export function getDashboardValueKind(contentType: string | null | undefined): string {
+  return (contentType && CONTENT_TYPE_LABELS[contentType]) || 'UNKNOWN'
 }
@@ -1,6 +1,27 @@
 import { type ClassValue, clsx } from "clsx"
 import { twMerge } from "tailwind-merge"
 
+export function formatBytes(bytes: number): string {
+  if (bytes < 1024) return `${bytes} B`
+  const units = ["KB", "MB", "GB", "TB"]
+  let value = bytes / 1024
+  let unit = 0
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024
+    unit++
+  }
+  return `${value.toFixed(1)} ${units[unit]}`
+}
+
+/** Sidebar filter: `key=value` / `key!=value` terms become the server-side label selector, the rest match names */
+export function splitLabelFilter(filter: string): { name: string; label: string } {
+  const terms = filter.trim().split(/\s+/).filter(Boolean)
+  return {
+    name: terms.filter(t => !t.includes("=")).join(" ").toLowerCase(),
+    label: terms.filter(t => t.includes("=")).join(","),
+  }
+}
+
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
@@ -0,0 +1,57 @@
+import { useQuery } from "@tanstack/react-query"
+import { ScrollArea } from "@/components/ui/scroll-area"
+import { QueryError } from "@/components/ui/query-error"
+import { formatBytes } from "@/lib/utils"
+import { ActivityEvent, ActivityFeed } from "@/pages/dashboard/components/activity/types.ts"
+
+function describe(e: ActivityEvent): string {
+    switch (e.event) {
+        case 'queueCreated': return `Queue ${e.queue} created`
+        case 'queueDeleted': return `Queue ${e.queue} deleted`
+        case 'dlqThreshold': return `Queue ${e.queue}: DLQ reached ${e.dlqDepth} messages (threshold ${e.threshold})`
+        case 'groupRebalanced': return `Topic ${e.topic}, group ${e.group}: rebalanced (${e.reason}), generation ${e.generation}, ${e.members} members`
+        case 'segmentsDeleted': return `Topic ${e.topic}: retention deleted ${e.segments} segments (${formatBytes(e.bytes)}), head now ${e.headSeq}`
+        case 'persistenceError': return `${e.broker}${e.resource ? ` ${e.resource}` : ''}: ${e.error}`
+    }
+}
+
+export function ActivityView() {
+    const { data, error, refetch } = useQuery({
+        queryKey: ['activity-feed'],
+        queryFn: async (): Promise<ActivityFeed> => {
+            const res = await fetch('/api/events?limit=200')
+            if (!res.ok) throw new Error('Failed to fetch activity')
+            return res.json()
+        },
+        refetchInterval: 5000,
+    })
+
+    if (error) {
+        return <QueryError error={error} onRetry={refetch} title="ERROR_LOADING_ACTIVITY" />
+    }
+
+    const events = data ?? []
+
+    return (
+        <div className="flex flex-col h-full border-2 border-border rounded-sm bg-panel overflow-hidden font-mono text-sm">
+            <div className="p-3 border-b-2 border-border text-xs font-bold text-muted-foreground uppercase tracking-widest">
+                Recent Activity ({events.length})
+            </div>
+            <ScrollArea className="flex-1">
+                {events.length === 0 ? (
+                    <div className="p-6 text-xs text-muted-foreground">No events yet</div>
+                ) : (
+                    <div className="divide-y divide-border">
+                        {events.map((e) => (
+                            <div key={e.id} className="grid grid-cols-[12rem_10rem_1fr] gap-4 px-4 py-2 text-xs">
+                                <span className="text-muted-foreground">{new Date(e.timestamp).toLocaleString()}</span>
+                                <span className={e.event === 'persistenceError' || e.event === 'dlqThreshold' ? 'text-destructive' : 'text-primary'}>{e.event}</span>
+                                <span className="text-foreground break-all">{describe(e)}</span>
+                            </div>
+                        ))}
+                    </div>
+                )}
+            </ScrollArea>
+        </div>
+    )
+}
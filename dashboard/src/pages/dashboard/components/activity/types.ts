@@ -0,0 +1,16 @@
+export type ActivityFeed = ActivityEvent[];
+
+interface EventBase {
+    id: number;
+    /** ms since epoch */
+    timestamp: number;
+}
+
+export type ActivityEvent = EventBase & (
+    | { event: 'queueCreated'; queue: string }
+    | { event: 'queueDeleted'; queue: string }
+    | { event: 'dlqThreshold'; queue: string; dlqDepth: number; threshold: number }
+    | { event: 'groupRebalanced'; topic: string; group: string; generation: number; members: number; reason: 'joined' | 'left' | 'expired' | 'seek' }
+    | { event: 'segmentsDeleted'; topic: string; segments: number; bytes: number; headSeq: number }
+    | { event: 'persistenceError'; broker: string; resource: string | null; error: string }
+);
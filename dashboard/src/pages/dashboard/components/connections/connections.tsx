@@ -0,0 +1,68 @@
+import { useQuery, useQueryClient } from "@tanstack/react-query"
+import { Button } from "@/components/ui/button"
+import { ScrollArea } from "@/components/ui/scroll-area"
+import { QueryError } from "@/components/ui/query-error"
+import { formatBytes } from "@/lib/utils"
+import { ConnectionsSnapshot } from "@/pages/dashboard/components/connections/types.ts"
+
+export function ConnectionsView() {
+    const queryClient = useQueryClient()
+
+    const { data, error, refetch } = useQuery({
+        queryKey: ['connections-snapshot'],
+        queryFn: async (): Promise<ConnectionsSnapshot> => {
+            const res = await fetch('/api/connections')
+            if (!res.ok) throw new Error('Failed to fetch connections')
+            return res.json()
+        },
+    })
+
+    const kick = async (id: string) => {
+        await fetch(`/api/connections/${encodeURIComponent(id)}/kick`, { method: 'POST' })
+        queryClient.invalidateQueries({ queryKey: ['connections-snapshot'] })
+    }
+
+    if (error) {
+        return <QueryError error={error} onRetry={refetch} title="ERROR_LOADING_CONNECTIONS" />
+    }
+
+    const connections = data ?? []
+
+    return (
+        <div className="flex flex-col h-full border-2 border-border rounded-sm bg-panel overflow-hidden font-mono text-sm">
+            <div className="p-3 border-b-2 border-border text-xs font-bold text-muted-foreground uppercase tracking-widest">
+                Connections ({connections.length})
+            </div>
+            <ScrollArea className="flex-1">
+                {connections.length === 0 ? (
+                    <div className="p-6 text-xs text-muted-foreground">No active connections</div>
+                ) : (
+                    <div className="divide-y divide-border">
+                        {connections.map((conn) => (
+                            <div key={conn.id} className="grid grid-cols-[1fr_auto] gap-4 px-4 py-3">
+                                <div className="space-y-1 min-w-0">
+                                    <div className="text-foreground truncate">{conn.peer_addr} <span className="text-muted-foreground">({conn.id})</span></div>
+                                    <div className="text-xs text-muted-foreground">
+                                        Since {new Date(conn.connected_at).toLocaleString()} · In: {conn.frames_in} frames / {formatBytes(conn.bytes_in)} · Out: {conn.frames_out} frames / {formatBytes(conn.bytes_out)} · Errors: {conn.errors} · Slowest: {conn.max_latency_ms.toFixed(1)} ms ({conn.slow_requests} slow) · Read buffers: {conn.read_buffers_reused} reused / {conn.read_buffers_allocated} allocated
+                                    </div>
+                                    {conn.subscriptions.length > 0 && (
+                                        <div className="text-xs text-muted-foreground truncate">Subscriptions: {conn.subscriptions.join(', ')}</div>
+                                    )}
+                                    {conn.groups.length > 0 && (
+                                        <div className="text-xs text-muted-foreground truncate">Groups: {conn.groups.join(', ')}</div>
+                                    )}
+                                    {conn.semaphores.length > 0 && (
+                                        <div className="text-xs text-muted-foreground truncate">Semaphores: {conn.semaphores.join(', ')}</div>
+                                    )}
+                                </div>
+                                <Button variant="destructive" size="sm" onClick={() => kick(conn.id)}>
+                                    KICK
+                                </Button>
+                            </div>
+                        ))}
+                    </div>
+                )}
+            </ScrollArea>
+        </div>
+    )
+}
@@ -0,0 +1,19 @@
+export type ConnectionsSnapshot = ConnectionSummary[];
+
+export interface ConnectionSummary {
+    id: string;
+    peer_addr: string;
+    connected_at: string;
+    frames_in: number;
+    frames_out: number;
+    bytes_in: number;
+    bytes_out: number;
+    errors: number;
+    max_latency_ms: number;
+    slow_requests: number;
+    read_buffers_reused: number;
+    read_buffers_allocated: number;
+    subscriptions: string[];
+    groups: string[];
+    semaphores: string[];
+}
@@ -1,6 +1,6 @@
 import { useState, useEffect } from "react"
 import { useQuery } from "@tanstack/react-query"
-import { formatDashboardValue, getDashboardValueSize } from "@/lib/dashboard-value"
+import { formatDashboardValue, getDashboardValueKind, getDashboardValueSize } from "@/lib/dashboard-value"
 import { Input } from "@/components/ui/input"
 import { ScrollArea } from "@/components/ui/scroll-area"
 import {
@@ -18,6 +18,7 @@ import {
 } from "lucide-react"
 import { Badge } from "@/components/ui/badge"
 import { QueryError } from "@/components/ui/query-error"
+import { formatBytes } from "@/lib/utils"
 import {PubSubBrokerSnapshot, WildcardSubscription} from "@/pages/dashboard/components/pubsub/types.ts";
 
 const PAGE_SIZE = 50
@@ -63,6 +64,8 @@ export function PubSubView() {
     const topics = data?.topics ?? []
     const totalTopics = data?.total_topics ?? 0
     const wildcards = data?.wildcards
+    const matchCache = data?.match_cache
+    const retained = data?.retained
     const hasMore = offset + PAGE_SIZE < totalTopics
     
     const allWildcards = wildcards ? [...wildcards.multi_level, ...wildcards.single_level] : []
@@ -74,7 +77,7 @@ export function PubSubView() {
     const wildcard = allWildcards.find(w => w.pattern === selectedPath)
     const selectedItem = !selectedPath ? null
         : topic ? { ...topic, is_wildcard: false }
-        : wildcard ? { full_path: wildcard.pattern, subscribers: 1, retained_value: null, is_wildcard: true, client_id: wildcard.client_id }
+        : wildcard ? { full_path: wildcard.pattern, subscribers: 1, retained_value: null, retained_content_type: null, is_wildcard: true, client_id: wildcard.client_id }
         : null
 
     return (
@@ -127,6 +130,16 @@ export function PubSubView() {
                             </button>
                         </div>
                     )}
+                    {matchCache && matchCache.capacity > 0 && (
+                        <div className="mt-2 text-[10px] uppercase text-muted-foreground">
+                            MATCH CACHE: {matchCache.entries}/{matchCache.capacity} TOPICS · {matchCache.hits} HITS · {matchCache.misses} MISSES
+                        </div>
+                    )}
+                    {retained && (
+                        <div className="mt-1 text-[10px] uppercase text-muted-foreground">
+                            RETAINED: {retained.topics} TOPICS · {formatBytes(retained.bytes)} · {retained.evictions} EVICTED · {retained.rejections} REJECTED
+                        </div>
+                    )}
                 </div>
 
                 {/* List */}
@@ -288,7 +301,7 @@ export function PubSubView() {
                                 </div>
                                 <div className="flex items-center gap-2">
                                     <FileJson className="h-3 w-3" />
-                                    <span>UTF-8 CONTENT</span>
+                                    <span>{getDashboardValueKind(selectedItem.retained_content_type)}</span>
                                 </div>
                             </div>
                         )}
@@ -4,6 +4,22 @@ export interface PubSubBrokerSnapshot {
     total_topics: number;
     topics: TopicSnapshot[];
     wildcards: WildcardSubscriptions;
+    match_cache: MatchCacheSummary;
+    retained: RetainedSummary;
+}
+
+export interface RetainedSummary {
+    topics: number;
+    bytes: number;
+    evictions: number;
+    rejections: number;
+}
+
+export interface MatchCacheSummary {
+    capacity: number;
+    entries: number;
+    hits: number;
+    misses: number;
 }
 
 export interface WildcardSubscriptions {
@@ -20,4 +36,5 @@ export interface TopicSnapshot {
     full_path: string;
     subscribers: number;
     retained_value: any | null;
+    retained_content_type: string | null;
 }
@@ -0,0 +1,30 @@
+import { ConsumerSummary } from "@/pages/dashboard/components/queue/types.ts"
+
+export function ConsumersList({ consumers }: { consumers: ConsumerSummary[] }) {
+    return (
+        <div className="mt-3 border border-border rounded-sm max-h-40 overflow-y-auto">
+            <div className="sticky top-0 bg-section-header px-2 py-1 grid grid-cols-[1fr_60px_60px_70px_90px] gap-2 text-[10px] font-bold uppercase text-muted-foreground">
+                <div>Consumer ({consumers.length})</div>
+                <div className="text-center">Prefetch</div>
+                <div className="text-center">InFlight</div>
+                <div className="text-center">Acks/s</div>
+                <div className="text-right">Last Active</div>
+            </div>
+            {consumers.map((c) => (
+                <div
+                    key={c.id}
+                    className="px-2 py-1 grid grid-cols-[1fr_60px_60px_70px_90px] gap-2 text-[10px] font-mono text-muted-foreground border-t border-border/50"
+                    title={`delivered ${c.delivered} · acked ${c.acked} · nacked ${c.nacked} · expired ${c.expired}`}
+                >
+                    <span className="truncate text-foreground">
+                        {c.id}{c.push && <span className="text-muted-foreground"> (push)</span>}{c.waiting > 0 && <span className="text-muted-foreground"> (waiting)</span>}
+                    </span>
+                    <span className="text-center">{c.prefetch}</span>
+                    <span className="text-center">{c.in_flight}</span>
+                    <span className="text-center">{c.ack_rate.toFixed(1)}</span>
+                    <span className="text-right">{new Date(c.last_active).toLocaleTimeString()}</span>
+                </div>
+            ))}
+        </div>
+    )
+}
@@ -0,0 +1,46 @@
+import { PriorityLaneSummary } from "@/pages/dashboard/components/queue/types.ts"
+
+export function PriorityLanesList({ lanes, selected, onSelect, onPurge }: {
+    lanes: PriorityLaneSummary[]
+    selected: number | null
+    onSelect: (priority: number | null) => void
+    onPurge: (priority: number) => void
+}) {
+    return (
+        <div className="mt-3 border border-border rounded-sm max-h-40 overflow-y-auto">
+            <div className="sticky top-0 bg-section-header px-2 py-1 grid grid-cols-[1fr_60px_60px_60px_60px_60px_50px] gap-2 text-[10px] font-bold uppercase text-muted-foreground">
+                <div>Priority ({lanes.length})</div>
+                <div className="text-center">Pending</div>
+                <div className="text-center">InFlight</div>
+                <div className="text-center">Sched</div>
+                <div className="text-center">Push/s</div>
+                <div className="text-center">Acks/s</div>
+                <div />
+            </div>
+            {lanes.map((l) => (
+                <div
+                    key={l.priority}
+                    onClick={() => onSelect(selected === l.priority ? null : l.priority)}
+                    className={`px-2 py-1 grid grid-cols-[1fr_60px_60px_60px_60px_60px_50px] gap-2 text-[10px] font-mono text-muted-foreground border-t border-border/50 cursor-pointer ${selected === l.priority ? 'bg-secondary' : 'hover:bg-muted/50'}`}
+                    title={`pushed ${l.pushed} · acked ${l.acked}`}
+                >
+                    <span className="text-foreground">P{l.priority}</span>
+                    <span className="text-center">{l.pending}</span>
+                    <span className="text-center">{l.inflight}</span>
+                    <span className="text-center">{l.scheduled}</span>
+                    <span className="text-center">{l.push_rate.toFixed(1)}</span>
+                    <span className="text-center">{l.ack_rate.toFixed(1)}</span>
+                    <button
+                        className="text-right text-destructive hover:underline"
+                        onClick={(e) => {
+                            e.stopPropagation()
+                            onPurge(l.priority)
+                        }}
+                    >
+                        PURGE
+                    </button>
+                </div>
+            ))}
+        </div>
+    )
+}
@@ -1,6 +1,7 @@
 import { useState, useEffect } from "react"
-import { useQuery } from "@tanstack/react-query"
-import { formatDashboardValue } from "@/lib/dashboard-value"
+import { useQuery, useQueryClient } from "@tanstack/react-query"
+import { formatDashboardValue, getDashboardValueKind } from "@/lib/dashboard-value"
+import { formatBytes, splitLabelFilter } from "@/lib/utils"
 import { Input } from "@/components/ui/input"
 import { ScrollArea } from "@/components/ui/scroll-area"
 import { Tabs, TabsList, TabsTrigger } from "@/components/ui/tabs"
@@ -11,75 +12,178 @@ import {
     Box,
     ChevronLeft,
     ChevronRight, MessageSquare,
+    Archive,
+    RotateCcw,
+    Trash2,
 } from "lucide-react"
 
 import { QueryError } from "@/components/ui/query-error"
 import {
+    ArchivedMessageSummary,
     DlqMessageSummary, MessageSummary,
+    PaginatedArchivedMessages,
     PaginatedDlqMessages,
     PaginatedMessages,
     QueueBrokerSnapshot,
     QueueSummary
 } from "@/pages/dashboard/components/queue/types.ts";
+import { ConsumersList } from "@/pages/dashboard/components/queue/consumers.tsx";
+import { PriorityLanesList } from "@/pages/dashboard/components/queue/priorities.tsx";
 
 
 
 const PAGE_SIZE = 50
 
 export function QueueView() {
+  const queryClient = useQueryClient()
+  const [filter, setFilter] = useState("")
+  const { name: nameFilter, label: labelFilter } = splitLabelFilter(filter)
   const { data: snapshot, isLoading: snapshotLoading, error: snapshotError, refetch } = useQuery({
-    queryKey: ['queue-snapshot'],
+    queryKey: ['queue-snapshot', labelFilter],
     queryFn: async (): Promise<QueueBrokerSnapshot> => {
-      const res = await fetch('/api/queue')
+      const res = await fetch(labelFilter ? `/api/queue?label=${encodeURIComponent(labelFilter)}` : '/api/queue')
       if (!res.ok) throw new Error('Failed to fetch queue')
       return res.json()
     },
   })
 
   const data = snapshot ?? []
-  const [filter, setFilter] = useState("")
   const [selectedQueueName, setSelectedQueueName] = useState<string | null>(null)
   
-  // View Mode: 'traffic' (Active) or 'dlq' (Dead Letter)
-  const [viewMode, setViewMode] = useState<'traffic' | 'dlq'>('traffic')
+  // View Mode: 'traffic' (Active), 'dlq' (Dead Letter) or 'archive' (Acked, archiving queues only)
+  const [viewMode, setViewMode] = useState<ViewMode>('traffic')
 
   const [selectedMessageId, setSelectedMessageId] = useState<string | null>(null)
 
   // Message State Filter (for Traffic view)
-  const [messageState, setMessageState] = useState<'Pending' | 'InFlight'>('Pending')
+  const [messageState, setMessageState] = useState<'Pending' | 'InFlight' | 'Scheduled'>('Pending')
+  // Priority lane filter (for Traffic view), null = all lanes
+  const [priority, setPriority] = useState<number | null>(null)
   
+  // Payload Search (both views), debounced
+  const [searchInput, setSearchInput] = useState("")
+  const [search, setSearch] = useState("")
+
   // Pagination State
   const [offset, setOffset] = useState(0)
+
+  useEffect(() => {
+      const timer = setTimeout(() => setSearch(searchInput), 300)
+      return () => clearTimeout(timer)
+  }, [searchInput])
   
   // Reset pagination when changing context
   useEffect(() => {
       setOffset(0)
       setSelectedMessageId(null)
-  }, [selectedQueueName, viewMode, messageState])
+  }, [selectedQueueName, viewMode, messageState, priority, search])
 
-  const filteredQueues = (data || []).filter(q => q.name.toLowerCase().includes(filter.toLowerCase()))
+  useEffect(() => {
+      setPriority(null)
+      setViewMode(v => v === 'archive' ? 'traffic' : v)
+  }, [selectedQueueName])
+
+  const filteredQueues = (data || []).filter(q => q.name.toLowerCase().includes(nameFilter))
   const selectedQueue = (data || []).find((q: QueueSummary) => q.name === selectedQueueName)
 
   // Fetch paginated messages
   const { data: paginatedData, isLoading } = useQuery({
-    queryKey: ['queue-messages', selectedQueueName, viewMode, messageState, offset],
+    queryKey: ['queue-messages', selectedQueueName, viewMode, messageState, priority, search, offset],
     queryFn: async () => {
         if (!selectedQueueName) return null
-        
-        const stateParam = viewMode === 'dlq' ? 'dlq' : messageState.toLowerCase()
-        const res = await fetch(`/api/queue/${selectedQueueName}/messages?state=${stateParam}&offset=${offset}&limit=${PAGE_SIZE}`)
-        
-        if (!res.ok) throw new Error('Failed to fetch messages')
+
+        if (viewMode === 'archive') {
+            const params = new URLSearchParams({ offset: String(offset), limit: String(PAGE_SIZE) })
+            const res = await fetch(`/api/queue/${encodeURIComponent(selectedQueueName)}/archive?${params}`)
+            if (!res.ok) throw new Error('Failed to fetch archive')
+            return await res.json() as PaginatedArchivedMessages
+        }
         
         if (viewMode === 'dlq') {
+            const params = new URLSearchParams({ offset: String(offset), limit: String(PAGE_SIZE) })
+            if (search) params.set("search", search)
+            const res = await fetch(`/api/queue/${encodeURIComponent(selectedQueueName)}/dlq?${params}`)
+            if (!res.ok) throw new Error('Failed to fetch DLQ')
             return await res.json() as PaginatedDlqMessages
-        } else {
-            return await res.json() as PaginatedMessages
         }
+
+        const params = new URLSearchParams({ state: messageState.toLowerCase(), offset: String(offset), limit: String(PAGE_SIZE) })
+        if (priority !== null) params.set("priority", String(priority))
+        if (search) params.set("search", search)
+        const res = await fetch(`/api/queue/${selectedQueueName}/messages?${params}`)
+        
+        if (!res.ok) throw new Error('Failed to fetch messages')
+        return await res.json() as PaginatedMessages
     },
     enabled: !!selectedQueueName,
   })
 
+  const purgePriority = async (queueName: string, level: number) => {
+      await fetch(`/api/queue/${encodeURIComponent(queueName)}/priorities/${level}/purge`, { method: 'POST' })
+      queryClient.invalidateQueries({ queryKey: ['queue-snapshot'] })
+      queryClient.invalidateQueries({ queryKey: ['queue-messages', queueName] })
+  }
+
+  const replayArchived = async (queueName: string, id: string) => {
+      await fetch(`/api/queue/${encodeURIComponent(queueName)}/archive/${id}/replay`, { method: 'POST' })
+      queryClient.invalidateQueries({ queryKey: ['queue-snapshot'] })
+      queryClient.invalidateQueries({ queryKey: ['queue-messages', queueName] })
+  }
+
+  const refreshQueue = (queueName: string) => {
+      queryClient.invalidateQueries({ queryKey: ['queue-snapshot'] })
+      queryClient.invalidateQueries({ queryKey: ['queue-messages', queueName] })
+  }
+
+  const replayDlqMessage = async (queueName: string, id: string) => {
+      await fetch(`/api/queue/${encodeURIComponent(queueName)}/dlq/${id}/replay`, { method: 'POST' })
+      setSelectedMessageId(null)
+      refreshQueue(queueName)
+  }
+
+  const deleteDlqMessage = async (queueName: string, id: string) => {
+      await fetch(`/api/queue/${encodeURIComponent(queueName)}/dlq/${id}`, { method: 'DELETE' })
+      setSelectedMessageId(null)
+      refreshQueue(queueName)
+  }
+
+  // Bulk DLQ action over everything matching the current search; the server
+  // streams one JSON progress line per chunk.
+  const [bulkProgress, setBulkProgress] = useState<{ action: 'replay' | 'delete', done: number, total: number } | null>(null)
+
+  const runDlqBulk = async (queueName: string, action: 'replay' | 'delete') => {
+      const scope = search ? `matching "${search}"` : 'in the DLQ'
+      if (!window.confirm(`${action === 'replay' ? 'Replay' : 'Delete'} all messages ${scope}?`)) return
+      setBulkProgress({ action, done: 0, total: 0 })
+      try {
+          const res = await fetch(`/api/queue/${encodeURIComponent(queueName)}/dlq/${action}`, {
+              method: 'POST',
+              headers: { 'Content-Type': 'application/json' },
+              body: JSON.stringify({ filter: search ? { search } : {} }),
+          })
+          if (!res.ok || !res.body) return
+          const reader = res.body.pipeThrough(new TextDecoderStream()).getReader()
+          let buffer = ''
+          for (;;) {
+              const { value, done } = await reader.read()
+              if (done) break
+              buffer += value
+              const lines = buffer.split('\n')
+              buffer = lines.pop() ?? ''
+              for (const line of lines) {
+                  if (!line) continue
+                  const update = JSON.parse(line)
+                  if (update.error) throw new Error(update.error)
+                  setBulkProgress({ action, done: update.done, total: update.total })
+              }
+          }
+      } finally {
+          setBulkProgress(null)
+          setSelectedMessageId(null)
+          refreshQueue(queueName)
+      }
+  }
+
   const selectedMessage = paginatedData && selectedMessageId
       ? paginatedData.messages.find(m => m.id === selectedMessageId)
       : undefined
@@ -113,7 +217,7 @@ export function QueueView() {
                   <div className="relative">
                       <Search className="absolute left-2.5 top-2.5 h-3.5 w-3.5 text-muted-foreground" />
                       <Input 
-                          placeholder="FILTER..."
+                          placeholder="FILTER... (team=payments)"
                           value={filter}
                           onChange={(e) => setFilter(e.target.value)}
                           className="h-9 pl-8 bg-background border-border text-xs font-mono placeholder:text-muted-foreground focus-visible:ring-1 focus-visible:ring-ring"
@@ -143,6 +247,9 @@ export function QueueView() {
                                           <span className={`font-mono text-xs truncate ${selectedQueueName === q.name ? 'text-foreground' : 'text-muted-foreground'}`}>
                                               {q.name}
                                           </span>
+                                          {Object.entries(q.config.labels ?? {}).map(([key, value]) => (
+                                              <span key={key} className="text-[10px] font-mono text-muted-foreground border border-border px-1 shrink-0">{key}={value}</span>
+                                          ))}
                                       </div>
                                   </div>
                               )
@@ -162,9 +269,9 @@ export function QueueView() {
                      {/* Header Stats & Tabs */}
                      <div className="p-3 border-b-2 border-border bg-section-header">
                          <div className="flex justify-between items-center mb-3">
-                             <h2 className="text-xs text-foreground">{selectedQueue.name}</h2>
+                             <h2 className="text-xs text-foreground">{selectedQueue.name} <span className="text-muted-foreground">({formatBytes(selectedQueue.bytes)}{selectedQueue.spilled > 0 && `, ${selectedQueue.spilled} paged out to disk`})</span></h2>
                              
-                             <Tabs value={viewMode} onValueChange={(v) => setViewMode(v as 'traffic' | 'dlq')} className="h-7">
+                             <Tabs value={viewMode} onValueChange={(v) => setViewMode(v as ViewMode)} className="h-7">
                                 <TabsList className="h-7 bg-muted/50 p-0.5">
                                     <TabsTrigger value="traffic" className="h-6 text-[10px] px-3 data-[state=active]:bg-background">
                                         TRAFFIC
@@ -172,6 +279,11 @@ export function QueueView() {
                                     <TabsTrigger value="dlq" className="h-6 text-[10px] px-3 data-[state=active]:bg-destructive data-[state=active]:text-destructive-foreground">
                                         DLQ {selectedQueue.dlq > 0 && `(${selectedQueue.dlq})`}
                                     </TabsTrigger>
+                                    {selectedQueue.archive && (
+                                        <TabsTrigger value="archive" className="h-6 text-[10px] px-3 data-[state=active]:bg-background">
+                                            ARCHIVE {selectedQueue.archive.records > 0 && `(${selectedQueue.archive.records})`}
+                                        </TabsTrigger>
+                                    )}
                                 </TabsList>
                              </Tabs>
                          </div>
@@ -180,8 +292,62 @@ export function QueueView() {
                              <div className="flex gap-1">
                                  <FilterButton label="Pending" count={selectedQueue.pending} active={messageState === 'Pending'} onClick={() => setMessageState('Pending')} />
                                  <FilterButton label="InFlight" count={selectedQueue.inflight} active={messageState === 'InFlight'} onClick={() => setMessageState('InFlight')} />
+                                 <FilterButton label="Scheduled" count={selectedQueue.scheduled} active={messageState === 'Scheduled'} onClick={() => setMessageState('Scheduled')} />
+                             </div>
+                         )}
+                         {viewMode === 'archive' && selectedQueue.archive && (
+                             <div className="text-xs text-muted-foreground">
+                                 ACKED MESSAGES, NEWEST FIRST ({formatBytes(selectedQueue.archive.bytes)})
                              </div>
                          )}
+                         {viewMode !== 'archive' && (
+                         <div className="relative mt-3">
+                             <Search className="absolute left-2.5 top-2 h-3.5 w-3.5 text-muted-foreground" />
+                             <Input
+                                 placeholder={viewMode === 'dlq' ? "SEARCH PAYLOAD OR REASON..." : "SEARCH PAYLOAD..."}
+                                 value={searchInput}
+                                 onChange={(e) => setSearchInput(e.target.value)}
+                                 className="h-8 pl-8 bg-background border-border text-xs font-mono placeholder:text-muted-foreground focus-visible:ring-1 focus-visible:ring-ring"
+                             />
+                         </div>
+                         )}
+                         {viewMode === 'dlq' && selectedQueue.dlq > 0 && (
+                             <div className="flex items-center gap-2 mt-2 text-xs font-mono">
+                                 {bulkProgress ? (
+                                     <span className="text-muted-foreground">
+                                         {bulkProgress.action === 'replay' ? 'REPLAYING' : 'DELETING'} {bulkProgress.done}/{bulkProgress.total}
+                                     </span>
+                                 ) : (
+                                     <>
+                                         <button
+                                             onClick={() => runDlqBulk(selectedQueue.name, 'replay')}
+                                             className="h-7 px-2 flex items-center gap-1 uppercase bg-background border border-border rounded hover:bg-muted transition-colors"
+                                         >
+                                             <RotateCcw className="h-3 w-3" />
+                                             {search ? 'Replay matching' : 'Replay all'}
+                                         </button>
+                                         <button
+                                             onClick={() => runDlqBulk(selectedQueue.name, 'delete')}
+                                             className="h-7 px-2 flex items-center gap-1 uppercase bg-background border border-destructive/40 text-destructive rounded hover:bg-destructive/10 transition-colors"
+                                         >
+                                             <Trash2 className="h-3 w-3" />
+                                             {search ? 'Delete matching' : 'Delete all'}
+                                         </button>
+                                     </>
+                                 )}
+                             </div>
+                         )}
+                         {viewMode === 'traffic' && selectedQueue.consumers.length > 0 && (
+                             <ConsumersList consumers={selectedQueue.consumers} />
+                         )}
+                         {viewMode === 'traffic' && selectedQueue.priorities.length > 0 && (
+                             <PriorityLanesList
+                                 lanes={selectedQueue.priorities}
+                                 selected={priority}
+                                 onSelect={setPriority}
+                                 onPurge={(level) => purgePriority(selectedQueue.name, level)}
+                             />
+                         )}
                      </div>
 
                      {/* Messages Table - Simple Map */}
@@ -195,7 +361,7 @@ export function QueueView() {
                             <div className="flex-1 flex flex-col items-center justify-center text-muted-foreground">
                                 <Box className="h-12 w-12 opacity-20 mb-4" />
                                 <p className="text-xs font-mono uppercase tracking-widest opacity-50">
-                                    {viewMode === 'traffic' ? 'NO_MESSAGES' : 'NO_ERRORS_FOUND'}
+                                    {viewMode === 'traffic' ? 'NO_MESSAGES' : viewMode === 'dlq' ? 'NO_ERRORS_FOUND' : 'NO_ARCHIVED_MESSAGES'}
                                 </p>
                             </div>
                         ) : (
@@ -211,7 +377,7 @@ export function QueueView() {
                                     <div className="sticky top-0 bg-section-header border-b border-border px-3 py-2 grid grid-cols-[1fr_80px_120px] gap-2 text-xs font-bold uppercase text-muted-foreground z-10 shadow-sm">
                                         <div>Message ID</div>
                                         <div className="text-center">Attempts</div>
-                                        <div className="text-right">Failed At</div>
+                                        <div className="text-right">{viewMode === 'dlq' ? 'Failed At' : 'Acked At'}</div>
                                     </div>
                                 )}
 
@@ -219,6 +385,30 @@ export function QueueView() {
                                 <div className="flex-1">
                                     {messages.map((msg: any) => {
                                         const isSelected = selectedMessageId === msg.id
+
+                                        if (viewMode === 'archive') {
+                                            const archivedMsg = msg as ArchivedMessageSummary
+                                            return (
+                                                <div
+                                                    key={archivedMsg.seq}
+                                                    onClick={() => setSelectedMessageId(msg.id)}
+                                                    className={`
+                                                        grid grid-cols-[1fr_80px_120px] gap-2 px-3 py-2 border-b border-border/50 cursor-pointer transition-all items-center h-9
+                                                        ${isSelected ? 'bg-secondary border-l-2 border-l-primary' : 'hover:bg-muted/50'}
+                                                    `}
+                                                >
+                                                    <span className={`font-mono text-xs truncate ${isSelected ? 'text-foreground' : 'text-muted-foreground'}`} title={archivedMsg.id}>
+                                                        {archivedMsg.id}
+                                                    </span>
+                                                    <span className="font-mono text-xs text-center text-muted-foreground">
+                                                        {archivedMsg.attempts}
+                                                    </span>
+                                                    <span className="font-mono text-xs text-right text-muted-foreground">
+                                                        {new Date(archivedMsg.acked_at).toLocaleTimeString()}
+                                                    </span>
+                                                </div>
+                                            )
+                                        }
                                         
                                         if (viewMode === 'dlq') {
                                             const dlqMsg = msg as DlqMessageSummary
@@ -238,7 +428,7 @@ export function QueueView() {
                                                         {dlqMsg.attempts}
                                                     </span>
                                                     <span className="font-mono text-xs text-right text-muted-foreground">
-                                                        {new Date(dlqMsg.created_at).toLocaleTimeString()}
+                                                        {new Date(dlqMsg.failed_at).toLocaleTimeString()}
                                                     </span>
                                                 </div>
                                             )
@@ -310,16 +500,61 @@ export function QueueView() {
                       {/* DLQ Context Header */}
                       {viewMode === 'dlq' && 'failure_reason' in selectedMessage && (
                           <div className="p-4 bg-destructive/10 border-b border-destructive/20">
-                              <div className="flex items-center gap-2 mb-2 text-destructive font-bold text-xs uppercase">
-                                  <AlertCircle className="h-4 w-4" />
-                                  Failure Reason
+                              <div className="flex items-center justify-between mb-2">
+                                  <div className="flex items-center gap-2 text-destructive font-bold text-xs uppercase">
+                                      <AlertCircle className="h-4 w-4" />
+                                      Failure Reason
+                                  </div>
+                                  <div className="flex items-center gap-2">
+                                      <button
+                                          onClick={() => replayDlqMessage(selectedQueue.name, selectedMessage.id)}
+                                          className="h-7 px-2 flex items-center gap-1 text-xs font-mono uppercase bg-background border border-border rounded hover:bg-muted transition-colors"
+                                      >
+                                          <RotateCcw className="h-3 w-3" />
+                                          Replay
+                                      </button>
+                                      <button
+                                          onClick={() => deleteDlqMessage(selectedQueue.name, selectedMessage.id)}
+                                          className="h-7 px-2 flex items-center gap-1 text-xs font-mono uppercase bg-background border border-destructive/40 text-destructive rounded hover:bg-destructive/10 transition-colors"
+                                      >
+                                          <Trash2 className="h-3 w-3" />
+                                          Delete
+                                      </button>
+                                  </div>
                               </div>
                               <p className="font-mono text-sm text-foreground break-words mb-3">
                                   {(selectedMessage as DlqMessageSummary).failure_reason}
                               </p>
                               <div className="flex gap-4 text-xs text-muted-foreground font-mono">
-                                  <span>Failed: {new Date((selectedMessage as DlqMessageSummary).created_at).toLocaleString()}</span>
+                                  <span>Failed: {new Date((selectedMessage as DlqMessageSummary).failed_at).toLocaleString()}</span>
+                                  <span>Attempts: {selectedMessage.attempts}</span>
+                              </div>
+                          </div>
+                      )}
+
+                      {/* Archive Context Header */}
+                      {viewMode === 'archive' && 'acked_at' in selectedMessage && (
+                          <div className="p-4 bg-muted/30 border-b border-border">
+                              <div className="flex items-center justify-between mb-2">
+                                  <div className="flex items-center gap-2 text-foreground font-bold text-xs uppercase">
+                                      <Archive className="h-4 w-4" />
+                                      Acked Message
+                                  </div>
+                                  <button
+                                      onClick={() => replayArchived(selectedQueue.name, selectedMessage.id)}
+                                      className="h-7 px-2 flex items-center gap-1 text-xs font-mono uppercase bg-background border border-border rounded hover:bg-muted transition-colors"
+                                  >
+                                      <RotateCcw className="h-3 w-3" />
+                                      Replay
+                                  </button>
+                              </div>
+                              <div className="flex flex-wrap gap-4 text-xs text-muted-foreground font-mono">
+                                  <span>Acked: {new Date((selectedMessage as ArchivedMessageSummary).acked_at).toLocaleString()}</span>
                                   <span>Attempts: {selectedMessage.attempts}</span>
+                                  <span>Priority: {(selectedMessage as ArchivedMessageSummary).priority}</span>
+                                  {(selectedMessage as ArchivedMessageSummary).message_group && (
+                                      <span>Group: {(selectedMessage as ArchivedMessageSummary).message_group}</span>
+                                  )}
                               </div>
                           </div>
                       )}
@@ -328,6 +563,7 @@ export function QueueView() {
                       <div className="flex-1 flex flex-col min-h-0">
                           <div className="px-5 py-3 border-b border-border bg-section-header flex items-center justify-between shrink-0">
                               <span className="text-xs text-muted-foreground uppercase tracking-wider font-bold">PAYLOAD</span>
+                              <span className="text-xs font-mono text-muted-foreground">{getDashboardValueKind(selectedMessage.content_type)}</span>
                           </div>
                           <ScrollArea className="flex-1">
                               <div className="p-5">
@@ -357,6 +593,8 @@ export function QueueView() {
   )
 }
 
+type ViewMode = 'traffic' | 'dlq' | 'archive'
+
 export interface FilterButtonProps {
     label: string
     count: number
@@ -4,7 +4,58 @@ export interface QueueSummary {
     name: string;
     pending: number;
     inflight: number;
+    scheduled: number;
     dlq: number;
+    bytes: number;
+    spilled: number; // pending messages paged out to disk (not in the message list)
+    traced: boolean;
+    config: QueueConfigSummary;
+    consumers: ConsumerSummary[];
+    persistence: PersistenceSummary;
+    priorities: PriorityLaneSummary[]; // highest priority first
+    archive?: ArchiveSummary; // only for queues created with `archive`
+}
+
+export interface QueueConfigSummary {
+    labels?: Record<string, string>; // absent when the queue has none
+}
+
+export interface ArchiveSummary {
+    records: number;
+    bytes: number;
+}
+
+export interface PriorityLaneSummary {
+    priority: number; // u8
+    pending: number;
+    inflight: number;
+    scheduled: number;
+    pushed: number;
+    acked: number;
+    push_rate: number; // pushes/sec over the last minute
+    ack_rate: number; // acks/sec over the last minute
+}
+
+export interface PersistenceSummary {
+    wal_bytes: number;
+    checkpoint_lag_frames: number;
+    last_checkpoint_at: number; // 0 = none yet
+    checkpoint_failures: number;
+}
+
+export interface ConsumerSummary {
+    id: string; // connection id
+    prefetch: number; // batch size, or push window
+    push: boolean; // server-push subscription
+    in_flight: number;
+    waiting: number;
+    delivered: number;
+    acked: number;
+    nacked: number;
+    expired: number;
+    ack_rate: number; // acks/sec over the last minute
+    first_seen: number;
+    last_active: number;
 }
 
 export interface PaginatedMessages {
@@ -17,9 +68,15 @@ export interface PaginatedDlqMessages {
     total: number;
 }
 
+export interface PaginatedArchivedMessages {
+    messages: ArchivedMessageSummary[];
+    total: number;
+}
+
 export interface MessageSummary {
     id: string; // UUID
     payload: any;
+    content_type: string;
     state: string; // "Pending", "InFlight"
     priority: number; // u8
     attempts: number; // u32
@@ -28,7 +85,21 @@ export interface MessageSummary {
 export interface DlqMessageSummary {
     id: string; // UUID
     payload: any;
+    content_type: string;
     attempts: number; // u32
     failure_reason: string;
     created_at: number;
+    failed_at: number;
+}
+
+export interface ArchivedMessageSummary {
+    id: string; // UUID
+    seq: number; // position in the archive
+    payload: any;
+    content_type: string;
+    priority: number; // u8
+    attempts: number; // u32
+    message_group?: string;
+    created_at: number;
+    acked_at: number;
 }
@@ -0,0 +1,32 @@
+import { Lock } from "lucide-react"
+import { ScrollArea } from "@/components/ui/scroll-area"
+import { LockDetail } from "./map-types.ts"
+
+export function LocksPanel({ locks }: { locks: LockDetail[] }) {
+    if (locks.length === 0) {
+        return (
+            <div className="flex-1 flex flex-col items-center justify-center text-muted-foreground/50">
+                <Lock className="h-12 w-12 opacity-20 mb-4" />
+                <p className="text-xs font-mono uppercase tracking-widest opacity-50">NO_LOCKS_HELD</p>
+            </div>
+        )
+    }
+
+    return (
+        <ScrollArea className="flex-1">
+            <div className="divide-y divide-border">
+                {locks.map((lock) => (
+                    <div key={lock.name} className="grid grid-cols-[1fr_auto] gap-4 px-4 py-3">
+                        <div className="space-y-1 min-w-0">
+                            <div className="text-foreground truncate">{lock.name}</div>
+                            <div className="text-xs text-muted-foreground truncate">
+                                Owner: {lock.owner || '-'} · Since {new Date(lock.acquired_at).toLocaleString()} · Expires {new Date(lock.expires_at).toLocaleString()}
+                            </div>
+                        </div>
+                        <span className="text-xs text-muted-foreground self-center">TOKEN {lock.token}</span>
+                    </div>
+                ))}
+            </div>
+        </ScrollArea>
+    )
+}
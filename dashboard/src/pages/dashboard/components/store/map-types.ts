@@ -3,10 +3,28 @@ export interface StoreBrokerSnapshot {
   total: number;
   offset: number;
   limit: number;
+  memory: StoreMemory;
+}
+
+export interface StoreMemory {
+  used_bytes: number; // estimated
+  max_bytes: number | null; // null = no cap
+  policy: string;
+  evicted_keys: number;
+  rejected_writes: number;
 }
 
 export interface KeyDetail {
   key: string;
   value: any;
-  exp_at: string; // ISO8601
+  content_type: string;
+  exp_at: string | null; // ISO8601, null = never expires
+}
+
+export interface LockDetail {
+  name: string;
+  owner: string;
+  token: number;
+  acquired_at: string; // ISO8601
+  expires_at: string; // ISO8601
 }
@@ -1,7 +1,9 @@
 import { useState, useEffect } from "react"
 import { useQuery } from '@tanstack/react-query'
-import { StoreBrokerSnapshot, KeyDetail } from "./map-types.ts"
+import { StoreBrokerSnapshot, KeyDetail, LockDetail } from "./map-types.ts"
+import { LocksPanel } from "./locks.tsx"
 import { formatDashboardValue, getDashboardValueKind, getDashboardValueSize } from "@/lib/dashboard-value"
+import { formatBytes } from "@/lib/utils"
 import { Input } from "@/components/ui/input"
 import { Button } from "@/components/ui/button"
 import { 
@@ -10,6 +12,7 @@ import {
     Clock, 
     List,
     Layers,
+    Lock,
     FileJson,
     Binary,
     Loader2,
@@ -19,7 +22,7 @@ import {
 
 import { QueryError } from "@/components/ui/query-error"
 
-type StructureType = 'hashmap' | 'list' | 'set' 
+type StructureType = 'hashmap' | 'list' | 'set' | 'locks'
 
 export function StoreView() {
   const [activeStructure, setActiveStructure] = useState<StructureType>('hashmap')
@@ -54,6 +57,15 @@ export function StoreView() {
     }
   })
 
+  const { data: locks } = useQuery({
+    queryKey: ['store-locks'],
+    queryFn: async () => {
+      const response = await fetch('/api/store/locks')
+      if (!response.ok) throw new Error('Failed to fetch locks')
+      return response.json() as Promise<LockDetail[]>
+    }
+  })
+
   // Reset selection if list changes and current selection is gone
   useEffect(() => {
       if (selectedKey && data?.keys && !data.keys.find(k => k.key === selectedKey.key)) {
@@ -107,10 +119,34 @@ export function StoreView() {
                       onClick={() => setActiveStructure('set')}
                       disabled
                   />
+                  <NavButton 
+                      label="LOCKS" 
+                      icon={<Lock className="h-3.5 w-3.5" />} 
+                      count={locks?.length || 0}
+                      active={activeStructure === 'locks'}
+                      onClick={() => setActiveStructure('locks')}
+                  />
               </div>
+              {data?.memory && (
+                  <div className="mt-auto p-3 border-t-2 border-border text-[10px] text-muted-foreground uppercase space-y-1">
+                      <div className="font-bold tracking-widest">MEMORY</div>
+                      <div className="text-foreground">
+                          {formatBytes(data.memory.used_bytes)}
+                          {data.memory.max_bytes !== null && <> / {formatBytes(data.memory.max_bytes)}</>}
+                      </div>
+                      <div>{data.memory.policy}</div>
+                      <div>evicted: {data.memory.evicted_keys}</div>
+                      <div>rejected: {data.memory.rejected_writes}</div>
+                  </div>
+              )}
           </div>
 
           {/* MAIN AREA: Browser */}
+          {activeStructure === 'locks' ? (
+          <div className="flex-1 min-w-0 flex flex-col h-full bg-content">
+              <LocksPanel locks={locks ?? []} />
+          </div>
+          ) : (
           <div className="flex-1 min-w-0 relative flex h-full">
              {isLoading && !data ? (
                  <div className="absolute inset-0 flex items-center justify-center bg-background/50 z-10 backdrop-blur-[1px]">
@@ -164,7 +200,8 @@ export function StoreView() {
                                        : "hover:bg-muted/50 text-muted-foreground hover:text-foreground"
                                    }`}
                                >
-                                   {k.key}
+                                   <span className="truncate flex-1">{k.key}</span>
+                                   {!k.exp_at && <span className="ml-2 text-[10px] uppercase text-muted-foreground">no ttl</span>}
                                </button>
                            ))}
                        </div>
@@ -207,7 +244,9 @@ export function StoreView() {
                            <div className="flex items-center gap-4 text-xs text-muted-foreground uppercase tracking-wide">
                                <span className="flex items-center gap-1.5">
                                    <Clock className="h-3 w-3" /> 
-                                   expire at: {new Date(selectedKey.exp_at).toLocaleString()}
+                                   {selectedKey.exp_at
+                                       ? <>expire at: {new Date(selectedKey.exp_at).toLocaleString()}</>
+                                       : <>never expires</>}
                                </span>
                            </div>
                        </div>
@@ -228,7 +267,7 @@ export function StoreView() {
                            </div>
                            <div className="flex items-center gap-2">
                                <FileJson className="h-3 w-3" />
-                               <span>{getDashboardValueKind(selectedKey.value)}</span>
+                               <span>{getDashboardValueKind(selectedKey.content_type)}</span>
                            </div>
                        </div>
                    </>
@@ -240,6 +279,7 @@ export function StoreView() {
                )}
              </div>
           </div>
+          )}
       </div>
   )
 }
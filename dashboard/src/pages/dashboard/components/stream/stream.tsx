@@ -1,6 +1,7 @@
 import { useState, useEffect } from "react"
 import { useQuery } from "@tanstack/react-query"
-import { formatDashboardValue } from "@/lib/dashboard-value"
+import { formatDashboardValue, getDashboardValueKind } from "@/lib/dashboard-value"
+import { formatBytes, splitLabelFilter } from "@/lib/utils"
 import { Input } from "@/components/ui/input"
 import { ScrollArea } from "@/components/ui/scroll-area"
 import {
@@ -15,6 +16,8 @@ import {
 import { QueryError } from "@/components/ui/query-error"
 import {
     ConsumerGroupSummary,
+    MirrorSummary,
+    BackfillSummary,
     StreamBrokerSnapshot,
     StreamMessages,
     TopicSummary
@@ -24,17 +27,19 @@ import {
 const PAGE_SIZE = 50
 
 export function StreamView() {
+    const [filter, setFilter] = useState("")
+    const { name: nameFilter, label: labelFilter } = splitLabelFilter(filter)
     const { data: snapshot, isLoading: snapshotLoading, error: snapshotError, refetch } = useQuery({
-        queryKey: ['stream-snapshot'],
+        queryKey: ['stream-snapshot', labelFilter],
         queryFn: async (): Promise<StreamBrokerSnapshot> => {
-            const res = await fetch('/api/stream')
+            const res = await fetch(labelFilter ? `/api/stream?label=${encodeURIComponent(labelFilter)}` : '/api/stream')
             if (!res.ok) throw new Error('Failed to fetch stream')
             return res.json()
         },
     })
-    const data = snapshot ?? { topics: [] }
+    const data = snapshot ?? { topics: [], mirrors: [], backfills: [] }
+    const readCache = snapshot?.read_cache
 
-    const [filter, setFilter] = useState("")
     const [selectedTopicName, setSelectedTopicName] = useState<string | null>(null)
     const [selectedMessageSeq, setSelectedMessageSeq] = useState<number | null>(null)
     const [fromSeq, setFromSeq] = useState<number | null>(null)
@@ -46,8 +51,10 @@ export function StreamView() {
         setFromSeq(null)
     }, [selectedTopicName])
 
-    const filteredTopics = data.topics.filter((t: TopicSummary) => t.name.toLowerCase().includes(filter.toLowerCase()))
+    const filteredTopics = data.topics.filter((t: TopicSummary) => t.name.toLowerCase().includes(nameFilter))
     const selectedTopic = data.topics.find((t: TopicSummary) => t.name === selectedTopicName)
+    const selectedMirrors = data.mirrors.filter((m: MirrorSummary) => m.local_topic === selectedTopicName)
+    const selectedBackfills = data.backfills.filter((b: BackfillSummary) => b.source === selectedTopicName || b.target === selectedTopicName)
 
     // Fetch messages on-demand when topic selected
     const { data: streamData, isLoading } = useQuery({
@@ -96,12 +103,17 @@ export function StreamView() {
 
                         <Search className="absolute left-2.5 top-2.5 h-3.5 w-3.5 text-muted-foreground" />
                         <Input
-                            placeholder="FILTER TOPICS..."
+                            placeholder="FILTER TOPICS... (env=staging)"
                             value={filter}
                             onChange={(e) => setFilter(e.target.value)}
                             className="h-8 pl-8 bg-background border-border text-xs font-mono placeholder:text-muted-foreground focus-visible:ring-1 focus-visible:ring-ring"
                         />
                     </div>
+                    {readCache && readCache.capacity > 0 && (
+                        <div className="mt-2 text-[10px] uppercase text-muted-foreground">
+                            READ CACHE: {readCache.blocks}/{readCache.capacity} BLOCKS · {readCache.hits} HITS · {readCache.misses} MISSES
+                        </div>
+                    )}
                 </div>
                 <ScrollArea className="flex-1">
                     <div className="p-0">
@@ -118,6 +130,12 @@ export function StreamView() {
                                     <div className={`font-mono text-xs truncate ${selectedTopicName === t.name ? 'text-foreground font-medium' : 'text-muted-foreground'}`}>
                                         {t.name}
                                     </div>
+                                    {Object.entries(t.config.labels ?? {}).map(([key, value]) => (
+                                        <span key={key} className="text-[10px] font-mono text-muted-foreground border border-border px-1 shrink-0">{key}={value}</span>
+                                    ))}
+                                    {t.recovered && (
+                                        <span className="text-[10px] uppercase text-muted-foreground" title="Restored from disk at startup">recovered</span>
+                                    )}
                                 </div>
                             </div>
                         ))}
@@ -134,8 +152,46 @@ export function StreamView() {
                             {/* Header: stats + groups */}
                             <div className="px-5 py-3 border-b-2 border-border bg-section-header">
                                 <div className="text-sm text-muted-foreground font-mono font-medium pb-4">
-                                    Total messages: {selectedTopic.last_seq}
+                                    Total messages: {selectedTopic.last_seq} ({formatBytes(selectedTopic.bytes)})
+                                    <div>
+                                        Retained: seq {selectedTopic.head_seq}–{selectedTopic.last_seq} · Flushed: seq {selectedTopic.persisted_seq} · Publish: {selectedTopic.publish_rate.toFixed(1)}/s ({formatBytes(selectedTopic.byte_rate)}/s)
+                                    </div>
+                                    <div>
+                                        In memory: {selectedTopic.ram_messages} ({formatBytes(selectedTopic.ram_bytes)}) · Reads: {selectedTopic.hot_reads} hot / {selectedTopic.cold_reads} cold
+                                    </div>
                                 </div>
+                                {selectedMirrors.length > 0 && (
+                                    <div className="pb-4 space-y-1">
+                                        <div className="text-sm text-muted-foreground font-mono font-medium">Mirrored from:</div>
+                                        {selectedMirrors.map((mirror: MirrorSummary) => (
+                                            <div key={mirror.source} className="grid grid-cols-3 gap-4 text-sm text-muted-foreground font-mono">
+                                                <div className="truncate ml-6" title={mirror.last_error ?? undefined}>
+                                                    - {mirror.source} {mirror.connected ? '' : '(disconnected)'}
+                                                </div>
+                                                <div className="text-right">Mirrored: {mirror.mirrored} (seq {mirror.last_seq})</div>
+                                                <div className="text-right">Lag: {mirror.lag_ms} ms</div>
+                                            </div>
+                                        ))}
+                                    </div>
+                                )}
+                                {selectedBackfills.length > 0 && (
+                                    <div className="pb-4 space-y-1">
+                                        <div className="text-sm text-muted-foreground font-mono font-medium">Backfills:</div>
+                                        {selectedBackfills.map((job: BackfillSummary) => (
+                                            <div key={job.id} className="grid grid-cols-3 gap-4 text-sm text-muted-foreground font-mono">
+                                                <div className="truncate ml-6" title={job.error ?? undefined}>
+                                                    - #{job.id} {job.source} → {job.target} ({job.state})
+                                                </div>
+                                                <div className="text-right">
+                                                    Seq: {Math.min(job.next_seq, job.to_seq + 1) - 1}/{job.to_seq}
+                                                </div>
+                                                <div className="text-right">
+                                                    Copied: {job.copied} · Skipped: {job.skipped} · Failed: {job.failed}
+                                                </div>
+                                            </div>
+                                        ))}
+                                    </div>
+                                )}
                                 <div className="text-sm text-muted-foreground font-mono font-medium">Consumer groups:</div>
                                 {selectedTopic.groups.length > 0 ? (
                                     <div className="space-y-1">
@@ -238,7 +294,7 @@ export function StreamView() {
                             <div className="px-5 py-3 border-b border-border bg-section-header flex items-center justify-between shrink-0">
                                 <span className="text-xs text-muted-foreground uppercase tracking-wider font-bold">Payload</span>
                                 {selectedMessage && (
-                                    <span className="text-xs font-mono text-muted-foreground">Seq {selectedMessage.seq}</span>
+                                    <span className="text-xs font-mono text-muted-foreground">Seq {selectedMessage.seq} · {getDashboardValueKind(selectedMessage.content_type)}</span>
                                 )}
                             </div>
                             <ScrollArea className="flex-1">
@@ -1,12 +1,70 @@
 
 export interface StreamBrokerSnapshot {
     topics: TopicSummary[];
+    mirrors: MirrorSummary[];
+    backfills: BackfillSummary[];
+    read_cache: ReadCacheSummary;
+}
+
+export interface ReadCacheSummary {
+    capacity: number;
+    blocks: number;
+    hits: number;
+    misses: number;
+}
+
+export interface MirrorSummary {
+    source: string;
+    local_topic: string;
+    connected: boolean;
+    mirrored: number;
+    last_seq: number;
+    lag_ms: number;
+    last_error: string | null;
+}
+
+export interface BackfillSummary {
+    id: number;
+    source: string;
+    target: string;
+    from_seq: number;
+    to_seq: number;
+    next_seq: number;
+    copied: number;
+    skipped: number;
+    failed: number;
+    state: 'running' | 'completed' | 'cancelled' | 'failed';
+    error: string | null;
 }
 
 export interface TopicSummary {
     name: string;
+    /** High watermark */
     last_seq: number;
+    /** Oldest sequence retention kept */
+    head_seq: number;
+    /** Newest sequence flushed to disk */
+    persisted_seq: number;
+    bytes: number;
+    /** Appends and record bytes per second over the last minute */
+    publish_rate: number;
+    byte_rate: number;
+    /** RAM window: newest records, served without touching disk */
+    ram_messages: number;
+    ram_bytes: number;
+    /** Messages served from the RAM window (hot) and from disk (cold) since start */
+    hot_reads: number;
+    cold_reads: number;
     groups: ConsumerGroupSummary[];
+    traced: boolean;
+    /** Restored from disk at startup */
+    recovered: boolean;
+    config: TopicConfigSummary;
+}
+
+export interface TopicConfigSummary {
+    /** Absent when the topic has none */
+    labels?: Record<string, string>;
 }
 
 export interface ConsumerGroupSummary {
@@ -19,6 +77,7 @@ export interface MessagePreview {
     seq: number;
     timestamp: string;
     payload: unknown;
+    content_type: string;
 }
 
 export interface StreamMessages {
@@ -0,0 +1,139 @@
+import { useState } from "react"
+import { useQuery, useQueryClient } from "@tanstack/react-query"
+import { Button } from "@/components/ui/button"
+import { Input } from "@/components/ui/input"
+import { ScrollArea } from "@/components/ui/scroll-area"
+import { QueryError } from "@/components/ui/query-error"
+import { Search } from "lucide-react"
+import { QueueBrokerSnapshot } from "@/pages/dashboard/components/queue/types.ts"
+import { StreamBrokerSnapshot } from "@/pages/dashboard/components/stream/types.ts"
+import { MessageTrace } from "@/pages/dashboard/components/trace/types.ts"
+
+type Broker = 'queue' | 'stream'
+
+export function TraceView() {
+    const queryClient = useQueryClient()
+    const [input, setInput] = useState("")
+    const [messageId, setMessageId] = useState<string | null>(null)
+
+    const { data: queues, error: queuesError, refetch: refetchQueues } = useQuery({
+        queryKey: ['queue-snapshot'],
+        queryFn: async (): Promise<QueueBrokerSnapshot> => {
+            const res = await fetch('/api/queue')
+            if (!res.ok) throw new Error('Failed to fetch queue')
+            return res.json()
+        },
+    })
+
+    const { data: stream, error: streamError, refetch: refetchStream } = useQuery({
+        queryKey: ['stream-snapshot'],
+        queryFn: async (): Promise<StreamBrokerSnapshot> => {
+            const res = await fetch('/api/stream')
+            if (!res.ok) throw new Error('Failed to fetch stream')
+            return res.json()
+        },
+    })
+
+    const { data: trace, isFetching } = useQuery({
+        queryKey: ['message-trace', messageId],
+        queryFn: async (): Promise<MessageTrace | null> => {
+            const res = await fetch(`/api/trace/${encodeURIComponent(messageId!)}`)
+            if (res.status === 404) return null
+            if (!res.ok) throw new Error('Failed to fetch trace')
+            return res.json()
+        },
+        enabled: !!messageId,
+    })
+
+    const toggle = async (broker: Broker, name: string, enabled: boolean) => {
+        await fetch(`/api/trace/${broker}/${encodeURIComponent(name)}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ enabled }),
+        })
+        queryClient.invalidateQueries({ queryKey: [broker === 'queue' ? 'queue-snapshot' : 'stream-snapshot'] })
+    }
+
+    if (queuesError) {
+        return <QueryError error={queuesError} onRetry={refetchQueues} title="ERROR_LOADING_QUEUE" />
+    }
+    if (streamError) {
+        return <QueryError error={streamError} onRetry={refetchStream} title="ERROR_LOADING_STREAM" />
+    }
+
+    const subjects = [
+        ...(queues ?? []).map(q => ({ broker: 'queue' as Broker, name: q.name, traced: q.traced })),
+        ...(stream?.topics ?? []).map(t => ({ broker: 'stream' as Broker, name: t.name, traced: t.traced })),
+    ]
+
+    return (
+        <div className="flex h-full gap-0 border-2 border-border rounded-sm bg-panel overflow-hidden font-mono text-sm">
+            {/* SIDEBAR: tracing toggles */}
+            <div className="w-[320px] flex flex-col border-r-2 border-border bg-sidebar">
+                <div className="p-3 border-b-2 border-border text-xs font-bold uppercase text-muted-foreground">
+                    Tracing ({subjects.filter(s => s.traced).length} enabled)
+                </div>
+                <ScrollArea className="flex-1">
+                    {subjects.length === 0 ? (
+                        <div className="py-8 text-center text-xs text-muted-foreground italic">NO_QUEUES_OR_TOPICS</div>
+                    ) : (
+                        subjects.map(s => (
+                            <div key={`${s.broker}/${s.name}`} className="flex items-center justify-between px-4 py-2 border-b border-border/50">
+                                <div className="min-w-0">
+                                    <div className="truncate text-foreground">{s.name}</div>
+                                    <div className="text-[10px] uppercase text-muted-foreground">{s.broker}</div>
+                                </div>
+                                <Button variant={s.traced ? 'default' : 'outline'} size="sm" onClick={() => toggle(s.broker, s.name, !s.traced)}>
+                                    {s.traced ? 'ON' : 'OFF'}
+                                </Button>
+                            </div>
+                        ))
+                    )}
+                </ScrollArea>
+            </div>
+
+            {/* MAIN: lookup by message id */}
+            <div className="flex-1 flex flex-col min-w-0">
+                <form
+                    className="p-3 border-b-2 border-border flex gap-2"
+                    onSubmit={(e) => { e.preventDefault(); setMessageId(input.trim() || null) }}
+                >
+                    <div className="relative flex-1">
+                        <Search className="absolute left-2.5 top-2.5 h-3.5 w-3.5 text-muted-foreground" />
+                        <Input
+                            placeholder="QUEUE MESSAGE UUID OR TOPIC:SEQ"
+                            value={input}
+                            onChange={(e) => setInput(e.target.value)}
+                            className="h-9 pl-8 bg-background border-border text-xs font-mono"
+                        />
+                    </div>
+                    <Button type="submit" size="sm">TRACE</Button>
+                </form>
+                <ScrollArea className="flex-1">
+                    {!messageId ? (
+                        <div className="p-6 text-xs text-muted-foreground">Enable tracing on a queue or topic, then look up a message id.</div>
+                    ) : isFetching ? (
+                        <div className="p-6 text-xs text-muted-foreground">LOADING...</div>
+                    ) : !trace ? (
+                        <div className="p-6 text-xs text-muted-foreground">No trace recorded for {messageId}</div>
+                    ) : (
+                        <div className="p-4 space-y-3">
+                            <div className="text-xs text-muted-foreground uppercase">
+                                {trace.broker} · {trace.subject} · {trace.id}
+                            </div>
+                            <div className="border-l-2 border-border pl-4 space-y-2">
+                                {trace.events.map((event, i) => (
+                                    <div key={i} className="grid grid-cols-[180px_140px_1fr] gap-4 text-xs">
+                                        <span className="text-muted-foreground">{new Date(event.at).toLocaleString()}</span>
+                                        <span className="font-bold uppercase text-foreground">{event.kind.replace('_', ' ')}</span>
+                                        <span className="text-muted-foreground truncate">{event.detail ?? ''}</span>
+                                    </div>
+                                ))}
+                            </div>
+                        </div>
+                    )}
+                </ScrollArea>
+            </div>
+        </div>
+    )
+}
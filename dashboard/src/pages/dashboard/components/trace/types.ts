@@ -0,0 +1,23 @@
+export type TraceEventKind =
+    | 'pushed'
+    | 'published'
+    | 'dispatched'
+    | 'acked'
+    | 'nacked'
+    | 'expired'
+    | 'dead_lettered'
+    | 'replayed'
+    | 'discarded';
+
+export interface TraceEvent {
+    at: number;
+    kind: TraceEventKind;
+    detail?: string;
+}
+
+export interface MessageTrace {
+    id: string;
+    broker: 'queue' | 'stream';
+    subject: string;
+    events: TraceEvent[];
+}
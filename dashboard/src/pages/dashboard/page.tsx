@@ -1,39 +1,83 @@
 import { useState } from 'react'
-import { useQueryClient, useIsFetching } from '@tanstack/react-query'
+import { useQuery, useQueryClient, useIsFetching } from '@tanstack/react-query'
 import { StoreView } from './components/store/store'
 import { QueueView } from './components/queue/queue'
 import { StreamView } from './components/stream/stream'
 import { PubSubView } from './components/pubsub/pubsub'
+import { ConnectionsView } from './components/connections/connections'
+import { TraceView } from './components/trace/trace'
+import { ActivityView } from './components/activity/activity'
 import { NavCard } from '@/components/layout/nav-card'
-import { Database, MessageSquare, Radio, Activity } from 'lucide-react'
+import { Database, MessageSquare, Radio, Activity, Plug, Route, History } from 'lucide-react'
+
+interface SystemSummary {
+  version: string
+  uptime_secs: number
+  ephemeral: boolean
+  ephemeral_dir: string | null
+  memory: {
+    limit_bytes: number
+    used_bytes: number
+    pressure: 'normal' | 'high' | 'critical'
+    rejections: number
+  } | null
+}
 
 const SNAPSHOT_KEYS = {
   store: ['store-snapshot'],
   queue: ['queue-snapshot'],
   stream: ['stream-snapshot'],
   pubsub: ['pubsub-snapshot'],
+  connections: ['connections-snapshot'],
+  trace: ['message-trace'],
+  activity: ['activity-feed'],
 } as const
 
 export function DashboardPage() {
-  const [activeTab, setActiveTab] = useState<'store' | 'queue' | 'stream' | 'pubsub'>('store')
+  const [activeTab, setActiveTab] = useState<keyof typeof SNAPSHOT_KEYS>('store')
   const queryClient = useQueryClient()
 
   const isStoreFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.store }) > 0
   const isQueueFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.queue }) > 0
   const isStreamFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.stream }) > 0
   const isPubsubFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.pubsub }) > 0
+  const isConnectionsFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.connections }) > 0
+  const isTraceFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.trace }) > 0
+  const isActivityFetching = useIsFetching({ queryKey: SNAPSHOT_KEYS.activity }) > 0
 
   const onRefresh = (tab: keyof typeof SNAPSHOT_KEYS) => () => {
     queryClient.invalidateQueries({ queryKey: SNAPSHOT_KEYS[tab] })
   }
 
+  const { data: system } = useQuery<SystemSummary>({
+    queryKey: ['system'],
+    queryFn: async () => {
+      const res = await fetch('/api/system')
+      if (!res.ok) throw new Error('Failed to fetch system info')
+      return res.json()
+    },
+    refetchInterval: 5000,
+  })
+
   return (
     <div className="flex flex-col h-screen bg-background text-foreground font-sans selection:bg-primary/20 selection:text-primary">
       {/* MAIN CONTAINER */}
       <div className="flex flex-col flex-1 max-w-[1600px] mx-auto w-full px-6 pt-6">
         
+        {system?.ephemeral && (
+          <div className="mb-4 rounded border border-status-pending/30 bg-status-pending/10 px-3 py-2 text-xs font-mono uppercase text-status-pending">
+            EPHEMERAL MODE: NOTHING IS KEPT ACROSS RESTARTS ({system.ephemeral_dir})
+          </div>
+        )}
+
+        {system?.memory && system.memory.pressure !== 'normal' && (
+          <div className="mb-4 rounded border border-destructive/30 bg-destructive/10 px-3 py-2 text-xs font-mono uppercase text-destructive">
+            MEMORY PRESSURE {system.memory.pressure}: {system.memory.used_bytes} OF {system.memory.limit_bytes} BYTES IN USE, {system.memory.rejections} PUBLISHES SHED
+          </div>
+        )}
+
         {/* NAVIGATION GRID - HORIZONTAL COMPACT */}
-        <div className="grid grid-cols-1 md:grid-cols-4 gap-4 mb-6">
+        <div className="grid grid-cols-1 md:grid-cols-7 gap-4 mb-6">
             <NavCard 
                 label="STORE" 
                 desc="Cache IN MEMORY"
@@ -70,6 +114,33 @@ export function DashboardPage() {
                 onRefresh={onRefresh('pubsub')}
                 isRefreshing={isPubsubFetching}
             />
+            <NavCard 
+                label="CONNECTIONS" 
+                desc="Client Sessions"
+                active={activeTab === 'connections'}
+                onClick={() => setActiveTab('connections')}
+                icon={<Plug className="h-5 w-5" />}
+                onRefresh={onRefresh('connections')}
+                isRefreshing={isConnectionsFetching}
+            />
+            <NavCard 
+                label="TRACE" 
+                desc="Message Lifecycle"
+                active={activeTab === 'trace'}
+                onClick={() => setActiveTab('trace')}
+                icon={<Route className="h-5 w-5" />}
+                onRefresh={onRefresh('trace')}
+                isRefreshing={isTraceFetching}
+            />
+            <NavCard 
+                label="ACTIVITY" 
+                desc="Recent Events"
+                active={activeTab === 'activity'}
+                onClick={() => setActiveTab('activity')}
+                icon={<History className="h-5 w-5" />}
+                onRefresh={onRefresh('activity')}
+                isRefreshing={isActivityFetching}
+            />
         </div>
 
         {/* CONTENT AREA */}
@@ -94,6 +165,21 @@ export function DashboardPage() {
                     <PubSubView />
                 </div>
             )}
+            {activeTab === 'connections' && (
+                <div className="h-full">
+                    <ConnectionsView />
+                </div>
+            )}
+            {activeTab === 'trace' && (
+                <div className="h-full">
+                    <TraceView />
+                </div>
+            )}
+            {activeTab === 'activity' && (
+                <div className="h-full">
+                    <ActivityView />
+                </div>
+            )}
         </main>
       </div>
     </div>
@@ -1,10 +1,13 @@
 import { NexoConnection } from '../connection';
+import { ResponseStatus } from '../protocol';
 import { Logger } from '../utils/logger';
 
 enum PubSubOpcode {
   PUB = 0x21,
   SUB = 0x22,
   UNSUB = 0x23,
+  ALIAS = 0x24,
+  PUB_ALIAS = 0x25,
 }
 
 const PubSubCommands = {
@@ -15,8 +18,21 @@ const PubSubCommands = {
       .any(data)
     ),
 
-  subscribe: (conn: NexoConnection, topic: string) =>
-    conn.send(PubSubOpcode.SUB, w => w.string(topic)),
+  registerAlias: (conn: NexoConnection, alias: number, topic: string) =>
+    conn.send(PubSubOpcode.ALIAS, w => w.u16(alias).string(topic)),
+
+  publishAlias: (conn: NexoConnection, alias: number, data: any, options: PublishOptions) =>
+    conn.send(PubSubOpcode.PUB_ALIAS, w => w
+      .u16(alias)
+      .string(JSON.stringify(options || {}))
+      .any(data)
+    ),
+
+  subscribe: (conn: NexoConnection, topic: string, id: number, options: SubscribeOptions) =>
+    conn.send(PubSubOpcode.SUB, w => w
+      .string(topic)
+      .string(JSON.stringify({ ...options, id }))
+    ),
 
   unsubscribe: (conn: NexoConnection, topic: string) =>
     conn.send(PubSubOpcode.UNSUB, w => w.string(topic)),
@@ -24,30 +40,93 @@ const PubSubCommands = {
 
 export interface PublishOptions {
   retain?: boolean;
+  /** Resolve with a publish confirm once every matched subscriber has been handed the message. */
+  confirm?: boolean;
+  /** Register a per-connection alias for this topic and send only the alias on later publishes. */
+  alias?: boolean;
+  /** Drop the message for subscribers that have not received it this long after the publish. */
+  expiryMs?: number;
 }
 
+export interface PublishConfirm {
+  /** Assigned by the server */
+  publishId: number;
+  /** Subscribers the message was handed to */
+  delivered: number;
+}
+
+export interface SubscribeOptions {
+  /** Skip messages published by this client. */
+  noLocal?: boolean;
+  /** Keep the retain flag on live messages published with `retain: true`. */
+  retainAsPublished?: boolean;
+  /** 0 = receive retained messages on subscribe (default), 1 = only if not already subscribed, 2 = never. */
+  retainHandling?: 0 | 1 | 2;
+  /**
+   * Have the server drop the subscription once this client has stopped reading
+   * pushes for this long, instead of buffering for it indefinitely.
+   */
+  idleTtlMs?: number;
+}
+
+export interface MessageInfo {
+  topic: string;
+  /** Replayed retained message, or a retained publish on a `retainAsPublished` subscription */
+  retain: boolean;
+}
+
+const MAX_ALIAS = 0xFFFF;
+
 export class NexoTopic<T = any> {
   constructor(private broker: NexoPubSub, public readonly name: string) { }
-  async publish(data: T, options?: PublishOptions) { return this.broker.publish(this.name, data, options); }
-  async subscribe(cb: (data: T) => void) { return this.broker.subscribe(this.name, cb); }
+  async publish(data: T, options?: PublishOptions): Promise<PublishConfirm | undefined> { return this.broker.publish(this.name, data, options); }
+  async subscribe(cb: (data: T, info: MessageInfo) => void, options?: SubscribeOptions) { return this.broker.subscribe(this.name, cb, options); }
   async unsubscribe() { return this.broker.unsubscribe(this.name); }
 }
 
-type Handler = (data: any) => void;
+type Handler = (data: any, info: MessageInfo) => void;
+
+interface Subscription {
+  /** Sent with the subscription; the server returns it with every message it matches */
+  id: number;
+  cb: Handler;
+  options: SubscribeOptions;
+  /** Pattern levels, for wildcard subscriptions */
+  parts?: string[];
+}
 
 export class NexoPubSub {
-  private exact = new Map<string, Handler>();
-  private wild = new Map<string, { parts: string[], cb: Handler }>();
+  private exact = new Map<string, Subscription>();
+  private wild = new Map<string, Subscription>();
+  private byId = new Map<number, Subscription>();
+  private nextSubscriptionId = 1;
+  private aliases = new Map<string, number>();
+  private unaliasable = new Set<string>();
+  private nextAlias = 1;
 
   constructor(private conn: NexoConnection, private logger: Logger) {
-    conn.onPush = (topic, data) => this.dispatch(topic, data);
+    conn.onPush = (topic, data, info) => this.dispatch(topic, data, info.retain, info.subscriptionIds);
+    conn.onUnsubscribed = (topic, reason) => {
+      const sub = this.exact.get(topic) ?? this.wild.get(topic);
+      if (!sub) return;
+      this.logger.warn(`[PubSub] Server dropped subscription to "${topic}": ${reason}`);
+      this.exact.delete(topic);
+      this.wild.delete(topic);
+      this.byId.delete(sub.id);
+    };
 
     conn.on('reconnect', async () => {
-      const topics = [...this.exact.keys(), ...this.wild.keys()];
+      // Aliases live on the server connection: start over on the new one
+      this.aliases.clear();
+      this.unaliasable.clear();
+      this.nextAlias = 1;
+
+      const subs = [...this.exact.entries(), ...this.wild.entries()];
+      const topics = subs.map(([t]) => t);
       if (topics.length === 0) return;
       this.logger.info(`[PubSub] Restoring ${topics.length} subscription(s)...`);
       const results = await Promise.allSettled(
-        topics.map(t => PubSubCommands.subscribe(this.conn, t))
+        subs.map(([t, sub]) => PubSubCommands.subscribe(this.conn, t, sub.id, sub.options))
       );
       results.forEach((r, i) => {
         if (r.status === 'rejected') {
@@ -57,55 +136,96 @@ export class NexoPubSub {
     });
   }
 
-  async publish(topic: string, data: any, options?: PublishOptions): Promise<void> {
-    await PubSubCommands.publish(this.conn, topic, data, options || {});
+  async publish(topic: string, data: any, options?: PublishOptions): Promise<PublishConfirm | undefined> {
+    const { alias: useAlias, ...wireOptions } = options || {};
+    const alias = useAlias ? await this.resolveAlias(topic) : undefined;
+    const res = alias !== undefined
+      ? await PubSubCommands.publishAlias(this.conn, alias, data, wireOptions)
+      : await PubSubCommands.publish(this.conn, topic, data, wireOptions);
+    if (res.status !== ResponseStatus.DATA) return undefined;
+    return { publishId: Number(res.cursor.readU64()), delivered: res.cursor.readU32() };
+  }
+
+  private async resolveAlias(topic: string): Promise<number | undefined> {
+    const existing = this.aliases.get(topic);
+    if (existing !== undefined) return existing;
+    if (this.unaliasable.has(topic) || this.nextAlias > MAX_ALIAS) return undefined;
+
+    const alias = this.nextAlias++;
+    try {
+      await PubSubCommands.registerAlias(this.conn, alias, topic);
+      this.aliases.set(topic, alias);
+      return alias;
+    } catch (e) {
+      // Server refused (e.g. alias limit reached): keep publishing by name
+      this.logger.warn(`[PubSub] Alias registration failed for "${topic}", publishing by name`, e);
+      this.unaliasable.add(topic);
+      return undefined;
+    }
   }
 
-  async subscribe(topic: string, callback: Handler): Promise<void> {
+  async subscribe(topic: string, callback: Handler, options: SubscribeOptions = {}): Promise<void> {
     if (this.exact.has(topic) || this.wild.has(topic)) {
       throw new Error(`[PubSub] Already subscribed to "${topic}". Call unsubscribe() first.`);
     }
 
     const isWild = NexoPubSub.isWildcard(topic);
+    const sub: Subscription = { id: this.nextSubscriptionId++, cb: callback, options };
     if (isWild) {
-      this.wild.set(topic, { parts: topic.split('/'), cb: callback });
+      sub.parts = topic.split('/');
+      this.wild.set(topic, sub);
     } else {
-      this.exact.set(topic, callback);
+      this.exact.set(topic, sub);
     }
+    this.byId.set(sub.id, sub);
 
     try {
-      await PubSubCommands.subscribe(this.conn, topic);
+      await PubSubCommands.subscribe(this.conn, topic, sub.id, options);
     } catch (e) {
       if (isWild) this.wild.delete(topic);
       else this.exact.delete(topic);
+      this.byId.delete(sub.id);
       throw e;
     }
   }
 
   async unsubscribe(topic: string): Promise<void> {
-    if (!this.exact.has(topic) && !this.wild.has(topic)) return;
+    const sub = this.exact.get(topic) ?? this.wild.get(topic);
+    if (!sub) return;
 
     await PubSubCommands.unsubscribe(this.conn, topic);
     this.exact.delete(topic);
     this.wild.delete(topic);
+    this.byId.delete(sub.id);
   }
 
-  private dispatch(topic: string, data: any) {
-    const exactCb = this.exact.get(topic);
-    if (exactCb) {
-      try { exactCb(data); } catch (e) { this.logger.error('[PubSub] handler error', e); }
+  private dispatch(topic: string, data: any, retain: boolean, subscriptionIds: number[]) {
+    const info: MessageInfo = { topic, retain };
+
+    // The server names the subscriptions that matched: no local matching needed
+    if (subscriptionIds.length > 0) {
+      for (const id of subscriptionIds) {
+        const sub = this.byId.get(id);
+        if (sub) this.invoke(sub.cb, data, info);
+      }
+      return;
     }
 
+    const exact = this.exact.get(topic);
+    if (exact) this.invoke(exact.cb, data, info);
+
     if (this.wild.size === 0) return;
 
     const tParts = topic.split('/');
     for (const { parts, cb } of this.wild.values()) {
-      if (NexoPubSub.matchesParts(parts, tParts)) {
-        try { cb(data); } catch (e) { this.logger.error('[PubSub] handler error', e); }
-      }
+      if (NexoPubSub.matchesParts(parts!, tParts)) this.invoke(cb, data, info);
     }
   }
 
+  private invoke(cb: Handler, data: any, info: MessageInfo) {
+    try { cb(data, info); } catch (e) { this.logger.error('[PubSub] handler error', e); }
+  }
+
   private static isWildcard(topic: string): boolean {
     return topic.includes('+') || topic.includes('#');
   }
@@ -1,9 +1,11 @@
 import { NexoConnection } from '../connection';
 import { Cursor } from '../codec';
+import { DataType, ResponseStatus } from '../protocol';
 import { Logger } from '../utils/logger';
 import { DEFAULT_CONFIG } from '../config';
 import { ConnectionClosedError } from '../errors';
 import { runConcurrent } from '../utils/concurrent';
+import { SchemaCommands, SchemaKind, SchemaVersion } from './schema';
 
 enum QueueOpcode {
   Q_CREATE = 0x10,
@@ -17,10 +19,43 @@ enum QueueOpcode {
   Q_DELETE_DLQ = 0x18,
   Q_PURGE_DLQ = 0x19,
   Q_NACK = 0x1A,
+  Q_FETCH_BLOB = 0x1B,
+  Q_LIST_SCHEDULED = 0x1C,
+  Q_RESCHEDULE = 0x1D,
+  Q_PURGE_PRIORITY = 0x1E,
+  Q_REPLAY_DLQ = 0x1F,
+  // The 0x10-0x1F block is full
+  Q_SUBSCRIBE = 0x50,
+  Q_UNSUBSCRIBE = 0x51,
+  Q_UNBIND_GROUP = 0x52,
 }
 
 const CONSUME_TIMEOUT_MARGIN_MS = 5000;
 
+/**
+ * Creation time (unix ms) embedded in a queue message id.
+ * Message ids are UUIDv7, so they also sort by creation time.
+ * Returns undefined for legacy (UUIDv4) ids.
+ */
+export function messageTimestamp(id: string): number | undefined {
+  const hex = id.replace(/-/g, '');
+  if (hex.length !== 32 || hex[12] !== '7') return undefined;
+  return parseInt(hex.slice(0, 12), 16);
+}
+
+const BLOB_REF_LEN = 1 + 16 + 8;
+
+/** Decode a message payload, fetching it from the blob store when offloaded. */
+async function decodePayload(conn: NexoConnection, name: string, payload: Buffer): Promise<any> {
+  if (payload.length === BLOB_REF_LEN && payload[0] === DataType.BLOB_REF) {
+    const blobId = new Cursor(payload, 1).readUUID();
+    const res = await conn.send(QueueOpcode.Q_FETCH_BLOB, w => w.string(name).uuid(blobId));
+    if (res.status === ResponseStatus.NULL) return null;
+    return new Cursor(res.cursor.buf, res.cursor.offset).decodeAny();
+  }
+  return new Cursor(payload).decodeAny();
+}
+
 const QueueCommands = {
   create: (conn: NexoConnection, name: string, config: QueueConfig) =>
     conn.send(QueueOpcode.Q_CREATE, w => w
@@ -47,44 +82,102 @@ const QueueCommands = {
       .any(data)
     ),
 
-  consume: async <T>(conn: NexoConnection, name: string, batchSize: number, waitMs: number): Promise<{ id: string, data: T }[]> => {
+  consume: async <T>(conn: NexoConnection, name: string, batchSize: number, waitMs: number, priorityQuotas?: Record<number, number>, group?: string): Promise<{ id: string, data: T }[]> => {
     const res = await conn.send(QueueOpcode.Q_CONSUME, w => w
       .string(name)
-      .string(JSON.stringify({ batchSize, waitMs }))
+      .string(JSON.stringify({ batchSize, waitMs, priorityQuotas, group }))
       , { timeoutMs: waitMs + CONSUME_TIMEOUT_MARGIN_MS });
 
-    const count = res.cursor.readU32();
+    return QueueCommands.decodeBatch<T>(conn, name, res.cursor);
+  },
+
+  // [count u32]{[id 16][len u32][payload]}, as answered to CONSUME and pushed to subscriptions
+  decodeBatch: async <T>(conn: NexoConnection, name: string, cursor: Cursor): Promise<{ id: string, data: T }[]> => {
+    const count = cursor.readU32();
     if (count === 0) return [];
 
     const messages: { id: string; data: T }[] = [];
     for (let i = 0; i < count; i++) {
-      const idHex = res.cursor.readUUID();
-      const payloadLen = res.cursor.readU32();
-      const payloadBuf = res.cursor.readBuffer(payloadLen);
-      const data = new Cursor(payloadBuf).decodeAny();
+      const idHex = cursor.readUUID();
+      const payloadLen = cursor.readU32();
+      const payloadBuf = cursor.readBuffer(payloadLen);
+      const data = await decodePayload(conn, name, payloadBuf);
       messages.push({ id: idHex, data });
     }
     return messages;
   },
 
-  ack: (conn: NexoConnection, name: string, id: string) =>
-    conn.sendFireAndForget(QueueOpcode.Q_ACK, w => w.uuid(id).string(name)),
-
-  nack: (conn: NexoConnection, name: string, id: string, reason: string) =>
-    conn.sendFireAndForget(QueueOpcode.Q_NACK, w => w
-      .uuid(id)
+  subscribe: (conn: NexoConnection, name: string, prefetch: number, priorityQuotas?: Record<number, number>, idleTtlMs?: number) =>
+    conn.send(QueueOpcode.Q_SUBSCRIBE, w => w
       .string(name)
-      .string(reason)
+      .string(JSON.stringify({ prefetch, priorityQuotas, idleTtlMs }))
     ),
 
-  // DLQ Commands
-  peekDLQ: async <T>(conn: NexoConnection, name: string, limit: number, offset: number): Promise<{ total: number, items: { id: string, data: T, attempts: number, failureReason: string }[] }> => {
-    const res = await conn.send(QueueOpcode.Q_PEEK_DLQ, w => w
+  unsubscribe: (conn: NexoConnection, name: string) =>
+    conn.send(QueueOpcode.Q_UNSUBSCRIBE, w => w.string(name)),
+
+  // Fanout queues settle per consumer group: the group goes last
+  ack: (conn: NexoConnection, name: string, id: string, group?: string) =>
+    conn.sendFireAndForget(QueueOpcode.Q_ACK, w => {
+      w.uuid(id).string(name);
+      if (group !== undefined) w.string(group);
+    }),
+
+  nack: (conn: NexoConnection, name: string, id: string, reason: string, group?: string) =>
+    conn.sendFireAndForget(QueueOpcode.Q_NACK, w => {
+      w.uuid(id).string(name).string(reason);
+      if (group !== undefined) w.string(group);
+    }),
+
+  listScheduled: async <T>(conn: NexoConnection, name: string, from: number, to: number, limit: number): Promise<ScheduledMessage<T>[]> => {
+    const res = await conn.send(QueueOpcode.Q_LIST_SCHEDULED, w => w
       .string(name)
+      .u64(from)
+      .u64(to)
       .u32(limit)
-      .u32(offset)
     );
 
+    const count = res.cursor.readU32();
+    const messages: ScheduledMessage<T>[] = [];
+    for (let i = 0; i < count; i++) {
+      const id = res.cursor.readUUID();
+      const scheduledAt = Number(res.cursor.readU64());
+      const priority = res.cursor.readU8();
+      const payloadLen = res.cursor.readU32();
+      const data = await decodePayload(conn, name, res.cursor.readBuffer(payloadLen));
+      messages.push({ id, data, priority, scheduledAt });
+    }
+    return messages;
+  },
+
+  reschedule: async (conn: NexoConnection, name: string, messageId: string, at: number): Promise<boolean> => {
+    const res = await conn.send(QueueOpcode.Q_RESCHEDULE, w => w
+      .string(name)
+      .uuid(messageId)
+      .u64(at)
+    );
+    return res.cursor.readU8() === 1;
+  },
+
+  purgePriority: async (conn: NexoConnection, name: string, priority: number): Promise<number> => {
+    const res = await conn.send(QueueOpcode.Q_PURGE_PRIORITY, w => w
+      .string(name)
+      .u8(priority)
+    );
+    return res.cursor.readU32();
+  },
+
+  unbindGroup: async (conn: NexoConnection, name: string, group: string): Promise<void> => {
+    await conn.send(QueueOpcode.Q_UNBIND_GROUP, w => w.string(name).string(group));
+  },
+
+  // DLQ Commands
+  peekDLQ: async <T>(conn: NexoConnection, name: string, limit: number, offset: number, search?: string): Promise<{ total: number, items: { id: string, data: T, attempts: number, failureReason: string }[] }> => {
+    const res = await conn.send(QueueOpcode.Q_PEEK_DLQ, w => {
+      w.string(name).u32(limit).u32(offset);
+      if (search) w.string(search);
+    });
+
     const total = res.cursor.readU32();
     const count = res.cursor.readU32();
 
@@ -93,7 +186,7 @@ const QueueCommands = {
       const idHex = res.cursor.readUUID();
       const payloadLen = res.cursor.readU32();
       const payloadBuf = res.cursor.readBuffer(payloadLen);
-      const data = new Cursor(payloadBuf).decodeAny();
+      const data = await decodePayload(conn, name, payloadBuf);
       const attempts = res.cursor.readU32();
       const failureReason = res.cursor.readString();
       items.push({ id: idHex, data, attempts, failureReason });
@@ -101,12 +194,21 @@ const QueueCommands = {
     return { total, items };
   },
 
-  moveToQueue: async (conn: NexoConnection, name: string, messageId: string): Promise<boolean> => {
-    const res = await conn.send(QueueOpcode.Q_MOVE_TO_QUEUE, w => w
+  moveToQueue: async (conn: NexoConnection, name: string, messageId: string, options?: DlqReplayOptions): Promise<boolean> => {
+    const res = await conn.send(QueueOpcode.Q_MOVE_TO_QUEUE, w => {
+      w.string(name).uuid(messageId);
+      if (options !== undefined) w.string(JSON.stringify(options));
+    });
+    return res.cursor.readU8() === 1;
+  },
+
+  replayDLQ: async (conn: NexoConnection, name: string, search: string, options: DlqReplayOptions): Promise<number> => {
+    const res = await conn.send(QueueOpcode.Q_REPLAY_DLQ, w => w
       .string(name)
-      .uuid(messageId)
+      .string(search)
+      .string(JSON.stringify(options))
     );
-    return res.cursor.readU8() === 1;
+    return res.cursor.readU32();
   },
 
   deleteDLQ: async (conn: NexoConnection, name: string, messageId: string): Promise<boolean> => {
@@ -126,16 +228,106 @@ const QueueCommands = {
 export interface QueueConfig {
   visibilityTimeoutMs?: number;
   maxRetries?: number;
+  /** DLQ depth that publishes a `threshold` alert (0 = off) */
+  dlqAlertThreshold?: number;
+  /** `fileSync` acks pushes only once they are fsynced (default: `fileAsync`) */
+  persistence?: 'fileAsync' | 'fileSync';
+  webhook?: QueueWebhookConfig;
+  /** Broadcast: every consumer group receives every message (subscribe with `group`) */
+  fanout?: boolean;
+  /** Where messages go once they run out of retries (default: the queue's DLQ) */
+  deadLetter?: QueueDeadLetterTarget;
+  /** Keep acked messages in an archive, browsable and replayable from the dashboard */
+  archive?: QueueArchiveConfig;
+  /** Release delayed messages from a dedicated scheduler thread: typically well under 1ms late, instead of up to ~50ms */
+  preciseDelays?: boolean;
+  /** `key=value` pairs for ownership and cost attribution (`{ team: 'payments' }`); the dashboard filters on them */
+  labels?: Record<string, string>;
+}
+
+/** Unset limits take the server defaults; 0 = no limit */
+export interface QueueArchiveConfig {
+  maxAgeMs?: number;
+  maxBytes?: number;
+}
+
+/** Non-DLQ targets receive a JSON envelope with the failure metadata and the original payload */
+export type QueueDeadLetterTarget =
+  | { type: 'dlq' }
+  | { type: 'queue'; name: string }
+  | { type: 'stream'; topic: string }
+  | { type: 'disabled' };
+
+export interface QueueWebhookConfig {
+  url: string;
+  timeoutMs?: number;
+  concurrency?: number;
 }
 
 export interface QueueSubscribeOptions {
   batchSize?: number;
   waitMs?: number;
   concurrency?: number;
+  /** Max share (0-1) of each batch per priority, e.g. `{ 255: 0.8 }` */
+  priorityQuotas?: Record<number, number>;
+  /** Consumer group, required on fanout queues */
+  group?: string;
+  /**
+   * Have the server push messages as they become ready instead of polling.
+   * Not available on fanout queues.
+   */
+  push?: boolean;
+  /** Push mode: most messages delivered and not yet acked or nacked (default: `batchSize`) */
+  prefetch?: number;
+  /**
+   * Push mode: have the server drop the subscription when messages stay in
+   * flight this long without an ack or nack (a stuck consumer)
+   */
+  idleTtlMs?: number;
 }
 
 export interface QueuePushOptions {
   priority?: number;
+  /** Keep the message invisible to consumers for this long */
+  delayMs?: number;
+  /**
+   * Messages of the same group are delivered one at a time, in push order:
+   * the next only after the previous is acked. Groups run in parallel.
+   */
+  messageGroup?: string;
+  /**
+   * Pushes with a key the queue has already seen within its dedup window
+   * (`QUEUE_DEDUP_WINDOW_MS`) are accepted and dropped.
+   */
+  idempotencyKey?: string;
+}
+
+/** How a DLQ message goes back to its queue (attempts are always reset) */
+export interface DlqReplayOptions {
+  /** Ahead of the messages already waiting at its priority (not with `delayMs`) */
+  front?: boolean;
+  /** Replace the original priority */
+  priority?: number;
+  /** Keep the message invisible to consumers for this long */
+  delayMs?: number;
+  /** Rewrite the payload on the way back, server-side */
+  transform?: DlqReplayTransform;
+}
+
+/** Applied in order: fields stripped first, then the payload wrapped */
+export interface DlqReplayTransform {
+  /** Top-level fields removed from JSON object payloads */
+  stripFields?: string[];
+  /** Replay `{ replay: { failureReason, attempts, failedAt, replayedAt }, contentType, payload }` instead */
+  wrap?: boolean;
+}
+
+export interface ScheduledMessage<T = any> {
+  id: string;
+  data: T;
+  priority: number;
+  /** When the message becomes visible (unix ms) */
+  scheduledAt: number;
 }
 
 /**
@@ -153,22 +345,34 @@ export class NexoDLQ<T = any> {
    * Peek messages in the DLQ without consuming them.
    * @param limit Maximum number of messages to return (default 10)
    * @param offset Pagination offset (default: 0)
+   * @param search Only messages whose payload or failure reason match it
    * @returns Object containing total count and array of messages
    */
-  async peek(limit: number = DEFAULT_CONFIG.queue.peek.limit, offset: number = DEFAULT_CONFIG.queue.peek.offset): Promise<{ total: number, items: { id: string; data: T; attempts: number; failureReason: string }[] }> {
+  async peek(limit: number = DEFAULT_CONFIG.queue.peek.limit, offset: number = DEFAULT_CONFIG.queue.peek.offset, search?: string): Promise<{ total: number, items: { id: string; data: T; attempts: number; failureReason: string }[] }> {
     this.logger.debug(`[DLQ:${this.queueName}] Peeking ${limit} messages at offset ${offset}`);
-    return QueueCommands.peekDLQ<T>(this.conn, this.queueName, limit, offset);
+    return QueueCommands.peekDLQ<T>(this.conn, this.queueName, limit, offset, search);
   }
 
   /**
    * Move a message from DLQ back to the main queue (replay/retry).
    * The message will be reset with attempts = 0 and become available for consumption.
    * @param messageId ID of the message to move
+   * @param options Place it at the front, override its priority or delay it
    * @returns true if the message was moved, false if not found
    */
-  async moveToQueue(messageId: string): Promise<boolean> {
+  async moveToQueue(messageId: string, options?: DlqReplayOptions): Promise<boolean> {
     this.logger.debug(`[DLQ:${this.queueName}] Moving message ${messageId} to main queue`);
-    return QueueCommands.moveToQueue(this.conn, this.queueName, messageId);
+    return QueueCommands.moveToQueue(this.conn, this.queueName, messageId, options);
+  }
+
+  /**
+   * Move every DLQ message whose payload or failure reason contains `search`
+   * (all of them when omitted) back to the main queue, oldest failure first.
+   * @returns Number of messages moved
+   */
+  async moveAllToQueue(search: string = '', options: DlqReplayOptions = {}): Promise<number> {
+    this.logger.debug(`[DLQ:${this.queueName}] Moving all messages matching '${search}' to main queue`);
+    return QueueCommands.replayDLQ(this.conn, this.queueName, search, options);
   }
 
   /**
@@ -228,6 +432,51 @@ export class NexoQueue<T = any> {
     await QueueCommands.push(this.conn, this.name, data, options);
   }
 
+  /**
+   * Delayed messages activating between `from` and `to` (unix ms, inclusive),
+   * earliest first.
+   */
+  async listScheduled(from = 0, to = Number.MAX_SAFE_INTEGER, limit = 100): Promise<ScheduledMessage<T>[]> {
+    return QueueCommands.listScheduled<T>(this.conn, this.name, from, to, limit);
+  }
+
+  /**
+   * Move a delayed message to `at` (unix ms); a time in the past releases it now.
+   * Returns false if the message is no longer scheduled.
+   */
+  async reschedule(messageId: string, at: number): Promise<boolean> {
+    return QueueCommands.reschedule(this.conn, this.name, messageId, at);
+  }
+
+  /**
+   * Drop every pending and scheduled message of one priority level; messages
+   * already delivered to a consumer are left alone. Returns how many were dropped.
+   */
+  async purgePriority(priority: number): Promise<number> {
+    return QueueCommands.purgePriority(this.conn, this.name, priority);
+  }
+
+  /**
+   * Fanout queues: stop keeping messages for a consumer group that is gone.
+   * Consuming with the group again binds it anew, from the oldest message.
+   */
+  async unbindGroup(group: string): Promise<void> {
+    await QueueCommands.unbindGroup(this.conn, this.name, group);
+  }
+
+  /**
+   * Attach a JSON Schema to this queue. Later pushes that don't match it are rejected.
+   * Returns the new schema version.
+   */
+  async setSchema(schema: object): Promise<number> {
+    return SchemaCommands.set(this.conn, SchemaKind.QUEUE, this.name, schema);
+  }
+
+  /** Get a schema version (latest when omitted), or null if none is attached. */
+  async getSchema(version?: number): Promise<SchemaVersion | null> {
+    return SchemaCommands.get(this.conn, SchemaKind.QUEUE, this.name, version);
+  }
+
   async subscribe(callback: (data: T) => Promise<any> | any, options: QueueSubscribeOptions = {}): Promise<{ stop: () => void }> {
     if (this.isSubscribed) throw new Error(`Queue '${this.name}' already subscribed.`);
 
@@ -237,6 +486,7 @@ export class NexoQueue<T = any> {
     }
 
     this.isSubscribed = true;
+    if (options.push) return this.subscribePush(callback, options);
 
     const batchSize = options.batchSize ?? DEFAULT_CONFIG.queue.batchSize;
     const waitMs = options.waitMs ?? DEFAULT_CONFIG.queue.waitMs;
@@ -255,27 +505,11 @@ export class NexoQueue<T = any> {
           // Double check before sending
           if (!this.conn.isConnected) continue;
 
-          const messages = await QueueCommands.consume<T>(this.conn, this.name, batchSize, waitMs);
+          const messages = await QueueCommands.consume<T>(this.conn, this.name, batchSize, waitMs, options.priorityQuotas, options.group);
 
           if (messages.length === 0) continue;
 
-          await runConcurrent(messages, concurrency, async (msg) => {
-            if (!active) {
-              if (this.conn.isConnected) {
-                this.nack(msg.id, "Consumer stopped");
-              }
-              return;
-            }
-            try {
-              await callback(msg.data);
-              this.ack(msg.id);
-            } catch (e: any) {
-              if (!this.conn.isConnected) return;
-              const reason = e instanceof Error ? e.message : String(e);
-              this.logger.error(`[Queue:${this.name}] Consumer error, sending NACK. Reason: ${reason}`);
-              this.nack(msg.id, reason);
-            }
-          });
+          await runConcurrent(messages, concurrency, msg => this.process(msg, callback, () => active, options.group));
 
         } catch (e: any) {
           if (!active) break;
@@ -304,11 +538,82 @@ export class NexoQueue<T = any> {
     };
   }
 
-  private ack(id: string): void {
-    QueueCommands.ack(this.conn, this.name, id);
+  // Push mode: the server keeps up to `prefetch` messages in flight to this
+  // connection, and each ack or nack lets the next one through
+  private async subscribePush(callback: (data: T) => Promise<any> | any, options: QueueSubscribeOptions): Promise<{ stop: () => void }> {
+    const prefetch = options.prefetch ?? options.batchSize ?? DEFAULT_CONFIG.queue.batchSize;
+    const concurrency = options.concurrency ?? DEFAULT_CONFIG.queue.concurrency;
+    const subscribe = () => QueueCommands.subscribe(this.conn, this.name, prefetch, options.priorityQuotas, options.idleTtlMs);
+
+    let active = true;
+    // Deliveries are handled in arrival order
+    let handled: Promise<void> = Promise.resolve();
+    this.conn.queuePushHandlers.set(this.name, (cursor, unsubscribed) => {
+      if (unsubscribed !== undefined) {
+        // Messages already delivered are still settled as they complete
+        this.logger.warn(`[Queue:${this.name}] Server dropped push subscription: ${unsubscribed}`);
+        release();
+        return;
+      }
+      handled = handled
+        .then(async () => {
+          const messages = await QueueCommands.decodeBatch<T>(this.conn, this.name, cursor);
+          await runConcurrent(messages, concurrency, msg => this.process(msg, callback, () => active));
+        })
+        .catch(err => this.logger.error(`[Queue:${this.name}] Push delivery failed`, err));
+    });
+
+    // Subscriptions live on the server connection: renew them on the new one
+    const onReconnect = () => {
+      if (active) subscribe().catch(err => this.logger.error(`[Queue:${this.name}] Resubscribe failed`, err));
+    };
+    this.conn.on('reconnect', onReconnect);
+
+    const release = () => {
+      this.conn.off('reconnect', onReconnect);
+      this.conn.queuePushHandlers.delete(this.name);
+      this.isSubscribed = false;
+    };
+    try {
+      await subscribe();
+    } catch (e) {
+      release();
+      throw e;
+    }
+
+    return {
+      stop: async () => {
+        active = false;
+        if (this.conn.isConnected) await QueueCommands.unsubscribe(this.conn, this.name).catch(() => {});
+        release();
+        await handled;
+      }
+    };
+  }
+
+  private async process(msg: { id: string, data: T }, callback: (data: T) => Promise<any> | any, isActive: () => boolean, group?: string): Promise<void> {
+    if (!isActive()) {
+      if (this.conn.isConnected) {
+        this.nack(msg.id, "Consumer stopped", group);
+      }
+      return;
+    }
+    try {
+      await callback(msg.data);
+      this.ack(msg.id, group);
+    } catch (e: any) {
+      if (!this.conn.isConnected) return;
+      const reason = e instanceof Error ? e.message : String(e);
+      this.logger.error(`[Queue:${this.name}] Consumer error, sending NACK. Reason: ${reason}`);
+      this.nack(msg.id, reason, group);
+    }
+  }
+
+  private ack(id: string, group?: string): void {
+    QueueCommands.ack(this.conn, this.name, id, group);
   }
 
-  private nack(id: string, reason: string): void {
-    QueueCommands.nack(this.conn, this.name, id, reason);
+  private nack(id: string, reason: string, group?: string): void {
+    QueueCommands.nack(this.conn, this.name, id, reason, group);
   }
 }
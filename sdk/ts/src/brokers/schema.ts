@@ -0,0 +1,45 @@
+import { NexoConnection } from '../connection';
+import { ResponseStatus } from '../protocol';
+
+enum AdminOpcode {
+  SET_SCHEMA = 0x42,
+  GET_SCHEMA = 0x43,
+}
+
+/** @internal */
+export enum SchemaKind {
+  QUEUE = 0x01,
+  STREAM = 0x02,
+}
+
+export interface SchemaVersion {
+  version: number;
+  createdAt: number;
+  schema: object;
+}
+
+/** @internal */
+export const SchemaCommands = {
+  set: async (conn: NexoConnection, kind: SchemaKind, name: string, schema: object): Promise<number> => {
+    const res = await conn.send(AdminOpcode.SET_SCHEMA, w => w
+      .u8(kind)
+      .string(name)
+      .string(JSON.stringify(schema))
+    );
+    return res.cursor.readU32();
+  },
+
+  get: async (conn: NexoConnection, kind: SchemaKind, name: string, version?: number): Promise<SchemaVersion | null> => {
+    const res = await conn.send(AdminOpcode.GET_SCHEMA, w => w
+      .u8(kind)
+      .string(name)
+      .u32(version ?? 0)
+    );
+    if (res.status === ResponseStatus.NULL) return null;
+    return {
+      version: res.cursor.readU32(),
+      createdAt: Number(res.cursor.readU64()),
+      schema: JSON.parse(res.cursor.readString()),
+    };
+  },
+};
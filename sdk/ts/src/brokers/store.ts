@@ -1,10 +1,26 @@
 import { NexoConnection } from '../connection';
 import { ResponseStatus } from '../protocol';
+import { Cursor } from '../codec';
 
 enum StoreOpcode {
   MAP_SET = 0x02,
   MAP_GET = 0x03,
   MAP_DEL = 0x04,
+  MAP_GET_AT = 0x05,
+  MAP_HISTORY = 0x06,
+  LOCK = 0x07,
+  UNLOCK = 0x08,
+  LOCK_EXTEND = 0x09,
+  SEM_ACQUIRE = 0x0A,
+  SEM_RELEASE = 0x0B,
+  SEM_EXTEND = 0x0C,
+  MAP_SET_PUBLISH = 0x0D,
+  MAP_MGET = 0x0E,
+  MAP_MSET = 0x0F,
+  MAP_MDEL = 0x60,
+  MAP_TTL = 0x61,
+  MAP_EXPIRE = 0x62,
+  MAP_PERSIST = 0x63,
 }
 
 const StoreCommands = {
@@ -15,6 +31,14 @@ const StoreCommands = {
       .any(value)
     ),
 
+  mapSetPublish: (conn: NexoConnection, key: string, value: any, options: SetAndPublishOptions) =>
+    conn.send(StoreOpcode.MAP_SET_PUBLISH, w => w
+      .string(key)
+      .string(JSON.stringify({ ttl: options.ttl, pubsub: options.pubsub, stream: options.stream, retain: options.retain }))
+      .sizedAny(value)
+      .any(options.event === undefined ? value : options.event)
+    ),
+
   mapGet: async (conn: NexoConnection, key: string) => {
     const res = await conn.send(StoreOpcode.MAP_GET, w => w.string(key));
     if (res.status === ResponseStatus.NULL) return null;
@@ -23,10 +47,197 @@ const StoreCommands = {
 
   mapDel: (conn: NexoConnection, key: string) =>
     conn.send(StoreOpcode.MAP_DEL, w => w.string(key)),
+
+  mapMget: async (conn: NexoConnection, keys: string[]) => {
+    const res = await conn.send(StoreOpcode.MAP_MGET, w => {
+      w.u32(keys.length);
+      for (const key of keys) w.string(key);
+    });
+    const count = res.cursor.readU32();
+    const values: any[] = [];
+    for (let i = 0; i < count; i++) {
+      const found = res.cursor.readU8() === 1;
+      values.push(found ? new Cursor(res.cursor.readBuffer(res.cursor.readU32())).decodeAny() : null);
+    }
+    return values;
+  },
+
+  mapMset: (conn: NexoConnection, entries: [string, any][], options: MapSetOptions) =>
+    conn.send(StoreOpcode.MAP_MSET, w => {
+      w.string(JSON.stringify(options || {})).u32(entries.length);
+      for (const [key, value] of entries) w.string(key).sizedAny(value);
+    }),
+
+  mapMdel: async (conn: NexoConnection, keys: string[]) => {
+    const res = await conn.send(StoreOpcode.MAP_MDEL, w => {
+      w.u32(keys.length);
+      for (const key of keys) w.string(key);
+    });
+    const count = res.cursor.readU32();
+    const deleted: boolean[] = [];
+    for (let i = 0; i < count; i++) deleted.push(res.cursor.readU8() === 1);
+    return deleted;
+  },
+
+  mapTtl: async (conn: NexoConnection, key: string): Promise<number | null | undefined> => {
+    const res = await conn.send(StoreOpcode.MAP_TTL, w => w.string(key));
+    if (res.status === ResponseStatus.NULL) return undefined;
+    const expires = res.cursor.readU8() === 1;
+    const remainingMs = Number(res.cursor.readU64());
+    return expires ? remainingMs : null;
+  },
+
+  mapExpire: async (conn: NexoConnection, key: string, ttlMs: number) => {
+    const res = await conn.send(StoreOpcode.MAP_EXPIRE, w => w.string(key).u64(ttlMs));
+    return res.status !== ResponseStatus.NULL;
+  },
+
+  mapPersist: async (conn: NexoConnection, key: string) => {
+    const res = await conn.send(StoreOpcode.MAP_PERSIST, w => w.string(key));
+    return res.status !== ResponseStatus.NULL;
+  },
+
+  mapGetAt: async (conn: NexoConnection, key: string, at: number) => {
+    const res = await conn.send(StoreOpcode.MAP_GET_AT, w => w.string(key).u64(at));
+    if (res.status === ResponseStatus.NULL) return null;
+    return res.cursor.decodeAny();
+  },
+
+  mapHistory: async (conn: NexoConnection, key: string, limit: number): Promise<MapVersion[]> => {
+    const res = await conn.send(StoreOpcode.MAP_HISTORY, w => w.string(key).u32(limit));
+    const count = res.cursor.readU32();
+    const versions: MapVersion[] = [];
+    for (let i = 0; i < count; i++) {
+      const setAt = Number(res.cursor.readU64());
+      const value = new Cursor(res.cursor.readBuffer(res.cursor.readU32())).decodeAny();
+      versions.push({ setAt, value });
+    }
+    return versions;
+  },
+
+  lock: async (conn: NexoConnection, name: string, owner: string, ttlMs: number) => {
+    const res = await conn.send(StoreOpcode.LOCK, w => w.string(name).string(owner).u64(ttlMs));
+    if (res.status === ResponseStatus.NULL) return null;
+    return { token: res.cursor.readU64(), expiresAt: Number(res.cursor.readU64()) };
+  },
+
+  unlock: (conn: NexoConnection, name: string, token: bigint) =>
+    conn.send(StoreOpcode.UNLOCK, w => w.string(name).u64(token)),
+
+  lockExtend: async (conn: NexoConnection, name: string, token: bigint, ttlMs: number) => {
+    const res = await conn.send(StoreOpcode.LOCK_EXTEND, w => w.string(name).u64(token).u64(ttlMs));
+    return Number(res.cursor.readU64());
+  },
+
+  semAcquire: async (conn: NexoConnection, name: string, max: number, ttlMs: number) => {
+    const res = await conn.send(StoreOpcode.SEM_ACQUIRE, w => w.string(name).u32(max).u64(ttlMs));
+    if (res.status === ResponseStatus.NULL) return null;
+    return { lease: res.cursor.readU64(), expiresAt: Number(res.cursor.readU64()) };
+  },
+
+  semRelease: (conn: NexoConnection, name: string, lease: bigint) =>
+    conn.send(StoreOpcode.SEM_RELEASE, w => w.string(name).u64(lease)),
+
+  semExtend: async (conn: NexoConnection, name: string, lease: bigint, ttlMs: number) => {
+    const res = await conn.send(StoreOpcode.SEM_EXTEND, w => w.string(name).u64(lease).u64(ttlMs));
+    return Number(res.cursor.readU64());
+  },
 };
 
 export interface MapSetOptions {
   ttl?: number;
+  /** Never expire, instead of taking the server's default TTL. */
+  keepForever?: boolean;
+}
+
+export interface SetAndPublishOptions extends Omit<MapSetOptions, 'keepForever'> {
+  /** Pub/Sub topic to publish the event on (set this or `stream`) */
+  pubsub?: string;
+  /** Stream topic to append the event to; it must already exist */
+  stream?: string;
+  /** Pub/Sub only: keep the event as the topic's retained message */
+  retain?: boolean;
+  /** Event payload (default: the value) */
+  event?: any;
+}
+
+export interface MapVersion<T = any> {
+  /** Write time, ms since epoch */
+  setAt: number;
+  value: T;
+}
+
+export interface LockOptions {
+  /** Lease length; the lock is released automatically once it runs out */
+  ttlMs: number;
+  /** Shown on the dashboard */
+  owner?: string;
+}
+
+/** A held lock. Pass `token` along to the protected resource to fence stale holders. */
+export class NexoLock {
+  constructor(
+    private conn: NexoConnection,
+    public readonly name: string,
+    public readonly token: bigint,
+    public expiresAt: number,
+  ) { }
+
+  /** Renew the lease for another `ttlMs`; fails once the lock has expired. */
+  async extend(ttlMs: number): Promise<void> {
+    this.expiresAt = await StoreCommands.lockExtend(this.conn, this.name, this.token, ttlMs);
+  }
+
+  async release(): Promise<void> {
+    await StoreCommands.unlock(this.conn, this.name, this.token);
+  }
+}
+
+export class NexoLocks {
+  constructor(private conn: NexoConnection) { }
+
+  /** Take the lock, or `null` if someone else holds it. */
+  async acquire(name: string, options: LockOptions): Promise<NexoLock | null> {
+    const grant = await StoreCommands.lock(this.conn, name, options.owner ?? '', options.ttlMs);
+    if (!grant) return null;
+    return new NexoLock(this.conn, name, grant.token, grant.expiresAt);
+  }
+}
+
+export interface SemaphoreOptions {
+  /** Concurrent leases allowed; must match the current holders' value */
+  max: number;
+  /** Lease length; the slot is freed automatically once it runs out */
+  ttlMs: number;
+}
+
+/** One slot of a semaphore, held by this connection until released, expired or disconnected. */
+export class NexoSemaphoreLease {
+  constructor(
+    private conn: NexoConnection,
+    public readonly name: string,
+    public readonly lease: bigint,
+    public expiresAt: number,
+  ) { }
+
+  async extend(ttlMs: number): Promise<void> {
+    this.expiresAt = await StoreCommands.semExtend(this.conn, this.name, this.lease, ttlMs);
+  }
+
+  async release(): Promise<void> {
+    await StoreCommands.semRelease(this.conn, this.name, this.lease);
+  }
+}
+
+export class NexoSemaphores {
+  constructor(private conn: NexoConnection) { }
+
+  /** Take a slot, or `null` if all `max` slots are leased. */
+  async acquire(name: string, options: SemaphoreOptions): Promise<NexoSemaphoreLease | null> {
+    const grant = await StoreCommands.semAcquire(this.conn, name, options.max, options.ttlMs);
+    if (!grant) return null;
+    return new NexoSemaphoreLease(this.conn, name, grant.lease, grant.expiresAt);
+  }
 }
 
 export class NexoMap {
@@ -36,6 +247,14 @@ export class NexoMap {
     await StoreCommands.mapSet(this.conn, key, value, options);
   }
 
+  /**
+   * Set the key and publish an event as one operation: after a server crash
+   * the pair is completed on restart, so neither happens without the other.
+   */
+  async setAndPublish(key: string, value: any, options: SetAndPublishOptions): Promise<void> {
+    await StoreCommands.mapSetPublish(this.conn, key, value, options);
+  }
+
   async get<T = any>(key: string): Promise<T | null> {
     return StoreCommands.mapGet(this.conn, key);
   }
@@ -43,11 +262,58 @@ export class NexoMap {
   async del(key: string): Promise<void> {
     await StoreCommands.mapDel(this.conn, key);
   }
+
+  /**
+   * Time left before the key expires, in ms: `null` if it never expires,
+   * `undefined` if the key does not exist.
+   */
+  async ttl(key: string): Promise<number | null | undefined> {
+    return StoreCommands.mapTtl(this.conn, key);
+  }
+
+  /** Make the key expire `ttlMs` from now, keeping its value. False if the key does not exist. */
+  async expire(key: string, ttlMs: number): Promise<boolean> {
+    return StoreCommands.mapExpire(this.conn, key, ttlMs);
+  }
+
+  /** Remove the key's expiry. False if the key does not exist. */
+  async persist(key: string): Promise<boolean> {
+    return StoreCommands.mapPersist(this.conn, key);
+  }
+
+  /** Values of many keys in one request, in order (`null` for missing keys). */
+  async mget<T = any>(keys: string[]): Promise<(T | null)[]> {
+    return StoreCommands.mapMget(this.conn, keys);
+  }
+
+  /** Set many keys in one request, all with the same options. */
+  async mset(entries: Record<string, any>, options: MapSetOptions = {}): Promise<void> {
+    await StoreCommands.mapMset(this.conn, Object.entries(entries), options);
+  }
+
+  /** Delete many keys in one request: for each, whether it existed. */
+  async mdel(keys: string[]): Promise<boolean[]> {
+    return StoreCommands.mapMdel(this.conn, keys);
+  }
+
+  /** Value the key held at `at` (versioned prefixes only). */
+  async getAt<T = any>(key: string, at: Date | number): Promise<T | null> {
+    return StoreCommands.mapGetAt(this.conn, key, at instanceof Date ? at.getTime() : at);
+  }
+
+  /** Retained versions of the key, newest first (versioned prefixes only). */
+  async history<T = any>(key: string, limit = 100): Promise<MapVersion<T>[]> {
+    return StoreCommands.mapHistory(this.conn, key, limit);
+  }
 }
 
 export class NexoStore {
   public readonly map: NexoMap;
+  public readonly locks: NexoLocks;
+  public readonly semaphores: NexoSemaphores;
   constructor(conn: NexoConnection) {
     this.map = new NexoMap(conn);
+    this.locks = new NexoLocks(conn);
+    this.semaphores = new NexoSemaphores(conn);
   }
 }
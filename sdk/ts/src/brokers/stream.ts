@@ -2,8 +2,9 @@ import { NexoConnection } from '../connection';
 import { Cursor } from '../codec';
 import { Logger } from '../utils/logger';
 import { DEFAULT_CONFIG } from '../config';
-import { ConnectionClosedError, NotConnectedError } from '../errors';
+import { ConnectionClosedError, ErrorCode, NexoServerError, NotConnectedError } from '../errors';
 import { runConcurrent } from '../utils/concurrent';
+import { SchemaCommands, SchemaKind, SchemaVersion } from './schema';
 
 const FETCH_TIMEOUT_MARGIN_MS = 5000;
 
@@ -15,8 +16,15 @@ enum StreamOpcode {
   S_ACK = 0x34,
   S_EXISTS = 0x35,
   S_DELETE = 0x36,
+  S_JOIN_MULTI = 0x37,
   S_SEEK = 0x38,
   S_LEAVE = 0x39,
+  S_PUB_LANE = 0x3A,
+  S_PAUSE = 0x3B,
+  S_RESUME = 0x3C,
+  S_NACK = 0x3D,
+  S_FETCH_MULTI = 0x3E,
+  S_ALTER = 0x3F,
 }
 
 export interface RetentionOptions {
@@ -26,6 +34,38 @@ export interface RetentionOptions {
 
 export interface StreamCreateOptions {
   retention?: RetentionOptions;
+  priorityLanes?: boolean;
+  /** `fileSync` acks publishes only once they are fsynced (default: `fileAsync`) */
+  persistence?: 'fileAsync' | 'fileSync';
+  /** Copy records that exhaust their deliveries to `<topic>.<group>.dlq` (default: false) */
+  deadLetter?: boolean;
+  /** Flushed records kept in RAM (default: `STREAM_RAM_SOFT_LIMIT`; `0`: tail-only) */
+  maxMemoryMessages?: number;
+  /** Payload bytes kept in RAM once flushed (default and `0`: no cap) */
+  maxMemoryBytes?: number;
+  /** Publishes held this long to be appended as one batch (default: `STREAM_DEFAULT_LINGER_MS`; `0`: none) */
+  lingerMs?: number;
+  /** `key=value` pairs for ownership and cost attribution (`{ team: 'payments' }`) */
+  labels?: Record<string, string>;
+}
+
+/** Settings `alter` changes; the ones left out are kept. */
+export interface StreamAlterOptions {
+  retention?: RetentionOptions;
+  /** Flush interval of the topic's writes (`0`: back to the server default) */
+  flushMs?: number;
+  /** Publishes held this long to be appended as one batch (`0`: none) */
+  lingerMs?: number;
+  persistence?: 'fileAsync' | 'fileSync';
+  deadLetter?: boolean;
+  maxMemoryMessages?: number;
+  maxMemoryBytes?: number;
+  /** Replaces the whole set (`{}` clears it) */
+  labels?: Record<string, string>;
+}
+
+export interface StreamPublishOptions {
+  priority?: 'normal' | 'high';
 }
 
 export interface StreamSubscribeOptions {
@@ -40,8 +80,7 @@ export interface StreamMessage<T> {
 }
 
 function isRecoverableMembershipError(e: any): boolean {
-  const msg = e instanceof Error ? e.message : String(e);
-  return msg.includes('FENCED') || msg.includes('NOT_MEMBER');
+  return e instanceof NexoServerError && (e.code === ErrorCode.FENCED || e.code === ErrorCode.NOT_MEMBER);
 }
 
 function sleep(ms: number): Promise<void> {
@@ -53,6 +92,8 @@ class StreamSubscription<T> {
   private loopDone: Promise<void> = Promise.resolve();
   private consumerId: string | null = null;
   private generation: bigint = 0n;
+  private sessionToken: string | null = null;
+  private paused = false;
 
   constructor(
     private readonly conn: NexoConnection,
@@ -87,18 +128,43 @@ class StreamSubscription<T> {
         );
       } catch { /* connection may already be closed */ }
     }
+    this.sessionToken = null;
     await this.loopDone;
   }
 
   private async join(): Promise<void> {
     if (!this.conn.isConnected) throw new NotConnectedError();
-    const res = await this.conn.send(StreamOpcode.S_JOIN, w => w
-      .string(this.group)
-      .string(this.streamName)
-    );
+    // Presenting the previous session token resumes the membership after a reconnect
+    const res = await this.conn.send(StreamOpcode.S_JOIN, w => {
+      w.string(this.group).string(this.streamName);
+      if (this.sessionToken !== null) w.string(this.sessionToken);
+    });
     res.cursor.readU64(); // ack_floor (unused client-side)
     this.generation = res.cursor.readU64();
     this.consumerId = res.cursor.readString();
+    this.sessionToken = res.cursor.readString();
+    // A pause belongs to the previous generation: re-apply it
+    if (this.paused) await this.sendPauseState();
+  }
+
+  async setPaused(paused: boolean): Promise<void> {
+    this.paused = paused;
+    if (this.consumerId === null) return;
+    try {
+      await this.sendPauseState();
+    } catch (e) {
+      // Stale membership: the loop rejoins and re-applies the state
+      if (!isRecoverableMembershipError(e)) throw e;
+    }
+  }
+
+  private async sendPauseState(): Promise<void> {
+    await this.conn.send(this.paused ? StreamOpcode.S_PAUSE : StreamOpcode.S_RESUME, w => w
+      .string(this.streamName)
+      .string(this.group)
+      .string(this.consumerId!)
+      .u64(this.generation)
+    );
   }
 
   private async loop(): Promise<void> {
@@ -110,7 +176,10 @@ class StreamSubscription<T> {
         if (!this.active) break;
         this.consumerId = null;
 
-        if (isRecoverableMembershipError(e)) continue;
+        if (isRecoverableMembershipError(e)) {
+          this.sessionToken = null;
+          continue;
+        }
 
         if (!this.conn.isConnected || e instanceof ConnectionClosedError || e.code === 'ECONNRESET') {
           await sleep(DEFAULT_CONFIG.connection.backoff.short);
@@ -160,12 +229,234 @@ class StreamSubscription<T> {
           .u64(seq)
         );
       } catch (err) {
-        this.logger.error(`[${this.streamName}:${this.group}] Processing error at seq=${seq}. Waiting for timeout-based retry.`, err);
+        this.logger.error(`[${this.streamName}:${this.group}] Processing error at seq=${seq}. Nacking for redelivery.`, err);
+        this.conn.sendFireAndForget(StreamOpcode.S_NACK, w => w
+          .string(this.streamName)
+          .string(this.group)
+          .string(consumerId)
+          .u64(generation)
+          .u64(seq)
+          .string(err instanceof Error ? err.message : String(err))
+        );
       }
     });
   }
 }
 
+interface TopicMembership {
+  consumerId: string;
+  generation: bigint;
+  sessionToken: string;
+}
+
+/**
+ * One consumer of a group across several topics. The group holds a membership
+ * (and an ack floor) per topic; a single fetch serves them all and every ack
+ * goes back to the topic its record came from.
+ */
+class MultiStreamSubscription<T> {
+  private active = false;
+  private loopDone: Promise<void> = Promise.resolve();
+  private memberships = new Map<string, TopicMembership>();
+  private sessionTokens = new Map<string, string>();
+  private paused = false;
+
+  constructor(
+    private readonly conn: NexoConnection,
+    private readonly selectors: string[],
+    private readonly group: string,
+    private readonly logger: Logger,
+    private readonly callback: (data: T, topic: string) => Promise<any> | any,
+    private readonly batchSize: number,
+    private readonly waitMs: number,
+    private readonly concurrency: number,
+  ) { }
+
+  private get label(): string {
+    return `${this.selectors.join(',')}:${this.group}`;
+  }
+
+  async start(): Promise<void> {
+    this.active = true;
+    await this.join();
+    this.loopDone = this.loop().catch(err => {
+      this.logger.error(`[${this.label}] Consumer crashed`, err);
+      this.active = false;
+    });
+  }
+
+  async stop(): Promise<void> {
+    this.active = false;
+    for (const [topic, m] of this.memberships) {
+      try {
+        await this.conn.send(StreamOpcode.S_LEAVE, w => w
+          .string(topic)
+          .string(this.group)
+          .string(m.consumerId)
+          .u64(m.generation)
+        );
+      } catch { /* connection may already be closed */ }
+    }
+    this.memberships.clear();
+    this.sessionTokens.clear();
+    await this.loopDone;
+  }
+
+  /** (Re)join every matching topic. Patterns are resolved again, picking up new topics. */
+  private async join(): Promise<void> {
+    if (!this.conn.isConnected) throw new NotConnectedError();
+    const res = await this.conn.send(StreamOpcode.S_JOIN_MULTI, w => {
+      w.string(this.group).u32(this.selectors.length);
+      for (const selector of this.selectors) w.string(selector);
+      if (this.sessionTokens.size > 0) w.string(JSON.stringify(Object.fromEntries(this.sessionTokens)));
+    });
+    this.memberships.clear();
+    this.sessionTokens.clear();
+    const count = res.cursor.readU32();
+    for (let i = 0; i < count; i++) {
+      const topic = res.cursor.readString();
+      res.cursor.readU64(); // ack_floor (unused client-side)
+      const generation = res.cursor.readU64();
+      const consumerId = res.cursor.readString();
+      const sessionToken = res.cursor.readString();
+      this.memberships.set(topic, { consumerId, generation, sessionToken });
+      this.sessionTokens.set(topic, sessionToken);
+    }
+    if (this.paused) await this.sendPauseState();
+  }
+
+  async setPaused(paused: boolean): Promise<void> {
+    this.paused = paused;
+    if (this.memberships.size === 0) return;
+    try {
+      await this.sendPauseState();
+    } catch (e) {
+      if (!isRecoverableMembershipError(e)) throw e;
+    }
+  }
+
+  private async sendPauseState(): Promise<void> {
+    for (const [topic, m] of this.memberships) {
+      await this.conn.send(this.paused ? StreamOpcode.S_PAUSE : StreamOpcode.S_RESUME, w => w
+        .string(topic)
+        .string(this.group)
+        .string(m.consumerId)
+        .u64(m.generation)
+      );
+    }
+  }
+
+  private async loop(): Promise<void> {
+    while (this.active) {
+      try {
+        if (this.memberships.size === 0) await this.join();
+        await this.pollOnce();
+      } catch (e: any) {
+        if (!this.active) break;
+        this.memberships.clear();
+
+        // Topics appeared or disappeared: rejoin, resuming the memberships we hold
+        if (e instanceof NexoServerError && e.code === ErrorCode.REBALANCE) continue;
+
+        if (isRecoverableMembershipError(e)) {
+          this.sessionTokens.clear();
+          continue;
+        }
+
+        if (!this.conn.isConnected || e instanceof ConnectionClosedError || e.code === 'ECONNRESET') {
+          await sleep(DEFAULT_CONFIG.connection.backoff.short);
+          continue;
+        }
+
+        this.logger.error(`[${this.label}] Error. Retrying in ${DEFAULT_CONFIG.connection.backoff.long}ms...`, e);
+        await sleep(DEFAULT_CONFIG.connection.backoff.long);
+      }
+    }
+  }
+
+  private async pollOnce(): Promise<void> {
+    const memberships = new Map(this.memberships);
+
+    const res = await this.conn.send(StreamOpcode.S_FETCH_MULTI, w => {
+      w.string(this.group).u32(this.batchSize).u32(this.waitMs).u32(memberships.size);
+      for (const [topic, m] of memberships) w.string(topic).string(m.consumerId).u64(m.generation);
+    }, { timeoutMs: this.waitMs + FETCH_TIMEOUT_MARGIN_MS });
+
+    const count = res.cursor.readU32();
+    if (count === 0) return;
+
+    const batch: { topic: string; seq: bigint; data: T }[] = [];
+    for (let i = 0; i < count; i++) {
+      const topic = res.cursor.readString();
+      const seq = res.cursor.readU64();
+      res.cursor.readU64(); // skip timestamp
+      const payloadLen = res.cursor.readU32();
+      const payloadBuf = res.cursor.readBuffer(payloadLen);
+      batch.push({ topic, seq, data: new Cursor(payloadBuf).decodeAny() as T });
+    }
+
+    await runConcurrent(batch, this.concurrency, async ({ topic, seq, data }) => {
+      if (!this.active) return;
+      const m = memberships.get(topic)!;
+      try {
+        await this.callback(data, topic);
+        this.conn.sendFireAndForget(StreamOpcode.S_ACK, w => w
+          .string(topic)
+          .string(this.group)
+          .string(m.consumerId)
+          .u64(m.generation)
+          .u64(seq)
+        );
+      } catch (err) {
+        this.logger.error(`[${topic}:${this.group}] Processing error at seq=${seq}. Nacking for redelivery.`, err);
+        this.conn.sendFireAndForget(StreamOpcode.S_NACK, w => w
+          .string(topic)
+          .string(this.group)
+          .string(m.consumerId)
+          .u64(m.generation)
+          .u64(seq)
+          .string(err instanceof Error ? err.message : String(err))
+        );
+      }
+    });
+  }
+}
+
+/**
+ * Several topics consumed by one group: exact names, or prefixes ending in `*`
+ * (e.g. `orders.*`). Matching topics created or deleted later rebalance the
+ * subscription: it rejoins and keeps consuming the new set.
+ */
+export class NexoStreamSet<T = any> {
+  constructor(
+    private readonly conn: NexoConnection,
+    public readonly selectors: string[],
+    private readonly logger: Logger,
+  ) { }
+
+  async subscribe(
+    group: string,
+    callback: (data: T, topic: string) => Promise<any> | any,
+    options: StreamSubscribeOptions = {}
+  ): Promise<{ stop: () => Promise<void>; pause: () => Promise<void>; resume: () => Promise<void> }> {
+    if (!group) throw new Error('Consumer Group is required for subscription');
+    if (this.selectors.length === 0) throw new Error('At least one topic is required for subscription');
+
+    const batchSize = options.batchSize ?? DEFAULT_CONFIG.stream.batchSize;
+    const waitMs = options.waitMs ?? DEFAULT_CONFIG.stream.waitMs;
+    const concurrency = Math.max(1, options.concurrency ?? DEFAULT_CONFIG.stream.concurrency);
+
+    const sub = new MultiStreamSubscription<T>(this.conn, this.selectors, group, this.logger, callback, batchSize, waitMs, concurrency);
+    await sub.start();
+
+    return {
+      stop: () => sub.stop(),
+      pause: () => sub.setPaused(true),
+      resume: () => sub.setPaused(false),
+    };
+  }
+}
+
 export class NexoStream<T = any> {
   constructor(
     private readonly conn: NexoConnection,
@@ -181,6 +472,19 @@ export class NexoStream<T = any> {
     return this;
   }
 
+  /**
+   * Change the settings of an existing topic; only the ones given are touched.
+   * `create` with settings that differ from the topic's fails with `CONFLICT`.
+   * Priority lanes are fixed at creation.
+   */
+  async alter(options: StreamAlterOptions): Promise<this> {
+    await this.conn.send(StreamOpcode.S_ALTER, w => w
+      .string(this.name)
+      .string(JSON.stringify(options))
+    );
+    return this;
+  }
+
   async exists(): Promise<boolean> {
     try {
       const res = await this.conn.send(StreamOpcode.S_EXISTS, w => w.string(this.name));
@@ -194,18 +498,39 @@ export class NexoStream<T = any> {
     await this.conn.send(StreamOpcode.S_DELETE, w => w.string(this.name));
   }
 
-  async publish(data: T): Promise<void> {
+  async publish(data: T, options: StreamPublishOptions = {}): Promise<void> {
+    if (options.priority === 'high') {
+      await this.conn.send(StreamOpcode.S_PUB_LANE, w => w
+        .string(this.name)
+        .u8(1)
+        .any(data)
+      );
+      return;
+    }
     await this.conn.send(StreamOpcode.S_PUB, w => w
       .string(this.name)
       .any(data)
     );
   }
 
+  /**
+   * Attach a JSON Schema to this topic. Later publishes that don't match it are rejected.
+   * Returns the new schema version.
+   */
+  async setSchema(schema: object): Promise<number> {
+    return SchemaCommands.set(this.conn, SchemaKind.STREAM, this.name, schema);
+  }
+
+  /** Get a schema version (latest when omitted), or null if none is attached. */
+  async getSchema(version?: number): Promise<SchemaVersion | null> {
+    return SchemaCommands.get(this.conn, SchemaKind.STREAM, this.name, version);
+  }
+
   async subscribe(
     group: string,
     callback: (data: T) => Promise<any> | any,
     options: StreamSubscribeOptions = {}
-  ): Promise<{ stop: () => Promise<void> }> {
+  ): Promise<{ stop: () => Promise<void>; pause: () => Promise<void>; resume: () => Promise<void> }> {
     if (!group) throw new Error('Consumer Group is required for subscription');
 
     const batchSize = options.batchSize ?? DEFAULT_CONFIG.stream.batchSize;
@@ -215,7 +540,11 @@ export class NexoStream<T = any> {
     const sub = new StreamSubscription<T>(this.conn, this.name, group, this.logger, callback, batchSize, waitMs, concurrency);
     await sub.start();
 
-    return { stop: () => sub.stop() };
+    return {
+      stop: () => sub.stop(),
+      pause: () => sub.setPaused(true),
+      resume: () => sub.setPaused(false),
+    };
   }
 
   /** Seek to beginning or end of the stream for a consumer group. */
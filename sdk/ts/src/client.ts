@@ -4,11 +4,13 @@ import { NexoConnection } from './connection';
 import { NexoStore } from './brokers/store';
 import { NexoQueue } from './brokers/queue';
 import { NexoPubSub, NexoTopic } from './brokers/pubsub';
-import { NexoStream } from './brokers/stream';
+import { NexoStream, NexoStreamSet } from './brokers/stream';
 
 export interface NexoOptions {
-  host: string;
-  port: number;
+  host?: string;
+  port?: number;
+  /** Unix domain socket path (same-host servers); replaces host and port */
+  path?: string;
   logger?: LogHandler;
   logLevel?: string;
 }
@@ -16,7 +18,7 @@ export interface NexoOptions {
 export class NexoClient {
   private conn: NexoConnection;
   private queues = new Map<string, NexoQueue<any>>();
-  private streams = new Map<string, NexoStream<any>>();
+  private streamHandles = new Map<string, NexoStream<any>>();
   private topics = new Map<string, NexoTopic<any>>();
   private logger: Logger;
 
@@ -30,8 +32,9 @@ export class NexoClient {
     });
 
     this.conn = new NexoConnection({
-      host: options.host,
-      port: options.port,
+      host: options.host ?? 'localhost',
+      port: options.port ?? 7654,
+      path: options.path,
       ...DEFAULT_CONFIG.connection,
     }, this.logger);
 
@@ -58,14 +61,19 @@ export class NexoClient {
   }
 
   stream<T = any>(name: string): NexoStream<T> {
-    let s = this.streams.get(name);
+    let s = this.streamHandles.get(name);
     if (!s) {
       s = new NexoStream<T>(this.conn, name, this.logger);
-      this.streams.set(name, s);
+      this.streamHandles.set(name, s);
     }
     return s;
   }
 
+  /** Consume several topics (names, or `prefix*` patterns) with one consumer group. */
+  streams<T = any>(selectors: string[]): NexoStreamSet<T> {
+    return new NexoStreamSet<T>(this.conn, selectors, this.logger);
+  }
+
   pubsub<T = any>(name: string): NexoTopic<T> {
     let t = this.topics.get(name);
     if (!t) {
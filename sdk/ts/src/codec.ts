@@ -1,10 +1,12 @@
 import { DataType, FrameType } from './protocol';
+import { EncodedPayload } from './payload';
 
 /** @internal */
 export class Cursor {
   constructor(public buf: Buffer, public offset = 0) { }
 
   readU8(): number { return this.buf.readUInt8(this.offset++); }
+  readU16(): number { const v = this.buf.readUInt16BE(this.offset); this.offset += 2; return v; }
   readU32(): number { const v = this.buf.readUInt32BE(this.offset); this.offset += 4; return v; }
   readU64(): bigint { const v = this.buf.readBigUInt64BE(this.offset); this.offset += 8; return v; }
 
@@ -41,6 +43,10 @@ export class Cursor {
         return start === end ? null : JSON.parse(this.buf.toString('utf8', start, end));
       case DataType.STRING:
         return this.buf.toString('utf8', start, end);
+      case DataType.MSGPACK:
+        return EncodedPayload.msgpack(this.buf.subarray(start, end));
+      case DataType.PROTOBUF:
+        return EncodedPayload.protobuf(this.buf.subarray(start, end));
       case DataType.RAW:
       default:
         return this.buf.subarray(start, end);
@@ -90,6 +96,13 @@ export class FrameWriter {
     return this;
   }
 
+  u16(v: number): this {
+    this.ensure(2);
+    this.buf.writeUInt16BE(v, this.offset);
+    this.offset += 2;
+    return this;
+  }
+
   u32(v: number): this {
     this.ensure(4);
     this.buf.writeUInt32BE(v, this.offset);
@@ -140,7 +153,12 @@ export class FrameWriter {
   }
 
   any(data: unknown): this {
-    if (Buffer.isBuffer(data)) {
+    if (data instanceof EncodedPayload) {
+      this.ensure(1 + data.data.length);
+      this.buf.writeUInt8(data.dataType, this.offset++);
+      data.data.copy(this.buf, this.offset);
+      this.offset += data.data.length;
+    } else if (Buffer.isBuffer(data)) {
       this.ensure(1 + data.length);
       this.buf.writeUInt8(DataType.RAW, this.offset++);
       data.copy(this.buf, this.offset);
@@ -162,6 +180,14 @@ export class FrameWriter {
     return this;
   }
 
+  /** `any` behind a u32 length, for a value followed by more fields. */
+  sizedAny(data: unknown): this {
+    const start = this.offset;
+    this.u32(0).any(data);
+    this.buf.writeUInt32BE(this.offset - start - 4, start);
+    return this;
+  }
+
   /**
    * Finalize the frame: writes the 10-byte header in-place and returns a
    * zero-copy view of the populated bytes. After calling this, the writer must
@@ -1,9 +1,13 @@
 export interface NexoConnectionConfig {
   host: string;
   port: number;
+  /** Unix domain socket path; when set, host and port are ignored */
+  path?: string;
   requestTimeoutMs: number;
   reconnectDelayMs: number;
   sweepIntervalMs: number;
+  /** Requests with a larger payload are sent as chunked frames */
+  chunkSize: number;
   backoff: {
     short: number;
     long: number;
@@ -31,6 +35,7 @@ export const DEFAULT_CONFIG = {
     requestTimeoutMs: 15000,
     reconnectDelayMs: 1500,
     sweepIntervalMs: 1000,
+    chunkSize: 1024 * 1024,
     backoff: {
       short: 1000,
       long: 2000,
@@ -2,9 +2,10 @@ import * as net from 'net';
 import { EventEmitter } from 'events';
 import { Logger } from './utils/logger';
 import { NexoConnectionConfig } from './config';
-import { FrameType, ResponseStatus } from './protocol';
+import { FrameType, PushFlag, ResponseStatus } from './protocol';
 import { Cursor, FrameWriter } from './codec';
-import { ConnectionClosedError, NotConnectedError, RequestTimeoutError } from './errors';
+import { crc32 } from './utils/crc32';
+import { ConnectionClosedError, ErrorCode, NexoServerError, NotConnectedError, RequestTimeoutError } from './errors';
 
 /** @internal */
 export class NexoConnection extends EventEmitter {
@@ -21,10 +22,17 @@ export class NexoConnection extends EventEmitter {
   private readonly logger: Logger;
   private sweepInterval: NodeJS.Timeout | null = null;
 
-  public onPush?: (topic: string, data: any) => void;
+  public onPush?: (topic: string, data: any, info: { retain: boolean, subscriptionIds: number[] }) => void;
+  // The server dropped a pub/sub subscription (idle TTL)
+  public onUnsubscribed?: (pattern: string, reason: string) => void;
+  // Queue push subscriptions, by queue name: the cursor is past the queue name.
+  // `unsubscribed` is set, with no messages, when the server dropped the subscription
+  public queuePushHandlers = new Map<string, (cursor: Cursor, unsubscribed?: string) => void>();
 
   private buffer: Buffer = Buffer.alloc(0);
   private chunks: Buffer[] = [];
+  // Chunked responses/pushes being reassembled, by correlation id
+  private partials = new Map<number, { type: number, meta: number, total: number, parts: Buffer[], received: number, crc: number }>();
 
   private readonly host: string;
   private readonly port: number;
@@ -85,16 +93,24 @@ export class NexoConnection extends EventEmitter {
     //   effect is neutral-to-slightly-negative on median but improves MAX.
     // - keepAlive makes the kernel probe idle connections so dead sockets
     //   (NAT/LB idle timeouts) are detected in seconds instead of minutes.
-    this.socket.setNoDelay(true);
-    this.socket.setKeepAlive(true, 30_000);
+    // (TCP only: a Unix domain socket has neither)
+    if (!this.config.path) {
+      this.socket.setNoDelay(true);
+      this.socket.setKeepAlive(true, 30_000);
+    }
 
     this.setupListeners();
 
     return new Promise((res, rej) => {
-      this.socket.connect(this.port, this.host, () => {
+      const onConnect = () => {
         this.isConnected = true;
         res();
-      });
+      };
+      if (this.config.path) {
+        this.socket.connect(this.config.path, onConnect);
+      } else {
+        this.socket.connect(this.port, this.host, onConnect);
+      }
       this.socket.once('error', rej);
     });
   }
@@ -119,6 +135,7 @@ export class NexoConnection extends EventEmitter {
       this.pending.clear();
       this.chunks = [];
       this.buffer = Buffer.alloc(0);
+      this.partials.clear();
 
       if (this.shouldReconnect && !this.isReconnecting) {
         this.startReconnectLoop();
@@ -182,7 +199,10 @@ export class NexoConnection extends EventEmitter {
     cursor.readU32(); // Skip payloadLen
 
     const payload = cursor.buf.subarray(cursor.offset);
+    this.routeFrame(type, meta, id, payload);
+  }
 
+  private routeFrame(type: number, meta: number, id: number, payload: Buffer) {
     switch (type) {
       case FrameType.RESPONSE: {
         const req = this.pending.get(id);
@@ -190,23 +210,124 @@ export class NexoConnection extends EventEmitter {
           this.pending.delete(id);
           // Status is in header (byte 1), payload is clean data
           req.resolve({ status: meta, data: payload });
+        } else if (id === 0 && meta === ResponseStatus.ERR) {
+          // Refused at accept (connection limit, address list): the server closes next
+          const errCursor = new Cursor(payload);
+          const code = errCursor.readU16();
+          errCursor.readU8();
+          this.logger.error(`Connection refused by server [${code}] (${errCursor.readString()})`);
         }
         break;
       }
       case FrameType.PUSH_PUBSUB: {
-        if (this.onPush) {
+        if (meta & PushFlag.UNSUBSCRIBED) {
+          const pushCursor = new Cursor(payload);
+          const pattern = pushCursor.readString();
+          this.onUnsubscribed?.(pattern, pushCursor.readString());
+        } else if (this.onPush) {
           const pushCursor = new Cursor(payload);
+          const subscriptionIds: number[] = [];
+          if (meta & PushFlag.SUBSCRIPTION_IDS) {
+            const count = pushCursor.readU16();
+            for (let i = 0; i < count; i++) subscriptionIds.push(pushCursor.readU32());
+          }
           const topic = pushCursor.readString();
           const data = pushCursor.decodeAny();
-          this.onPush(topic, data);
+          this.onPush(topic, data, { retain: (meta & PushFlag.RETAIN) !== 0, subscriptionIds });
         }
         break;
       }
+      case FrameType.PUSH_QUEUE: {
+        const pushCursor = new Cursor(payload);
+        const queue = pushCursor.readString();
+        const unsubscribed = meta & PushFlag.UNSUBSCRIBED ? pushCursor.readString() : undefined;
+        this.queuePushHandlers.get(queue)?.(pushCursor, unsubscribed);
+        break;
+      }
+      case FrameType.CHUNK_BEGIN: {
+        this.partials.set(id, {
+          type: payload.readUInt8(0),
+          meta,
+          total: payload.readUInt32BE(1),
+          parts: [],
+          received: 0,
+          crc: 0,
+        });
+        break;
+      }
+      case FrameType.CHUNK: {
+        const partial = this.partials.get(id);
+        if (!partial) break;
+        const data = payload.subarray(4);
+        if (crc32(data) !== payload.readUInt32BE(0)) {
+          this.partials.delete(id);
+          this.logger.error(`Chunk checksum mismatch in message ${id}`);
+          break;
+        }
+        partial.parts.push(data);
+        partial.received += data.length;
+        partial.crc = crc32(data, partial.crc);
+        break;
+      }
+      case FrameType.CHUNK_END: {
+        const partial = this.partials.get(id);
+        if (!partial) break;
+        this.partials.delete(id);
+        if (partial.received !== partial.total || partial.crc !== payload.readUInt32BE(0)) {
+          this.logger.error(`Chunked message ${id} is incomplete or corrupted`);
+          break;
+        }
+        this.routeFrame(partial.type, partial.meta, id, Buffer.concat(partial.parts, partial.total));
+        break;
+      }
       default:
         this.logger.warn(`Unknown frame type: 0x${type.toString(16).padStart(2, '0')}`);
     }
   }
 
+  /**
+   * Write a request frame, as BEGIN / CHUNK... / END when its payload exceeds
+   * `chunkSize`. Chunks are zero-copy views of the packet.
+   */
+  private writePacket(packet: Buffer, id: number, opcode: number) {
+    const payload = packet.subarray(10);
+    if (payload.length <= this.config.chunkSize) {
+      this.socket.write(packet);
+      return;
+    }
+
+    const header = (type: number, meta: number, len: number) => {
+      const buf = Buffer.allocUnsafe(10);
+      buf.writeUInt8(type, 0);
+      buf.writeUInt8(meta, 1);
+      buf.writeUInt32BE(id, 2);
+      buf.writeUInt32BE(len, 6);
+      return buf;
+    };
+
+    const begin = Buffer.allocUnsafe(15);
+    header(FrameType.CHUNK_BEGIN, opcode, 5).copy(begin, 0);
+    begin.writeUInt8(FrameType.REQUEST, 10);
+    begin.writeUInt32BE(payload.length, 11);
+    this.socket.write(begin);
+
+    let crc = 0;
+    for (let offset = 0; offset < payload.length; offset += this.config.chunkSize) {
+      const data = payload.subarray(offset, offset + this.config.chunkSize);
+      const prefix = header(FrameType.CHUNK, 0, 4 + data.length);
+      const checksum = Buffer.allocUnsafe(4);
+      checksum.writeUInt32BE(crc32(data), 0);
+      crc = crc32(data, crc);
+      this.socket.write(Buffer.concat([prefix, checksum]));
+      this.socket.write(data);
+    }
+
+    const end = Buffer.allocUnsafe(14);
+    header(FrameType.CHUNK_END, 0, 4).copy(end, 0);
+    end.writeUInt32BE(crc, 10);
+    this.socket.write(end);
+  }
+
   send(
     opcode: number,
     build?: (w: FrameWriter) => void,
@@ -229,13 +350,16 @@ export class NexoConnection extends EventEmitter {
       this.pending.set(id, {
         resolve: (res) => {
           if (res.status === ResponseStatus.ERR) {
+            // Error payload: [code: u16][retryable: u8][message: string]
             const errCursor = new Cursor(res.data);
+            const code = errCursor.readU16() as ErrorCode;
+            const retryable = errCursor.readU8() === 1;
             const errMsg = errCursor.readString();
             // Silence common expected errors
-            if (!errMsg.includes('FENCED') && !errMsg.includes('REBALANCE') && !errMsg.includes('NOT_MEMBER') && !errMsg.includes('not found')) {
-              this.logger.error(`<- ERROR 0x${opcode.toString(16).padStart(2, '0')} (${errMsg})`);
+            if (code !== ErrorCode.FENCED && code !== ErrorCode.NOT_MEMBER && code !== ErrorCode.NOT_FOUND) {
+              this.logger.error(`<- ERROR 0x${opcode.toString(16).padStart(2, '0')} [${code}] (${errMsg})`);
             }
-            reject(new Error(errMsg));
+            reject(new NexoServerError(code, errMsg, retryable));
             return;
           }
           resolve({ status: res.status, cursor: new Cursor(res.data) });
@@ -245,7 +369,7 @@ export class NexoConnection extends EventEmitter {
         timeoutMs
       });
 
-      this.socket.write(packet);
+      this.writePacket(packet, id, opcode);
     });
   }
 
@@ -264,7 +388,7 @@ export class NexoConnection extends EventEmitter {
     this.writer.begin();
     if (build) build(this.writer);
     const packet = this.writer.finish(id, opcode);
-    this.socket.write(packet);
+    this.writePacket(packet, id, opcode);
   }
 
   disconnect() {
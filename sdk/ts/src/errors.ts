@@ -25,3 +25,37 @@ export class NotConnectedError extends NexoError {
     this.name = 'NotConnectedError';
   }
 }
+
+/** Numeric error codes carried in server error frames. */
+export enum ErrorCode {
+  INVALID_REQUEST = 0x0001,
+  UNKNOWN_OPCODE = 0x0002,
+  PAYLOAD_TOO_LARGE = 0x0003,
+  SCHEMA_VIOLATION = 0x0004,
+  FORBIDDEN = 0x0005,
+  INVALID_NAME = 0x0006,
+  NOT_FOUND = 0x0100,
+  CONFLICT = 0x0101,
+  FENCED = 0x0200,
+  NOT_MEMBER = 0x0201,
+  NOT_OWNER = 0x0202,
+  NOT_PENDING = 0x0203,
+  REBALANCE = 0x0204,
+  STORAGE = 0x0300,
+  /** Server over its memory budget: retry the publish later */
+  BUSY = 0x0301,
+  /** Connection refused: server (or per-address) connection limit reached */
+  TOO_MANY_CONNECTIONS = 0x0302,
+  INTERNAL = 0x03FF,
+}
+
+export class NexoServerError extends NexoError {
+  constructor(
+    public readonly code: ErrorCode,
+    message: string,
+    public readonly retryable: boolean,
+  ) {
+    super(message);
+    this.name = 'NexoServerError';
+  }
+}
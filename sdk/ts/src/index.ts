@@ -1,6 +1,9 @@
 export { NexoClient, NexoOptions } from './client';
+export { ErrorCode, NexoServerError } from './errors';
 
-export { NexoQueue, QueueConfig, QueueSubscribeOptions, QueuePushOptions } from './brokers/queue';
-export { NexoStream, StreamSubscribeOptions } from './brokers/stream';
-export { NexoTopic, PublishOptions } from './brokers/pubsub';
-export { NexoStore, NexoMap } from './brokers/store';
+export { NexoQueue, QueueConfig, QueueDeadLetterTarget, QueueSubscribeOptions, QueuePushOptions, DlqReplayOptions, ScheduledMessage, messageTimestamp } from './brokers/queue';
+export { NexoStream, NexoStreamSet, StreamSubscribeOptions, StreamPublishOptions } from './brokers/stream';
+export { NexoTopic, PublishOptions, PublishConfirm, SubscribeOptions, MessageInfo } from './brokers/pubsub';
+export { NexoStore, NexoMap, MapVersion, SetAndPublishOptions, NexoLocks, NexoLock, LockOptions, NexoSemaphores, NexoSemaphoreLease, SemaphoreOptions } from './brokers/store';
+export { SchemaVersion } from './brokers/schema';
+export { EncodedPayload, ContentType } from './payload';
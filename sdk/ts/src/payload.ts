@@ -0,0 +1,26 @@
+import { DataType } from './protocol';
+
+export type ContentType = 'msgpack' | 'protobuf';
+
+/**
+ * Bytes the application already serialized, tagged with their format. Brokers
+ * store them untouched; the dashboard renders MessagePack as JSON and payload
+ * schemas validate it. Received payloads of these types come back as an
+ * `EncodedPayload`, so the format survives the round trip.
+ */
+export class EncodedPayload {
+  constructor(readonly contentType: ContentType, readonly data: Buffer) { }
+
+  static msgpack(data: Buffer): EncodedPayload {
+    return new EncodedPayload('msgpack', data);
+  }
+
+  static protobuf(data: Buffer): EncodedPayload {
+    return new EncodedPayload('protobuf', data);
+  }
+
+  /** @internal */
+  get dataType(): DataType {
+    return this.contentType === 'msgpack' ? DataType.MSGPACK : DataType.PROTOBUF;
+  }
+}
@@ -3,6 +3,12 @@ export enum FrameType {
   REQUEST = 0x01,
   RESPONSE = 0x02,
   PUSH_PUBSUB = 0x03,
+  // Chunked message: BEGIN [type u8][totalLen u32], CHUNK [crc32 u32][data], END [crc32 u32]
+  CHUNK_BEGIN = 0x04,
+  CHUNK = 0x05,
+  CHUNK_END = 0x06,
+  // Queue push subscription delivery: [queue string][count u32]{[id 16][len u32][payload]}
+  PUSH_QUEUE = 0x07,
 }
 
 /** @internal */
@@ -13,9 +19,23 @@ export enum ResponseStatus {
   DATA = 0x03,
 }
 
+/** @internal */
+export enum PushFlag {
+  RETAIN = 0x01,
+  // Payload starts with [count u16][subscriptionId u32]...
+  SUBSCRIPTION_IDS = 0x02,
+  // Not a message: the server dropped a subscription. Payload [pattern or queue][reason]
+  UNSUBSCRIBED = 0x04,
+}
+
 /** @internal */
 export enum DataType {
   RAW = 0x00,
   STRING = 0x01,
   JSON = 0x02,
+  // Queue payload offloaded to the server's blob store: [blobId: 16][size: u64]
+  BLOB_REF = 0x03,
+  // Application-serialized bytes (see `EncodedPayload`)
+  MSGPACK = 0x04,
+  PROTOBUF = 0x05,
 }
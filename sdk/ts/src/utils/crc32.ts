@@ -0,0 +1,21 @@
+const TABLE = (() => {
+  const table = new Uint32Array(256);
+  for (let n = 0; n < 256; n++) {
+    let c = n;
+    for (let k = 0; k < 8; k++) c = c & 1 ? 0xEDB88320 ^ (c >>> 1) : c >>> 1;
+    table[n] = c >>> 0;
+  }
+  return table;
+})();
+
+/**
+ * CRC32 (IEEE), as used by chunked frames. Pass the previous result as `crc`
+ * to checksum a message one chunk at a time.
+ *
+ * @internal
+ */
+export function crc32(buf: Buffer, crc = 0): number {
+  let c = (crc ^ 0xFFFFFFFF) >>> 0;
+  for (let i = 0; i < buf.length; i++) c = TABLE[(c ^ buf[i]) & 0xFF] ^ (c >>> 8);
+  return (c ^ 0xFFFFFFFF) >>> 0;
+}
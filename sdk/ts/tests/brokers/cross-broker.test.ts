@@ -1,6 +1,7 @@
 import { describe, it, expect } from 'vitest';
 import { nexo } from '../nexo';
 import { waitFor } from '../utils/wait-for';
+import { EncodedPayload } from '../../src/payload';
 import { randomUUID } from 'crypto';
 
 describe('CROSS-BROKER FEATURES', () => {
@@ -58,6 +59,27 @@ describe('CROSS-BROKER FEATURES', () => {
             expect(received[0].equals(binaryPayload)).toBe(true);
             sub.stop();
         });
+
+        it('Should keep the content type of encoded payloads', async () => {
+            // {"a": 1} as MessagePack
+            const msgpack = EncodedPayload.msgpack(Buffer.from([0x81, 0xA1, 0x61, 0x01]));
+            const key = `msgpack-store-${randomUUID()}`;
+            await nexo.store.map.set(key, msgpack);
+
+            const retrieved = await nexo.store.map.get(key);
+            expect(retrieved).toBeInstanceOf(EncodedPayload);
+            expect(retrieved.contentType).toBe('msgpack');
+            expect(retrieved.data.equals(msgpack.data)).toBe(true);
+
+            const topic = `protobuf-pubsub-${randomUUID()}`;
+            const received: any[] = [];
+            await nexo.pubsub(topic).subscribe(msg => received.push(msg));
+            await nexo.pubsub(topic).publish(EncodedPayload.protobuf(binaryPayload));
+
+            await waitFor(() => expect(received.length).toBe(1));
+            expect(received[0].contentType).toBe('protobuf');
+            expect(received[0].data.equals(binaryPayload)).toBe(true);
+        });
     });
 
     describe('SYSTEM & PROTOCOL', () => {
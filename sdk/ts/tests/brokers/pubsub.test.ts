@@ -76,4 +76,52 @@ describe('PUBSUB', () => {
 
         await nexo.pubsub(pattern).unsubscribe();
     });
+
+    it('should reject filters with misplaced wildcards', async () => {
+        const base = `bad-${randomUUID()}`;
+        await expect(nexo.pubsub(`${base}/#/tail`).subscribe(() => {})).rejects.toThrow(/'#' must be the whole last level/);
+        await expect(nexo.pubsub(`${base}/sp+rts`).subscribe(() => {})).rejects.toThrow(/'\+' must be a whole level/);
+
+        // The failed subscription left nothing behind: the same filter can be retried once fixed
+        await nexo.pubsub(`${base}/#`).subscribe(() => {});
+        await nexo.pubsub(`${base}/#`).unsubscribe();
+    });
+
+    it('should confirm publishes once subscribers have the message', async () => {
+        const topic = `orders-${randomUUID()}/created`;
+        const received: any[] = [];
+        await nexo.pubsub(topic).subscribe((data) => received.push(data));
+
+        const first = await nexo.pubsub(topic).publish({ n: 1 }, { confirm: true });
+        const second = await nexo.pubsub(topic).publish({ n: 2 }, { confirm: true });
+        expect(first?.delivered).toBe(1);
+        expect(second!.publishId).toBeGreaterThan(first!.publishId);
+        expect(await nexo.pubsub(topic).publish({ n: 3 })).toBeUndefined();
+
+        await waitFor(() => expect(received.length).toBe(3));
+        await nexo.pubsub(topic).unsubscribe();
+    });
+
+    it('should apply per-subscription options', async () => {
+        const baseId = randomUUID();
+        const retained = `cfg-${baseId}/theme`;
+        await nexo.pubsub(retained).publish('dark', { retain: true });
+
+        // Replayed retained messages are flagged
+        const infos: any[] = [];
+        await nexo.pubsub(`cfg-${baseId}/+`).subscribe((data, info) => infos.push({ data, ...info }));
+        await waitFor(() => expect(infos).toEqual([{ data: 'dark', topic: retained, retain: true }]));
+
+        // noLocal: this client's own publishes are skipped, and retainHandling 2 skips the replay
+        const local: any[] = [];
+        await nexo.pubsub(`cfg-${baseId}/#`).subscribe((data) => local.push(data), { noLocal: true, retainHandling: 2 });
+        await nexo.pubsub(retained).publish('light');
+
+        await waitFor(() => expect(infos.length).toBe(2));
+        expect(infos[1]).toEqual({ data: 'light', topic: retained, retain: false });
+        expect(local).toEqual([]);
+
+        await nexo.pubsub(`cfg-${baseId}/+`).unsubscribe();
+        await nexo.pubsub(`cfg-${baseId}/#`).unsubscribe();
+    });
 });
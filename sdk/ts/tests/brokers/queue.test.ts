@@ -21,6 +21,67 @@ describe('QUEUE', () => {
         sub.stop();
     });
 
+    it('should deliver by server push within the prefetch window', async () => {
+        const qName = `queue-push-${randomUUID()}`;
+        const q = await nexo.queue(qName).create();
+        for (let i = 0; i < 6; i++) await q.push(i);
+
+        const received: number[] = [];
+        let inFlight = 0;
+        let maxInFlight = 0;
+        const sub = await q.subscribe(async (data: number) => {
+            inFlight++;
+            maxInFlight = Math.max(maxInFlight, inFlight);
+            await new Promise(r => setTimeout(r, 20));
+            received.push(data);
+            inFlight--;
+        }, { push: true, prefetch: 2 });
+
+        await waitFor(() => expect(received.length).toBe(6));
+        expect(maxInFlight).toBeLessThanOrEqual(2);
+
+        // Later pushes arrive without polling
+        await q.push(99);
+        await waitFor(() => expect(received).toContain(99));
+        await sub.stop();
+    });
+
+    it('should deliver a message group in order, one at a time', async () => {
+        const qName = `queue-groups-${randomUUID()}`;
+        const q = await nexo.queue(qName).create();
+        for (let i = 0; i < 5; i++) await q.push({ seq: i }, { messageGroup: 'account-1' });
+
+        const received: number[] = [];
+        let inFlight = 0;
+        let maxInFlight = 0;
+        const sub = await q.subscribe(async (msg: { seq: number }) => {
+            inFlight++;
+            maxInFlight = Math.max(maxInFlight, inFlight);
+            await new Promise(r => setTimeout(r, 10));
+            received.push(msg.seq);
+            inFlight--;
+        }, { concurrency: 5, waitMs: 200 });
+
+        await waitFor(() => expect(received).toEqual([0, 1, 2, 3, 4]));
+        expect(maxInFlight).toBe(1);
+        await sub.stop();
+    });
+
+    it('should drop pushes repeating an idempotency key', async () => {
+        const qName = `queue-dedup-${randomUUID()}`;
+        const q = await nexo.queue(qName).create();
+        await q.push({ order: 1 }, { idempotencyKey: 'evt-1' });
+        await q.push({ order: 1 }, { idempotencyKey: 'evt-1' });
+        await q.push({ order: 2 }, { idempotencyKey: 'evt-2' });
+
+        const received: number[] = [];
+        const sub = await q.subscribe(async (msg: { order: number }) => { received.push(msg.order); }, { waitMs: 200 });
+        await waitFor(() => expect(received.length).toBe(2));
+        await new Promise(r => setTimeout(r, 300));
+        expect(received.sort()).toEqual([1, 2]);
+        await sub.stop();
+    });
+
     it('should move failed messages to DLQ', async () => {
         const qName = `queue-dlq-${randomUUID()}`;
         // Max 1 retry (2 attempts total)
@@ -43,6 +104,33 @@ describe('QUEUE', () => {
         expect(dlqResult.items[0].data).toBe('fail_payload');
     });
 
+    it('should send failed messages to a custom dead letter queue', async () => {
+        const qName = `queue-dl-target-${randomUUID()}`;
+        const target = `${qName}-failed`;
+        const q = await nexo.queue(qName).create({
+            maxRetries: 1,
+            deadLetter: { type: 'queue', name: target },
+        });
+
+        await q.push({ order: 42 });
+        const sub = await q.subscribe(async () => {
+            throw new Error('card declined');
+        });
+
+        const letters: any[] = [];
+        const failed = await nexo.queue<any>(target).create();
+        const dlSub = await failed.subscribe(async (letter) => {
+            letters.push(letter);
+        });
+
+        await waitFor(() => expect(letters.length).toBe(1));
+        sub.stop();
+        dlSub.stop();
+
+        expect(letters[0]).toMatchObject({ queue: qName, reason: 'card declined', payload: { order: 42 } });
+        expect((await q.dlq.peek(10)).total).toBe(0);
+    });
+
     it('should respect priority (High before Low)', async () => {
         const qName = `queue-prio-${randomUUID()}`;
         const q = await nexo.queue(qName).create();
@@ -63,6 +151,26 @@ describe('QUEUE', () => {
         expect(received).toEqual(['high', 'low']);
     });
 
+    it('should purge a single priority level', async () => {
+        const qName = `queue-prio-purge-${randomUUID()}`;
+        const q = await nexo.queue(qName).create();
+
+        await q.push('bulk-1', { priority: 0 });
+        await q.push('bulk-2', { priority: 0 });
+        await q.push('urgent', { priority: 10 });
+
+        expect(await q.purgePriority(0)).toBe(2);
+        expect(await q.purgePriority(0)).toBe(0);
+
+        const received: string[] = [];
+        const sub = await q.subscribe(async (msg) => {
+            received.push(msg);
+        });
+
+        await waitFor(() => expect(received).toEqual(['urgent']));
+        sub.stop();
+    });
+
     it('Should handle DLQ workflow: peek, moveToQueue, delete, purge', async () => {
         const qName = `dlq-test-${randomUUID()}`;
 
@@ -229,4 +337,12 @@ describe('QUEUE', () => {
         expect(dlqResult.total).toBe(1);
         expect(dlqResult.items[0].failureReason).toBe("Specific Failure Reason");
     });
+
+    it('should reject invalid queue names', async () => {
+        await expect(nexo.queue('../escape').create()).rejects.toThrow(/Invalid queue name/);
+
+        const qName = `names-${randomUUID()}`;
+        await nexo.queue(qName).create();
+        await expect(nexo.queue(qName.toUpperCase()).create()).rejects.toThrow(/only in case/);
+    });
 });
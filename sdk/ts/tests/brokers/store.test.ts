@@ -1,6 +1,7 @@
 import { describe, it, expect } from 'vitest';
 import { nexo } from '../nexo';
 import { randomUUID } from 'crypto';
+import { waitFor } from '../utils/wait-for';
 
 describe('STORE (KV)', () => {
     it('should perform basic CRUD operations', async () => {
@@ -35,4 +36,71 @@ describe('STORE (KV)', () => {
         // Should be gone
         expect(await nexo.store.map.get(key)).toBeNull();
     });
+
+    it('should inspect and change a key\'s TTL without rewriting it', async () => {
+        const key = `expire:${randomUUID()}`;
+        expect(await nexo.store.map.ttl(key)).toBeUndefined();
+        expect(await nexo.store.map.expire(key, 1000)).toBe(false);
+
+        await nexo.store.map.set(key, 'v', { ttl: 60 });
+        const ttl = await nexo.store.map.ttl(key);
+        expect(ttl).toBeGreaterThan(59_000);
+        expect(ttl).toBeLessThanOrEqual(60_000);
+
+        expect(await nexo.store.map.persist(key)).toBe(true);
+        expect(await nexo.store.map.ttl(key)).toBeNull();
+
+        const forever = `forever:${randomUUID()}`;
+        await nexo.store.map.set(forever, 'v', { keepForever: true });
+        expect(await nexo.store.map.ttl(forever)).toBeNull();
+        await nexo.store.map.del(forever);
+
+        expect(await nexo.store.map.expire(key, 200)).toBe(true);
+        expect(await nexo.store.map.get(key)).toBe('v');
+        await new Promise(r => setTimeout(r, 400));
+        expect(await nexo.store.map.get(key)).toBeNull();
+    });
+
+    it('should get, set and delete many keys in one request', async () => {
+        const [a, b, missing] = [`bulk:${randomUUID()}`, `bulk:${randomUUID()}`, `bulk:${randomUUID()}`];
+
+        await nexo.store.map.mset({ [a]: { n: 1 }, [b]: 'two' });
+        expect(await nexo.store.map.mget([a, missing, b])).toEqual([{ n: 1 }, null, 'two']);
+
+        expect(await nexo.store.map.mdel([a, missing, b])).toEqual([true, false, true]);
+        expect(await nexo.store.map.mget([a, b])).toEqual([null, null]);
+    });
+
+    it('should round-trip values larger than one chunk', async () => {
+        const key = `large:${randomUUID()}`;
+        // 3MB: sent and returned as chunked frames (chunk size 1MB)
+        const value = Buffer.alloc(3 * 1024 * 1024 + 17);
+        for (let i = 0; i < value.length; i++) value[i] = i % 251;
+
+        await nexo.store.map.set(key, value);
+        const result = await nexo.store.map.get<Buffer>(key);
+        expect(Buffer.isBuffer(result)).toBe(true);
+        expect(result!.equals(value)).toBe(true);
+
+        await nexo.store.map.del(key);
+    });
+
+    it('should set a key and publish its change event together', async () => {
+        const key = `order:${randomUUID()}`;
+        const topic = `orders-${randomUUID()}`;
+        const stream = await nexo.stream(topic).create();
+
+        await nexo.store.map.setAndPublish(key, { status: 'paid' }, { stream: topic, event: { key, status: 'paid' } });
+        expect(await nexo.store.map.get(key)).toEqual({ status: 'paid' });
+
+        const events: any[] = [];
+        const sub = await stream.subscribe('g1', msg => { events.push(msg); });
+        await waitFor(() => expect(events).toEqual([{ key, status: 'paid' }]));
+        sub.stop();
+
+        // The stream must exist: nothing is written otherwise
+        const other = `order:${randomUUID()}`;
+        await expect(nexo.store.map.setAndPublish(other, 'x', { stream: `missing-${randomUUID()}` })).rejects.toThrow();
+        expect(await nexo.store.map.get(other)).toBeNull();
+    });
 });
@@ -1,5 +1,6 @@
 import { describe, it, expect, beforeAll, afterAll } from 'vitest';
 import { NexoClient } from '../../src/client';
+import { ErrorCode } from '../../src/errors';
 import { nexo } from '../nexo';
 import { waitFor } from '../utils/wait-for';
 import { randomUUID } from 'crypto';
@@ -44,6 +45,19 @@ describe('STREAM', () => {
         ).rejects.toThrow();
     });
 
+    it('should reject a create conflicting with the topic settings, and alter them', async () => {
+        const topic = `stream-alter-${randomUUID()}`;
+        await nexo.stream(topic).create({ deadLetter: true });
+        await nexo.stream(topic).create(); // Nothing stated, nothing to contradict
+
+        await expect(nexo.stream(topic).create({ deadLetter: false }))
+            .rejects.toMatchObject({ code: ErrorCode.CONFLICT });
+
+        await nexo.stream(topic).alter({ deadLetter: false });
+        await nexo.stream(topic).create({ deadLetter: false });
+        await expect(nexo.stream(topic).alter({ priorityLanes: true } as any)).rejects.toThrow(/priorityLanes/);
+    });
+
     it('Independent CONSUMER GROUPS => should deliver all messages to each group', async () => {
         const topic = `stream-groups-${randomUUID()}`;
         await nexo.stream(topic).create();
@@ -272,4 +286,53 @@ describe('STREAM', () => {
         
         await subStart.stop();
     });
+
+    it('should consume several topics with one group (names and patterns)', async () => {
+        const prefix = `stream-multi-${randomUUID()}`;
+        const group = 'multi-group';
+        await nexo.stream(`${prefix}.created`).create();
+        await nexo.stream(`${prefix}.paid`).create();
+        await nexo.stream(`${prefix}-billing`).create();
+
+        const received: { topic: string; data: any }[] = [];
+        const sub = await clientA
+            .streams([`${prefix}.*`, `${prefix}-billing`])
+            .subscribe(group, (data, topic) => received.push({ topic, data }));
+
+        await nexo.stream(`${prefix}.created`).publish({ id: 1 });
+        await nexo.stream(`${prefix}.paid`).publish({ id: 1 });
+        await nexo.stream(`${prefix}-billing`).publish({ id: 1 });
+
+        await waitFor(() => expect(received.length).toBe(3));
+        expect(received.map(r => r.topic).sort()).toEqual([`${prefix}-billing`, `${prefix}.created`, `${prefix}.paid`]);
+        await sub.stop();
+
+        // Acked per topic: a new subscription of the same group gets nothing again
+        const again: any[] = [];
+        const sub2 = await clientA.streams([`${prefix}.*`]).subscribe(group, (data) => again.push(data), { waitMs: 200 });
+        await new Promise(r => setTimeout(r, 500));
+        expect(again).toEqual([]);
+        await sub2.stop();
+    });
+
+    it('should pick up topics created after a pattern subscription', async () => {
+        const prefix = `stream-pattern-${randomUUID()}`;
+        await nexo.stream(`${prefix}-a`).create();
+
+        const received: { topic: string; data: any }[] = [];
+        const sub = await clientA.streams([`${prefix}-*`]).subscribe('pattern-group', (data, topic) => received.push({ topic, data }));
+
+        await nexo.stream(`${prefix}-b`).create();
+        await nexo.stream(`${prefix}-b`).publish({ id: 'b1' });
+        await nexo.stream(`${prefix}-a`).publish({ id: 'a1' });
+
+        await waitFor(() => expect(received.map(r => r.topic).sort()).toEqual([`${prefix}-a`, `${prefix}-b`]));
+        await sub.stop();
+    });
+
+    it('should fail a multi-topic subscribe when nothing matches', async () => {
+        await expect(
+            clientA.streams([`stream-none-${randomUUID()}.*`]).subscribe('g', () => {})
+        ).rejects.toThrow();
+    });
 });
@@ -0,0 +1,249 @@
+//! Access statistics: when each queue, stream topic and pub/sub root (first
+//! topic segment) was last published to and consumed from.
+//!
+//! Each broker keeps an `ActivityTracker`, saved to `activity.json` in its
+//! data directory so idle ages survive restarts. A resource first seen by the
+//! tracker counts as active at that moment (`tracked_since`).
+//!
+//! The idle policy (`IDLE_*`) reaps resources untouched for `IDLE_AFTER_DAYS`:
+//! queues and topics are archived (moved under `IDLE_ARCHIVE_PATH`) or
+//! deleted; pub/sub roots lose their retained messages. Names matching
+//! `IDLE_EXCLUDE` are reported but never reaped.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::brokers::clock::SharedClock;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Activity {
+    /// ms since epoch, 0 = never
+    pub last_publish: u64,
+    /// ms since epoch, 0 = never
+    pub last_consume: u64,
+    pub tracked_since: u64,
+}
+
+impl Activity {
+    pub fn last_active(&self) -> u64 {
+        self.last_publish.max(self.last_consume).max(self.tracked_since)
+    }
+}
+
+pub struct ActivityTracker {
+    entries: DashMap<String, Activity>,
+    /// `activity.json`
+    path: PathBuf,
+    dirty: AtomicBool,
+    clock: SharedClock,
+}
+
+impl ActivityTracker {
+    /// Tracker saved at `path`, starting from what was saved there.
+    pub fn load(path: PathBuf, clock: SharedClock) -> Self {
+        let entries: std::collections::HashMap<String, Activity> = std::fs::read(&path).ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            entries: entries.into_iter().collect(),
+            path,
+            dirty: AtomicBool::new(false),
+            clock,
+        }
+    }
+
+    /// Start tracking `name` (created or restored) unless it already is.
+    pub fn track(&self, name: &str) {
+        if let dashmap::Entry::Vacant(entry) = self.entries.entry(name.to_string()) {
+            entry.insert(Activity { tracked_since: self.clock.now_ms(), ..Default::default() });
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn published(&self, name: &str) {
+        let now = self.clock.now_ms();
+        self.touch(name, |a| a.last_publish = now);
+    }
+
+    pub fn consumed(&self, name: &str) {
+        let now = self.clock.now_ms();
+        self.touch(name, |a| a.last_consume = now);
+    }
+
+    fn touch(&self, name: &str, update: impl FnOnce(&mut Activity)) {
+        match self.entries.get_mut(name) {
+            Some(mut activity) => update(&mut activity),
+            None => {
+                let mut activity = Activity { tracked_since: self.clock.now_ms(), ..Default::default() };
+                update(&mut activity);
+                self.entries.insert(name.to_string(), activity);
+            }
+        }
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    pub fn forget(&self, name: &str) {
+        if self.entries.remove(name).is_some() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Activity> {
+        self.entries.get(name).map(|a| *a)
+    }
+
+    /// Entries untouched for at least `min_idle_ms`, with their idle time.
+    pub fn idle_since(&self, min_idle_ms: u64) -> Vec<(String, Activity, u64)> {
+        let now = self.clock.now_ms();
+        self.snapshot().into_iter()
+            .filter_map(|(name, activity)| {
+                let idle_ms = now.saturating_sub(activity.last_active());
+                (idle_ms >= min_idle_ms).then_some((name, activity, idle_ms))
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, Activity)> {
+        let mut entries: Vec<(String, Activity)> = self.entries.iter().map(|e| (e.key().clone(), *e.value())).collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Write the tracker to disk if it changed since the last save.
+    pub fn save(&self) {
+        let path = &self.path;
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let entries: std::collections::BTreeMap<String, Activity> = self.snapshot().into_iter().collect();
+        let Ok(data) = serde_json::to_vec(&entries) else { return };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let tmp = path.with_extension("json.tmp");
+        if let Err(e) = std::fs::write(&tmp, data).and_then(|_| std::fs::rename(&tmp, path)) {
+            tracing::warn!("Failed to save activity stats to {:?}: {}", path, e);
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IdleAction {
+    /// Only list idle resources in the admin report
+    #[default]
+    Report,
+    Archive,
+    Delete,
+}
+
+impl std::str::FromStr for IdleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(Self::Report),
+            "archive" => Ok(Self::Archive),
+            "delete" => Ok(Self::Delete),
+            other => Err(format!("unknown idle action '{}'", other)),
+        }
+    }
+}
+
+/// One `IDLE_EXCLUDE` entry: `[broker:]name`, where a trailing `*` matches
+/// any suffix (`stream:audit-*`, `orders`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludeRule {
+    pub broker: Option<String>,
+    pub pattern: String,
+}
+
+impl ExcludeRule {
+    pub fn parse_list(value: &str) -> Vec<ExcludeRule> {
+        value.split(',').map(str::trim).filter(|e| !e.is_empty())
+            .map(|entry| match entry.split_once(':') {
+                Some((broker, pattern)) if matches!(broker, "queue" | "stream" | "pubsub") => {
+                    ExcludeRule { broker: Some(broker.to_string()), pattern: pattern.to_string() }
+                }
+                _ => ExcludeRule { broker: None, pattern: entry.to_string() },
+            })
+            .collect()
+    }
+
+    pub fn matches(&self, broker: &str, name: &str) -> bool {
+        if self.broker.as_deref().is_some_and(|b| b != broker) {
+            return false;
+        }
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == self.pattern,
+        }
+    }
+}
+
+/// A resource past the idle threshold, as shown in the admin report.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleResource {
+    pub broker: &'static str,
+    pub name: String,
+    pub last_publish: u64,
+    pub last_consume: u64,
+    /// ms since the last publish, consume or start of tracking
+    pub idle_ms: u64,
+    /// Matched by `IDLE_EXCLUDE`: never reaped
+    pub excluded: bool,
+}
+
+/// Move a file or directory to `target` (its parent is created), copying
+/// when `target` is on another filesystem.
+pub fn move_to_archive(source: &Path, target: &Path) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+    copy_recursive(source, target)?;
+    if source.is_dir() { std::fs::remove_dir_all(source) } else { std::fs::remove_file(source) }
+}
+
+fn copy_recursive(source: &Path, target: &Path) -> std::io::Result<()> {
+    if !source.is_dir() {
+        return std::fs::copy(source, target).map(|_| ());
+    }
+    std::fs::create_dir_all(target)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclude_rules_match_broker_and_prefix() {
+        let rules = ExcludeRule::parse_list("stream:audit-*, billing,,pubsub:sensors");
+        assert_eq!(rules.len(), 3);
+        let excluded = |broker: &str, name: &str| rules.iter().any(|r| r.matches(broker, name));
+
+        assert!(excluded("stream", "audit-2026"));
+        assert!(!excluded("queue", "audit-2026"), "Prefix rule is scoped to streams");
+        assert!(excluded("queue", "billing") && excluded("stream", "billing"));
+        assert!(!excluded("queue", "billing-eu"), "No `*`: exact name only");
+        assert!(excluded("pubsub", "sensors"));
+    }
+
+    #[test]
+    fn unknown_idle_action_is_rejected() {
+        assert_eq!("archive".parse::<IdleAction>(), Ok(IdleAction::Archive));
+        assert!("purge".parse::<IdleAction>().is_err());
+    }
+}
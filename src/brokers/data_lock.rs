@@ -0,0 +1,211 @@
+//! Exclusive ownership of the persistence roots by one Nexo process.
+//!
+//! Each root gets a `nexo.lock` file holding an OS lock (`flock` on Unix,
+//! `LockFileEx` on Windows) for the life of the process, and a `nexo.pid`
+//! file naming the owner. The OS drops the lock when its holder dies, so a
+//! crash never leaves a root locked on a local disk. `takeover` is for the
+//! rest (network filesystems, an owner gone from another PID namespace): it
+//! replaces a held lock only if the recorded owner isn't running on this host.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::brokers::integrity::DataDirs;
+
+const LOCK_FILE: &str = "nexo.lock";
+const OWNER_FILE: &str = "nexo.pid";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// Unix ms
+    pub started_at: u64,
+}
+
+/// Locks on the persistence roots, released when dropped (or when the process exits).
+pub struct DataLock {
+    roots: Vec<(PathBuf, File)>,
+}
+
+impl DataLock {
+    /// Lock every root of `dirs`. Fails, naming the owner, if another process holds one.
+    pub fn acquire(dirs: &DataDirs, takeover: bool) -> Result<Self, String> {
+        let mut roots: Vec<&Path> = vec![&dirs.queue, &dirs.stream, &dirs.pubsub];
+        roots.sort();
+        roots.dedup();
+
+        let mut lock = DataLock { roots: Vec::new() };
+        for root in roots {
+            let file = lock_root(root, takeover)?;
+            lock.roots.push((root.to_path_buf(), file));
+        }
+        Ok(lock)
+    }
+
+    /// Unlock the roots and remove their owner files.
+    pub fn release(self) {
+        drop(self);
+    }
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        for (root, _file) in &self.roots {
+            let _ = std::fs::remove_file(root.join(OWNER_FILE));
+        }
+    }
+}
+
+/// The owner recorded in `root`, if any.
+pub fn read_owner(root: &Path) -> Option<LockOwner> {
+    let data = std::fs::read(root.join(OWNER_FILE)).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+fn lock_root(root: &Path, takeover: bool) -> Result<File, String> {
+    std::fs::create_dir_all(root).map_err(|e| format!("Cannot create data directory {}: {}", root.display(), e))?;
+    let lock_path = root.join(LOCK_FILE);
+    let file = open_lock_file(&lock_path)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let owner = read_owner(root);
+            if !takeover {
+                return Err(match owner {
+                    Some(owner) => format!(
+                        "Data directory {} is in use by Nexo process {} (started at {}). Stop it, or start with --force-takeover if it no longer runs",
+                        root.display(), owner.pid, owner.started_at,
+                    ),
+                    None => format!("Data directory {} is locked by another process", root.display()),
+                });
+            }
+            let file = take_over(root, &lock_path, owner)?;
+            write_owner(root)?;
+            return Ok(file);
+        }
+        Err(TryLockError::Error(e)) => return Err(format!("Cannot lock {}: {}", lock_path.display(), e)),
+    }
+
+    if let Some(previous) = read_owner(root) {
+        info!("Data directory {:?}: previous owner (pid {}) did not shut down cleanly", root, previous.pid);
+    }
+    write_owner(root)?;
+    Ok(file)
+}
+
+/// Replace a held lock whose recorded owner is gone. The holder (if any)
+/// keeps a lock on an unlinked file and no longer excludes anyone.
+fn take_over(root: &Path, lock_path: &Path, owner: Option<LockOwner>) -> Result<File, String> {
+    match owner {
+        Some(owner) if process_alive(owner.pid) => {
+            return Err(format!(
+                "Data directory {} is in use by Nexo process {}, which is still running: refusing to take over",
+                root.display(), owner.pid,
+            ));
+        }
+        Some(owner) => warn!("Taking over data directory {:?} from pid {}, which no longer runs", root, owner.pid),
+        None => warn!("Taking over data directory {:?} from an unknown owner", root),
+    }
+
+    std::fs::remove_file(lock_path).map_err(|e| format!("Cannot remove stale lock {}: {}", lock_path.display(), e))?;
+    let file = open_lock_file(lock_path)?;
+    file.try_lock().map_err(|_| format!("Data directory {} was locked again during the takeover", root.display()))?;
+    Ok(file)
+}
+
+fn open_lock_file(path: &Path) -> Result<File, String> {
+    OpenOptions::new().create(true).truncate(false).write(true).open(path)
+        .map_err(|e| format!("Cannot open {}: {}", path.display(), e))
+}
+
+fn write_owner(root: &Path) -> Result<(), String> {
+    let owner = LockOwner {
+        pid: std::process::id(),
+        started_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+    };
+    let data = serde_json::to_vec(&owner).unwrap_or_default();
+    std::fs::write(root.join(OWNER_FILE), data).map_err(|e| format!("Cannot write {}: {}", root.join(OWNER_FILE).display(), e))
+}
+
+/// Whether `pid` runs on this host. Our own pid means a recycled one (an
+/// owner from another PID namespace, e.g. another container): not alive.
+pub(crate) fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else { return false };
+        // Signal 0 only checks the process exists; EPERM means it does, under another user
+        // SAFETY: kill with signal 0 sends nothing
+        unsafe { libc::kill(pid, 0) == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) }
+    }
+
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains(&format!("\"{}\"", pid)))
+            // Can't tell: assume it runs rather than risk two owners
+            .unwrap_or(true)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(root: &Path) -> DataDirs {
+        DataDirs { queue: root.join("queues"), stream: root.join("streams"), pubsub: root.join("pubsub") }
+    }
+
+    #[test]
+    fn second_owner_is_refused_with_the_owner_pid() {
+        let tmp = tempfile::tempdir().unwrap();
+        let lock = DataLock::acquire(&dirs(tmp.path()), false).unwrap();
+        assert_eq!(read_owner(&tmp.path().join("streams")).map(|o| o.pid), Some(std::process::id()));
+
+        let err = DataLock::acquire(&dirs(tmp.path()), false).err().unwrap();
+        assert!(err.contains(&std::process::id().to_string()), "{}", err);
+
+        lock.release();
+        assert!(read_owner(&tmp.path().join("streams")).is_none());
+        assert!(DataLock::acquire(&dirs(tmp.path()), false).is_ok(), "Released roots can be locked again");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn takeover_requires_a_gone_owner() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dirs = dirs(tmp.path());
+        let _held = DataLock::acquire(&dirs, false).unwrap();
+        let record = |owner: LockOwner| {
+            for root in [&dirs.queue, &dirs.stream, &dirs.pubsub] {
+                std::fs::write(root.join(OWNER_FILE), serde_json::to_vec(&owner).unwrap()).unwrap();
+            }
+        };
+
+        // A live owner (the test runner's parent) is never replaced
+        record(LockOwner { pid: std::os::unix::process::parent_id(), started_at: 0 });
+        let err = DataLock::acquire(&dirs, true).err().unwrap();
+        assert!(err.contains("still running"), "{}", err);
+
+        // One that exited is
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        record(LockOwner { pid: child.id(), started_at: 0 });
+        child.wait().unwrap();
+        let lock = DataLock::acquire(&dirs, true).unwrap();
+        assert_eq!(read_owner(&dirs.queue).map(|o| o.pid), Some(std::process::id()));
+        drop(lock);
+    }
+}
@@ -0,0 +1,73 @@
+//! Dead letter envelope: a message that ran out of deliveries, written to
+//! another queue or stream topic (a queue's dead letter target, a stream
+//! group's DLQ topic) along with where and why it failed.
+//!
+//! The payload is carried as published, data type byte included, in base64:
+//! `DeadLetterEnvelope::decode` gives a consumer of the letter the exact
+//! bytes back, whatever their content type.
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::brokers::payload::base64_payload;
+use crate::transport::tcp::protocol::DATA_TYPE_JSON;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetterEnvelope {
+    /// Queue or topic the message failed on
+    pub source: String,
+    /// Consumer group that gave up on it (stream groups)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Message id (queues) or sequence (streams)
+    pub id: String,
+    #[serde(default)]
+    pub priority: u8,
+    pub attempts: u32,
+    pub reason: String,
+    /// ms since epoch
+    pub created_at: u64,
+    pub failed_at: u64,
+    #[serde(with = "base64_payload")]
+    pub payload: Bytes,
+}
+
+impl DeadLetterEnvelope {
+    /// The letter as a `DATA_TYPE_JSON` payload.
+    pub fn encode(&self) -> Bytes {
+        let mut out = vec![DATA_TYPE_JSON];
+        // Plain fields: serializing cannot fail
+        out.extend(serde_json::to_vec(self).unwrap_or_default());
+        Bytes::from(out)
+    }
+
+    /// A letter written by `encode`; `None` for any other payload.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        serde_json::from_slice(payload.strip_prefix(&[DATA_TYPE_JSON])?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_round_trips_byte_for_byte() {
+        let letter = DeadLetterEnvelope {
+            source: "orders".to_string(),
+            group: Some("billing".to_string()),
+            id: "7".to_string(),
+            priority: 0,
+            attempts: 3,
+            reason: "timeout".to_string(),
+            created_at: 1,
+            failed_at: 2,
+            payload: Bytes::from_static(b"\x00\xff\xfe not utf-8"),
+        };
+        let encoded = letter.encode();
+        assert_eq!(encoded[0], DATA_TYPE_JSON);
+        assert_eq!(DeadLetterEnvelope::decode(&encoded), Some(letter));
+        assert_eq!(DeadLetterEnvelope::decode(b"\x02{}"), None);
+    }
+}
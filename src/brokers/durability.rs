@@ -0,0 +1,31 @@
+//! Persistence modes shared by the queue and stream brokers.
+//!
+//! `FileAsync` acks a write once it is in memory; the periodic flush puts it
+//! on disk. `FileSync` acks only after an fsync (group commit): sync writes
+//! queued while a commit runs, or arriving within the broker's window
+//! (`QUEUE_GROUP_COMMIT_MS`, `STREAM_GROUP_COMMIT_MS`), share the next fsync
+//! and are acked together.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PersistenceMode {
+    /// Ack from memory, written by the periodic flush
+    #[default]
+    FileAsync,
+    /// Ack after the write is fsynced (group commit)
+    FileSync,
+}
+
+impl std::str::FromStr for PersistenceMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file_async" => Ok(Self::FileAsync),
+            "file_sync" => Ok(Self::FileSync),
+            other => Err(format!("unknown persistence mode '{}'", other)),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Ephemeral mode (`NEXO_EPHEMERAL`): the whole engine keeps its data in a
+//! private scratch directory instead of the configured data directories,
+//! in RAM where the host has `/dev/shm`. Brokers behave as usual but nothing
+//! outlives the process: the directory is removed on shutdown, and one left
+//! behind by a crashed process is removed by the next ephemeral start.
+
+use std::path::{Path, PathBuf};
+
+use tracing::warn;
+
+use crate::brokers::data_lock::process_alive;
+
+const PREFIX: &str = "nexo-ephemeral-";
+
+/// Create this process' scratch directory, removing those of dead processes.
+pub fn scratch_dir() -> std::io::Result<PathBuf> {
+    let shm = Path::new("/dev/shm");
+    let base = if shm.is_dir() { shm.to_path_buf() } else { std::env::temp_dir() };
+    sweep(&base);
+
+    let dir = base.join(format!("{}{}", PREFIX, std::process::id()));
+    // Left by an earlier process with the same pid
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Delete the scratch directory and everything the brokers wrote to it.
+pub fn remove(dir: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(dir) {
+        warn!("Failed to remove ephemeral data directory {:?}: {}", dir, e);
+    }
+}
+
+fn sweep(base: &Path) {
+    let Ok(entries) = std::fs::read_dir(base) else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(|n| n.strip_prefix(PREFIX)).and_then(|pid| pid.parse().ok()) else { continue };
+        if !process_alive(pid) {
+            let _ = std::fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn sweep_removes_only_dead_owners() {
+        let base = tempfile::tempdir().unwrap();
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = base.path().join(format!("{}{}", PREFIX, child.id()));
+        child.wait().unwrap();
+        let live = base.path().join(format!("{}{}", PREFIX, std::os::unix::process::parent_id()));
+        let other = base.path().join("unrelated");
+        for dir in [&dead, &live, &other] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+
+        sweep(base.path());
+        assert!(!dead.exists());
+        assert!(live.exists() && other.exists());
+    }
+}
@@ -0,0 +1,78 @@
+//! Errors returned by the brokers, shared by every transport.
+
+// ========================================
+// ERROR CODES
+// ========================================
+
+/// What went wrong, independent of the transport that reports it (see
+/// `transport::tcp::protocol::errors::wire_code` for the TCP numbering).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    // Protocol
+    InvalidRequest,
+    UnknownOpcode,
+    PayloadTooLarge,
+    SchemaViolation,
+    /// Opcode not served on the listener the request came in on
+    Forbidden,
+    /// Queue, topic or filter name breaking the naming rules
+    InvalidName,
+    // Resources
+    NotFound,
+    /// Create of an existing resource with settings that contradict it
+    Conflict,
+    // Consumer membership
+    Fenced,
+    NotMember,
+    NotOwner,
+    NotPending,
+    /// The topics behind a multi-topic subscription changed: rejoin (same session tokens)
+    Rebalance,
+    // Server
+    Storage,
+    /// Memory budget above its high-water mark: publish shed (see `brokers::memory`)
+    Busy,
+    /// Connection refused at accept: over `SERVER_MAX_CONNECTIONS` or its per-address limit
+    TooManyConnections,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Whether the same request may succeed if retried (possibly after rejoining).
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Fenced | Self::NotMember | Self::Rebalance | Self::Storage | Self::Busy | Self::TooManyConnections | Self::Internal)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NexoError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+}
+
+impl NexoError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: code.is_retryable(),
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+}
+
+impl std::fmt::Display for NexoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for NexoError {}
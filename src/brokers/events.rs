@@ -0,0 +1,162 @@
+//! Engine-wide event bus: the lifecycle events the brokers post (queue
+//! created or deleted, DLQ threshold, group rebalanced, segments deleted by
+//! retention, persistence error; see `brokers::hooks`) go through one
+//! `EventBus` owned by the engine.
+//!
+//! The bus keeps the last `EVENT_LOG_CAPACITY` events in a ring, served to
+//! the dashboard as an activity feed (`GET /api/events`), and forwards each
+//! one to the lifecycle hooks when `HOOK_URLS` is set. A failing disk errors
+//! on every flush, so `persistenceError` is posted at most once per resource
+//! every `PERSISTENCE_ERROR_INTERVAL`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::brokers::clock::SharedClock;
+use crate::brokers::hooks::{HookDispatcher, LifecycleEvent};
+
+pub const PERSISTENCE_ERROR_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRecord {
+    /// Increasing from 1 since start, to page with `after`
+    pub id: u64,
+    /// ms since epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
+    pub event: LifecycleEvent,
+}
+
+pub struct EventBus {
+    ring: Mutex<VecDeque<EventRecord>>,
+    capacity: usize,
+    last_id: AtomicU64,
+    hooks: Option<Arc<HookDispatcher>>,
+    clock: SharedClock,
+    /// Last `persistenceError` posted, by broker and resource
+    reported: Mutex<HashMap<(&'static str, Option<String>), Instant>>,
+}
+
+impl EventBus {
+    /// Bus keeping the last `capacity` events (0 = none, hooks only),
+    /// stamped with the engine clock.
+    pub fn new(capacity: usize, hooks: Option<Arc<HookDispatcher>>, clock: SharedClock) -> Arc<Self> {
+        Arc::new(Self {
+            ring: Mutex::new(VecDeque::with_capacity(capacity.min(4096))),
+            capacity,
+            last_id: AtomicU64::new(0),
+            hooks,
+            clock,
+            reported: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn post(&self, event: LifecycleEvent) {
+        if self.capacity > 0 {
+            let mut ring = self.ring.lock();
+            // Ids are taken under the lock so the ring stays in id order
+            let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+            if ring.len() == self.capacity {
+                ring.pop_front();
+            }
+            ring.push_back(EventRecord { id, timestamp: self.clock.now_ms(), event: event.clone() });
+        }
+        if let Some(hooks) = &self.hooks {
+            hooks.emit(event);
+        }
+    }
+
+    /// Post a `persistenceError`, unless one was posted for the same resource
+    /// less than `PERSISTENCE_ERROR_INTERVAL` ago.
+    pub fn persistence_error(&self, broker: &'static str, resource: Option<&str>, error: String) {
+        let key = (broker, resource.map(str::to_string));
+        {
+            let mut reported = self.reported.lock();
+            let now = self.clock.now();
+            if reported.get(&key).is_some_and(|at| now.duration_since(*at) < PERSISTENCE_ERROR_INTERVAL) {
+                return;
+            }
+            reported.insert(key.clone(), now);
+        }
+        self.post(LifecycleEvent::PersistenceError { broker, resource: key.1, error });
+    }
+
+    /// Up to `limit` events with an id above `after`, newest first.
+    pub fn recent(&self, after: u64, limit: usize) -> Vec<EventRecord> {
+        self.ring.lock().iter().rev()
+            .take_while(|record| record.id > after)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+/// A broker's view of the bus: drops events until the engine attaches one
+/// (a bare manager in tests has none).
+#[derive(Clone, Default)]
+pub struct EventHandle(Arc<OnceLock<Arc<EventBus>>>);
+
+impl EventHandle {
+    pub fn attach(&self, bus: Arc<EventBus>) {
+        let _ = self.0.set(bus);
+    }
+
+    pub fn post(&self, event: LifecycleEvent) {
+        if let Some(bus) = self.0.get() {
+            bus.post(event);
+        }
+    }
+
+    pub fn persistence_error(&self, broker: &'static str, resource: Option<&str>, error: String) {
+        if let Some(bus) = self.0.get() {
+            bus.persistence_error(broker, resource, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brokers::clock::{self, Clock, MockClock};
+
+    fn created(queue: &str) -> LifecycleEvent {
+        LifecycleEvent::QueueCreated { queue: queue.to_string() }
+    }
+
+    #[test]
+    fn ring_keeps_the_newest() {
+        let clock = MockClock::new();
+        let bus = EventBus::new(3, None, clock.clone());
+        for i in 0..5 {
+            bus.post(created(&format!("q{}", i)));
+        }
+        let ids: Vec<u64> = bus.recent(0, 10).iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![5, 4, 3]);
+        let ids: Vec<u64> = bus.recent(3, 10).iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![5, 4]);
+        assert_eq!(bus.recent(0, 1).len(), 1);
+
+        let json = serde_json::to_value(&bus.recent(4, 1)[0]).unwrap();
+        assert_eq!((json["event"].as_str(), json["queue"].as_str()), (Some("queueCreated"), Some("q4")));
+        assert_eq!(json["timestamp"].as_u64(), Some(clock.now_ms()));
+    }
+
+    #[test]
+    fn persistence_errors_are_throttled_per_resource() {
+        let bus = EventBus::new(10, None, clock::system());
+        let handle = EventHandle::default();
+        handle.persistence_error("queue", Some("orders"), "lost".to_string());
+        handle.attach(bus.clone());
+        for _ in 0..3 {
+            handle.persistence_error("queue", Some("orders"), "disk full".to_string());
+        }
+        handle.persistence_error("queue", Some("payments"), "disk full".to_string());
+        assert_eq!(bus.recent(0, 10).len(), 2);
+    }
+}
@@ -0,0 +1,87 @@
+//! Liveness and readiness, for orchestrators (`/healthz`, `/readyz`) and the
+//! admin protocol (opcode `0x49`). The engine is ready once warm-start
+//! recovery is over, every persistence writer runs, each data directory
+//! accepts writes and memory is below the high-water mark.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::brokers::warm_start::WarmStartSnapshot;
+use crate::config::Config;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    pub fn new(name: &'static str, ok: bool, detail: impl Into<String>) -> Self {
+        Self { name, ok, detail: detail.into() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl Readiness {
+    pub fn from_checks(checks: Vec<HealthCheck>) -> Self {
+        Self { ready: checks.iter().all(|c| c.ok), checks }
+    }
+
+    pub fn recovery(warm_start: &WarmStartSnapshot) -> HealthCheck {
+        let state = if warm_start.done { "completed" } else { "in progress" };
+        HealthCheck::new("recovery", warm_start.done, format!("warm start {}: {}", state, warm_start.summary()))
+    }
+
+    /// Before the engine exists: only the recovery check, still running.
+    pub fn recovering(warm_start: &WarmStartSnapshot) -> Self {
+        Self::from_checks(vec![Self::recovery(warm_start)])
+    }
+}
+
+/// The data directories a readiness check writes a probe file to.
+pub struct DiskProbe {
+    dirs: Vec<(&'static str, PathBuf)>,
+}
+
+impl DiskProbe {
+    const PROBE_FILE: &'static str = ".nexo-ready-probe";
+
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            dirs: vec![
+                ("store", PathBuf::from(&config.store.persistence_path)),
+                ("queue", PathBuf::from(&config.queue.persistence_path)),
+                ("stream", PathBuf::from(&config.stream.persistence_path)),
+                ("pubsub", PathBuf::from(&config.pubsub.persistence_path)),
+            ],
+        }
+    }
+
+    /// Create and remove a probe file in every directory (created if missing,
+    /// as a broker would on its first write).
+    pub async fn check(&self) -> HealthCheck {
+        let mut failed = Vec::new();
+        for (broker, dir) in &self.dirs {
+            let probe = dir.join(Self::PROBE_FILE);
+            let written = async {
+                tokio::fs::create_dir_all(dir).await?;
+                tokio::fs::write(&probe, b"ok").await?;
+                tokio::fs::remove_file(&probe).await
+            };
+            if let Err(e) = written.await {
+                failed.push(format!("{} ({}): {}", broker, dir.display(), e));
+            }
+        }
+        match failed.is_empty() {
+            true => HealthCheck::new("disk", true, format!("{} data directories writable", self.dirs.len())),
+            false => HealthCheck::new("disk", false, format!("not writable: {}", failed.join("; "))),
+        }
+    }
+}
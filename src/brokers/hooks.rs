@@ -0,0 +1,240 @@
+//! Lifecycle hooks: JSON events POSTed to the URLs in `HOOK_URLS` when
+//! something happens to a resource rather than to a message: a queue created
+//! or deleted, a DLQ crossing its alert threshold, a consumer group
+//! rebalanced, retention deleting stream segments, a persistence error.
+//!
+//! Each URL gets its own worker and buffer (`HOOK_BUFFER` events), so a slow
+//! endpoint only delays its own deliveries, in order. A delivery is retried
+//! on any non-2xx answer, timeout or transport error with exponential
+//! backoff, `HOOK_MAX_ATTEMPTS` times in all. Events that still fail, or
+//! find the buffer full, are appended to `HOOK_DEAD_LETTER_PATH` (JSONL) with
+//! the URL and the last error, for replay by hand.
+//!
+//! The brokers post events to the engine's `EventBus` (see `brokers::events`)
+//! without waiting, and the bus hands them to the dispatcher.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Mutex};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::brokers::clock::SharedClock;
+use crate::config::HooksConfig;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum LifecycleEvent {
+    #[serde(rename_all = "camelCase")]
+    QueueCreated { queue: String },
+    #[serde(rename_all = "camelCase")]
+    QueueDeleted { queue: String },
+    /// The DLQ grew to the queue's `dlqAlertThreshold`
+    #[serde(rename_all = "camelCase")]
+    DlqThreshold { queue: String, dlq_depth: usize, threshold: usize },
+    /// A member joined, left or timed out, or the group was seeked: the
+    /// members hold `generation` from now on
+    #[serde(rename_all = "camelCase")]
+    GroupRebalanced { topic: String, group: String, generation: u64, members: usize, reason: RebalanceReason },
+    /// A retention pass deleted the oldest segments of a topic
+    #[serde(rename_all = "camelCase")]
+    SegmentsDeleted { topic: String, segments: usize, bytes: u64, head_seq: u64 },
+    /// A write, fsync or open failed; `resource` is the queue or topic when known
+    #[serde(rename_all = "camelCase")]
+    PersistenceError { broker: &'static str, resource: Option<String>, error: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RebalanceReason {
+    Joined,
+    Left,
+    Expired,
+    Seek,
+}
+
+impl LifecycleEvent {
+    /// The `event` field, as listed in `HOOK_EVENTS`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::QueueCreated { .. } => "queueCreated",
+            Self::QueueDeleted { .. } => "queueDeleted",
+            Self::DlqThreshold { .. } => "dlqThreshold",
+            Self::GroupRebalanced { .. } => "groupRebalanced",
+            Self::SegmentsDeleted { .. } => "segmentsDeleted",
+            Self::PersistenceError { .. } => "persistenceError",
+        }
+    }
+}
+
+/// What a hook receives: the event plus an id to deduplicate retries.
+#[derive(Debug, Serialize)]
+struct Envelope<'a> {
+    id: Uuid,
+    timestamp: u64,
+    #[serde(flatten)]
+    event: &'a LifecycleEvent,
+}
+
+/// A line of the dead-letter file.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter<'a> {
+    url: &'a str,
+    attempts: u32,
+    error: &'a str,
+    failed_at: u64,
+    body: &'a Value,
+}
+
+struct Delivery {
+    name: &'static str,
+    body: Arc<Value>,
+}
+
+pub struct HookDispatcher {
+    workers: Vec<(String, mpsc::Sender<Delivery>)>,
+    /// Empty = every event
+    events: HashSet<String>,
+    dead_letters: Arc<DeadLetterFile>,
+    clock: SharedClock,
+}
+
+impl HookDispatcher {
+    /// Start one worker per URL; `None` when `HOOK_URLS` is empty.
+    pub fn start(config: &HooksConfig, clock: SharedClock) -> Option<Arc<Self>> {
+        if config.urls.is_empty() {
+            return None;
+        }
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms.max(1)))
+            .build()
+            .unwrap_or_default();
+        let dead_letters = Arc::new(DeadLetterFile {
+            path: PathBuf::from(&config.dead_letter_path),
+            lock: Mutex::new(()),
+            clock: clock.clone(),
+        });
+        let workers = config.urls.iter().map(|url| {
+            let (tx, rx) = mpsc::channel(config.buffer.max(1));
+            let worker = Worker { url: url.clone(), http: http.clone(), config: config.clone(), dead_letters: dead_letters.clone() };
+            tokio::spawn(worker.run(rx));
+            (url.clone(), tx)
+        }).collect();
+        Some(Arc::new(Self {
+            workers,
+            events: config.events.iter().cloned().collect(),
+            dead_letters,
+            clock,
+        }))
+    }
+
+    pub fn emit(&self, event: LifecycleEvent) {
+        let name = event.name();
+        if !self.events.is_empty() && !self.events.contains(name) {
+            return;
+        }
+        let envelope = Envelope { id: Uuid::new_v4(), timestamp: self.clock.now_ms(), event: &event };
+        let Ok(body) = serde_json::to_value(&envelope) else { return };
+        let body = Arc::new(body);
+        for (url, tx) in &self.workers {
+            if let Err(mpsc::error::TrySendError::Full(delivery)) = tx.try_send(Delivery { name, body: body.clone() }) {
+                warn!("Hook {}: buffer full, dead-lettering {} event", url, name);
+                let (dead_letters, url) = (self.dead_letters.clone(), url.clone());
+                if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                    runtime.spawn(async move { dead_letters.append(&url, 0, "buffer full", &delivery.body).await });
+                }
+            }
+        }
+    }
+}
+
+struct Worker {
+    url: String,
+    http: reqwest::Client,
+    config: HooksConfig,
+    dead_letters: Arc<DeadLetterFile>,
+}
+
+impl Worker {
+    /// Deliver in order until every sender is gone.
+    async fn run(self, mut rx: mpsc::Receiver<Delivery>) {
+        while let Some(delivery) = rx.recv().await {
+            let attempts = self.config.max_attempts.max(1);
+            let mut last_error = String::new();
+            for attempt in 1..=attempts {
+                match self.post(&delivery).await {
+                    Ok(()) => {
+                        last_error.clear();
+                        break;
+                    }
+                    Err(e) => {
+                        debug!("Hook {}: {} attempt {}/{} failed: {}", self.url, delivery.name, attempt, attempts, e);
+                        last_error = e;
+                    }
+                }
+                if attempt < attempts {
+                    tokio::time::sleep(Duration::from_millis(backoff_ms(&self.config, attempt))).await;
+                }
+            }
+            if !last_error.is_empty() {
+                warn!("Hook {}: giving up on {} event after {} attempts: {}", self.url, delivery.name, attempts, last_error);
+                self.dead_letters.append(&self.url, attempts, &last_error, &delivery.body).await;
+            }
+        }
+    }
+
+    async fn post(&self, delivery: &Delivery) -> Result<(), String> {
+        let response = self.http.post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("x-nexo-event", delivery.name)
+            .body(delivery.body.to_string())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(format!("HTTP {}", response.status())),
+        }
+    }
+}
+
+/// Exponential backoff: base, 2x base, 4x base, ... capped at the configured max.
+fn backoff_ms(config: &HooksConfig, attempt: u32) -> u64 {
+    config.backoff_ms
+        .saturating_mul(1u64 << attempt.saturating_sub(1).min(20))
+        .min(config.max_backoff_ms)
+}
+
+struct DeadLetterFile {
+    path: PathBuf,
+    /// One line at a time across workers
+    lock: Mutex<()>,
+    clock: SharedClock,
+}
+
+impl DeadLetterFile {
+    async fn append(&self, url: &str, attempts: u32, error: &str, body: &Value) {
+        let letter = DeadLetter { url, attempts, error, failed_at: self.clock.now_ms(), body };
+        let Ok(mut line) = serde_json::to_vec(&letter) else { return };
+        line.push(b'\n');
+        let _guard = self.lock.lock().await;
+        let written = async {
+            if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await?;
+            file.write_all(&line).await?;
+            file.flush().await
+        };
+        if let Err(e) = written.await {
+            warn!("Hook dead letter lost, cannot write {:?}: {}", self.path, e);
+        }
+    }
+}
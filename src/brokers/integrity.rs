@@ -0,0 +1,340 @@
+//! Offline integrity check for everything the brokers persist.
+//!
+//! Scans queue databases and logs, every stream segment (not just the tail that
+//! recovery reads), consumer group offsets, retained pub/sub messages and the
+//! JSON sidecar files, producing a machine-readable report. With `repair`,
+//! logs are truncated to their last valid record and unreadable JSON files are
+//! moved aside; SQLite databases only get a `REINDEX`, anything worse is
+//! reported and left for manual recovery.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use crc32fast::Hasher;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::brokers::stream::domain::persistence::decode_record_len;
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    Sqlite,
+    /// Queue stored by the log backend (`.qlog`)
+    QueueLog,
+    Segment,
+    Groups,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Ok,
+    Corrupt,
+    Repaired,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub broker: &'static str,
+    pub kind: FileKind,
+    pub path: String,
+    pub status: FileStatus,
+    pub size: u64,
+    /// Valid records found (logs only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records: Option<u64>,
+    /// Offset of the end of the last valid record (logs only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    /// No file is left corrupt.
+    pub ok: bool,
+    pub repair: bool,
+    pub scanned: usize,
+    pub corrupt: usize,
+    pub repaired: usize,
+    pub files: Vec<FileReport>,
+}
+
+/// Root persistence directories of the brokers to check.
+#[derive(Debug, Clone)]
+pub struct DataDirs {
+    pub queue: PathBuf,
+    pub stream: PathBuf,
+    pub pubsub: PathBuf,
+}
+
+impl DataDirs {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            queue: PathBuf::from(&config.queue.persistence_path),
+            stream: PathBuf::from(&config.stream.persistence_path),
+            pubsub: PathBuf::from(&config.pubsub.persistence_path),
+        }
+    }
+}
+
+/// Scan all persisted data under `dirs`. Blocking: run it before the engine
+/// starts or on a blocking thread.
+pub fn check(dirs: &DataDirs, repair: bool) -> IntegrityReport {
+    let mut files = Vec::new();
+    check_queues(&dirs.queue, repair, &mut files);
+    check_streams(&dirs.stream, repair, &mut files);
+
+    let retained = dirs.pubsub.join("retained.db");
+    if retained.is_file() {
+        files.push(check_sqlite("pubsub", &retained, repair));
+    }
+
+    let corrupt = files.iter().filter(|f| f.status == FileStatus::Corrupt).count();
+    let repaired = files.iter().filter(|f| f.status == FileStatus::Repaired).count();
+    IntegrityReport {
+        ok: corrupt == 0,
+        repair,
+        scanned: files.len(),
+        corrupt,
+        repaired,
+        files,
+    }
+}
+
+// ==========================================
+// PER-BROKER LAYOUTS
+// ==========================================
+
+fn check_queues(dir: &Path, repair: bool, files: &mut Vec<FileReport>) {
+    for path in sorted_files(dir) {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+        if name.ends_with(".db") {
+            files.push(check_sqlite("queue", &path, repair));
+        } else if name.ends_with(".qlog") {
+            files.push(check_log(&path, FileKind::QueueLog, repair));
+        } else if name.ends_with(".config.json") || name.ends_with(".schemas.json") {
+            files.push(check_json("queue", &path, repair));
+        }
+    }
+}
+
+fn check_streams(dir: &Path, repair: bool, files: &mut Vec<FileReport>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    let mut topics: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect();
+    topics.sort();
+
+    for topic in topics {
+        let mut segments = Vec::new();
+        for path in sorted_files(&topic) {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+            if name == "groups.log" {
+                files.push(check_log(&path, FileKind::Groups, repair));
+            } else if name == "config.json" || name == "schemas.json" || name == "tiered.json" || name == "epochs.json" {
+                files.push(check_json("stream", &path, repair));
+            } else if let Some(start_seq) = name.strip_suffix(".log").and_then(|s| s.parse::<u64>().ok()) {
+                segments.push((start_seq, path));
+            }
+        }
+        segments.sort_by_key(|(start_seq, _)| *start_seq);
+        for (_, path) in segments {
+            files.push(check_log(&path, FileKind::Segment, repair));
+        }
+    }
+}
+
+// ==========================================
+// FILE CHECKS
+// ==========================================
+
+fn check_sqlite(broker: &'static str, path: &Path, repair: bool) -> FileReport {
+    let mut report = FileReport::new(broker, FileKind::Sqlite, path);
+    let mut issues = match integrity_check(path) {
+        Ok(issues) => issues,
+        Err(e) => vec![format!("Cannot open database: {}", e)],
+    };
+
+    if !issues.is_empty() && repair {
+        // REINDEX rebuilds damaged indexes; table-level damage survives it
+        let reindexed = Connection::open(path).and_then(|conn| conn.execute_batch("REINDEX;"));
+        match reindexed.and_then(|_| integrity_check(path)) {
+            Ok(remaining) if remaining.is_empty() => {
+                report.issues = issues;
+                report.status = FileStatus::Repaired;
+                return report;
+            }
+            Ok(remaining) => issues = remaining,
+            Err(e) => issues.push(format!("REINDEX failed: {}", e)),
+        }
+    }
+
+    if !issues.is_empty() {
+        report.status = FileStatus::Corrupt;
+    }
+    report.issues = issues;
+    report
+}
+
+fn integrity_check(path: &Path) -> rusqlite::Result<Vec<String>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    let mut issues = Vec::new();
+    for row in rows {
+        let row = row?;
+        if row != "ok" {
+            issues.push(row);
+        }
+    }
+    Ok(issues)
+}
+
+fn check_json(broker: &'static str, path: &Path, repair: bool) -> FileReport {
+    let mut report = FileReport::new(broker, FileKind::Json, path);
+    let error = match std::fs::read(path) {
+        Ok(data) => serde_json::from_slice::<serde_json::Value>(&data).err().map(|e| format!("Invalid JSON: {}", e)),
+        Err(e) => Some(format!("Cannot read file: {}", e)),
+    };
+    let Some(error) = error else { return report };
+
+    report.issues.push(error);
+    report.status = FileStatus::Corrupt;
+    if repair {
+        // Brokers fall back to defaults when the file is missing
+        let aside = path.with_extension("json.corrupt");
+        match std::fs::rename(path, &aside) {
+            Ok(()) => {
+                report.issues.push(format!("Moved aside to {}", aside.display()));
+                report.status = FileStatus::Repaired;
+            }
+            Err(e) => report.issues.push(format!("Cannot move file aside: {}", e)),
+        }
+    }
+    report
+}
+
+/// Walk a `[len][crc][content]` log, stopping at the first invalid record.
+fn check_log(path: &Path, kind: FileKind, repair: bool) -> FileReport {
+    let broker = if kind == FileKind::QueueLog { "queue" } else { "stream" };
+    let mut report = FileReport::new(broker, kind, path);
+    let (records, valid_bytes, issue) = match scan_log(path, kind) {
+        Ok(scan) => scan,
+        Err(e) => (0, 0, Some(format!("Cannot read file: {}", e))),
+    };
+    report.records = Some(records);
+    report.valid_bytes = Some(valid_bytes);
+    let Some(issue) = issue else { return report };
+
+    report.issues.push(issue);
+    report.status = FileStatus::Corrupt;
+    if repair {
+        match OpenOptions::new().write(true).open(path).and_then(|f| f.set_len(valid_bytes)) {
+            Ok(()) => {
+                report.issues.push(format!("Truncated {} bytes after the last valid record", report.size - valid_bytes));
+                report.status = FileStatus::Repaired;
+            }
+            Err(e) => report.issues.push(format!("Truncation failed: {}", e)),
+        }
+    }
+    report
+}
+
+fn scan_log(path: &Path, kind: FileKind) -> std::io::Result<(u64, u64, Option<String>)> {
+    let size = std::fs::metadata(path)?.len();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut records = 0u64;
+    let mut offset = 0u64;
+
+    loop {
+        let mut header = [0u8; 8];
+        match read_full(&mut reader, &mut header)? {
+            0 => return Ok((records, offset, None)),
+            8 => {}
+            n => return Ok((records, offset, Some(format!("Truncated record header at offset {} ({} of 8 bytes)", offset, n)))),
+        }
+        let raw_len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let stored_crc = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let (len, has_lane) = match kind {
+            FileKind::Segment => decode_record_len(raw_len),
+            _ => (raw_len, false),
+        };
+
+        if offset + 8 + len as u64 > size {
+            return Ok((records, offset, Some(format!("Record at offset {} claims {} bytes past end of file", offset, len))));
+        }
+        let mut content = vec![0u8; len as usize];
+        reader.read_exact(&mut content)?;
+
+        let mut hasher = Hasher::new();
+        hasher.update(&content);
+        if hasher.finalize() != stored_crc {
+            return Ok((records, offset, Some(format!("CRC mismatch at offset {}", offset))));
+        }
+        if let Some(problem) = malformed(kind, &content, has_lane) {
+            return Ok((records, offset, Some(format!("{} at offset {}", problem, offset))));
+        }
+
+        records += 1;
+        offset += 8 + len as u64;
+    }
+}
+
+/// Structural checks on a record whose CRC already matched.
+fn malformed(kind: FileKind, content: &[u8], has_lane: bool) -> Option<&'static str> {
+    match kind {
+        // [seq:8][timestamp:8][lane:1?][payload]
+        FileKind::Segment if content.len() < 16 + has_lane as usize => Some("Record too short"),
+        // [tag:1][op]: an empty record has no op
+        FileKind::QueueLog if content.is_empty() => Some("Record too short"),
+        // [ack_floor:8][group_len:2][group]
+        FileKind::Groups => {
+            if content.len() < 10 {
+                return Some("Group entry too short");
+            }
+            let group_len = u16::from_be_bytes([content[8], content[9]]) as usize;
+            (content.len() != 10 + group_len).then_some("Group entry length mismatch")
+        }
+        _ => None,
+    }
+}
+
+/// `read_exact` that reports how many bytes were read before EOF instead of failing.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn sorted_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<PathBuf> = entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect();
+    files.sort();
+    files
+}
+
+impl FileReport {
+    fn new(broker: &'static str, kind: FileKind, path: &Path) -> Self {
+        Self {
+            broker,
+            kind,
+            path: path.display().to_string(),
+            status: FileStatus::Ok,
+            size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+            records: None,
+            valid_bytes: None,
+            issues: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Intent log for store-and-publish: a store write plus a pub/sub or stream
+//! publish applied as one operation.
+//!
+//! The intent is committed to SQLite (WAL + `synchronous = FULL`) before
+//! either side is applied and deleted once both are; a stream publish is
+//! fsynced first. Intents still in the log at startup were cut short by a
+//! crash and are applied again, so the pair is at-least-once: a recovered
+//! publish may duplicate one that went out before the crash.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use parking_lot::Mutex;
+use rusqlite::{params, Connection};
+
+use crate::brokers::error::{ErrorCode, NexoError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishTarget {
+    PubSub { topic: String, retain: bool },
+    Stream { topic: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Intent {
+    pub key: String,
+    pub value: Bytes,
+    /// As for a plain SET: seconds, `None`/0 = the store default
+    pub ttl: Option<u64>,
+    pub target: PublishTarget,
+    pub payload: Bytes,
+    /// ms since epoch
+    pub created_at: u64,
+}
+
+impl Intent {
+    /// TTL left for the key when the intent is replayed at `now_ms`, `None`
+    /// once an explicit TTL has run out (the key is not written again).
+    pub fn remaining_ttl(&self, now_ms: u64) -> Option<Option<u64>> {
+        match self.ttl {
+            Some(ttl) if ttl > 0 => {
+                let left_ms = (self.created_at + ttl * 1000).checked_sub(now_ms).filter(|ms| *ms > 0)?;
+                Some(Some(left_ms.div_ceil(1000)))
+            }
+            ttl => Some(ttl),
+        }
+    }
+}
+
+pub struct IntentLog {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl IntentLog {
+    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or(());
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = FULL;"
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS intents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key TEXT NOT NULL,
+                value BLOB NOT NULL,
+                ttl INTEGER,
+                broker TEXT NOT NULL,
+                topic TEXT NOT NULL,
+                retain INTEGER NOT NULL,
+                payload BLOB NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    /// Durably record `intent`; returns its id for `resolve`.
+    pub async fn record(&self, intent: &Intent) -> Result<i64, NexoError> {
+        let intent = intent.clone();
+        self.blocking(move |conn| {
+            let (broker, topic, retain) = match &intent.target {
+                PublishTarget::PubSub { topic, retain } => ("pubsub", topic, *retain),
+                PublishTarget::Stream { topic } => ("stream", topic, false),
+            };
+            conn.execute(
+                "INSERT INTO intents (key, value, ttl, broker, topic, retain, payload, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    intent.key,
+                    intent.value.as_ref(),
+                    intent.ttl.map(|t| t as i64),
+                    broker,
+                    topic,
+                    retain,
+                    intent.payload.as_ref(),
+                    intent.created_at as i64,
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        }).await
+    }
+
+    /// Drop an intent once both of its sides are applied.
+    pub async fn resolve(&self, id: i64) -> Result<(), NexoError> {
+        self.blocking(move |conn| conn.execute("DELETE FROM intents WHERE id = ?", params![id]).map(|_| ())).await
+    }
+
+    /// Intents not resolved yet, oldest first.
+    pub async fn pending(&self) -> Result<Vec<(i64, Intent)>, NexoError> {
+        self.blocking(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, key, value, ttl, broker, topic, retain, payload, created_at FROM intents ORDER BY id"
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let broker: String = row.get(4)?;
+                let topic: String = row.get(5)?;
+                let target = match broker.as_str() {
+                    "stream" => PublishTarget::Stream { topic },
+                    _ => PublishTarget::PubSub { topic, retain: row.get(6)? },
+                };
+                let intent = Intent {
+                    key: row.get(1)?,
+                    value: Bytes::from(row.get::<_, Vec<u8>>(2)?),
+                    ttl: row.get::<_, Option<i64>>(3)?.map(|t| t as u64),
+                    target,
+                    payload: Bytes::from(row.get::<_, Vec<u8>>(7)?),
+                    created_at: row.get::<_, i64>(8)? as u64,
+                };
+                Ok((row.get(0)?, intent))
+            })?;
+            rows.collect()
+        }).await
+    }
+
+    async fn blocking<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    ) -> Result<T, NexoError> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || op(&conn.lock()))
+            .await
+            .map_err(|e| NexoError::new(ErrorCode::Internal, e.to_string()))?
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Intent log: {}", e)))
+    }
+}
@@ -0,0 +1,133 @@
+//! Labels: `key=value` pairs attached to queues and stream topics, for
+//! ownership and cost attribution (`team=payments`, `env=staging`).
+//!
+//! They are set at create time or replaced as a whole later, persisted with
+//! the resource's config and never looked at by the brokers themselves; the
+//! list APIs filter on them with a selector: comma-separated `key=value`,
+//! `key!=value` or a bare `key` (present), all of which must hold.
+
+use std::collections::BTreeMap;
+
+use crate::brokers::error::NexoError;
+
+pub type Labels = BTreeMap<String, String>;
+
+pub const MAX_LABELS: usize = 32;
+pub const MAX_KEY_LEN: usize = 63;
+pub const MAX_VALUE_LEN: usize = 256;
+
+/// Keys are `[A-Za-z0-9._/-]` starting with a letter or digit; values are
+/// any text without control characters.
+pub fn validate(labels: &Labels) -> Result<(), NexoError> {
+    if labels.len() > MAX_LABELS {
+        return Err(NexoError::invalid(format!("More than {} labels", MAX_LABELS)));
+    }
+    for (key, value) in labels {
+        check_key(key).map_err(|reason| NexoError::invalid(format!("Invalid label key '{}': {}", key, reason)))?;
+        if value.len() > MAX_VALUE_LEN {
+            return Err(NexoError::invalid(format!("Label '{}': value longer than {} bytes", key, MAX_VALUE_LEN)));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(NexoError::invalid(format!("Label '{}': control characters not allowed", key)));
+        }
+    }
+    Ok(())
+}
+
+fn check_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("empty".to_string());
+    }
+    if key.len() > MAX_KEY_LEN {
+        return Err(format!("longer than {} bytes", MAX_KEY_LEN));
+    }
+    if !key.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err("must start with a letter or digit".to_string());
+    }
+    if let Some(c) = key.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '/' | '-'))) {
+        return Err(format!("'{}' not allowed (letters, digits, '.', '_', '/', '-')", c.escape_default()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    Exists(String),
+}
+
+/// A parsed label filter; the empty selector matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+impl LabelSelector {
+    pub fn parse(selector: &str) -> Result<Self, NexoError> {
+        let mut requirements = Vec::new();
+        for term in selector.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let requirement = if let Some((key, value)) = term.split_once("!=") {
+                Requirement::NotEquals(key.trim().to_string(), value.trim().to_string())
+            } else if let Some((key, value)) = term.split_once('=') {
+                Requirement::Equals(key.trim().to_string(), value.trim().to_string())
+            } else {
+                Requirement::Exists(term.to_string())
+            };
+            let key = match &requirement {
+                Requirement::Equals(key, _) | Requirement::NotEquals(key, _) | Requirement::Exists(key) => key,
+            };
+            check_key(key).map_err(|reason| NexoError::invalid(format!("Invalid label selector '{}': {}", term, reason)))?;
+            requirements.push(requirement);
+        }
+        Ok(Self { requirements })
+    }
+
+    pub fn matches(&self, labels: &Labels) -> bool {
+        self.requirements.iter().all(|requirement| match requirement {
+            Requirement::Equals(key, value) => labels.get(key) == Some(value),
+            Requirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            Requirement::Exists(key) => labels.contains_key(key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> Labels {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn keys_and_values_are_checked() {
+        assert!(validate(&labels(&[("team", "payments"), ("nexo.io/cost-center", "42 EU")])).is_ok());
+        for key in ["", "-team", "team name", "caf\u{e9}", &"k".repeat(MAX_KEY_LEN + 1)] {
+            assert!(validate(&labels(&[(key, "x")])).is_err(), "{:?}", key);
+        }
+        assert!(validate(&labels(&[("team", "a\nb")])).is_err());
+        assert!(validate(&labels(&[("team", &"v".repeat(MAX_VALUE_LEN + 1))])).is_err());
+        let many: Labels = (0..=MAX_LABELS).map(|i| (format!("k{}", i), String::new())).collect();
+        assert!(validate(&many).is_err());
+    }
+
+    #[test]
+    fn selector_requires_every_term() {
+        let set = labels(&[("team", "payments"), ("env", "staging")]);
+        for (selector, expected) in [
+            ("", true),
+            ("team=payments", true),
+            ("team=payments,env=staging", true),
+            ("team=payments, env=prod", false),
+            ("env!=prod", true),
+            ("owner!=bob", true),
+            ("team", true),
+            ("owner", false),
+        ] {
+            assert_eq!(LabelSelector::parse(selector).unwrap().matches(&set), expected, "{}", selector);
+        }
+        assert!(LabelSelector::parse("=payments").is_err());
+        assert!(LabelSelector::parse("te am=x").is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Engine-wide memory budget (`MEMORY_BUDGET_BYTES`). A sampler adds up what
+//! the brokers hold in RAM — queue registries and DLQs, stream RAM windows,
+//! retained pub/sub messages — and sets the pressure level each broker checks
+//! before accepting a publish:
+//!
+//! - `High` (`MEMORY_HIGH_WATER_PERCENT` of the budget): stream RAM windows are
+//!   spilled to disk and non-durable publishes are refused with `Busy`. Pushes
+//!   to `fileSync` queues and publishes to `fileSync` topics still go through.
+//! - `Critical` (the whole budget): every publish is refused with `Busy`.
+//!
+//! `Busy` is retryable: producers back off until consumers and retention bring
+//! usage back under the mark. Messages moved inside the engine (dead letters,
+//! mirrors, backfills) are never refused.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use parking_lot::Mutex;
+
+use crate::config::MemoryConfig;
+use crate::brokers::error::ErrorCode;
+use crate::brokers::error::NexoError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pressure {
+    Normal = 0,
+    High = 1,
+    Critical = 2,
+}
+
+impl Pressure {
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => Self::Normal,
+            1 => Self::High,
+            _ => Self::Critical,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+/// Bytes held in RAM by each broker at the last sample.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    pub queue: u64,
+    pub stream: u64,
+    pub pubsub: u64,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.queue + self.stream + self.pubsub
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemorySnapshot {
+    pub limit: u64,
+    pub high_water: u64,
+    pub usage: MemoryUsage,
+    pub pressure: Pressure,
+    /// Publishes refused with `Busy` since start
+    pub rejections: u64,
+}
+
+pub struct MemoryBudget {
+    /// 0 = no budget: every publish is admitted
+    limit: u64,
+    high_water: u64,
+    usage: Mutex<MemoryUsage>,
+    level: AtomicU8,
+    rejections: AtomicU64,
+}
+
+impl MemoryBudget {
+    pub fn new(config: &MemoryConfig) -> Self {
+        let percent = config.high_water_percent.clamp(1, 100);
+        Self {
+            limit: config.budget_bytes,
+            high_water: (config.budget_bytes as u128 * percent as u128 / 100) as u64,
+            usage: Mutex::new(MemoryUsage::default()),
+            level: AtomicU8::new(Pressure::Normal as u8),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    pub fn pressure(&self) -> Pressure {
+        Pressure::from_u8(self.level.load(Ordering::Acquire))
+    }
+
+    /// Take a new sample and return the pressure level it sets.
+    pub fn record(&self, usage: MemoryUsage) -> Pressure {
+        let total = usage.total();
+        let pressure = if !self.is_enabled() || total < self.high_water {
+            Pressure::Normal
+        } else if total < self.limit {
+            Pressure::High
+        } else {
+            Pressure::Critical
+        };
+        *self.usage.lock() = usage;
+        self.level.store(pressure as u8, Ordering::Release);
+        pressure
+    }
+
+    /// Refuse a publish the current pressure level sheds. `durable` publishes
+    /// (fsynced before the producer is acked) only give way at `Critical`.
+    pub fn admit(&self, durable: bool) -> Result<(), NexoError> {
+        let refused = match self.pressure() {
+            Pressure::Normal => return Ok(()),
+            Pressure::High if durable => return Ok(()),
+            Pressure::High => "above the high-water mark: only durable publishes are accepted",
+            Pressure::Critical => "exhausted",
+        };
+        self.rejections.fetch_add(1, Ordering::Relaxed);
+        Err(NexoError::new(ErrorCode::Busy, format!(
+            "Memory budget {} ({} of {} bytes in use): retry later",
+            refused, self.usage.lock().total(), self.limit,
+        )))
+    }
+
+    pub fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            limit: self.limit,
+            high_water: self.high_water,
+            usage: *self.usage.lock(),
+            pressure: self.pressure(),
+            rejections: self.rejections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A broker's view of the engine budget: admits everything until attached.
+#[derive(Clone, Default)]
+pub struct BudgetHandle(Arc<OnceLock<Arc<MemoryBudget>>>);
+
+impl BudgetHandle {
+    pub fn attach(&self, budget: Arc<MemoryBudget>) {
+        let _ = self.0.set(budget);
+    }
+
+    pub fn admit(&self, durable: bool) -> Result<(), NexoError> {
+        match self.0.get() {
+            Some(budget) => budget.admit(durable),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(bytes: u64, percent: u8) -> MemoryBudget {
+        MemoryBudget::new(&MemoryConfig { budget_bytes: bytes, high_water_percent: percent, sample_interval_ms: 1000 })
+    }
+
+    #[test]
+    fn pressure_follows_the_marks() {
+        let budget = budget(1000, 80);
+        let sample = |queue| budget.record(MemoryUsage { queue, ..Default::default() });
+        assert_eq!(sample(799), Pressure::Normal);
+        assert!(budget.admit(false).is_ok());
+
+        assert_eq!(sample(800), Pressure::High);
+        assert_eq!(budget.admit(false).unwrap_err().code, ErrorCode::Busy);
+        assert!(budget.admit(true).is_ok());
+
+        assert_eq!(sample(1000), Pressure::Critical);
+        assert!(budget.admit(true).is_err());
+
+        assert_eq!(sample(10), Pressure::Normal);
+        assert!(budget.admit(false).is_ok());
+        assert_eq!(budget.snapshot().rejections, 2);
+    }
+
+    #[test]
+    fn zero_budget_admits_everything() {
+        let budget = budget(0, 80);
+        assert_eq!(budget.record(MemoryUsage { stream: u64::MAX / 2, ..Default::default() }), Pressure::Normal);
+        assert!(budget.admit(false).is_ok());
+    }
+}
@@ -0,0 +1,121 @@
+//! Message tracing: a debug facility recording the lifecycle of individual
+//! messages (pushed, dispatched, acked, nacked, dead-lettered, replayed).
+//!
+//! Tracing is opt-in per queue/topic and kept in a bounded in-memory LRU keyed
+//! by message id, so "where did my message go" can be answered from the
+//! dashboard without touching persistence.
+
+use std::fmt::Display;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use dashmap::DashMap;
+use lru::LruCache;
+use serde::Serialize;
+
+use crate::brokers::clock::SharedClock;
+
+/// Oldest events are dropped past this, so a poison message can't grow unbounded.
+const MAX_EVENTS_PER_MESSAGE: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceEventKind {
+    Pushed,
+    Published,
+    Dispatched,
+    Acked,
+    Nacked,
+    Expired,
+    DeadLettered,
+    Replayed,
+    Discarded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEvent {
+    pub at: u64,
+    pub kind: TraceEventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageTrace {
+    pub id: String,
+    pub broker: &'static str,
+    pub subject: String,
+    pub events: Vec<TraceEvent>,
+}
+
+pub struct MessageTracer {
+    broker: &'static str,
+    enabled: DashMap<String, ()>,
+    traces: Mutex<LruCache<String, MessageTrace>>,
+    clock: SharedClock,
+}
+
+impl MessageTracer {
+    pub fn new(broker: &'static str, capacity: usize, clock: SharedClock) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            broker,
+            enabled: DashMap::new(),
+            traces: Mutex::new(LruCache::new(capacity)),
+            clock,
+        }
+    }
+
+    pub fn set_enabled(&self, subject: &str, enabled: bool) {
+        if enabled {
+            self.enabled.insert(subject.to_string(), ());
+        } else {
+            self.enabled.remove(subject);
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self, subject: &str) -> bool {
+        self.enabled.contains_key(subject)
+    }
+
+    /// Record an event for `id` if tracing is enabled for `subject`. Callers
+    /// building an id or detail check `is_enabled` first, to skip the allocation.
+    pub fn record(&self, subject: &str, id: impl Display, kind: TraceEventKind, detail: Option<String>) {
+        if !self.is_enabled(subject) {
+            return;
+        }
+        let event = TraceEvent { at: self.clock.now_ms(), kind, detail };
+        let id = id.to_string();
+
+        let mut traces = self.traces.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let trace = traces.get_or_insert_mut(id.clone(), || MessageTrace {
+            id,
+            broker: self.broker,
+            subject: subject.to_string(),
+            events: Vec::new(),
+        });
+        if trace.events.len() >= MAX_EVENTS_PER_MESSAGE {
+            trace.events.remove(0);
+        }
+        trace.events.push(event);
+    }
+
+    pub fn get(&self, id: &str) -> Option<MessageTrace> {
+        let traces = self.traces.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        traces.peek(id).cloned()
+    }
+
+    /// Disable tracing for a deleted queue/topic and drop its recorded traces.
+    pub fn forget(&self, subject: &str) {
+        self.enabled.remove(subject);
+        let mut traces = self.traces.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let ids: Vec<String> = traces.iter()
+            .filter(|(_, trace)| trace.subject == subject)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            traces.pop(&id);
+        }
+    }
+}
@@ -3,3 +3,25 @@ pub mod queue;
 #[path = "pub-sub/mod.rs"]
 pub mod pub_sub;
 pub mod stream;
+pub mod schema;
+pub mod payload;
+pub mod msgpack;
+pub mod dead_letter;
+pub mod message_trace;
+pub mod integrity;
+pub mod data_lock;
+pub mod ephemeral;
+pub mod names;
+pub mod labels;
+pub mod hooks;
+pub mod events;
+pub mod clock;
+pub mod durability;
+pub mod intent;
+pub mod activity;
+pub mod memory;
+pub mod rate;
+pub mod health;
+pub mod sys_topics;
+pub mod warm_start;
+pub mod error;
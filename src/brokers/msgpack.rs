@@ -0,0 +1,186 @@
+//! Minimal MessagePack decoder: turns `DATA_TYPE_MSGPACK` payloads into JSON
+//! for the dashboard and for schema validation. Binary and extension values
+//! have no JSON form and are rendered as `0x...` hex strings, like raw payloads.
+
+use serde_json::{Map, Number, Value};
+
+/// Nesting deeper than this is rejected rather than recursed into
+const MAX_DEPTH: usize = 128;
+
+/// Decode the single MessagePack value that makes up `data`. `None` if it is
+/// malformed, truncated, followed by trailing bytes or holds invalid UTF-8.
+pub fn decode(data: &[u8]) -> Option<Value> {
+    let mut reader = Reader { data, pos: 0 };
+    let value = reader.value(0)?;
+    (reader.pos == data.len()).then_some(value)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let marker = self.take_array::<1>()?[0];
+        let value = match marker {
+            0x00..=0x7f => Value::from(marker),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth)?,
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize)?,
+            0xc0 => Value::Null,
+            0xc1 => return None,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            // bin 8/16/32
+            0xc4..=0xc6 => {
+                let len = self.len(1 << (marker - 0xc4))?;
+                hex(self.take(len)?)
+            }
+            // ext 8/16/32
+            0xc7..=0xc9 => {
+                let len = self.len(1 << (marker - 0xc7))?;
+                self.ext(len)?
+            }
+            0xca => float(f32::from_be_bytes(self.take_array()?) as f64),
+            0xcb => float(f64::from_be_bytes(self.take_array()?)),
+            0xcc => Value::from(u8::from_be_bytes(self.take_array()?)),
+            0xcd => Value::from(u16::from_be_bytes(self.take_array()?)),
+            0xce => Value::from(u32::from_be_bytes(self.take_array()?)),
+            0xcf => Value::from(u64::from_be_bytes(self.take_array()?)),
+            0xd0 => Value::from(i8::from_be_bytes(self.take_array()?)),
+            0xd1 => Value::from(i16::from_be_bytes(self.take_array()?)),
+            0xd2 => Value::from(i32::from_be_bytes(self.take_array()?)),
+            0xd3 => Value::from(i64::from_be_bytes(self.take_array()?)),
+            // fixext 1/2/4/8/16
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4))?,
+            // str 8/16/32
+            0xd9..=0xdb => {
+                let len = self.len(1 << (marker - 0xd9))?;
+                self.str(len)?
+            }
+            0xdc | 0xdd => {
+                let len = self.len(if marker == 0xdc { 2 } else { 4 })?;
+                self.array(len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = self.len(if marker == 0xde { 2 } else { 4 })?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(marker as i8),
+        };
+        Some(value)
+    }
+
+    fn array(&mut self, len: usize, depth: usize) -> Option<Value> {
+        // `len` is untrusted: every element takes at least one byte
+        let mut items = Vec::with_capacity(len.min(self.remaining()));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Some(Value::Array(items))
+    }
+
+    /// JSON keys are strings: other keys are rendered as their JSON text.
+    fn map(&mut self, len: usize, depth: usize) -> Option<Value> {
+        let mut entries = Map::new();
+        for _ in 0..len {
+            let key = match self.value(depth + 1)? {
+                Value::String(key) => key,
+                other => other.to_string(),
+            };
+            entries.insert(key, self.value(depth + 1)?);
+        }
+        Some(Value::Object(entries))
+    }
+
+    fn str(&mut self, len: usize) -> Option<Value> {
+        let bytes = self.take(len)?;
+        std::str::from_utf8(bytes).ok().map(Value::from)
+    }
+
+    fn ext(&mut self, len: usize) -> Option<Value> {
+        let ext_type = i8::from_be_bytes(self.take_array()?);
+        let data = self.take(len)?;
+        Some(serde_json::json!({ "ext": ext_type, "data": hex(data) }))
+    }
+
+    /// Length field of `size` bytes (1, 2 or 4).
+    fn len(&mut self, size: usize) -> Option<usize> {
+        Some(match size {
+            1 => u8::from_be_bytes(self.take_array()?) as usize,
+            2 => u16::from_be_bytes(self.take_array()?) as usize,
+            _ => u32::from_be_bytes(self.take_array()?) as usize,
+        })
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let bytes = self.data.get(self.pos..end)?;
+        self.pos = end;
+        Some(bytes)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
+
+fn hex(bytes: &[u8]) -> Value {
+    Value::String(format!("0x{}", hex::encode(bytes)))
+}
+
+/// NaN and infinities have no JSON form
+fn float(f: f64) -> Value {
+    Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_nested_document() {
+        // {"id": 7, "tags": ["a", -1], "ok": true, "score": 1.5, "none": nil}
+        let data = [
+            0x85,
+            0xa2, b'i', b'd', 0x07,
+            0xa4, b't', b'a', b'g', b's', 0x92, 0xa1, b'a', 0xff,
+            0xa2, b'o', b'k', 0xc3,
+            0xa5, b's', b'c', b'o', b'r', b'e', 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0,
+            0xa4, b'n', b'o', b'n', b'e', 0xc0,
+        ];
+        assert_eq!(decode(&data), Some(json!({ "id": 7, "tags": ["a", -1], "ok": true, "score": 1.5, "none": null })));
+    }
+
+    #[test]
+    fn wide_integers_and_binary() {
+        assert_eq!(decode(&[0xcd, 0x01, 0x00]), Some(json!(256)));
+        assert_eq!(decode(&[0xd2, 0xff, 0xff, 0xff, 0xfe]), Some(json!(-2)));
+        assert_eq!(decode(&[0xc4, 0x02, 0xde, 0xad]), Some(json!("0xdead")));
+        assert_eq!(decode(&[0xd4, 0x05, 0x2a]), Some(json!({ "ext": 5, "data": "0x2a" })));
+        // Non-string keys become their JSON text
+        assert_eq!(decode(&[0x81, 0x01, 0xc2]), Some(json!({ "1": false })));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&[0xc1]), None, "Reserved marker");
+        assert_eq!(decode(&[0x92, 0x01]), None, "Truncated array");
+        assert_eq!(decode(&[0x01, 0x02]), None, "Trailing bytes");
+        assert_eq!(decode(&[0xa2, 0xff, 0xfe]), None, "Invalid UTF-8");
+        assert_eq!(decode(&[0xdd, 0xff, 0xff, 0xff, 0xff]), None, "Huge declared length");
+        let mut deep = vec![0x91; MAX_DEPTH + 1];
+        deep.push(0xc0);
+        assert_eq!(decode(&deep), None, "Too deep");
+    }
+}
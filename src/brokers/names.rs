@@ -0,0 +1,145 @@
+//! Resource names: one set of rules for queue names, stream topics, consumer
+//! groups and pub/sub topics, checked wherever a client names something new.
+//!
+//! Queue names, stream topics and consumer groups (part of their DLQ topic's
+//! name) become file and directory names, so they are a single level of
+//! `[A-Za-z0-9._-]` not starting with `.`, and two of them may not differ
+//! only in case (they would share files on macOS and Windows). Pub/sub topics are `/`-separated levels of printable characters;
+//! the `$nexo/` tree is reserved for the server's own publishes, clients may
+//! only subscribe to it. Existing resources are not re-checked: a name an
+//! older version accepted stays reachable, and deletable.
+
+use crate::brokers::error::{ErrorCode, NexoError};
+
+/// Bytes per level (file-backed names: the whole name)
+pub const MAX_LEVEL_LEN: usize = 200;
+/// Levels of a pub/sub topic or filter
+pub const MAX_DEPTH: usize = 16;
+pub const RESERVED_PREFIX: &str = "$nexo/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Queue,
+    StreamTopic,
+    StreamGroup,
+    /// A concrete topic (publish, alias, store publish target)
+    PubSubTopic,
+    /// A subscription filter: `+` and `#` allowed as whole levels, `#` last
+    PubSubFilter,
+}
+
+impl NameKind {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Queue => "queue name",
+            Self::StreamTopic => "stream topic",
+            Self::StreamGroup => "consumer group",
+            Self::PubSubTopic => "topic",
+            Self::PubSubFilter => "subscription filter",
+        }
+    }
+}
+
+/// `check`, as an `INVALID_NAME` error.
+pub fn validate(kind: NameKind, name: &str) -> Result<(), NexoError> {
+    check(kind, name).map_err(|e| NexoError::new(ErrorCode::InvalidName, e))
+}
+
+pub fn check(kind: NameKind, name: &str) -> Result<(), String> {
+    let invalid = |reason: String| Err(format!("Invalid {} '{}': {}", kind.label(), name, reason));
+    if name.is_empty() {
+        return Err(format!("The {} is empty", kind.label()));
+    }
+
+    match kind {
+        NameKind::Queue | NameKind::StreamTopic | NameKind::StreamGroup => {
+            if name.len() > MAX_LEVEL_LEN {
+                return invalid(format!("longer than {} bytes", MAX_LEVEL_LEN));
+            }
+            if name.starts_with('.') {
+                return invalid("cannot start with '.'".to_string());
+            }
+            if let Some(c) = name.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))) {
+                return invalid(format!("'{}' not allowed (letters, digits, '.', '_', '-')", c.escape_default()));
+            }
+        }
+        NameKind::PubSubTopic | NameKind::PubSubFilter => {
+            if kind == NameKind::PubSubTopic && name.starts_with(RESERVED_PREFIX) {
+                return invalid(format!("'{}' is reserved for the server", RESERVED_PREFIX));
+            }
+            let levels: Vec<&str> = name.split('/').collect();
+            if levels.len() > MAX_DEPTH {
+                return invalid(format!("more than {} levels", MAX_DEPTH));
+            }
+            for (i, level) in levels.iter().enumerate() {
+                check_level(kind, level, i + 1 == levels.len()).or_else(invalid)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_level(kind: NameKind, level: &str, last: bool) -> Result<(), String> {
+    if level.is_empty() {
+        return Err("empty level".to_string());
+    }
+    if level.len() > MAX_LEVEL_LEN {
+        return Err(format!("level longer than {} bytes", MAX_LEVEL_LEN));
+    }
+    if level.chars().any(char::is_control) {
+        return Err("control characters not allowed".to_string());
+    }
+    let wildcards = kind == NameKind::PubSubFilter;
+    if level.contains('#') && !(wildcards && level == "#" && last) {
+        return Err(if wildcards { "'#' must be the whole last level" } else { "wildcards are subscribe-only" }.to_string());
+    }
+    if level.contains('+') && !(wildcards && level == "+") {
+        return Err(if wildcards { "'+' must be a whole level" } else { "wildcards are subscribe-only" }.to_string());
+    }
+    Ok(())
+}
+
+/// `validate` a file-backed name about to be created, and refuse it if one
+/// of `existing` differs from it only in case.
+pub fn validate_new(kind: NameKind, name: &str, existing: impl IntoIterator<Item = String>) -> Result<(), NexoError> {
+    validate(kind, name)?;
+    match existing.into_iter().find(|other| other != name && other.eq_ignore_ascii_case(name)) {
+        Some(other) => Err(NexoError::new(
+            ErrorCode::InvalidName,
+            format!("Invalid {} '{}': differs from '{}' only in case", kind.label(), name, other),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_backed_names_are_one_safe_level() {
+        for name in ["orders", "orders.eu-1", "a_b", "topic.group.dlq"] {
+            assert!(check(NameKind::Queue, name).is_ok(), "{}", name);
+            assert!(check(NameKind::StreamTopic, name).is_ok(), "{}", name);
+        }
+        for name in ["", "a/b", "..", ".hidden", "a b", "caf\u{e9}", "a:b", &"x".repeat(MAX_LEVEL_LEN + 1)] {
+            assert!(check(NameKind::Queue, name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn pubsub_levels_and_reserved_tree() {
+        assert!(check(NameKind::PubSubTopic, "home/kitchen/temp").is_ok());
+        assert!(check(NameKind::PubSubTopic, "caf\u{e9}/men\u{fc}").is_ok(), "Any printable character");
+        for topic in ["a//b", "/a", "a/", "a/+", "a/#", "$nexo/alerts/x", "a\nb", &vec!["l"; MAX_DEPTH + 1].join("/")] {
+            assert!(check(NameKind::PubSubTopic, topic).is_err(), "{:?}", topic);
+        }
+
+        for filter in ["#", "+/+", "a/+/#", "$nexo/alerts/dlq/+"] {
+            assert!(check(NameKind::PubSubFilter, filter).is_ok(), "{}", filter);
+        }
+        let err = check(NameKind::PubSubFilter, "a/#/b").unwrap_err();
+        assert!(err.contains("'#' must be the whole last level"), "{}", err);
+        assert!(check(NameKind::PubSubFilter, "sp+rts").is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! Payload rendering: a protocol payload (`[DataType: 1 byte][Data...]`) shown
+//! as JSON, for the dashboard, replay and backfill transforms and reports;
+//! or carried verbatim in a JSON document as base64.
+
+use serde_json::Value;
+
+use crate::brokers::msgpack;
+use crate::brokers::queue::blob;
+use crate::transport::tcp::protocol::{
+    DATA_TYPE_JSON, DATA_TYPE_MSGPACK, DATA_TYPE_PROTOBUF, DATA_TYPE_RAW, DATA_TYPE_STRING,
+};
+
+/// Converts a protocol-compliant data payload into a serde_json::Value for HTTP/JSON consumption.
+/// Format: [DataType: 1 byte][Data...]
+pub fn payload_to_json_value(payload: &[u8]) -> Value {
+    render_payload(payload).0
+}
+
+/// The payload as JSON plus its content type (`json`, `text`, `binary`,
+/// `msgpack`, `protobuf` or `blob`), read from the leading data type byte.
+/// Bytes that can't be shown as text are rendered as `0x...` hex, never lossily.
+pub fn render_payload(payload: &[u8]) -> (Value, &'static str) {
+    if let Some((id, size)) = blob::parse_reference(payload) {
+        return (serde_json::json!({ "blob": id.to_string(), "size": size }), "blob");
+    }
+    let Some((&data_type, content)) = payload.split_first() else {
+        return (Value::Null, "binary");
+    };
+
+    match data_type {
+        DATA_TYPE_JSON => match serde_json::from_slice(content) {
+            Ok(value) => (value, "json"),
+            Err(_) => (text_or_hex(content), "json"),
+        },
+        DATA_TYPE_STRING => (text_or_hex(content), "text"),
+        DATA_TYPE_RAW => (hex(content), "binary"),
+        DATA_TYPE_MSGPACK => (msgpack::decode(content).unwrap_or_else(|| hex(content)), "msgpack"),
+        // No descriptor to decode it with
+        DATA_TYPE_PROTOBUF => (hex(content), "protobuf"),
+        // Untyped payload from a client that doesn't prefix a data type
+        _ => match serde_json::from_slice(payload) {
+            Ok(value) => (value, "json"),
+            Err(_) => match std::str::from_utf8(payload) {
+                Ok(text) => (Value::from(text), "text"),
+                Err(_) => (hex(payload), "binary"),
+            },
+        },
+    }
+}
+
+fn text_or_hex(content: &[u8]) -> Value {
+    std::str::from_utf8(content).map(Value::from).unwrap_or_else(|_| hex(content))
+}
+
+fn hex(content: &[u8]) -> Value {
+    Value::String(format!("0x{}", hex::encode(content)))
+}
+
+/// Serde for raw payload bytes as a base64 string, where they must survive
+/// a JSON document byte for byte (topic dumps, dead letter envelopes).
+pub mod base64_payload {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payload: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(payload))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        STANDARD.decode(s).map(Bytes::from).map_err(serde::de::Error::custom)
+    }
+}
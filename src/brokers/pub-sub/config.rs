@@ -6,6 +6,43 @@ pub struct PubSubConfig {
     pub default_retained_ttl_seconds: u64,
     pub cleanup_interval_seconds: u64,
     pub retained_flush_ms: u64,
+    pub max_payload_bytes: usize,
+    pub max_topic_aliases: usize,
+    /// Topics whose matched subscriber set is cached for publish (0 = disabled)
+    pub match_cache_size: usize,
+    /// Publishes matching at least this many subscribers fan out in parallel (0 = never)
+    pub fanout_threshold: usize,
+    pub fanout_workers: usize,
+    /// Retained topics kept per topic root (0 = unlimited)
+    pub max_retained_per_root: usize,
+    /// Retained payload bytes kept per topic root (0 = unlimited)
+    pub max_retained_bytes_per_root: usize,
+    /// Largest payload a publish may retain (0 = only `max_payload_bytes`)
+    pub max_retained_payload_bytes: usize,
+    /// What a retained publish over a root's limits does
+    pub retained_eviction: RetainedEviction,
+}
+
+/// Policy when a root is at its retained limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetainedEviction {
+    /// The publish fails; what is retained stays
+    #[default]
+    Reject,
+    /// The root's least recently retained topics are dropped to make room
+    Lru,
+}
+
+impl std::str::FromStr for RetainedEviction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "lru" => Ok(Self::Lru),
+            other => Err(format!("unknown retained eviction policy '{}'", other)),
+        }
+    }
 }
 
 impl Default for PubSubConfig {
@@ -15,6 +52,15 @@ impl Default for PubSubConfig {
             default_retained_ttl_seconds: 3600,
             cleanup_interval_seconds: 60,
             retained_flush_ms: 500,
+            max_payload_bytes: 10485760, // 10MB
+            max_topic_aliases: 256,
+            match_cache_size: 4096,
+            fanout_threshold: 10_000,
+            fanout_workers: 4,
+            max_retained_per_root: 100_000,
+            max_retained_bytes_per_root: 268435456, // 256MB
+            max_retained_payload_bytes: 1048576, // 1MB
+            retained_eviction: RetainedEviction::Reject,
         }
     }
 }
@@ -27,6 +73,15 @@ impl PubSubConfig {
             default_retained_ttl_seconds: get_env("PUBSUB_DEFAULT_RETAINED_TTL_SECS", default.default_retained_ttl_seconds),
             cleanup_interval_seconds: get_env("PUBSUB_CLEANUP_INTERVAL_SECS", default.cleanup_interval_seconds),
             retained_flush_ms: get_env("PUBSUB_RETAINED_FLUSH_MS", default.retained_flush_ms),
+            max_payload_bytes: get_env("PUBSUB_MAX_PAYLOAD_BYTES", default.max_payload_bytes),
+            max_topic_aliases: get_env("PUBSUB_MAX_TOPIC_ALIASES", default.max_topic_aliases),
+            match_cache_size: get_env("PUBSUB_MATCH_CACHE_SIZE", default.match_cache_size),
+            fanout_threshold: get_env("PUBSUB_FANOUT_THRESHOLD", default.fanout_threshold),
+            fanout_workers: get_env("PUBSUB_FANOUT_WORKERS", default.fanout_workers),
+            max_retained_per_root: get_env("PUBSUB_MAX_RETAINED_PER_ROOT", default.max_retained_per_root),
+            max_retained_bytes_per_root: get_env("PUBSUB_MAX_RETAINED_BYTES_PER_ROOT", default.max_retained_bytes_per_root),
+            max_retained_payload_bytes: get_env("PUBSUB_MAX_RETAINED_PAYLOAD_BYTES", default.max_retained_payload_bytes),
+            retained_eviction: get_env("PUBSUB_RETAINED_EVICTION", default.retained_eviction),
         }
     }
 }
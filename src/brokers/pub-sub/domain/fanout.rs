@@ -0,0 +1,112 @@
+//! Parallel fan-out for topics with very large subscriber sets.
+//!
+//! Sends are sharded over a fixed pool of workers by subscriber id, so every
+//! message for a given subscriber goes through the same FIFO worker and
+//! per-subscriber ordering holds. While any job is still queued, smaller
+//! publishes go through the pool too instead of overtaking it inline.
+
+use std::hash::BuildHasher;
+use std::collections::hash_map::RandomState;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+
+use super::types::{ClientId, ClientRegistry, PubSubMessage, Route};
+
+struct FanoutJob {
+    msg: Arc<PubSubMessage>,
+    routes: Arc<[Route]>,
+    publisher: Option<ClientId>,
+    retained: bool,
+    /// Positions in `routes` owned by this worker
+    indices: Vec<u32>,
+    /// Publish confirm: receives the number of subscribers reached once done
+    done: Option<oneshot::Sender<usize>>,
+}
+
+pub(crate) struct FanoutPool {
+    workers: Vec<mpsc::UnboundedSender<FanoutJob>>,
+    pending: Arc<AtomicUsize>,
+    hasher: RandomState,
+}
+
+impl FanoutPool {
+    /// `None` when `workers` is 0: every publish sends inline.
+    pub(crate) fn new(workers: usize, clients: ClientRegistry) -> Option<Self> {
+        if workers == 0 {
+            return None;
+        }
+        let pending = Arc::new(AtomicUsize::new(0));
+        let senders = (0..workers)
+            .map(|_| {
+                let (tx, mut rx) = mpsc::unbounded_channel::<FanoutJob>();
+                let clients = clients.clone();
+                let pending = pending.clone();
+                tokio::spawn(async move {
+                    while let Some(job) = rx.recv().await {
+                        // Dead senders are left to the connection's own disconnect
+                        let mut delivered = 0;
+                        for &i in &job.indices {
+                            let route = &job.routes[i as usize];
+                            let Some(msg) = route.deliver(&job.msg, job.publisher.as_ref(), job.retained) else { continue };
+                            if let Some(info) = clients.get(&route.client) {
+                                if info.sender.send(msg).is_ok() {
+                                    delivered += 1;
+                                }
+                            }
+                        }
+                        if let Some(done) = job.done {
+                            let _ = done.send(delivered);
+                        }
+                        pending.fetch_sub(1, Ordering::Release);
+                    }
+                });
+                tx
+            })
+            .collect();
+
+        Some(Self { workers: senders, pending, hasher: RandomState::new() })
+    }
+
+    /// Jobs queued or running; inline sends must wait their turn while non-zero.
+    pub(crate) fn is_busy(&self) -> bool {
+        self.pending.load(Ordering::Acquire) > 0
+    }
+
+    /// Queue `msg` for every client in `routes`, returning how many were queued.
+    /// With `confirm`, also one receiver per job, resolved with the number of
+    /// subscribers the job reached.
+    pub(crate) fn dispatch(
+        &self,
+        msg: Arc<PubSubMessage>,
+        routes: Arc<[Route]>,
+        publisher: Option<ClientId>,
+        retained: bool,
+        confirm: bool,
+    ) -> (usize, Vec<oneshot::Receiver<usize>>) {
+        let mut shards: Vec<Vec<u32>> = vec![Vec::new(); self.workers.len()];
+        for (i, route) in routes.iter().enumerate() {
+            let shard = self.hasher.hash_one(&route.client) as usize % self.workers.len();
+            shards[shard].push(i as u32);
+        }
+
+        let mut confirms = Vec::new();
+        for (worker, indices) in self.workers.iter().zip(shards) {
+            if indices.is_empty() {
+                continue;
+            }
+            let done = confirm.then(|| {
+                let (tx, rx) = oneshot::channel();
+                confirms.push(rx);
+                tx
+            });
+            self.pending.fetch_add(1, Ordering::Release);
+            let job = FanoutJob { msg: msg.clone(), routes: routes.clone(), publisher: publisher.clone(), retained, indices, done };
+            if worker.send(job).is_err() {
+                self.pending.fetch_sub(1, Ordering::Release);
+            }
+        }
+        (routes.len(), confirms)
+    }
+}
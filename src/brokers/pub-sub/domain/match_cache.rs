@@ -0,0 +1,61 @@
+//! Publish-side routing cache: concrete topic -> routes, one per subscriber.
+//!
+//! Matching a topic walks every `+`/`#` branch of the tree, which dominates
+//! publish cost in wildcard-heavy deployments. Results are kept in a bounded
+//! LRU and the whole cache is dropped whenever the subscription set changes.
+//! Callers read and fill it under the tree read lock and clear it under the
+//! write lock, so a result computed from an older tree is never stored.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use super::types::Route;
+use crate::brokers::pub_sub::snapshot::MatchCacheSnapshot;
+
+pub(crate) struct MatchCache {
+    entries: Mutex<LruCache<String, Arc<[Route]>>>,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MatchCache {
+    /// `None` when `capacity` is 0: every publish walks the tree.
+    pub(crate) fn new(capacity: usize) -> Option<Self> {
+        let size = NonZeroUsize::new(capacity)?;
+        Some(Self {
+            entries: Mutex::new(LruCache::new(size)),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn get(&self, topic: &str) -> Option<Arc<[Route]>> {
+        let hit = self.entries.lock().get(topic).cloned();
+        let counter = if hit.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    pub(crate) fn put(&self, topic: &str, routes: Arc<[Route]>) {
+        self.entries.lock().put(topic.to_string(), routes);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    pub(crate) fn snapshot(&self) -> MatchCacheSnapshot {
+        MatchCacheSnapshot {
+            capacity: self.capacity,
+            entries: self.entries.lock().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
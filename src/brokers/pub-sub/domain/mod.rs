@@ -1,4 +1,7 @@
 pub mod types;
 pub mod radix_tree;
 pub mod retained;
+pub mod retained_index;
 pub mod persistence;
+pub mod match_cache;
+pub mod fanout;
@@ -1,4 +1,8 @@
 //! SQLite persistence for retained messages.
+//!
+//! Each flush rewrites the full retained set inside a single transaction, so a
+//! crash leaves either the previous or the new snapshot on disk, never a mix.
+//! WAL + `synchronous = FULL` makes every committed flush durable.
 
 use bytes::Bytes;
 use rusqlite::{params, Connection};
@@ -11,6 +15,10 @@ pub(crate) fn init_db(path: &str) -> std::result::Result<Connection, rusqlite::E
     }
 
     let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = FULL;"
+    )?;
     conn.execute(
         "CREATE TABLE IF NOT EXISTS retained (
             path TEXT PRIMARY KEY,
@@ -1,11 +1,11 @@
 //! PubSub Radix Tree Node: Topic routing data structure
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use bytes::Bytes;
 use crate::brokers::pub_sub::snapshot::TopicSnapshot;
 
 use super::retained::RetainedMessage;
-use super::types::ClientId;
+use super::types::{ClientId, SubscriptionOptions};
 
 pub(crate) struct Node {
     // Exact match children: "kitchen" -> Node
@@ -18,8 +18,8 @@ pub(crate) struct Node {
     // Wildcard '#' child: matches everything remaining
     // Note: Used for routing messages TO subscribers who used '#'
     pub(crate) hash_child: Option<Box<Node>>,
-    /// Only stores ClientIds. Sender resolution happens via shared DashMap at publish time.
-    pub(crate) subscribers: HashSet<ClientId>,
+    /// ClientIds with their subscription options. Sender resolution happens via shared DashMap at publish time.
+    pub(crate) subscribers: HashMap<ClientId, SubscriptionOptions>,
     pub(crate) retained: Option<RetainedMessage>,
 }
 
@@ -29,7 +29,7 @@ impl Node {
             children: HashMap::new(),
             plus_child: None,
             hash_child: None,
-            subscribers: HashSet::new(),
+            subscribers: HashMap::new(),
             retained: None,
         }
     }
@@ -42,15 +42,16 @@ impl Node {
             && self.retained.is_none()
     }
 
-    pub(crate) fn insert_subscriber(&mut self, parts: &[String], client: &ClientId) {
+    /// Returns false when the client was already subscribed to this pattern
+    /// (its options are replaced).
+    pub(crate) fn insert_subscriber(&mut self, parts: &[String], client: &ClientId, options: SubscriptionOptions) -> bool {
         let mut current = self;
         for (i, part) in parts.iter().enumerate() {
             if part == "#" {
                 if current.hash_child.is_none() {
                     current.hash_child = Some(Box::new(Node::new()));
                 }
-                current.hash_child.as_mut().unwrap().subscribers.insert(client.clone());
-                return;
+                return current.hash_child.as_mut().unwrap().subscribers.insert(client.clone(), options).is_none();
             } else if part == "+" {
                 if current.plus_child.is_none() {
                     current.plus_child = Some(Box::new(Node::new()));
@@ -60,7 +61,7 @@ impl Node {
                 current = current.children.entry(part.clone()).or_insert_with(Node::new);
             }
         }
-        current.subscribers.insert(client.clone());
+        current.subscribers.insert(client.clone(), options).is_none()
     }
 
     pub(crate) fn remove_subscriber(&mut self, parts: &[String], client: &ClientId) -> bool {
@@ -106,17 +107,17 @@ impl Node {
         self.is_empty()
     }
 
-    pub(crate) fn match_subscribers(&self, parts: &[String], results: &mut Vec<ClientId>) {
+    pub(crate) fn match_subscribers(&self, parts: &[String], results: &mut Vec<(ClientId, SubscriptionOptions)>) {
         // "#" matches everything from here
         if let Some(hash_node) = &self.hash_child {
-            for client in &hash_node.subscribers {
-                results.push(client.clone());
+            for (client, options) in &hash_node.subscribers {
+                results.push((client.clone(), *options));
             }
         }
 
         if parts.is_empty() {
-            for client in &self.subscribers {
-                results.push(client.clone());
+            for (client, options) in &self.subscribers {
+                results.push((client.clone(), *options));
             }
             return;
         }
@@ -193,35 +194,29 @@ impl Node {
         }
     }
 
-    pub(crate) fn cleanup_expired_retained(&mut self) -> bool {
-        let mut cleaned = false;
-        
-        if let Some(retained) = &self.retained {
-            if retained.is_expired() {
-                self.retained = None;
-                cleaned = true;
-            }
+    /// Drop every retained message at or below this node; returns how many.
+    pub(crate) fn clear_retained(&mut self) -> usize {
+        let own = usize::from(self.retained.take().is_some());
+        own + self.children.values_mut().map(Node::clear_retained).sum::<usize>()
+    }
+
+    /// Drop expired retained messages, pushing the path of each to `removed`.
+    pub(crate) fn cleanup_expired_retained(&mut self, current_path: &str, removed: &mut Vec<String>) {
+        if self.retained.as_ref().is_some_and(RetainedMessage::is_expired) {
+            self.retained = None;
+            removed.push(current_path.to_string());
         }
-        
-        for child in self.children.values_mut() {
-            if child.cleanup_expired_retained() {
-                cleaned = true;
-            }
+
+        let join = |key: &str| if current_path.is_empty() { key.to_string() } else { format!("{}/{}", current_path, key) };
+        for (key, child) in self.children.iter_mut() {
+            child.cleanup_expired_retained(&join(key), removed);
         }
-        
         if let Some(ref mut plus_child) = self.plus_child {
-            if plus_child.cleanup_expired_retained() {
-                cleaned = true;
-            }
+            plus_child.cleanup_expired_retained(&join("+"), removed);
         }
-        
         if let Some(ref mut hash_child) = self.hash_child {
-            if hash_child.cleanup_expired_retained() {
-                cleaned = true;
-            }
+            hash_child.cleanup_expired_retained(&join("#"), removed);
         }
-        
-        cleaned
     }
 
     pub(crate) fn collect_filtered_topics(&self, base_path: &str, search: Option<&str>, topics: &mut Vec<TopicSnapshot>) {
@@ -0,0 +1,168 @@
+//! Per-root accounting of retained messages, enforcing the retained limits.
+//!
+//! The tree holds the messages; this index mirrors which topics of each root
+//! retain one and how large it is, least recently retained first, so a
+//! publish is checked against its root's limits without walking the tree.
+//! The manager updates both under the tree write lock.
+
+use std::collections::HashMap;
+
+use lru::LruCache;
+
+use crate::brokers::pub_sub::config::{PubSubConfig, RetainedEviction};
+use crate::brokers::pub_sub::snapshot::RetainedSnapshot;
+use crate::brokers::error::{ErrorCode, NexoError};
+
+struct RootUsage {
+    /// Topic -> payload bytes
+    topics: LruCache<String, usize>,
+    bytes: usize,
+}
+
+pub(crate) struct RetainedIndex {
+    max_topics: usize,
+    max_bytes: usize,
+    max_payload: usize,
+    eviction: RetainedEviction,
+    roots: HashMap<String, RootUsage>,
+    evictions: u64,
+    rejections: u64,
+}
+
+impl RetainedIndex {
+    pub(crate) fn new(config: &PubSubConfig) -> Self {
+        Self {
+            max_topics: config.max_retained_per_root,
+            max_bytes: config.max_retained_bytes_per_root,
+            max_payload: config.max_retained_payload_bytes,
+            eviction: config.retained_eviction,
+            roots: HashMap::new(),
+            evictions: 0,
+            rejections: 0,
+        }
+    }
+
+    /// Account `topic` retaining `size` bytes, replacing what it retained.
+    /// Returns the topics evicted to make room (to drop from the tree), or
+    /// the error to answer when the root can't take it.
+    pub(crate) fn admit(&mut self, topic: &str, size: usize) -> Result<Vec<String>, NexoError> {
+        let (max_topics, max_bytes) = (self.max_topics, self.max_bytes);
+        let over = |topics: usize, bytes: usize| (max_topics > 0 && topics > max_topics) || (max_bytes > 0 && bytes > max_bytes);
+
+        let too_large = if self.max_payload > 0 && size > self.max_payload {
+            Some(self.max_payload)
+        } else if max_bytes > 0 && size > max_bytes {
+            Some(max_bytes)
+        } else {
+            None
+        };
+        if let Some(limit) = too_large {
+            self.rejections += 1;
+            return Err(NexoError::new(
+                ErrorCode::PayloadTooLarge,
+                format!("Retained payload of {} bytes is over the limit of {}", size, limit),
+            ));
+        }
+
+        let root = topic_root(topic);
+        let usage = self.roots.entry(root.to_string()).or_insert_with(|| RootUsage { topics: LruCache::unbounded(), bytes: 0 });
+        let previous = usage.topics.peek(topic).copied();
+        let mut evicted = Vec::new();
+
+        if over(usage.topics.len() + usize::from(previous.is_none()), usage.bytes - previous.unwrap_or(0) + size) {
+            if self.eviction == RetainedEviction::Reject {
+                self.rejections += 1;
+                return Err(NexoError::invalid(format!(
+                    "Retained limit reached for root '{}' ({} topics, {} bytes retained)",
+                    root, usage.topics.len(), usage.bytes,
+                )));
+            }
+            if let Some(previous) = usage.topics.pop(topic) {
+                usage.bytes -= previous;
+            }
+            while over(usage.topics.len() + 1, usage.bytes + size) {
+                let Some((old, old_size)) = usage.topics.pop_lru() else { break };
+                usage.bytes -= old_size;
+                evicted.push(old);
+            }
+            self.evictions += evicted.len() as u64;
+        }
+
+        if let Some(previous) = usage.topics.put(topic.to_string(), size) {
+            usage.bytes -= previous;
+        }
+        usage.bytes += size;
+        Ok(evicted)
+    }
+
+    /// `topic` no longer retains anything (cleared, expired or evicted).
+    pub(crate) fn remove(&mut self, topic: &str) {
+        let root = topic_root(topic);
+        let Some(usage) = self.roots.get_mut(root) else { return };
+        if let Some(size) = usage.topics.pop(topic) {
+            usage.bytes -= size;
+        }
+        if usage.topics.is_empty() {
+            self.roots.remove(root);
+        }
+    }
+
+    pub(crate) fn clear_root(&mut self, root: &str) {
+        self.roots.remove(root);
+    }
+
+    pub(crate) fn snapshot(&self) -> RetainedSnapshot {
+        RetainedSnapshot {
+            topics: self.roots.values().map(|usage| usage.topics.len()).sum(),
+            bytes: self.roots.values().map(|usage| usage.bytes).sum(),
+            evictions: self.evictions,
+            rejections: self.rejections,
+        }
+    }
+}
+
+fn topic_root(topic: &str) -> &str {
+    topic.split('/').next().unwrap_or(topic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(max_topics: usize, max_bytes: usize, eviction: RetainedEviction) -> RetainedIndex {
+        RetainedIndex::new(&PubSubConfig {
+            max_retained_per_root: max_topics,
+            max_retained_bytes_per_root: max_bytes,
+            max_retained_payload_bytes: 0,
+            retained_eviction: eviction,
+            ..PubSubConfig::default()
+        })
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_retained_of_the_root() {
+        let mut index = index(2, 0, RetainedEviction::Lru);
+        index.admit("a/1", 1).unwrap();
+        index.admit("a/2", 1).unwrap();
+        index.admit("b/1", 1).unwrap();
+        index.admit("a/1", 1).unwrap(); // refreshed: a/2 is now the oldest
+
+        assert_eq!(index.admit("a/3", 1).unwrap(), vec!["a/2".to_string()]);
+        assert_eq!(index.snapshot().topics, 3, "Other roots are untouched");
+        assert_eq!(index.snapshot().evictions, 1);
+    }
+
+    #[test]
+    fn byte_budget_and_reject_policy() {
+        let mut index = index(0, 10, RetainedEviction::Reject);
+        index.admit("a/1", 6).unwrap();
+        assert_eq!(index.admit("a/2", 6).unwrap_err().code, ErrorCode::InvalidRequest);
+        index.admit("a/1", 10).unwrap(); // replacing frees the old size
+        assert_eq!(index.admit("a/1", 11).unwrap_err().code, ErrorCode::PayloadTooLarge);
+
+        index.remove("a/1");
+        index.admit("a/2", 6).unwrap();
+        let snapshot = index.snapshot();
+        assert_eq!((snapshot.topics, snapshot.bytes, snapshot.rejections), (1, 6, 2));
+    }
+}
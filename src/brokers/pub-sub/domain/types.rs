@@ -1,25 +1,99 @@
 //! PubSub Types: Public types used across PubSub modules
 
 use std::sync::{Arc, OnceLock};
-use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
 use bytes::{Bytes, BytesMut, BufMut};
 use tokio::sync::mpsc;
 use dashmap::DashMap;
 
+use crate::transport::tcp::protocol::{PUSH_FLAG_RETAIN, PUSH_FLAG_SUBSCRIPTION_IDS, PUSH_FLAG_UNSUBSCRIBED};
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ClientId(pub String);
 
 pub struct ClientInfo {
     pub sender: mpsc::UnboundedSender<Arc<PubSubMessage>>,
     pub subscriptions: HashSet<String>,
+    /// Topic aliases registered by this connection (alias -> topic).
+    pub aliases: HashMap<u16, String>,
+    /// Idle TTL of the subscriptions that have one (pattern -> TTL)
+    pub idle_ttls: HashMap<String, Duration>,
+    /// Since when (ms) the connection's socket has not taken pushes off its channel
+    pub stalled_since: Option<u64>,
 }
 
 pub type ClientRegistry = Arc<DashMap<ClientId, ClientInfo>>;
 
+/// Which retained messages a new subscription receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetainHandling {
+    #[default]
+    SendOnSubscribe,
+    /// Only when the subscription did not exist yet (not on a re-subscribe)
+    SendIfNew,
+    DontSend,
+}
+
+/// Per-subscription options, stored with the subscriber in the topic tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubscriptionOptions {
+    /// Returned with every message delivered through this subscription
+    pub id: Option<u32>,
+    /// Skip messages published by the subscribing connection itself
+    pub no_local: bool,
+    /// Keep the retain flag of live messages (otherwise only retained
+    /// messages replayed on subscribe carry it)
+    pub retain_as_published: bool,
+    pub retain_handling: RetainHandling,
+}
+
+/// A client matched by a topic, with every subscription of that client the
+/// topic matched (overlapping filters still deliver once).
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub client: ClientId,
+    pub subscriptions: Vec<SubscriptionOptions>,
+}
+
+impl Route {
+    /// The message this client receives, `None` when every matching
+    /// subscription is `no_local` and the client published it.
+    pub fn deliver(&self, msg: &Arc<PubSubMessage>, publisher: Option<&ClientId>, retained: bool) -> Option<Arc<PubSubMessage>> {
+        let local = publisher == Some(&self.client);
+        let mut matched = false;
+        let mut retain = false;
+        let mut ids = Vec::new();
+        for sub in self.subscriptions.iter().filter(|sub| !(local && sub.no_local)) {
+            matched = true;
+            retain |= retained && sub.retain_as_published;
+            ids.extend(sub.id);
+        }
+        if !matched {
+            return None;
+        }
+        if ids.is_empty() && !retain {
+            return Some(msg.clone());
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        Some(Arc::new(msg.with_delivery(ids, retain)))
+    }
+}
+
 #[derive(Debug)]
 pub struct PubSubMessage {
     pub topic: String,
     pub payload: Bytes,
+    /// Carries the retain flag to the subscriber (see `SubscriptionOptions`)
+    pub retain: bool,
+    /// Ids of the receiving client's subscriptions that matched
+    pub subscription_ids: Vec<u32>,
+    /// Not a message: the server dropped the subscription named by `topic`
+    /// (the payload is the reason, as a string)
+    pub unsubscribed: bool,
+    /// Dropped instead of sent once past this (publish `expiryMs`)
+    pub expires_at: Option<Instant>,
     network_cache: OnceLock<Bytes>,
 }
 
@@ -28,14 +102,70 @@ impl PubSubMessage {
         Self {
             topic,
             payload,
+            retain: false,
+            subscription_ids: Vec::new(),
+            unsubscribed: false,
+            expires_at: None,
             network_cache: OnceLock::new(),
         }
     }
 
+    /// Tells the client its subscription to `pattern` was dropped.
+    pub fn unsubscribed(pattern: String, reason: &str) -> Self {
+        let mut payload = BytesMut::with_capacity(4 + reason.len());
+        payload.put_u32(reason.len() as u32);
+        payload.put_slice(reason.as_bytes());
+        Self { unsubscribed: true, ..Self::new(pattern, payload.freeze()) }
+    }
+
+    /// Copy of the message for one subscriber.
+    pub fn with_delivery(&self, subscription_ids: Vec<u32>, retain: bool) -> Self {
+        Self {
+            topic: self.topic.clone(),
+            payload: self.payload.clone(),
+            retain,
+            subscription_ids,
+            unsubscribed: false,
+            expires_at: self.expires_at,
+            network_cache: OnceLock::new(),
+        }
+    }
+
+    /// Expire the message `expiry` from now.
+    pub fn expiring(self, expiry: Option<Duration>) -> Self {
+        Self { expires_at: expiry.map(|expiry| Instant::now() + expiry), ..self }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// Meta byte of the push frame.
+    pub fn push_flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.retain {
+            flags |= PUSH_FLAG_RETAIN;
+        }
+        if !self.subscription_ids.is_empty() {
+            flags |= PUSH_FLAG_SUBSCRIPTION_IDS;
+        }
+        if self.unsubscribed {
+            flags |= PUSH_FLAG_UNSUBSCRIBED;
+        }
+        flags
+    }
+
     pub fn get_network_packet(&self) -> &Bytes {
         self.network_cache.get_or_init(|| {
             let topic_len = self.topic.len();
-            let mut buf = BytesMut::with_capacity(4 + topic_len + self.payload.len());
+            let ids_len = if self.subscription_ids.is_empty() { 0 } else { 2 + 4 * self.subscription_ids.len() };
+            let mut buf = BytesMut::with_capacity(ids_len + 4 + topic_len + self.payload.len());
+            if ids_len > 0 {
+                buf.put_u16(self.subscription_ids.len() as u16);
+                for id in &self.subscription_ids {
+                    buf.put_u32(*id);
+                }
+            }
             buf.put_u32(topic_len as u32);
             buf.put_slice(self.topic.as_bytes());
             buf.put_slice(&self.payload);
@@ -7,8 +7,8 @@ use axum::Router;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::brokers::pub_sub::snapshot::{PubSubSnapshot, WildcardSubscription, WildcardSubscriptions};
-use crate::transport::http::payload::payload_to_json_value;
+use crate::brokers::pub_sub::snapshot::{MatchCacheSnapshot, PubSubSnapshot, RetainedSnapshot, WildcardSubscription, WildcardSubscriptions};
+use crate::brokers::payload::render_payload;
 use crate::NexoEngine;
 
 const PUBSUB_PAGE_SIZE: usize = 50;
@@ -24,6 +24,36 @@ pub struct PubSubBrokerSnapshot {
     pub total_topics: usize,
     pub topics: Vec<TopicSummary>,
     pub wildcards: WildcardSubscriptionsDto,
+    pub match_cache: MatchCacheSummary,
+    pub retained: RetainedSummary,
+}
+
+#[derive(Serialize)]
+pub struct MatchCacheSummary {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl From<MatchCacheSnapshot> for MatchCacheSummary {
+    fn from(m: MatchCacheSnapshot) -> Self {
+        Self { capacity: m.capacity, entries: m.entries, hits: m.hits, misses: m.misses }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RetainedSummary {
+    pub topics: usize,
+    pub bytes: usize,
+    pub evictions: u64,
+    pub rejections: u64,
+}
+
+impl From<RetainedSnapshot> for RetainedSummary {
+    fn from(r: RetainedSnapshot) -> Self {
+        Self { topics: r.topics, bytes: r.bytes, evictions: r.evictions, rejections: r.rejections }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -31,6 +61,7 @@ pub struct TopicSummary {
     pub full_path: String,
     pub subscribers: usize,
     pub retained_value: Option<Value>,
+    pub retained_content_type: Option<&'static str>,
 }
 
 #[derive(Serialize, Clone)]
@@ -65,12 +96,18 @@ impl From<PubSubSnapshot> for PubSubBrokerSnapshot {
         Self {
             active_clients: s.active_clients,
             total_topics: s.total_topics,
-            topics: s.topics.into_iter().map(|topic| TopicSummary {
-                full_path: topic.full_path,
-                subscribers: topic.subscribers,
-                retained_value: topic.retained_payload.as_ref().map(|p| payload_to_json_value(p)),
+            topics: s.topics.into_iter().map(|topic| {
+                let (retained_value, retained_content_type) = topic.retained_payload.as_deref().map(render_payload).unzip();
+                TopicSummary {
+                    full_path: topic.full_path,
+                    subscribers: topic.subscribers,
+                    retained_value,
+                    retained_content_type,
+                }
             }).collect(),
             wildcards: s.wildcards.into(),
+            match_cache: s.match_cache.into(),
+            retained: s.retained.into(),
         }
     }
 }
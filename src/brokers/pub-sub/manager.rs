@@ -1,40 +1,96 @@
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::Duration;
 use bytes::Bytes;
 use dashmap::DashMap;
-use parking_lot::RwLock;
-use tokio::sync::mpsc;
-use std::collections::HashSet;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::{mpsc, oneshot};
+use std::collections::{HashMap, HashSet};
 
+use crate::brokers::activity::ActivityTracker;
+use crate::brokers::clock::{self, SharedClock};
+use crate::brokers::memory::{BudgetHandle, MemoryBudget};
 use crate::brokers::pub_sub::config::PubSubConfig;
+use crate::brokers::pub_sub::domain::fanout::FanoutPool;
+use crate::brokers::pub_sub::domain::match_cache::MatchCache;
 use crate::brokers::pub_sub::domain::persistence;
+use crate::brokers::names::{self, NameKind};
 use crate::brokers::pub_sub::domain::radix_tree::Node;
 use crate::brokers::pub_sub::domain::retained::RetainedMessage;
+use crate::brokers::pub_sub::domain::retained_index::RetainedIndex;
 use crate::brokers::pub_sub::snapshot::{PubSubSnapshot, WildcardSubscription, WildcardSubscriptions};
-use crate::brokers::pub_sub::{ClientId, ClientInfo, ClientRegistry, PubSubMessage};
+use crate::brokers::pub_sub::{ClientId, ClientInfo, ClientRegistry, PubSubMessage, RetainHandling, Route, SubscriptionOptions};
+use crate::brokers::error::NexoError;
+
+/// Outcome of a confirmed publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishConfirm {
+    /// Assigned by the server, increasing per process
+    pub publish_id: u64,
+    /// Subscribers whose channel holds the message
+    pub delivered: usize,
+}
+
+/// How far a publish got when `route` returns.
+enum Routed {
+    Sent(usize),
+    /// Handed to the fan-out pool; confirm receivers if requested
+    Queued(usize, Vec<oneshot::Receiver<usize>>),
+}
 
 pub struct PubSubManager {
     tree: Arc<RwLock<Node>>,
     clients: ClientRegistry,
+    match_cache: Option<MatchCache>,
+    fanout: Option<FanoutPool>,
     retained_dirty: Arc<AtomicBool>,
+    /// Retained usage per root; locked after `tree`
+    retained: Arc<Mutex<RetainedIndex>>,
+    next_publish_id: AtomicU64,
+    /// Keyed by topic root (first segment)
+    activity: Arc<ActivityTracker>,
     config: Arc<PubSubConfig>,
+    /// Engine memory budget (see `brokers::memory`)
+    budget: BudgetHandle,
+    clock: SharedClock,
 }
 
 impl PubSubManager {
     pub fn new(config: Arc<PubSubConfig>) -> Self {
+        Self::with_clock(config, clock::system())
+    }
+
+    /// Manager whose timestamps and stall timers run on `clock` (a `MockClock` in tests).
+    pub fn with_clock(config: Arc<PubSubConfig>, clock: SharedClock) -> Self {
         let tree = Arc::new(RwLock::new(Node::new()));
         let retained_dirty = Arc::new(AtomicBool::new(false));
+        let retained = Arc::new(Mutex::new(RetainedIndex::new(&config)));
         let clients = Arc::new(DashMap::new());
         let persistence_path = format!("{}/retained.db", config.persistence_path);
+        let activity = Arc::new(ActivityTracker::load(format!("{}/activity.json", config.persistence_path).into(), clock.clone()));
 
         // Load retained from SQLite
         if let Ok(conn) = persistence::init_db(&persistence_path) {
             if let Ok(loaded) = persistence::load_all(&conn) {
                 let mut root = tree.write();
+                let mut index = retained.lock();
                 for (path, msg) in loaded {
-                    let parts: Vec<String> = path.split('/').map(|s| s.to_string()).collect();
-                    root.set_retained(&parts, Some(msg));
+                    activity.track(topic_root(&path));
+                    // The limits may have been lowered since: what no longer fits is dropped
+                    match index.admit(&path, msg.data.len()) {
+                        Ok(evicted) => {
+                            for topic in &evicted {
+                                root.set_retained(&topic.split('/').map(|s| s.to_string()).collect::<Vec<_>>(), None);
+                                retained_dirty.store(true, Ordering::Relaxed);
+                            }
+                            let parts: Vec<String> = path.split('/').map(|s| s.to_string()).collect();
+                            root.set_retained(&parts, Some(msg));
+                        }
+                        Err(e) => {
+                            tracing::warn!("Dropping retained topic '{}' on load: {}", path, e.message);
+                            retained_dirty.store(true, Ordering::Relaxed);
+                        }
+                    }
                 }
             } else {
                 tracing::warn!("Failed to load retained topics from SQLite DB");
@@ -76,6 +132,7 @@ impl PubSubManager {
         // Background Cleanup Task
         let cleanup_tree = tree.clone();
         let cleanup_dirty = retained_dirty.clone();
+        let cleanup_index = retained.clone();
         let cleanup_secs = config.cleanup_interval_seconds;
 
         tokio::spawn(async move {
@@ -84,28 +141,77 @@ impl PubSubManager {
             interval.tick().await; // skip first
             loop {
                 interval.tick().await;
-                let cleaned = {
+                let mut expired = Vec::new();
+                {
                     let mut root = cleanup_tree.write();
-                    root.cleanup_expired_retained()
-                };
-                if cleaned {
+                    root.cleanup_expired_retained("", &mut expired);
+                    let mut index = cleanup_index.lock();
+                    for topic in &expired {
+                        index.remove(topic);
+                    }
+                }
+                if !expired.is_empty() {
                     cleanup_dirty.store(true, Ordering::Relaxed);
                 }
             }
         });
 
+        let fanout = match config.fanout_threshold {
+            0 => None,
+            _ => FanoutPool::new(config.fanout_workers, clients.clone()),
+        };
+
         Self {
             tree,
             clients,
+            fanout,
+            match_cache: MatchCache::new(config.match_cache_size),
             retained_dirty,
+            retained,
+            next_publish_id: AtomicU64::new(1),
+            activity,
             config,
+            budget: BudgetHandle::default(),
+            clock,
         }
     }
 
+    /// Shed publishes by the engine memory budget from now on.
+    pub fn attach_budget(&self, budget: Arc<MemoryBudget>) {
+        self.budget.attach(budget);
+    }
+
+    /// Payload bytes of the retained messages.
+    pub fn memory_usage(&self) -> u64 {
+        self.retained.lock().snapshot().bytes as u64
+    }
+
+    /// Last publish/delivery per topic root.
+    pub fn activity(&self) -> &ActivityTracker {
+        &self.activity
+    }
+
+    /// Drop the retained messages of every topic under `root`; returns how many.
+    pub fn clear_root(&self, root: &str) -> usize {
+        let cleared = {
+            let mut tree = self.tree.write();
+            self.retained.lock().clear_root(root);
+            tree.children.get_mut(root).map(Node::clear_retained).unwrap_or(0)
+        };
+        if cleared > 0 {
+            self.retained_dirty.store(true, Ordering::Relaxed);
+        }
+        self.activity.forget(root);
+        cleared
+    }
+
     pub fn connect(&self, client_id: ClientId, sender: mpsc::UnboundedSender<Arc<PubSubMessage>>) {
         self.clients.insert(client_id, ClientInfo {
             sender,
             subscriptions: HashSet::new(),
+            aliases: HashMap::new(),
+            idle_ttls: HashMap::new(),
+            stalled_since: None,
         });
     }
 
@@ -116,77 +222,254 @@ impl PubSubManager {
                 let parts: Vec<String> = sub.split('/').map(|s| s.to_string()).collect();
                 root.remove_subscriber(&parts, client_id);
             }
+            self.invalidate_matches();
+        }
+    }
+
+    /// Drop cached routes; call with the tree write lock held.
+    fn invalidate_matches(&self) {
+        if let Some(cache) = &self.match_cache {
+            cache.clear();
         }
     }
 
-    pub fn subscribe(&self, client_id: &ClientId, pattern: &str) {
+    pub fn subscribe(&self, client_id: &ClientId, pattern: &str) -> Result<(), NexoError> {
+        self.subscribe_with(client_id, pattern, SubscriptionOptions::default())
+    }
+
+    /// Subscribe with per-subscription options. Subscribing again to the same
+    /// pattern replaces its options. Filters breaking the naming rules
+    /// (see `brokers::names`) are rejected.
+    pub fn subscribe_with(&self, client_id: &ClientId, pattern: &str, options: SubscriptionOptions) -> Result<(), NexoError> {
+        names::validate(NameKind::PubSubFilter, pattern)?;
         let sender = if let Some(mut info) = self.clients.get_mut(client_id) {
             info.subscriptions.insert(pattern.to_string());
             info.sender.clone()
         } else {
-            return;
+            return Ok(());
         };
 
         let parts: Vec<String> = pattern.split('/').map(|s| s.to_string()).collect();
+        if !matches!(parts[0].as_str(), "+" | "#") {
+            self.activity.consumed(&parts[0]);
+        }
         let mut root = self.tree.write();
-        root.insert_subscriber(&parts, client_id);
+        let is_new = root.insert_subscriber(&parts, client_id, options);
+        self.invalidate_matches();
+
+        let send_retained = match options.retain_handling {
+            RetainHandling::SendOnSubscribe => true,
+            RetainHandling::SendIfNew => is_new,
+            RetainHandling::DontSend => false,
+        };
+        if !send_retained {
+            return Ok(());
+        }
 
         let mut retained = Vec::new();
         root.collect_retained_for_pattern(&parts, "", &mut retained);
 
         for (p, b) in retained {
             let p = if p.starts_with('/') { p[1..].to_string() } else { p };
-            let msg = Arc::new(PubSubMessage::new(p, b));
-            let _ = sender.send(msg);
+            let msg = PubSubMessage::new(p, b).with_delivery(options.id.into_iter().collect(), true);
+            let _ = sender.send(Arc::new(msg));
         }
+        Ok(())
     }
 
     pub fn unsubscribe(&self, client_id: &ClientId, pattern: &str) {
         if let Some(mut info) = self.clients.get_mut(client_id) {
             info.subscriptions.remove(pattern);
+            info.idle_ttls.remove(pattern);
         }
         let parts: Vec<String> = pattern.split('/').map(|s| s.to_string()).collect();
         let mut root = self.tree.write();
         root.remove_subscriber(&parts, client_id);
+        self.invalidate_matches();
+    }
+
+    /// Drop the subscription to `pattern` once the connection has stalled
+    /// for `ttl` (see `expire_idle`); `None` keeps it whatever happens.
+    pub fn set_idle_ttl(&self, client_id: &ClientId, pattern: &str, ttl: Option<Duration>) {
+        let Some(mut info) = self.clients.get_mut(client_id) else { return };
+        match ttl {
+            Some(ttl) if info.subscriptions.contains(pattern) => { info.idle_ttls.insert(pattern.to_string(), ttl); }
+            _ => { info.idle_ttls.remove(pattern); }
+        }
+    }
+
+    /// The connection's socket stopped (`true`) or resumed taking pushes.
+    pub fn set_stalled(&self, client_id: &ClientId, stalled: bool) {
+        let Some(mut info) = self.clients.get_mut(client_id) else { return };
+        match stalled {
+            true => { info.stalled_since.get_or_insert_with(|| self.clock.now_ms()); }
+            false => info.stalled_since = None,
+        }
+    }
+
+    /// Drop every subscription whose connection has been stalled for longer
+    /// than its idle TTL, telling the client with a notice queued behind the
+    /// pushes it did not take. Returns the dropped (client, pattern) pairs.
+    pub fn expire_idle(&self) -> Vec<(ClientId, String)> {
+        let now = self.clock.now_ms();
+        let mut expired = Vec::new();
+        for entry in self.clients.iter() {
+            let Some(since) = entry.stalled_since else { continue };
+            let stalled = Duration::from_millis(now.saturating_sub(since));
+            for (pattern, ttl) in &entry.idle_ttls {
+                if stalled >= *ttl {
+                    expired.push((entry.key().clone(), pattern.clone(), stalled));
+                }
+            }
+        }
+        expired.into_iter().map(|(client_id, pattern, stalled)| {
+            tracing::warn!("PubSub: {} unsubscribed from '{}', stalled for {}ms", client_id.0, pattern, stalled.as_millis());
+            self.unsubscribe(&client_id, &pattern);
+            if let Some(info) = self.clients.get(&client_id) {
+                let reason = format!("no message taken for {}ms", stalled.as_millis());
+                let _ = info.sender.send(Arc::new(PubSubMessage::unsubscribed(pattern.clone(), &reason)));
+            }
+            (client_id, pattern)
+        }).collect()
+    }
+
+    /// Bind `alias` to `topic` for this connection. Re-binding an alias replaces it.
+    pub fn register_alias(&self, client_id: &ClientId, alias: u16, topic: &str) -> Result<(), NexoError> {
+        if topic.is_empty() || topic.contains('+') || topic.contains('#') {
+            return Err(NexoError::invalid("Alias topic must be a concrete topic"));
+        }
+        let Some(mut info) = self.clients.get_mut(client_id) else {
+            return Err(NexoError::not_found("Client not connected"));
+        };
+        if !info.aliases.contains_key(&alias) && info.aliases.len() >= self.config.max_topic_aliases {
+            return Err(NexoError::invalid(format!("Topic alias limit reached ({})", self.config.max_topic_aliases)));
+        }
+        info.aliases.insert(alias, topic.to_string());
+        Ok(())
+    }
+
+    pub fn resolve_alias(&self, client_id: &ClientId, alias: u16) -> Option<String> {
+        self.clients.get(client_id)?.aliases.get(&alias).cloned()
+    }
+
+    /// Returns the number of subscribers reached; for a parallel fan-out, the number queued.
+    /// Fails, delivering nothing, when the memory budget sheds the publish
+    /// (`Busy`) or the retained limits refuse a retained one.
+    pub fn publish(&self, topic: &str, data: Bytes, retain: bool, ttl_seconds: Option<u64>) -> Result<usize, NexoError> {
+        self.publish_from(None, topic, data, retain, ttl_seconds, None)
     }
 
-    pub fn publish(&self, topic: &str, data: Bytes, retain: bool, ttl_seconds: Option<u64>) -> usize {
-        let parts: Vec<String> = topic.split('/').map(|s| s.to_string()).collect();
-        if parts.is_empty() { return 0; }
+    /// Publish on behalf of a connected client, so its `no_local`
+    /// subscriptions skip the message. With an `expiry`, subscribers that
+    /// have not taken the message off their channel by then never get it.
+    pub fn publish_from(&self, publisher: Option<&ClientId>, topic: &str, data: Bytes, retain: bool, ttl_seconds: Option<u64>, expiry: Option<Duration>) -> Result<usize, NexoError> {
+        match self.route(publisher, topic, data, retain, ttl_seconds, expiry, false)? {
+            Routed::Sent(count) | Routed::Queued(count, _) => Ok(count),
+        }
+    }
+
+    /// Publish and resolve once the message is in the channel of every
+    /// matched subscriber, including those served by the fan-out pool.
+    pub async fn publish_confirmed(&self, publisher: Option<&ClientId>, topic: &str, data: Bytes, retain: bool, ttl_seconds: Option<u64>, expiry: Option<Duration>) -> Result<PublishConfirm, NexoError> {
+        let publish_id = self.next_publish_id.fetch_add(1, Ordering::Relaxed);
+        let delivered = match self.route(publisher, topic, data, retain, ttl_seconds, expiry, true)? {
+            Routed::Sent(count) => count,
+            Routed::Queued(_, confirms) => {
+                let mut delivered = 0;
+                for confirm in confirms {
+                    delivered += confirm.await.unwrap_or(0);
+                }
+                delivered
+            }
+        };
+        Ok(PublishConfirm { publish_id, delivered })
+    }
+
+    #[tracing::instrument(name = "pubsub.publish", skip_all, fields(topic = %topic, retain = retain, delivered = tracing::field::Empty))]
+    #[allow(clippy::too_many_arguments)]
+    fn route(&self, publisher: Option<&ClientId>, topic: &str, data: Bytes, retain: bool, ttl_seconds: Option<u64>, expiry: Option<Duration>, confirm: bool) -> Result<Routed, NexoError> {
+        // Split lazily: a cached route needs no tree walk
+        let split = || -> Vec<String> { topic.split('/').map(|s| s.to_string()).collect() };
+        // Publishes are never durable; clearing a retained message frees memory
+        if !(retain && data.is_empty()) {
+            self.budget.admit(false)?;
+        }
 
         if retain {
+            let parts = split();
             let mut root = self.tree.write();
+            let mut index = self.retained.lock();
             if data.is_empty() {
                 root.set_retained(&parts, None);
+                index.remove(topic);
             } else {
+                for evicted in index.admit(topic, data.len())? {
+                    root.set_retained(&evicted.split('/').map(|s| s.to_string()).collect::<Vec<_>>(), None);
+                }
                 let effective_ttl = ttl_seconds.unwrap_or(self.config.default_retained_ttl_seconds);
                 root.set_retained(&parts, Some(RetainedMessage::new(data.clone(), Some(effective_ttl))));
             }
             self.retained_dirty.store(true, Ordering::Relaxed);
         }
 
-        let mut matched = Vec::new();
-        {
+        let matched = {
             let root = self.tree.read();
-            root.match_subscribers(&parts, &mut matched);
+            match self.match_cache.as_ref().and_then(|cache| cache.get(topic)) {
+                Some(hit) => hit,
+                None => {
+                    let mut matched = Vec::new();
+                    root.match_subscribers(&split(), &mut matched);
+
+                    // One route per client, holding every subscription that matched
+                    let mut routes: Vec<Route> = Vec::new();
+                    let mut positions: HashMap<ClientId, usize> = HashMap::new();
+                    for (client, options) in matched {
+                        match positions.get(&client) {
+                            Some(&i) => routes[i].subscriptions.push(options),
+                            None => {
+                                positions.insert(client.clone(), routes.len());
+                                routes.push(Route { client, subscriptions: vec![options] });
+                            }
+                        }
+                    }
+
+                    let matched: Arc<[Route]> = routes.into();
+                    if let Some(cache) = &self.match_cache {
+                        cache.put(topic, matched.clone());
+                    }
+                    matched
+                }
+            }
+        };
+
+        self.activity.published(topic_root(topic));
+        if !matched.is_empty() {
+            self.activity.consumed(topic_root(topic));
         }
 
-        let mut seen = HashSet::new();
-        matched.retain(|id| seen.insert(id.clone()));
+        // Retained copies live by `ttl_seconds`: only live deliveries expire
+        let msg = Arc::new(PubSubMessage::new(topic.to_string(), data).expiring(expiry));
+        if let Some(pool) = &self.fanout {
+            if matched.len() >= self.config.fanout_threshold || pool.is_busy() {
+                let (queued, confirms) = pool.dispatch(msg, matched, publisher.cloned(), retain, confirm);
+                tracing::Span::current().record("delivered", queued);
+                return Ok(Routed::Queued(queued, confirms));
+            }
+        }
 
-        let msg = Arc::new(PubSubMessage::new(topic.to_string(), data));
         let mut sent_count = 0;
         let mut zombies = Vec::new();
 
-        for client_id in matched {
-            if let Some(info) = self.clients.get(&client_id) {
-                if info.sender.send(msg.clone()).is_ok() {
+        for route in matched.iter() {
+            let Some(delivery) = route.deliver(&msg, publisher, retain) else { continue };
+            if let Some(info) = self.clients.get(&route.client) {
+                if info.sender.send(delivery).is_ok() {
                     sent_count += 1;
                 } else {
-                    zombies.push(client_id);
+                    zombies.push(route.client.clone());
                 }
             } else {
-                zombies.push(client_id);
+                zombies.push(route.client.clone());
             }
         }
 
@@ -194,7 +477,8 @@ impl PubSubManager {
             self.disconnect(&client_id);
         }
 
-        sent_count
+        tracing::Span::current().record("delivered", sent_count);
+        Ok(Routed::Sent(sent_count))
     }
 
     pub fn scan_topics(&self, limit: usize, offset: usize, search: Option<&str>) -> PubSubSnapshot {
@@ -245,6 +529,12 @@ impl PubSubManager {
             total_topics,
             topics: paginated,
             wildcards,
+            match_cache: self.match_cache.as_ref().map(|cache| cache.snapshot()).unwrap_or_default(),
+            retained: self.retained.lock().snapshot(),
         }
     }
 }
+
+fn topic_root(topic: &str) -> &str {
+    topic.split('/').next().unwrap_or(topic)
+}
@@ -1,14 +1,22 @@
 //! PubSub option types shared between the manager and the TCP adapter.
 
+use std::time::Duration;
+
 use serde::Deserialize;
 
 use crate::brokers::pub_sub::config::PubSubConfig;
+use crate::brokers::pub_sub::{RetainHandling, SubscriptionOptions};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PubSubPublishOptions {
     pub retain: Option<bool>,
     pub ttl: Option<u64>,
+    /// Answer with a publish confirm once every subscriber channel holds the message
+    pub confirm: Option<bool>,
+    /// Drop the message, rather than deliver it late, for subscribers still
+    /// behind on their pushes this long after the publish
+    pub expiry_ms: Option<u64>,
 }
 
 /// Resolved publish configuration after merging client options with system defaults
@@ -16,6 +24,8 @@ pub struct PubSubPublishOptions {
 pub struct PubSubPublishConfig {
     pub retain: bool,
     pub ttl_seconds: u64,
+    pub confirm: bool,
+    pub expiry: Option<Duration>,
 }
 
 impl PubSubPublishConfig {
@@ -23,6 +33,39 @@ impl PubSubPublishConfig {
         Self {
             retain: opts.retain.unwrap_or(false),
             ttl_seconds: opts.ttl.unwrap_or(sys.default_retained_ttl_seconds),
+            confirm: opts.confirm.unwrap_or(false),
+            expiry: opts.expiry_ms.filter(|ms| *ms > 0).map(Duration::from_millis),
         }
     }
 }
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PubSubSubscribeOptions {
+    pub id: Option<u32>,
+    pub no_local: Option<bool>,
+    pub retain_as_published: Option<bool>,
+    /// 0 = send retained messages on subscribe, 1 = only for a new
+    /// subscription, 2 = never
+    pub retain_handling: Option<u8>,
+    /// Drop the subscription once the connection has taken no message off
+    /// its push channel for this long (kept by the manager, not the tree)
+    pub idle_ttl_ms: Option<u64>,
+}
+
+impl PubSubSubscribeOptions {
+    pub fn into_subscription(self) -> Result<SubscriptionOptions, String> {
+        let retain_handling = match self.retain_handling.unwrap_or(0) {
+            0 => RetainHandling::SendOnSubscribe,
+            1 => RetainHandling::SendIfNew,
+            2 => RetainHandling::DontSend,
+            other => return Err(format!("Invalid retainHandling: {} (expected 0, 1 or 2)", other)),
+        };
+        Ok(SubscriptionOptions {
+            id: self.id,
+            no_local: self.no_local.unwrap_or(false),
+            retain_as_published: self.retain_as_published.unwrap_or(false),
+            retain_handling,
+        })
+    }
+}
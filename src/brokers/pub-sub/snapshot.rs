@@ -8,6 +8,28 @@ pub struct PubSubSnapshot {
     pub total_topics: usize,
     pub topics: Vec<TopicSnapshot>,
     pub wildcards: WildcardSubscriptions,
+    pub match_cache: MatchCacheSnapshot,
+    pub retained: RetainedSnapshot,
+}
+
+/// Retained messages across all roots, and what the retained limits turned away.
+#[derive(Clone, Copy, Default)]
+pub struct RetainedSnapshot {
+    pub topics: usize,
+    pub bytes: usize,
+    /// Topics dropped by the `lru` policy to make room
+    pub evictions: u64,
+    /// Retained publishes refused (over the payload limit, or a full root under `reject`)
+    pub rejections: u64,
+}
+
+/// Publish routing cache counters; all zero when the cache is disabled.
+#[derive(Clone, Copy, Default)]
+pub struct MatchCacheSnapshot {
+    pub capacity: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
 }
 
 #[derive(Clone)]
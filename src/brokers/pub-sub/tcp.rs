@@ -1,12 +1,15 @@
 //! PubSub broker TCP surface: opcodes, command parsing, dispatch entry point.
 
-use bytes::Bytes;
+use std::time::Duration;
 
-use crate::brokers::pub_sub::options::{PubSubPublishConfig, PubSubPublishOptions};
-use crate::brokers::pub_sub::ClientId;
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::brokers::names::{self, NameKind};
+use crate::brokers::pub_sub::options::{PubSubPublishConfig, PubSubPublishOptions, PubSubSubscribeOptions};
+use crate::brokers::pub_sub::{ClientId, PublishConfirm, SubscriptionOptions};
 use crate::config::Config;
 use crate::transport::tcp::protocol::cursor::PayloadCursor;
-use crate::transport::tcp::protocol::{ParseError, Response};
+use crate::transport::tcp::protocol::{NexoError, ParseError, Response, ToWire};
 use crate::NexoEngine;
 
 // ==========================================
@@ -19,6 +22,8 @@ pub const OPCODE_MAX: u8 = 0x2F;
 pub const OP_PUB: u8 = 0x21;
 pub const OP_SUB: u8 = 0x22;
 pub const OP_UNSUB: u8 = 0x23;
+pub const OP_ALIAS: u8 = 0x24;
+pub const OP_PUB_ALIAS: u8 = 0x25;
 
 // ==========================================
 // COMMANDS
@@ -27,8 +32,10 @@ pub const OP_UNSUB: u8 = 0x23;
 #[derive(Debug)]
 enum PubSubCommand {
     Publish { topic: String, options: PubSubPublishOptions, payload: Bytes },
-    Subscribe { topic: String },
+    Subscribe { topic: String, options: SubscriptionOptions, idle_ttl: Option<Duration> },
     Unsubscribe { topic: String },
+    RegisterAlias { alias: u16, topic: String },
+    PublishAlias { alias: u16, options: PubSubPublishOptions, payload: Bytes },
 }
 
 impl PubSubCommand {
@@ -36,15 +43,37 @@ impl PubSubCommand {
         match opcode {
             OP_PUB => {
                 let topic = cursor.read_string()?;
-                let json_str = cursor.read_string()?;
-                let options: PubSubPublishOptions = serde_json::from_str(&json_str)
-                    .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?;
+                names::check(NameKind::PubSubTopic, &topic).map_err(ParseError::InvalidName)?;
+                let options = parse_options(cursor)?;
                 let payload = cursor.read_remaining();
                 Ok(Self::Publish { topic, options, payload })
             }
+            OP_ALIAS => {
+                let alias = cursor.read_u16()?;
+                let topic = cursor.read_string()?;
+                names::check(NameKind::PubSubTopic, &topic).map_err(ParseError::InvalidName)?;
+                Ok(Self::RegisterAlias { alias, topic })
+            }
+            OP_PUB_ALIAS => {
+                let alias = cursor.read_u16()?;
+                let options = parse_options(cursor)?;
+                let payload = cursor.read_remaining();
+                Ok(Self::PublishAlias { alias, options, payload })
+            }
             OP_SUB => {
                 let topic = cursor.read_string()?;
-                Ok(Self::Subscribe { topic })
+                names::check(NameKind::PubSubFilter, &topic).map_err(ParseError::InvalidName)?;
+                // Options are optional: older clients send the topic only
+                let requested = if cursor.len() > 0 {
+                    let json_str = cursor.read_string()?;
+                    serde_json::from_str::<PubSubSubscribeOptions>(&json_str)
+                        .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?
+                } else {
+                    PubSubSubscribeOptions::default()
+                };
+                let idle_ttl = requested.idle_ttl_ms.filter(|ms| *ms > 0).map(Duration::from_millis);
+                let options = requested.into_subscription().map_err(ParseError::Invalid)?;
+                Ok(Self::Subscribe { topic, options, idle_ttl })
             }
             OP_UNSUB => {
                 let topic = cursor.read_string()?;
@@ -55,10 +84,47 @@ impl PubSubCommand {
     }
 }
 
+fn parse_options(cursor: &mut PayloadCursor) -> Result<PubSubPublishOptions, ParseError> {
+    let json_str = cursor.read_string()?;
+    serde_json::from_str(&json_str)
+        .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))
+}
+
+// ==========================================
+// RESPONSES
+// ==========================================
+
+/// Publish confirm: [PublishId: 8 bytes (BE)] [Delivered: 4 bytes (BE)]
+impl ToWire for PublishConfirm {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(12);
+        buf.put_u64(self.publish_id);
+        buf.put_u32(self.delivered as u32);
+        buf.freeze()
+    }
+}
+
 // ==========================================
 // DISPATCH ENTRY POINT
 // ==========================================
 
+/// Without `confirm` the publish is answered right away with OK (or the
+/// error of a retained publish the retained limits refused).
+async fn publish(engine: &NexoEngine, client_id: &ClientId, topic: &str, payload: Bytes, options: PubSubPublishOptions) -> Response {
+    let config = PubSubPublishConfig::from_options(options, &Config::global().pubsub);
+    let ttl = Some(config.ttl_seconds);
+    if config.confirm {
+        return match engine.pubsub.publish_confirmed(Some(client_id), topic, payload, config.retain, ttl, config.expiry).await {
+            Ok(confirm) => Response::Data(confirm.to_wire()),
+            Err(e) => Response::Error(e),
+        };
+    }
+    match engine.pubsub.publish_from(Some(client_id), topic, payload, config.retain, ttl, config.expiry) {
+        Ok(_) => Response::Ok,
+        Err(e) => Response::Error(e),
+    }
+}
+
 pub async fn handle(
     opcode: u8,
     cursor: &mut PayloadCursor,
@@ -67,23 +133,34 @@ pub async fn handle(
 ) -> Response {
     let cmd = match PubSubCommand::parse(opcode, cursor) {
         Ok(c) => c,
-        Err(e) => return Response::Error(e.to_string()),
+        Err(e) => return Response::Error(e.into()),
     };
 
     let pubsub = &engine.pubsub;
 
     match cmd {
-        PubSubCommand::Publish { options, topic, payload } => {
-            let config = PubSubPublishConfig::from_options(options, &Config::global().pubsub);
-            let _count = pubsub.publish(&topic, payload, config.retain, Some(config.ttl_seconds));
-            Response::Ok
-        }
-        PubSubCommand::Subscribe { topic } => {
-            pubsub.subscribe(client_id, &topic);
-            Response::Ok
+        PubSubCommand::Publish { options, topic, payload } => publish(engine, client_id, &topic, payload, options).await,
+        PubSubCommand::RegisterAlias { alias, topic } => match pubsub.register_alias(client_id, alias, &topic) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
+        PubSubCommand::PublishAlias { alias, options, payload } => {
+            let Some(topic) = pubsub.resolve_alias(client_id, alias) else {
+                return Response::Error(NexoError::invalid(format!("Unknown topic alias: {}", alias)));
+            };
+            publish(engine, client_id, &topic, payload, options).await
         }
+        PubSubCommand::Subscribe { topic, options, idle_ttl } => match pubsub.subscribe_with(client_id, &topic, options) {
+            Ok(()) => {
+                pubsub.set_idle_ttl(client_id, &topic, idle_ttl);
+                engine.connections.add_subscription(&client_id.0, &topic);
+                Response::Ok
+            }
+            Err(e) => Response::Error(e),
+        },
         PubSubCommand::Unsubscribe { topic } => {
             pubsub.unsubscribe(client_id, &topic);
+            engine.connections.remove_subscription(&client_id.0, &topic);
             Response::Ok
         }
     }
@@ -0,0 +1,89 @@
+//! DLQ alerts: dead-lettered messages are announced on the pub/sub topic
+//! `$nexo/alerts/dlq/<queue>` so ops tooling can subscribe instead of polling.
+//! Threshold crossings are also posted as lifecycle events.
+//!
+//! The queue manager is built before it knows about pub/sub, so the publisher
+//! is attached afterwards; until then (and in a bare `QueueManager`) alerts
+//! are dropped.
+
+use std::sync::{Arc, OnceLock};
+
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::brokers::events::EventHandle;
+use crate::brokers::hooks::LifecycleEvent;
+use crate::brokers::pub_sub::PubSubManager;
+use crate::brokers::queue::domain::dlq::DlqMessage;
+
+pub const DLQ_ALERT_PREFIX: &str = "$nexo/alerts/dlq/";
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum DlqAlert<'a> {
+    /// A message ran out of retries
+    #[serde(rename_all = "camelCase")]
+    DeadLettered {
+        queue: &'a str,
+        id: String,
+        reason: &'a str,
+        attempts: u32,
+        dlq_depth: usize,
+    },
+    /// The DLQ grew to the queue's `dlqAlertThreshold`
+    #[serde(rename_all = "camelCase")]
+    Threshold {
+        queue: &'a str,
+        dlq_depth: usize,
+        threshold: usize,
+    },
+}
+
+#[derive(Default)]
+pub struct DlqAlerts {
+    pubsub: OnceLock<Arc<PubSubManager>>,
+    events: EventHandle,
+}
+
+impl DlqAlerts {
+    pub fn new(events: EventHandle) -> Self {
+        Self { pubsub: OnceLock::new(), events }
+    }
+
+    pub fn attach(&self, pubsub: Arc<PubSubManager>) {
+        let _ = self.pubsub.set(pubsub);
+    }
+
+    /// Announce `moved` (in DLQ order); `depth` is the DLQ size after the move.
+    pub fn dead_lettered(&self, queue: &str, moved: &[DlqMessage], depth: usize, threshold: usize) {
+        let before = depth.saturating_sub(moved.len());
+        // Fires once per crossing: draining the DLQ below the threshold re-arms it
+        let crossed = threshold > 0 && before < threshold && depth >= threshold;
+        if crossed {
+            self.events.post(LifecycleEvent::DlqThreshold { queue: queue.to_string(), dlq_depth: depth, threshold });
+        }
+        let Some(pubsub) = self.pubsub.get() else { return };
+        let topic = format!("{}{}", DLQ_ALERT_PREFIX, queue);
+
+        for (i, msg) in moved.iter().enumerate() {
+            let alert = DlqAlert::DeadLettered {
+                queue,
+                id: msg.id.to_string(),
+                reason: &msg.failure_reason,
+                attempts: msg.attempts,
+                dlq_depth: before + i + 1,
+            };
+            Self::publish(pubsub, &topic, &alert);
+        }
+
+        if crossed {
+            Self::publish(pubsub, &topic, &DlqAlert::Threshold { queue, dlq_depth: depth, threshold });
+        }
+    }
+
+    fn publish(pubsub: &PubSubManager, topic: &str, alert: &DlqAlert) {
+        if let Ok(json) = serde_json::to_vec(alert) {
+            let _ = pubsub.publish(topic, Bytes::from(json), false, None);
+        }
+    }
+}
@@ -0,0 +1,514 @@
+//! Message archive: queues created with `archive` keep their acked messages
+//! in an append-only log at `<queue>.archive/` instead of forgetting them,
+//! so they can be browsed and replayed later (audit, debugging).
+//!
+//! The log is split in segments named after the sequence number of their
+//! first record. Records are framed like stream records:
+//! [Len: u32][CRC32: u32][Seq: u64][AckedAt: u64][Id: 16][CreatedAt: u64]
+//! [Priority: u8][Attempts: u32][GroupLen: u16][Group][Payload]
+//!
+//! Retention drops whole segments, oldest first: a segment goes once its
+//! newest record is older than `max_age_ms`, or while the archive is above
+//! `max_bytes`. Segments roll at an eighth of either limit, so the archive
+//! overshoots them by about that much.
+//!
+//! The archive has its own writer task, next to the queue DB writer. Appends
+//! are buffered and written every flush interval; reads go through the same
+//! task, after the buffer, so they see every ack issued before them.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, Bytes};
+use crc32fast::Hasher;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::brokers::clock::SharedClock;
+use crate::brokers::queue::domain::queue::{ArchiveConfig, Message};
+
+/// Largest segment, whatever the size limit
+const MAX_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+/// Seq, acked_at, id, created_at, priority, attempts, group length
+const FIXED_LEN: usize = 8 + 8 + 16 + 8 + 1 + 4 + 2;
+
+/// An acked message, as kept by the archive.
+#[derive(Debug, Clone)]
+pub struct ArchivedMessage {
+    /// Position in the archive, assigned on append
+    pub seq: u64,
+    pub id: Uuid,
+    pub payload: Bytes,
+    pub priority: u8,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub acked_at: u64,
+    pub message_group: Option<String>,
+}
+
+impl ArchivedMessage {
+    /// `payload` is the message's own, with a blob reference resolved.
+    pub fn acked(msg: &Message, payload: Bytes, acked_at: u64) -> Self {
+        Self {
+            seq: 0,
+            id: msg.id,
+            payload,
+            priority: msg.priority,
+            attempts: msg.attempts,
+            created_at: msg.created_at,
+            acked_at,
+            message_group: msg.message_group.clone(),
+        }
+    }
+}
+
+/// Size of an archive (see `MessageArchive::stats`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    pub records: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct ArchiveCounters {
+    records: AtomicU64,
+    bytes: AtomicU64,
+}
+
+enum ArchiveMsg {
+    Append(ArchivedMessage),
+    /// Newest first: skip `offset`, take `limit`, plus the total
+    Page { offset: usize, limit: usize, reply: oneshot::Sender<(usize, Vec<ArchivedMessage>)> },
+    /// Newest record of message `id`
+    Find { id: Uuid, reply: oneshot::Sender<Option<ArchivedMessage>> },
+    /// Write and fsync the buffer
+    Flush(oneshot::Sender<bool>),
+}
+
+// ==========================================
+// MESSAGE ARCHIVE (Public API)
+// ==========================================
+
+pub struct MessageArchive {
+    sender: Mutex<Option<mpsc::UnboundedSender<ArchiveMsg>>>,
+    writer_handle: Mutex<Option<JoinHandle<()>>>,
+    counters: Arc<ArchiveCounters>,
+}
+
+impl MessageArchive {
+    /// Open (or create) the archive in `dir`. Existing segments are scanned
+    /// by the writer task, before it serves anything.
+    pub fn new(dir: PathBuf, config: ArchiveConfig, flush_ms: u64, clock: SharedClock) -> Self {
+        let counters = Arc::new(ArchiveCounters::default());
+        let log = ArchiveLog {
+            dir,
+            config,
+            segments: VecDeque::new(),
+            active: None,
+            next_seq: 0,
+            counters: counters.clone(),
+        };
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(run_writer(log, rx, flush_ms, clock));
+
+        Self {
+            sender: Mutex::new(Some(tx)),
+            writer_handle: Mutex::new(Some(handle)),
+            counters,
+        }
+    }
+
+    /// Queue `msg` for the log (sync, never blocks).
+    pub fn append(&self, msg: ArchivedMessage) {
+        self.send(ArchiveMsg::Append(msg));
+    }
+
+    /// Archived messages, newest first, and how many there are in total.
+    pub async fn page(&self, offset: usize, limit: usize) -> Result<(usize, Vec<ArchivedMessage>), String> {
+        let (reply, rx) = oneshot::channel();
+        self.send(ArchiveMsg::Page { offset, limit, reply });
+        rx.await.map_err(|_| "archive writer stopped".to_string())
+    }
+
+    /// The archived copy of message `id`, `None` once retention dropped it.
+    pub async fn find(&self, id: Uuid) -> Result<Option<ArchivedMessage>, String> {
+        let (reply, rx) = oneshot::channel();
+        self.send(ArchiveMsg::Find { id, reply });
+        rx.await.map_err(|_| "archive writer stopped".to_string())
+    }
+
+    /// Write everything appended so far and fsync the active segment.
+    pub async fn flush(&self) -> Result<(), String> {
+        let (done, rx) = oneshot::channel();
+        self.send(ArchiveMsg::Flush(done));
+        match rx.await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("archive flush failed".to_string()),
+            Err(_) => Err("archive writer stopped".to_string()),
+        }
+    }
+
+    pub fn stats(&self) -> ArchiveStats {
+        ArchiveStats {
+            records: self.counters.records.load(Ordering::Relaxed),
+            bytes: self.counters.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drop the sender so the writer drains, fsyncs and exits, then wait for it.
+    pub async fn shutdown(&self) {
+        self.sender.lock().unwrap().take();
+        let handle = self.writer_handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = handle.await;
+        }
+    }
+
+    fn send(&self, msg: ArchiveMsg) {
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            if sender.send(msg).is_err() {
+                error!("Archive writer channel closed, request lost");
+            }
+        }
+    }
+}
+
+// ==========================================
+// BACKGROUND WRITER
+// ==========================================
+
+async fn run_writer(mut log: ArchiveLog, mut rx: mpsc::UnboundedReceiver<ArchiveMsg>, flush_ms: u64, clock: SharedClock) {
+    if let Err(e) = log.open() {
+        error!("Failed to open message archive at {:?}: {}", log.dir, e);
+    }
+    log.trim(clock.now_ms());
+
+    let mut flush_timer = tokio::time::interval(Duration::from_millis(flush_ms.max(1)));
+    flush_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Some(ArchiveMsg::Append(msg)) => {
+                    if let Err(e) = log.append(msg) {
+                        error!("Failed to archive message in {:?}: {}", log.dir, e);
+                    }
+                }
+                Some(ArchiveMsg::Page { offset, limit, reply }) => {
+                    log.trim(clock.now_ms());
+                    let _ = reply.send(log.page(offset, limit));
+                }
+                Some(ArchiveMsg::Find { id, reply }) => {
+                    log.trim(clock.now_ms());
+                    let _ = reply.send(log.find(id));
+                }
+                Some(ArchiveMsg::Flush(done)) => {
+                    let _ = done.send(log.sync().is_ok());
+                }
+                None => {
+                    if let Err(e) = log.sync() {
+                        error!("Failed to flush message archive {:?}: {}", log.dir, e);
+                    }
+                    info!("Message archive writer stopped for {:?}", log.dir);
+                    return;
+                }
+            },
+            _ = flush_timer.tick() => {
+                if let Err(e) = log.write_buffer() {
+                    error!("Failed to write message archive {:?}: {}", log.dir, e);
+                }
+                log.trim(clock.now_ms());
+            }
+        }
+    }
+}
+
+// ==========================================
+// SEGMENTED LOG
+// ==========================================
+
+#[derive(Debug)]
+struct Segment {
+    base_seq: u64,
+    path: PathBuf,
+    records: u64,
+    bytes: u64,
+    /// Ack time of its first and last record
+    first_at: u64,
+    last_at: u64,
+}
+
+struct ArchiveLog {
+    dir: PathBuf,
+    config: ArchiveConfig,
+    /// Oldest first; the last one takes the appends
+    segments: VecDeque<Segment>,
+    /// Open on the last segment, `None` until the next append
+    active: Option<BufWriter<File>>,
+    next_seq: u64,
+    counters: Arc<ArchiveCounters>,
+}
+
+impl ArchiveLog {
+    /// Load the segments left by a previous run. A record torn by a crash at
+    /// the end of the last segment is cut off so appends start clean.
+    fn open(&mut self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut found: Vec<(u64, PathBuf)> = std::fs::read_dir(&self.dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+            .filter_map(|path| {
+                let base = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((base, path))
+            })
+            .collect();
+        found.sort_by_key(|(base, _)| *base);
+
+        let last = found.len().saturating_sub(1);
+        for (i, (base_seq, path)) in found.into_iter().enumerate() {
+            let data = std::fs::read(&path)?;
+            let (records, valid_len) = decode_records(&data);
+            if i == last && valid_len < data.len() {
+                warn!("Message archive {:?}: dropping {} torn bytes", path, data.len() - valid_len);
+                OpenOptions::new().write(true).open(&path)?.set_len(valid_len as u64)?;
+            }
+            let (Some(first), Some(newest)) = (records.first(), records.last()) else {
+                let _ = std::fs::remove_file(&path);
+                continue;
+            };
+            self.next_seq = newest.seq + 1;
+            self.segments.push_back(Segment {
+                base_seq,
+                path,
+                records: records.len() as u64,
+                bytes: valid_len as u64,
+                first_at: first.acked_at,
+                last_at: newest.acked_at,
+            });
+        }
+
+        let (records, bytes) = self.segments.iter().fold((0, 0), |(r, b), s| (r + s.records, b + s.bytes));
+        self.counters.records.store(records, Ordering::Relaxed);
+        self.counters.bytes.store(bytes, Ordering::Relaxed);
+        if records > 0 {
+            info!("Message archive {:?}: recovered {} records in {} segments", self.dir, records, self.segments.len());
+        }
+        Ok(())
+    }
+
+    fn segment_bytes(&self) -> u64 {
+        match self.config.max_bytes {
+            0 => MAX_SEGMENT_BYTES,
+            max => (max / 8).clamp(1, MAX_SEGMENT_BYTES),
+        }
+    }
+
+    fn should_roll(&self, now: u64) -> bool {
+        let Some(last) = self.segments.back() else { return true };
+        let aged = self.config.max_age_ms > 0 && last.first_at + self.config.max_age_ms / 8 <= now;
+        last.bytes >= self.segment_bytes() || aged
+    }
+
+    fn append(&mut self, mut msg: ArchivedMessage) -> std::io::Result<()> {
+        if self.should_roll(msg.acked_at) {
+            self.write_buffer()?;
+            self.active = None;
+            let path = self.dir.join(format!("{:020}.log", self.next_seq));
+            self.segments.push_back(Segment {
+                base_seq: self.next_seq,
+                path,
+                records: 0,
+                bytes: 0,
+                first_at: msg.acked_at,
+                last_at: msg.acked_at,
+            });
+        }
+        let Some(segment) = self.segments.back_mut() else { return Ok(()) };
+        if self.active.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&segment.path)?;
+            self.active = Some(BufWriter::new(file));
+        }
+
+        msg.seq = self.next_seq;
+        let mut buf = Vec::with_capacity(8 + FIXED_LEN + msg.payload.len());
+        encode_record(&mut buf, &msg);
+        if let Some(active) = self.active.as_mut() {
+            active.write_all(&buf)?;
+        }
+
+        self.next_seq += 1;
+        segment.records += 1;
+        segment.bytes += buf.len() as u64;
+        segment.last_at = msg.acked_at;
+        self.counters.records.fetch_add(1, Ordering::Relaxed);
+        self.counters.bytes.fetch_add(buf.len() as u64, Ordering::Relaxed);
+
+        self.trim(msg.acked_at);
+        Ok(())
+    }
+
+    fn write_buffer(&mut self) -> std::io::Result<()> {
+        match self.active.as_mut() {
+            Some(active) => active.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.write_buffer()?;
+        match self.active.as_ref() {
+            Some(active) => active.get_ref().sync_all(),
+            None => Ok(()),
+        }
+    }
+
+    /// Drop the segments past retention. The last segment only goes on age.
+    fn trim(&mut self, now: u64) {
+        let ArchiveConfig { max_age_ms, max_bytes } = self.config;
+        while let Some(oldest) = self.segments.front() {
+            let expired = max_age_ms > 0 && oldest.last_at + max_age_ms <= now;
+            let total = self.counters.bytes.load(Ordering::Relaxed);
+            let oversize = max_bytes > 0 && total > max_bytes && self.segments.len() > 1;
+            if !expired && !oversize {
+                break;
+            }
+            let Some(segment) = self.segments.pop_front() else { break };
+            if self.segments.is_empty() {
+                self.active = None;
+            }
+            if let Err(e) = std::fs::remove_file(&segment.path) {
+                warn!("Message archive: failed to remove segment {:?}: {}", segment.path, e);
+            }
+            self.counters.records.fetch_sub(segment.records, Ordering::Relaxed);
+            self.counters.bytes.fetch_sub(segment.bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn read_segment(&mut self, index: usize) -> Vec<ArchivedMessage> {
+        if index + 1 == self.segments.len() {
+            if let Err(e) = self.write_buffer() {
+                error!("Failed to write message archive {:?}: {}", self.dir, e);
+            }
+        }
+        let segment = &self.segments[index];
+        match std::fs::read(&segment.path) {
+            Ok(data) => decode_records(&data).0,
+            Err(e) => {
+                error!("Failed to read archive segment {:?} (base {}): {}", segment.path, segment.base_seq, e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn page(&mut self, offset: usize, limit: usize) -> (usize, Vec<ArchivedMessage>) {
+        let total = self.counters.records.load(Ordering::Relaxed) as usize;
+        let mut skip = offset as u64;
+        let mut page = Vec::new();
+        for index in (0..self.segments.len()).rev() {
+            if page.len() >= limit {
+                break;
+            }
+            let records = self.segments[index].records;
+            if skip >= records {
+                skip -= records;
+                continue;
+            }
+            let newest_first = self.read_segment(index).into_iter().rev();
+            page.extend(newest_first.skip(skip as usize).take(limit - page.len()));
+            skip = 0;
+        }
+        (total, page)
+    }
+
+    fn find(&mut self, id: Uuid) -> Option<ArchivedMessage> {
+        (0..self.segments.len()).rev()
+            .find_map(|index| self.read_segment(index).into_iter().rev().find(|m| m.id == id))
+    }
+}
+
+// ==========================================
+// RECORD ENCODING
+// ==========================================
+
+fn encode_record(buf: &mut Vec<u8>, msg: &ArchivedMessage) {
+    let group = msg.message_group.as_deref().unwrap_or_default().as_bytes();
+    let mut content = Vec::with_capacity(FIXED_LEN + group.len() + msg.payload.len());
+    content.put_u64(msg.seq);
+    content.put_u64(msg.acked_at);
+    content.put_slice(msg.id.as_bytes());
+    content.put_u64(msg.created_at);
+    content.put_u8(msg.priority);
+    content.put_u32(msg.attempts);
+    content.put_u16(group.len() as u16);
+    content.put_slice(group);
+    content.put_slice(&msg.payload);
+
+    let mut hasher = Hasher::new();
+    hasher.update(&content);
+    buf.put_u32(content.len() as u32);
+    buf.put_u32(hasher.finalize());
+    buf.put_slice(&content);
+}
+
+/// Decode the records of a segment, skipping those whose CRC does not match.
+/// Also returns the length of the complete records (a torn tail is excluded).
+fn decode_records(data: &[u8]) -> (Vec<ArchivedMessage>, usize) {
+    let mut buf = data;
+    let mut records = Vec::new();
+    while buf.remaining() >= 8 {
+        let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let stored_crc = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        if buf.remaining() < 8 + len {
+            break;
+        }
+        let content = &buf[8..8 + len];
+        buf = &buf[8 + len..];
+
+        let mut hasher = Hasher::new();
+        hasher.update(content);
+        if hasher.finalize() != stored_crc || content.len() < FIXED_LEN {
+            continue;
+        }
+        if let Some(msg) = decode_content(content) {
+            records.push(msg);
+        }
+    }
+    (records, data.len() - buf.len())
+}
+
+fn decode_content(mut cursor: &[u8]) -> Option<ArchivedMessage> {
+    let seq = cursor.get_u64();
+    let acked_at = cursor.get_u64();
+    let id = Uuid::from_slice(&cursor[..16]).ok()?;
+    cursor.advance(16);
+    let created_at = cursor.get_u64();
+    let priority = cursor.get_u8();
+    let attempts = cursor.get_u32();
+    let group_len = cursor.get_u16() as usize;
+    if cursor.remaining() < group_len {
+        return None;
+    }
+    let message_group = match group_len {
+        0 => None,
+        _ => Some(String::from_utf8_lossy(&cursor[..group_len]).into_owned()),
+    };
+    cursor.advance(group_len);
+    Some(ArchivedMessage {
+        seq,
+        id,
+        payload: Bytes::copy_from_slice(cursor),
+        priority,
+        attempts,
+        created_at,
+        acked_at,
+        message_group,
+    })
+}
@@ -0,0 +1,140 @@
+//! Claim-check blob store: payloads above `QUEUE_BLOB_THRESHOLD_BYTES` are
+//! written to `QUEUE_BLOB_PATH/<queue>/<message id>.blob` and the message
+//! carries a reference instead:
+//! [DataType: 0x03] [BlobId: 16 bytes] [BlobLen: 8 bytes (BE)]
+//!
+//! Only the broker writes references: a pushed or imported payload of that
+//! data type is refused, or a forged one could fetch or delete another
+//! message's blob. Consumers resolve the reference with `OP_Q_FETCH_BLOB`.
+//! The blob id is the message id, so a blob is deleted with its message (ack, DLQ delete/purge,
+//! queue delete); files no live message points to are swept during
+//! compaction.
+
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use uuid::Uuid;
+
+use crate::brokers::error::NexoError;
+use crate::transport::tcp::protocol::DATA_TYPE_BLOB_REF;
+
+const REFERENCE_LEN: usize = 1 + 16 + 8;
+
+pub struct BlobStore {
+    root: PathBuf,
+    threshold: usize,
+}
+
+impl BlobStore {
+    /// `None` when `root` is empty (offloading disabled).
+    pub fn new(root: &str, threshold: usize) -> Option<Self> {
+        if root.is_empty() {
+            return None;
+        }
+        Some(Self { root: PathBuf::from(root), threshold })
+    }
+
+    pub fn should_offload(&self, payload: &[u8]) -> bool {
+        payload.len() > self.threshold
+    }
+
+    pub fn queue_dir(&self, queue: &str) -> PathBuf {
+        self.root.join(queue)
+    }
+
+    fn path(&self, queue: &str, id: &Uuid) -> PathBuf {
+        self.queue_dir(queue).join(format!("{}.blob", id))
+    }
+
+    /// Write the payload of message `id` and return the reference the message
+    /// carries in its place. With `sync` the file is fsynced first.
+    pub async fn put(&self, queue: &str, id: &Uuid, payload: Bytes, sync: bool) -> std::io::Result<Bytes> {
+        let dir = self.queue_dir(queue);
+        let path = self.path(queue, id);
+        let len = payload.len();
+        tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+            std::fs::create_dir_all(&dir)?;
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, &payload)?;
+            if sync {
+                std::fs::File::open(&tmp)?.sync_all()?;
+            }
+            std::fs::rename(&tmp, &path)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        Ok(reference(id, len as u64))
+    }
+
+    /// The blob of message `id`, `None` once it has been collected.
+    pub async fn get(&self, queue: &str, id: &Uuid) -> std::io::Result<Option<Bytes>> {
+        match tokio::fs::read(self.path(queue, id)).await {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn remove(&self, queue: &str, id: &Uuid) {
+        if let Err(e) = std::fs::remove_file(self.path(queue, id)) {
+            if e.kind() != ErrorKind::NotFound {
+                tracing::warn!("Queue '{}': failed to remove blob {}: {}", queue, id, e);
+            }
+        }
+    }
+
+    pub fn remove_queue(&self, queue: &str) {
+        let _ = std::fs::remove_dir_all(self.queue_dir(queue));
+    }
+
+    /// Delete blobs older than `grace` whose message is gone (`is_live` false),
+    /// e.g. left behind by a crash between writing the blob and the message.
+    /// Returns the number of files removed.
+    pub fn sweep(&self, queue: &str, grace: Duration, is_live: impl Fn(&Uuid) -> bool) -> usize {
+        let Ok(entries) = std::fs::read_dir(self.queue_dir(queue)) else { return 0 };
+        let cutoff = SystemTime::now() - grace;
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let id = path.file_stem().and_then(|s| s.to_str()).and_then(|s| Uuid::parse_str(s).ok());
+            let old = entry.metadata().and_then(|m| m.modified()).map(|t| t < cutoff).unwrap_or(false);
+            let orphan = match id {
+                Some(id) => !is_live(&id),
+                None => true, // interrupted write (.tmp)
+            };
+            if old && orphan && std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+pub fn reference(id: &Uuid, len: u64) -> Bytes {
+    let mut buf = BytesMut::with_capacity(REFERENCE_LEN);
+    buf.put_u8(DATA_TYPE_BLOB_REF);
+    buf.put_slice(id.as_bytes());
+    buf.put_u64(len);
+    buf.freeze()
+}
+
+/// Refuse a client payload claiming the data type of blob references.
+pub fn check_client_payload(payload: &[u8]) -> Result<(), NexoError> {
+    match payload.first() {
+        Some(&DATA_TYPE_BLOB_REF) => Err(NexoError::invalid("Payload data type 0x03 is reserved for blob references")),
+        _ => Ok(()),
+    }
+}
+
+/// Blob id and size if `payload` is a blob reference.
+pub fn parse_reference(payload: &[u8]) -> Option<(Uuid, u64)> {
+    if payload.len() != REFERENCE_LEN || payload[0] != DATA_TYPE_BLOB_REF {
+        return None;
+    }
+    let id = Uuid::from_slice(&payload[1..17]).ok()?;
+    let len = u64::from_be_bytes(payload[17..].try_into().ok()?);
+    Some((id, len))
+}
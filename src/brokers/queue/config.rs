@@ -1,10 +1,16 @@
 use std::env;
 
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::queue::domain::backend::BackendKind;
+use crate::brokers::queue::domain::persistence::{SqliteSynchronous, SqliteTuning};
+
 #[derive(Debug, Clone)]
 pub struct SystemQueueConfig {
     // CREATE config
     pub visibility_timeout_ms: u64,
     pub max_retries: u32,
+    /// DLQ depth that triggers a threshold alert (0 = off)
+    pub dlq_alert_threshold: usize,
     // PUSH config
     pub default_batch_size: usize,
     pub default_wait_ms: u64,
@@ -12,6 +18,57 @@ pub struct SystemQueueConfig {
     pub persistence_path: String,
     pub default_flush_ms: u64,
     pub writer_batch_size: usize,
+    /// Default mode for new queues
+    pub persistence: PersistenceMode,
+    /// Storage format of new queues (existing ones keep theirs)
+    pub storage_backend: BackendKind,
+    /// Ephemeral engine (`NEXO_EPHEMERAL`): a queue asking for `fileSync` runs `fileAsync`
+    pub ephemeral: bool,
+    /// How long a `FileSync` commit waits for more writes to share its fsync
+    pub group_commit_ms: u64,
+    /// `PRAGMA synchronous` of `file_async` queues
+    pub sqlite_synchronous: SqliteSynchronous,
+    pub sqlite_busy_timeout_ms: u64,
+    /// Interval of the passive WAL checkpoint, or of the log compaction check
+    /// (0 = SQLite auto-checkpoint only, logs never compacted)
+    pub wal_checkpoint_interval_ms: u64,
+    /// Budget of the synchronous flush when a queue writer is dropped without `close()` (0 = none)
+    pub drop_flush_timeout_ms: u64,
+    // MAINTENANCE config
+    pub compaction_interval_ms: u64,
+    // LIMITS config
+    pub max_payload_bytes: usize,
+    // BLOB config
+    /// Claim-check directory for large payloads (empty = keep payloads inline)
+    pub blob_path: String,
+    pub blob_threshold_bytes: usize,
+    // WEBHOOK config
+    pub webhook_timeout_ms: u64,
+    pub webhook_concurrency: usize,
+    pub webhook_backoff_ms: u64,
+    pub webhook_max_backoff_ms: u64,
+    // DEDUP config
+    /// How long a push's idempotency key is remembered
+    pub dedup_window_ms: u64,
+    // SPILL config
+    /// Messages in a queue's RAM registry above which ready ones beyond the
+    /// head of each priority are paged out to its DB (0 = never)
+    pub spill_threshold: usize,
+    /// Ready messages kept in RAM per priority once paging starts, and the
+    /// size of each chunk loaded back
+    pub spill_chunk: usize,
+    // ARCHIVE config
+    /// Default retention of archiving queues (0 = no limit)
+    pub archive_retention_age_ms: u64,
+    pub archive_retention_bytes: u64,
+    // SEARCH config
+    /// Word index over pending and DLQ payloads for the search API
+    pub search_index: bool,
+    // TIMER config
+    /// Scheduled and in-flight messages in timing wheels instead of sorted maps
+    pub timing_wheel: bool,
+    // DEBUG config
+    pub trace_capacity: usize,
 }
 
 impl Default for SystemQueueConfig {
@@ -19,26 +76,84 @@ impl Default for SystemQueueConfig {
         Self {
             visibility_timeout_ms: 30000,
             max_retries: 5,
+            dlq_alert_threshold: 0,
             default_batch_size: 10,
             default_wait_ms: 0,
             persistence_path: "./data/queues".to_string(),
             default_flush_ms: 100,
             writer_batch_size: 50000,
+            persistence: PersistenceMode::FileAsync,
+            storage_backend: BackendKind::Sqlite,
+            ephemeral: false,
+            group_commit_ms: 0,
+            sqlite_synchronous: SqliteSynchronous::Off,
+            sqlite_busy_timeout_ms: 5000,
+            wal_checkpoint_interval_ms: 30000,
+            drop_flush_timeout_ms: 2000,
+            compaction_interval_ms: 60000,
+            max_payload_bytes: 10485760, // 10MB
+            blob_path: String::new(),
+            blob_threshold_bytes: 1048576, // 1MB
+            webhook_timeout_ms: 10000,
+            webhook_concurrency: 4,
+            webhook_backoff_ms: 1000,
+            webhook_max_backoff_ms: 60000,
+            dedup_window_ms: 300000, // 5 min
+            spill_threshold: 1000000,
+            spill_chunk: 10000,
+            archive_retention_age_ms: 604800000, // 7 days
+            archive_retention_bytes: 1073741824, // 1GB
+            search_index: false,
+            timing_wheel: false,
+            trace_capacity: 10000,
         }
     }
 }
 
 impl SystemQueueConfig {
+    pub fn sqlite_tuning(&self) -> SqliteTuning {
+        SqliteTuning {
+            busy_timeout_ms: self.sqlite_busy_timeout_ms,
+            synchronous: self.sqlite_synchronous,
+            checkpoint_interval_ms: self.wal_checkpoint_interval_ms,
+        }
+    }
+
     pub fn load() -> Self {
         let default = Self::default();
         Self {
             visibility_timeout_ms: get_env("QUEUE_VISIBILITY_MS", default.visibility_timeout_ms),
             max_retries:           get_env("QUEUE_MAX_RETRIES", default.max_retries),
+            dlq_alert_threshold:   get_env("QUEUE_DLQ_ALERT_THRESHOLD", default.dlq_alert_threshold),
             default_batch_size:    get_env("QUEUE_DEFAULT_BATCH_SIZE", default.default_batch_size),
             default_wait_ms:       get_env("QUEUE_DEFAULT_WAIT_MS", default.default_wait_ms),
             persistence_path:      get_env_str("QUEUE_ROOT_PERSISTENCE_PATH", &default.persistence_path),
             default_flush_ms:      get_env("QUEUE_DEFAULT_FLUSH_MS", default.default_flush_ms),
             writer_batch_size:     get_env("QUEUE_WRITER_BATCH_SIZE", default.writer_batch_size),
+            persistence:           get_env("QUEUE_PERSISTENCE", default.persistence),
+            storage_backend:       get_env("QUEUE_STORAGE_BACKEND", default.storage_backend),
+            ephemeral:             default.ephemeral,
+            group_commit_ms:       get_env("QUEUE_GROUP_COMMIT_MS", default.group_commit_ms),
+            sqlite_synchronous:    get_env("QUEUE_SQLITE_SYNCHRONOUS", default.sqlite_synchronous),
+            sqlite_busy_timeout_ms: get_env("QUEUE_SQLITE_BUSY_TIMEOUT_MS", default.sqlite_busy_timeout_ms),
+            wal_checkpoint_interval_ms: get_env("QUEUE_WAL_CHECKPOINT_MS", default.wal_checkpoint_interval_ms),
+            drop_flush_timeout_ms: get_env("QUEUE_DROP_FLUSH_TIMEOUT_MS", default.drop_flush_timeout_ms),
+            compaction_interval_ms: get_env("QUEUE_COMPACTION_INTERVAL_MS", default.compaction_interval_ms),
+            max_payload_bytes:     get_env("QUEUE_MAX_PAYLOAD_BYTES", default.max_payload_bytes),
+            blob_path:             get_env_str("QUEUE_BLOB_PATH", &default.blob_path),
+            blob_threshold_bytes:  get_env("QUEUE_BLOB_THRESHOLD_BYTES", default.blob_threshold_bytes),
+            webhook_timeout_ms:    get_env("QUEUE_WEBHOOK_TIMEOUT_MS", default.webhook_timeout_ms),
+            webhook_concurrency:   get_env("QUEUE_WEBHOOK_CONCURRENCY", default.webhook_concurrency),
+            webhook_backoff_ms:    get_env("QUEUE_WEBHOOK_BACKOFF_MS", default.webhook_backoff_ms),
+            webhook_max_backoff_ms: get_env("QUEUE_WEBHOOK_MAX_BACKOFF_MS", default.webhook_max_backoff_ms),
+            dedup_window_ms:       get_env("QUEUE_DEDUP_WINDOW_MS", default.dedup_window_ms),
+            spill_threshold:       get_env("QUEUE_SPILL_THRESHOLD", default.spill_threshold),
+            spill_chunk:           get_env("QUEUE_SPILL_CHUNK", default.spill_chunk),
+            archive_retention_age_ms: get_env("QUEUE_ARCHIVE_RETENTION_AGE_MS", default.archive_retention_age_ms),
+            archive_retention_bytes: get_env("QUEUE_ARCHIVE_RETENTION_BYTES", default.archive_retention_bytes),
+            search_index:          get_env("QUEUE_SEARCH_INDEX", default.search_index),
+            timing_wheel:          get_env("QUEUE_TIMING_WHEEL", default.timing_wheel),
+            trace_capacity:        get_env("QUEUE_TRACE_CAPACITY", default.trace_capacity),
         }
     }
 }
@@ -0,0 +1,707 @@
+//! Append-only backend: `<queue>.qlog` holds one `[len:4][crc:4][tag:1][body]`
+//! record per op, and a commit record closes every batch. Replay drops the
+//! ops after the last commit record (a batch torn by a crash) and truncates
+//! them. Live messages are indexed in memory by record offset, which serves
+//! spill reloads; compaction rewrites the live set once most of the file is
+//! dead records.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use bytes::{BufMut, Bytes};
+use crc32fast::Hasher;
+use hashlink::LinkedHashMap;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::queue::domain::backend::{restored_state, QueueBackend};
+use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::queue::domain::persistence::{CheckpointStats, Recovered, SpilledLaneInfo, StorageOp};
+use crate::brokers::queue::domain::queue::{current_time_ms, Message};
+
+/// Suffix of the file a compaction writes before renaming it over the log
+pub const COMPACT_SUFFIX: &str = ".compact";
+
+const HEADER_LEN: u64 = 8;
+/// Logs smaller than this are never compacted
+const COMPACT_MIN_BYTES: u64 = 1 << 20;
+
+const TAG_COMMIT: u8 = 0;
+const TAG_INSERT: u8 = 1;
+const TAG_DELETE: u8 = 2;
+const TAG_UPDATE_STATE: u8 = 3;
+const TAG_SPILL: u8 = 4;
+const TAG_DELETE_SPILLED: u8 = 5;
+const TAG_INSERT_DLQ: u8 = 6;
+const TAG_DELETE_DLQ: u8 = 7;
+const TAG_MOVE_TO_DLQ: u8 = 8;
+const TAG_MOVE_TO_MAIN: u8 = 9;
+const TAG_PURGE_DLQ: u8 = 10;
+
+/// A message of the main queue: where its record is and the state ops
+/// applied on top of it since.
+struct LiveMessage {
+    /// Record holding the message (insert, spill or move back from the DLQ)
+    offset: u64,
+    len: u64,
+    priority: u8,
+    visible_at: u64,
+    attempts: u32,
+    payload_len: usize,
+    /// Set while the message is paged out of RAM
+    spill_seq: Option<u64>,
+}
+
+impl LiveMessage {
+    fn new(msg: &Message, offset: u64, len: u64) -> Self {
+        Self {
+            offset,
+            len,
+            priority: msg.priority,
+            visible_at: msg.visible_at,
+            attempts: msg.attempts,
+            payload_len: msg.payload.len(),
+            spill_seq: None,
+        }
+    }
+}
+
+struct LiveDlq {
+    offset: u64,
+    len: u64,
+}
+
+pub struct LogBackend {
+    path: PathBuf,
+    file: File,
+    mode: PersistenceMode,
+    /// End of the last committed batch
+    len: u64,
+    /// Records in the file, commit records included
+    records: u64,
+    /// Bytes of the records `main` and `dlq` point to
+    live_bytes: u64,
+    main: LinkedHashMap<Uuid, LiveMessage>,
+    dlq: LinkedHashMap<Uuid, LiveDlq>,
+    spilled: BTreeMap<(u8, u64), Uuid>,
+    stats: Arc<CheckpointStats>,
+}
+
+impl LogBackend {
+    pub fn open(path: &Path, mode: PersistenceMode, stats: Arc<CheckpointStats>) -> Result<Self, String> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)
+            .map_err(|e| format!("Cannot open queue log at {:?}: {}", path, e))?;
+        let mut backend = Self {
+            path: path.to_path_buf(),
+            file,
+            mode,
+            len: 0,
+            records: 0,
+            live_bytes: 0,
+            main: LinkedHashMap::new(),
+            dlq: LinkedHashMap::new(),
+            spilled: BTreeMap::new(),
+            stats,
+        };
+        backend.replay().map_err(|e| format!("Failed to replay queue log {:?}: {}", path, e))?;
+        Ok(backend)
+    }
+
+    /// Rebuild the index from the committed batches and cut what follows them.
+    fn replay(&mut self) -> std::io::Result<()> {
+        let size = self.file.metadata()?.len();
+        let mut reader = BufReader::new(self.file.try_clone()?);
+        let mut offset = 0u64;
+        let mut pending = Vec::new();
+
+        while let Some(content) = read_record(&mut reader, offset, size)? {
+            let len = HEADER_LEN + content.len() as u64;
+            if content.first() == Some(&TAG_COMMIT) {
+                for (op, op_offset, op_len) in pending.drain(..) {
+                    let _ = self.index(&op, op_offset, op_len);
+                }
+                self.records += 1;
+                self.len = offset + len;
+            } else {
+                match decode_op(&content) {
+                    Ok(op) => pending.push((op, offset, len)),
+                    Err(e) => {
+                        warn!("Queue log {:?}: undecodable record at offset {}: {}", self.path, offset, e);
+                        break;
+                    }
+                }
+                self.records += 1;
+            }
+            offset += len;
+        }
+        self.records -= pending.len() as u64;
+
+        if self.len < size {
+            warn!("Queue log {:?}: dropping {} bytes after the last committed batch", self.path, size - self.len);
+            self.file.set_len(self.len)?;
+        }
+        Ok(())
+    }
+
+    /// Apply `op`, written at `offset`, to the index. Ops that do not apply
+    /// (the same ones SQLite would reject) leave it untouched.
+    fn index(&mut self, op: &StorageOp, offset: u64, len: u64) -> Result<(), &'static str> {
+        match op {
+            StorageOp::Insert(msg) => {
+                // A spill may have written the message first
+                if !self.main.contains_key(&msg.id) {
+                    self.insert_main(msg.id, LiveMessage::new(msg, offset, len));
+                }
+            }
+            StorageOp::Delete(id) => self.remove_main(id),
+            StorageOp::UpdateState { id, visible_at, attempts } => {
+                // Dispatched or requeued: the message is back in RAM
+                if let Some(entry) = self.main.get_mut(id) {
+                    entry.visible_at = *visible_at;
+                    entry.attempts = *attempts;
+                    if let Some(seq) = entry.spill_seq.take() {
+                        self.spilled.remove(&(entry.priority, seq));
+                    }
+                }
+            }
+            StorageOp::Spill { msg, seq } => match self.main.get_mut(&msg.id) {
+                Some(entry) => {
+                    if let Some(previous) = entry.spill_seq.replace(*seq) {
+                        self.spilled.remove(&(entry.priority, previous));
+                    }
+                    self.spilled.insert((entry.priority, *seq), msg.id);
+                }
+                None => {
+                    let mut entry = LiveMessage::new(msg, offset, len);
+                    entry.spill_seq = Some(*seq);
+                    self.spilled.insert((msg.priority, *seq), msg.id);
+                    self.insert_main(msg.id, entry);
+                }
+            },
+            StorageOp::DeleteSpilled { priority, after_seq } => {
+                let ids: Vec<Uuid> = self.spilled
+                    .range((Bound::Excluded((*priority, *after_seq)), Bound::Included((*priority, u64::MAX))))
+                    .map(|(_, id)| *id)
+                    .collect();
+                for id in ids {
+                    self.remove_main(&id);
+                }
+            }
+            StorageOp::InsertDLQ(msg) => self.insert_dlq(msg.id, offset, len)?,
+            StorageOp::DeleteDLQ(id) => self.remove_dlq(id),
+            StorageOp::MoveToDLQ { id, msg } => {
+                self.remove_main(id);
+                self.insert_dlq(msg.id, offset, len)?;
+            }
+            StorageOp::MoveToMain { id, msg } => {
+                self.remove_dlq(id);
+                if self.main.contains_key(&msg.id) {
+                    return Err("message already in the queue");
+                }
+                let mut entry = LiveMessage::new(msg, offset, len);
+                entry.attempts = 0;
+                self.insert_main(msg.id, entry);
+            }
+            StorageOp::PurgeDLQ => {
+                self.live_bytes -= self.dlq.values().map(|entry| entry.len).sum::<u64>();
+                self.dlq.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_main(&mut self, id: Uuid, entry: LiveMessage) {
+        self.live_bytes += entry.len;
+        self.main.insert(id, entry);
+    }
+
+    fn remove_main(&mut self, id: &Uuid) {
+        if let Some(entry) = self.main.remove(id) {
+            self.live_bytes -= entry.len;
+            if let Some(seq) = entry.spill_seq {
+                self.spilled.remove(&(entry.priority, seq));
+            }
+        }
+    }
+
+    fn insert_dlq(&mut self, id: Uuid, offset: u64, len: u64) -> Result<(), &'static str> {
+        if self.dlq.contains_key(&id) {
+            return Err("message already in the DLQ");
+        }
+        self.live_bytes += len;
+        self.dlq.insert(id, LiveDlq { offset, len });
+        Ok(())
+    }
+
+    fn remove_dlq(&mut self, id: &Uuid) {
+        if let Some(entry) = self.dlq.remove(id) {
+            self.live_bytes -= entry.len;
+        }
+    }
+
+    fn read_op(&self, offset: u64) -> Result<StorageOp, String> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        let content = read_record(&mut file, offset, self.len)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("no valid record at offset {}", offset))?;
+        decode_op(&content)
+    }
+
+    fn read_message(&self, entry: &LiveMessage) -> Result<Message, String> {
+        let mut msg = match self.read_op(entry.offset)? {
+            StorageOp::Insert(msg) | StorageOp::Spill { msg, .. } | StorageOp::MoveToMain { msg, .. } => msg,
+            other => return Err(format!("record at offset {} holds no message: {:?}", entry.offset, other)),
+        };
+        msg.visible_at = entry.visible_at;
+        msg.attempts = entry.attempts;
+        msg.state = restored_state(entry.visible_at, entry.attempts);
+        Ok(msg)
+    }
+
+    fn read_dlq(&self, entry: &LiveDlq) -> Result<DlqMessage, String> {
+        match self.read_op(entry.offset)? {
+            StorageOp::InsertDLQ(msg) | StorageOp::MoveToDLQ { msg, .. } => Ok(msg),
+            other => Err(format!("record at offset {} holds no DLQ message: {:?}", entry.offset, other)),
+        }
+    }
+
+    /// Write the live messages to a new log and rename it over this one.
+    fn rewrite(&mut self) -> Result<(), String> {
+        let mut compact_path = self.path.clone().into_os_string();
+        compact_path.push(COMPACT_SUFFIX);
+        let compact_path = PathBuf::from(compact_path);
+        let file = File::create(&compact_path).map_err(|e| e.to_string())?;
+        let mut out = BufWriter::new(file);
+
+        let mut main = LinkedHashMap::with_capacity(self.main.len());
+        let mut dlq = LinkedHashMap::with_capacity(self.dlq.len());
+        let mut record = Vec::new();
+        let mut written = 0u64;
+
+        for (id, entry) in self.main.iter() {
+            let msg = self.read_message(entry)?;
+            let op = match entry.spill_seq {
+                Some(seq) => StorageOp::Spill { msg, seq },
+                None => StorageOp::Insert(msg),
+            };
+            let len = encode_record(&mut record, |buf| encode_op(buf, &op));
+            out.write_all(&record).map_err(|e| e.to_string())?;
+            main.insert(*id, LiveMessage { offset: written, len, ..*entry });
+            written += len;
+        }
+        for (id, entry) in self.dlq.iter() {
+            let op = StorageOp::InsertDLQ(self.read_dlq(entry)?);
+            let len = encode_record(&mut record, |buf| encode_op(buf, &op));
+            out.write_all(&record).map_err(|e| e.to_string())?;
+            dlq.insert(*id, LiveDlq { offset: written, len });
+            written += len;
+        }
+        let live_bytes = written;
+        written += encode_record(&mut record, |buf| buf.put_u8(TAG_COMMIT));
+        out.write_all(&record).map_err(|e| e.to_string())?;
+
+        let file = out.into_inner().map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+        std::fs::rename(&compact_path, &self.path).map_err(|e| e.to_string())?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path).map_err(|e| e.to_string())?;
+
+        info!(
+            "Compacted queue log {:?}: {} -> {} bytes ({} messages, {} in the DLQ)",
+            self.path, self.len, written, main.len(), dlq.len()
+        );
+        self.records = (main.len() + dlq.len()) as u64 + 1;
+        self.main = main;
+        self.dlq = dlq;
+        self.len = written;
+        self.live_bytes = live_bytes;
+        Ok(())
+    }
+}
+
+impl QueueBackend for LogBackend {
+    fn load_all(&mut self) -> Result<Recovered, String> {
+        let mut main_messages = Vec::with_capacity(self.main.len());
+        for entry in self.main.values().filter(|entry| entry.spill_seq.is_none()) {
+            main_messages.push(self.read_message(entry).map_err(|e| format!("Failed to load main messages: {}", e))?);
+        }
+
+        let mut dlq_messages = Vec::with_capacity(self.dlq.len());
+        for entry in self.dlq.values() {
+            dlq_messages.push(self.read_dlq(entry).map_err(|e| format!("Failed to load DLQ messages: {}", e))?);
+        }
+
+        let mut lanes: BTreeMap<u8, SpilledLaneInfo> = BTreeMap::new();
+        for ((priority, seq), id) in &self.spilled {
+            let lane = lanes.entry(*priority).or_insert(SpilledLaneInfo { priority: *priority, count: 0, bytes: 0, last_seq: 0 });
+            lane.count += 1;
+            lane.bytes += self.main.get(id).map_or(0, |entry| entry.payload_len);
+            lane.last_seq = lane.last_seq.max(*seq);
+        }
+
+        Ok((main_messages, dlq_messages, lanes.into_values().collect()))
+    }
+
+    fn apply(&mut self, ops: &[&StorageOp]) -> Result<Vec<usize>, String> {
+        let mut batch = Vec::new();
+        let mut record = Vec::new();
+        let mut placed = Vec::with_capacity(ops.len());
+        for op in ops {
+            let len = encode_record(&mut record, |buf| encode_op(buf, op));
+            placed.push((self.len + batch.len() as u64, len));
+            batch.extend_from_slice(&record);
+        }
+        encode_record(&mut record, |buf| buf.put_u8(TAG_COMMIT));
+        batch.extend_from_slice(&record);
+
+        let mut file = &self.file;
+        let written = file.seek(SeekFrom::Start(self.len)).and_then(|_| file.write_all(&batch));
+        if let Err(e) = written {
+            // Replay would drop the partial batch anyway, keep the file clean
+            let _ = self.file.set_len(self.len);
+            return Err(format!("Failed to append batch to {:?}: {}", self.path, e));
+        }
+        if self.mode == PersistenceMode::FileSync {
+            self.file.sync_data().map_err(|e| format!("Failed to fsync {:?}: {}", self.path, e))?;
+        }
+        self.len += batch.len() as u64;
+        self.records += ops.len() as u64 + 1;
+
+        let mut skipped = Vec::new();
+        for (i, (op, (offset, len))) in ops.iter().zip(placed).enumerate() {
+            if let Err(e) = self.index(op, offset, len) {
+                error!("Failed to exec op {:?}: {}", op, e);
+                skipped.push(i);
+            }
+        }
+        Ok(skipped)
+    }
+
+    fn sync(&mut self) -> Result<(), String> {
+        self.file.sync_data().map_err(|e| format!("Failed to fsync {:?}: {}", self.path, e))
+    }
+
+    fn load_spilled(&mut self, priority: u8, after_seq: u64, limit: usize) -> Result<Vec<(Message, u64)>, String> {
+        self.spilled
+            .range((Bound::Excluded((priority, after_seq)), Bound::Included((priority, u64::MAX))))
+            .take(limit)
+            .map(|((_, seq), id)| {
+                let entry = self.main.get(id).ok_or_else(|| format!("paged-out message {} not indexed", id))?;
+                Ok((self.read_message(entry)?, *seq))
+            })
+            .collect()
+    }
+
+    /// Rewrite the log when it is more than twice the size of its live records.
+    fn compact(&mut self) {
+        let dead_records = self.records.saturating_sub((self.main.len() + self.dlq.len()) as u64);
+        self.stats.lag_frames.store(dead_records, Ordering::Relaxed);
+        self.stats.last_at.store(current_time_ms(), Ordering::Relaxed);
+        if self.len < COMPACT_MIN_BYTES || self.len <= self.live_bytes * 2 {
+            return;
+        }
+        match self.rewrite() {
+            Ok(()) => self.stats.lag_frames.store(0, Ordering::Relaxed),
+            Err(e) => {
+                self.stats.failures.fetch_add(1, Ordering::Relaxed);
+                warn!("Compaction failed for {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+// ==========================================
+// RECORD FORMAT
+// ==========================================
+
+/// Read the record at `offset`; `None` at the end of the valid data (EOF,
+/// a torn record or a CRC mismatch).
+fn read_record(reader: &mut impl Read, offset: u64, size: u64) -> std::io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+    let crc = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if len == 0 || offset + HEADER_LEN + len > size {
+        return Ok(None);
+    }
+    let mut content = vec![0u8; len as usize];
+    reader.read_exact(&mut content)?;
+    let mut hasher = Hasher::new();
+    hasher.update(&content);
+    Ok((hasher.finalize() == crc).then_some(content))
+}
+
+/// Frame what `encode` writes into `record` (cleared first); returns the record size.
+fn encode_record(record: &mut Vec<u8>, encode: impl FnOnce(&mut Vec<u8>)) -> u64 {
+    record.clear();
+    record.extend_from_slice(&[0u8; HEADER_LEN as usize]);
+    encode(record);
+    let content_len = record.len() - HEADER_LEN as usize;
+    let mut hasher = Hasher::new();
+    hasher.update(&record[HEADER_LEN as usize..]);
+    record[0..4].copy_from_slice(&(content_len as u32).to_be_bytes());
+    record[4..8].copy_from_slice(&hasher.finalize().to_be_bytes());
+    record.len() as u64
+}
+
+fn encode_op(buf: &mut Vec<u8>, op: &StorageOp) {
+    match op {
+        StorageOp::Insert(msg) => {
+            buf.put_u8(TAG_INSERT);
+            put_message(buf, msg);
+        }
+        StorageOp::Delete(id) => {
+            buf.put_u8(TAG_DELETE);
+            buf.put_slice(id.as_bytes());
+        }
+        StorageOp::UpdateState { id, visible_at, attempts } => {
+            buf.put_u8(TAG_UPDATE_STATE);
+            buf.put_slice(id.as_bytes());
+            buf.put_u64(*visible_at);
+            buf.put_u32(*attempts);
+        }
+        StorageOp::Spill { msg, seq } => {
+            buf.put_u8(TAG_SPILL);
+            buf.put_u64(*seq);
+            put_message(buf, msg);
+        }
+        StorageOp::DeleteSpilled { priority, after_seq } => {
+            buf.put_u8(TAG_DELETE_SPILLED);
+            buf.put_u8(*priority);
+            buf.put_u64(*after_seq);
+        }
+        StorageOp::InsertDLQ(msg) => {
+            buf.put_u8(TAG_INSERT_DLQ);
+            put_dlq_message(buf, msg);
+        }
+        StorageOp::DeleteDLQ(id) => {
+            buf.put_u8(TAG_DELETE_DLQ);
+            buf.put_slice(id.as_bytes());
+        }
+        StorageOp::MoveToDLQ { id, msg } => {
+            buf.put_u8(TAG_MOVE_TO_DLQ);
+            buf.put_slice(id.as_bytes());
+            put_dlq_message(buf, msg);
+        }
+        StorageOp::MoveToMain { id, msg } => {
+            buf.put_u8(TAG_MOVE_TO_MAIN);
+            buf.put_slice(id.as_bytes());
+            put_message(buf, msg);
+        }
+        StorageOp::PurgeDLQ => buf.put_u8(TAG_PURGE_DLQ),
+    }
+}
+
+/// `[id:16][priority:1][visible_at:8][attempts:4][created_at:8][group][payload]`
+fn put_message(buf: &mut Vec<u8>, msg: &Message) {
+    buf.put_slice(msg.id.as_bytes());
+    buf.put_u8(msg.priority);
+    buf.put_u64(msg.visible_at);
+    buf.put_u32(msg.attempts);
+    buf.put_u64(msg.created_at);
+    put_opt_str(buf, msg.message_group.as_deref());
+    buf.put_slice(&msg.payload);
+}
+
+/// `[id:16][priority:1][attempts:4][created_at:8][failed_at:8][reason][group][payload]`
+fn put_dlq_message(buf: &mut Vec<u8>, msg: &DlqMessage) {
+    buf.put_slice(msg.id.as_bytes());
+    buf.put_u8(msg.priority);
+    buf.put_u32(msg.attempts);
+    buf.put_u64(msg.created_at);
+    buf.put_u64(msg.failed_at);
+    put_opt_str(buf, Some(&msg.failure_reason));
+    put_opt_str(buf, msg.message_group.as_deref());
+    buf.put_slice(&msg.payload);
+}
+
+/// `[present:1][len:4][utf8]`
+fn put_opt_str(buf: &mut Vec<u8>, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            buf.put_u8(1);
+            buf.put_u32(value.len() as u32);
+            buf.put_slice(value.as_bytes());
+        }
+        None => buf.put_u8(0),
+    }
+}
+
+fn decode_op(content: &[u8]) -> Result<StorageOp, String> {
+    let mut cursor = Cursor(content);
+    let op = match cursor.u8()? {
+        TAG_INSERT => StorageOp::Insert(cursor.message()?),
+        TAG_DELETE => StorageOp::Delete(cursor.uuid()?),
+        TAG_UPDATE_STATE => StorageOp::UpdateState { id: cursor.uuid()?, visible_at: cursor.u64()?, attempts: cursor.u32()? },
+        TAG_SPILL => {
+            let seq = cursor.u64()?;
+            StorageOp::Spill { msg: cursor.message()?, seq }
+        }
+        TAG_DELETE_SPILLED => StorageOp::DeleteSpilled { priority: cursor.u8()?, after_seq: cursor.u64()? },
+        TAG_INSERT_DLQ => StorageOp::InsertDLQ(cursor.dlq_message()?),
+        TAG_DELETE_DLQ => StorageOp::DeleteDLQ(cursor.uuid()?),
+        TAG_MOVE_TO_DLQ => StorageOp::MoveToDLQ { id: cursor.uuid()?, msg: cursor.dlq_message()? },
+        TAG_MOVE_TO_MAIN => StorageOp::MoveToMain { id: cursor.uuid()?, msg: cursor.message()? },
+        TAG_PURGE_DLQ => StorageOp::PurgeDLQ,
+        tag => return Err(format!("unknown op tag {}", tag)),
+    };
+    Ok(op)
+}
+
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err(format!("record truncated: {} bytes left, {} needed", self.0.len(), n));
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().expect("4 bytes")))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().expect("8 bytes")))
+    }
+
+    fn uuid(&mut self) -> Result<Uuid, String> {
+        Uuid::from_slice(self.take(16)?).map_err(|e| e.to_string())
+    }
+
+    fn opt_str(&mut self) -> Result<Option<String>, String> {
+        if self.u8()? == 0 {
+            return Ok(None);
+        }
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn rest(&mut self) -> Bytes {
+        Bytes::copy_from_slice(std::mem::take(&mut self.0))
+    }
+
+    fn message(&mut self) -> Result<Message, String> {
+        let id = self.uuid()?;
+        let priority = self.u8()?;
+        let visible_at = self.u64()?;
+        let attempts = self.u32()?;
+        let created_at = self.u64()?;
+        let message_group = self.opt_str()?;
+        Ok(Message {
+            id,
+            payload: self.rest(),
+            priority,
+            attempts,
+            created_at,
+            visible_at,
+            failure_reason: None,
+            state: restored_state(visible_at, attempts),
+            message_group,
+        })
+    }
+
+    fn dlq_message(&mut self) -> Result<DlqMessage, String> {
+        let id = self.uuid()?;
+        let priority = self.u8()?;
+        let attempts = self.u32()?;
+        let created_at = self.u64()?;
+        let failed_at = self.u64()?;
+        let failure_reason = self.opt_str()?.unwrap_or_default();
+        let message_group = self.opt_str()?;
+        Ok(DlqMessage {
+            id,
+            payload: self.rest(),
+            priority,
+            attempts,
+            created_at,
+            failed_at,
+            failure_reason,
+            message_group,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(path: &Path) -> LogBackend {
+        LogBackend::open(path, PersistenceMode::FileAsync, Arc::new(CheckpointStats::default())).unwrap()
+    }
+
+    #[test]
+    fn torn_batch_is_dropped_on_replay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("q.qlog");
+        let kept = Message::new(Bytes::from("kept"), 0);
+        {
+            let mut log = open(&path);
+            log.apply(&[&StorageOp::Insert(kept.clone())]).unwrap();
+        }
+        let committed = std::fs::metadata(&path).unwrap().len();
+
+        // A batch cut before its commit record, then half a record
+        let mut torn = Vec::new();
+        encode_record(&mut torn, |buf| encode_op(buf, &StorageOp::Insert(Message::new(Bytes::from("lost"), 0))));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&torn).unwrap();
+        file.write_all(&torn[..5]).unwrap();
+
+        let mut log = open(&path);
+        let (main, dlq, spilled) = log.load_all().unwrap();
+        assert_eq!(main.iter().map(|m| m.id).collect::<Vec<_>>(), vec![kept.id]);
+        assert!(dlq.is_empty() && spilled.is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), committed, "Uncommitted tail is truncated");
+    }
+
+    #[test]
+    fn compaction_keeps_live_messages_and_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("q.qlog");
+        let payload = Bytes::from(vec![7u8; 4096]);
+        let mut log = open(&path);
+
+        let mut ids = Vec::new();
+        for _ in 0..400 {
+            let msg = Message::new(payload.clone(), 0);
+            ids.push(msg.id);
+            log.apply(&[&StorageOp::Insert(msg)]).unwrap();
+        }
+        for id in &ids[..390] {
+            log.apply(&[&StorageOp::Delete(*id)]).unwrap();
+        }
+        log.apply(&[&StorageOp::UpdateState { id: ids[390], visible_at: 0, attempts: 3 }]).unwrap();
+        log.apply(&[&StorageOp::Spill { msg: Message::new(payload.clone(), 2), seq: 1 }]).unwrap();
+        let before = std::fs::metadata(&path).unwrap().len();
+
+        log.compact();
+        let after = std::fs::metadata(&path).unwrap().len();
+        assert!(after * 10 < before, "{} -> {} bytes", before, after);
+        assert_eq!(log.stats.lag_frames.load(Ordering::Relaxed), 0);
+
+        let mut log = open(&path);
+        let (main, _, spilled) = log.load_all().unwrap();
+        assert_eq!(main.iter().map(|m| m.id).collect::<Vec<_>>(), ids[390..].to_vec());
+        assert_eq!(main[0].attempts, 3);
+        assert_eq!((spilled.len(), spilled[0].priority, spilled[0].bytes), (1, 2, 4096));
+        assert_eq!(log.load_spilled(2, 0, 10).unwrap().len(), 1);
+    }
+}
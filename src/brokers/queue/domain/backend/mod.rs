@@ -0,0 +1,132 @@
+//! Storage backends behind a queue's persistence writer. The writer task owns
+//! one `QueueBackend` and hands it batches of `StorageOp`s; recovery, spill
+//! reloads and periodic maintenance go through the same trait. SQLite is the
+//! default; the append-only log needs nothing but a filesystem.
+
+pub mod log;
+pub mod sqlite;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::queue::domain::persistence::{CheckpointStats, Recovered, SqliteTuning, StorageOp};
+use crate::brokers::queue::domain::queue::{current_time_ms, Message, MessageState};
+
+pub trait QueueBackend: Send {
+    /// Everything persisted: messages in RAM, the DLQ and the paged-out lanes
+    /// (counted, left on disk).
+    fn load_all(&mut self) -> Result<Recovered, String>;
+
+    /// Apply a batch atomically: after a crash either all of it or none of
+    /// it is recovered. An op that does not apply (e.g. a duplicate DLQ
+    /// insert) is logged and skipped, the rest of the batch still commits:
+    /// returns the positions of the skipped ops in `ops`.
+    fn apply(&mut self, ops: &[&StorageOp]) -> Result<Vec<usize>, String>;
+
+    /// Fsync what `apply` committed, whatever the persistence mode.
+    fn sync(&mut self) -> Result<(), String>;
+
+    /// Up to `limit` paged-out messages of `priority` spilled after
+    /// `after_seq`, with their spill sequence, in spill order.
+    fn load_spilled(&mut self, priority: u8, after_seq: u64, limit: usize) -> Result<Vec<(Message, u64)>, String>;
+
+    /// Periodic maintenance (WAL checkpoint, log compaction), reported in
+    /// the shared `CheckpointStats`.
+    fn compact(&mut self);
+}
+
+/// On-disk format of a queue, chosen by `QUEUE_STORAGE_BACKEND` for new
+/// queues. An existing queue keeps the format found on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// `<queue>.db` (WAL mode)
+    #[default]
+    Sqlite,
+    /// `<queue>.qlog`, an append-only log of CRC-framed ops
+    Log,
+}
+
+impl std::str::FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sqlite" => Ok(Self::Sqlite),
+            "log" => Ok(Self::Log),
+            other => Err(format!("unknown queue storage backend '{}'", other)),
+        }
+    }
+}
+
+impl BackendKind {
+    pub const ALL: [BackendKind; 2] = [BackendKind::Sqlite, BackendKind::Log];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Sqlite => "db",
+            Self::Log => "qlog",
+        }
+    }
+
+    pub fn path(self, dir: &Path, queue: &str) -> PathBuf {
+        dir.join(format!("{}.{}", queue, self.extension()))
+    }
+
+    /// Every file of a queue in this format, whether it exists or not.
+    pub fn files(self, dir: &Path, queue: &str) -> Vec<PathBuf> {
+        let main = self.path(dir, queue);
+        let suffixes: &[&str] = match self {
+            Self::Sqlite => &["-wal", "-shm"],
+            Self::Log => &[log::COMPACT_SUFFIX],
+        };
+        let mut files = vec![main.clone()];
+        for suffix in suffixes {
+            let mut path = main.clone().into_os_string();
+            path.push(suffix);
+            files.push(PathBuf::from(path));
+        }
+        files
+    }
+
+    /// The format `queue` is stored in: `preferred` if its file exists or
+    /// none does, otherwise the one found on disk.
+    pub fn detect(dir: &Path, queue: &str, preferred: BackendKind) -> BackendKind {
+        if preferred.path(dir, queue).exists() {
+            return preferred;
+        }
+        Self::ALL.into_iter().find(|kind| kind.path(dir, queue).exists()).unwrap_or(preferred)
+    }
+
+    /// The queue a file of the persistence directory stores, if it is one.
+    pub fn queue_name(file_name: &str) -> Option<&str> {
+        Self::ALL.into_iter().find_map(|kind| {
+            file_name.strip_suffix(kind.extension())?.strip_suffix('.').filter(|name| !name.is_empty())
+        })
+    }
+
+    pub fn open(
+        self,
+        path: &Path,
+        mode: PersistenceMode,
+        tuning: &SqliteTuning,
+        stats: Arc<CheckpointStats>,
+    ) -> Result<Box<dyn QueueBackend>, String> {
+        match self {
+            Self::Sqlite => Ok(Box::new(sqlite::SqliteBackend::open(path, mode, tuning, stats)?)),
+            Self::Log => Ok(Box::new(log::LogBackend::open(path, mode, stats)?)),
+        }
+    }
+}
+
+/// State of a recovered message: persisted as `visible_at` and `attempts` only.
+fn restored_state(visible_at: u64, attempts: u32) -> MessageState {
+    let now = current_time_ms();
+    if visible_at > now && attempts > 0 {
+        MessageState::InFlight(visible_at)
+    } else if visible_at > now {
+        MessageState::Scheduled(visible_at)
+    } else {
+        MessageState::Ready
+    }
+}
@@ -0,0 +1,411 @@
+//! The default backend: one SQLite database per queue in WAL mode, a `queue`
+//! and a `dlq_messages` table. Each batch is one transaction.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use rusqlite::{params, types::Type, Connection, Result};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::queue::domain::backend::{restored_state, QueueBackend};
+use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::queue::domain::persistence::{
+    CheckpointStats, Recovered, SpilledLaneInfo, SqliteSynchronous, SqliteTuning, StorageOp,
+};
+use crate::brokers::queue::domain::queue::{current_time_ms, Message};
+
+pub struct SqliteBackend {
+    conn: Connection,
+    db_path: PathBuf,
+    stats: Arc<CheckpointStats>,
+}
+
+impl SqliteBackend {
+    pub fn open(db_path: &Path, mode: PersistenceMode, tuning: &SqliteTuning, stats: Arc<CheckpointStats>) -> Result<Self, String> {
+        let conn = Connection::open(db_path).map_err(|e| format!("Cannot open queue DB at {:?}: {}", db_path, e))?;
+        init_db(&conn, tuning).map_err(|e| format!("Failed to initialize queue DB at {:?}: {}", db_path, e))?;
+
+        // Writer pragmas for high-throughput batch operations.
+        // FileAsync: synchronous per QUEUE_SQLITE_SYNCHRONOUS (default OFF, data is
+        // flushed periodically via timer).
+        // FileSync: synchronous=FULL, every commit fsyncs the WAL.
+        let synchronous = match mode {
+            PersistenceMode::FileAsync => tuning.synchronous.pragma(),
+            PersistenceMode::FileSync => SqliteSynchronous::Full.pragma(),
+        };
+        if let Err(e) = conn.execute_batch(&format!(
+            "PRAGMA synchronous = {};
+             PRAGMA mmap_size = 268435456;
+             PRAGMA page_size = 8192;",
+            synchronous
+        )) {
+            error!("Failed to set writer pragmas: {}", e);
+        }
+
+        Ok(Self { conn, db_path: db_path.to_path_buf(), stats })
+    }
+}
+
+impl QueueBackend for SqliteBackend {
+    fn load_all(&mut self) -> Result<Recovered, String> {
+        let main_messages = load_all_messages(&self.conn)
+            .map_err(|e| format!("Failed to load main messages: {}", e))?;
+
+        let dlq_messages = load_dlq_messages(&self.conn)
+            .map_err(|e| format!("Failed to load DLQ messages: {}", e))?;
+
+        let spilled = load_spilled_lanes(&self.conn)
+            .map_err(|e| format!("Failed to count paged-out messages: {}", e))?;
+
+        Ok((main_messages, dlq_messages, spilled))
+    }
+
+    fn apply(&mut self, ops: &[&StorageOp]) -> Result<Vec<usize>, String> {
+        let tx = self.conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let mut skipped = Vec::new();
+        for (i, op) in ops.iter().enumerate() {
+            if let Err(e) = exec_op(&tx, op) {
+                error!("Failed to exec op {:?}: {}", op, e);
+                skipped.push(i);
+            }
+        }
+        tx.commit().map_err(|e| format!("Failed to commit batch: {}", e))?;
+        Ok(skipped)
+    }
+
+    /// Fsync the DB and its WAL (committed `FileAsync` writes may sit in the page cache).
+    fn sync(&mut self) -> Result<(), String> {
+        let mut wal_path = self.db_path.as_os_str().to_owned();
+        wal_path.push("-wal");
+        for path in [self.db_path.as_path(), Path::new(&wal_path)] {
+            match std::fs::File::open(path) {
+                Ok(file) => file.sync_all().map_err(|e| format!("Failed to fsync {:?}: {}", path, e))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to open {:?}: {}", path, e)),
+            }
+        }
+        Ok(())
+    }
+
+    fn load_spilled(&mut self, priority: u8, after_seq: u64, limit: usize) -> Result<Vec<(Message, u64)>, String> {
+        load_spilled(&self.conn, priority, after_seq, limit).map_err(|e| e.to_string())
+    }
+
+    /// Passive checkpoint: copies what it can from the WAL without waiting on
+    /// readers, so it never stalls the writer.
+    fn compact(&mut self) {
+        let result = self.conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+            Ok((row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+        });
+        match result {
+            Ok((log, checkpointed)) => {
+                self.stats.lag_frames.store((log - checkpointed).max(0) as u64, Ordering::Relaxed);
+                self.stats.last_at.store(current_time_ms(), Ordering::Relaxed);
+            }
+            Err(e) => {
+                self.stats.failures.fetch_add(1, Ordering::Relaxed);
+                warn!("WAL checkpoint failed for {:?}: {}", self.db_path, e);
+            }
+        }
+    }
+}
+
+// ==========================================
+// SQLITE OPERATIONS
+// ==========================================
+
+fn uuid_from_blob(id_blob: Vec<u8>) -> Result<Uuid> {
+    Uuid::from_slice(&id_blob).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(id_blob.len(), Type::Blob, Box::new(e))
+    })
+}
+
+fn init_db(conn: &Connection, tuning: &SqliteTuning) -> Result<()> {
+    conn.busy_timeout(Duration::from_millis(tuning.busy_timeout_ms))?;
+    // synchronous=NORMAL for safety during table creation, the writer
+    // settings are applied once the schema exists
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         PRAGMA foreign_keys = ON;
+         PRAGMA cache_size = -64000;
+         PRAGMA temp_store = MEMORY;
+         "
+    )?;
+
+    // Main Queue Table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS queue (
+            id BLOB PRIMARY KEY,
+            payload BLOB NOT NULL,
+            priority INTEGER NOT NULL,
+            visible_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            message_group TEXT
+        )",
+        [],
+    )?;
+
+    // DLQ Table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dlq_messages (
+            id BLOB PRIMARY KEY,
+            payload BLOB NOT NULL,
+            priority INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            failed_at INTEGER NOT NULL,
+            error TEXT,
+            message_group TEXT
+        )",
+        [],
+    )?;
+
+    // Files created before message groups
+    for table in ["queue", "dlq_messages"] {
+        if !has_column(conn, table, "message_group")? {
+            conn.execute(&format!("ALTER TABLE {} ADD COLUMN message_group TEXT", table), [])?;
+        }
+    }
+
+    // Files created before spill-to-disk. NULL = the message is in RAM
+    if !has_column(conn, "queue", "spill_seq")? {
+        conn.execute("ALTER TABLE queue ADD COLUMN spill_seq INTEGER", [])?;
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS queue_spilled ON queue (priority, spill_seq) WHERE spill_seq IS NOT NULL",
+        [],
+    )?;
+
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let mut names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    names.try_fold(false, |found, name| Ok(found || name? == column))
+}
+
+fn load_all_messages(conn: &Connection) -> Result<Vec<Message>> {
+    let mut stmt = conn.prepare(
+        // rowid keeps insertion order: legacy UUIDv4 ids carry no time ordering of their own
+        "SELECT id, payload, priority, visible_at, attempts, created_at, message_group FROM queue WHERE spill_seq IS NULL ORDER BY rowid"
+    )?;
+
+    let message_iter = stmt.query_map([], message_from_row)?;
+
+    let mut messages = Vec::new();
+    for msg in message_iter {
+        messages.push(msg?);
+    }
+    Ok(messages)
+}
+
+fn message_from_row(row: &rusqlite::Row) -> Result<Message> {
+    let id_blob: Vec<u8> = row.get(0)?;
+    let id = uuid_from_blob(id_blob)?;
+    let payload: Vec<u8> = row.get(1)?;
+    let priority: u8 = row.get(2)?;
+    let visible_at = row.get::<_, i64>(3)? as u64;
+    let attempts: u32 = row.get(4)?;
+    let created_at = row.get::<_, i64>(5)? as u64;
+    let message_group: Option<String> = row.get(6)?;
+
+    Ok(Message {
+        id,
+        payload: bytes::Bytes::from(payload),
+        priority,
+        attempts,
+        created_at,
+        visible_at,
+        failure_reason: None, // Not persisted in main queue yet
+        state: restored_state(visible_at, attempts),
+        message_group,
+    })
+}
+
+fn load_spilled_lanes(conn: &Connection) -> Result<Vec<SpilledLaneInfo>> {
+    let mut stmt = conn.prepare(
+        "SELECT priority, COUNT(*), SUM(LENGTH(payload)), MAX(spill_seq) FROM queue
+         WHERE spill_seq IS NOT NULL GROUP BY priority"
+    )?;
+    let lanes = stmt.query_map([], |row| {
+        Ok(SpilledLaneInfo {
+            priority: row.get(0)?,
+            count: row.get::<_, i64>(1)? as usize,
+            bytes: row.get::<_, i64>(2)? as usize,
+            last_seq: row.get::<_, i64>(3)? as u64,
+        })
+    })?;
+    lanes.collect()
+}
+
+fn load_spilled(conn: &Connection, priority: u8, after_seq: u64, limit: usize) -> Result<Vec<(Message, u64)>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, payload, priority, visible_at, attempts, created_at, message_group, spill_seq FROM queue
+         WHERE priority = ?1 AND spill_seq > ?2 ORDER BY spill_seq LIMIT ?3"
+    )?;
+    let rows = stmt.query_map(params![priority, after_seq as i64, limit.min(i64::MAX as usize) as i64], |row| {
+        Ok((message_from_row(row)?, row.get::<_, i64>(7)? as u64))
+    })?;
+    rows.collect()
+}
+
+fn load_dlq_messages(conn: &Connection) -> Result<Vec<DlqMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, payload, priority, attempts, created_at, failed_at, error, message_group FROM dlq_messages ORDER BY rowid"
+    )?;
+
+    let message_iter = stmt.query_map([], |row| {
+        let id_blob: Vec<u8> = row.get(0)?;
+        let id = uuid_from_blob(id_blob)?;
+        let payload: Vec<u8> = row.get(1)?;
+        let priority: u8 = row.get(2)?;
+        let attempts: u32 = row.get(3)?;
+        let created_at = row.get::<_, i64>(4)? as u64;
+        let failed_at = row.get::<_, i64>(5)? as u64;
+        let error: Option<String> = row.get(6)?;
+        let message_group: Option<String> = row.get(7)?;
+
+        Ok(DlqMessage {
+            id,
+            payload: bytes::Bytes::from(payload),
+            priority,
+            attempts,
+            created_at,
+            failed_at,
+            failure_reason: error.unwrap_or_default(),
+            message_group,
+        })
+    })?;
+
+    let mut messages = Vec::new();
+    for msg in message_iter {
+        messages.push(msg?);
+    }
+    Ok(messages)
+}
+
+fn exec_op(tx: &rusqlite::Transaction, op: &StorageOp) -> Result<()> {
+    match op {
+        StorageOp::Insert(msg) => {
+            let mut stmt = tx.prepare_cached(
+                // A spill may have written the row first
+                "INSERT OR IGNORE INTO queue (id, payload, priority, visible_at, attempts, created_at, message_group)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+            )?;
+            stmt.execute(params![
+                msg.id.as_bytes(),
+                msg.payload.as_ref(), // Bytes -> &[u8]
+                msg.priority,
+                msg.visible_at as i64,
+                msg.attempts,
+                msg.created_at as i64,
+                msg.message_group
+            ])?;
+        }
+        StorageOp::Delete(id) => {
+            let mut stmt = tx.prepare_cached("DELETE FROM queue WHERE id = ?1")?;
+            stmt.execute(params![id.as_bytes()])?;
+        }
+        StorageOp::UpdateState { id, visible_at, attempts } => {
+            // Dispatched or requeued: the message is back in RAM
+            let mut stmt = tx.prepare_cached(
+                "UPDATE queue SET visible_at = ?1, attempts = ?2, spill_seq = NULL WHERE id = ?3"
+            )?;
+            stmt.execute(params![*visible_at as i64, *attempts, id.as_bytes()])?;
+        }
+        StorageOp::Spill { msg, seq } => {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO queue (id, payload, priority, visible_at, attempts, created_at, message_group, spill_seq)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT(id) DO UPDATE SET spill_seq = excluded.spill_seq"
+            )?;
+            stmt.execute(params![
+                msg.id.as_bytes(),
+                msg.payload.as_ref(),
+                msg.priority,
+                msg.visible_at as i64,
+                msg.attempts,
+                msg.created_at as i64,
+                msg.message_group,
+                *seq as i64
+            ])?;
+        }
+        StorageOp::DeleteSpilled { priority, after_seq } => {
+            let mut stmt = tx.prepare_cached("DELETE FROM queue WHERE priority = ?1 AND spill_seq > ?2")?;
+            stmt.execute(params![priority, *after_seq as i64])?;
+        }
+
+        // DLQ Operations
+        StorageOp::InsertDLQ(msg) => {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO dlq_messages (id, payload, priority, attempts, created_at, failed_at, error, message_group)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            stmt.execute(params![
+                msg.id.as_bytes(),
+                msg.payload.as_ref(),
+                msg.priority,
+                msg.attempts,
+                msg.created_at as i64,
+                msg.failed_at as i64,
+                msg.failure_reason,
+                msg.message_group
+            ])?;
+        }
+        StorageOp::DeleteDLQ(id) => {
+            let mut stmt = tx.prepare_cached("DELETE FROM dlq_messages WHERE id = ?1")?;
+            stmt.execute(params![id.as_bytes()])?;
+        }
+        StorageOp::MoveToDLQ { id, msg } => {
+            // Atomic: delete from queue, insert into DLQ
+            let mut stmt = tx.prepare_cached("DELETE FROM queue WHERE id = ?1")?;
+            stmt.execute(params![id.as_bytes()])?;
+
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO dlq_messages (id, payload, priority, attempts, created_at, failed_at, error, message_group)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            stmt.execute(params![
+                msg.id.as_bytes(),
+                msg.payload.as_ref(),
+                msg.priority,
+                msg.attempts,
+                msg.created_at as i64,
+                msg.failed_at as i64,
+                msg.failure_reason,
+                msg.message_group
+            ])?;
+        }
+        StorageOp::MoveToMain { id, msg } => {
+            // Atomic: delete from DLQ, insert into queue
+            let mut stmt = tx.prepare_cached("DELETE FROM dlq_messages WHERE id = ?1")?;
+            stmt.execute(params![id.as_bytes()])?;
+
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO queue (id, payload, priority, visible_at, attempts, created_at, message_group)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+            )?;
+            stmt.execute(params![
+                msg.id.as_bytes(),
+                msg.payload.as_ref(),
+                msg.priority,
+                msg.visible_at as i64, // 0 = ready, else scheduled (delayed replay)
+                0u32, // reset attempts
+                msg.created_at as i64,
+                msg.message_group
+            ])?;
+            // failure_reason is lost when moving back to main because table doesn't support it yet
+            // and we are resetting the message anyway.
+        }
+        StorageOp::PurgeDLQ => {
+            tx.execute("DELETE FROM dlq_messages", [])?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Per-queue consumer bookkeeping for introspection.
+//!
+//! A consumer is the connection that issued CONSUME, or subscribed for push
+//! delivery (see `queue::push`). Each dispatched message
+//! remembers its consumer until it is acked, nacked or its visibility timeout
+//! expires, which gives per-consumer in-flight counts; acks feed a sliding
+//! one-minute rate (`rate::RateWindow`, also used for the per-priority rates of the
+//! queue state). Consumers are dropped when their connection closes.
+
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::brokers::queue::domain::queue::current_time_ms;
+use crate::brokers::rate::RateWindow;
+
+/// How a dispatched message left its consumer's hands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settlement {
+    Acked,
+    Nacked,
+    Expired,
+    /// Taken for a request cancelled before the reply: never delivered
+    Returned,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsumerSnapshot {
+    pub id: String,
+    /// Batch size of the consumer's last CONSUME, or its push window
+    pub prefetch: usize,
+    /// Subscribed for push delivery
+    pub push: bool,
+    pub in_flight: usize,
+    /// Long-polls currently parked on the queue
+    pub waiting: usize,
+    pub delivered: u64,
+    pub acked: u64,
+    pub nacked: u64,
+    pub expired: u64,
+    /// Acks per second over the last minute
+    pub ack_rate: f64,
+    pub first_seen: u64,
+    pub last_active: u64,
+}
+
+#[derive(Debug, Default)]
+struct ConsumerStats {
+    prefetch: usize,
+    push: bool,
+    in_flight: usize,
+    waiting: usize,
+    delivered: u64,
+    acked: u64,
+    nacked: u64,
+    expired: u64,
+    acks: RateWindow,
+    first_seen: u64,
+    last_active: u64,
+    /// Since when it has held deliveries without settling any
+    stalled_since: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct ConsumerRegistry {
+    consumers: HashMap<String, ConsumerStats>,
+    /// Consumer currently holding each in-flight message
+    owners: HashMap<Uuid, String>,
+}
+
+impl ConsumerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn stats(&mut self, consumer: &str) -> &mut ConsumerStats {
+        let now = current_time_ms();
+        let stats = self.consumers.entry(consumer.to_string()).or_insert_with(|| ConsumerStats {
+            first_seen: now,
+            ..Default::default()
+        });
+        stats.last_active = now;
+        stats
+    }
+
+    /// A CONSUME request arrived.
+    pub fn requested(&mut self, consumer: &str, prefetch: usize) {
+        self.stats(consumer).prefetch = prefetch;
+    }
+
+    /// A push subscription started (`Some(window)`) or ended.
+    pub fn set_push(&mut self, consumer: &str, window: Option<usize>) {
+        let stats = self.stats(consumer);
+        stats.push = window.is_some();
+        if let Some(window) = window {
+            stats.prefetch = window;
+            // A new subscription starts a fresh idle clock
+            stats.stalled_since = (stats.in_flight > 0).then_some(stats.last_active);
+        }
+    }
+
+    /// Since when (ms) `consumer` has held deliveries without acking or
+    /// nacking any; `None` while it keeps up.
+    pub fn stalled_since(&self, consumer: &str) -> Option<u64> {
+        self.consumers.get(consumer)?.stalled_since
+    }
+
+    /// Messages dispatched to `consumer` and not settled yet.
+    pub fn in_flight(&self, consumer: &str) -> usize {
+        self.consumers.get(consumer).map_or(0, |stats| stats.in_flight)
+    }
+
+    pub fn set_waiting(&mut self, consumer: &str, waiting: bool) {
+        let stats = self.stats(consumer);
+        if waiting {
+            stats.waiting += 1;
+        } else {
+            stats.waiting = stats.waiting.saturating_sub(1);
+        }
+    }
+
+    pub fn dispatched(&mut self, consumer: &str, ids: impl IntoIterator<Item = Uuid>) {
+        let mut count = 0;
+        for id in ids {
+            // A redelivered message changes hands
+            if let Some(previous) = self.owners.insert(id, consumer.to_string()) {
+                if let Some(stats) = self.consumers.get_mut(&previous) {
+                    stats.in_flight = stats.in_flight.saturating_sub(1);
+                }
+            }
+            count += 1;
+        }
+        let stats = self.stats(consumer);
+        stats.in_flight += count;
+        stats.delivered += count as u64;
+        if count > 0 {
+            stats.stalled_since.get_or_insert(stats.last_active);
+        }
+    }
+
+    pub fn settled(&mut self, id: &Uuid, how: Settlement) {
+        let Some(consumer) = self.owners.remove(id) else { return };
+        let Some(stats) = self.consumers.get_mut(&consumer) else { return };
+        stats.in_flight = stats.in_flight.saturating_sub(1);
+        match how {
+            Settlement::Acked => {
+                let now = current_time_ms();
+                stats.acked += 1;
+                stats.acks.record(now);
+                stats.last_active = now;
+                stats.stalled_since = (stats.in_flight > 0).then_some(now);
+            }
+            Settlement::Nacked => {
+                let now = current_time_ms();
+                stats.nacked += 1;
+                stats.last_active = now;
+                stats.stalled_since = (stats.in_flight > 0).then_some(now);
+            }
+            Settlement::Expired => stats.expired += 1,
+            Settlement::Returned => stats.delivered = stats.delivered.saturating_sub(1),
+        }
+    }
+
+    /// Forget a consumer whose connection closed. Its in-flight messages are
+    /// redelivered when their visibility timeout expires.
+    pub fn remove(&mut self, consumer: &str) {
+        if self.consumers.remove(consumer).is_some() {
+            self.owners.retain(|_, owner| owner != consumer);
+        }
+    }
+
+    pub fn snapshot(&mut self) -> Vec<ConsumerSnapshot> {
+        let now = current_time_ms();
+        let mut list: Vec<ConsumerSnapshot> = self.consumers.iter_mut()
+            .map(|(id, stats)| ConsumerSnapshot {
+                id: id.clone(),
+                prefetch: stats.prefetch,
+                push: stats.push,
+                in_flight: stats.in_flight,
+                waiting: stats.waiting,
+                delivered: stats.delivered,
+                acked: stats.acked,
+                nacked: stats.nacked,
+                expired: stats.expired,
+                ack_rate: stats.acks.per_sec(now),
+                first_seen: stats.first_seen,
+                last_active: stats.last_active,
+            })
+            .collect();
+        list.sort_by_key(|c| c.first_seen);
+        list
+    }
+}
@@ -0,0 +1,45 @@
+//! Idempotency keys of recent pushes, per queue.
+//!
+//! A push carrying a key already seen within the window is dropped before it
+//! is enqueued. The window counts from the first push of a key (duplicates do
+//! not extend it) and lives in memory only: a restart forgets every key.
+
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Default)]
+pub struct DedupWindow {
+    /// Key -> time it expires (ms)
+    keys: HashMap<String, u64>,
+    /// Keys in expiry order, for pruning
+    expiries: VecDeque<(u64, String)>,
+}
+
+impl DedupWindow {
+    /// Remember `key` until `now + window_ms`. Returns false if it is a
+    /// duplicate of a push still in the window.
+    pub fn insert(&mut self, key: &str, now: u64, window_ms: u64) -> bool {
+        self.prune(now);
+        if self.keys.contains_key(key) {
+            return false;
+        }
+        let expires_at = now + window_ms;
+        self.keys.insert(key.to_string(), expires_at);
+        self.expiries.push_back((expires_at, key.to_string()));
+        true
+    }
+
+    /// Forget `key`, e.g. when the push it guarded failed.
+    pub fn remove(&mut self, key: &str) {
+        self.keys.remove(key);
+    }
+
+    fn prune(&mut self, now: u64) {
+        while self.expiries.front().is_some_and(|(expires_at, _)| *expires_at <= now) {
+            let Some((expires_at, key)) = self.expiries.pop_front() else { break };
+            // A removed key may have been pushed again since: keep the newer entry
+            if self.keys.get(&key) == Some(&expires_at) {
+                self.keys.remove(&key);
+            }
+        }
+    }
+}
@@ -10,6 +10,7 @@ use uuid::Uuid;
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use crate::brokers::queue::domain::queue::{Message, MessageState, current_time_ms};
+use crate::brokers::queue::domain::search::SearchIndex;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DlqMessage {
@@ -20,6 +21,9 @@ pub struct DlqMessage {
     pub created_at: u64,
     pub failed_at: u64,
     pub failure_reason: String,
+    /// Kept so a replayed message goes back to its group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group: Option<String>,
 }
 
 impl DlqMessage {
@@ -32,6 +36,7 @@ impl DlqMessage {
             created_at: msg.created_at,
             failed_at: current_time_ms(),
             failure_reason: reason,
+            message_group: msg.message_group,
         }
     }
 
@@ -45,40 +50,158 @@ impl DlqMessage {
             visible_at: 0, // Ready immediately
             failure_reason: None, // Clear reason
             state: MessageState::Ready,
+            message_group: self.message_group,
         }
     }
 }
 
+/// DLQ messages a filtered page or bulk operation covers. Every criterion
+/// set must hold; an empty filter matches every message.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DlqFilter {
+    /// Substring of the failure reason
+    pub reason: Option<String>,
+    /// Substring of the payload
+    pub payload: Option<String>,
+    /// Payload or failure reason, like `DlqState::matching`
+    pub search: Option<String>,
+    /// Failed at least this long ago
+    pub older_than_ms: Option<u64>,
+    /// Failed at most this long ago
+    pub newer_than_ms: Option<u64>,
+}
+
+impl DlqFilter {
+    pub fn is_empty(&self) -> bool {
+        let blank = |s: &Option<String>| s.as_deref().is_none_or(str::is_empty);
+        blank(&self.reason) && blank(&self.payload) && blank(&self.search)
+            && self.older_than_ms.is_none() && self.newer_than_ms.is_none()
+    }
+
+    fn matches(&self, msg: &DlqMessage, now: u64) -> bool {
+        let age = now.saturating_sub(msg.failed_at);
+        self.reason.as_deref().is_none_or(|s| msg.failure_reason.contains(s))
+            && self.payload.as_deref().is_none_or(|s| String::from_utf8_lossy(&msg.payload).contains(s))
+            && self.older_than_ms.is_none_or(|ms| age >= ms)
+            && self.newer_than_ms.is_none_or(|ms| age <= ms)
+    }
+}
+
 pub struct DlqState {
     /// Ordered map of failed messages.
     /// Order is FIFO (insertion order).
     messages: LinkedHashMap<Uuid, DlqMessage>,
+    /// Payload and failure reason words (`QUEUE_SEARCH_INDEX`)
+    search: Option<SearchIndex>,
+    /// Payload and failure reason bytes
+    bytes: usize,
 }
 
 impl DlqState {
     pub fn new() -> Self {
         Self {
             messages: LinkedHashMap::new(),
+            search: None,
+            bytes: 0,
         }
     }
 
+    /// Keep a search index from now on (messages already here included).
+    pub fn enable_search_index(&mut self) {
+        let mut index = SearchIndex::default();
+        for msg in self.messages.values() {
+            index.insert(msg.id, &[&msg.payload, msg.failure_reason.as_bytes()]);
+        }
+        self.search = Some(index);
+    }
+
     pub fn push(&mut self, msg: DlqMessage) {
+        if let Some(index) = self.search.as_mut() {
+            index.insert(msg.id, &[&msg.payload, msg.failure_reason.as_bytes()]);
+        }
+        self.bytes += msg.payload.len() + msg.failure_reason.len();
         // Updates position to end if already exists (which shouldn't happen usually)
-        self.messages.insert(msg.id, msg);
+        if let Some(previous) = self.messages.insert(msg.id, msg) {
+            self.bytes -= previous.payload.len() + previous.failure_reason.len();
+        }
     }
 
     pub fn remove(&mut self, id: &Uuid) -> Option<DlqMessage> {
-        self.messages.remove(id)
+        if let Some(index) = self.search.as_mut() {
+            index.remove(id);
+        }
+        let removed = self.messages.remove(id)?;
+        self.bytes -= removed.payload.len() + removed.failure_reason.len();
+        Some(removed)
     }
 
     pub fn clear(&mut self) {
+        if let Some(index) = self.search.as_mut() {
+            index.clear();
+        }
         self.messages.clear();
+        self.bytes = 0;
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.messages.contains_key(id)
     }
 
     pub fn len(&self) -> usize {
         self.messages.len()
     }
 
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Ids of the messages matching `search` (all of them without one), oldest
+    /// failure first. With the search index a message matches when its payload
+    /// and failure reason hold every word of `search`, else when either contains it.
+    pub fn matching(&self, search: Option<&str>) -> Vec<Uuid> {
+        let indexed = search.zip(self.search.as_ref()).and_then(|(s, index)| index.query(s));
+        self.messages.values()
+            .filter(|m| match (&indexed, search) {
+                (Some(ids), _) => ids.contains(&m.id),
+                (None, Some(s)) => m.failure_reason.contains(s) || String::from_utf8_lossy(&m.payload).contains(s),
+                (None, None) => true,
+            })
+            .map(|m| m.id)
+            .collect()
+    }
+
+    /// Ids of the messages matching `filter` at `now`, oldest failure first.
+    pub fn filtered(&self, filter: &DlqFilter, now: u64) -> Vec<Uuid> {
+        let search = filter.search.as_deref().filter(|s| !s.is_empty());
+        self.matching(search).into_iter()
+            .filter(|id| self.messages.get(id).is_some_and(|m| filter.matches(m, now)))
+            .collect()
+    }
+
+    /// Like `peek` (most recent failure first), over the messages matching `filter`.
+    pub fn peek_filtered(&self, filter: &DlqFilter, now: u64, offset: usize, limit: usize) -> (usize, Vec<DlqMessage>) {
+        let ids = self.filtered(filter, now);
+        let items = ids.iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|id| self.messages.get(id).cloned())
+            .collect();
+        (ids.len(), items)
+    }
+
+    /// Like `peek` (most recent failure first), over the messages matching `search`.
+    pub fn search(&self, search: &str, offset: usize, limit: usize) -> (usize, Vec<DlqMessage>) {
+        let ids = self.matching(Some(search));
+        let items = ids.iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|id| self.messages.get(id).cloned())
+            .collect();
+        (ids.len(), items)
+    }
+
     /// Peek all messages (for snapshotting).
     /// Returns iterator over all DLQ messages.
     pub fn peek_all(&self) -> Vec<&DlqMessage> {
@@ -0,0 +1,357 @@
+//! Fanout State: broadcast flavor of a queue.
+//!
+//! Every bound consumer group receives every message. Messages live once in a
+//! shared ring; each group only keeps a cursor plus the seqs it has delivered
+//! but not yet settled, with the usual visibility timeout, retries and DLQ.
+//! The ring is trimmed once every group has settled a message.
+//!
+//! A group is bound the first time it consumes and starts from the oldest
+//! message still in the ring, until it is unbound. With no group bound
+//! nothing is trimmed, so messages wait for their first consumer like in a
+//! regular queue.
+//!
+//! Seqs are not stable across restarts (the ring is rebuilt from the store),
+//! so a group's progress is saved by message id (`GroupProgress`).
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::brokers::clock::SharedClock;
+use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::queue::domain::queue::{Message, MessageState};
+
+struct Delivery {
+    attempts: u32,
+    /// In flight until this time (ms); 0 = waiting for redelivery
+    visible_at: u64,
+}
+
+#[derive(Default)]
+struct GroupCursor {
+    /// Next seq never delivered to this group
+    next_seq: u64,
+    /// Delivered seqs not yet acked (in flight or waiting for redelivery)
+    unsettled: BTreeMap<u64, Delivery>,
+}
+
+impl GroupCursor {
+    /// Every seq below this one is settled for the group.
+    fn floor(&self) -> u64 {
+        self.unsettled.keys().next().copied().unwrap_or(self.next_seq).min(self.next_seq)
+    }
+}
+
+/// A group's cursor, as saved next to the queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupProgress {
+    pub name: String,
+    /// Last message handed to the group; `None` = nothing delivered yet
+    pub last_delivered: Option<Uuid>,
+    /// Delivered but not settled, with their attempts so far
+    pub unsettled: Vec<(Uuid, u32)>,
+}
+
+pub struct FanoutGroupSnapshot {
+    pub name: String,
+    /// Messages not delivered to the group yet
+    pub lag: usize,
+    pub inflight: usize,
+}
+
+pub struct FanoutState {
+    ring: VecDeque<Message>,
+    /// Seq of `ring[0]`
+    first_seq: u64,
+    seqs: HashMap<Uuid, u64>,
+    groups: BTreeMap<String, GroupCursor>,
+    bytes: usize,
+    clock: SharedClock,
+    /// Group progress changed since `progress` was last taken
+    dirty: bool,
+}
+
+impl FanoutState {
+    pub fn new(clock: SharedClock) -> Self {
+        Self {
+            ring: VecDeque::new(),
+            first_seq: 0,
+            seqs: HashMap::new(),
+            groups: BTreeMap::new(),
+            bytes: 0,
+            clock,
+            dirty: false,
+        }
+    }
+
+    fn end_seq(&self) -> u64 {
+        self.first_seq + self.ring.len() as u64
+    }
+
+    fn message(&self, seq: u64) -> Option<&Message> {
+        seq.checked_sub(self.first_seq).and_then(|idx| self.ring.get(idx as usize))
+    }
+
+    pub fn push(&mut self, mut msg: Message) {
+        if self.seqs.contains_key(&msg.id) {
+            return;
+        }
+        msg.state = MessageState::Ready;
+        msg.attempts = 0;
+        msg.visible_at = 0;
+        self.bytes += msg.payload.len();
+        self.seqs.insert(msg.id, self.end_seq());
+        self.ring.push_back(msg);
+    }
+
+    /// Bind `group` at the oldest retained message. Returns false if already bound.
+    pub fn bind(&mut self, group: &str) -> bool {
+        if self.groups.contains_key(group) {
+            return false;
+        }
+        let cursor = GroupCursor { next_seq: self.first_seq, ..Default::default() };
+        self.groups.insert(group.to_string(), cursor);
+        self.dirty = true;
+        true
+    }
+
+    /// Forget `group` and its unsettled deliveries. Returns the messages
+    /// trimmed as a result (settled by every remaining group), `None` if the
+    /// group was not bound.
+    pub fn unbind(&mut self, group: &str) -> Option<Vec<Message>> {
+        self.groups.remove(group)?;
+        self.dirty = true;
+        Some(self.trim())
+    }
+
+    /// Every group's cursor, clearing the changed flag.
+    pub fn progress(&mut self) -> Vec<GroupProgress> {
+        self.dirty = false;
+        self.groups.iter()
+            .map(|(name, cursor)| GroupProgress {
+                name: name.clone(),
+                last_delivered: cursor.next_seq.checked_sub(1).and_then(|seq| self.message(seq)).map(|m| m.id),
+                unsettled: cursor.unsettled.iter()
+                    .filter_map(|(seq, d)| self.message(*seq).map(|m| (m.id, d.attempts)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Bind the groups of `progress` where they left off, once the ring holds
+    /// the recovered messages in id order. In-flight deliveries come back as
+    /// waiting for redelivery. Returns the messages every group had settled.
+    pub fn restore(&mut self, progress: Vec<GroupProgress>) -> Vec<Message> {
+        for group in progress {
+            let next_seq = match group.last_delivered {
+                // Ids are time-ordered: past the last delivered one, or the end if it was trimmed
+                Some(last) => self.first_seq + self.ring.partition_point(|m| m.id <= last) as u64,
+                None => self.first_seq,
+            };
+            let unsettled = group.unsettled.into_iter()
+                .filter_map(|(id, attempts)| self.seqs.get(&id).map(|seq| (*seq, attempts)))
+                .filter(|(seq, _)| *seq < next_seq)
+                .map(|(seq, attempts)| (seq, Delivery { attempts, visible_at: 0 }))
+                .collect();
+            self.groups.insert(group.name, GroupCursor { next_seq, unsettled });
+        }
+        self.trim()
+    }
+
+    pub fn is_bound(&self, group: &str) -> bool {
+        self.groups.contains_key(group)
+    }
+
+    /// Take up to `max` messages for `group`: redeliveries first, then new
+    /// messages. Each returned copy carries the group's attempt count.
+    pub fn take_batch(&mut self, group: &str, max: usize, visibility_timeout_ms: u64) -> Vec<Message> {
+        let timeout = self.clock.now_ms() + visibility_timeout_ms;
+        let end_seq = self.end_seq();
+        let Some(cursor) = self.groups.get_mut(group) else { return Vec::new() };
+
+        let mut seqs: Vec<u64> = cursor.unsettled.iter()
+            .filter(|(_, d)| d.visible_at == 0)
+            .map(|(seq, _)| *seq)
+            .take(max)
+            .collect();
+        while seqs.len() < max && cursor.next_seq < end_seq {
+            cursor.unsettled.insert(cursor.next_seq, Delivery { attempts: 0, visible_at: 0 });
+            seqs.push(cursor.next_seq);
+            cursor.next_seq += 1;
+        }
+        self.dirty |= !seqs.is_empty();
+
+        let mut result = Vec::with_capacity(seqs.len());
+        for seq in seqs {
+            let Some(delivery) = cursor.unsettled.get_mut(&seq) else { continue };
+            let Some(msg) = seq.checked_sub(self.first_seq).and_then(|idx| self.ring.get(idx as usize)) else { continue };
+            delivery.attempts += 1;
+            delivery.visible_at = timeout;
+            let mut msg = msg.clone();
+            msg.attempts = delivery.attempts;
+            msg.visible_at = timeout;
+            msg.state = MessageState::InFlight(timeout);
+            result.push(msg);
+        }
+        result
+    }
+
+    pub fn has_ready_messages(&self, group: &str) -> bool {
+        let end_seq = self.end_seq();
+        self.groups.get(group).is_some_and(|cursor| {
+            cursor.next_seq < end_seq || cursor.unsettled.values().any(|d| d.visible_at == 0)
+        })
+    }
+
+    /// Settle an in-flight message for `group`. Returns the messages trimmed
+    /// from the ring as a result (settled by every group).
+    pub fn ack(&mut self, group: &str, id: Uuid) -> Option<Vec<Message>> {
+        let seq = *self.seqs.get(&id)?;
+        let cursor = self.groups.get_mut(group)?;
+        match cursor.unsettled.get(&seq) {
+            Some(delivery) if delivery.visible_at != 0 => {}
+            _ => return None,
+        }
+        cursor.unsettled.remove(&seq);
+        self.dirty = true;
+        Some(self.trim())
+    }
+
+    /// Negative ack for `group`: requeue for the group, or dead-letter once
+    /// `max_retries` is reached. Returns `None` if the message was not in flight.
+    pub fn nack(&mut self, group: &str, id: Uuid, reason: String, max_retries: u32) -> Option<(Option<DlqMessage>, Vec<Message>)> {
+        let seq = *self.seqs.get(&id)?;
+        let cursor = self.groups.get_mut(group)?;
+        let delivery = cursor.unsettled.get_mut(&seq).filter(|d| d.visible_at != 0)?;
+        if delivery.attempts < max_retries {
+            delivery.visible_at = 0;
+            self.dirty = true;
+            return Some((None, Vec::new()));
+        }
+        let dlq = self.dead_letter(group, seq, reason);
+        Some((dlq, self.trim()))
+    }
+
+    /// Put an in-flight message back for `group` as if it was never taken:
+    /// its request was cancelled before the reply.
+    pub fn requeue(&mut self, group: &str, id: Uuid) -> bool {
+        let Some(seq) = self.seqs.get(&id) else { return false };
+        let Some(cursor) = self.groups.get_mut(group) else { return false };
+        match cursor.unsettled.get_mut(seq).filter(|d| d.visible_at != 0) {
+            Some(delivery) => {
+                delivery.visible_at = 0;
+                delivery.attempts = delivery.attempts.saturating_sub(1);
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Requeue or dead-letter expired deliveries across all groups.
+    /// Returns (requeued count, dead letters, trimmed messages).
+    pub fn process_expired(&mut self, max_retries: u32) -> (usize, Vec<DlqMessage>, Vec<Message>) {
+        let now = self.clock.now_ms();
+        let mut requeued = 0;
+        let mut to_dlq = Vec::new();
+
+        for (name, cursor) in self.groups.iter_mut() {
+            for (seq, delivery) in cursor.unsettled.iter_mut() {
+                if delivery.visible_at == 0 || delivery.visible_at > now {
+                    continue;
+                }
+                if delivery.attempts >= max_retries {
+                    to_dlq.push((name.clone(), *seq));
+                } else {
+                    delivery.visible_at = 0;
+                    requeued += 1;
+                }
+            }
+        }
+
+        let dead: Vec<DlqMessage> = to_dlq.into_iter()
+            .filter_map(|(group, seq)| self.dead_letter(&group, seq, "Timeout".to_string()))
+            .collect();
+        let trimmed = if dead.is_empty() { Vec::new() } else { self.trim() };
+        (requeued, dead, trimmed)
+    }
+
+    pub fn next_inflight_timeout(&self) -> Option<u64> {
+        self.groups.values()
+            .flat_map(|cursor| cursor.unsettled.values())
+            .map(|d| d.visible_at)
+            .filter(|ts| *ts != 0)
+            .min()
+    }
+
+    /// Settle `seq` for `group` and return a DLQ copy under a fresh id: the
+    /// message itself stays in the ring for the other groups.
+    fn dead_letter(&mut self, group: &str, seq: u64, reason: String) -> Option<DlqMessage> {
+        let delivery = self.groups.get_mut(group)?.unsettled.remove(&seq)?;
+        self.dirty = true;
+        let mut msg = self.message(seq)?.clone();
+        msg.id = Uuid::now_v7();
+        msg.attempts = delivery.attempts;
+        Some(DlqMessage::from_message(msg, format!("group '{}': {}", group, reason)))
+    }
+
+    /// Drop messages every group has settled.
+    fn trim(&mut self) -> Vec<Message> {
+        let Some(floor) = self.groups.values().map(GroupCursor::floor).min() else { return Vec::new() };
+        let mut trimmed = Vec::new();
+        while self.first_seq < floor {
+            let Some(msg) = self.ring.pop_front() else { break };
+            self.first_seq += 1;
+            self.seqs.remove(&msg.id);
+            self.bytes = self.bytes.saturating_sub(msg.payload.len());
+            trimmed.push(msg);
+        }
+        trimmed
+    }
+
+    /// Messages in the ring (pending for at least one group)
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.seqs.contains_key(id)
+    }
+
+    pub fn all_messages(&self) -> Vec<Message> {
+        self.ring.iter().cloned().collect()
+    }
+
+    pub fn inflight(&self) -> usize {
+        self.groups.values()
+            .flat_map(|cursor| cursor.unsettled.values())
+            .filter(|d| d.visible_at != 0)
+            .count()
+    }
+
+    pub fn snapshot(&self) -> Vec<FanoutGroupSnapshot> {
+        let end_seq = self.end_seq();
+        self.groups.iter()
+            .map(|(name, cursor)| FanoutGroupSnapshot {
+                name: name.clone(),
+                lag: (end_seq - cursor.next_seq) as usize
+                    + cursor.unsettled.values().filter(|d| d.visible_at == 0).count(),
+                inflight: cursor.unsettled.values().filter(|d| d.visible_at != 0).count(),
+            })
+            .collect()
+    }
+}
@@ -1,3 +1,12 @@
 pub mod queue;
 pub mod dlq;
 pub mod persistence;
+pub mod backend;
+pub mod consumers;
+pub mod fanout;
+pub mod search;
+pub mod timer;
+pub mod dedup;
+
+#[cfg(test)]
+mod queue_sim;
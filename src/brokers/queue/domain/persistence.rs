@@ -1,13 +1,17 @@
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
-use rusqlite::{params, types::Type, Connection, Result};
-use tracing::{error, info};
+use tokio::time::{sleep_until, Instant};
+use tracing::{error, info, warn, Span};
 use uuid::Uuid;
 
-use crate::brokers::queue::domain::queue::{Message, MessageState, current_time_ms};
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::events::EventHandle;
+use crate::brokers::queue::domain::backend::{BackendKind, QueueBackend};
+use crate::brokers::queue::domain::queue::Message;
 use crate::brokers::queue::domain::dlq::DlqMessage;
 
 // ==========================================
@@ -27,6 +31,17 @@ pub enum StorageOp {
         visible_at: u64,
         attempts: u32,
     },
+    /// Page a ready message out of RAM: the row is kept (or written, if its
+    /// insert has not landed yet) with its spill sequence
+    Spill {
+        msg: Message,
+        seq: u64,
+    },
+    /// Delete the paged-out messages of a priority spilled after `after_seq` (purge)
+    DeleteSpilled {
+        priority: u8,
+        after_seq: u64,
+    },
     
     // DLQ Operations
     /// Insert a message into DLQ
@@ -47,73 +62,266 @@ pub enum StorageOp {
     PurgeDLQ,
 }
 
+// ==========================================
+// SQLITE TUNING
+// ==========================================
+
+/// `PRAGMA synchronous` for `FileAsync` queues (`FileSync` always runs FULL).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SqliteSynchronous {
+    /// Leave flushing to the OS: fastest, a power loss can drop recent writes
+    #[default]
+    Off,
+    /// Fsync at checkpoints only: the WAL survives a crash of the process
+    Normal,
+    Full,
+}
+
+impl SqliteSynchronous {
+    pub fn pragma(self) -> &'static str {
+        match self {
+            Self::Off => "OFF",
+            Self::Normal => "NORMAL",
+            Self::Full => "FULL",
+        }
+    }
+}
+
+impl std::str::FromStr for SqliteSynchronous {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "normal" => Ok(Self::Normal),
+            "full" => Ok(Self::Full),
+            other => Err(format!("unknown synchronous level '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SqliteTuning {
+    /// How long a connection waits on a locked DB before failing
+    pub busy_timeout_ms: u64,
+    pub synchronous: SqliteSynchronous,
+    /// Interval of the writer's passive WAL checkpoint, or of the log
+    /// backend's compaction check (0 = SQLite auto-checkpoint only, no compaction)
+    pub checkpoint_interval_ms: u64,
+}
+
+/// Checkpoint (or compaction) progress, updated by the backend.
+#[derive(Debug, Default)]
+pub struct CheckpointStats {
+    /// WAL frames not yet copied back into the DB after the last checkpoint;
+    /// for the log backend, dead records not yet compacted away
+    pub lag_frames: AtomicU64,
+    pub last_at: AtomicU64,
+    pub failures: AtomicU64,
+}
+
+/// Persistence health of a queue's storage (see `QueueStore::health`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PersistenceHealth {
+    /// SQLite WAL size, or the whole log for the log backend
+    pub wal_bytes: u64,
+    pub checkpoint_lag_frames: u64,
+    /// 0 = no checkpoint yet
+    pub last_checkpoint_at: u64,
+    pub checkpoint_failures: u64,
+}
+
 // ==========================================
 // QUEUE STORE (Public API)
 // ==========================================
 
+/// An op on its way to the writer. `done` is set for `FileSync` commits and
+/// resolves once the batch holding the op is committed (true) or failed.
+struct WriteRequest {
+    op: StorageOp,
+    span: Span,
+    done: Option<oneshot::Sender<bool>>,
+}
+
+enum WriterMsg {
+    Write(WriteRequest),
+    /// Commit everything queued before it, fsync, then resolve (see `QueueStore::flush`)
+    Flush(oneshot::Sender<bool>),
+    /// Commit everything queued before it, then read paged-out messages back
+    /// (see `QueueStore::load_spilled`)
+    LoadSpilled(SpillLoad),
+}
+
+/// Paged-out messages with their spill sequence, in spill order.
+pub type SpilledChunk = Result<Vec<(Message, u64)>, String>;
+
+struct SpillLoad {
+    priority: u8,
+    after_seq: u64,
+    limit: usize,
+    reply: oneshot::Sender<SpilledChunk>,
+}
+
+/// What recovery finds: (main_messages, dlq_messages, spilled_lanes).
+pub type Recovered = (Vec<Message>, Vec<DlqMessage>, Vec<SpilledLaneInfo>);
+
+/// Paged-out messages of one priority found by recovery.
+#[derive(Debug, Clone, Copy)]
+pub struct SpilledLaneInfo {
+    pub priority: u8,
+    pub count: usize,
+    pub bytes: usize,
+    pub last_seq: u64,
+}
+
+pub struct StoreOptions {
+    /// Where failed commits and fsyncs are reported
+    pub events: EventHandle,
+    pub flush_ms: u64,
+    pub batch_size: usize,
+    pub mode: PersistenceMode,
+    pub group_commit_ms: u64,
+    pub tuning: SqliteTuning,
+    /// Budget of the synchronous drain when the writer task is dropped (0 = none)
+    pub drop_flush_timeout_ms: u64,
+}
+
+/// Each queue has one storage backend, opened and read back synchronously
+/// here, then owned by the background writer: every later write and spill
+/// reload goes through it, in order.
 pub struct QueueStore {
-    sender: Mutex<Option<mpsc::UnboundedSender<StorageOp>>>,
+    sender: Mutex<Option<mpsc::UnboundedSender<WriterMsg>>>,
     writer_handle: Mutex<Option<JoinHandle<()>>>,
-    db_path: PathBuf,
+    /// What the backend loaded at open, until `recover` takes it
+    recovered: Mutex<Option<Result<Recovered, String>>>,
+    checkpoints: Arc<CheckpointStats>,
+    kind: BackendKind,
+    path: PathBuf,
 }
 
 impl QueueStore {
-    pub fn new(
-        db_path: PathBuf, 
-        flush_ms: u64,
-        batch_size: usize,
-    ) -> Self {
-        // SYNCHRONOUS INIT: Ensure DB schema exists before anything else
-        // This prevents race conditions where recover() runs before Writer creates tables.
-        if let Ok(conn) = Connection::open(&db_path) {
-            if let Err(e) = init_db(&conn) {
-                error!("FATAL: Failed to initialize Queue DB at {:?}: {}", db_path, e);
+    pub fn new(path: PathBuf, kind: BackendKind, options: StoreOptions) -> Self {
+        let checkpoints = Arc::new(CheckpointStats::default());
+        let opened = kind.open(&path, options.mode, &options.tuning, checkpoints.clone())
+            .map(|mut backend| {
+                let recovered = backend.load_all();
+                (backend, recovered)
+            });
+
+        let (sender, writer_handle, recovered) = match opened {
+            Ok((backend, recovered)) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                let writer_path = path.clone();
+                let handle = tokio::spawn(async move {
+                    run_writer(rx, backend, writer_path, options).await;
+                });
+                (Some(tx), Some(handle), recovered)
             }
-        } else {
-            error!("FATAL: Failed to open Queue DB for initialization at {:?}", db_path);
-        }
-
-        let (tx, rx) = mpsc::unbounded_channel();
-        
-        let path_clone = db_path.clone();
-        let handle = tokio::spawn(async move {
-            run_writer(rx, path_clone, flush_ms, batch_size).await;
-        });
+            Err(e) => {
+                error!("FATAL: {}", e);
+                options.events.persistence_error("queue", queue_name(&path).as_deref(), e.clone());
+                (None, None, Err(e))
+            }
+        };
 
         Self {
-            sender: Mutex::new(Some(tx)),
-            writer_handle: Mutex::new(Some(handle)),
-            db_path,
+            sender: Mutex::new(sender),
+            writer_handle: Mutex::new(writer_handle),
+            recovered: Mutex::new(Some(recovered)),
+            checkpoints,
+            kind,
+            path,
         }
     }
 
-    /// Recover all messages from DB (Read-Only connection)
-    /// Returns (main_messages, dlq_messages)
-    pub fn recover(&self) -> Result<(Vec<Message>, Vec<DlqMessage>), String> {
-        let conn = Connection::open(&self.db_path)
-            .map_err(|e| format!("Failed to open DB for recovery: {}", e))?;
+    /// Messages loaded when the store was opened (only once).
+    /// Returns (main_messages, dlq_messages, spilled_lanes): paged-out
+    /// messages stay on disk and are only counted
+    pub fn recover(&self) -> Result<Recovered, String> {
+        self.recovered.lock().unwrap().take().unwrap_or_else(|| Err("Queue store already recovered".to_string()))
+    }
+
+    /// Ask the writer for up to `limit` paged-out messages of `priority`
+    /// spilled after `after_seq`. The request is queued behind the ops sent so
+    /// far (send it under the queue lock); await the receiver outside of it.
+    pub fn load_spilled(&self, priority: u8, after_seq: u64, limit: usize) -> oneshot::Receiver<SpilledChunk> {
+        let (reply, rx) = oneshot::channel();
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            let _ = sender.send(WriterMsg::LoadSpilled(SpillLoad { priority, after_seq, limit, reply }));
+        }
+        rx
+    }
 
-        let main_messages = load_all_messages(&conn)
-            .map_err(|e| format!("Failed to load main messages: {}", e))?;
-        
-        let dlq_messages = load_dlq_messages(&conn)
-            .map_err(|e| format!("Failed to load DLQ messages: {}", e))?;
+    /// Whether the background writer is still taking ops (false once it
+    /// stopped, or after `shutdown`).
+    pub fn is_running(&self) -> bool {
+        self.sender.lock().unwrap().is_some()
+            && self.writer_handle.lock().unwrap().as_ref().is_some_and(|handle| !handle.is_finished())
+    }
 
-        Ok((main_messages, dlq_messages))
+    /// WAL (or log) size on disk plus the writer's checkpoint progress.
+    pub fn health(&self) -> PersistenceHealth {
+        let wal_path = match self.kind {
+            BackendKind::Sqlite => {
+                let mut wal_path = self.path.clone().into_os_string();
+                wal_path.push("-wal");
+                PathBuf::from(wal_path)
+            }
+            BackendKind::Log => self.path.clone(),
+        };
+        PersistenceHealth {
+            wal_bytes: std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0),
+            checkpoint_lag_frames: self.checkpoints.lag_frames.load(Ordering::Relaxed),
+            last_checkpoint_at: self.checkpoints.last_at.load(Ordering::Relaxed),
+            checkpoint_failures: self.checkpoints.failures.load(Ordering::Relaxed),
+        }
     }
 
-    /// Send a storage op to the background writer (sync, never blocks)
+    /// Send a storage op to the background writer (sync, never blocks).
+    /// The caller's span travels with the op so the flush can link back to it.
     #[inline]
     pub fn execute(&self, op: StorageOp) {
+        self.send(op, None);
+    }
+
+    /// Send a storage op and wait until it is committed to disk. Commits
+    /// arriving within the group-commit window share one transaction (and fsync).
+    pub async fn commit(&self, op: StorageOp) -> Result<(), String> {
+        let (done_tx, done_rx) = oneshot::channel();
+        self.send(op, Some(done_tx));
+        match done_rx.await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("queue commit failed".to_string()),
+            Err(_) => Err("queue writer stopped".to_string()),
+        }
+    }
+
+    fn send(&self, op: StorageOp, done: Option<oneshot::Sender<bool>>) {
         if let Some(sender) = self.sender.lock().unwrap().as_ref() {
-            if let Err(e) = sender.send(op) {
-                error!("Writer channel closed, op lost: {:?}", e.0);
+            if let Err(mpsc::error::SendError(WriterMsg::Write(req))) = sender.send(WriterMsg::Write(WriteRequest { op, span: Span::current(), done })) {
+                error!("Writer channel closed, op lost: {:?}", req.op);
             }
         }
     }
 
-    /// Graceful shutdown: drop sender so writer drains remaining ops, then wait for it to exit
+    /// Commit every op sent so far and fsync the storage files, whatever the
+    /// persistence mode. Ops sent afterwards are not covered.
+    pub async fn flush(&self) -> Result<(), String> {
+        let (done_tx, done_rx) = oneshot::channel();
+        let sent = self.sender.lock().unwrap().as_ref().is_some_and(|sender| sender.send(WriterMsg::Flush(done_tx)).is_ok());
+        if !sent {
+            return Err("queue writer stopped".to_string());
+        }
+        match done_rx.await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err("queue flush failed".to_string()),
+            Err(_) => Err("queue writer stopped".to_string()),
+        }
+    }
+
+    /// Graceful shutdown: drop sender so writer drains remaining ops and fsyncs, then wait for it to exit
     pub async fn shutdown(&self) {
+        self.recovered.lock().unwrap().take();
         self.sender.lock().unwrap().take(); // drop sender → writer recv() returns None after draining
         let handle = self.writer_handle.lock().unwrap().take();
         if let Some(handle) = handle {
@@ -126,316 +334,213 @@ impl QueueStore {
 // BACKGROUND WRITER
 // ==========================================
 
-async fn run_writer(
-    mut rx: mpsc::UnboundedReceiver<StorageOp>,
-    db_path: PathBuf,
-    flush_ms: u64,
-    batch_size: usize,
-) {
-    let mut conn = match Connection::open(&db_path) {
-        Ok(c) => c,
-        Err(e) => {
-            error!("FATAL: Cannot open queue DB at {:?}: {}", db_path, e);
+/// What the writer task owns. If the task is dropped mid-flight (runtime
+/// shutdown, a manager dropped at the end of a test) instead of seeing its
+/// channel close, `Drop` commits the batch and what is still queued within
+/// `drop_flush_timeout`, then fsyncs: best effort, ops beyond it are lost.
+struct Writer {
+    backend: Box<dyn QueueBackend>,
+    rx: mpsc::UnboundedReceiver<WriterMsg>,
+    batch: Vec<WriteRequest>,
+    path: PathBuf,
+    drop_flush_timeout: Duration,
+    events: EventHandle,
+}
+
+impl Writer {
+    /// Take `msg`, then whatever is queued behind it up to `batch_size` ops.
+    /// Returns the flush requests met on the way; spill loads go to `loads`.
+    fn take(&mut self, msg: WriterMsg, batch_size: usize, loads: &mut Vec<SpillLoad>) -> Vec<oneshot::Sender<bool>> {
+        let mut flushes = Vec::new();
+        let mut next = Some(msg);
+        while let Some(msg) = next.take() {
+            match msg {
+                WriterMsg::Write(req) => self.batch.push(req),
+                WriterMsg::Flush(done) => flushes.push(done),
+                WriterMsg::LoadSpilled(load) => loads.push(load),
+            }
+            if self.batch.len() < batch_size {
+                next = self.rx.try_recv().ok();
+            }
+        }
+        flushes
+    }
+
+    /// Commit the batch (see `flush_batch`).
+    fn flush(&mut self) -> bool {
+        match flush_batch(self.backend.as_mut(), &mut self.batch) {
+            Ok(()) => true,
+            Err(e) => {
+                self.events.persistence_error("queue", queue_name(&self.path).as_deref(), e);
+                false
+            }
+        }
+    }
+
+    /// Commit the batch and fsync the storage files.
+    fn commit_and_sync(&mut self) -> bool {
+        let committed = self.flush();
+        match self.backend.sync() {
+            Ok(()) => committed,
+            Err(e) => {
+                error!("Failed to fsync queue storage {:?}: {}", self.path, e);
+                self.events.persistence_error("queue", queue_name(&self.path).as_deref(), format!("fsync failed: {}", e));
+                false
+            }
+        }
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        if self.drop_flush_timeout.is_zero() {
             return;
         }
-    };
-    if let Err(e) = conn.execute_batch(
-        // Writer connection pragmas for high-throughput batch operations
-        // synchronous=OFF for performance (data is flushed periodically via timer)
-        "PRAGMA journal_mode = WAL;
-         PRAGMA synchronous = OFF;
-         PRAGMA cache_size = -64000;
-         PRAGMA temp_store = MEMORY;
-         PRAGMA mmap_size = 268435456;
-         PRAGMA page_size = 8192;"
-    ) {
-        error!("Failed to set writer pragmas: {}", e);
+        let deadline = std::time::Instant::now() + self.drop_flush_timeout;
+        let mut flushes = Vec::new();
+        while std::time::Instant::now() < deadline {
+            match self.rx.try_recv() {
+                Ok(WriterMsg::Write(req)) => self.batch.push(req),
+                Ok(WriterMsg::Flush(done)) => flushes.push(done),
+                // Nobody waits for a reload any more
+                Ok(WriterMsg::LoadSpilled(_)) => {}
+                Err(_) => break,
+            }
+        }
+        if self.batch.is_empty() && flushes.is_empty() {
+            return;
+        }
+        warn!("Queue writer for {:?} dropped with {} pending ops, flushing", self.path, self.batch.len());
+        let ok = self.commit_and_sync();
+        for done in flushes {
+            let _ = done.send(ok);
+        }
     }
+}
+
+async fn run_writer(
+    rx: mpsc::UnboundedReceiver<WriterMsg>,
+    backend: Box<dyn QueueBackend>,
+    path: PathBuf,
+    options: StoreOptions,
+) {
+    let StoreOptions { events, flush_ms, batch_size, mode, group_commit_ms, tuning, drop_flush_timeout_ms } = options;
 
-    info!("Queue Persistence Writer started for {:?}", db_path);
+    info!("Queue Persistence Writer started for {:?} ({:?})", path, mode);
 
     let mut flush_timer = tokio::time::interval(Duration::from_millis(flush_ms));
     flush_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-
-    let mut batch = Vec::with_capacity(batch_size);
+    let checkpoint_enabled = tuning.checkpoint_interval_ms > 0;
+    let mut checkpoint_timer = tokio::time::interval(Duration::from_millis(tuning.checkpoint_interval_ms.max(1)));
+    checkpoint_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    checkpoint_timer.reset();
+    let group_commit = Duration::from_millis(group_commit_ms);
+
+    let mut writer = Writer {
+        backend,
+        rx,
+        batch: Vec::with_capacity(batch_size),
+        path,
+        drop_flush_timeout: Duration::from_millis(drop_flush_timeout_ms),
+        events,
+    };
+    // Set when the batch holds a commit waiter: the batch is flushed by then
+    let mut commit_deadline: Option<Instant> = None;
 
     loop {
         tokio::select! {
-            recv_result = rx.recv() => {
+            recv_result = writer.rx.recv() => {
                 match recv_result {
-                    Some(op) => {
-                        batch.push(op);
-
+                    Some(msg) => {
                         // Drain everything currently available in the channel
-                        while batch.len() < batch_size {
-                            match rx.try_recv() {
-                                Ok(op) => batch.push(op),
-                                Err(_) => break,
+                        let mut loads = Vec::new();
+                        let flushes = writer.take(msg, batch_size, &mut loads);
+
+                        if !loads.is_empty() {
+                            // Reads see every op queued before them
+                            writer.flush();
+                            commit_deadline = None;
+                            for load in loads {
+                                let chunk = writer.backend.load_spilled(load.priority, load.after_seq, load.limit)
+                                    .map_err(|e| format!("Failed to load paged-out messages: {}", e));
+                                let _ = load.reply.send(chunk);
                             }
                         }
 
-                        if batch.len() >= batch_size {
-                            flush_batch(&mut conn, &mut batch);
+                        if !flushes.is_empty() {
+                            let ok = writer.commit_and_sync();
+                            commit_deadline = None;
+                            for done in flushes {
+                                let _ = done.send(ok);
+                            }
+                            continue;
+                        }
+
+                        if commit_deadline.is_none() && writer.batch.iter().any(|req| req.done.is_some()) {
+                            commit_deadline = Some(Instant::now() + group_commit);
+                        }
+                        let commit_due = commit_deadline.is_some_and(|deadline| deadline <= Instant::now());
+                        if writer.batch.len() >= batch_size || commit_due {
+                            writer.flush();
+                            commit_deadline = None;
                         }
                     }
                     None => {
-                        // Sender dropped — flush remaining and exit
-                        if !batch.is_empty() {
-                            flush_batch(&mut conn, &mut batch);
-                        }
-                        info!("Queue Persistence Writer stopped for {:?}", db_path);
+                        // Sender dropped — flush remaining, fsync and exit
+                        writer.commit_and_sync();
+                        info!("Queue Persistence Writer stopped for {:?}", writer.path);
                         return;
                     }
                 }
             }
+
+            _ = sleep_until(commit_deadline.unwrap_or_else(Instant::now)), if commit_deadline.is_some() => {
+                writer.flush();
+                commit_deadline = None;
+            }
             
             _ = flush_timer.tick() => {
-                if !batch.is_empty() {
-                    flush_batch(&mut conn, &mut batch);
+                if !writer.batch.is_empty() {
+                    writer.flush();
+                    commit_deadline = None;
                 }
             }
-        }
-    }
-}
-
-fn flush_batch(conn: &mut Connection, batch: &mut Vec<StorageOp>) {
-    let tx = match conn.transaction() {
-        Ok(t) => t,
-        Err(e) => {
-            error!("Failed to start transaction: {}", e);
-            return;
-        }
-    };
 
-    for op in batch.iter() {
-        if let Err(e) = exec_op(&tx, op) {
-            error!("Failed to exec op {:?}: {}", op, e);
+            _ = checkpoint_timer.tick(), if checkpoint_enabled => {
+                writer.backend.compact();
+            }
         }
     }
-
-    if let Err(e) = tx.commit() {
-        error!("Failed to commit batch: {}", e);
-    }
-    
-    batch.clear();
-}
-
-// ==========================================
-// SQLITE OPERATIONS
-// ==========================================
-
-fn uuid_from_blob(id_blob: Vec<u8>) -> Result<Uuid> {
-    Uuid::from_slice(&id_blob).map_err(|e| {
-        rusqlite::Error::FromSqlConversionFailure(id_blob.len(), Type::Blob, Box::new(e))
-    })
 }
 
-fn init_db(conn: &Connection) -> Result<()> {
-    // Set pragmas for schema initialization connection
-    // synchronous=NORMAL for safety during table creation
-    conn.execute_batch(
-        "PRAGMA journal_mode = WAL;
-         PRAGMA synchronous = NORMAL;
-         PRAGMA foreign_keys = ON;
-         PRAGMA cache_size = -64000;
-         PRAGMA temp_store = MEMORY;
-         "
-    )?;
-
-    // Main Queue Table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS queue (
-            id BLOB PRIMARY KEY,
-            payload BLOB NOT NULL,
-            priority INTEGER NOT NULL,
-            visible_at INTEGER NOT NULL,
-            attempts INTEGER NOT NULL,
-            created_at INTEGER NOT NULL
-        )",
-        [],
-    )?;
-
-    // DLQ Table
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS dlq_messages (
-            id BLOB PRIMARY KEY,
-            payload BLOB NOT NULL,
-            priority INTEGER NOT NULL,
-            attempts INTEGER NOT NULL,
-            created_at INTEGER NOT NULL,
-            failed_at INTEGER NOT NULL,
-            error TEXT
-        )",
-        [],
-    )?;
-
-    Ok(())
+/// The queue a storage file belongs to, for error reports.
+fn queue_name(path: &std::path::Path) -> Option<String> {
+    path.file_name().and_then(|n| n.to_str()).and_then(BackendKind::queue_name).map(str::to_string)
 }
 
-fn load_all_messages(conn: &Connection) -> Result<Vec<Message>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, payload, priority, visible_at, attempts, created_at FROM queue"
-    )?;
-
-    let message_iter = stmt.query_map([], |row| {
-        let id_blob: Vec<u8> = row.get(0)?;
-        let id = uuid_from_blob(id_blob)?;
-        let payload: Vec<u8> = row.get(1)?;
-        let priority: u8 = row.get(2)?;
-        let visible_at = row.get::<_, i64>(3)? as u64;
-        let attempts: u32 = row.get(4)?;
-        let created_at = row.get::<_, i64>(5)? as u64;
- 
-         let now = current_time_ms();
-         
-         // Reconstruct State
-         let state = if visible_at > now && attempts > 0 {
-             MessageState::InFlight(visible_at)
-         } else {
-             MessageState::Ready
-         };
-
-         Ok(Message {
-             id,
-             payload: bytes::Bytes::from(payload),
-             priority,
-             attempts,
-             created_at,
-             visible_at,
-             failure_reason: None, // Not persisted in main queue yet
-             state,
-         })
-    })?;
-
-    let mut messages = Vec::new();
-    for msg in message_iter {
-        messages.push(msg?);
+/// Commit the batch and answer its waiters: a waiter whose op was skipped
+/// is answered `false` like the whole batch failing.
+fn flush_batch(backend: &mut dyn QueueBackend, batch: &mut Vec<WriteRequest>) -> Result<(), String> {
+    if batch.is_empty() {
+        return Ok(());
     }
-    Ok(messages)
-}
-
-fn load_dlq_messages(conn: &Connection) -> Result<Vec<DlqMessage>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, payload, priority, attempts, created_at, failed_at, error FROM dlq_messages"
-    )?;
-
-    let message_iter = stmt.query_map([], |row| {
-        let id_blob: Vec<u8> = row.get(0)?;
-        let id = uuid_from_blob(id_blob)?;
-        let payload: Vec<u8> = row.get(1)?;
-        let priority: u8 = row.get(2)?;
-        let attempts: u32 = row.get(3)?;
-        let created_at = row.get::<_, i64>(4)? as u64;
-        let failed_at = row.get::<_, i64>(5)? as u64;
-        let error: Option<String> = row.get(6)?;
-
-        Ok(DlqMessage {
-            id,
-            payload: bytes::Bytes::from(payload),
-            priority,
-            attempts,
-            created_at,
-            failed_at,
-            failure_reason: error.unwrap_or_default(),
-        })
-    })?;
-
-    let mut messages = Vec::new();
-    for msg in message_iter {
-        messages.push(msg?);
+    let committed = commit_batch(backend, batch);
+    let skipped = committed.as_deref().unwrap_or_default();
+    for (i, req) in batch.drain(..).enumerate() {
+        if let Some(done) = req.done {
+            let _ = done.send(committed.is_ok() && !skipped.contains(&i));
+        }
     }
-    Ok(messages)
+    committed.map(|_| ())
 }
 
-fn exec_op(tx: &rusqlite::Transaction, op: &StorageOp) -> Result<()> {
-    match op {
-        StorageOp::Insert(msg) => {
-            let mut stmt = tx.prepare_cached(
-                "INSERT INTO queue (id, payload, priority, visible_at, attempts, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
-            )?;
-            stmt.execute(params![
-                msg.id.as_bytes(),
-                msg.payload.as_ref(), // Bytes -> &[u8]
-                msg.priority,
-                msg.visible_at as i64,
-                msg.attempts,
-                msg.created_at as i64
-            ])?;
-        }
-        StorageOp::Delete(id) => {
-            let mut stmt = tx.prepare_cached("DELETE FROM queue WHERE id = ?1")?;
-            stmt.execute(params![id.as_bytes()])?;
-        }
-        StorageOp::UpdateState { id, visible_at, attempts } => {
-            let mut stmt = tx.prepare_cached(
-                "UPDATE queue SET visible_at = ?1, attempts = ?2 WHERE id = ?3"
-            )?;
-            stmt.execute(params![*visible_at as i64, *attempts, id.as_bytes()])?;
-        }
-        
-        // DLQ Operations
-        StorageOp::InsertDLQ(msg) => {
-            let mut stmt = tx.prepare_cached(
-                "INSERT INTO dlq_messages (id, payload, priority, attempts, created_at, failed_at, error)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
-            )?;
-            stmt.execute(params![
-                msg.id.as_bytes(),
-                msg.payload.as_ref(),
-                msg.priority,
-                msg.attempts,
-                msg.created_at as i64,
-                msg.failed_at as i64,
-                msg.failure_reason
-            ])?;
-        }
-        StorageOp::DeleteDLQ(id) => {
-            let mut stmt = tx.prepare_cached("DELETE FROM dlq_messages WHERE id = ?1")?;
-            stmt.execute(params![id.as_bytes()])?;
-        }
-        StorageOp::MoveToDLQ { id, msg } => {
-            // Atomic: delete from queue, insert into DLQ
-            let mut stmt = tx.prepare_cached("DELETE FROM queue WHERE id = ?1")?;
-            stmt.execute(params![id.as_bytes()])?;
-            
-            let mut stmt = tx.prepare_cached(
-                "INSERT INTO dlq_messages (id, payload, priority, attempts, created_at, failed_at, error)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
-            )?;
-            stmt.execute(params![
-                msg.id.as_bytes(),
-                msg.payload.as_ref(),
-                msg.priority,
-                msg.attempts,
-                msg.created_at as i64,
-                msg.failed_at as i64,
-                msg.failure_reason
-            ])?;
-        }
-        StorageOp::MoveToMain { id, msg } => {
-            // Atomic: delete from DLQ, insert into queue
-            let mut stmt = tx.prepare_cached("DELETE FROM dlq_messages WHERE id = ?1")?;
-            stmt.execute(params![id.as_bytes()])?;
-            
-            let mut stmt = tx.prepare_cached(
-                "INSERT INTO queue (id, payload, priority, visible_at, attempts, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
-            )?;
-            stmt.execute(params![
-                msg.id.as_bytes(),
-                msg.payload.as_ref(),
-                msg.priority,
-                0i64, // visible_at = 0 (ready immediately)
-                0u32, // reset attempts
-                msg.created_at as i64
-            ])?;
-            // failure_reason is lost when moving back to main because table doesn't support it yet
-            // and we are resetting the message anyway.
-        }
-        StorageOp::PurgeDLQ => {
-            tx.execute("DELETE FROM dlq_messages", [])?;
-        }
+fn commit_batch(backend: &mut dyn QueueBackend, batch: &[WriteRequest]) -> Result<Vec<usize>, String> {
+    let span = tracing::info_span!("queue.persist", ops = batch.len());
+    for req in batch {
+        span.follows_from(&req.span);
     }
-    Ok(())
+    let _enter = span.enter();
+
+    let ops: Vec<&StorageOp> = batch.iter().map(|req| &req.op).collect();
+    backend.apply(&ops).inspect_err(|e| error!("{}", e))
 }
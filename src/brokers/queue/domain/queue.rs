@@ -3,17 +3,23 @@
 //! This module contains the pure state logic without any concurrency primitives.
 //! The QueueManager wraps this state in a Mutex<QueueInner> per queue.
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::time::{SystemTime, UNIX_EPOCH};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use hashlink::LinkedHashSet;
 
-use crate::brokers::queue::options::QueueCreateOptions;
+use crate::brokers::clock::SharedClock;
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::labels::Labels;
+use crate::brokers::queue::options::{DeadLetterTarget, QueueCreateOptions};
 use crate::brokers::queue::config::SystemQueueConfig;
+use crate::brokers::rate::RateWindow;
 use crate::brokers::queue::domain::dlq::DlqMessage;
-use crate::brokers::queue::snapshot::{MessageStateTag, QueueMessagePreview};
+use crate::brokers::queue::domain::search::SearchIndex;
+use crate::brokers::queue::domain::timer::TimerIndex;
+use crate::brokers::queue::snapshot::{MessageStateTag, PriorityLaneSnapshot, QueueMessagePreview};
 
 // ==========================================
 // MESSAGE & CONFIG
@@ -23,6 +29,7 @@ use crate::brokers::queue::snapshot::{MessageStateTag, QueueMessagePreview};
 pub enum MessageState {
     Ready,                  // In waiting_for_dispatch
     InFlight(u64),          // In waiting_for_ack (timestamp scadenza)
+    Scheduled(u64),         // In waiting_for_time (activation timestamp)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +42,9 @@ pub struct Message {
     pub visible_at: u64,
     pub failure_reason: Option<String>,
     pub state: MessageState,
+    /// Messages of a group are delivered one at a time, in order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group: Option<String>,
 }
 
 impl Message {
@@ -42,7 +52,8 @@ impl Message {
         let now = current_time_ms();
 
         Self {
-            id: Uuid::new_v4(),
+            // UUIDv7: ids sort by creation time (see `id_timestamp_ms`)
+            id: Uuid::now_v7(),
             payload,
             priority,
             attempts: 0,
@@ -50,6 +61,7 @@ impl Message {
             visible_at: 0,
             failure_reason: None,
             state: MessageState::Ready,
+            message_group: None,
         }
     }
 
@@ -63,6 +75,7 @@ impl Message {
             visible_at: 0,
             failure_reason: None,
             state: MessageState::Ready,
+            message_group: dlq_msg.message_group,
         }
     }
 }
@@ -71,6 +84,38 @@ impl Message {
 pub struct QueueConfig {
     pub visibility_timeout_ms: u64,
     pub max_retries: u32,
+    #[serde(default)]
+    pub dlq_alert_threshold: usize,
+    #[serde(default)]
+    pub persistence: PersistenceMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConfig>,
+    #[serde(default)]
+    pub fanout: bool,
+    #[serde(default)]
+    pub dead_letter: DeadLetterTarget,
+    /// Keep acked messages in the message archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ArchiveConfig>,
+    /// Delayed messages in a timing wheel, released by the scheduler thread
+    #[serde(default)]
+    pub precise_delays: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub timeout_ms: u64,
+    pub concurrency: usize,
+}
+
+/// Retention of the message archive (0 = no limit).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArchiveConfig {
+    pub max_age_ms: u64,
+    pub max_bytes: u64,
 }
 
 impl QueueConfig {
@@ -78,6 +123,21 @@ impl QueueConfig {
         Self {
             visibility_timeout_ms: opts.visibility_timeout_ms.unwrap_or(sys.visibility_timeout_ms),
             max_retries: opts.max_retries.unwrap_or(sys.max_retries),
+            dlq_alert_threshold: opts.dlq_alert_threshold.unwrap_or(sys.dlq_alert_threshold),
+            persistence: if sys.ephemeral { PersistenceMode::FileAsync } else { opts.persistence.unwrap_or(sys.persistence) },
+            webhook: opts.webhook.map(|w| WebhookConfig {
+                url: w.url,
+                timeout_ms: w.timeout_ms.unwrap_or(sys.webhook_timeout_ms),
+                concurrency: w.concurrency.unwrap_or(sys.webhook_concurrency).max(1),
+            }),
+            fanout: opts.fanout.unwrap_or(false),
+            dead_letter: opts.dead_letter.unwrap_or_default(),
+            archive: opts.archive.map(|a| ArchiveConfig {
+                max_age_ms: a.max_age_ms.unwrap_or(sys.archive_retention_age_ms),
+                max_bytes: a.max_bytes.unwrap_or(sys.archive_retention_bytes),
+            }),
+            precise_delays: opts.precise_delays.unwrap_or(false),
+            labels: opts.labels.unwrap_or_default(),
         }
     }
 }
@@ -91,21 +151,91 @@ pub struct QueueState {
     registry: HashMap<Uuid, Message>,
     /// Ready messages by priority (high priority first)
     waiting_for_dispatch: BTreeMap<u8, LinkedHashSet<Uuid>>,
-    /// In-flight messages by timeout time
-    waiting_for_ack: BTreeMap<u64, LinkedHashSet<Uuid>>,
+    /// In-flight messages by timeout time (a timing wheel with `QUEUE_TIMING_WHEEL`)
+    waiting_for_ack: TimerIndex,
+    /// Scheduled (delayed) messages by activation time (a timing wheel with
+    /// `preciseDelays` or `QUEUE_TIMING_WHEEL`)
+    waiting_for_time: TimerIndex,
+    /// Ready and in-flight messages of each message group, in delivery order.
+    /// Only the front of a line is ever in `waiting_for_dispatch` or in flight:
+    /// the rest are held until it is acked or dead-lettered.
+    groups: HashMap<String, LinkedHashSet<Uuid>>,
+    /// Total payload bytes held in `registry`
+    bytes: usize,
+    /// Push/ack counters per priority level
+    lanes: BTreeMap<u8, LaneStats>,
+    /// Payload words of every message (`QUEUE_SEARCH_INDEX`)
+    search: Option<SearchIndex>,
+    /// Ready messages paged out to the store, by priority (`QUEUE_SPILL_THRESHOLD`)
+    spilled: BTreeMap<u8, SpilledLane>,
+    /// Spill sequence of the next message paged out
+    next_spill_seq: u64,
+    /// Time source for visibility timeouts and retry backoff
+    clock: SharedClock,
+}
+
+/// Ready messages of one priority that live in the store instead of
+/// `registry`. A priority dispatches its bucket in `waiting_for_dispatch`
+/// first, then the messages on disk in spill order, then `overflow`.
+#[derive(Debug, Default)]
+struct SpilledLane {
+    /// Messages on disk, and their payload bytes
+    count: usize,
+    bytes: usize,
+    /// Spill sequence of the last message loaded back: the rest come after it
+    cursor: u64,
+    /// Became ready while the lane had messages on disk: they queue behind them
+    overflow: LinkedHashSet<Uuid>,
+    /// A chunk is on its way back from the store
+    loading: bool,
+}
+
+#[derive(Debug, Default)]
+struct LaneStats {
+    pushed: u64,
+    acked: u64,
+    pushes: RateWindow,
+    acks: RateWindow,
 }
 
 impl QueueState {
     /// Returns the earliest visibility timeout (ms) for in-flight messages.
     pub fn next_inflight_timeout(&self) -> Option<u64> {
-        self.waiting_for_ack.keys().next().cloned()
+        self.waiting_for_ack.next()
     }
 
-    pub fn new() -> Self {
+    pub fn new(clock: SharedClock) -> Self {
         Self {
             registry: HashMap::new(),
             waiting_for_dispatch: BTreeMap::new(),
-            waiting_for_ack: BTreeMap::new(),
+            waiting_for_ack: TimerIndex::default(),
+            waiting_for_time: TimerIndex::default(),
+            groups: HashMap::new(),
+            bytes: 0,
+            lanes: BTreeMap::new(),
+            search: None,
+            spilled: BTreeMap::new(),
+            next_spill_seq: 1,
+            clock,
+        }
+    }
+
+    /// Keep a payload search index from now on (messages already here included).
+    pub fn enable_search_index(&mut self) {
+        let mut index = SearchIndex::default();
+        for msg in self.registry.values() {
+            index.insert(msg.id, &[&msg.payload]);
+        }
+        self.search = Some(index);
+    }
+
+    /// Keep scheduled messages, and in-flight ones too if `inflight`, in
+    /// timing wheels from now on (messages already here included).
+    pub fn enable_timing_wheel(&mut self, inflight: bool) {
+        let now = self.clock.now_ms();
+        self.waiting_for_time.to_wheel(now);
+        if inflight {
+            self.waiting_for_ack.to_wheel(now);
         }
     }
 
@@ -114,19 +244,47 @@ impl QueueState {
         let id = msg.id;
         let initial_state = msg.state.clone();
         let priority = msg.priority;
+        let group = msg.message_group.clone();
 
-        self.registry.insert(id, msg);
+        self.bytes += msg.payload.len();
+        if let Some(index) = self.search.as_mut() {
+            index.insert(id, &[&msg.payload]);
+        }
+        if let Some(old) = self.registry.insert(id, msg) {
+            self.bytes = self.bytes.saturating_sub(old.payload.len());
+            self.remove_from_index(&old.state, id, old.priority);
+            if let Some(old_group) = old.message_group {
+                self.leave_line(id, &old_group);
+            }
+        }
 
         match initial_state {
-            MessageState::Ready => {
-                self.waiting_for_dispatch.entry(priority).or_default().insert(id);
-            }
+            MessageState::Ready => self.index_ready(id, priority, group.as_deref()),
             MessageState::InFlight(ts) => {
-                self.waiting_for_ack.entry(ts).or_default().insert(id);
+                self.waiting_for_ack.insert(ts, id);
+                if let Some(group) = group {
+                    self.groups.entry(group).or_default().insert(id);
+                }
+            }
+            MessageState::Scheduled(ts) => {
+                self.waiting_for_time.insert(ts, id);
             }
         }
     }
 
+    /// Push a message ahead of the ready messages of its priority. Scheduled
+    /// messages join the back once released, like any other.
+    pub fn push_front(&mut self, msg: Message) {
+        let (id, priority) = (msg.id, msg.priority);
+        self.push(msg);
+        if self.spilled.get_mut(&priority).is_some_and(|lane| lane.overflow.remove(&id)) {
+            self.waiting_for_dispatch.entry(priority).or_default().insert(id);
+        }
+        if let Some(ids) = self.waiting_for_dispatch.get_mut(&priority) {
+            ids.to_front(&id);
+        }
+    }
+
     /// Pop the highest priority message. Returns (message, needs_pulse).
     pub fn pop(&mut self, visibility_timeout_ms: u64) -> (Option<Message>, bool) {
         self.pop_single(visibility_timeout_ms)
@@ -134,32 +292,142 @@ impl QueueState {
 
     /// Acknowledge a message (remove from system).
     pub fn ack(&mut self, id: Uuid) -> bool {
-        self.delete_message(id)
+        let Some(msg) = self.delete_message_and_return(id) else { return false };
+        let now = self.clock.now_ms();
+        let lane = self.lanes.entry(msg.priority).or_default();
+        lane.acked += 1;
+        lane.acks.record(now);
+        true
+    }
+
+    /// Count a producer push in its priority's throughput (restored, imported
+    /// and replayed messages are not counted).
+    pub fn record_push(&mut self, priority: u8) {
+        let now = self.clock.now_ms();
+        let lane = self.lanes.entry(priority).or_default();
+        lane.pushed += 1;
+        lane.pushes.record(now);
+    }
+
+    /// Depth and throughput of every priority level that holds messages or
+    /// saw traffic since startup, highest first.
+    pub fn priority_lanes(&mut self) -> Vec<PriorityLaneSnapshot> {
+        let mut depth: BTreeMap<u8, (usize, usize, usize)> = BTreeMap::new();
+        for (&priority, ids) in &self.waiting_for_dispatch {
+            depth.entry(priority).or_default().0 += ids.len();
+        }
+        for msg in self.held().filter_map(|id| self.registry.get(id)) {
+            depth.entry(msg.priority).or_default().0 += 1;
+        }
+        for (&priority, lane) in &self.spilled {
+            depth.entry(priority).or_default().0 += lane.count + lane.overflow.len();
+        }
+        for msg in self.waiting_for_ack.iter().filter_map(|(_, id)| self.registry.get(id)) {
+            depth.entry(msg.priority).or_default().1 += 1;
+        }
+        for msg in self.waiting_for_time.iter().filter_map(|(_, id)| self.registry.get(id)) {
+            depth.entry(msg.priority).or_default().2 += 1;
+        }
+
+        let now = self.clock.now_ms();
+        let priorities: BTreeSet<u8> = depth.keys().chain(self.lanes.keys()).copied().collect();
+        priorities.into_iter().rev().map(|priority| {
+            let (pending, inflight, scheduled) = depth.get(&priority).copied().unwrap_or_default();
+            let lane = self.lanes.entry(priority).or_default();
+            PriorityLaneSnapshot {
+                priority,
+                pending,
+                inflight,
+                scheduled,
+                pushed: lane.pushed,
+                acked: lane.acked,
+                push_rate: lane.pushes.per_sec(now),
+                ack_rate: lane.acks.per_sec(now),
+            }
+        }).collect()
+    }
+
+    /// Remove the pending and scheduled messages of `priority`. In-flight
+    /// ones stay with their consumers. Returns the removed messages; those
+    /// on disk are dropped with `drop_spilled`.
+    pub fn purge_priority(&mut self, priority: u8) -> Vec<Message> {
+        let mut ids: Vec<Uuid> = self.waiting_for_dispatch.get(&priority)
+            .map(|ids| ids.iter().copied().collect())
+            .unwrap_or_default();
+        if let Some(lane) = self.spilled.get(&priority) {
+            ids.extend(lane.overflow.iter().copied());
+        }
+        ids.extend(self.held().chain(self.waiting_for_time.iter().map(|(_, id)| id))
+            .filter(|id| self.registry.get(id).is_some_and(|m| m.priority == priority)));
+        ids.into_iter().filter_map(|id| self.delete_message_and_return(id)).collect()
     }
 
     /// Take up to `max` messages for batch consumption.
-    pub fn take_batch(&mut self, max: usize, visibility_timeout_ms: u64) -> (Vec<Message>, bool) {
-        let mut result = Vec::with_capacity(max);
-        let mut any_earliest = false;
+    ///
+    /// `quotas` optionally caps the share of the batch (0.0..=1.0) a priority may
+    /// fill, so lower priorities still get through. Slots a capped priority leaves
+    /// unused by others are filled from it anyway; FIFO holds within each priority.
+    pub fn take_batch(&mut self, max: usize, visibility_timeout_ms: u64, quotas: Option<&HashMap<u8, f64>>) -> (Vec<Message>, bool) {
+        let ids = match quotas {
+            Some(quotas) if !quotas.is_empty() => self.select_with_quotas(max, quotas),
+            _ => self.waiting_for_dispatch.values().rev().flatten().take(max).cloned().collect(),
+        };
 
-        while result.len() < max {
-            match self.pop_single(visibility_timeout_ms) {
-                (Some(msg), is_earliest) => {
-                    if is_earliest {
-                        any_earliest = true;
-                    }
-                    result.push(msg);
-                }
-                (None, _) => break,
+        let mut result = Vec::with_capacity(ids.len());
+        let mut any_earliest = false;
+        for id in ids {
+            if let (Some(msg), is_earliest) = self.dispatch(id, visibility_timeout_ms) {
+                any_earliest |= is_earliest;
+                result.push(msg);
             }
         }
 
         (result, any_earliest)
     }
 
+    /// Pick ready ids priority by priority: first within each quota, then fill
+    /// the remaining slots ignoring quotas. Every bucket is taken as a prefix.
+    fn select_with_quotas(&self, max: usize, quotas: &HashMap<u8, f64>) -> Vec<Uuid> {
+        let mut counts: Vec<(u8, usize)> = Vec::new();
+        let mut taken = 0;
+
+        for (&priority, bucket) in self.waiting_for_dispatch.iter().rev() {
+            let cap = match quotas.get(&priority) {
+                // A positive share always allows at least one message
+                Some(&share) if share > 0.0 => ((max as f64 * share).floor() as usize).max(1),
+                Some(_) => 0,
+                None => max,
+            };
+            let n = cap.min(max - taken).min(bucket.len());
+            counts.push((priority, n));
+            taken += n;
+        }
+
+        for ((_, count), bucket) in counts.iter_mut().zip(self.waiting_for_dispatch.values().rev()) {
+            let n = (bucket.len() - *count).min(max - taken);
+            *count += n;
+            taken += n;
+        }
+
+        counts.into_iter()
+            .zip(self.waiting_for_dispatch.values().rev())
+            .flat_map(|((_, count), bucket)| bucket.iter().take(count).cloned())
+            .collect()
+    }
+
     /// Negative Acknowledge. Returns (requeued_msg, dlq_msg).
     /// If dlq_msg is Some, the message was removed from this state and should be added to DLQ state.
     pub fn nack(&mut self, id: Uuid, reason: String, max_retries: u32) -> (Option<Message>, Option<DlqMessage>) {
+        self.nack_after(id, reason, max_retries, 0)
+    }
+
+    /// Like `nack`, but a requeued message stays invisible for `delay_ms` (retry backoff).
+    pub fn nack_after(&mut self, id: Uuid, reason: String, max_retries: u32, delay_ms: u64) -> (Option<Message>, Option<DlqMessage>) {
+        // Held group members were never delivered
+        if self.is_held(&id) {
+            return (None, None);
+        }
+
         // 1. Check existence and update fields
         let (should_dlq, priority) = if let Some(msg) = self.registry.get_mut(&id) {
             msg.failure_reason = Some(reason.clone());
@@ -177,10 +445,17 @@ impl QueueState {
             }
             (None, None)
         } else {
-            // Requeue (Ready)
-            if self.transition_to(id, MessageState::Ready) {
+            // Requeue (Ready, or hidden until the backoff expires)
+            let (state, visible_at) = match delay_ms {
+                0 => (MessageState::Ready, 0),
+                delay => {
+                    let at = self.clock.now_ms() + delay;
+                    (MessageState::InFlight(at), at)
+                }
+            };
+            if self.transition_to(id, state) {
                 if let Some(msg) = self.registry.get_mut(&id) {
-                    msg.visible_at = 0;
+                    msg.visible_at = visible_at;
                     return (Some(msg.clone()), None);
                 }
             }
@@ -193,24 +468,18 @@ impl QueueState {
     /// requeued_messages: messages that transitioned to Ready (need UpdateState in DB)
     /// dlq_messages: messages moved to DLQ (need MoveToDlq in DB)
     pub fn process_expired(&mut self, max_retries: u32) -> (Vec<Message>, Vec<DlqMessage>) {
-        let now = current_time_ms();
+        let now = self.clock.now_ms();
         let mut ids_to_ready = Vec::new();
         let mut ids_to_dlq = Vec::new();
 
-        // Timed out in-flight messages
-        for (&ts, ids) in &self.waiting_for_ack {
-            if ts <= now {
-                for &id in ids {
-                    if let Some(msg) = self.registry.get(&id) {
-                        if msg.attempts >= max_retries {
-                            ids_to_dlq.push(id);
-                        } else {
-                            ids_to_ready.push(id);
-                        }
-                    }
+        // Timed out in-flight messages, out of the index
+        for id in self.waiting_for_ack.pop_due(now) {
+            if let Some(msg) = self.registry.get(&id) {
+                if msg.attempts >= max_retries {
+                    ids_to_dlq.push(id);
+                } else {
+                    ids_to_ready.push(id);
                 }
-            } else {
-                break;
             }
         }
 
@@ -229,16 +498,12 @@ impl QueueState {
         let mut dlq_msgs = Vec::new();
         for id in ids_to_dlq {
             if let Some(msg) = self.registry.remove(&id) { // Remove returns value
-                // Clean up indexes
-                match msg.state {
-                    MessageState::InFlight(ts) => {
-                        if let Some(queue) = self.waiting_for_ack.get_mut(&ts) {
-                            queue.remove(&id);
-                            if queue.is_empty() { self.waiting_for_ack.remove(&ts); }
-                        }
-                    },
-                    // Should be InFlight mostly, but handle others if logic changes
-                    _ => {}
+                self.bytes = self.bytes.saturating_sub(msg.payload.len());
+                if let Some(index) = self.search.as_mut() {
+                    index.remove(&id);
+                }
+                if let Some(group) = &msg.message_group {
+                    self.leave_line(id, group);
                 }
                 dlq_msgs.push(DlqMessage::from_message(msg, "Timeout".to_string()));
             }
@@ -247,6 +512,59 @@ impl QueueState {
         (requeued_msgs, dlq_msgs)
     }
 
+    /// Earliest activation time of a scheduled message.
+    pub fn next_scheduled(&self) -> Option<u64> {
+        self.waiting_for_time.next()
+    }
+
+    /// Make scheduled messages whose time has come ready for dispatch.
+    pub fn promote_due(&mut self) -> Vec<Message> {
+        let now = self.clock.now_ms();
+        let due = self.waiting_for_time.pop_due(now);
+
+        let mut promoted = Vec::with_capacity(due.len());
+        for id in due {
+            if self.transition_to(id, MessageState::Ready) {
+                if let Some(msg) = self.registry.get_mut(&id) {
+                    msg.visible_at = 0;
+                    promoted.push(msg.clone());
+                }
+            }
+        }
+        promoted
+    }
+
+    /// Scheduled messages activating in `from..=to`, in activation order.
+    pub fn list_scheduled(&self, from: u64, to: u64, limit: usize) -> Vec<Message> {
+        if from > to {
+            return Vec::new();
+        }
+        self.waiting_for_time.range(from, to)
+            .filter_map(|(_, id)| self.registry.get(id).cloned())
+            .take(limit)
+            .collect()
+    }
+
+    /// Move a scheduled message to `at`; a time already passed makes it ready.
+    /// Returns the updated message, or `None` if `id` is not scheduled.
+    pub fn reschedule(&mut self, id: Uuid, at: u64) -> Option<Message> {
+        if !matches!(self.registry.get(&id)?.state, MessageState::Scheduled(_)) {
+            return None;
+        }
+        let (state, visible_at) = match at {
+            at if at > self.clock.now_ms() => (MessageState::Scheduled(at), at),
+            _ => (MessageState::Ready, 0),
+        };
+        self.transition_to(id, state);
+        let msg = self.registry.get_mut(&id)?;
+        msg.visible_at = visible_at;
+        Some(msg.clone())
+    }
+
+    pub fn scheduled_count(&self) -> usize {
+        self.waiting_for_time.len()
+    }
+
     pub fn get_counters(&self) -> (usize, usize) {
         let mut pending = 0;
         let mut inflight = 0;
@@ -254,32 +572,47 @@ impl QueueState {
         for (_, queue) in &self.waiting_for_dispatch {
             pending += queue.len();
         }
+        pending += self.held().count();
+        pending += self.spilled.values().map(|lane| lane.count + lane.overflow.len()).sum::<usize>();
 
-        for (_, list) in &self.waiting_for_ack {
-            inflight += list.len();
-        }
+        inflight += self.waiting_for_ack.len();
 
         (pending, inflight)
     }
 
-    pub fn get_messages(&self, state_filter: String, offset: usize, limit: usize, search: Option<String>) -> (usize, Vec<QueueMessagePreview>) {
+    /// A page of the messages in one state, optionally of a single priority.
+    pub fn get_messages(&self, state_filter: String, priority: Option<u8>, offset: usize, limit: usize, search: Option<String>) -> (usize, Vec<QueueMessagePreview>) {
         let filter_tag = match state_filter.to_lowercase().as_str() {
             "pending" => MessageStateTag::Pending,
             "inflight" => MessageStateTag::InFlight,
+            "scheduled" => MessageStateTag::Scheduled,
             _ => return (0, vec![]),
         };
 
         let ids: Vec<&Uuid> = match filter_tag {
-            MessageStateTag::Pending => self.waiting_for_dispatch.values().flat_map(|q| q.iter()).collect(),
-            MessageStateTag::InFlight => self.waiting_for_ack.values().flat_map(|q| q.iter()).collect(),
+            MessageStateTag::Pending => match priority {
+                Some(priority) => self.waiting_for_dispatch.get(&priority).into_iter().flat_map(|q| q.iter())
+                    .chain(self.held())
+                    .chain(self.spilled.get(&priority).into_iter().flat_map(|lane| lane.overflow.iter()))
+                    .collect(),
+                None => self.waiting_for_dispatch.values().flat_map(|q| q.iter())
+                    .chain(self.held())
+                    .chain(self.spilled.values().flat_map(|lane| lane.overflow.iter()))
+                    .collect(),
+            },
+            MessageStateTag::InFlight => self.waiting_for_ack.ordered().map(|(_, id)| id).collect(),
+            MessageStateTag::Scheduled => self.waiting_for_time.ordered().map(|(_, id)| id).collect(),
         };
 
+        // With the index: every word of `search`, else a substring of the payload
+        let indexed = search.as_deref().zip(self.search.as_ref()).and_then(|(s, index)| index.query(s));
         let mut all_filtered: Vec<&Message> = Vec::new();
         for id in ids {
-            if let Some(msg) = self.registry.get(id) {
-                let matches_search = match &search {
-                    Some(s) => String::from_utf8_lossy(&msg.payload).contains(s),
-                    None => true,
+            if let Some(msg) = self.registry.get(id).filter(|m| priority.is_none_or(|p| m.priority == p)) {
+                let matches_search = match (&indexed, &search) {
+                    (Some(matching), _) => matching.contains(id),
+                    (None, Some(s)) => String::from_utf8_lossy(&msg.payload).contains(s),
+                    (None, None) => true,
                 };
                 if matches_search {
                     all_filtered.push(msg);
@@ -296,6 +629,7 @@ impl QueueState {
                 let state = match msg.state {
                     MessageState::Ready => MessageStateTag::Pending,
                     MessageState::InFlight(_) => MessageStateTag::InFlight,
+                    MessageState::Scheduled(_) => MessageStateTag::Scheduled,
                 };
                 QueueMessagePreview {
                     id: msg.id,
@@ -303,6 +637,7 @@ impl QueueState {
                     state,
                     priority: msg.priority,
                     attempts: msg.attempts,
+                    created_at: msg.created_at,
                 }
             })
             .collect();
@@ -310,6 +645,11 @@ impl QueueState {
         (total, paged)
     }
 
+    /// Messages of `group` still ready or in flight.
+    pub fn group_depth(&self, group: &str) -> usize {
+        self.groups.get(group).map_or(0, |line| line.len())
+    }
+
     pub fn has_ready_messages(&self) -> bool {
         !self.waiting_for_dispatch.is_empty()
     }
@@ -358,11 +698,328 @@ impl QueueState {
         messages
     }
 
+    pub fn contains(&self, id: &Uuid) -> bool {
+        self.registry.contains_key(id)
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&Message> {
+        self.registry.get(id)
+    }
+
+    /// All messages in RAM, in-flight ones first (they were already at the head
+    /// of the line), then ready ones in dispatch order (held group members and
+    /// spill overflow last), then scheduled ones.
+    pub fn all_messages(&self) -> Vec<Message> {
+        let inflight = self.waiting_for_ack.ordered().map(|(_, id)| id);
+        let ready = self.waiting_for_dispatch.values().rev().flat_map(|ids| ids.iter())
+            .chain(self.held())
+            .chain(self.spilled.values().rev().flat_map(|lane| lane.overflow.iter()));
+        let scheduled = self.waiting_for_time.ordered().map(|(_, id)| id);
+        inflight.chain(ready).chain(scheduled)
+            .filter_map(|id| self.registry.get(id).cloned())
+            .collect()
+    }
+
     /// Get total message count
     pub fn len(&self) -> usize {
         self.registry.len()
     }
 
+    /// Total payload bytes currently held by the queue in RAM
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Ready messages paged out to the store, and their payload bytes
+    pub fn spilled(&self) -> (usize, usize) {
+        self.spilled.values().fold((0, 0), |(count, bytes), lane| (count + lane.count, bytes + lane.bytes))
+    }
+
+    /// Page ready messages out of RAM. Overflow always follows the messages
+    /// already on disk; while the registry holds more than `threshold`
+    /// messages, a priority with nothing on disk keeps the first `keep` of
+    /// its bucket and pages out the rest. Grouped messages never leave RAM.
+    /// Returns the paged-out messages with their spill sequence, to be
+    /// written to the store before anything else touches them.
+    pub fn page_out(&mut self, threshold: usize, keep: usize) -> Vec<(Message, u64)> {
+        let mut paged = Vec::new();
+        let priorities: Vec<u8> = self.spilled.iter()
+            .filter(|(_, lane)| !lane.overflow.is_empty())
+            .map(|(&priority, _)| priority)
+            .collect();
+        for priority in priorities {
+            let ids: Vec<Uuid> = self.spilled.get_mut(&priority).map(|lane| lane.overflow.drain().collect()).unwrap_or_default();
+            self.spill_ids(priority, ids, &mut paged);
+        }
+
+        if threshold == 0 || self.registry.len() <= threshold {
+            return paged;
+        }
+        let buckets: Vec<(u8, Vec<Uuid>)> = self.waiting_for_dispatch.iter()
+            .filter(|(priority, ids)| ids.len() > keep && !self.spilled.contains_key(priority))
+            .map(|(&priority, ids)| {
+                let tail = ids.iter().skip(keep)
+                    .filter(|id| self.registry.get(id).is_some_and(|m| m.message_group.is_none()))
+                    .copied()
+                    .collect();
+                (priority, tail)
+            })
+            .collect();
+        for (priority, ids) in buckets {
+            if ids.is_empty() {
+                continue;
+            }
+            if let Some(bucket) = self.waiting_for_dispatch.get_mut(&priority) {
+                for id in &ids {
+                    bucket.remove(id);
+                }
+            }
+            self.spilled.insert(priority, SpilledLane { cursor: self.next_spill_seq - 1, ..Default::default() });
+            self.spill_ids(priority, ids, &mut paged);
+        }
+        paged
+    }
+
+    /// Move ready ids, already out of every index, from RAM to `priority`'s lane.
+    fn spill_ids(&mut self, priority: u8, ids: Vec<Uuid>, paged: &mut Vec<(Message, u64)>) {
+        for id in ids {
+            let Some(msg) = self.registry.remove(&id) else { continue };
+            self.bytes = self.bytes.saturating_sub(msg.payload.len());
+            if let Some(index) = self.search.as_mut() {
+                index.remove(&id);
+            }
+            let lane = self.spilled.entry(priority).or_default();
+            lane.count += 1;
+            lane.bytes += msg.payload.len();
+            paged.push((msg, self.next_spill_seq));
+            self.next_spill_seq += 1;
+        }
+    }
+
+    /// Mark the lanes whose bucket ran below `low_water` as loading and return
+    /// `(priority, cursor)` for each: the next chunk is the messages of the
+    /// priority spilled after `cursor`.
+    pub fn begin_reload(&mut self, low_water: usize) -> Vec<(u8, u64)> {
+        let dispatch = &self.waiting_for_dispatch;
+        self.spilled.iter_mut()
+            .filter(|(priority, lane)| !lane.loading && dispatch.get(priority).map_or(0, |ids| ids.len()) < low_water)
+            .map(|(&priority, lane)| {
+                lane.loading = true;
+                (priority, lane.cursor)
+            })
+            .collect()
+    }
+
+    /// Put a chunk loaded back from the store at the back of its bucket.
+    /// A chunk for a lane purged (or reloaded) since `begin_reload` is
+    /// dropped: returns false. Once the disk is drained the overflow
+    /// follows, and the lane is gone.
+    pub fn end_reload(&mut self, priority: u8, cursor: u64, chunk: Vec<(Message, u64)>) -> bool {
+        match self.spilled.get_mut(&priority) {
+            Some(lane) if lane.loading && lane.cursor == cursor => lane.loading = false,
+            _ => return false,
+        }
+        // An empty chunk means the store has nothing left for the lane
+        let drained = chunk.is_empty();
+        for (mut msg, seq) in chunk {
+            let Some(lane) = self.spilled.get_mut(&priority) else { break };
+            lane.count = lane.count.saturating_sub(1);
+            lane.bytes = lane.bytes.saturating_sub(msg.payload.len());
+            lane.cursor = lane.cursor.max(seq);
+            msg.state = MessageState::Ready;
+            msg.visible_at = 0;
+            let id = msg.id;
+            if self.registry.contains_key(&id) {
+                continue;
+            }
+            self.bytes += msg.payload.len();
+            if let Some(index) = self.search.as_mut() {
+                index.insert(id, &[&msg.payload]);
+            }
+            self.registry.insert(id, msg);
+            self.waiting_for_dispatch.entry(priority).or_default().insert(id);
+        }
+        if drained || self.spilled.get(&priority).is_some_and(|lane| lane.count == 0) {
+            if let Some(lane) = self.spilled.remove(&priority) {
+                let bucket = self.waiting_for_dispatch.entry(priority).or_default();
+                for id in lane.overflow {
+                    bucket.insert(id);
+                }
+                self.waiting_for_dispatch.retain(|_, ids| !ids.is_empty());
+            }
+        }
+        true
+    }
+
+    /// A reload failed: the lane may try again.
+    pub fn abort_reload(&mut self, priority: u8) {
+        if let Some(lane) = self.spilled.get_mut(&priority) {
+            lane.loading = false;
+        }
+    }
+
+    /// Forget the messages of `priority` on disk (purged). Returns the
+    /// cursor above which the store holds them, and how many there were.
+    pub fn drop_spilled(&mut self, priority: u8) -> Option<(u64, usize)> {
+        let lane = self.spilled.remove(&priority)?;
+        let bucket = self.waiting_for_dispatch.entry(priority).or_default();
+        for id in lane.overflow {
+            bucket.insert(id);
+        }
+        self.waiting_for_dispatch.retain(|_, ids| !ids.is_empty());
+        Some((lane.cursor, lane.count))
+    }
+
+    /// `(priority, cursor)` of every lane with messages on disk.
+    pub fn spilled_lanes(&self) -> Vec<(u8, u64)> {
+        self.spilled.iter().map(|(&priority, lane)| (priority, lane.cursor)).collect()
+    }
+
+    /// Recovery: `count` messages of `priority` are on disk, the last one
+    /// spilled as `last_seq`. Restore after the messages in RAM.
+    pub fn restore_spilled(&mut self, priority: u8, count: usize, bytes: usize, last_seq: u64) {
+        if count == 0 {
+            return;
+        }
+        self.spilled.insert(priority, SpilledLane { count, bytes, ..Default::default() });
+        self.next_spill_seq = self.next_spill_seq.max(last_seq + 1);
+    }
+
+    /// Validate registry/index consistency and repair any drift.
+    /// Drops index ids with no registry entry (or indexed under the wrong
+    /// state), re-indexes registry entries missing from their index and
+    /// recomputes byte accounting. Returns the number of repaired entries.
+    pub fn compact(&mut self) -> usize {
+        let mut repaired = 0;
+
+        for (group, line) in self.groups.iter_mut() {
+            let registry = &self.registry;
+            let before = line.len();
+            line.retain(|id| registry.get(id).is_some_and(|m| {
+                m.message_group.as_ref() == Some(group) && matches!(m.state, MessageState::Ready | MessageState::InFlight(_))
+            }));
+            repaired += before - line.len();
+        }
+        self.groups.retain(|_, line| !line.is_empty());
+
+        for (&priority, ids) in self.waiting_for_dispatch.iter_mut() {
+            let (registry, groups) = (&self.registry, &self.groups);
+            let before = ids.len();
+            ids.retain(|id| registry.get(id).is_some_and(|m| {
+                m.state == MessageState::Ready && m.priority == priority
+                    && m.message_group.as_ref().is_none_or(|g| groups.get(g).and_then(|line| line.front()) == Some(id))
+            }));
+            repaired += before - ids.len();
+        }
+        self.waiting_for_dispatch.retain(|_, ids| !ids.is_empty());
+
+        let registry = &self.registry;
+        repaired += self.waiting_for_ack.retain(|ts, id| registry.get(id).is_some_and(|m| m.state == MessageState::InFlight(ts)));
+        repaired += self.waiting_for_time.retain(|ts, id| registry.get(id).is_some_and(|m| m.state == MessageState::Scheduled(ts)));
+
+        let mut bytes = 0;
+        for (id, msg) in &self.registry {
+            bytes += msg.payload.len();
+            let line = msg.message_group.as_ref().map(|g| self.groups.get(g).is_some_and(|line| line.contains(id)));
+            let indexed = match msg.state {
+                // Grouped ones: the line's front is re-indexed below
+                MessageState::Ready => line.unwrap_or_else(|| {
+                    self.waiting_for_dispatch.get(&msg.priority).is_some_and(|ids| ids.contains(id))
+                        || self.spilled.get(&msg.priority).is_some_and(|lane| lane.overflow.contains(id))
+                }),
+                MessageState::InFlight(ts) => line != Some(false) && self.waiting_for_ack.contains(ts, id),
+                MessageState::Scheduled(ts) => self.waiting_for_time.contains(ts, id),
+            };
+            if !indexed {
+                match msg.state {
+                    MessageState::Ready if line.is_none() => { self.waiting_for_dispatch.entry(msg.priority).or_default().insert(*id); }
+                    MessageState::Ready => {}
+                    MessageState::InFlight(ts) => { self.waiting_for_ack.insert(ts, *id); }
+                    MessageState::Scheduled(ts) => { self.waiting_for_time.insert(ts, *id); }
+                }
+                if let (Some(group), Some(false)) = (&msg.message_group, line) {
+                    self.groups.entry(group.clone()).or_default().insert(*id);
+                }
+                repaired += 1;
+            }
+        }
+        self.bytes = bytes;
+
+        for line in self.groups.values() {
+            let front = line.front().and_then(|id| self.registry.get(id)).filter(|m| m.state == MessageState::Ready);
+            if let Some(msg) = front {
+                if self.waiting_for_dispatch.entry(msg.priority).or_default().insert(msg.id) {
+                    repaired += 1;
+                }
+            }
+        }
+
+        repaired
+    }
+
+    /// Panic unless every registry entry is indexed exactly once, under its
+    /// current state and priority, and byte accounting matches. `compact`
+    /// repairs the same drift in production; tests want it to never happen.
+    #[cfg(test)]
+    pub(crate) fn assert_invariants(&self) {
+        let mut indexed = 0;
+
+        for (&priority, ids) in &self.waiting_for_dispatch {
+            assert!(!ids.is_empty(), "empty ready bucket for priority {}", priority);
+            for id in ids {
+                let msg = self.registry.get(id).unwrap_or_else(|| panic!("ready id {} not in registry", id));
+                assert_eq!(msg.state, MessageState::Ready, "id {} indexed as ready", id);
+                assert_eq!(msg.priority, priority, "id {} in the wrong priority bucket", id);
+                if let Some(group) = &msg.message_group {
+                    assert_eq!(self.groups.get(group).and_then(|line| line.front()), Some(id), "id {} dispatchable out of group order", id);
+                }
+                indexed += 1;
+            }
+        }
+
+        if let TimerIndex::Sorted(buckets) = &self.waiting_for_ack {
+            assert!(buckets.values().all(|ids| !ids.is_empty()), "empty in-flight bucket");
+        }
+        for (ts, id) in self.waiting_for_ack.iter() {
+            let msg = self.registry.get(id).unwrap_or_else(|| panic!("in-flight id {} not in registry", id));
+            assert_eq!(msg.state, MessageState::InFlight(ts), "id {} indexed under {}", id, ts);
+            assert_eq!(msg.visible_at, ts, "id {} visible_at out of sync", id);
+            if let Some(group) = &msg.message_group {
+                assert_eq!(self.groups.get(group).and_then(|line| line.front()), Some(id), "id {} in flight out of group order", id);
+            }
+            indexed += 1;
+        }
+
+        for (ts, id) in self.waiting_for_time.iter() {
+            let msg = self.registry.get(id).unwrap_or_else(|| panic!("scheduled id {} not in registry", id));
+            assert_eq!(msg.state, MessageState::Scheduled(ts), "id {} scheduled under {}", id, ts);
+            indexed += 1;
+        }
+
+        for (&priority, lane) in &self.spilled {
+            for id in &lane.overflow {
+                let msg = self.registry.get(id).unwrap_or_else(|| panic!("overflow id {} not in registry", id));
+                assert_eq!(msg.state, MessageState::Ready, "id {} in overflow while not ready", id);
+                assert_eq!((msg.priority, msg.message_group.as_ref()), (priority, None), "id {} in the wrong overflow", id);
+                indexed += 1;
+            }
+        }
+
+        for (group, line) in &self.groups {
+            assert!(!line.is_empty(), "empty line for group {}", group);
+            for id in line.iter().skip(1) {
+                let msg = self.registry.get(id).unwrap_or_else(|| panic!("held id {} not in registry", id));
+                assert_eq!(msg.state, MessageState::Ready, "id {} held while not ready", id);
+                assert_eq!(msg.message_group.as_ref(), Some(group), "id {} in the wrong group", id);
+                indexed += 1;
+            }
+        }
+
+        assert_eq!(indexed, self.registry.len(), "registry entries missing from indexes");
+        let bytes: usize = self.registry.values().map(|m| m.payload.len()).sum();
+        assert_eq!(self.bytes, bytes, "byte accounting drifted");
+    }
+
     /// Remove a message by ID (for DLQ operations)
     pub fn remove_by_id(&mut self, id: Uuid) -> Option<Message> {
         self.delete_message_and_return(id)
@@ -373,25 +1030,31 @@ impl QueueState {
         self.registry.clear();
         self.waiting_for_dispatch.clear();
         self.waiting_for_ack.clear();
+        self.waiting_for_time.clear();
+        self.groups.clear();
+        self.spilled.clear();
+        self.bytes = 0;
     }
 
     // --- Internal helpers ---
 
     /// Pop a single message from the queue. Returns (message, is_earliest_timeout).
     fn pop_single(&mut self, visibility_timeout_ms: u64) -> (Option<Message>, bool) {
-        let now = current_time_ms();
-
         // Find highest priority ready message
         let next_id = self.waiting_for_dispatch
             .iter()
             .rev()
             .find_map(|(_, queue)| queue.front().cloned());
 
-        let next_id = match next_id {
-            Some(id) => id,
-            None => return (None, false),
-        };
+        match next_id {
+            Some(id) => self.dispatch(id, visibility_timeout_ms),
+            None => (None, false),
+        }
+    }
 
+    /// Move a ready message in flight and count the delivery attempt.
+    fn dispatch(&mut self, next_id: Uuid, visibility_timeout_ms: u64) -> (Option<Message>, bool) {
+        let now = self.clock.now_ms();
         let timeout = now + visibility_timeout_ms;
         self.transition_to(next_id, MessageState::InFlight(timeout));
 
@@ -402,9 +1065,8 @@ impl QueueState {
 
             // Check if this is the earliest timeout
             let is_earliest = self.waiting_for_ack
-                .keys()
                 .next()
-                .map(|&t| t == timeout)
+                .map(|t| t == timeout)
                 .unwrap_or(false);
 
             return (Some(msg.clone()), is_earliest);
@@ -421,19 +1083,22 @@ impl QueueState {
                     queue.remove(&id);
                     if queue.is_empty() { self.waiting_for_dispatch.remove(&priority); }
                 }
+                if let Some(lane) = self.spilled.get_mut(&priority) {
+                    lane.overflow.remove(&id);
+                }
             }
             MessageState::InFlight(ts) => {
-                if let Some(queue) = self.waiting_for_ack.get_mut(ts) {
-                    queue.remove(&id);
-                    if queue.is_empty() { self.waiting_for_ack.remove(ts); }
-                }
+                self.waiting_for_ack.remove(*ts, &id);
+            }
+            MessageState::Scheduled(ts) => {
+                self.waiting_for_time.remove(*ts, &id);
             }
         }
     }
 
     fn transition_to(&mut self, id: Uuid, new_state: MessageState) -> bool {
-        let (old_state, priority) = match self.registry.get(&id) {
-            Some(m) => (m.state.clone(), m.priority),
+        let (old_state, priority, group) = match self.registry.get_mut(&id) {
+            Some(m) => (std::mem::replace(&mut m.state, new_state.clone()), m.priority, m.message_group.clone()),
             None => return false,
         };
 
@@ -441,22 +1106,17 @@ impl QueueState {
             return true;
         }
 
-        // Remove from old index
+        // Remove from old index, add to new index
         self.remove_from_index(&old_state, id, priority);
-
-        // Update state and add to new index
-        if let Some(msg) = self.registry.get_mut(&id) {
-            msg.state = new_state.clone();
-
-            match new_state {
-                MessageState::Ready => {
-                    self.waiting_for_dispatch
-                        .entry(msg.priority)
-                        .or_default()
-                        .insert(id);
-                }
-                MessageState::InFlight(ts) => {
-                    self.waiting_for_ack.entry(ts).or_default().insert(id);
+        match new_state {
+            MessageState::Ready => self.index_ready(id, priority, group.as_deref()),
+            MessageState::InFlight(ts) => {
+                self.waiting_for_ack.insert(ts, id);
+            }
+            MessageState::Scheduled(ts) => {
+                self.waiting_for_time.insert(ts, id);
+                if let Some(group) = group {
+                    self.leave_line(id, &group);
                 }
             }
         }
@@ -464,15 +1124,71 @@ impl QueueState {
         true
     }
 
+    /// Index a message that became ready. A grouped one joins the back of its
+    /// line (or keeps its place there) and is dispatchable only at the front.
+    fn index_ready(&mut self, id: Uuid, priority: u8, group: Option<&str>) {
+        if let Some(group) = group {
+            let line = self.groups.entry(group.to_string()).or_default();
+            if !line.contains(&id) {
+                line.insert(id);
+            }
+            if line.front() != Some(&id) {
+                return;
+            }
+        } else if let Some(lane) = self.spilled.get_mut(&priority) {
+            lane.overflow.insert(id);
+            return;
+        }
+        self.waiting_for_dispatch.entry(priority).or_default().insert(id);
+    }
+
+    /// Take a message out of its group's line; the next one becomes
+    /// dispatchable if it is ready.
+    fn leave_line(&mut self, id: Uuid, group: &str) {
+        let Some(line) = self.groups.get_mut(group) else { return };
+        let was_front = line.front() == Some(&id);
+        if !line.remove(&id) {
+            return;
+        }
+        let Some(&next) = line.front() else {
+            self.groups.remove(group);
+            return;
+        };
+        if was_front {
+            if let Some(msg) = self.registry.get(&next).filter(|m| m.state == MessageState::Ready) {
+                self.waiting_for_dispatch.entry(msg.priority).or_default().insert(next);
+            }
+        }
+    }
+
+    fn is_held(&self, id: &Uuid) -> bool {
+        self.registry.get(id)
+            .and_then(|m| m.message_group.as_ref())
+            .and_then(|group| self.groups.get(group))
+            .is_some_and(|line| line.front() != Some(id) && line.contains(id))
+    }
+
+    /// Ready messages waiting behind the front of their group's line.
+    fn held(&self) -> impl Iterator<Item = &Uuid> {
+        self.groups.values().flat_map(|line| line.iter().skip(1))
+    }
+
     fn delete_message(&mut self, id: Uuid) -> bool {
         self.delete_message_and_return(id).is_some()
     }
 
     fn delete_message_and_return(&mut self, id: Uuid) -> Option<Message> {
         let msg = self.registry.remove(&id)?;
+        self.bytes = self.bytes.saturating_sub(msg.payload.len());
+        if let Some(index) = self.search.as_mut() {
+            index.remove(&id);
+        }
 
         // Remove from index
         self.remove_from_index(&msg.state, id, msg.priority);
+        if let Some(group) = &msg.message_group {
+            self.leave_line(id, group);
+        }
 
         Some(msg)
     }
@@ -489,3 +1205,9 @@ pub fn current_time_ms() -> u64 {
         .as_millis() as u64
 }
 
+/// Creation time (unix ms) embedded in a time-ordered message id.
+/// Returns `None` for ids without a timestamp (legacy UUIDv4 messages).
+pub fn id_timestamp_ms(id: &Uuid) -> Option<u64> {
+    let (secs, nanos) = id.get_timestamp()?.to_unix();
+    Some(secs * 1000 + (nanos / 1_000_000) as u64)
+}
@@ -0,0 +1,372 @@
+//! Model-based simulation of `QueueState`.
+//!
+//! Random operation sequences run against both the real state machine and a
+//! plain reference model (ready FIFOs per priority, in-flight buckets per
+//! deadline). After every step the state must pass `assert_invariants` and
+//! hold exactly the model's messages, in the model's dispatch order, with the
+//! same attempt counts. Time is a `MockClock`, so expiry is deterministic.
+//!
+//! The same sequences run again with scheduled and in-flight messages in
+//! timing wheels (`QUEUE_TIMING_WHEEL`).
+//!
+//! A second run mixes in message groups and checks that each group is
+//! dispatched one message at a time, in push order.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+use bytes::Bytes;
+use proptest::prelude::*;
+use uuid::Uuid;
+
+use crate::brokers::clock::{Clock, MockClock};
+use crate::brokers::queue::domain::queue::{Message, MessageState, QueueState};
+
+const MAX_RETRIES: u32 = 3;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Push { priority: u8, len: usize },
+    Pop { visibility_ms: u64 },
+    TakeBatch { max: usize, visibility_ms: u64, quota: Option<(u8, f64)> },
+    Ack(usize),
+    Nack { pick: usize, delay_ms: u64 },
+    Requeue(usize),
+    Remove(usize),
+    /// Advance the clock, then process expired in-flight messages
+    Advance(u64),
+    Compact,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let priority = prop::sample::select(vec![0u8, 1, 128, 255]);
+    let visibility = prop::sample::select(vec![0u64, 10, 50, 200]);
+    prop_oneof![
+        4 => (priority.clone(), 0usize..32).prop_map(|(priority, len)| Op::Push { priority, len }),
+        2 => visibility.clone().prop_map(|visibility_ms| Op::Pop { visibility_ms }),
+        2 => (1usize..8, visibility, prop::option::of((priority, 0.0f64..=1.0)))
+            .prop_map(|(max, visibility_ms, quota)| Op::TakeBatch { max, visibility_ms, quota }),
+        2 => any::<usize>().prop_map(Op::Ack),
+        2 => (any::<usize>(), prop::sample::select(vec![0u64, 0, 30])).prop_map(|(pick, delay_ms)| Op::Nack { pick, delay_ms }),
+        1 => any::<usize>().prop_map(Op::Requeue),
+        1 => any::<usize>().prop_map(Op::Remove),
+        2 => (0u64..120).prop_map(Op::Advance),
+        1 => Just(Op::Compact),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Where {
+    Ready,
+    InFlight(u64),
+}
+
+#[derive(Debug)]
+struct ModelMsg {
+    priority: u8,
+    attempts: u32,
+    at: Where,
+}
+
+/// What `QueueState` should hold, kept in the simplest possible structures.
+#[derive(Default)]
+struct Model {
+    msgs: HashMap<Uuid, ModelMsg>,
+    ready: BTreeMap<u8, VecDeque<Uuid>>,
+    inflight: BTreeMap<u64, Vec<Uuid>>,
+}
+
+impl Model {
+    fn unindex(&mut self, id: Uuid) {
+        let msg = &self.msgs[&id];
+        match msg.at {
+            Where::Ready => {
+                let bucket = self.ready.get_mut(&msg.priority).unwrap();
+                bucket.retain(|x| *x != id);
+                if bucket.is_empty() {
+                    self.ready.remove(&msg.priority);
+                }
+            }
+            Where::InFlight(ts) => {
+                let bucket = self.inflight.get_mut(&ts).unwrap();
+                bucket.retain(|x| *x != id);
+                if bucket.is_empty() {
+                    self.inflight.remove(&ts);
+                }
+            }
+        }
+    }
+
+    /// Same rule as `transition_to`: staying in place keeps the queue position.
+    fn move_to(&mut self, id: Uuid, to: Where) {
+        if self.msgs[&id].at == to {
+            return;
+        }
+        self.unindex(id);
+        let msg = self.msgs.get_mut(&id).unwrap();
+        msg.at = to;
+        match to {
+            Where::Ready => self.ready.entry(msg.priority).or_default().push_back(id),
+            Where::InFlight(ts) => self.inflight.entry(ts).or_default().push(id),
+        }
+    }
+
+    fn remove(&mut self, id: Uuid) -> bool {
+        if !self.msgs.contains_key(&id) {
+            return false;
+        }
+        self.unindex(id);
+        self.msgs.remove(&id);
+        true
+    }
+
+    fn dispatch(&mut self, id: Uuid, deadline: u64) {
+        self.move_to(id, Where::InFlight(deadline));
+        self.msgs.get_mut(&id).unwrap().attempts += 1;
+    }
+
+    fn next_ready(&self) -> Option<Uuid> {
+        self.ready.values().next_back().and_then(|bucket| bucket.front().copied())
+    }
+
+    fn ready_count(&self) -> usize {
+        self.ready.values().map(VecDeque::len).sum()
+    }
+
+    /// Dispatch order as reported by `QueueState::all_messages`.
+    fn order(&self) -> Vec<Uuid> {
+        let inflight = self.inflight.values().flatten();
+        let ready = self.ready.values().rev().flatten();
+        inflight.chain(ready).copied().collect()
+    }
+}
+
+fn run(ops: Vec<Op>, timing_wheel: bool) {
+    let clock = MockClock::new();
+    let mut state = QueueState::new(clock.clone());
+    if timing_wheel {
+        state.enable_timing_wheel(true);
+    }
+    let mut model = Model::default();
+    let mut pushed: Vec<Uuid> = Vec::new();
+
+    // Ids are picked among every id ever pushed, so acks/nacks of messages that
+    // are already gone are exercised too
+    let pick = |pushed: &[Uuid], i: usize| (!pushed.is_empty()).then(|| pushed[i % pushed.len()]);
+
+    for op in ops {
+        let now = clock.now_ms();
+        match op {
+            Op::Push { priority, len } => {
+                let msg = Message::new(Bytes::from(vec![0u8; len]), priority);
+                pushed.push(msg.id);
+                model.msgs.insert(msg.id, ModelMsg { priority, attempts: 0, at: Where::Ready });
+                model.ready.entry(priority).or_default().push_back(msg.id);
+                state.push(msg);
+            }
+            Op::Pop { visibility_ms } => {
+                let expected = model.next_ready();
+                let (msg, _) = state.pop(visibility_ms);
+                assert_eq!(msg.as_ref().map(|m| m.id), expected, "pop must take the oldest highest-priority message");
+                if let Some(id) = expected {
+                    model.dispatch(id, now + visibility_ms);
+                }
+            }
+            Op::TakeBatch { max, visibility_ms, quota } => {
+                let quotas = quota.map(|(priority, share)| HashMap::from([(priority, share)]));
+                let expected_len = max.min(model.ready_count());
+                let unquoted: Vec<Uuid> = model.ready.values().rev().flatten().take(max).copied().collect();
+
+                let (batch, _) = state.take_batch(max, visibility_ms, quotas.as_ref());
+                let ids: Vec<Uuid> = batch.iter().map(|m| m.id).collect();
+                assert_eq!(ids.len(), expected_len, "a batch fills up to max when messages are ready");
+                if quotas.is_none() {
+                    assert_eq!(ids, unquoted);
+                }
+                for id in ids {
+                    assert_eq!(model.msgs[&id].at, Where::Ready, "batch took a message that was not ready");
+                    model.dispatch(id, now + visibility_ms);
+                }
+            }
+            Op::Ack(i) => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                assert_eq!(state.ack(id), model.remove(id));
+            }
+            Op::Nack { pick: i, delay_ms } => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                let (requeued, dead) = state.nack_after(id, "sim".to_string(), MAX_RETRIES, delay_ms);
+                match model.msgs.get(&id).map(|m| m.attempts) {
+                    None => assert!(requeued.is_none() && dead.is_none()),
+                    Some(attempts) if attempts >= MAX_RETRIES => {
+                        assert_eq!(dead.map(|d| d.id), Some(id), "out of retries goes to the DLQ");
+                        model.remove(id);
+                    }
+                    Some(_) => {
+                        assert_eq!(requeued.map(|m| m.id), Some(id));
+                        let to = if delay_ms == 0 { Where::Ready } else { Where::InFlight(now + delay_ms) };
+                        model.move_to(id, to);
+                    }
+                }
+            }
+            Op::Requeue(i) => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                let inflight = model.msgs.get(&id).is_some_and(|m| matches!(m.at, Where::InFlight(_)));
+                assert_eq!(state.requeue_inflight(id), inflight);
+                if inflight {
+                    model.move_to(id, Where::Ready);
+                    let msg = model.msgs.get_mut(&id).unwrap();
+                    msg.attempts = msg.attempts.saturating_sub(1);
+                }
+            }
+            Op::Remove(i) => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                assert_eq!(state.remove_by_id(id).is_some(), model.remove(id));
+            }
+            Op::Advance(ms) => {
+                clock.advance(Duration::from_millis(ms));
+                let now = clock.now_ms();
+                let due: Vec<Uuid> = model.inflight.range(..=now).flat_map(|(_, ids)| ids.iter().copied()).collect();
+                let (to_dlq, to_ready): (Vec<Uuid>, Vec<Uuid>) = due.into_iter().partition(|id| model.msgs[id].attempts >= MAX_RETRIES);
+
+                let (requeued, dead) = state.process_expired(MAX_RETRIES);
+                assert_eq!(requeued.iter().map(|m| m.id).collect::<Vec<_>>(), to_ready);
+                assert_eq!(dead.iter().map(|m| m.id).collect::<Vec<_>>(), to_dlq);
+                for id in to_ready {
+                    model.move_to(id, Where::Ready);
+                }
+                for id in to_dlq {
+                    model.remove(id);
+                }
+            }
+            Op::Compact => {
+                assert_eq!(state.compact(), 0, "nothing to repair in a consistent state");
+            }
+        }
+
+        state.assert_invariants();
+        assert_eq!(state.len(), model.msgs.len(), "messages lost or duplicated");
+        let held = state.all_messages();
+        assert_eq!(held.iter().map(|m| m.id).collect::<Vec<_>>(), model.order());
+        for msg in held {
+            assert_eq!(msg.attempts, model.msgs[&msg.id].attempts, "attempts of {}", msg.id);
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn random_operations_keep_queue_state_consistent(ops in prop::collection::vec(op(), 1..200)) {
+        run(ops, false);
+    }
+
+    #[test]
+    fn random_operations_keep_queue_state_consistent_on_timing_wheels(ops in prop::collection::vec(op(), 1..200)) {
+        run(ops, true);
+    }
+}
+
+#[derive(Debug, Clone)]
+enum GroupOp {
+    Push { group: Option<u8>, priority: u8 },
+    TakeBatch { max: usize },
+    Ack(usize),
+    Nack { pick: usize, delay_ms: u64 },
+    Remove(usize),
+    Advance(u64),
+    Compact,
+}
+
+fn group_op() -> impl Strategy<Value = GroupOp> {
+    prop_oneof![
+        4 => (prop::option::of(0u8..3), prop::sample::select(vec![0u8, 255]))
+            .prop_map(|(group, priority)| GroupOp::Push { group, priority }),
+        3 => (1usize..6).prop_map(|max| GroupOp::TakeBatch { max }),
+        3 => any::<usize>().prop_map(GroupOp::Ack),
+        2 => (any::<usize>(), prop::sample::select(vec![0u64, 30])).prop_map(|(pick, delay_ms)| GroupOp::Nack { pick, delay_ms }),
+        1 => any::<usize>().prop_map(GroupOp::Remove),
+        2 => (0u64..80).prop_map(GroupOp::Advance),
+        1 => Just(GroupOp::Compact),
+    ]
+}
+
+fn run_groups(ops: Vec<GroupOp>) {
+    let clock = MockClock::new();
+    let mut state = QueueState::new(clock.clone());
+    let mut pushed: Vec<Uuid> = Vec::new();
+    // Messages of each group still in the queue, in push order
+    let mut lines: HashMap<String, VecDeque<Uuid>> = HashMap::new();
+    let pick = |pushed: &[Uuid], i: usize| (!pushed.is_empty()).then(|| pushed[i % pushed.len()]);
+
+    for op in ops {
+        match op {
+            GroupOp::Push { group, priority } => {
+                let mut msg = Message::new(Bytes::from_static(b"m"), priority);
+                msg.message_group = group.map(|g| format!("g{}", g));
+                if let Some(group) = &msg.message_group {
+                    lines.entry(group.clone()).or_default().push_back(msg.id);
+                }
+                pushed.push(msg.id);
+                state.push(msg);
+            }
+            GroupOp::TakeBatch { max } => {
+                let (batch, _) = state.take_batch(max, 50, None);
+                let mut seen: Vec<&String> = Vec::new();
+                for (group, id) in batch.iter().filter_map(|m| m.message_group.as_ref().map(|g| (g, m.id))) {
+                    assert!(!seen.contains(&group), "two messages of {} in one batch", group);
+                    assert_eq!(lines[group].front(), Some(&id), "{} dispatched out of order", group);
+                    seen.push(group);
+                }
+            }
+            GroupOp::Ack(i) => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                state.ack(id);
+            }
+            GroupOp::Nack { pick: i, delay_ms } => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                state.nack_after(id, "sim".to_string(), MAX_RETRIES, delay_ms);
+            }
+            GroupOp::Remove(i) => {
+                let Some(id) = pick(&pushed, i) else { continue };
+                state.remove_by_id(id);
+            }
+            GroupOp::Advance(ms) => {
+                clock.advance(Duration::from_millis(ms));
+                state.process_expired(MAX_RETRIES);
+            }
+            GroupOp::Compact => {
+                assert_eq!(state.compact(), 0, "nothing to repair in a consistent state");
+            }
+        }
+
+        state.assert_invariants();
+        for (group, line) in lines.iter_mut() {
+            line.retain(|id| state.contains(id));
+            let in_flight = line.iter()
+                .filter(|id| state.get(id).is_some_and(|m| matches!(m.state, MessageState::InFlight(_))))
+                .collect::<Vec<_>>();
+            assert!(in_flight.len() <= 1, "{} has {} messages in flight", group, in_flight.len());
+            assert_eq!(state.group_depth(group), line.len());
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn random_grouped_operations_keep_group_order(ops in prop::collection::vec(group_op(), 1..200)) {
+        run_groups(ops);
+    }
+}
+
+#[test]
+fn expiry_requeues_then_dead_letters() {
+    let mut ops = vec![Op::Push { priority: 0, len: 4 }];
+    for _ in 0..MAX_RETRIES {
+        ops.push(Op::Pop { visibility_ms: 10 });
+        ops.push(Op::Advance(10));
+    }
+    run(ops, false);
+}
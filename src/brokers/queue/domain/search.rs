@@ -0,0 +1,104 @@
+//! Search Index: inverted index (word -> message ids) over message text, so
+//! the dashboard can find one payload among thousands without scanning them.
+//!
+//! Enabled with `QUEUE_SEARCH_INDEX`. Words are lowercase runs of letters and
+//! digits; a query matches messages holding every one of its words. Only the
+//! first `MAX_INDEXED_BYTES` of each text are indexed.
+
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+const MAX_INDEXED_BYTES: usize = 4096;
+const MIN_WORD_LEN: usize = 2;
+const MAX_WORD_LEN: usize = 64;
+
+#[derive(Default)]
+pub struct SearchIndex {
+    postings: HashMap<String, HashSet<Uuid>>,
+    /// Words of each message, to clean up its postings on removal
+    words: HashMap<Uuid, Vec<String>>,
+}
+
+impl SearchIndex {
+    /// Index `texts` (payload, failure reason...) under `id`, replacing what it had.
+    pub fn insert(&mut self, id: Uuid, texts: &[&[u8]]) {
+        self.remove(&id);
+        let mut words: Vec<String> = texts.iter()
+            .flat_map(|text| tokenize(&String::from_utf8_lossy(&text[..text.len().min(MAX_INDEXED_BYTES)])))
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        for word in &words {
+            self.postings.entry(word.clone()).or_default().insert(id);
+        }
+        self.words.insert(id, words);
+    }
+
+    pub fn remove(&mut self, id: &Uuid) {
+        let Some(words) = self.words.remove(id) else { return };
+        for word in words {
+            if let Some(ids) = self.postings.get_mut(&word) {
+                ids.remove(id);
+                if ids.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.words.clear();
+    }
+
+    /// Ids holding every word of `query`. `None` if the query has no words.
+    pub fn query(&self, query: &str) -> Option<HashSet<Uuid>> {
+        let mut words = tokenize(query);
+        // Rarest word first: the intersection only shrinks from there
+        words.sort_by_key(|w| self.postings.get(w).map_or(0, |ids| ids.len()));
+        let (first, rest) = words.split_first()?;
+        let mut ids = self.postings.get(first).cloned().unwrap_or_default();
+        for word in rest {
+            let Some(postings) = self.postings.get(word) else { return Some(HashSet::new()) };
+            ids.retain(|id| postings.contains(id));
+        }
+        Some(ids)
+    }
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_WORD_LEN)
+        .map(|w| w.chars().take(MAX_WORD_LEN).collect::<String>().to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_every_word_case_insensitively() {
+        let mut index = SearchIndex::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        index.insert(a, &[br#"{"order":"A-17","status":"Declined"}"#, b"card declined"]);
+        index.insert(b, &[br#"{"order":"B-3","status":"shipped"}"#]);
+
+        assert_eq!(index.query("DECLINED"), Some(HashSet::from([a])));
+        assert_eq!(index.query("order status"), Some(HashSet::from([a, b])));
+        assert_eq!(index.query("shipped declined"), Some(HashSet::new()));
+        assert_eq!(index.query("unknown"), Some(HashSet::new()));
+        assert_eq!(index.query(" - "), None, "No words: not a query");
+    }
+
+    #[test]
+    fn removal_drops_postings() {
+        let mut index = SearchIndex::default();
+        let id = Uuid::new_v4();
+        index.insert(id, &[b"timeout upstream"]);
+        index.remove(&id);
+        assert_eq!(index.query("timeout"), Some(HashSet::new()));
+        assert!(index.postings.is_empty() && index.words.is_empty());
+    }
+}
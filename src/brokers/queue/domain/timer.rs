@@ -0,0 +1,377 @@
+//! Index of message ids by a deadline (ms): the activation time of scheduled
+//! messages, the visibility timeout of in-flight ones.
+//!
+//! `Sorted` is a `BTreeMap` of deadline to ids, released by range scans.
+//! `Wheel` is a hierarchical timing wheel: six levels of 64 slots, 1ms per
+//! slot on the lowest one, so an insert or a removal is O(1) whatever the
+//! number of entries and releasing due ids only touches the slots that
+//! expire. Entries on the upper levels cascade down as their slot comes up.
+//! Queues use it for scheduled messages with `preciseDelays`, and for both
+//! indexes with `QUEUE_TIMING_WHEEL`.
+//!
+//! Both release and list entries by deadline, ties in insertion order.
+
+use std::collections::{BTreeMap, HashMap};
+
+use hashlink::LinkedHashSet;
+use uuid::Uuid;
+
+pub enum TimerIndex {
+    Sorted(BTreeMap<u64, LinkedHashSet<Uuid>>),
+    Wheel(TimingWheel),
+}
+
+impl Default for TimerIndex {
+    fn default() -> Self {
+        Self::Sorted(BTreeMap::new())
+    }
+}
+
+impl TimerIndex {
+    /// Move the entries to a timing wheel starting at `now`.
+    pub fn to_wheel(&mut self, now: u64) {
+        if matches!(self, Self::Wheel(_)) {
+            return;
+        }
+        let mut wheel = TimingWheel::new(now);
+        for (at, id) in self.iter() {
+            wheel.insert(at, *id);
+        }
+        *self = Self::Wheel(wheel);
+    }
+
+    pub fn insert(&mut self, at: u64, id: Uuid) {
+        match self {
+            Self::Sorted(map) => { map.entry(at).or_default().insert(id); }
+            Self::Wheel(wheel) => wheel.insert(at, id),
+        }
+    }
+
+    pub fn remove(&mut self, at: u64, id: &Uuid) -> bool {
+        match self {
+            Self::Sorted(map) => {
+                let Some(ids) = map.get_mut(&at) else { return false };
+                let removed = ids.remove(id);
+                if ids.is_empty() {
+                    map.remove(&at);
+                }
+                removed
+            }
+            Self::Wheel(wheel) => wheel.remove(at, id),
+        }
+    }
+
+    pub fn contains(&self, at: u64, id: &Uuid) -> bool {
+        match self {
+            Self::Sorted(map) => map.get(&at).is_some_and(|ids| ids.contains(id)),
+            Self::Wheel(wheel) => wheel.contains(at, id),
+        }
+    }
+
+    /// Earliest deadline. For the wheel, the start of the next slot to
+    /// expire: never later than the earliest deadline.
+    pub fn next(&self) -> Option<u64> {
+        match self {
+            Self::Sorted(map) => map.keys().next().copied(),
+            Self::Wheel(wheel) => wheel.next_deadline(),
+        }
+    }
+
+    /// Remove and return the ids whose deadline is `now` or earlier, in
+    /// deadline order.
+    pub fn pop_due(&mut self, now: u64) -> Vec<Uuid> {
+        match self {
+            Self::Sorted(map) => {
+                let later = map.split_off(&now.saturating_add(1));
+                std::mem::replace(map, later).into_values().flatten().collect()
+            }
+            Self::Wheel(wheel) => wheel.pop_due(now),
+        }
+    }
+
+    /// Every entry, in no particular order. See `ordered` for deadline order.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (u64, &Uuid)> + '_> {
+        match self {
+            Self::Sorted(map) => Box::new(map.iter().flat_map(|(&at, ids)| ids.iter().map(move |id| (at, id)))),
+            Self::Wheel(wheel) => Box::new(wheel.entries.iter().map(|(id, entry)| (entry.at, id))),
+        }
+    }
+
+    /// Every entry in deadline order (the wheel sorts them on each call).
+    pub fn ordered(&self) -> Box<dyn Iterator<Item = (u64, &Uuid)> + '_> {
+        match self {
+            Self::Sorted(_) => self.iter(),
+            Self::Wheel(wheel) => {
+                let mut entries: Vec<(&Uuid, &Entry)> = wheel.entries.iter().collect();
+                entries.sort_unstable_by_key(|(_, entry)| (entry.at, entry.seq));
+                Box::new(entries.into_iter().map(|(id, entry)| (entry.at, id)))
+            }
+        }
+    }
+
+    /// Entries with a deadline in `from..=to`, in deadline order.
+    pub fn range(&self, from: u64, to: u64) -> Box<dyn Iterator<Item = (u64, &Uuid)> + '_> {
+        match self {
+            Self::Sorted(map) => Box::new(map.range(from..=to).flat_map(|(&at, ids)| ids.iter().map(move |id| (at, id)))),
+            Self::Wheel(_) => Box::new(self.ordered().filter(move |(at, _)| (from..=to).contains(at))),
+        }
+    }
+
+    /// Keep the entries `keep` accepts; returns how many were dropped.
+    pub fn retain(&mut self, mut keep: impl FnMut(u64, &Uuid) -> bool) -> usize {
+        let dropped: Vec<(u64, Uuid)> = self.iter().filter(|(at, id)| !keep(*at, id)).map(|(at, id)| (at, *id)).collect();
+        for (at, id) in &dropped {
+            self.remove(*at, id);
+        }
+        dropped.len()
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Sorted(map) => map.values().map(|ids| ids.len()).sum(),
+            Self::Wheel(wheel) => wheel.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Sorted(map) => map.is_empty(),
+            Self::Wheel(wheel) => wheel.is_empty(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            Self::Sorted(map) => map.clear(),
+            Self::Wheel(wheel) => wheel.clear(),
+        }
+    }
+}
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const LEVELS: usize = 6;
+/// Span of the whole wheel (~2.2 years); later times wait on the top level
+const MAX_SPAN: u64 = (1 << (SLOT_BITS as usize * LEVELS)) - 1;
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    at: u64,
+    /// Insertion order: ties in `ordered`, and which copy in the slots is live
+    seq: u64,
+}
+
+struct Level {
+    /// Bit `n` set when slot `n` holds entries
+    occupied: u64,
+    slots: Vec<Vec<(Uuid, u64)>>,
+}
+
+/// Removal only forgets the entry: its copy stays in the slot and is skipped
+/// once the slot expires, so slots are plain vectors.
+pub struct TimingWheel {
+    /// Time the wheel was advanced to (ms): every entry in a slot is later
+    elapsed: u64,
+    levels: Vec<Level>,
+    /// Entries whose time has come
+    due: Vec<(Uuid, u64)>,
+    entries: HashMap<Uuid, Entry>,
+    next_seq: u64,
+}
+
+impl TimingWheel {
+    pub fn new(now: u64) -> Self {
+        Self {
+            elapsed: now,
+            levels: (0..LEVELS).map(|_| Level { occupied: 0, slots: vec![Vec::new(); SLOTS] }).collect(),
+            due: Vec::new(),
+            entries: HashMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn insert(&mut self, at: u64, id: Uuid) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.insert(id, Entry { at, seq });
+        self.place(at, seq, id);
+    }
+
+    pub fn remove(&mut self, at: u64, id: &Uuid) -> bool {
+        match self.entries.get(id) {
+            Some(entry) if entry.at == at => self.entries.remove(id).is_some(),
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, at: u64, id: &Uuid) -> bool {
+        self.entries.get(id).is_some_and(|entry| entry.at == at)
+    }
+
+    /// Never later than the earliest deadline; may be earlier, or `Some`
+    /// with only removed entries left in the slots.
+    pub fn next_deadline(&self) -> Option<u64> {
+        if !self.due.is_empty() {
+            return Some(self.elapsed);
+        }
+        self.next_expiration().map(|(_, _, deadline)| deadline)
+    }
+
+    pub fn pop_due(&mut self, now: u64) -> Vec<Uuid> {
+        while let Some((level, slot, deadline)) = self.next_expiration() {
+            if deadline > now {
+                break;
+            }
+            self.elapsed = deadline;
+            let ids = std::mem::take(&mut self.levels[level].slots[slot]);
+            self.levels[level].occupied &= !(1 << slot);
+            // Lower levels are exact; live entries of upper levels move down
+            for (id, seq) in ids {
+                match self.entries.get(&id) {
+                    Some(entry) if entry.seq == seq => {
+                        let at = entry.at;
+                        self.place(at, seq, id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+        // Slots expire in deadline order; entries inserted already due may not
+        let mut due: Vec<(Uuid, Entry)> = Vec::with_capacity(self.due.len());
+        for (id, seq) in std::mem::take(&mut self.due) {
+            if self.entries.get(&id).is_some_and(|entry| entry.seq == seq) {
+                due.push((id, self.entries.remove(&id).unwrap()));
+            }
+        }
+        due.sort_by_key(|(_, entry)| (entry.at, entry.seq));
+        due.into_iter().map(|(id, _)| id).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        let now = self.elapsed;
+        *self = Self::new(now);
+    }
+
+    fn place(&mut self, at: u64, seq: u64, id: Uuid) {
+        if at <= self.elapsed {
+            self.due.push((id, seq));
+            return;
+        }
+        let level = level_for(self.elapsed, at);
+        let slot = ((at >> (level as u32 * SLOT_BITS)) as usize) & (SLOTS - 1);
+        self.levels[level].slots[slot].push((id, seq));
+        self.levels[level].occupied |= 1 << slot;
+    }
+
+    /// Next slot to expire as `(level, slot, deadline)`: the lowest level
+    /// with entries expires first.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        let (level, occupied) = self.levels.iter().enumerate().find(|(_, l)| l.occupied != 0).map(|(i, l)| (i, l.occupied))?;
+        let slot_range = 1u64 << (level as u32 * SLOT_BITS);
+        let level_range = slot_range << SLOT_BITS;
+        let now_slot = self.elapsed / slot_range;
+        let slot = (occupied.rotate_right(now_slot as u32).trailing_zeros() as u64 + now_slot) as usize % SLOTS;
+        let mut deadline = (self.elapsed & !(level_range - 1)) + slot as u64 * slot_range;
+        if deadline <= self.elapsed {
+            // Past the top level: its slots wrap around
+            deadline += level_range;
+        }
+        Some((level, slot, deadline))
+    }
+}
+
+/// Level of an entry due at `at`: the highest 6-bit group where it differs
+/// from `elapsed`.
+fn level_for(elapsed: u64, at: u64) -> usize {
+    let masked = ((elapsed ^ at) | (SLOTS as u64 - 1)).min(MAX_SPAN - 1);
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / SLOT_BITS as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pops at increasing times must release exactly what a sorted index
+    /// does, in the same order.
+    #[test]
+    fn wheel_releases_like_sorted_index() {
+        let start = 1_700_000_000_000u64;
+        let mut wheel = TimerIndex::Sorted(BTreeMap::new());
+        wheel.to_wheel(start);
+        let mut sorted = TimerIndex::default();
+        let mut seed = 42u64;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            seed >> 33
+        };
+
+        let mut ids = Vec::new();
+        for i in 0..5_000 {
+            let delay = match i % 5 {
+                4 => { let (at, id) = (start - next() % 1_000, Uuid::new_v4()); wheel.insert(at, id); sorted.insert(at, id); continue; }
+                0 => next() % 64,
+                1 => next() % 5_000,
+                2 => next() % 600_000,
+                _ => next() % 90_000_000,
+            };
+            let id = Uuid::new_v4();
+            wheel.insert(start + delay, id);
+            sorted.insert(start + delay, id);
+            ids.push((start + delay, id));
+        }
+        for (at, id) in ids.iter().step_by(7) {
+            assert!(wheel.remove(*at, id));
+            assert!(sorted.remove(*at, id));
+        }
+        assert!(!wheel.remove(start, &Uuid::new_v4()));
+        assert_eq!(wheel.len(), sorted.len());
+        assert!(wheel.ordered().eq(sorted.ordered()));
+
+        let mut now = start;
+        while !sorted.is_empty() {
+            now += next() % 2_000_000;
+            assert_eq!(wheel.pop_due(now), sorted.pop_due(now), "release at {}", now - start);
+            assert_eq!(wheel.len(), sorted.len());
+            if let Some(earliest) = sorted.next() {
+                assert!(wheel.next().is_some_and(|deadline| deadline <= earliest));
+            }
+            // Entries added as time goes by land below the ones waiting
+            for _ in 0..50 {
+                let (at, id) = (now + next() % 200_000, Uuid::new_v4());
+                wheel.insert(at, id);
+                sorted.insert(at, id);
+            }
+            if now - start > 200_000_000 {
+                break;
+            }
+        }
+        assert_eq!(wheel.pop_due(u64::MAX / 2), sorted.pop_due(u64::MAX / 2));
+        assert_eq!(wheel.next(), None);
+    }
+
+    #[test]
+    fn wheel_keeps_deadline_order() {
+        let start = 10_000u64;
+        let mut wheel = TimingWheel::new(start);
+        let (a, b, c, d) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        wheel.insert(start + 5, a);
+        wheel.insert(start + 3, b);
+        wheel.insert(start + 3, c);
+        wheel.insert(start - 1, d);
+        assert_eq!(wheel.next_deadline(), Some(start));
+        assert_eq!(wheel.pop_due(start), vec![d]);
+        assert_eq!(wheel.next_deadline(), Some(start + 3));
+        assert_eq!(wheel.pop_due(start + 2), Vec::<Uuid>::new());
+        assert_eq!(wheel.pop_due(start + 10), vec![b, c, a]);
+    }
+}
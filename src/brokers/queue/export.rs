@@ -0,0 +1,187 @@
+//! Portable queue snapshots: a versioned JSON file holding a queue's config,
+//! its pending/scheduled messages and its DLQ, for moving a queue between
+//! environments or archiving it. In-flight messages are written as pending,
+//! so an imported queue redelivers them.
+
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::brokers::queue::blob;
+use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::queue::domain::queue::{current_time_ms, Message, MessageState, QueueConfig};
+use crate::brokers::error::NexoError;
+
+pub const EXPORT_FORMAT: &str = "nexo-queue";
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueExport {
+    pub format: String,
+    pub version: u32,
+    pub queue: String,
+    pub exported_at: u64,
+    pub config: QueueConfig,
+    pub messages: Vec<ExportedMessage>,
+    pub dlq: Vec<ExportedDlqMessage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedMessage {
+    pub id: Uuid,
+    #[serde(with = "hex_payload")]
+    pub payload: Bytes,
+    pub priority: u8,
+    pub attempts: u32,
+    pub created_at: u64,
+    /// 0 = pending; a future timestamp = scheduled (hidden until then)
+    #[serde(default)]
+    pub visible_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedDlqMessage {
+    pub id: Uuid,
+    #[serde(with = "hex_payload")]
+    pub payload: Bytes,
+    pub priority: u8,
+    pub attempts: u32,
+    pub created_at: u64,
+    pub failed_at: u64,
+    pub failure_reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_group: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ExportSummary {
+    pub messages: usize,
+    pub dlq: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ImportSummary {
+    pub messages: usize,
+    pub dlq: usize,
+    /// Messages whose id already exists in the target queue (merge only)
+    pub skipped: usize,
+}
+
+impl QueueExport {
+    pub fn new(queue: &str, config: QueueConfig, messages: Vec<Message>, dlq: Vec<DlqMessage>) -> Self {
+        Self {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            queue: queue.to_string(),
+            exported_at: current_time_ms(),
+            config,
+            messages: messages.into_iter().map(ExportedMessage::from).collect(),
+            dlq: dlq.into_iter().map(ExportedDlqMessage::from).collect(),
+        }
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, NexoError> {
+        let export: Self = serde_json::from_slice(data)
+            .map_err(|e| NexoError::invalid(format!("Invalid queue export: {}", e)))?;
+        if export.format != EXPORT_FORMAT {
+            return Err(NexoError::invalid(format!("Not a queue export (format '{}')", export.format)));
+        }
+        if export.version == 0 || export.version > EXPORT_VERSION {
+            return Err(NexoError::invalid(format!("Unsupported queue export version {}", export.version)));
+        }
+        // Exports inline blobs: a reference here points into some other blob store
+        for payload in export.messages.iter().map(|m| &m.payload).chain(export.dlq.iter().map(|m| &m.payload)) {
+            blob::check_client_payload(payload)?;
+        }
+        Ok(export)
+    }
+}
+
+impl From<Message> for ExportedMessage {
+    fn from(msg: Message) -> Self {
+        // Hidden messages (in flight, or waiting out a nack backoff) are exported
+        // as pending: their consumer does not come along with the file.
+        // Delayed pushes keep their activation time.
+        let visible_at = match msg.state {
+            MessageState::Scheduled(at) => at,
+            _ => 0,
+        };
+        Self {
+            id: msg.id,
+            payload: msg.payload,
+            priority: msg.priority,
+            attempts: msg.attempts,
+            created_at: msg.created_at,
+            visible_at,
+            failure_reason: msg.failure_reason,
+            message_group: msg.message_group,
+        }
+    }
+}
+
+impl From<ExportedMessage> for Message {
+    fn from(msg: ExportedMessage) -> Self {
+        let (state, visible_at) = match msg.visible_at {
+            at if at > current_time_ms() => (MessageState::Scheduled(at), at),
+            _ => (MessageState::Ready, 0),
+        };
+        Self {
+            id: msg.id,
+            payload: msg.payload,
+            priority: msg.priority,
+            attempts: msg.attempts,
+            created_at: msg.created_at,
+            visible_at,
+            failure_reason: msg.failure_reason,
+            state,
+            message_group: msg.message_group,
+        }
+    }
+}
+
+impl From<DlqMessage> for ExportedDlqMessage {
+    fn from(msg: DlqMessage) -> Self {
+        Self {
+            id: msg.id,
+            payload: msg.payload,
+            priority: msg.priority,
+            attempts: msg.attempts,
+            created_at: msg.created_at,
+            failed_at: msg.failed_at,
+            failure_reason: msg.failure_reason,
+            message_group: msg.message_group,
+        }
+    }
+}
+
+impl From<ExportedDlqMessage> for DlqMessage {
+    fn from(msg: ExportedDlqMessage) -> Self {
+        Self {
+            id: msg.id,
+            payload: msg.payload,
+            priority: msg.priority,
+            attempts: msg.attempts,
+            created_at: msg.created_at,
+            failed_at: msg.failed_at,
+            failure_reason: msg.failure_reason,
+            message_group: msg.message_group,
+        }
+    }
+}
+
+mod hex_payload {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(payload: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(payload))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map(Bytes::from).map_err(serde::de::Error::custom)
+    }
+}
@@ -1,19 +1,34 @@
 //! Queue broker HTTP surface: DTOs (with `Serialize`), mapping from domain
 //! snapshots, axum handlers, and the `routes()` sub-router.
 
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
-use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::Router;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
-use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::labels::{LabelSelector, Labels};
+use crate::brokers::queue::archive::{ArchiveStats, ArchivedMessage};
+use crate::brokers::queue::domain::consumers::ConsumerSnapshot;
+use crate::brokers::queue::domain::dlq::{DlqFilter, DlqMessage};
+use crate::brokers::queue::domain::fanout::FanoutGroupSnapshot;
+use crate::brokers::queue::domain::persistence::PersistenceHealth;
 use crate::brokers::queue::domain::queue::QueueConfig;
-use crate::brokers::queue::snapshot::{MessageStateTag, QueueMessagePreview, QueueSnapshot};
-use crate::transport::http::payload::payload_to_json_value;
+use crate::brokers::queue::options::{ImportConflict, ReplayOptions, ReplayTransform};
+use crate::brokers::queue::snapshot::{MessageStateTag, PriorityLaneSnapshot, QueueMessagePreview, QueueSnapshot};
+use crate::transport::http::export_path;
+use crate::brokers::payload::render_payload;
+use crate::transport::tcp::protocol::{ErrorCode, NexoError};
 use crate::NexoEngine;
 
 // ==========================================
@@ -25,8 +40,126 @@ pub struct QueueSummary {
     pub name: String,
     pub pending: usize,
     pub inflight: usize,
+    pub scheduled: usize,
     pub dlq: usize,
+    pub bytes: usize,
+    pub spilled: usize,
+    pub index_drift: u64,
+    pub traced: bool,
     pub config: QueueConfig,
+    pub consumers: Vec<ConsumerSummary>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<FanoutGroupSummary>,
+    pub persistence: PersistenceSummary,
+    pub priorities: Vec<PriorityLaneSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive: Option<ArchiveSummary>,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveSummary {
+    pub records: u64,
+    pub bytes: u64,
+}
+
+impl From<ArchiveStats> for ArchiveSummary {
+    fn from(a: ArchiveStats) -> Self {
+        Self { records: a.records, bytes: a.bytes }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PriorityLaneSummary {
+    pub priority: u8,
+    pub pending: usize,
+    pub inflight: usize,
+    pub scheduled: usize,
+    pub pushed: u64,
+    pub acked: u64,
+    pub push_rate: f64,
+    pub ack_rate: f64,
+}
+
+impl From<PriorityLaneSnapshot> for PriorityLaneSummary {
+    fn from(l: PriorityLaneSnapshot) -> Self {
+        Self {
+            priority: l.priority,
+            pending: l.pending,
+            inflight: l.inflight,
+            scheduled: l.scheduled,
+            pushed: l.pushed,
+            acked: l.acked,
+            push_rate: l.push_rate,
+            ack_rate: l.ack_rate,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PersistenceSummary {
+    pub wal_bytes: u64,
+    pub checkpoint_lag_frames: u64,
+    pub last_checkpoint_at: u64,
+    pub checkpoint_failures: u64,
+}
+
+impl From<PersistenceHealth> for PersistenceSummary {
+    fn from(h: PersistenceHealth) -> Self {
+        Self {
+            wal_bytes: h.wal_bytes,
+            checkpoint_lag_frames: h.checkpoint_lag_frames,
+            last_checkpoint_at: h.last_checkpoint_at,
+            checkpoint_failures: h.checkpoint_failures,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FanoutGroupSummary {
+    pub name: String,
+    pub lag: usize,
+    pub inflight: usize,
+}
+
+impl From<FanoutGroupSnapshot> for FanoutGroupSummary {
+    fn from(g: FanoutGroupSnapshot) -> Self {
+        Self { name: g.name, lag: g.lag, inflight: g.inflight }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ConsumerSummary {
+    pub id: String,
+    pub prefetch: usize,
+    pub push: bool,
+    pub in_flight: usize,
+    pub waiting: usize,
+    pub delivered: u64,
+    pub acked: u64,
+    pub nacked: u64,
+    pub expired: u64,
+    pub ack_rate: f64,
+    pub first_seen: u64,
+    pub last_active: u64,
+}
+
+impl From<ConsumerSnapshot> for ConsumerSummary {
+    fn from(c: ConsumerSnapshot) -> Self {
+        Self {
+            id: c.id,
+            prefetch: c.prefetch,
+            push: c.push,
+            in_flight: c.in_flight,
+            waiting: c.waiting,
+            delivered: c.delivered,
+            acked: c.acked,
+            nacked: c.nacked,
+            expired: c.expired,
+            ack_rate: c.ack_rate,
+            first_seen: c.first_seen,
+            last_active: c.last_active,
+        }
+    }
 }
 
 impl From<QueueSnapshot> for QueueSummary {
@@ -35,8 +168,18 @@ impl From<QueueSnapshot> for QueueSummary {
             name: s.name,
             pending: s.pending,
             inflight: s.inflight,
+            scheduled: s.scheduled,
             dlq: s.dlq,
+            bytes: s.bytes,
+            spilled: s.spilled,
+            index_drift: s.index_drift,
+            traced: s.traced,
             config: s.config,
+            consumers: s.consumers.into_iter().map(Into::into).collect(),
+            groups: s.groups.into_iter().map(Into::into).collect(),
+            persistence: s.persistence.into(),
+            priorities: s.priorities.into_iter().map(Into::into).collect(),
+            archive: s.archive.map(Into::into),
         }
     }
 }
@@ -45,9 +188,11 @@ impl From<QueueSnapshot> for QueueSummary {
 pub struct MessageSummary {
     pub id: Uuid,
     pub payload: Value,
+    pub content_type: &'static str,
     pub state: String,
     pub priority: u8,
     pub attempts: u32,
+    pub created_at: u64,
 }
 
 impl From<QueueMessagePreview> for MessageSummary {
@@ -55,13 +200,17 @@ impl From<QueueMessagePreview> for MessageSummary {
         let state = match m.state {
             MessageStateTag::Pending => "pending".to_string(),
             MessageStateTag::InFlight => "inflight".to_string(),
+            MessageStateTag::Scheduled => "scheduled".to_string(),
         };
+        let (payload, content_type) = render_payload(&m.payload);
         Self {
             id: m.id,
-            payload: payload_to_json_value(&m.payload),
+            payload,
+            content_type,
             state,
             priority: m.priority,
             attempts: m.attempts,
+            created_at: m.created_at,
         }
     }
 }
@@ -70,19 +219,55 @@ impl From<QueueMessagePreview> for MessageSummary {
 pub struct DlqMessageSummary {
     pub id: Uuid,
     pub payload: Value,
+    pub content_type: &'static str,
     pub attempts: u32,
     pub failure_reason: Option<String>,
     pub created_at: u64,
+    pub failed_at: u64,
 }
 
 impl From<DlqMessage> for DlqMessageSummary {
     fn from(m: DlqMessage) -> Self {
+        let (payload, content_type) = render_payload(&m.payload);
         Self {
             id: m.id,
-            payload: payload_to_json_value(&m.payload),
+            payload,
+            content_type,
             attempts: m.attempts,
             failure_reason: Some(m.failure_reason),
             created_at: m.created_at,
+            failed_at: m.failed_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ArchivedMessageSummary {
+    pub id: Uuid,
+    pub seq: u64,
+    pub payload: Value,
+    pub content_type: &'static str,
+    pub priority: u8,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_group: Option<String>,
+    pub created_at: u64,
+    pub acked_at: u64,
+}
+
+impl From<ArchivedMessage> for ArchivedMessageSummary {
+    fn from(m: ArchivedMessage) -> Self {
+        let (payload, content_type) = render_payload(&m.payload);
+        Self {
+            id: m.id,
+            seq: m.seq,
+            payload,
+            content_type,
+            priority: m.priority,
+            attempts: m.attempts,
+            message_group: m.message_group,
+            created_at: m.created_at,
+            acked_at: m.acked_at,
         }
     }
 }
@@ -99,9 +284,81 @@ pub struct PaginatedDlqMessages {
     pub total: usize,
 }
 
+#[derive(Serialize)]
+pub struct PaginatedArchivedMessages {
+    pub messages: Vec<ArchivedMessageSummary>,
+    pub total: usize,
+}
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+/// `path` is relative to `EXPORT_DIR` on the server.
+#[derive(Deserialize)]
+pub struct QueueExportRequest {
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+pub struct QueueImportRequest {
+    pub path: String,
+    #[serde(default)]
+    pub on_conflict: ImportConflict,
+}
+
+/// A `DlqFilter` plus paging (query strings cannot hold a flattened struct).
+#[derive(Deserialize)]
+pub struct DlqPageQuery {
+    pub reason: Option<String>,
+    pub payload: Option<String>,
+    pub search: Option<String>,
+    pub older_than_ms: Option<u64>,
+    pub newer_than_ms: Option<u64>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DlqReplayRequest {
+    pub filter: DlqFilter,
+    pub front: Option<bool>,
+    pub priority: Option<u8>,
+    pub delay_ms: Option<u64>,
+    pub transform: Option<ReplayTransform>,
+}
+
+impl DlqReplayRequest {
+    fn options(&self) -> ReplayOptions {
+        ReplayOptions { front: self.front, priority: self.priority, delay_ms: self.delay_ms, transform: self.transform.clone() }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DlqDeleteRequest {
+    pub filter: DlqFilter,
+}
+
+/// `label` is a selector: `team=payments,env!=prod`
+#[derive(Deserialize)]
+pub struct QueueListQuery {
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct QueueLabelsRequest {
+    pub labels: Labels,
+}
+
 #[derive(Deserialize)]
 pub struct QueueMessagesQuery {
     pub state: String,
+    /// Only messages of this priority level
+    pub priority: Option<u8>,
     pub offset: Option<usize>,
     pub limit: Option<usize>,
     pub search: Option<String>,
@@ -111,10 +368,30 @@ pub struct QueueMessagesQuery {
 // HANDLERS
 // ==========================================
 
-async fn get_queue(State(engine): State<NexoEngine>) -> impl IntoResponse {
+async fn get_queue(State(engine): State<NexoEngine>, Query(query): Query<QueueListQuery>) -> impl IntoResponse {
+    let selector = match LabelSelector::parse(query.label.as_deref().unwrap_or("")) {
+        Ok(selector) => selector,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.message).into_response(),
+    };
     let snap = engine.queue.get_snapshot().await;
-    let dto: Vec<QueueSummary> = snap.into_iter().map(Into::into).collect();
-    axum::Json(dto)
+    let dto: Vec<QueueSummary> = snap.into_iter()
+        .filter(|q| selector.matches(&q.config.labels))
+        .map(Into::into)
+        .collect();
+    axum::Json(dto).into_response()
+}
+
+async fn set_queue_labels(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<QueueLabelsRequest>,
+) -> impl IntoResponse {
+    match engine.queue.set_labels(&name, body.labels.clone()) {
+        Ok(()) => Json(serde_json::json!({ "labels": body.labels })).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) if e.code == ErrorCode::Storage => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
 }
 
 async fn get_queue_messages(
@@ -125,18 +402,22 @@ async fn get_queue_messages(
     let limit = query.limit.unwrap_or(50).min(1000);
     let offset = query.offset.unwrap_or(0);
     let state_filter = query.state.to_lowercase();
-    let search = query.search;
+    let search = query.search.filter(|s| !s.is_empty());
 
     if state_filter == "dlq" {
-        match engine.queue.peek_dlq(&name, limit, offset).await {
+        let page = match &search {
+            Some(search) => engine.queue.search_dlq(&name, search, limit, offset).await,
+            None => engine.queue.peek_dlq(&name, limit, offset).await,
+        };
+        match page {
             Ok((total, dlq_msgs)) => {
                 let messages: Vec<DlqMessageSummary> = dlq_msgs.into_iter().map(Into::into).collect();
                 axum::Json(PaginatedDlqMessages { messages, total }).into_response()
             }
-            Err(e) => (StatusCode::NOT_FOUND, e).into_response(),
+            Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
         }
     } else {
-        match engine.queue.get_messages(name, state_filter, offset, limit, search).await {
+        match engine.queue.get_messages(name, state_filter, query.priority, offset, limit, search).await {
             Some((total, previews)) => {
                 let messages: Vec<MessageSummary> = previews.into_iter().map(Into::into).collect();
                 axum::Json(PaginatedMessages { messages, total }).into_response()
@@ -146,6 +427,198 @@ async fn get_queue_messages(
     }
 }
 
+async fn purge_priority(
+    State(engine): State<NexoEngine>,
+    Path((name, priority)): Path<(String, u8)>,
+) -> impl IntoResponse {
+    match engine.queue.purge_priority(&name, priority).await {
+        Ok(purged) => Json(serde_json::json!({ "purged": purged })).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn unbind_group(
+    State(engine): State<NexoEngine>,
+    Path((name, group)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match engine.queue.unbind_group(&name, &group).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn get_dlq(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Query(query): Query<DlqPageQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).min(1000);
+    let offset = query.offset.unwrap_or(0);
+    let filter = DlqFilter {
+        reason: query.reason,
+        payload: query.payload,
+        search: query.search,
+        older_than_ms: query.older_than_ms,
+        newer_than_ms: query.newer_than_ms,
+    };
+    match engine.queue.peek_dlq_filtered(&name, &filter, limit, offset).await {
+        Ok((total, dlq_msgs)) => {
+            let messages: Vec<DlqMessageSummary> = dlq_msgs.into_iter().map(Into::into).collect();
+            Json(PaginatedDlqMessages { messages, total }).into_response()
+        }
+        Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
+    }
+}
+
+async fn replay_dlq_message(
+    State(engine): State<NexoEngine>,
+    Path((name, id)): Path<(String, Uuid)>,
+    body: Option<Json<DlqReplayRequest>>,
+) -> impl IntoResponse {
+    let options = body.map(|Json(b)| b.options()).unwrap_or_default();
+    match engine.queue.move_to_queue_with(&name, id, &options).await {
+        Ok(replayed) => Json(serde_json::json!({ "replayed": replayed })).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn delete_dlq_message(
+    State(engine): State<NexoEngine>,
+    Path((name, id)): Path<(String, Uuid)>,
+) -> impl IntoResponse {
+    match engine.queue.delete_dlq(&name, id).await {
+        Ok(deleted) => Json(serde_json::json!({ "deleted": deleted })).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
+    }
+}
+
+/// Replay the DLQ messages matching a filter; streams progress (see `progress_stream`).
+async fn replay_dlq(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<DlqReplayRequest>,
+) -> Response {
+    if !engine.queue.exists(&name).await {
+        return (StatusCode::NOT_FOUND, format!("Queue '{}' not found", name)).into_response();
+    }
+    progress_stream(|progress| async move {
+        engine.queue.replay_dlq_matching(&name, &body.filter, &body.options(), progress).await
+    })
+}
+
+/// Delete the DLQ messages matching a filter; streams progress (see `progress_stream`).
+async fn delete_dlq(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<DlqDeleteRequest>,
+) -> Response {
+    if !engine.queue.exists(&name).await {
+        return (StatusCode::NOT_FOUND, format!("Queue '{}' not found", name)).into_response();
+    }
+    progress_stream(|progress| async move {
+        engine.queue.delete_dlq_matching(&name, &body.filter, progress).await
+    })
+}
+
+/// Run a bulk operation in the background and stream its progress as
+/// newline-delimited JSON: `{"done","total"}` after each chunk, then
+/// `{"done","total","finished":true}` or `{"error"}`. The operation runs to
+/// the end even if the client goes away.
+fn progress_stream<F, Fut>(run: F) -> Response
+where
+    F: FnOnce(Box<dyn FnMut(usize, usize) + Send>) -> Fut,
+    Fut: Future<Output = Result<usize, NexoError>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel::<Value>();
+    let total = Arc::new(AtomicUsize::new(0));
+    let progress = {
+        let (tx, total) = (tx.clone(), total.clone());
+        Box::new(move |done: usize, of: usize| {
+            total.store(of, Ordering::Relaxed);
+            let _ = tx.send(serde_json::json!({ "done": done, "total": of }));
+        })
+    };
+    let work = run(progress);
+    tokio::spawn(async move {
+        let last = match work.await {
+            Ok(done) => {
+                let total = total.load(Ordering::Relaxed).max(done);
+                serde_json::json!({ "done": done, "total": total, "finished": true })
+            }
+            Err(e) => serde_json::json!({ "error": e.message }),
+        };
+        let _ = tx.send(last);
+    });
+
+    let lines = futures_util::stream::unfold(rx, |mut rx| async move {
+        let line = rx.recv().await?;
+        Some((Ok::<_, Infallible>(format!("{}\n", line)), rx))
+    });
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response()
+}
+
+async fn get_archive(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).min(1000);
+    match engine.queue.peek_archive(&name, limit, query.offset.unwrap_or(0)).await {
+        Ok((total, archived)) => {
+            let messages: Vec<ArchivedMessageSummary> = archived.into_iter().map(Into::into).collect();
+            Json(PaginatedArchivedMessages { messages, total }).into_response()
+        }
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) if e.code == ErrorCode::InvalidRequest => (StatusCode::BAD_REQUEST, e.message).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+    }
+}
+
+async fn replay_archived(
+    State(engine): State<NexoEngine>,
+    Path((name, id)): Path<(String, Uuid)>,
+) -> impl IntoResponse {
+    match engine.queue.replay_archived(&name, id).await {
+        Ok(replayed) => Json(serde_json::json!({ "replayed": replayed })).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn export_queue(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<QueueExportRequest>,
+) -> impl IntoResponse {
+    let path = match export_path::resolve_for_write(&engine.export_dir, &body.path).await {
+        Ok(path) => path,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match engine.queue.export_queue(&name, &path).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+    }
+}
+
+async fn import_queue(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<QueueImportRequest>,
+) -> impl IntoResponse {
+    let path = match export_path::resolve(&engine.export_dir, &body.path) {
+        Ok(path) => path,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match engine.queue.import_queue(&path, name, body.on_conflict).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
 // ==========================================
 // ROUTES
 // ==========================================
@@ -154,6 +627,18 @@ pub fn routes() -> Router<NexoEngine> {
     Router::new()
         .route("/api/queue", get(get_queue))
         .route("/api/queue/{name}/messages", get(get_queue_messages))
+        .route("/api/queue/{name}/labels", put(set_queue_labels))
+        .route("/api/queue/{name}/priorities/{priority}/purge", post(purge_priority))
+        .route("/api/queue/{name}/groups/{group}", delete(unbind_group))
+        .route("/api/queue/{name}/dlq", get(get_dlq))
+        .route("/api/queue/{name}/dlq/replay", post(replay_dlq))
+        .route("/api/queue/{name}/dlq/delete", post(delete_dlq))
+        .route("/api/queue/{name}/dlq/{id}/replay", post(replay_dlq_message))
+        .route("/api/queue/{name}/dlq/{id}", delete(delete_dlq_message))
+        .route("/api/queue/{name}/archive", get(get_archive))
+        .route("/api/queue/{name}/archive/{id}/replay", post(replay_archived))
+        .route("/api/queue/{name}/export", post(export_queue))
+        .route("/api/queue/{name}/import", post(import_queue))
 }
 
 
@@ -2,23 +2,64 @@
 //! Each queue is an Arc<QueueShared> with a Mutex<QueueInner> for state
 //! and a Notify for long-polling wakeup.
 
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 use std::time::Duration;
 
 use dashmap::DashMap;
-use tokio::sync::Notify;
+use tokio::sync::{mpsc, Notify};
 use tokio::time::{sleep_until, Instant};
 use tokio_util::sync::CancellationToken;
 use bytes::Bytes;
+use serde::Deserialize;
 use uuid::Uuid;
-use tracing::{error, info};
-
-use crate::brokers::queue::domain::queue::{QueueConfig, QueueState, Message, current_time_ms};
-use crate::brokers::queue::options::QueueCreateOptions;
-use crate::brokers::queue::domain::dlq::{DlqMessage, DlqState};
-use crate::brokers::queue::domain::persistence::{QueueStore, StorageOp};
+use tracing::{error, info, warn};
+
+use crate::brokers::activity::{self, ActivityTracker};
+use crate::brokers::clock::{self, SharedClock};
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::memory::{BudgetHandle, MemoryBudget};
+use crate::brokers::events::{EventBus, EventHandle};
+use crate::brokers::hooks::LifecycleEvent;
+use crate::brokers::labels::{self, Labels};
+use crate::brokers::names::{self, NameKind};
+use crate::brokers::pub_sub::PubSubManager;
+use crate::brokers::queue::alerts::DlqAlerts;
+use crate::brokers::queue::archive::{ArchivedMessage, MessageArchive};
+use crate::brokers::queue::blob::{self, BlobStore};
+use crate::brokers::queue::domain::queue::{QueueConfig, QueueState, Message, MessageState, current_time_ms};
+use crate::brokers::queue::domain::consumers::{ConsumerRegistry, Settlement};
+use crate::brokers::queue::domain::fanout::{FanoutState, GroupProgress};
+use crate::brokers::queue::domain::dedup::DedupWindow;
+use crate::brokers::queue::options::{DeadLetterTarget, ImportConflict, QueueCreateOptions, QueuePushOptions, QueueSubscribeOptions, ReplayOptions};
+use crate::brokers::queue::replay;
+use crate::brokers::queue::push::{PushConsumers, QueueDelivery};
+use crate::brokers::queue::export::{ExportSummary, ExportedMessage, ImportSummary, QueueExport};
+use crate::brokers::queue::domain::dlq::{DlqFilter, DlqMessage, DlqState};
+use crate::brokers::queue::domain::backend::BackendKind;
+use crate::brokers::queue::domain::persistence::{QueueStore, StorageOp, StoreOptions};
 use crate::brokers::queue::config::SystemQueueConfig;
-use crate::brokers::queue::snapshot::{QueueMessagePreview, QueueSnapshot};
+use crate::brokers::queue::snapshot::{QueueMessagePreview, QueueSnapshot, ScheduledMessage};
+use crate::brokers::queue::webhook;
+use crate::brokers::schema::{SchemaRegistry, SchemaVersion};
+use crate::brokers::stream::StreamManager;
+use crate::brokers::warm_start::WarmStart;
+use crate::brokers::dead_letter::DeadLetterEnvelope;
+use crate::brokers::message_trace::{MessageTrace, MessageTracer, TraceEventKind};
+use crate::brokers::error::{ErrorCode, NexoError};
+use crate::transport::tcp::protocol::DATA_TYPE_JSON;
+
+/// Longest message group id and idempotency key, in bytes
+const MAX_MESSAGE_GROUP_LEN: usize = 128;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+/// DLQ messages a bulk replay or delete handles per lock
+const DLQ_BULK_CHUNK: usize = 1000;
+/// Longest the precise delay scheduler sleeps, so it notices shutdown
+const SCHEDULER_MAX_PARK: Duration = Duration::from_millis(50);
+/// How often moved fanout group cursors are saved (also on flush and close)
+const FANOUT_SAVE_INTERVAL: Duration = Duration::from_secs(1);
 
 // ==========================================
 // SHARED STATE
@@ -27,7 +68,17 @@ use crate::brokers::queue::snapshot::{QueueMessagePreview, QueueSnapshot};
 struct QueueShared {
     inner: Mutex<QueueInner>,
     notify: Notify,
+    /// A dispatched message was acked, nacked or expired: push windows have room
+    settled: Notify,
     store: QueueStore,
+    /// Acked messages, for queues created with `archive`
+    archive: Option<MessageArchive>,
+    /// Stops the queue's webhook workers on delete/shutdown.
+    cancel: CancellationToken,
+    /// Delayed messages are released by the scheduler thread (`preciseDelays`)
+    precise_delays: bool,
+    /// One config save at a time, taken without `inner` (see `set_labels`)
+    config_save: Mutex<()>,
 }
 
 struct QueueInner {
@@ -35,6 +86,21 @@ struct QueueInner {
     state: QueueState,
     dlq: DlqState,
     config: QueueConfig,
+    index_drift: u64,
+    consumers: ConsumerRegistry,
+    /// Broadcast ring and group cursors (fanout queues); `state` stays empty
+    fanout: Option<FanoutState>,
+    /// Idempotency keys of recent pushes
+    dedup: DedupWindow,
+}
+
+impl QueueInner {
+    /// Keep a dead letter in the DLQ, unless the queue sends them elsewhere.
+    fn park(&mut self, msg: &DlqMessage) {
+        if self.config.dead_letter == DeadLetterTarget::Dlq {
+            self.dlq.push(msg.clone());
+        }
+    }
 }
 
 // ==========================================
@@ -46,10 +112,43 @@ pub struct QueueManager {
     queues: Arc<DashMap<String, Arc<QueueShared>>>,
     config: Arc<SystemQueueConfig>,
     cancel: CancellationToken,
+    http: reqwest::Client,
+    schemas: Arc<SchemaRegistry>,
+    tracer: Arc<MessageTracer>,
+    alerts: Arc<DlqAlerts>,
+    /// Target of `DeadLetterTarget::Stream`, attached like the alerts publisher
+    streams: Arc<OnceLock<Arc<StreamManager>>>,
+    /// Claim-check store for large payloads (`QUEUE_BLOB_PATH`)
+    blobs: Option<Arc<BlobStore>>,
+    activity: Arc<ActivityTracker>,
+    clock: SharedClock,
+    push: Arc<PushConsumers>,
+    /// Engine memory budget, attached like the alerts publisher
+    budget: BudgetHandle,
+    /// Lifecycle events (see `brokers::events`), attached like the budget
+    events: EventHandle,
+    /// Thread releasing the delayed messages of `preciseDelays` queues,
+    /// started with the first of them (`None` if it failed to start)
+    scheduler: Arc<OnceLock<Option<std::thread::Thread>>>,
 }
 
 impl QueueManager {
     pub fn new(system_config: Arc<SystemQueueConfig>) -> Self {
+        Self::with_clock(system_config, clock::system())
+    }
+
+    /// Manager whose visibility timeouts and retry backoff run on `clock` (a `MockClock` in tests).
+    pub fn with_clock(system_config: Arc<SystemQueueConfig>, clock: SharedClock) -> Self {
+        Self::build(system_config, clock, &WarmStart::default())
+    }
+
+    /// Manager on `clock` restoring the queues on disk `warm_start.concurrency`
+    /// at a time, and counting them in `warm_start`.
+    pub fn with_warm_start(system_config: Arc<SystemQueueConfig>, clock: SharedClock, warm_start: &WarmStart) -> Self {
+        Self::build(system_config, clock, warm_start)
+    }
+
+    fn build(system_config: Arc<SystemQueueConfig>, clock: SharedClock, warm_start: &WarmStart) -> Self {
         let queues = Arc::new(DashMap::new());
         let cancel = CancellationToken::new();
 
@@ -59,41 +158,46 @@ impl QueueManager {
             error!("Failed to create queue data directory at {:?}: {}", persistence_path, e);
         }
 
+        let events = EventHandle::default();
         let manager = Self {
             queues: queues.clone(),
             config: system_config.clone(),
             cancel: cancel.clone(),
+            http: reqwest::Client::new(),
+            schemas: Arc::new(SchemaRegistry::new()),
+            tracer: Arc::new(MessageTracer::new("queue", system_config.trace_capacity, clock.clone())),
+            alerts: Arc::new(DlqAlerts::new(events.clone())),
+            streams: Arc::new(OnceLock::new()),
+            blobs: BlobStore::new(&system_config.blob_path, system_config.blob_threshold_bytes).map(Arc::new),
+            activity: Arc::new(ActivityTracker::load(persistence_path.join("activity.json"), clock.clone())),
+            clock,
+            push: Arc::new(PushConsumers::default()),
+            budget: BudgetHandle::default(),
+            events,
+            scheduler: Arc::new(OnceLock::new()),
         };
 
-        // WARM START: Discover and restore queues from filesystem
-        let persistence_path = std::path::PathBuf::from(&system_config.persistence_path);
-        if persistence_path.exists() {
-            if let Ok(entries) = std::fs::read_dir(&persistence_path) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                            if filename.ends_with(".db") && !filename.ends_with(".db-wal") && !filename.ends_with(".db-shm") {
-                                let queue_name = filename.trim_end_matches(".db").to_string();
-
-                                let config_path = persistence_path.join(format!("{}.config.json", queue_name));
-                                let config = if let Ok(data) = std::fs::read_to_string(&config_path) {
-                                    serde_json::from_str(&data).unwrap_or_else(|_| QueueConfig::from_options(QueueCreateOptions::default(), &system_config))
-                                } else {
-                                    QueueConfig::from_options(QueueCreateOptions::default(), &system_config)
-                                };
-
-                                let shared = Self::build_queue(queue_name.clone(), config, &system_config);
-                                queues.insert(queue_name.clone(), shared);
-                                info!("[QueueManager] Warm start: Restored queue '{}'", queue_name);
-                            }
-                        }
+        // WARM START: Discover and restore queues from filesystem, a few at a
+        // time. Recovery reads SQLite synchronously, so it runs on scoped
+        // threads entered into the runtime (writers are tokio tasks).
+        let names = Self::discover_queues(&persistence_path);
+        warm_start.queues.discovered(names.len());
+        let next = AtomicUsize::new(0);
+        let runtime = tokio::runtime::Handle::current();
+        std::thread::scope(|scope| {
+            for _ in 0..warm_start.concurrency.min(names.len()) {
+                scope.spawn(|| {
+                    let _runtime = runtime.enter();
+                    while let Some(queue_name) = names.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let messages = manager.restore_queue(queue_name);
+                        warm_start.queues.recovered(messages);
                     }
-                }
+                });
             }
-        }
+        });
 
         manager.spawn_timeout_task();
+        manager.spawn_compaction_task();
         manager
     }
 
@@ -101,26 +205,89 @@ impl QueueManager {
     // INTERNAL HELPERS
     // ==========================================
 
-    fn build_queue(name: String, config: QueueConfig, system_config: &SystemQueueConfig) -> Arc<QueueShared> {
+    /// Names of the queues with a database or log in `dir`.
+    fn discover_queues(dir: &Path) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else { return Vec::new() };
+        let names: BTreeSet<String> = entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter_map(|path| path.file_name().and_then(|n| n.to_str()).and_then(BackendKind::queue_name).map(str::to_string))
+            .collect();
+        names.into_iter().collect()
+    }
+
+    /// Restore one queue found on disk; returns the messages loaded into memory.
+    fn restore_queue(&self, queue_name: &str) -> usize {
+        let config = if let Ok(data) = std::fs::read_to_string(self.config_path(queue_name)) {
+            serde_json::from_str(&data).unwrap_or_else(|_| QueueConfig::from_options(QueueCreateOptions::default(), &self.config))
+        } else {
+            QueueConfig::from_options(QueueCreateOptions::default(), &self.config)
+        };
+
+        self.schemas.load(queue_name, &self.schema_path(queue_name));
+        let (shared, messages) = self.build_queue(queue_name.to_string(), config);
+        self.spawn_webhook_workers(queue_name, &shared);
+        self.queues.insert(queue_name.to_string(), shared);
+        self.activity.track(queue_name);
+        info!("[QueueManager] Warm start: Restored queue '{}'", queue_name);
+        messages
+    }
+
+    /// Open (or create) a queue's store and recover what it holds. Also
+    /// returns the number of messages recovered into memory.
+    fn build_queue(&self, name: String, config: QueueConfig) -> (Arc<QueueShared>, usize) {
+        let system_config = &self.config;
         let persistence_path = std::path::PathBuf::from(&system_config.persistence_path);
-        let db_path = persistence_path.join(format!("{}.db", name));
-        let store = QueueStore::new(
-            db_path,
-            system_config.default_flush_ms,
-            system_config.writer_batch_size,
-        );
-
-        let mut main_state = QueueState::new();
+        let kind = BackendKind::detect(&persistence_path, &name, system_config.storage_backend);
+        let store = QueueStore::new(kind.path(&persistence_path, &name), kind, StoreOptions {
+            events: self.events.clone(),
+            flush_ms: system_config.default_flush_ms,
+            batch_size: system_config.writer_batch_size,
+            mode: config.persistence,
+            group_commit_ms: system_config.group_commit_ms,
+            tuning: system_config.sqlite_tuning(),
+            drop_flush_timeout_ms: system_config.drop_flush_timeout_ms,
+        });
+
+        let archive = config.archive.map(|retention| {
+            MessageArchive::new(self.archive_dir(&name), retention, system_config.default_flush_ms, self.clock.clone())
+        });
+
+        let mut main_state = QueueState::new(self.clock.clone());
         let mut dlq_state = DlqState::new();
+        let mut fanout = config.fanout.then(|| FanoutState::new(self.clock.clone()));
+        if system_config.search_index {
+            main_state.enable_search_index();
+            dlq_state.enable_search_index();
+        }
+        let precise_delays = config.precise_delays && !config.fanout;
+        if precise_delays || system_config.timing_wheel {
+            main_state.enable_timing_wheel(system_config.timing_wheel);
+        }
+        if precise_delays {
+            self.start_scheduler();
+        }
 
         // Recovery
-        match store.recover() {
-            Ok((main_messages, dlq_messages)) => {
+        let recovered = match store.recover() {
+            Ok((mut main_messages, dlq_messages, spilled)) => {
                 let main_count = main_messages.len();
                 let dlq_count = dlq_messages.len();
 
-                for msg in main_messages {
-                    main_state.push(msg);
+                if let Some(fanout) = fanout.as_mut() {
+                    // Ids are time-ordered: restore push order, which group cursors rely on
+                    main_messages.sort_by_key(|m| m.id);
+                    for msg in main_messages {
+                        fanout.push(msg);
+                    }
+                } else {
+                    for msg in main_messages {
+                        main_state.push(msg);
+                    }
+                    // After the messages in RAM: those ready then join the head
+                    for lane in &spilled {
+                        main_state.restore_spilled(lane.priority, lane.count, lane.bytes, lane.last_seq);
+                    }
                 }
                 for msg in dlq_messages {
                     dlq_state.push(msg);
@@ -129,88 +296,492 @@ impl QueueManager {
                 if main_count > 0 || dlq_count > 0 {
                     info!("Queue '{}': Recovered {} main + {} DLQ messages from storage", name, main_count, dlq_count);
                 }
+                let spilled_count: usize = spilled.iter().map(|lane| lane.count).sum();
+                if spilled_count > 0 {
+                    info!("Queue '{}': {} messages stay paged out on disk", name, spilled_count);
+                }
+                main_count + dlq_count
             }
             Err(e) => {
                 error!("Queue '{}': Persistence recovery failed: {}", name, e);
+                0
+            }
+        };
+        if let Some(fanout) = fanout.as_mut() {
+            // Settled by every group, but saved before the rows were deleted
+            for msg in fanout.restore(self.load_groups(&name)) {
+                store.execute(StorageOp::Delete(msg.id));
             }
         }
 
-        Arc::new(QueueShared {
+        let shared = Arc::new(QueueShared {
             inner: Mutex::new(QueueInner {
                 name,
                 state: main_state,
                 dlq: dlq_state,
                 config,
+                index_drift: 0,
+                consumers: ConsumerRegistry::new(),
+                fanout,
+                dedup: DedupWindow::default(),
             }),
             notify: Notify::new(),
+            settled: Notify::new(),
             store,
-        })
+            archive,
+            cancel: self.cancel.child_token(),
+            precise_delays,
+            config_save: Mutex::new(()),
+        });
+        (shared, recovered)
+    }
+
+    fn spawn_webhook_workers(&self, name: &str, shared: &Arc<QueueShared>) {
+        let webhook = Self::lock(&shared.inner).config.webhook.clone();
+        if let Some(webhook) = webhook {
+            info!("Queue '{}': pushing to webhook {} with {} worker(s)", name, webhook.url, webhook.concurrency);
+            webhook::spawn_workers(self.clone(), name.to_string(), webhook, shared.cancel.clone());
+        }
+    }
+
+    fn config_path(&self, name: &str) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.config.persistence_path).join(format!("{}.config.json", name))
+    }
+
+    /// Written to a temp file then renamed: a crash never leaves half a config.
+    fn save_config(&self, name: &str, config: &QueueConfig) -> std::io::Result<()> {
+        let path = self.config_path(name);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(config)?)?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    fn schema_path(&self, name: &str) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.config.persistence_path).join(format!("{}.schemas.json", name))
+    }
+
+    fn archive_dir(&self, name: &str) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.config.persistence_path).join(format!("{}.archive", name))
+    }
+
+    fn groups_path(&self, name: &str) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.config.persistence_path).join(format!("{}.groups.json", name))
+    }
+
+    /// Fanout groups bound before the last shutdown, with their cursors.
+    fn load_groups(&self, name: &str) -> Vec<GroupProgress> {
+        /// Files written before cursors were saved hold bare group names
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum SavedGroup {
+            Name(String),
+            Progress(GroupProgress),
+        }
+        let groups: Vec<SavedGroup> = std::fs::read(self.groups_path(name)).ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        groups.into_iter()
+            .map(|group| match group {
+                SavedGroup::Name(name) => GroupProgress { name, last_delivered: None, unsettled: Vec::new() },
+                SavedGroup::Progress(progress) => progress,
+            })
+            .collect()
+    }
+
+    /// Save the cursors of a fanout queue's groups, if they moved (or `force`).
+    fn save_groups(&self, name: &str, shared: &QueueShared, force: bool) {
+        let progress = {
+            let mut inner = Self::lock(&shared.inner);
+            match inner.fanout.as_mut() {
+                Some(fanout) if force || fanout.is_dirty() => fanout.progress(),
+                _ => return,
+            }
+        };
+        let path = self.groups_path(name);
+        let tmp = path.with_extension("json.tmp");
+        let written = serde_json::to_vec(&progress).map_err(std::io::Error::from)
+            .and_then(|data| std::fs::write(&tmp, data))
+            .and_then(|()| std::fs::rename(&tmp, &path));
+        if let Err(e) = written {
+            warn!("Queue '{}': failed to persist fanout groups: {}", name, e);
+        }
+    }
+
+    /// Save the group cursors of every fanout queue that moved.
+    fn save_all_groups(&self) {
+        let queues: Vec<(String, Arc<QueueShared>)> = self.queues.iter().map(|q| (q.key().clone(), q.value().clone())).collect();
+        for (name, shared) in queues {
+            self.save_groups(&name, &shared, false);
+        }
+    }
+
+    pub(crate) fn http_client(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    pub(crate) fn system_config(&self) -> &SystemQueueConfig {
+        &self.config
+    }
+
+    /// Publish DLQ alerts through `pubsub` from now on.
+    pub fn attach_alerts(&self, pubsub: Arc<PubSubManager>) {
+        self.alerts.attach(pubsub);
+    }
+
+    /// Publish dead letters of queues targeting a stream topic through `stream`.
+    pub fn attach_streams(&self, stream: Arc<StreamManager>) {
+        let _ = self.streams.set(stream);
+    }
+
+    /// Shed pushes by the engine memory budget from now on.
+    pub fn attach_budget(&self, budget: Arc<MemoryBudget>) {
+        self.budget.attach(budget);
+    }
+
+    /// Post lifecycle events to `bus` from now on.
+    pub fn attach_events(&self, bus: Arc<EventBus>) {
+        self.events.attach(bus);
     }
 
     fn spawn_timeout_task(&self) {
-        let queues = self.queues.clone();
+        let manager = self.clone();
         let cancel = self.cancel.clone();
 
         tokio::spawn(async move {
             let mut timer = tokio::time::interval(Duration::from_millis(50));
             timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut group_saves = tokio::time::interval(FANOUT_SAVE_INTERVAL);
+            group_saves.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
             loop {
                 tokio::select! {
                     _ = cancel.cancelled() => break,
+                    _ = group_saves.tick() => {
+                        manager.save_all_groups();
+                        continue;
+                    }
                     _ = timer.tick() => {}
                 }
+                manager.expire_inflight();
+                manager.refill_all().await;
+            }
+        });
+    }
+
+    /// Requeue or dead-letter in-flight messages whose visibility timeout has
+    /// passed. Runs every 50ms in the background; tests on a `MockClock` call it
+    /// right after advancing the clock.
+    pub fn expire_inflight(&self) {
+        for entry in self.queues.iter() {
+            let shared = entry.value().clone();
+            let now = self.clock.now_ms();
+
+            if Self::lock(&shared.inner).fanout.is_some() {
+                self.expire_fanout(entry.key(), &shared, now);
+                continue;
+            }
+            Self::promote_scheduled(entry.key(), &shared, now);
+
+            let (requeued, dlq_msgs, dlq_depth, threshold) = {
+                let mut inner = Self::lock(&shared.inner);
+
+                // Check if processing is needed
+                let should_process = inner.state.next_inflight_timeout().map(|ts| ts <= now).unwrap_or(false);
+                if !should_process {
+                    continue;
+                }
 
-                for entry in queues.iter() {
-                    let shared = entry.value().clone();
-                    let now = current_time_ms();
+                let max_retries = inner.config.max_retries;
+                let (requeued, dlq_msgs) = inner.state.process_expired(max_retries);
 
-                    let (requeued, dlq_msgs) = {
-                        let mut inner = Self::lock(&shared.inner);
-                        
-                        // Check if processing is needed
-                        let should_process = inner.state.next_inflight_timeout().map(|ts| ts <= now).unwrap_or(false);
-                        if !should_process {
-                            continue;
-                        }
+                for dlq_msg in &dlq_msgs {
+                    inner.park(dlq_msg);
+                }
+                for id in requeued.iter().map(|m| m.id).chain(dlq_msgs.iter().map(|m| m.id)) {
+                    inner.consumers.settled(&id, Settlement::Expired);
+                }
 
-                        let max_retries = inner.config.max_retries;
-                        let (requeued, dlq_msgs) = inner.state.process_expired(max_retries);
+                (requeued, dlq_msgs, inner.dlq.len(), inner.config.dlq_alert_threshold)
+            };
+
+            if requeued.is_empty() && dlq_msgs.is_empty() {
+                continue;
+            }
+            shared.settled.notify_waiters();
+            // A dead-lettered group member lets the next one through
+            let released = dlq_msgs.iter().any(|m| m.message_group.is_some());
+
+            for msg in &requeued {
+                self.tracer.record(entry.key(), msg.id, TraceEventKind::Expired, None);
+                shared.store.execute(StorageOp::UpdateState {
+                    id: msg.id,
+                    visible_at: 0,
+                    attempts: msg.attempts,
+                });
+            }
+
+            self.dead_letter(entry.key(), &shared, dlq_msgs, dlq_depth, threshold, true);
+
+            if !requeued.is_empty() || released {
+                shared.notify.notify_waiters();
+            }
+        }
+    }
+
+    /// Release delayed messages whose activation time has come.
+    fn promote_scheduled(queue_name: &str, shared: &Arc<QueueShared>, now: u64) {
+        let promoted = {
+            let mut inner = Self::lock(&shared.inner);
+            if inner.state.next_scheduled().is_none_or(|ts| ts > now) {
+                return;
+            }
+            inner.state.promote_due()
+        };
+        if promoted.is_empty() {
+            return;
+        }
+
+        for msg in &promoted {
+            shared.store.execute(StorageOp::UpdateState {
+                id: msg.id,
+                visible_at: 0,
+                attempts: msg.attempts,
+            });
+        }
+        tracing::debug!("Queue '{}': {} scheduled messages released", queue_name, promoted.len());
+        shared.notify.notify_waiters();
+    }
 
-                        for ref dlq_msg in &dlq_msgs {
-                            inner.dlq.push((*dlq_msg).clone());
+    /// Start the scheduler thread of `preciseDelays` queues unless it runs.
+    /// It sleeps until the earliest activation among them (at most
+    /// `SCHEDULER_MAX_PARK`) and is woken early when a delayed message is
+    /// added or moved. It stops with the manager.
+    fn start_scheduler(&self) {
+        self.scheduler.get_or_init(|| {
+            let queues = Arc::downgrade(&self.queues);
+            let (clock, cancel) = (self.clock.clone(), self.cancel.clone());
+            let spawned = std::thread::Builder::new().name("nexo-queue-scheduler".to_string()).spawn(move || {
+                while !cancel.is_cancelled() {
+                    let Some(queues) = queues.upgrade() else { break };
+                    let mut next: Option<u64> = None;
+                    for entry in queues.iter().filter(|q| q.value().precise_delays) {
+                        Self::promote_scheduled(entry.key(), entry.value(), clock.now_ms());
+                        if let Some(ts) = Self::lock(&entry.value().inner).state.next_scheduled() {
+                            next = Some(next.map_or(ts, |n| n.min(ts)));
                         }
+                    }
+                    drop(queues);
+                    let wait = next
+                        .map(|ts| Duration::from_micros((ts * 1000).saturating_sub(clock.now_us())))
+                        .map_or(SCHEDULER_MAX_PARK, |wait| wait.min(SCHEDULER_MAX_PARK));
+                    std::thread::park_timeout(wait);
+                }
+            });
+            spawned.map(|handle| handle.thread().clone())
+                // The 50ms pulse still releases the messages
+                .inspect_err(|e| error!("Failed to start the queue scheduler thread: {}", e))
+                .ok()
+        });
+    }
+
+    /// Have the scheduler thread look at the delayed messages again.
+    fn wake_scheduler(&self) {
+        if let Some(Some(scheduler)) = self.scheduler.get() {
+            scheduler.unpark();
+        }
+    }
+
+    fn expire_fanout(&self, queue_name: &str, shared: &Arc<QueueShared>, now: u64) {
+        let (requeued, dlq_msgs, trimmed, dlq_depth, threshold) = {
+            let mut inner = Self::lock(&shared.inner);
+            let max_retries = inner.config.max_retries;
+            let Some(fanout) = inner.fanout.as_mut() else { return };
+            if fanout.next_inflight_timeout().is_none_or(|ts| ts > now) {
+                return;
+            }
+            let (requeued, dlq_msgs, trimmed) = fanout.process_expired(max_retries);
+            for dlq_msg in &dlq_msgs {
+                inner.park(dlq_msg);
+            }
+            (requeued, dlq_msgs, trimmed, inner.dlq.len(), inner.config.dlq_alert_threshold)
+        };
+
+        self.settle_fanout(queue_name, shared, dlq_msgs, trimmed, dlq_depth, threshold);
+        if requeued > 0 {
+            shared.notify.notify_waiters();
+        }
+    }
+
+    /// Persist the outcome of a fanout settlement: dead letters go to the dead
+    /// letter target, messages every group has settled leave the store.
+    fn settle_fanout(&self, queue_name: &str, shared: &QueueShared, dlq_msgs: Vec<DlqMessage>, trimmed: Vec<Message>, dlq_depth: usize, threshold: usize) {
+        self.dead_letter(queue_name, shared, dlq_msgs, dlq_depth, threshold, false);
+        for msg in trimmed {
+            shared.store.execute(StorageOp::Delete(msg.id));
+        }
+    }
+
+    /// Hand messages that ran out of retries to the queue's dead letter target
+    /// (already parked in `inner.dlq` when it is the DLQ). `stored`: they are
+    /// still rows of the main table, not fanout copies.
+    fn dead_letter(&self, queue_name: &str, shared: &QueueShared, dlq_msgs: Vec<DlqMessage>, dlq_depth: usize, threshold: usize, stored: bool) {
+        if dlq_msgs.is_empty() {
+            return;
+        }
+        let target = Self::lock(&shared.inner).config.dead_letter.clone();
+        if target == DeadLetterTarget::Dlq {
+            self.alerts.dead_lettered(queue_name, &dlq_msgs, dlq_depth, threshold);
+        }
+        let traced = self.tracer.is_enabled(queue_name);
+        for dlq_msg in &dlq_msgs {
+            if traced {
+                self.tracer.record(queue_name, dlq_msg.id, TraceEventKind::DeadLettered, Some(dlq_msg.failure_reason.clone()));
+            }
+            // A forwarded message stays stored until the target has it
+            match (&target, stored) {
+                (DeadLetterTarget::Dlq, true) => shared.store.execute(StorageOp::MoveToDLQ { id: dlq_msg.id, msg: dlq_msg.clone() }),
+                (DeadLetterTarget::Dlq, false) => shared.store.execute(StorageOp::InsertDLQ(dlq_msg.clone())),
+                (DeadLetterTarget::Disabled, true) => shared.store.execute(StorageOp::Delete(dlq_msg.id)),
+                _ => {}
+            }
+        }
+        match target {
+            DeadLetterTarget::Dlq => {}
+            DeadLetterTarget::Disabled => {
+                for dlq_msg in &dlq_msgs {
+                    self.release_blob(queue_name, &dlq_msg.payload);
+                }
+            }
+            target => {
+                let manager = self.clone();
+                let queue_name = queue_name.to_string();
+                tokio::spawn(async move { manager.forward_dead_letters(&queue_name, target, dlq_msgs, stored).await });
+            }
+        }
+    }
 
-                        (requeued, dlq_msgs)
-                    };
+    /// Deliver dead letters to another queue or a stream topic, in order, as
+    /// `DeadLetterEnvelope`s. The target is created on first use. A message
+    /// leaves this queue only once the target has it; one the target does
+    /// not take is kept in the queue's own DLQ instead. `stored`: as in
+    /// `dead_letter`.
+    async fn forward_dead_letters(&self, queue_name: &str, target: DeadLetterTarget, dlq_msgs: Vec<DlqMessage>, stored: bool) {
+        let Some(shared) = self.get_queue(queue_name) else { return };
+        let created = match &target {
+            DeadLetterTarget::Queue { name } => self.create_queue(name.clone(), QueueCreateOptions::default()).await,
+            DeadLetterTarget::Stream { topic } => match self.streams.get() {
+                Some(stream) => stream.create_topic(topic.clone(), Default::default()).await,
+                None => Err(NexoError::invalid("No stream broker attached")),
+            },
+            DeadLetterTarget::Dlq | DeadLetterTarget::Disabled => return,
+        };
+        if let Err(e) = &created {
+            error!("Queue '{}': dead letter target {:?} unavailable, keeping {} messages in the DLQ: {}", queue_name, target, dlq_msgs.len(), e.message);
+        }
 
-                    if requeued.is_empty() && dlq_msgs.is_empty() {
+        for dlq_msg in dlq_msgs {
+            if created.is_ok() {
+                match self.send_dead_letter(queue_name, &target, &dlq_msg).await {
+                    Ok(()) => {
+                        if stored {
+                            shared.store.execute(StorageOp::Delete(dlq_msg.id));
+                        }
+                        self.release_blob(queue_name, &dlq_msg.payload);
                         continue;
                     }
+                    Err(e) => error!("Queue '{}': failed to dead-letter message {} to {:?}, keeping it in the DLQ: {}", queue_name, dlq_msg.id, target, e.message),
+                }
+            }
+            let op = match stored {
+                true => StorageOp::MoveToDLQ { id: dlq_msg.id, msg: dlq_msg.clone() },
+                false => StorageOp::InsertDLQ(dlq_msg.clone()),
+            };
+            Self::lock(&shared.inner).dlq.push(dlq_msg);
+            shared.store.execute(op);
+        }
+    }
 
-                    for msg in &requeued {
-                        shared.store.execute(StorageOp::UpdateState {
-                            id: msg.id,
-                            visible_at: 0,
-                            attempts: msg.attempts,
-                        });
-                    }
+    async fn send_dead_letter(&self, queue_name: &str, target: &DeadLetterTarget, dlq_msg: &DlqMessage) -> Result<(), NexoError> {
+        let payload = self.resolve_blob(queue_name, dlq_msg.payload.clone()).await.unwrap_or_else(|_| dlq_msg.payload.clone());
+        let letter = DeadLetterEnvelope {
+            source: queue_name.to_string(),
+            group: None,
+            id: dlq_msg.id.to_string(),
+            priority: dlq_msg.priority,
+            attempts: dlq_msg.attempts,
+            reason: dlq_msg.failure_reason.clone(),
+            created_at: dlq_msg.created_at,
+            failed_at: dlq_msg.failed_at,
+            payload,
+        }.encode();
+
+        match target {
+            DeadLetterTarget::Queue { name } => {
+                let options = QueuePushOptions { priority: Some(dlq_msg.priority), ..Default::default() };
+                self.enqueue(name.clone(), letter, options, false).await
+            }
+            DeadLetterTarget::Stream { topic } => match self.streams.get() {
+                Some(stream) => stream.forward(topic, letter).await.map(|_| ()),
+                None => Err(NexoError::invalid("No stream broker attached")),
+            },
+            DeadLetterTarget::Dlq | DeadLetterTarget::Disabled => Ok(()),
+        }
+    }
 
-                    for dlq_msg in dlq_msgs {
-                        shared.store.execute(StorageOp::MoveToDLQ {
-                            id: dlq_msg.id,
-                            msg: dlq_msg,
-                        });
-                    }
+    fn spawn_compaction_task(&self) {
+        let queues = self.queues.clone();
+        let cancel = self.cancel.clone();
+        let blobs = self.blobs.clone();
+        let interval_ms = self.config.compaction_interval_ms.max(1);
+
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(Duration::from_millis(interval_ms));
+            timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            timer.tick().await; // skip first
 
-                    if !requeued.is_empty() {
-                        shared.notify.notify_waiters();
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = timer.tick() => {}
+                }
+
+                for entry in queues.iter() {
+                    let shared = entry.value().clone();
+                    {
+                        let mut inner = Self::lock(&shared.inner);
+                        let repaired = inner.state.compact();
+                        if repaired > 0 {
+                            inner.index_drift += repaired as u64;
+                            warn!("Queue '{}': compaction repaired {} index entries", inner.name, repaired);
+                        }
+                    }
+                    if let Some(blobs) = &blobs {
+                        Self::sweep_queue_blobs(blobs, entry.key(), &shared, Duration::from_millis(interval_ms));
                     }
                 }
             }
         });
     }
 
+    fn sweep_queue_blobs(blobs: &BlobStore, queue_name: &str, shared: &QueueShared, grace: Duration) -> usize {
+        let removed = blobs.sweep(queue_name, grace, |id| {
+            let inner = Self::lock(&shared.inner);
+            // Paged-out messages are not in RAM to check against: keep every blob meanwhile
+            inner.state.contains(id) || inner.dlq.contains(id) || inner.state.spilled().0 > 0
+        });
+        if removed > 0 {
+            info!("Queue '{}': removed {} orphaned blobs", queue_name, removed);
+        }
+        removed
+    }
+
+    /// Delete the blob behind `payload`, if it is a blob reference.
+    fn release_blob(&self, queue_name: &str, payload: &[u8]) {
+        if let (Some(blobs), Some((id, _))) = (&self.blobs, blob::parse_reference(payload)) {
+            blobs.remove(queue_name, &id);
+        }
+    }
+
     #[inline]
     fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
         mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
@@ -221,8 +792,13 @@ impl QueueManager {
         self.queues.get(name).map(|r| r.value().clone())
     }
 
-    fn persist_batch_state(&self, shared: &Arc<QueueShared>, msgs: &[Message]) {
+    fn persist_batch_state(&self, queue_name: &str, shared: &Arc<QueueShared>, msgs: &[Message]) {
+        tracing::Span::current().record("count", msgs.len());
+        let traced = self.tracer.is_enabled(queue_name);
         for msg in msgs {
+            if traced {
+                self.tracer.record(queue_name, msg.id, TraceEventKind::Dispatched, Some(format!("attempt {}", msg.attempts)));
+            }
             shared.store.execute(StorageOp::UpdateState {
                 id: msg.id,
                 visible_at: msg.visible_at,
@@ -235,158 +811,636 @@ impl QueueManager {
     // PUBLIC API
     // ==========================================
 
-    pub async fn create_queue(&self, name: String, options: QueueCreateOptions) -> Result<(), String> {
+    #[tracing::instrument(name = "queue.create", skip_all, fields(queue = %name))]
+    pub async fn create_queue(&self, name: String, options: QueueCreateOptions) -> Result<(), NexoError> {
+        let config = QueueConfig::from_options(options, &self.config);
+        self.create_with_config(name, config)
+    }
+
+    /// Create the queue unless it already exists (an existing queue keeps its config).
+    fn create_with_config(&self, name: String, config: QueueConfig) -> Result<(), NexoError> {
         use dashmap::mapref::entry::Entry;
 
+        // Before taking the entry: scanning the map while holding it would deadlock
+        if !self.queues.contains_key(&name) {
+            names::validate_new(NameKind::Queue, &name, self.queue_names())?;
+        }
         match self.queues.entry(name.clone()) {
             Entry::Occupied(_) => Ok(()),
             Entry::Vacant(v) => {
-                let config = QueueConfig::from_options(options, &self.config);
-
-                // Persist config
-                let persistence_path = std::path::PathBuf::from(&self.config.persistence_path);
-                let config_path = persistence_path.join(format!("{}.config.json", name));
-                if let Ok(data) = serde_json::to_string_pretty(&config) {
-                    let _ = std::fs::write(&config_path, data);
+                if let Some(webhook) = &config.webhook {
+                    if !(webhook.url.starts_with("http://") || webhook.url.starts_with("https://")) {
+                        return Err(NexoError::invalid(format!("Invalid webhook URL: {}", webhook.url)));
+                    }
+                    if config.fanout {
+                        return Err(NexoError::invalid("A fanout queue cannot push to a webhook"));
+                    }
+                }
+                if config.archive.is_some() && config.fanout {
+                    return Err(NexoError::invalid("A fanout queue cannot archive acked messages"));
+                }
+                if config.precise_delays && config.fanout {
+                    return Err(NexoError::invalid("A fanout queue cannot delay messages"));
                 }
+                match &config.dead_letter {
+                    DeadLetterTarget::Queue { name: target } if *target == name => {
+                        return Err(NexoError::invalid(format!("Invalid dead letter queue: '{}'", target)));
+                    }
+                    DeadLetterTarget::Queue { name: target } => names::validate(NameKind::Queue, target)?,
+                    DeadLetterTarget::Stream { topic } => names::validate(NameKind::StreamTopic, topic)?,
+                    _ => {}
+                }
+
+                labels::validate(&config.labels)?;
+
+                let _ = self.save_config(&name, &config);
 
-                let shared = Self::build_queue(name, config, &self.config);
+                self.schemas.load(&name, &self.schema_path(&name));
+                let (shared, _) = self.build_queue(name.clone(), config);
+                self.spawn_webhook_workers(&name, &shared);
                 v.insert(shared);
+                self.activity.track(&name);
+                self.events.post(LifecycleEvent::QueueCreated { queue: name });
                 Ok(())
             }
         }
     }
 
-    pub async fn delete_queue(&self, name: String) -> Result<(), String> {
+    #[tracing::instrument(name = "queue.delete", skip_all, fields(queue = %name))]
+    pub async fn delete_queue(&self, name: String) -> Result<(), NexoError> {
+        self.remove_queue(name, None).await
+    }
+
+    /// Remove the queue, moving its files (database, config, schemas, blobs) into the directory `target`.
+    #[tracing::instrument(name = "queue.archive", skip_all, fields(queue = %name))]
+    pub async fn archive_queue(&self, name: String, target: &Path) -> Result<(), NexoError> {
+        self.remove_queue(name, Some(target)).await
+    }
+
+    async fn remove_queue(&self, name: String, archive_to: Option<&Path>) -> Result<(), NexoError> {
         if let Some((_, shared)) = self.queues.remove(&name) {
+            shared.cancel.cancel();
             shared.store.shutdown().await;
+            if let Some(archive) = &shared.archive {
+                archive.shutdown().await;
+            }
+            self.events.post(LifecycleEvent::QueueDeleted { queue: name.clone() });
         }
+        self.schemas.remove(&name);
+        self.tracer.forget(&name);
+        self.activity.forget(&name);
 
         // Delete Persistence (safe: writer has flushed and closed)
         let base_path = std::path::PathBuf::from(&self.config.persistence_path);
-        let db_path = base_path.join(format!("{}.db", name));
-        let wal_path = base_path.join(format!("{}.db-wal", name));
-        let shm_path = base_path.join(format!("{}.db-shm", name));
-        let config_path = base_path.join(format!("{}.config.json", name));
+        let mut files: Vec<std::path::PathBuf> = BackendKind::ALL.iter().flat_map(|kind| kind.files(&base_path, &name)).collect();
+        files.extend([self.config_path(&name), self.schema_path(&name), self.groups_path(&name)]);
+
+        if let Some(target) = archive_to {
+            let mut sources: Vec<(std::path::PathBuf, std::path::PathBuf)> = files.into_iter()
+                .filter(|f| f.exists())
+                .map(|f| { let to = target.join(f.file_name().unwrap_or_default()); (f, to) })
+                .collect();
+            if let Some(blobs) = self.blobs.as_ref().map(|b| b.queue_dir(&name)).filter(|d| d.exists()) {
+                sources.push((blobs, target.join("blobs")));
+            }
+            let archive_dir = self.archive_dir(&name);
+            if archive_dir.exists() {
+                let to = target.join(archive_dir.file_name().unwrap_or_default());
+                sources.push((archive_dir, to));
+            }
+            for (source, to) in sources {
+                activity::move_to_archive(&source, &to).map_err(|e| NexoError::new(
+                    ErrorCode::Storage, format!("Failed to archive {} to {}: {}", source.display(), to.display(), e),
+                ))?;
+            }
+            return Ok(());
+        }
 
-        let _ = std::fs::remove_file(db_path);
-        let _ = std::fs::remove_file(wal_path);
-        let _ = std::fs::remove_file(shm_path);
-        let _ = std::fs::remove_file(config_path);
+        for file in files {
+            let _ = std::fs::remove_file(file);
+        }
+        let _ = std::fs::remove_dir_all(self.archive_dir(&name));
+        if let Some(blobs) = &self.blobs {
+            blobs.remove_queue(&name);
+        }
 
         Ok(())
     }
 
-    pub async fn push(&self, queue_name: String, payload: Bytes, priority: u8) -> Result<(), String> {
-        let shared = self.get_queue(&queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found. Create it first.", queue_name))?;
-
-        let msg = Message::new(payload, priority);
-        {
-            let mut inner = Self::lock(&shared.inner);
-            inner.state.push(msg.clone());
+    /// Commit every write issued so far and fsync the queue databases,
+    /// whatever each queue's persistence mode.
+    pub async fn flush(&self) -> Result<(), NexoError> {
+        let queues: Vec<(String, Arc<QueueShared>)> = self.queues.iter().map(|q| (q.key().clone(), q.value().clone())).collect();
+        self.save_all_groups();
+        for (name, shared) in queues {
+            shared.store.flush().await
+                .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to flush queue '{}': {}", name, e)))?;
+            if let Some(archive) = &shared.archive {
+                archive.flush().await
+                    .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to flush archive of queue '{}': {}", name, e)))?;
+            }
         }
-
-        shared.store.execute(StorageOp::Insert(msg));
-        shared.notify.notify_waiters();
-
         Ok(())
     }
 
-    pub async fn pop(&self, queue_name: &str) -> Option<Message> {
-        let shared = self.get_queue(queue_name)?;
+    /// Stop the background tasks and shut every queue writer down once it
+    /// has drained its channel and fsynced. The manager is unusable afterwards.
+    pub async fn close(&self) {
+        self.cancel.cancel();
+        self.wake_scheduler();
+        self.save_all_groups();
+        let queues: Vec<Arc<QueueShared>> = self.queues.iter().map(|q| q.value().clone()).collect();
+        for shared in queues {
+            shared.cancel.cancel();
+            shared.store.shutdown().await;
+            if let Some(archive) = &shared.archive {
+                archive.shutdown().await;
+            }
+        }
+        self.activity.save();
+    }
 
-        let (msg_opt, _) = {
-            let mut inner = Self::lock(&shared.inner);
-            let vt = inner.config.visibility_timeout_ms;
-            inner.state.pop(vt)
-        };
+    /// Last publish/consume per queue.
+    pub fn activity(&self) -> &ActivityTracker {
+        &self.activity
+    }
 
-        if let Some(msg) = &msg_opt {
-            shared.store.execute(StorageOp::UpdateState {
-                id: msg.id,
-                visible_at: msg.visible_at,
-                attempts: msg.attempts,
-            });
-        }
+    pub fn queue_names(&self) -> Vec<String> {
+        self.queues.iter().map(|q| q.key().clone()).collect()
+    }
 
-        msg_opt
+    /// Queues whose persistence writer is no longer running.
+    pub fn stopped_writers(&self) -> Vec<String> {
+        self.queues.iter().filter(|q| !q.store.is_running()).map(|q| q.key().clone()).collect()
     }
 
-    pub async fn ack(&self, queue_name: &str, id: Uuid) -> bool {
-        let shared = match self.get_queue(queue_name) {
-            Some(s) => s,
-            None => return false,
-        };
+    /// Write the queue's config, messages and DLQ to a portable file at `path`.
+    #[tracing::instrument(name = "queue.export", skip_all, fields(queue = %queue_name))]
+    pub async fn export_queue(&self, queue_name: &str, path: &Path) -> Result<ExportSummary, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
 
-        let result = {
+        let (export, loads) = {
             let mut inner = Self::lock(&shared.inner);
-            inner.state.ack(id)
+            // Overflow to disk first: paged-out messages come right after the RAM ones
+            self.page_out(&mut inner, &shared.store);
+            let loads: Vec<_> = inner.state.spilled_lanes().into_iter()
+                .map(|(priority, cursor)| shared.store.load_spilled(priority, cursor, usize::MAX))
+                .collect();
+            let dlq = inner.dlq.peek_all().into_iter().cloned().collect();
+            let messages = match &inner.fanout {
+                Some(fanout) => fanout.all_messages(),
+                None => inner.state.all_messages(),
+            };
+            (QueueExport::new(queue_name, inner.config.clone(), messages, dlq), loads)
         };
-
-        if result {
-            shared.store.execute(StorageOp::Delete(id));
+        let mut export = export;
+        for load in loads {
+            let chunk = load.await.unwrap_or_else(|_| Err("queue writer stopped".to_string()))
+                .map_err(|e| NexoError::new(ErrorCode::Storage, e))?;
+            export.messages.extend(chunk.into_iter().map(|(msg, _)| ExportedMessage::from(msg)));
+        }
+        // Blobs are inlined: the file must not point into this server's blob store
+        for msg in &mut export.messages {
+            msg.payload = self.resolve_blob(queue_name, msg.payload.clone()).await?;
+        }
+        for msg in &mut export.dlq {
+            msg.payload = self.resolve_blob(queue_name, msg.payload.clone()).await?;
         }
+        let summary = ExportSummary { messages: export.messages.len(), dlq: export.dlq.len() };
+
+        let data = serde_json::to_vec_pretty(&export)
+            .map_err(|e| NexoError::new(ErrorCode::Internal, format!("Failed to serialize queue: {}", e)))?;
+        let tmp = path.with_extension("tmp");
+        let written = match tokio::fs::write(&tmp, data).await {
+            Ok(()) => tokio::fs::rename(&tmp, path).await,
+            Err(e) => Err(e),
+        };
+        written.map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to write {}: {}", path.display(), e)))?;
 
-        result
+        info!("Queue '{}': exported {} messages + {} DLQ to {}", queue_name, summary.messages, summary.dlq, path.display());
+        Ok(summary)
     }
 
-    pub async fn nack(&self, queue_name: &str, id: Uuid, reason: String) -> bool {
-        let shared = match self.get_queue(queue_name) {
+    /// Restore a file written by `export_queue` into `queue_name`.
+    #[tracing::instrument(name = "queue.import", skip_all, fields(queue = %queue_name))]
+    pub async fn import_queue(&self, path: &Path, queue_name: String, on_conflict: ImportConflict) -> Result<ImportSummary, NexoError> {
+        let data = tokio::fs::read(path).await
+            .map_err(|e| NexoError::invalid(format!("Cannot read {}: {}", path.display(), e)))?;
+        let export = QueueExport::parse(&data)?;
+
+        if self.get_queue(&queue_name).is_some() {
+            match on_conflict {
+                ImportConflict::Fail => return Err(NexoError::invalid(format!("Queue '{}' already exists", queue_name))),
+                ImportConflict::Replace => self.delete_queue(queue_name.clone()).await?,
+                ImportConflict::Merge => {}
+            }
+        }
+        self.create_with_config(queue_name.clone(), export.config)?;
+        let shared = self.get_queue(&queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+
+        let mut summary = ImportSummary::default();
+        let mut ops = Vec::new();
+        {
+            let mut inner = Self::lock(&shared.inner);
+            for msg in export.messages {
+                let in_fanout = inner.fanout.as_ref().is_some_and(|f| f.contains(&msg.id));
+                if inner.state.contains(&msg.id) || inner.dlq.contains(&msg.id) || in_fanout {
+                    summary.skipped += 1;
+                    continue;
+                }
+                let msg = Message::from(msg);
+                match inner.fanout.as_mut() {
+                    Some(fanout) => fanout.push(msg.clone()),
+                    None => inner.state.push(msg.clone()),
+                }
+                ops.push(StorageOp::Insert(msg));
+                summary.messages += 1;
+            }
+            for msg in export.dlq {
+                if inner.state.contains(&msg.id) || inner.dlq.contains(&msg.id) {
+                    summary.skipped += 1;
+                    continue;
+                }
+                let msg = DlqMessage::from(msg);
+                inner.dlq.push(msg.clone());
+                ops.push(StorageOp::InsertDLQ(msg));
+                summary.dlq += 1;
+            }
+        }
+
+        let traced = self.tracer.is_enabled(&queue_name);
+        for op in ops {
+            if let (StorageOp::Insert(msg), true) = (&op, traced) {
+                self.tracer.record(&queue_name, msg.id, TraceEventKind::Pushed, Some("imported".to_string()));
+            }
+            shared.store.execute(op);
+        }
+        shared.notify.notify_waiters();
+
+        info!("Queue '{}': imported {} messages + {} DLQ from {} ({} skipped)", queue_name, summary.messages, summary.dlq, path.display(), summary.skipped);
+        Ok(summary)
+    }
+
+    pub async fn push(&self, queue_name: String, payload: Bytes, priority: u8) -> Result<(), NexoError> {
+        self.push_delayed(queue_name, payload, priority, 0).await
+    }
+
+    /// Push a message that stays scheduled (invisible to consumers) for `delay_ms`.
+    pub async fn push_delayed(&self, queue_name: String, payload: Bytes, priority: u8, delay_ms: u64) -> Result<(), NexoError> {
+        let options = QueuePushOptions { priority: Some(priority), delay_ms: Some(delay_ms), ..Default::default() };
+        self.push_with(queue_name, payload, options).await
+    }
+
+    /// Push with every option. Messages sharing a `message_group` are delivered
+    /// one at a time, in push order: the next only once the previous is acked
+    /// or dead-lettered. Different groups are consumed in parallel.
+    pub async fn push_with(&self, queue_name: String, payload: Bytes, options: QueuePushOptions) -> Result<(), NexoError> {
+        self.enqueue(queue_name, payload, options, true).await
+    }
+
+    /// Push; with `shed` the memory budget may refuse it (dead letters moved
+    /// here from another queue are not shed: they already took their memory).
+    #[tracing::instrument(name = "queue.push", skip_all, fields(queue = %queue_name, priority = options.priority.unwrap_or(0), message_id = tracing::field::Empty))]
+    async fn enqueue(&self, queue_name: String, payload: Bytes, options: QueuePushOptions, shed: bool) -> Result<(), NexoError> {
+        let shared = self.get_queue(&queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found. Create it first.", queue_name)))?;
+        blob::check_client_payload(&payload)?;
+        self.schemas.validate(&queue_name, &payload)?;
+        let priority = options.priority.unwrap_or(0);
+        let delay_ms = options.delay_ms.unwrap_or(0);
+
+        let mut msg = Message::new(payload, priority);
+        tracing::Span::current().record("message_id", tracing::field::display(msg.id));
+        let (persistence, fanout) = {
+            let inner = Self::lock(&shared.inner);
+            (inner.config.persistence, inner.fanout.is_some())
+        };
+        if shed {
+            self.budget.admit(persistence == PersistenceMode::FileSync)?;
+        }
+        if let Some(group) = options.message_group {
+            if fanout {
+                return Err(NexoError::invalid(format!("Queue '{}' is a fanout queue: message groups are not supported", queue_name)));
+            }
+            if group.is_empty() || group.len() > MAX_MESSAGE_GROUP_LEN {
+                return Err(NexoError::invalid(format!("Message group must be 1 to {} bytes", MAX_MESSAGE_GROUP_LEN)));
+            }
+            msg.message_group = Some(group);
+        }
+        if delay_ms > 0 {
+            if fanout {
+                return Err(NexoError::invalid(format!("Queue '{}' is a fanout queue: delayed pushes are not supported", queue_name)));
+            }
+            let at = self.clock.now_ms() + delay_ms;
+            msg.visible_at = at;
+            msg.state = MessageState::Scheduled(at);
+        }
+
+        // Duplicate of a push still in the dedup window: accepted, not enqueued
+        let key = options.idempotency_key;
+        if let Some(key) = &key {
+            if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+                return Err(NexoError::invalid(format!("Idempotency key must be 1 to {} bytes", MAX_IDEMPOTENCY_KEY_LEN)));
+            }
+            let now = self.clock.now_ms();
+            if !Self::lock(&shared.inner).dedup.insert(key, now, self.config.dedup_window_ms) {
+                tracing::debug!("Queue '{}': dropped duplicate push with key '{}'", queue_name, key);
+                return Ok(());
+            }
+        }
+
+        let stored: Result<(), NexoError> = async {
+            // Claim check: the payload goes to the blob store, the message carries a reference.
+            // Fanout messages stay inline: their DLQ copies outlive the ring entry.
+            if let Some(blobs) = self.blobs.as_ref().filter(|b| !fanout && b.should_offload(&msg.payload)) {
+                let sync = persistence == PersistenceMode::FileSync;
+                msg.payload = blobs.put(&queue_name, &msg.id, msg.payload.clone(), sync).await
+                    .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to write blob: {}", e)))?;
+            }
+
+            // FileSync: the message becomes visible only once it is on disk
+            if persistence == PersistenceMode::FileSync {
+                shared.store.commit(StorageOp::Insert(msg.clone())).await
+                    .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to persist message: {}", e)))?;
+            }
+            Ok(())
+        }.await;
+        if let Err(e) = stored {
+            // Let the producer retry with the same key
+            if let Some(key) = &key {
+                Self::lock(&shared.inner).dedup.remove(key);
+            }
+            return Err(e);
+        }
+        {
+            let mut inner = Self::lock(&shared.inner);
+            match inner.fanout.as_mut() {
+                Some(fanout) => fanout.push(msg.clone()),
+                None => {
+                    inner.state.push(msg.clone());
+                    inner.state.record_push(priority);
+                    self.page_out(&mut inner, &shared.store);
+                }
+            }
+        }
+        if self.tracer.is_enabled(&queue_name) {
+            self.tracer.record(&queue_name, msg.id, TraceEventKind::Pushed, Some(format!("priority {}", priority)));
+        }
+        self.activity.published(&queue_name);
+
+        if persistence == PersistenceMode::FileAsync {
+            shared.store.execute(StorageOp::Insert(msg));
+        }
+        shared.notify.notify_waiters();
+        if delay_ms > 0 && shared.precise_delays {
+            self.wake_scheduler();
+        }
+
+        Ok(())
+    }
+
+    /// Scheduled messages activating in `from_ts..=to_ts`, earliest first.
+    pub fn list_scheduled(&self, queue_name: &str, from_ts: u64, to_ts: u64, limit: usize) -> Result<Vec<ScheduledMessage>, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let inner = Self::lock(&shared.inner);
+        Ok(inner.state.list_scheduled(from_ts, to_ts, limit).into_iter()
+            .map(|m| ScheduledMessage {
+                id: m.id,
+                payload: m.payload,
+                priority: m.priority,
+                scheduled_at: m.visible_at,
+                created_at: m.created_at,
+            })
+            .collect())
+    }
+
+    /// Move a scheduled message to `new_ts`; a time already passed releases it
+    /// right away. Returns false if the message is not scheduled.
+    pub fn reschedule(&self, queue_name: &str, id: Uuid, new_ts: u64) -> Result<bool, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let Some(msg) = Self::lock(&shared.inner).state.reschedule(id, new_ts) else {
+            return Ok(false);
+        };
+
+        shared.store.execute(StorageOp::UpdateState {
+            id: msg.id,
+            visible_at: msg.visible_at,
+            attempts: msg.attempts,
+        });
+        if msg.state == MessageState::Ready {
+            shared.notify.notify_waiters();
+        } else if shared.precise_delays {
+            self.wake_scheduler();
+        }
+        Ok(true)
+    }
+
+    /// Attach a new schema version to the queue; later pushes must match it.
+    pub fn set_schema(&self, queue_name: &str, schema: serde_json::Value) -> Result<u32, NexoError> {
+        if self.get_queue(queue_name).is_none() {
+            return Err(NexoError::not_found(format!("Queue '{}' not found", queue_name)));
+        }
+        self.schemas.register(queue_name, schema, &self.schema_path(queue_name))
+    }
+
+    pub fn get_schema(&self, queue_name: &str, version: Option<u32>) -> Option<SchemaVersion> {
+        self.schemas.get(queue_name, version)
+    }
+
+    /// Replace the queue's labels, persisted with its config.
+    pub fn set_labels(&self, queue_name: &str, labels: Labels) -> Result<(), NexoError> {
+        labels::validate(&labels)?;
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        // The file is written without the queue locked; saves go one at a
+        // time so the file and the config end up with the same labels
+        let _save = Self::lock(&shared.config_save);
+        let mut config = Self::lock(&shared.inner).config.clone();
+        config.labels = labels;
+        self.save_config(queue_name, &config)
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to persist labels: {}", e)))?;
+        Self::lock(&shared.inner).config.labels = config.labels;
+        Ok(())
+    }
+
+    /// Toggle message tracing (debug) for the queue.
+    pub fn set_tracing(&self, queue_name: &str, enabled: bool) -> Result<(), NexoError> {
+        if self.get_queue(queue_name).is_none() {
+            return Err(NexoError::not_found(format!("Queue '{}' not found", queue_name)));
+        }
+        self.tracer.set_enabled(queue_name, enabled);
+        Ok(())
+    }
+
+    /// Recorded lifecycle of a traced message.
+    pub fn trace(&self, message_id: &str) -> Option<MessageTrace> {
+        self.tracer.get(message_id)
+    }
+
+    #[tracing::instrument(name = "queue.pop", skip_all, fields(queue = %queue_name, message_id = tracing::field::Empty))]
+    pub async fn pop(&self, queue_name: &str) -> Option<Message> {
+        let shared = self.get_queue(queue_name)?;
+        self.activity.consumed(queue_name);
+        self.refill(queue_name, &shared).await;
+
+        let (msg_opt, _) = {
+            let mut inner = Self::lock(&shared.inner);
+            let vt = inner.config.visibility_timeout_ms;
+            inner.state.pop(vt)
+        };
+
+        if let Some(msg) = &msg_opt {
+            tracing::Span::current().record("message_id", tracing::field::display(msg.id));
+            if self.tracer.is_enabled(queue_name) {
+                self.tracer.record(queue_name, msg.id, TraceEventKind::Dispatched, Some(format!("attempt {}", msg.attempts)));
+            }
+            shared.store.execute(StorageOp::UpdateState {
+                id: msg.id,
+                visible_at: msg.visible_at,
+                attempts: msg.attempts,
+            });
+        }
+
+        msg_opt
+    }
+
+    #[tracing::instrument(name = "queue.ack", skip_all, fields(queue = %queue_name, message_id = %id))]
+    pub async fn ack(&self, queue_name: &str, id: Uuid) -> bool {
+        let shared = match self.get_queue(queue_name) {
             Some(s) => s,
             None => return false,
         };
 
-        let (requeued, dlq_msg) = {
+        let (result, payload, grouped, archived) = {
+            let mut inner = Self::lock(&shared.inner);
+            let (payload, grouped) = inner.state.get(&id)
+                .map_or((None, false), |m| (Some(m.payload.clone()), m.message_group.is_some()));
+            let archived = shared.archive.as_ref().and(inner.state.get(&id)).cloned();
+            let acked = inner.state.ack(id);
+            if acked {
+                inner.consumers.settled(&id, Settlement::Acked);
+            }
+            (acked, payload, grouped, archived)
+        };
+
+        if result {
+            shared.settled.notify_waiters();
+            // The next message of the group is ready now
+            if grouped {
+                shared.notify.notify_waiters();
+            }
+            self.tracer.record(queue_name, id, TraceEventKind::Acked, None);
+            shared.store.execute(StorageOp::Delete(id));
+            if let (Some(archive), Some(msg)) = (&shared.archive, archived) {
+                // Before the blob goes: the archive keeps the payload itself
+                let payload = self.resolve_blob(queue_name, msg.payload.clone()).await.unwrap_or_else(|_| msg.payload.clone());
+                archive.append(ArchivedMessage::acked(&msg, payload, self.clock.now_ms()));
+            }
+            if let Some(payload) = payload {
+                self.release_blob(queue_name, &payload);
+            }
+        }
+
+        result
+    }
+
+    pub async fn nack(&self, queue_name: &str, id: Uuid, reason: String) -> bool {
+        self.nack_after(queue_name, id, reason, 0).await
+    }
+
+    /// Nack with a retry backoff: a requeued message stays hidden for `delay_ms`.
+    #[tracing::instrument(name = "queue.nack", skip_all, fields(queue = %queue_name, message_id = %id))]
+    pub async fn nack_after(&self, queue_name: &str, id: Uuid, reason: String, delay_ms: u64) -> bool {
+        let shared = match self.get_queue(queue_name) {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let (requeued, dlq_msg, dlq_depth, threshold) = {
             let mut inner = Self::lock(&shared.inner);
             let max_retries = inner.config.max_retries;
-            let (requeued, dlq_msg) = inner.state.nack(id, reason, max_retries);
+            let (requeued, dlq_msg) = inner.state.nack_after(id, reason.clone(), max_retries, delay_ms);
 
             if let Some(ref dlq_message) = dlq_msg {
-                inner.dlq.push(dlq_message.clone());
+                inner.park(dlq_message);
+            }
+            if requeued.is_some() || dlq_msg.is_some() {
+                inner.consumers.settled(&id, Settlement::Nacked);
             }
 
-            (requeued, dlq_msg)
+            (requeued, dlq_msg, inner.dlq.len(), inner.config.dlq_alert_threshold)
         };
 
+        if requeued.is_some() || dlq_msg.is_some() {
+            shared.settled.notify_waiters();
+            self.tracer.record(queue_name, id, TraceEventKind::Nacked, Some(reason));
+        }
+
         if let Some(msg) = requeued {
             shared.store.execute(StorageOp::UpdateState {
                 id: msg.id,
                 visible_at: msg.visible_at,
                 attempts: msg.attempts,
             });
-            shared.notify.notify_waiters();
+            if delay_ms == 0 {
+                shared.notify.notify_waiters();
+            }
             return true;
         }
 
         if let Some(dlq_message) = dlq_msg {
-            shared.store.execute(StorageOp::MoveToDLQ {
-                id: dlq_message.id,
-                msg: dlq_message,
-            });
+            let released = dlq_message.message_group.is_some();
+            self.dead_letter(queue_name, &shared, vec![dlq_message], dlq_depth, threshold, true);
+            if released {
+                shared.notify.notify_waiters();
+            }
             return true;
         }
 
         false
     }
 
-    pub async fn consume_batch(&self, queue_name: String, max: Option<usize>, wait_ms: Option<u64>) -> Result<Vec<Message>, String> {
+    pub async fn consume_batch(&self, queue_name: String, max: Option<usize>, wait_ms: Option<u64>) -> Result<Vec<Message>, NexoError> {
+        self.consume_batch_with_quotas(queue_name, max, wait_ms, None).await
+    }
+
+    /// Batch consume where `quotas` caps each priority's share of the batch (see `QueueState::take_batch`).
+    pub async fn consume_batch_with_quotas(&self, queue_name: String, max: Option<usize>, wait_ms: Option<u64>, quotas: Option<&HashMap<u8, f64>>) -> Result<Vec<Message>, NexoError> {
+        self.consume_batch_as(queue_name, max, wait_ms, quotas, None, &CancellationToken::new()).await
+    }
+
+    /// Batch consume on behalf of `consumer` (the connection id), tracked in the consumer stats.
+    /// Once `cancel` fires (the client went away) a long poll ends early, and
+    /// messages taken for the request go back to Ready without an attempt.
+    #[tracing::instrument(name = "queue.consume", skip_all, fields(queue = %queue_name, count = tracing::field::Empty))]
+    pub async fn consume_batch_as(&self, queue_name: String, max: Option<usize>, wait_ms: Option<u64>, quotas: Option<&HashMap<u8, f64>>, consumer: Option<&str>, cancel: &CancellationToken) -> Result<Vec<Message>, NexoError> {
         let shared = self.get_queue(&queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found. Create it first.", queue_name))?;
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found. Create it first.", queue_name)))?;
+
+        Self::check_quotas(quotas)?;
+        if Self::lock(&shared.inner).fanout.is_some() {
+            return Err(NexoError::invalid(format!("Queue '{}' is a fanout queue: consume with a group", queue_name)));
+        }
+        self.activity.consumed(&queue_name);
 
         let max_val = max.unwrap_or(self.config.default_batch_size);
         let wait_val = wait_ms.unwrap_or(self.config.default_wait_ms);
+        self.refill(&queue_name, &shared).await;
 
         // Try immediate fetch
         let msgs = {
             let mut inner = Self::lock(&shared.inner);
-            let vt = inner.config.visibility_timeout_ms;
-            let (msgs, _) = inner.state.take_batch(max_val, vt);
-            msgs
+            if let Some(consumer) = consumer {
+                inner.consumers.requested(consumer, max_val);
+            }
+            Self::take_for(&mut inner, max_val, quotas, consumer)
         };
         if !msgs.is_empty() {
-            self.persist_batch_state(&shared, &msgs);
-            return Ok(msgs);
+            return Ok(self.deliver_batch(&queue_name, &shared, msgs, cancel));
         }
 
         // No messages and no wait -> return empty
@@ -396,31 +1450,369 @@ impl QueueManager {
 
         // Long polling loop (like stream fetch)
         let deadline = Instant::now() + Duration::from_millis(wait_val);
+        if let Some(consumer) = consumer {
+            Self::lock(&shared.inner).consumers.set_waiting(consumer, true);
+        }
 
-        loop {
+        let msgs = loop {
             let notified = shared.notify.notified();
+            self.refill(&queue_name, &shared).await;
 
             // Try fetch under lock
+            let msgs = Self::take_for(&mut Self::lock(&shared.inner), max_val, quotas, consumer);
+            if !msgs.is_empty() || Instant::now() >= deadline {
+                break msgs;
+            }
+
+            tokio::select! {
+                _ = notified => {}
+                _ = sleep_until(deadline) => break vec![],
+                _ = cancel.cancelled() => break vec![],
+            }
+        };
+
+        if let Some(consumer) = consumer {
+            Self::lock(&shared.inner).consumers.set_waiting(consumer, false);
+        }
+        if msgs.is_empty() {
+            return Ok(msgs);
+        }
+        Ok(self.deliver_batch(&queue_name, &shared, msgs, cancel))
+    }
+
+    /// Persist a taken batch, or, if its request was cancelled meanwhile,
+    /// put it back: nobody is left to receive it.
+    fn deliver_batch(&self, queue_name: &str, shared: &Arc<QueueShared>, msgs: Vec<Message>, cancel: &CancellationToken) -> Vec<Message> {
+        if !cancel.is_cancelled() {
+            self.persist_batch_state(queue_name, shared, &msgs);
+            return msgs;
+        }
+        {
+            let mut inner = Self::lock(&shared.inner);
+            for msg in &msgs {
+                if inner.state.requeue_inflight(msg.id) {
+                    inner.consumers.settled(&msg.id, Settlement::Returned);
+                }
+            }
+        }
+        shared.notify.notify_waiters();
+        tracing::debug!("Queue '{}': {} messages returned, consume cancelled", queue_name, msgs.len());
+        Vec::new()
+    }
+
+    /// Batch consume for `group` on a fanout queue. The first consume binds
+    /// the group; from then on it receives every message, with its own
+    /// visibility timeouts and retries.
+    pub async fn consume_group(&self, queue_name: &str, group: &str, max: Option<usize>, wait_ms: Option<u64>) -> Result<Vec<Message>, NexoError> {
+        self.consume_group_cancellable(queue_name, group, max, wait_ms, &CancellationToken::new()).await
+    }
+
+    /// `consume_group` for a request `cancel` aborts, as in `consume_batch_as`.
+    #[tracing::instrument(name = "queue.consume_group", skip_all, fields(queue = %queue_name, group = %group, count = tracing::field::Empty))]
+    pub async fn consume_group_cancellable(&self, queue_name: &str, group: &str, max: Option<usize>, wait_ms: Option<u64>, cancel: &CancellationToken) -> Result<Vec<Message>, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found. Create it first.", queue_name)))?;
+        if group.is_empty() {
+            return Err(NexoError::invalid("Consumer group is required"));
+        }
+        self.activity.consumed(queue_name);
+
+        let max_val = max.unwrap_or(self.config.default_batch_size);
+        let wait_val = wait_ms.unwrap_or(self.config.default_wait_ms);
+
+        let bound = {
+            let mut inner = Self::lock(&shared.inner);
+            let Some(fanout) = inner.fanout.as_mut() else {
+                return Err(NexoError::invalid(format!("Queue '{}' is not a fanout queue", queue_name)));
+            };
+            fanout.bind(group)
+        };
+        if bound {
+            info!("Queue '{}': bound fanout group '{}'", queue_name, group);
+            self.save_groups(queue_name, &shared, true);
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(wait_val);
+        let msgs = loop {
+            let notified = shared.notify.notified();
+
             let msgs = {
                 let mut inner = Self::lock(&shared.inner);
                 let vt = inner.config.visibility_timeout_ms;
-                let (msgs, _) = inner.state.take_batch(max_val, vt);
-                msgs
+                inner.fanout.as_mut().map(|f| f.take_batch(group, max_val, vt)).unwrap_or_default()
+            };
+            if !msgs.is_empty() || wait_val == 0 || Instant::now() >= deadline {
+                break msgs;
+            }
+
+            tokio::select! {
+                _ = notified => {}
+                _ = sleep_until(deadline) => break vec![],
+                _ = cancel.cancelled() => break vec![],
+            }
+        };
+
+        if cancel.is_cancelled() && !msgs.is_empty() {
+            {
+                let mut inner = Self::lock(&shared.inner);
+                if let Some(fanout) = inner.fanout.as_mut() {
+                    for msg in &msgs {
+                        fanout.requeue(group, msg.id);
+                    }
+                }
+            }
+            shared.notify.notify_waiters();
+            return Ok(Vec::new());
+        }
+
+        tracing::Span::current().record("count", msgs.len());
+        if self.tracer.is_enabled(queue_name) {
+            for msg in &msgs {
+                self.tracer.record(queue_name, msg.id, TraceEventKind::Dispatched, Some(format!("{} (attempt {})", group, msg.attempts)));
+            }
+        }
+        Ok(msgs)
+    }
+
+    /// Unbind `group` from a fanout queue: it stops holding messages back,
+    /// and those every remaining group has settled leave the queue. Consuming
+    /// with the group again binds it anew, at the oldest retained message.
+    pub async fn unbind_group(&self, queue_name: &str, group: &str) -> Result<(), NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let trimmed = {
+            let mut inner = Self::lock(&shared.inner);
+            let Some(fanout) = inner.fanout.as_mut() else {
+                return Err(NexoError::invalid(format!("Queue '{}' is not a fanout queue", queue_name)));
+            };
+            fanout.unbind(group)
+                .ok_or_else(|| NexoError::not_found(format!("Group '{}' is not bound to queue '{}'", group, queue_name)))?
+        };
+        info!("Queue '{}': unbound fanout group '{}' ({} messages released)", queue_name, group, trimmed.len());
+        self.settle_fanout(queue_name, &shared, Vec::new(), trimmed, 0, 0);
+        self.save_groups(queue_name, &shared, true);
+        Ok(())
+    }
+
+    /// Ack a message for one fanout group. It leaves the queue once every group acked it.
+    #[tracing::instrument(name = "queue.ack_group", skip_all, fields(queue = %queue_name, group = %group, message_id = %id))]
+    pub async fn ack_group(&self, queue_name: &str, group: &str, id: Uuid) -> bool {
+        let Some(shared) = self.get_queue(queue_name) else { return false };
+        let trimmed = {
+            let mut inner = Self::lock(&shared.inner);
+            inner.fanout.as_mut().and_then(|f| f.ack(group, id))
+        };
+        let Some(trimmed) = trimmed else { return false };
+
+        if self.tracer.is_enabled(queue_name) {
+            self.tracer.record(queue_name, id, TraceEventKind::Acked, Some(group.to_string()));
+        }
+        self.settle_fanout(queue_name, &shared, Vec::new(), trimmed, 0, 0);
+        true
+    }
+
+    /// Nack a message for one fanout group: retried for that group only, and
+    /// dead-lettered (as a copy) once the group runs out of retries.
+    #[tracing::instrument(name = "queue.nack_group", skip_all, fields(queue = %queue_name, group = %group, message_id = %id))]
+    pub async fn nack_group(&self, queue_name: &str, group: &str, id: Uuid, reason: String) -> bool {
+        let Some(shared) = self.get_queue(queue_name) else { return false };
+        let outcome = {
+            let mut inner = Self::lock(&shared.inner);
+            let max_retries = inner.config.max_retries;
+            let outcome = inner.fanout.as_mut().and_then(|f| f.nack(group, id, reason.clone(), max_retries));
+            if let Some((Some(dlq_msg), _)) = &outcome {
+                inner.park(dlq_msg);
+            }
+            outcome.map(|(dlq_msg, trimmed)| (dlq_msg, trimmed, inner.dlq.len(), inner.config.dlq_alert_threshold))
+        };
+        let Some((dlq_msg, trimmed, dlq_depth, threshold)) = outcome else { return false };
+
+        if self.tracer.is_enabled(queue_name) {
+            self.tracer.record(queue_name, id, TraceEventKind::Nacked, Some(format!("{}: {}", group, reason)));
+        }
+        let requeued = dlq_msg.is_none();
+        self.settle_fanout(queue_name, &shared, dlq_msg.into_iter().collect(), trimmed, dlq_depth, threshold);
+        if requeued {
+            shared.notify.notify_waiters();
+        }
+        true
+    }
+
+    fn check_quotas(quotas: Option<&HashMap<u8, f64>>) -> Result<(), NexoError> {
+        match quotas.into_iter().flatten().find(|(_, share)| !(0.0..=1.0).contains(*share)) {
+            Some((priority, share)) => Err(NexoError::invalid(format!("Invalid quota {} for priority {}: must be between 0 and 1", share, priority))),
+            None => Ok(()),
+        }
+    }
+
+    /// Attach the channel to a network connection, for push subscriptions.
+    pub fn connect(&self, client_id: &str, sink: mpsc::UnboundedSender<QueueDelivery>) {
+        self.push.connect(client_id, sink);
+    }
+
+    /// Push the queue's messages to `client_id` as they become Ready, up to
+    /// `prefetch` unsettled at a time (see `push`). Replaces the connection's
+    /// previous subscription to the queue.
+    #[tracing::instrument(name = "queue.subscribe", skip_all, fields(queue = %queue_name))]
+    pub fn subscribe(&self, queue_name: &str, client_id: &str, options: QueueSubscribeOptions) -> Result<(), NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found. Create it first.", queue_name)))?;
+        Self::check_quotas(options.priority_quotas.as_ref())?;
+        let prefetch = options.prefetch.unwrap_or(self.config.default_batch_size);
+        if prefetch == 0 {
+            return Err(NexoError::invalid("Prefetch must be at least 1"));
+        }
+        {
+            let mut inner = Self::lock(&shared.inner);
+            if inner.fanout.is_some() {
+                return Err(NexoError::invalid(format!("Queue '{}' is a fanout queue: consume with a group", queue_name)));
+            }
+            inner.consumers.set_push(client_id, Some(prefetch));
+        }
+
+        let idle_ttl = options.idle_ttl_ms.filter(|ms| *ms > 0).map(Duration::from_millis);
+        self.push.subscribe(self.clone(), queue_name.to_string(), client_id, prefetch, options.priority_quotas, idle_ttl)?;
+        info!("Queue '{}': push consumer {} subscribed (prefetch {})", queue_name, client_id, prefetch);
+        Ok(())
+    }
+
+    /// Stop pushing the queue to `client_id`. Messages already delivered stay
+    /// in flight until settled. Returns false if it wasn't subscribed.
+    pub fn unsubscribe(&self, queue_name: &str, client_id: &str) -> bool {
+        if let Some(shared) = self.get_queue(queue_name) {
+            Self::lock(&shared.inner).consumers.set_push(client_id, None);
+        }
+        self.push.unsubscribe(queue_name, client_id)
+    }
+
+    /// How long the push consumer has held deliveries without settling any.
+    pub(crate) fn push_stalled_for(&self, queue_name: &str, consumer: &str) -> Option<Duration> {
+        let shared = self.get_queue(queue_name)?;
+        let since = Self::lock(&shared.inner).consumers.stalled_since(consumer)?;
+        Some(Duration::from_millis(current_time_ms().saturating_sub(since)))
+    }
+
+    /// Drop the push subscription `cancel` stops, its idle TTL being over.
+    pub(crate) fn expire_push(&self, queue_name: &str, consumer: &str, cancel: &CancellationToken, stalled: Duration) {
+        let reason = format!("no ack or nack for {}ms", stalled.as_millis());
+        if !self.push.expire(queue_name, consumer, cancel, reason) {
+            return;
+        }
+        if let Some(shared) = self.get_queue(queue_name) {
+            Self::lock(&shared.inner).consumers.set_push(consumer, None);
+        }
+        warn!("Queue '{}': push consumer {} unsubscribed, idle for {}ms", queue_name, consumer, stalled.as_millis());
+    }
+
+    /// Wait until `consumer` has room in its push window and messages are
+    /// Ready, then dispatch as many as fit. Fails once the queue is deleted.
+    pub(crate) async fn take_push_batch(&self, queue_name: &str, consumer: &str, prefetch: usize, quotas: Option<&HashMap<u8, f64>>) -> Result<Vec<Message>, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        loop {
+            let ready = shared.notify.notified();
+            let settled = shared.settled.notified();
+            self.refill(queue_name, &shared).await;
+
+            let msgs = {
+                let mut inner = Self::lock(&shared.inner);
+                match prefetch.saturating_sub(inner.consumers.in_flight(consumer)) {
+                    0 => Vec::new(),
+                    room => Self::take_for(&mut inner, room, quotas, Some(consumer)),
+                }
             };
             if !msgs.is_empty() {
-                self.persist_batch_state(&shared, &msgs);
+                self.activity.consumed(queue_name);
+                self.persist_batch_state(queue_name, &shared, &msgs);
                 return Ok(msgs);
             }
 
-            if Instant::now() >= deadline {
-                return Ok(vec![]);
+            tokio::select! {
+                _ = ready => {}
+                _ = settled => {}
+                _ = shared.cancel.cancelled() => return Err(NexoError::not_found(format!("Queue '{}' was deleted", queue_name))),
             }
+        }
+    }
 
-            tokio::select! {
-                _ = notified => {}
-                _ = sleep_until(deadline) => return Ok(vec![]),
+    fn take_for(inner: &mut QueueInner, max: usize, quotas: Option<&HashMap<u8, f64>>, consumer: Option<&str>) -> Vec<Message> {
+        let vt = inner.config.visibility_timeout_ms;
+        let (msgs, _) = inner.state.take_batch(max, vt, quotas);
+        if let (Some(consumer), false) = (consumer, msgs.is_empty()) {
+            inner.consumers.dispatched(consumer, msgs.iter().map(|m| m.id));
+        }
+        msgs
+    }
+
+    /// Page ready messages beyond the head of each priority out to the store
+    /// (`QUEUE_SPILL_THRESHOLD`). Runs under the queue lock, so the spill
+    /// reaches the writer ahead of any later op on those messages.
+    fn page_out(&self, inner: &mut QueueInner, store: &QueueStore) {
+        if self.config.spill_threshold == 0 || inner.fanout.is_some() {
+            return;
+        }
+        for (msg, seq) in inner.state.page_out(self.config.spill_threshold, self.config.spill_chunk.max(1)) {
+            store.execute(StorageOp::Spill { msg, seq });
+        }
+    }
+
+    /// Load the next chunk of paged-out messages of every priority whose
+    /// head ran below half a chunk, and wake the consumers waiting for them.
+    async fn refill(&self, queue_name: &str, shared: &QueueShared) {
+        let chunk = self.config.spill_chunk.max(1);
+        let loads: Vec<_> = {
+            let mut inner = Self::lock(&shared.inner);
+            inner.state.begin_reload(chunk.div_ceil(2)).into_iter()
+                .map(|(priority, cursor)| (priority, cursor, shared.store.load_spilled(priority, cursor, chunk)))
+                .collect()
+        };
+        if loads.is_empty() {
+            return;
+        }
+        for (priority, cursor, load) in loads {
+            let loaded = load.await.unwrap_or_else(|_| Err("queue writer stopped".to_string()));
+            let mut inner = Self::lock(&shared.inner);
+            match loaded {
+                Ok(messages) => {
+                    inner.state.end_reload(priority, cursor, messages);
+                }
+                Err(e) => {
+                    error!("Queue '{}': failed to load paged-out messages: {}", queue_name, e);
+                    inner.state.abort_reload(priority);
+                }
             }
         }
+        shared.notify.notify_waiters();
+    }
+
+    /// `refill` every queue: keeps push consumers and webhooks fed.
+    async fn refill_all(&self) {
+        let queues: Vec<(String, Arc<QueueShared>)> = self.queues.iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+        for (name, shared) in queues {
+            self.refill(&name, &shared).await;
+        }
+    }
+
+    /// Drop the consumer stats of a closed connection from every queue.
+    pub fn disconnect(&self, client_id: &str) {
+        self.push.disconnect(client_id);
+        for entry in self.queues.iter() {
+            Self::lock(&entry.value().inner).consumers.remove(client_id);
+        }
+    }
+
+    /// Payload bytes held in RAM: pending, in-flight and scheduled messages
+    /// (or the fanout ring) plus DLQs.
+    pub fn memory_usage(&self) -> u64 {
+        self.queues.iter()
+            .map(|entry| {
+                let inner = Self::lock(&entry.value().inner);
+                let registry = inner.fanout.as_ref().map_or(inner.state.bytes(), FanoutState::bytes);
+                (registry + inner.dlq.bytes()) as u64
+            })
+            .sum()
     }
 
     pub async fn get_snapshot(&self) -> Vec<QueueSnapshot> {
@@ -428,100 +1820,354 @@ impl QueueManager {
 
         for entry in self.queues.iter() {
             let shared = entry.value().clone();
-            let inner = Self::lock(&shared.inner);
-            let (pending, inflight) = inner.state.get_counters();
+            let mut inner = Self::lock(&shared.inner);
+            let (pending, inflight, bytes, groups) = match &inner.fanout {
+                Some(fanout) => (fanout.len(), fanout.inflight(), fanout.bytes(), fanout.snapshot()),
+                None => {
+                    let (pending, inflight) = inner.state.get_counters();
+                    (pending, inflight, inner.state.bytes(), Vec::new())
+                }
+            };
+            let scheduled = inner.state.scheduled_count();
+            let (spilled, _) = inner.state.spilled();
+            let priorities = inner.state.priority_lanes();
             queues.push(QueueSnapshot {
                 name: inner.name.clone(),
                 pending,
                 inflight,
+                scheduled,
                 dlq: inner.dlq.len(),
+                bytes,
+                spilled,
+                index_drift: inner.index_drift,
+                traced: self.tracer.is_enabled(&inner.name),
                 config: inner.config.clone(),
+                consumers: inner.consumers.snapshot(),
+                groups,
+                persistence: shared.store.health(),
+                priorities,
+                archive: shared.archive.as_ref().map(MessageArchive::stats),
             });
         }
 
         queues
     }
 
-    pub async fn get_messages(&self, queue_name: String, state_filter: String, offset: usize, limit: usize, search: Option<String>) -> Option<(usize, Vec<QueueMessagePreview>)> {
+    pub async fn get_messages(&self, queue_name: String, state_filter: String, priority: Option<u8>, offset: usize, limit: usize, search: Option<String>) -> Option<(usize, Vec<QueueMessagePreview>)> {
         let shared = self.get_queue(&queue_name)?;
         let inner = Self::lock(&shared.inner);
-        Some(inner.state.get_messages(state_filter, offset, limit, search))
+        Some(inner.state.get_messages(state_filter, priority, offset, limit, search))
+    }
+
+    /// Run a registry/index consistency pass on demand.
+    /// Returns the number of repaired entries.
+    pub async fn compact(&self, queue_name: &str) -> Result<usize, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+
+        let mut inner = Self::lock(&shared.inner);
+        let repaired = inner.state.compact();
+        inner.index_drift += repaired as u64;
+        Ok(repaired)
     }
 
     pub async fn exists(&self, name: &str) -> bool {
         self.queues.contains_key(name)
     }
 
+    /// Drop the pending and scheduled messages of one priority level.
+    /// In-flight messages are left to their consumers.
+    #[tracing::instrument(name = "queue.purge_priority", skip_all, fields(queue = %queue_name, priority))]
+    pub async fn purge_priority(&self, queue_name: &str, priority: u8) -> Result<usize, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+
+        let removed = {
+            let mut inner = Self::lock(&shared.inner);
+            if inner.fanout.is_some() {
+                return Err(NexoError::invalid(format!("Queue '{}' is a fanout queue: priority levels cannot be purged", queue_name)));
+            }
+            let removed = inner.state.purge_priority(priority);
+            // Blobs of paged-out messages are left to the orphan sweep
+            let spilled = inner.state.drop_spilled(priority).map_or(0, |(after_seq, count)| {
+                shared.store.execute(StorageOp::DeleteSpilled { priority, after_seq });
+                count
+            });
+            (removed, spilled)
+        };
+        let (removed, spilled) = removed;
+
+        for msg in &removed {
+            self.tracer.record(queue_name, msg.id, TraceEventKind::Discarded, None);
+            shared.store.execute(StorageOp::Delete(msg.id));
+            self.release_blob(queue_name, &msg.payload);
+        }
+        Ok(removed.len() + spilled)
+    }
+
     // --- DLQ Operations ---
 
     /// Peek messages from DLQ without consuming them
-    pub async fn peek_dlq(&self, queue_name: &str, limit: usize, offset: usize) -> Result<(usize, Vec<DlqMessage>), String> {
+    pub async fn peek_dlq(&self, queue_name: &str, limit: usize, offset: usize) -> Result<(usize, Vec<DlqMessage>), NexoError> {
         let shared = self.get_queue(queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found", queue_name))?;
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
 
         let inner = Self::lock(&shared.inner);
         Ok(inner.dlq.peek(offset, limit))
     }
 
+    /// `peek_dlq` over the messages matching `search` (see `DlqState::matching`).
+    pub async fn search_dlq(&self, queue_name: &str, search: &str, limit: usize, offset: usize) -> Result<(usize, Vec<DlqMessage>), NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+
+        let inner = Self::lock(&shared.inner);
+        Ok(inner.dlq.search(search, offset, limit))
+    }
+
+    /// `peek_dlq` over the messages matching `filter`.
+    pub async fn peek_dlq_filtered(&self, queue_name: &str, filter: &DlqFilter, limit: usize, offset: usize) -> Result<(usize, Vec<DlqMessage>), NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+
+        let inner = Self::lock(&shared.inner);
+        Ok(inner.dlq.peek_filtered(filter, current_time_ms(), offset, limit))
+    }
+
+    /// Replay the DLQ messages matching `filter`, in failure order, one chunk
+    /// per lock so the queue keeps serving meanwhile. `progress(done, total)`
+    /// runs after each chunk. Returns the count.
+    #[tracing::instrument(name = "queue.dlq.replay_matching", skip_all, fields(queue = %queue_name))]
+    pub async fn replay_dlq_matching(&self, queue_name: &str, filter: &DlqFilter, options: &ReplayOptions, mut progress: impl FnMut(usize, usize)) -> Result<usize, NexoError> {
+        let ids = self.select_dlq(queue_name, filter)?;
+        let mut chunks: Vec<&[Uuid]> = ids.chunks(DLQ_BULK_CHUNK).collect();
+        // Each chunk goes ahead of the previous one: last chunk first
+        if options.front.unwrap_or(false) {
+            chunks.reverse();
+        }
+        let mut done = 0;
+        for chunk in chunks {
+            done += self.replay(queue_name, |_| chunk.to_vec(), options).await?;
+            progress(done, ids.len());
+            tokio::task::yield_now().await;
+        }
+        Ok(done)
+    }
+
+    /// Delete the DLQ messages matching `filter`, like `replay_dlq_matching`.
+    /// An empty filter purges the DLQ at once.
+    #[tracing::instrument(name = "queue.dlq.delete_matching", skip_all, fields(queue = %queue_name))]
+    pub async fn delete_dlq_matching(&self, queue_name: &str, filter: &DlqFilter, mut progress: impl FnMut(usize, usize)) -> Result<usize, NexoError> {
+        if filter.is_empty() {
+            let purged = self.purge_dlq(queue_name).await?;
+            progress(purged, purged);
+            return Ok(purged);
+        }
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let ids = self.select_dlq(queue_name, filter)?;
+        let mut done = 0;
+        for chunk in ids.chunks(DLQ_BULK_CHUNK) {
+            done += self.discard_dlq(queue_name, &shared, chunk);
+            progress(done, ids.len());
+            tokio::task::yield_now().await;
+        }
+        Ok(done)
+    }
+
+    fn select_dlq(&self, queue_name: &str, filter: &DlqFilter) -> Result<Vec<Uuid>, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let inner = Self::lock(&shared.inner);
+        Ok(inner.dlq.filtered(filter, current_time_ms()))
+    }
+
     /// Move a message from DLQ back to main queue (replay/retry)
-    pub async fn move_to_queue(&self, queue_name: &str, message_id: Uuid) -> Result<bool, String> {
+    pub async fn move_to_queue(&self, queue_name: &str, message_id: Uuid) -> Result<bool, NexoError> {
+        self.move_to_queue_with(queue_name, message_id, &ReplayOptions::default()).await
+    }
+
+    /// Replay a DLQ message at the front of its priority, with a new priority or after a delay.
+    #[tracing::instrument(name = "queue.dlq.replay", skip_all, fields(queue = %queue_name, message_id = %message_id))]
+    pub async fn move_to_queue_with(&self, queue_name: &str, message_id: Uuid, options: &ReplayOptions) -> Result<bool, NexoError> {
+        self.replay(queue_name, |_| vec![message_id], options).await.map(|count| count > 0)
+    }
+
+    /// Replay every DLQ message whose payload or failure reason contains
+    /// `search` (all of them without one), in failure order. Returns the count.
+    #[tracing::instrument(name = "queue.dlq.replay_all", skip_all, fields(queue = %queue_name))]
+    pub async fn move_all_to_queue(&self, queue_name: &str, search: Option<&str>, options: &ReplayOptions) -> Result<usize, NexoError> {
+        self.replay(queue_name, |dlq| dlq.matching(search), options).await
+    }
+
+    async fn replay(&self, queue_name: &str, select: impl FnOnce(&DlqState) -> Vec<Uuid>, options: &ReplayOptions) -> Result<usize, NexoError> {
         let shared = self.get_queue(queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found", queue_name))?;
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let front = options.front.unwrap_or(false);
+        let delay_ms = options.delay_ms.unwrap_or(0);
+        if front && delay_ms > 0 {
+            return Err(NexoError::invalid("A delayed replay cannot be placed at the front"));
+        }
 
-        let new_msg = {
+        let replayed = {
             let mut inner = Self::lock(&shared.inner);
-            if let Some(dlq_msg) = inner.dlq.remove(&message_id) {
-                let new_msg = dlq_msg.clone().to_message();
-                inner.state.push(new_msg.clone());
-                Some(new_msg)
-            } else {
-                None
+            if inner.fanout.is_some() {
+                return Err(NexoError::invalid("DLQ replay is not supported on fanout queues: push the payload again"));
+            }
+            let mut ids = select(&inner.dlq);
+            // Pushed to the front one by one: last first, so they keep their order
+            if front {
+                ids.reverse();
             }
+            let now = self.clock.now_ms();
+            let mut replayed = Vec::with_capacity(ids.len());
+            for id in ids {
+                let Some(dlq_msg) = inner.dlq.remove(&id) else { continue };
+                let payload = options.transform.as_ref().map(|transform| replay::apply(transform, &dlq_msg, now));
+                let mut msg = dlq_msg.to_message();
+                if let Some(payload) = payload {
+                    msg.payload = payload;
+                }
+                if let Some(priority) = options.priority {
+                    msg.priority = priority;
+                }
+                if delay_ms > 0 {
+                    msg.visible_at = now + delay_ms;
+                    msg.state = MessageState::Scheduled(msg.visible_at);
+                }
+                if front {
+                    inner.state.push_front(msg.clone());
+                } else {
+                    inner.state.push(msg.clone());
+                }
+                replayed.push(msg);
+            }
+            replayed
         };
 
-        if let Some(msg) = new_msg {
-            shared.store.execute(StorageOp::MoveToMain {
-                id: message_id,
-                msg,
-            });
+        let count = replayed.len();
+        for msg in replayed {
+            self.tracer.record(queue_name, msg.id, TraceEventKind::Replayed, None);
+            shared.store.execute(StorageOp::MoveToMain { id: msg.id, msg });
+        }
+        if count > 0 {
             shared.notify.notify_waiters();
-            Ok(true)
-        } else {
-            Ok(false)
+            if delay_ms > 0 && shared.precise_delays {
+                self.wake_scheduler();
+            }
         }
+        Ok(count)
     }
 
     /// Delete a specific message from DLQ
-    pub async fn delete_dlq(&self, queue_name: &str, message_id: Uuid) -> Result<bool, String> {
+    #[tracing::instrument(name = "queue.dlq.delete", skip_all, fields(queue = %queue_name, message_id = %message_id))]
+    pub async fn delete_dlq(&self, queue_name: &str, message_id: Uuid) -> Result<bool, NexoError> {
         let shared = self.get_queue(queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found", queue_name))?;
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
 
-        let removed = {
+        Ok(self.discard_dlq(queue_name, &shared, &[message_id]) > 0)
+    }
+
+    /// Delete DLQ messages by id. Returns how many were still there.
+    fn discard_dlq(&self, queue_name: &str, shared: &QueueShared, ids: &[Uuid]) -> usize {
+        let removed: Vec<DlqMessage> = {
             let mut inner = Self::lock(&shared.inner);
-            inner.dlq.remove(&message_id).is_some()
+            ids.iter().filter_map(|id| inner.dlq.remove(id)).collect()
         };
 
-        if removed {
-            shared.store.execute(StorageOp::DeleteDLQ(message_id));
+        for msg in &removed {
+            self.tracer.record(queue_name, msg.id, TraceEventKind::Discarded, None);
+            shared.store.execute(StorageOp::DeleteDLQ(msg.id));
+            self.release_blob(queue_name, &msg.payload);
         }
-
-        Ok(removed)
+        removed.len()
     }
 
     /// Purge all messages from DLQ
-    pub async fn purge_dlq(&self, queue_name: &str) -> Result<usize, String> {
+    #[tracing::instrument(name = "queue.dlq.purge", skip_all, fields(queue = %queue_name))]
+    pub async fn purge_dlq(&self, queue_name: &str) -> Result<usize, NexoError> {
         let shared = self.get_queue(queue_name)
-            .ok_or_else(|| format!("Queue '{}' not found", queue_name))?;
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
 
-        let count = {
+        let (count, payloads) = {
             let mut inner = Self::lock(&shared.inner);
             let count = inner.dlq.len();
+            let payloads: Vec<Bytes> = match self.blobs {
+                Some(_) => inner.dlq.peek_all().into_iter().map(|m| m.payload.clone()).collect(),
+                None => Vec::new(),
+            };
             inner.dlq.clear();
-            count
+            (count, payloads)
         };
 
         shared.store.execute(StorageOp::PurgeDLQ);
+        for payload in payloads {
+            self.release_blob(queue_name, &payload);
+        }
         Ok(count)
     }
+
+    // --- Message Archive ---
+
+    fn archive_of<'a>(queue_name: &str, shared: &'a QueueShared) -> Result<&'a MessageArchive, NexoError> {
+        shared.archive.as_ref()
+            .ok_or_else(|| NexoError::invalid(format!("Queue '{}' does not archive acked messages", queue_name)))
+    }
+
+    /// Page through the archived (acked) messages of a queue, newest first.
+    pub async fn peek_archive(&self, queue_name: &str, limit: usize, offset: usize) -> Result<(usize, Vec<ArchivedMessage>), NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        Self::archive_of(queue_name, &shared)?.page(offset, limit).await
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to read archive of queue '{}': {}", queue_name, e)))
+    }
+
+    /// Push an archived message again, as a new message with the same payload,
+    /// priority and group. The archived copy stays. Returns false if retention
+    /// already dropped it.
+    #[tracing::instrument(name = "queue.archive.replay", skip_all, fields(queue = %queue_name, message_id = %message_id))]
+    pub async fn replay_archived(&self, queue_name: &str, message_id: Uuid) -> Result<bool, NexoError> {
+        let shared = self.get_queue(queue_name)
+            .ok_or_else(|| NexoError::not_found(format!("Queue '{}' not found", queue_name)))?;
+        let found = Self::archive_of(queue_name, &shared)?.find(message_id).await
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to read archive of queue '{}': {}", queue_name, e)))?;
+        let Some(msg) = found else { return Ok(false) };
+
+        let options = QueuePushOptions { priority: Some(msg.priority), message_group: msg.message_group, ..Default::default() };
+        self.push_with(queue_name.to_string(), msg.payload, options).await?;
+        if self.tracer.is_enabled(queue_name) {
+            self.tracer.record(queue_name, message_id, TraceEventKind::Replayed, Some("from archive".to_string()));
+        }
+        Ok(true)
+    }
+
+    // --- Blobs ---
+
+    /// Content of an offloaded payload, `None` once its message is gone.
+    pub async fn fetch_blob(&self, queue_name: &str, blob_id: Uuid) -> Result<Option<Bytes>, NexoError> {
+        if self.get_queue(queue_name).is_none() {
+            return Err(NexoError::not_found(format!("Queue '{}' not found", queue_name)));
+        }
+        let Some(blobs) = &self.blobs else { return Ok(None) };
+        blobs.get(queue_name, &blob_id).await
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to read blob: {}", e)))
+    }
+
+    /// `payload` itself, or the blob it references.
+    pub async fn resolve_blob(&self, queue_name: &str, payload: Bytes) -> Result<Bytes, NexoError> {
+        let Some((id, _)) = blob::parse_reference(&payload) else { return Ok(payload) };
+        self.fetch_blob(queue_name, id).await?
+            .ok_or_else(|| NexoError::not_found(format!("Blob {} not found", id)))
+    }
+
+    /// Remove blobs of `queue_name` older than `older_than` that no message
+    /// references. Compaction runs this with its interval as the grace period,
+    /// so a blob written by a push still in progress is never collected.
+    pub fn sweep_blobs(&self, queue_name: &str, older_than: Duration) -> usize {
+        match (&self.blobs, self.get_queue(queue_name)) {
+            (Some(blobs), Some(shared)) => Self::sweep_queue_blobs(blobs, queue_name, &shared, older_than),
+            _ => 0,
+        }
+    }
 }
 
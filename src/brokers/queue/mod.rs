@@ -1,9 +1,16 @@
+pub mod alerts;
+pub mod archive;
+pub mod blob;
 pub mod config;
 pub mod domain;
+pub mod export;
 pub mod manager;
 pub mod options;
+pub mod push;
+pub mod replay;
 pub mod snapshot;
 pub mod tcp;
+pub mod webhook;
 pub mod http;
 
 pub use manager::*;
@@ -2,19 +2,133 @@
 //! adapter (wire parsing). They live here (not in `tcp.rs`) because the
 //! manager API consumes them directly.
 
-use serde::Deserialize;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::labels::Labels;
 
 #[derive(Debug, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct QueueCreateOptions {
     pub visibility_timeout_ms: Option<u64>,
     pub max_retries: Option<u32>,
+    /// DLQ depth that publishes a threshold alert (0 = off)
+    pub dlq_alert_threshold: Option<usize>,
+    /// `fileSync` acks pushes only once they are fsynced
+    pub persistence: Option<PersistenceMode>,
+    pub webhook: Option<WebhookOptions>,
+    /// Broadcast: every consumer group receives every message
+    pub fanout: Option<bool>,
+    /// Where messages go once they run out of retries (default: the queue's DLQ)
+    pub dead_letter: Option<DeadLetterTarget>,
+    /// Keep acked messages in an append-only archive instead of deleting them
+    pub archive: Option<ArchiveOptions>,
+    /// Release delayed messages from a dedicated scheduler thread (sub-ms
+    /// lateness) instead of the 50ms pulse
+    pub precise_delays: Option<bool>,
+    /// `key=value` pairs for ownership and cost attribution
+    pub labels: Option<Labels>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Retention of the message archive; unset limits take the system defaults
+/// (`QUEUE_ARCHIVE_RETENTION_AGE_MS`, `QUEUE_ARCHIVE_RETENTION_BYTES`), 0 = no limit.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveOptions {
+    pub max_age_ms: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+/// Destination of messages that ran out of retries. Messages sent to another
+/// queue or a stream topic are wrapped in a JSON envelope with the source
+/// queue, attempts and failure reason.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DeadLetterTarget {
+    /// The queue's own DLQ (peek, replay, purge)
+    #[default]
+    Dlq,
+    /// Pushed to another queue, created on first use
+    Queue { name: String },
+    /// Published to a stream topic, created on first use
+    Stream { topic: String },
+    /// Dropped
+    Disabled,
+}
+
+/// Push delivery: POST every message to `url` instead of waiting for consumers.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookOptions {
+    pub url: String,
+    pub timeout_ms: Option<u64>,
+    pub concurrency: Option<usize>,
+}
+
+/// What `import_queue` does when the target queue already exists.
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportConflict {
+    /// Refuse the import
+    #[default]
+    Fail,
+    /// Keep the existing queue and config, add messages whose id is not already there
+    Merge,
+    /// Delete the existing queue first
+    Replace,
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct QueuePushOptions {
     pub priority: Option<u8>,
+    /// Keep the message scheduled (invisible) for this long
+    pub delay_ms: Option<u64>,
+    /// Deliver one at a time, in order, with the other messages of this group
+    pub message_group: Option<String>,
+    /// Drop the push if the queue saw this key within `QUEUE_DEDUP_WINDOW_MS`
+    pub idempotency_key: Option<String>,
+}
+
+/// How a DLQ message goes back to its queue (attempts are always reset).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReplayOptions {
+    /// Ahead of the messages already waiting at its priority, instead of behind them
+    pub front: Option<bool>,
+    /// Replace the original priority
+    pub priority: Option<u8>,
+    /// Keep the message scheduled (invisible) for this long
+    pub delay_ms: Option<u64>,
+    /// Rewrite the payload on the way back (see `queue::replay`)
+    pub transform: Option<ReplayTransform>,
+}
+
+/// How a replayed payload is rewritten: fields stripped first, then wrapped.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReplayTransform {
+    /// Top-level fields removed from JSON object payloads
+    #[serde(default)]
+    pub strip_fields: Vec<String>,
+    /// Wrap the payload in an object carrying the failure it comes from
+    #[serde(default)]
+    pub wrap: bool,
+}
+
+/// Push delivery to the subscribing connection (see `queue::push`).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct QueueSubscribeOptions {
+    /// Most messages delivered and not yet settled (default: `QUEUE_DEFAULT_BATCH_SIZE`)
+    pub prefetch: Option<usize>,
+    /// Max share of each delivery (0.0..=1.0) per priority, as for CONSUME
+    pub priority_quotas: Option<HashMap<u8, f64>>,
+    /// Drop the subscription once it has held deliveries this long without
+    /// acking or nacking any
+    pub idle_ttl_ms: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -22,4 +136,8 @@ pub struct QueuePushOptions {
 pub struct QueueConsumeOptions {
     pub batch_size: Option<usize>,
     pub wait_ms: Option<u64>,
+    /// Max share of the batch (0.0..=1.0) per priority, e.g. `{"255": 0.8}`
+    pub priority_quotas: Option<HashMap<u8, f64>>,
+    /// Consumer group (fanout queues only)
+    pub group: Option<String>,
 }
@@ -0,0 +1,143 @@
+//! Push delivery to network consumers: a connection subscribes to a queue
+//! with a prefetch window, and the server sends it messages as they become
+//! Ready (`TYPE_PUSH_QUEUE` frames) instead of the client long-polling CONSUME.
+//!
+//! At most `prefetch` messages dispatched to the subscription are unsettled
+//! at a time; an ack, a nack or an expired visibility timeout makes room for
+//! the next. Acks and nacks are the regular ones. A connection holds at most
+//! one subscription per queue, and loses them all when it closes.
+//!
+//! With an idle TTL, a subscription that holds deliveries for that long
+//! without acking or nacking any is dropped, and the connection is told with
+//! a `PUSH_FLAG_UNSUBSCRIBED` frame: a consumer that keeps its socket open
+//! but stopped processing no longer pins messages it will never settle.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::brokers::queue::domain::queue::Message;
+use crate::brokers::queue::QueueManager;
+use crate::brokers::error::NexoError;
+use crate::transport::tcp::protocol::PUSH_FLAG_UNSUBSCRIBED;
+
+/// How often an idle TTL is checked, at most and at least
+const IDLE_CHECK_MAX: Duration = Duration::from_secs(1);
+const IDLE_CHECK_MIN: Duration = Duration::from_millis(10);
+
+/// Messages pushed to one connection for one of its subscriptions.
+#[derive(Debug)]
+pub struct QueueDelivery {
+    pub queue: String,
+    pub messages: Vec<Message>,
+    /// Set (with the reason) when the server dropped the subscription instead
+    pub unsubscribed: Option<String>,
+}
+
+impl QueueDelivery {
+    /// Meta byte of the push frame.
+    pub fn push_flags(&self) -> u8 {
+        match self.unsubscribed {
+            Some(_) => PUSH_FLAG_UNSUBSCRIBED,
+            None => 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PushConsumers {
+    /// Connection -> channel to its socket
+    sinks: DashMap<String, mpsc::UnboundedSender<QueueDelivery>>,
+    /// (connection, queue) -> stops the subscription's delivery task
+    subscriptions: DashMap<(String, String), CancellationToken>,
+}
+
+impl PushConsumers {
+    pub(crate) fn connect(&self, client_id: &str, sink: mpsc::UnboundedSender<QueueDelivery>) {
+        self.sinks.insert(client_id.to_string(), sink);
+    }
+
+    pub(crate) fn disconnect(&self, client_id: &str) {
+        self.sinks.remove(client_id);
+        self.subscriptions.retain(|(client, _), cancel| {
+            let keep = client != client_id;
+            if !keep {
+                cancel.cancel();
+            }
+            keep
+        });
+    }
+
+    /// Start delivering `queue` to `client_id`, replacing its previous subscription.
+    pub(crate) fn subscribe(&self, manager: QueueManager, queue: String, client_id: &str, prefetch: usize, quotas: Option<HashMap<u8, f64>>, idle_ttl: Option<Duration>) -> Result<(), NexoError> {
+        let sink = self.sinks.get(client_id).map(|sink| sink.clone())
+            .ok_or_else(|| NexoError::invalid("Push delivery needs a network connection"))?;
+        let cancel = CancellationToken::new();
+        if let Some(previous) = self.subscriptions.insert((client_id.to_string(), queue.clone()), cancel.clone()) {
+            previous.cancel();
+        }
+
+        let client_id = client_id.to_string();
+        if let Some(ttl) = idle_ttl {
+            tokio::spawn(watch_idle(manager.clone(), queue.clone(), client_id.clone(), ttl, cancel.clone()));
+        }
+        tokio::spawn(async move {
+            loop {
+                let batch = tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    batch = manager.take_push_batch(&queue, &client_id, prefetch, quotas.as_ref()) => batch,
+                };
+                // Queue deleted
+                let Ok(messages) = batch else { return };
+                if sink.send(QueueDelivery { queue: queue.clone(), messages, unsubscribed: None }).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Stop delivering `queue` to `client_id`. Returns false if it wasn't subscribed.
+    pub(crate) fn unsubscribe(&self, queue: &str, client_id: &str) -> bool {
+        match self.subscriptions.remove(&(client_id.to_string(), queue.to_string())) {
+            Some((_, cancel)) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop the subscription `cancel` belongs to, unless it was replaced or
+    /// ended meanwhile, and tell the connection why.
+    pub(crate) fn expire(&self, queue: &str, client_id: &str, cancel: &CancellationToken, reason: String) -> bool {
+        let key = (client_id.to_string(), queue.to_string());
+        if self.subscriptions.remove_if(&key, |_, _| !cancel.is_cancelled()).is_none() {
+            return false;
+        }
+        cancel.cancel();
+        if let Some(sink) = self.sinks.get(client_id) {
+            let _ = sink.send(QueueDelivery { queue: queue.to_string(), messages: Vec::new(), unsubscribed: Some(reason) });
+        }
+        true
+    }
+}
+
+/// Expire the subscription once its consumer has been stalled for `ttl`.
+async fn watch_idle(manager: QueueManager, queue: String, client_id: String, ttl: Duration, cancel: CancellationToken) {
+    let check = (ttl / 4).clamp(IDLE_CHECK_MIN, IDLE_CHECK_MAX);
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tokio::time::sleep(check) => {}
+        }
+        let Some(stalled) = manager.push_stalled_for(&queue, &client_id) else { continue };
+        if stalled >= ttl {
+            manager.expire_push(&queue, &client_id, &cancel, stalled);
+            return;
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Payload transforms applied as DLQ messages are replayed, so consumers can
+//! tell a replay from a fresh message without producers changing anything.
+//!
+//! `stripFields` drops top-level fields of JSON object payloads (a stale
+//! header, a poison flag). `wrap` then replaces the payload with
+//! `{"replay": {...}, "contentType": ..., "payload": <original>}`, the
+//! original rendered as in the dashboard (JSON as is, text as a string,
+//! binary as `0x...` hex). A payload keeps its framing: typed payloads stay
+//! typed (`DATA_TYPE_JSON`), untyped ones stay untyped. Blob references are
+//! replayed untouched.
+
+use bytes::Bytes;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::brokers::queue::blob;
+use crate::brokers::queue::domain::dlq::DlqMessage;
+use crate::brokers::queue::options::ReplayTransform;
+use crate::brokers::payload::render_payload;
+use crate::transport::tcp::protocol::{DATA_TYPE_JSON, DATA_TYPE_PROTOBUF, DATA_TYPE_RAW};
+
+/// What a wrapped replay carries about the failure it comes from.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplayInfo<'a> {
+    failure_reason: &'a str,
+    attempts: u32,
+    failed_at: u64,
+    replayed_at: u64,
+}
+
+/// The payload `msg` is replayed with.
+pub fn apply(transform: &ReplayTransform, msg: &DlqMessage, now: u64) -> Bytes {
+    let payload = &msg.payload;
+    if blob::parse_reference(payload).is_some() {
+        return payload.clone();
+    }
+    let typed = payload.first().is_some_and(|b| (DATA_TYPE_RAW..=DATA_TYPE_PROTOBUF).contains(b));
+
+    let stripped = match transform.strip_fields.is_empty() {
+        true => None,
+        false => json_object(payload, typed).map(|mut object| {
+            for field in &transform.strip_fields {
+                object.remove(field);
+            }
+            Value::Object(object)
+        }),
+    };
+    if !transform.wrap {
+        return match stripped {
+            Some(value) => encode(&value, typed),
+            None => payload.clone(),
+        };
+    }
+
+    let (original, content_type) = match stripped {
+        Some(value) => (value, "json"),
+        None => render_payload(payload),
+    };
+    let info = ReplayInfo {
+        failure_reason: &msg.failure_reason,
+        attempts: msg.attempts,
+        failed_at: msg.failed_at,
+        replayed_at: now,
+    };
+    let wrapped = serde_json::json!({ "replay": info, "contentType": content_type, "payload": original });
+    encode(&wrapped, typed)
+}
+
+fn json_object(payload: &[u8], typed: bool) -> Option<Map<String, Value>> {
+    let content = match typed {
+        true if payload.first() == Some(&DATA_TYPE_JSON) => &payload[1..],
+        true => return None,
+        false => payload,
+    };
+    match serde_json::from_slice(content) {
+        Ok(Value::Object(object)) => Some(object),
+        _ => None,
+    }
+}
+
+fn encode(value: &Value, typed: bool) -> Bytes {
+    let mut out = Vec::new();
+    if typed {
+        out.push(DATA_TYPE_JSON);
+    }
+    // Serializing a `Value` cannot fail
+    out.extend(serde_json::to_vec(value).unwrap_or_default());
+    Bytes::from(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn failed(payload: &[u8]) -> DlqMessage {
+        DlqMessage {
+            id: Uuid::now_v7(),
+            payload: Bytes::copy_from_slice(payload),
+            priority: 0,
+            attempts: 3,
+            created_at: 1,
+            failed_at: 2,
+            failure_reason: "timeout".to_string(),
+            message_group: None,
+        }
+    }
+
+    fn strip(fields: &[&str]) -> ReplayTransform {
+        ReplayTransform { strip_fields: fields.iter().map(|f| f.to_string()).collect(), wrap: false }
+    }
+
+    #[test]
+    fn strip_keeps_the_framing() {
+        let typed = [&[DATA_TYPE_JSON][..], br#"{"header":{"v":1},"order":7}"#].concat();
+        assert_eq!(apply(&strip(&["header"]), &failed(&typed), 0), Bytes::from([&[DATA_TYPE_JSON][..], br#"{"order":7}"#].concat()));
+        assert_eq!(apply(&strip(&["header"]), &failed(br#"{"header":1,"order":7}"#), 0), Bytes::from(r#"{"order":7}"#));
+        // Not a JSON object: replayed as is
+        assert_eq!(apply(&strip(&["header"]), &failed(b"plain text"), 0), Bytes::from("plain text"));
+        assert_eq!(apply(&strip(&["header"]), &failed(b"[1,2]"), 0), Bytes::from("[1,2]"));
+    }
+
+    #[test]
+    fn wrap_carries_the_failure() {
+        let transform = ReplayTransform { strip_fields: vec!["header".to_string()], wrap: true };
+        let typed = [&[DATA_TYPE_JSON][..], br#"{"header":1,"order":7}"#].concat();
+        let wrapped = apply(&transform, &failed(&typed), 5);
+        assert_eq!(wrapped[0], DATA_TYPE_JSON);
+        let value: Value = serde_json::from_slice(&wrapped[1..]).unwrap();
+        assert_eq!(value["payload"], serde_json::json!({ "order": 7 }));
+        assert_eq!(value["contentType"], "json");
+        assert_eq!(value["replay"], serde_json::json!({ "failureReason": "timeout", "attempts": 3, "failedAt": 2, "replayedAt": 5 }));
+
+        let wrap = ReplayTransform { strip_fields: Vec::new(), wrap: true };
+        let value: Value = serde_json::from_slice(&apply(&wrap, &failed(b"plain text"), 5)).unwrap();
+        assert_eq!((value["payload"].as_str(), value["contentType"].as_str()), (Some("plain text"), Some("text")));
+    }
+}
@@ -9,19 +9,56 @@
 use bytes::Bytes;
 use uuid::Uuid;
 
+use crate::brokers::queue::archive::ArchiveStats;
+use crate::brokers::queue::domain::consumers::ConsumerSnapshot;
+use crate::brokers::queue::domain::fanout::FanoutGroupSnapshot;
+use crate::brokers::queue::domain::persistence::PersistenceHealth;
 use crate::brokers::queue::domain::queue::QueueConfig;
 
 pub struct QueueSnapshot {
     pub name: String,
     pub pending: usize,
     pub inflight: usize,
+    /// Delayed messages waiting for their activation time
+    pub scheduled: usize,
     pub dlq: usize,
+    pub bytes: usize,
+    /// Pending messages paged out to disk: counted in `pending`, not in `bytes`
+    pub spilled: usize,
+    /// Registry/index inconsistencies repaired by compaction since startup
+    pub index_drift: u64,
+    /// Message tracing (debug) enabled
+    pub traced: bool,
     pub config: QueueConfig,
+    pub consumers: Vec<ConsumerSnapshot>,
+    /// Bound consumer groups (fanout queues)
+    pub groups: Vec<FanoutGroupSnapshot>,
+    /// WAL size and checkpoint progress of the queue DB
+    pub persistence: PersistenceHealth,
+    /// Depth and throughput per priority level, highest first
+    pub priorities: Vec<PriorityLaneSnapshot>,
+    /// Size of the message archive, for queues that keep one
+    pub archive: Option<ArchiveStats>,
+}
+
+/// One priority level of a queue, seen as its own sub-queue.
+pub struct PriorityLaneSnapshot {
+    pub priority: u8,
+    pub pending: usize,
+    pub inflight: usize,
+    pub scheduled: usize,
+    /// Pushes and acks since startup
+    pub pushed: u64,
+    pub acked: u64,
+    /// Per second over the last minute
+    pub push_rate: f64,
+    pub ack_rate: f64,
 }
 
 pub enum MessageStateTag {
     Pending,
     InFlight,
+    Scheduled,
 }
 
 pub struct QueueMessagePreview {
@@ -30,4 +67,15 @@ pub struct QueueMessagePreview {
     pub state: MessageStateTag,
     pub priority: u8,
     pub attempts: u32,
+    pub created_at: u64,
+}
+
+/// A delayed message, as listed by the scheduling calendar.
+pub struct ScheduledMessage {
+    pub id: Uuid,
+    pub payload: Bytes,
+    pub priority: u8,
+    /// When the message becomes visible to consumers (ms)
+    pub scheduled_at: u64,
+    pub created_at: u64,
 }
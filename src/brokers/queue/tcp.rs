@@ -3,14 +3,19 @@
 
 use bytes::Bytes;
 use uuid::Uuid;
+use tokio_util::sync::CancellationToken;
 
+use crate::brokers::names::{self, NameKind};
+use crate::brokers::pub_sub::ClientId;
 use crate::transport::tcp::protocol::cursor::PayloadCursor;
-use crate::transport::tcp::protocol::{ParseError, Response, ToWire};
+use crate::transport::tcp::protocol::{ErrorCode, NexoError, ParseError, Response, ToWire};
 use crate::NexoEngine;
 
 use crate::brokers::queue::domain::dlq::DlqMessage;
-use crate::brokers::queue::options::{QueueConsumeOptions, QueueCreateOptions, QueuePushOptions};
+use crate::brokers::queue::options::{QueueConsumeOptions, QueueCreateOptions, QueuePushOptions, QueueSubscribeOptions, ReplayOptions};
+use crate::brokers::queue::push::QueueDelivery;
 use crate::brokers::queue::domain::queue::Message;
+use crate::brokers::queue::snapshot::ScheduledMessage;
 
 // ==========================================
 // OPCODES
@@ -18,6 +23,14 @@ use crate::brokers::queue::domain::queue::Message;
 
 pub const OPCODE_MIN: u8 = 0x10;
 pub const OPCODE_MAX: u8 = 0x1F;
+// 0x10..=0x1F is full: later queue commands continue in 0x50..=0x5F
+pub const OPCODE_EXT_MIN: u8 = 0x50;
+pub const OPCODE_EXT_MAX: u8 = 0x5F;
+
+/// True if `opcode` belongs to the queue broker.
+pub fn owns(opcode: u8) -> bool {
+    (OPCODE_MIN..=OPCODE_MAX).contains(&opcode) || (OPCODE_EXT_MIN..=OPCODE_EXT_MAX).contains(&opcode)
+}
 
 pub const OP_Q_CREATE: u8 = 0x10;
 pub const OP_Q_PUSH: u8 = 0x11;
@@ -26,12 +39,24 @@ pub const OP_Q_ACK: u8 = 0x13;
 pub const OP_Q_EXISTS: u8 = 0x14;
 pub const OP_Q_DELETE: u8 = 0x15;
 pub const OP_Q_NACK: u8 = 0x1A;
+pub const OP_Q_FETCH_BLOB: u8 = 0x1B;
+pub const OP_Q_LIST_SCHEDULED: u8 = 0x1C;
+pub const OP_Q_RESCHEDULE: u8 = 0x1D;
+pub const OP_Q_PURGE_PRIORITY: u8 = 0x1E;
 
 // DLQ Operations
 pub const OP_Q_PEEK_DLQ: u8 = 0x16;
 pub const OP_Q_MOVE_TO_QUEUE: u8 = 0x17;
 pub const OP_Q_DELETE_DLQ: u8 = 0x18;
 pub const OP_Q_PURGE_DLQ: u8 = 0x19;
+pub const OP_Q_REPLAY_DLQ: u8 = 0x1F;
+
+// Push delivery
+pub const OP_Q_SUBSCRIBE: u8 = 0x50;
+pub const OP_Q_UNSUBSCRIBE: u8 = 0x51;
+
+// Fanout groups
+pub const OP_Q_UNBIND_GROUP: u8 = 0x52;
 
 // ==========================================
 // COMMANDS
@@ -43,13 +68,27 @@ enum QueueCommand {
     Push { q_name: String, options: QueuePushOptions, payload: Bytes },
     Consume { q_name: String, options: QueueConsumeOptions },
     Delete { q_name: String },
-    Ack { id: Uuid, q_name: String },
-    Nack { id: Uuid, q_name: String, reason: String },
+    Ack { id: Uuid, q_name: String, group: Option<String> },
+    Nack { id: Uuid, q_name: String, reason: String, group: Option<String> },
     Exists { q_name: String },
-    PeekDLQ { q_name: String, limit: usize, offset: usize },
-    MoveToQueue { q_name: String, message_id: Uuid },
+    // [QName][Limit:4][Offset:4] + optional [Search: string]
+    PeekDLQ { q_name: String, limit: usize, offset: usize, search: Option<String> },
+    // [QName][MessageID:16] + optional [Options: JSON string]
+    MoveToQueue { q_name: String, message_id: Uuid, options: ReplayOptions },
+    // [QName][Search: string, empty = all][Options: JSON string]
+    ReplayDLQ { q_name: String, search: Option<String>, options: ReplayOptions },
     DeleteDLQ { q_name: String, message_id: Uuid },
     PurgeDLQ { q_name: String },
+    FetchBlob { q_name: String, blob_id: Uuid },
+    ListScheduled { q_name: String, from_ts: u64, to_ts: u64, limit: usize },
+    Reschedule { q_name: String, message_id: Uuid, new_ts: u64 },
+    // [QName][Priority:1]
+    PurgePriority { q_name: String, priority: u8 },
+    // [QName][Options: JSON string]
+    Subscribe { q_name: String, options: QueueSubscribeOptions },
+    Unsubscribe { q_name: String },
+    // [QName][Group]
+    UnbindGroup { q_name: String, group: String },
 }
 
 impl QueueCommand {
@@ -57,6 +96,7 @@ impl QueueCommand {
         match opcode {
             OP_Q_CREATE => {
                 let q_name = cursor.read_string()?;
+                names::check(NameKind::Queue, &q_name).map_err(ParseError::InvalidName)?;
                 let json_str = cursor.read_string()?;
                 let options: QueueCreateOptions = serde_json::from_str(&json_str)
                     .map_err(|e| ParseError::Invalid(format!("Invalid JSON config: {}", e)))?;
@@ -80,13 +120,16 @@ impl QueueCommand {
             OP_Q_ACK => {
                 let id = Uuid::from_bytes(cursor.read_uuid_bytes()?);
                 let q_name = cursor.read_string()?;
-                Ok(Self::Ack { id, q_name })
+                // Optional trailing group: ack on behalf of a fanout consumer group
+                let group = if cursor.len() > 0 { Some(cursor.read_string()?) } else { None };
+                Ok(Self::Ack { id, q_name, group })
             }
             OP_Q_NACK => {
                 let id = Uuid::from_bytes(cursor.read_uuid_bytes()?);
                 let q_name = cursor.read_string()?;
                 let reason = cursor.read_string()?;
-                Ok(Self::Nack { id, q_name, reason })
+                let group = if cursor.len() > 0 { Some(cursor.read_string()?) } else { None };
+                Ok(Self::Nack { id, q_name, reason, group })
             }
             OP_Q_EXISTS => {
                 let q_name = cursor.read_string()?;
@@ -100,12 +143,20 @@ impl QueueCommand {
                 let q_name = cursor.read_string()?;
                 let limit = cursor.read_u32()? as usize;
                 let offset = cursor.read_u32()? as usize;
-                Ok(Self::PeekDLQ { q_name, limit, offset })
+                let search = if cursor.len() > 0 { Some(cursor.read_string()?).filter(|s| !s.is_empty()) } else { None };
+                Ok(Self::PeekDLQ { q_name, limit, offset, search })
             }
             OP_Q_MOVE_TO_QUEUE => {
                 let q_name = cursor.read_string()?;
                 let message_id = Uuid::from_bytes(cursor.read_uuid_bytes()?);
-                Ok(Self::MoveToQueue { q_name, message_id })
+                let options = if cursor.len() > 0 { parse_replay_options(cursor)? } else { ReplayOptions::default() };
+                Ok(Self::MoveToQueue { q_name, message_id, options })
+            }
+            OP_Q_REPLAY_DLQ => {
+                let q_name = cursor.read_string()?;
+                let search = Some(cursor.read_string()?).filter(|s| !s.is_empty());
+                let options = parse_replay_options(cursor)?;
+                Ok(Self::ReplayDLQ { q_name, search, options })
             }
             OP_Q_DELETE_DLQ => {
                 let q_name = cursor.read_string()?;
@@ -116,11 +167,56 @@ impl QueueCommand {
                 let q_name = cursor.read_string()?;
                 Ok(Self::PurgeDLQ { q_name })
             }
+            OP_Q_PURGE_PRIORITY => {
+                let q_name = cursor.read_string()?;
+                let priority = cursor.read_u8()?;
+                Ok(Self::PurgePriority { q_name, priority })
+            }
+            OP_Q_FETCH_BLOB => {
+                let q_name = cursor.read_string()?;
+                let blob_id = Uuid::from_bytes(cursor.read_uuid_bytes()?);
+                Ok(Self::FetchBlob { q_name, blob_id })
+            }
+            OP_Q_LIST_SCHEDULED => {
+                let q_name = cursor.read_string()?;
+                let from_ts = cursor.read_u64()?;
+                let to_ts = cursor.read_u64()?;
+                let limit = cursor.read_u32()? as usize;
+                Ok(Self::ListScheduled { q_name, from_ts, to_ts, limit })
+            }
+            OP_Q_RESCHEDULE => {
+                let q_name = cursor.read_string()?;
+                let message_id = Uuid::from_bytes(cursor.read_uuid_bytes()?);
+                let new_ts = cursor.read_u64()?;
+                Ok(Self::Reschedule { q_name, message_id, new_ts })
+            }
+            OP_Q_SUBSCRIBE => {
+                let q_name = cursor.read_string()?;
+                let json_str = cursor.read_string()?;
+                let options: QueueSubscribeOptions = serde_json::from_str(&json_str)
+                    .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?;
+                Ok(Self::Subscribe { q_name, options })
+            }
+            OP_Q_UNSUBSCRIBE => {
+                let q_name = cursor.read_string()?;
+                Ok(Self::Unsubscribe { q_name })
+            }
+            OP_Q_UNBIND_GROUP => {
+                let q_name = cursor.read_string()?;
+                let group = cursor.read_string()?;
+                Ok(Self::UnbindGroup { q_name, group })
+            }
             _ => Err(ParseError::Invalid(format!("Unknown Queue opcode: 0x{:02X}", opcode))),
         }
     }
 }
 
+fn parse_replay_options(cursor: &mut PayloadCursor) -> Result<ReplayOptions, ParseError> {
+    let json_str = cursor.read_string()?;
+    serde_json::from_str(&json_str)
+        .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))
+}
+
 // ==========================================
 // WIRE RESPONSES
 // ==========================================
@@ -142,6 +238,26 @@ impl ToWire for ConsumeBatchResponse {
     }
 }
 
+/// Payload of a `TYPE_PUSH_QUEUE` frame:
+/// [QName: string][Count:4]{[Id:16][Len:4][Payload]},
+/// or [QName: string][Reason: string] with `PUSH_FLAG_UNSUBSCRIBED`
+pub fn encode_delivery(delivery: &QueueDelivery) -> Bytes {
+    if let Some(reason) = &delivery.unsubscribed {
+        let mut buf = Vec::with_capacity(8 + delivery.queue.len() + reason.len());
+        for part in [&delivery.queue, reason] {
+            buf.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            buf.extend_from_slice(part.as_bytes());
+        }
+        return Bytes::from(buf);
+    }
+    let batch = ConsumeBatchResponse { messages: delivery.messages.clone() }.to_wire();
+    let mut buf = Vec::with_capacity(4 + delivery.queue.len() + batch.len());
+    buf.extend_from_slice(&(delivery.queue.len() as u32).to_be_bytes());
+    buf.extend_from_slice(delivery.queue.as_bytes());
+    buf.extend_from_slice(&batch);
+    Bytes::from(buf)
+}
+
 struct PeekDlqResponse {
     total: usize,
     messages: Vec<DlqMessage>,
@@ -166,6 +282,25 @@ impl ToWire for PeekDlqResponse {
     }
 }
 
+struct ScheduledResponse {
+    messages: Vec<ScheduledMessage>,
+}
+
+impl ToWire for ScheduledResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(self.messages.len() as u32).to_be_bytes());
+        for msg in &self.messages {
+            buf.extend_from_slice(msg.id.as_bytes());
+            buf.extend_from_slice(&msg.scheduled_at.to_be_bytes());
+            buf.push(msg.priority);
+            buf.extend_from_slice(&(msg.payload.len() as u32).to_be_bytes());
+            buf.extend_from_slice(&msg.payload);
+        }
+        Bytes::from(buf)
+    }
+}
+
 struct BoolResponse {
     value: bool,
 }
@@ -190,10 +325,10 @@ impl ToWire for CountResponse {
 // DISPATCH ENTRY POINT
 // ==========================================
 
-pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine) -> Response {
+pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine, client_id: &ClientId, cancel: &CancellationToken) -> Response {
     let cmd = match QueueCommand::parse(opcode, cursor) {
         Ok(c) => c,
-        Err(e) => return Response::Error(e.to_string()),
+        Err(e) => return Response::Error(e.into()),
     };
 
     let queue = &engine.queue;
@@ -203,47 +338,70 @@ pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine)
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
-        QueueCommand::Push { q_name, options, payload } => {
-            let priority = options.priority.unwrap_or(0);
-            match queue.push(q_name, payload, priority).await {
-                Ok(_) => Response::Ok,
-                Err(e) => Response::Error(e),
-            }
-        }
+        QueueCommand::Push { q_name, options, payload } => match queue.push_with(q_name, payload, options).await {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
         QueueCommand::Consume { q_name, options } => {
-            match queue.consume_batch(q_name, options.batch_size, options.wait_ms).await {
+            let consumed = match &options.group {
+                Some(group) => queue.consume_group_cancellable(&q_name, group, options.batch_size, options.wait_ms, cancel).await,
+                None => queue.consume_batch_as(q_name, options.batch_size, options.wait_ms, options.priority_quotas.as_ref(), Some(&client_id.0), cancel).await,
+            };
+            match consumed {
                 Ok(messages) => Response::Data(ConsumeBatchResponse { messages }.to_wire()),
                 Err(e) => Response::Error(e),
             }
         }
-        QueueCommand::Ack { id, q_name } => match queue.ack(&q_name, id).await {
-            true => Response::Ok,
-            false => Response::Error("ACK failed".to_string()),
-        },
-        QueueCommand::Nack { id, q_name, reason } => match queue.nack(&q_name, id, reason).await {
-            true => Response::Ok,
-            false => Response::Error("NACK failed".to_string()),
-        },
+        QueueCommand::Ack { id, q_name, group } => {
+            let acked = match group {
+                Some(group) => queue.ack_group(&q_name, &group, id).await,
+                None => queue.ack(&q_name, id).await,
+            };
+            match acked {
+                true => Response::Ok,
+                false => Response::Error(NexoError::new(ErrorCode::NotPending, "ACK failed")),
+            }
+        }
+        QueueCommand::Nack { id, q_name, reason, group } => {
+            let nacked = match group {
+                Some(group) => queue.nack_group(&q_name, &group, id, reason).await,
+                None => queue.nack(&q_name, id, reason).await,
+            };
+            match nacked {
+                true => Response::Ok,
+                false => Response::Error(NexoError::new(ErrorCode::NotPending, "NACK failed")),
+            }
+        }
         QueueCommand::Exists { q_name } => match queue.exists(&q_name).await {
             true => Response::Ok,
-            false => Response::Error("Queue not found".to_string()),
+            false => Response::Error(NexoError::not_found("Queue not found")),
         },
         QueueCommand::Delete { q_name } => match queue.delete_queue(q_name).await {
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
-        QueueCommand::PeekDLQ { q_name, limit, offset } => {
-            match queue.peek_dlq(&q_name, limit, offset).await {
+        QueueCommand::PeekDLQ { q_name, limit, offset, search } => {
+            let page = match search {
+                Some(search) => queue.search_dlq(&q_name, &search, limit, offset).await,
+                None => queue.peek_dlq(&q_name, limit, offset).await,
+            };
+            match page {
                 Ok((total, messages)) => Response::Data(PeekDlqResponse { total, messages }.to_wire()),
                 Err(e) => Response::Error(e),
             }
         }
-        QueueCommand::MoveToQueue { q_name, message_id } => {
-            match queue.move_to_queue(&q_name, message_id).await {
+        QueueCommand::MoveToQueue { q_name, message_id, options } => {
+            match queue.move_to_queue_with(&q_name, message_id, &options).await {
                 Ok(found) => Response::Data(BoolResponse { value: found }.to_wire()),
                 Err(e) => Response::Error(e),
             }
         }
+        QueueCommand::ReplayDLQ { q_name, search, options } => {
+            match queue.move_all_to_queue(&q_name, search.as_deref(), &options).await {
+                Ok(count) => Response::Data(CountResponse { count }.to_wire()),
+                Err(e) => Response::Error(e),
+            }
+        }
         QueueCommand::DeleteDLQ { q_name, message_id } => {
             match queue.delete_dlq(&q_name, message_id).await {
                 Ok(found) => Response::Data(BoolResponse { value: found }.to_wire()),
@@ -254,5 +412,37 @@ pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine)
             Ok(count) => Response::Data(CountResponse { count }.to_wire()),
             Err(e) => Response::Error(e),
         },
+        QueueCommand::PurgePriority { q_name, priority } => match queue.purge_priority(&q_name, priority).await {
+            Ok(count) => Response::Data(CountResponse { count }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
+        QueueCommand::FetchBlob { q_name, blob_id } => match queue.fetch_blob(&q_name, blob_id).await {
+            Ok(Some(blob)) => Response::Data(blob),
+            Ok(None) => Response::Null,
+            Err(e) => Response::Error(e),
+        },
+        QueueCommand::ListScheduled { q_name, from_ts, to_ts, limit } => {
+            match queue.list_scheduled(&q_name, from_ts, to_ts, limit) {
+                Ok(messages) => Response::Data(ScheduledResponse { messages }.to_wire()),
+                Err(e) => Response::Error(e),
+            }
+        }
+        QueueCommand::Reschedule { q_name, message_id, new_ts } => {
+            match queue.reschedule(&q_name, message_id, new_ts) {
+                Ok(found) => Response::Data(BoolResponse { value: found }.to_wire()),
+                Err(e) => Response::Error(e),
+            }
+        }
+        QueueCommand::Subscribe { q_name, options } => match queue.subscribe(&q_name, &client_id.0, options) {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
+        QueueCommand::Unsubscribe { q_name } => {
+            Response::Data(BoolResponse { value: queue.unsubscribe(&q_name, &client_id.0) }.to_wire())
+        }
+        QueueCommand::UnbindGroup { q_name, group } => match queue.unbind_group(&q_name, &group).await {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
     }
 }
@@ -0,0 +1,105 @@
+//! Webhook delivery: push-mode consumers for queues created with a webhook.
+//!
+//! Each webhook queue gets a small pool of workers that pop messages and POST
+//! them to the configured URL. A 2xx response acks the message; any other
+//! status, a timeout or a transport error nacks it with exponential backoff,
+//! so retries and dead-lettering go through the regular queue machinery.
+
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::brokers::queue::config::SystemQueueConfig;
+use crate::brokers::queue::domain::queue::{Message, WebhookConfig};
+use crate::brokers::queue::QueueManager;
+
+/// How long a worker parks on an empty queue before re-checking cancellation.
+const IDLE_WAIT_MS: u64 = 1000;
+
+pub fn spawn_workers(manager: QueueManager, queue: String, webhook: WebhookConfig, cancel: CancellationToken) {
+    for _ in 0..webhook.concurrency {
+        let worker = Worker {
+            manager: manager.clone(),
+            queue: queue.clone(),
+            webhook: webhook.clone(),
+        };
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = worker.run() => {}
+            }
+        });
+    }
+}
+
+struct Worker {
+    manager: QueueManager,
+    queue: String,
+    webhook: WebhookConfig,
+}
+
+impl Worker {
+    async fn run(self) {
+        loop {
+            let msgs = match self.manager.consume_batch(self.queue.clone(), Some(1), Some(IDLE_WAIT_MS)).await {
+                Ok(msgs) => msgs,
+                Err(_) => return, // queue deleted
+            };
+            for msg in msgs {
+                self.deliver(msg).await;
+            }
+        }
+    }
+
+    async fn deliver(&self, msg: Message) {
+        let payload = match self.manager.resolve_blob(&self.queue, msg.payload.clone()).await {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Queue '{}': cannot deliver {}: {}", self.queue, msg.id, e.message);
+                let delay = backoff_ms(self.manager.system_config(), msg.attempts);
+                self.manager.nack_after(&self.queue, msg.id, e.message, delay).await;
+                return;
+            }
+        };
+        let result = self.manager.http_client()
+            .post(&self.webhook.url)
+            .timeout(Duration::from_millis(self.webhook.timeout_ms))
+            .header("Content-Type", "application/octet-stream")
+            .header("X-Nexo-Queue", &self.queue)
+            .header("X-Nexo-Message-Id", msg.id.to_string())
+            .header("X-Nexo-Attempt", msg.attempts.to_string())
+            .body(payload)
+            .send()
+            .await;
+
+        let failure = match result {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => Some(format!("Webhook responded {}", response.status())),
+            Err(e) if e.is_timeout() => Some("Webhook timed out".to_string()),
+            Err(e) => Some(format!("Webhook request failed: {}", e)),
+        };
+
+        match failure {
+            None => {
+                self.manager.ack(&self.queue, msg.id).await;
+            }
+            Some(reason) => {
+                let delay = backoff_ms(self.manager.system_config(), msg.attempts);
+                debug!("Queue '{}': delivery of {} failed ({}), retrying in {}ms", self.queue, msg.id, reason, delay);
+                if !self.manager.nack_after(&self.queue, msg.id, reason, delay).await {
+                    warn!("Queue '{}': message {} vanished before nack", self.queue, msg.id);
+                }
+            }
+        }
+    }
+}
+
+/// Exponential backoff: base, 2x base, 4x base, ... capped at the configured max.
+fn backoff_ms(config: &SystemQueueConfig, attempts: u32) -> u64 {
+    let exponent = attempts.saturating_sub(1).min(20);
+    config.webhook_backoff_ms
+        .saturating_mul(1u64 << exponent)
+        .min(config.webhook_max_backoff_ms)
+}
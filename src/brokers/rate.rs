@@ -0,0 +1,41 @@
+//! Sliding one-minute rates for introspection: queue pushes and acks per
+//! priority and per consumer, stream publishes and bytes per topic.
+
+const RATE_BUCKET_MS: u64 = 5_000;
+const RATE_BUCKETS: usize = 12;
+
+/// Events counted in fixed buckets covering the last minute.
+#[derive(Debug, Default)]
+pub(crate) struct RateWindow {
+    buckets: [u64; RATE_BUCKETS],
+    /// Bucket number (ms / RATE_BUCKET_MS) of the newest bucket
+    head: u64,
+}
+
+impl RateWindow {
+    fn advance(&mut self, now: u64) {
+        let bucket = now / RATE_BUCKET_MS;
+        let gap = bucket.saturating_sub(self.head).min(RATE_BUCKETS as u64);
+        for step in 1..=gap {
+            self.buckets[((self.head + step) % RATE_BUCKETS as u64) as usize] = 0;
+        }
+        self.head = self.head.max(bucket);
+    }
+
+    pub(crate) fn record(&mut self, now: u64) {
+        self.record_many(now, 1);
+    }
+
+    /// Count `amount` events at once (e.g. the bytes of a publish).
+    pub(crate) fn record_many(&mut self, now: u64, amount: u64) {
+        self.advance(now);
+        let slot = (self.head % RATE_BUCKETS as u64) as usize;
+        self.buckets[slot] = self.buckets[slot].saturating_add(amount);
+    }
+
+    pub(crate) fn per_sec(&mut self, now: u64) -> f64 {
+        self.advance(now);
+        let total: u64 = self.buckets.iter().sum();
+        total as f64 / (RATE_BUCKET_MS * RATE_BUCKETS as u64 / 1000) as f64
+    }
+}
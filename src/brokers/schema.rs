@@ -0,0 +1,140 @@
+//! Payload schemas: versioned JSON Schemas attached to queues and stream topics.
+//!
+//! Every registration appends a new version; publishes are checked against the
+//! latest one. Each broker decides where a subject's history lives on disk
+//! (next to the queue database / inside the topic directory).
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use jsonschema::Validator;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::brokers::msgpack;
+use crate::brokers::error::{ErrorCode, NexoError};
+use crate::transport::tcp::protocol::{DATA_TYPE_JSON, DATA_TYPE_MSGPACK, DATA_TYPE_STRING};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaVersion {
+    pub version: u32,
+    pub schema: Value,
+    pub created_at: u64,
+}
+
+struct Subject {
+    versions: Vec<SchemaVersion>,
+    validator: Validator,
+}
+
+#[derive(Default)]
+pub struct SchemaRegistry {
+    subjects: DashMap<String, Arc<Subject>>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restore a subject's history from `file`. A missing file means no schema.
+    pub fn load(&self, name: &str, file: &Path) {
+        let Ok(data) = std::fs::read_to_string(file) else {
+            return;
+        };
+        let versions: Vec<SchemaVersion> = match serde_json::from_str(&data) {
+            Ok(versions) => versions,
+            Err(e) => {
+                warn!("Ignoring unreadable schema file {:?}: {}", file, e);
+                return;
+            }
+        };
+        let Some(latest) = versions.last() else {
+            return;
+        };
+        match jsonschema::validator_for(&latest.schema) {
+            Ok(validator) => {
+                self.subjects.insert(name.to_string(), Arc::new(Subject { versions, validator }));
+            }
+            Err(e) => warn!("Ignoring invalid schema for '{}' in {:?}: {}", name, file, e),
+        }
+    }
+
+    /// Register `schema` as the next version for `name` and persist the history to `file`.
+    pub fn register(&self, name: &str, schema: Value, file: &Path) -> Result<u32, NexoError> {
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|e| NexoError::invalid(format!("Invalid JSON Schema: {}", e)))?;
+
+        // Holding the entry serializes concurrent registrations for the same subject
+        let entry = self.subjects.entry(name.to_string());
+        let mut versions = match &entry {
+            Entry::Occupied(o) => o.get().versions.clone(),
+            Entry::Vacant(_) => Vec::new(),
+        };
+        let version = versions.last().map(|v| v.version + 1).unwrap_or(1);
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        versions.push(SchemaVersion { version, schema, created_at });
+
+        write_history(file, &versions)
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to persist schema: {}", e)))?;
+
+        entry.insert(Arc::new(Subject { versions, validator }));
+        Ok(version)
+    }
+
+    /// A specific version, or the latest when `version` is `None`.
+    pub fn get(&self, name: &str, version: Option<u32>) -> Option<SchemaVersion> {
+        let subject = self.subjects.get(name)?;
+        match version {
+            Some(version) => subject.versions.iter().find(|v| v.version == version).cloned(),
+            None => subject.versions.last().cloned(),
+        }
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.subjects.remove(name);
+    }
+
+    /// Check a wire payload (`[DataType][Data...]`) against the latest schema, if any.
+    pub fn validate(&self, name: &str, payload: &[u8]) -> Result<(), NexoError> {
+        let Some(subject) = self.subjects.get(name).map(|s| s.clone()) else {
+            return Ok(());
+        };
+
+        let instance = decode_json(payload)?;
+        let failures: Vec<String> = subject.validator.iter_errors(&instance)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                format!("{}: {}", if path.is_empty() { "/" } else { &path }, e)
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(NexoError::new(ErrorCode::SchemaViolation, format!("Schema validation failed at {}", failures.join("; "))))
+        }
+    }
+}
+
+fn decode_json(payload: &[u8]) -> Result<Value, NexoError> {
+    match payload.split_first() {
+        Some((&DATA_TYPE_JSON, content)) => serde_json::from_slice(content)
+            .map_err(|e| NexoError::new(ErrorCode::SchemaViolation, format!("Payload is not valid JSON: {}", e))),
+        Some((&DATA_TYPE_STRING, content)) => Ok(Value::String(String::from_utf8_lossy(content).into_owned())),
+        Some((&DATA_TYPE_MSGPACK, content)) => msgpack::decode(content)
+            .ok_or_else(|| NexoError::new(ErrorCode::SchemaViolation, "Payload is not valid MessagePack")),
+        _ => Err(NexoError::new(ErrorCode::SchemaViolation, "Payload must be JSON or MessagePack to be validated against the schema")),
+    }
+}
+
+fn write_history(file: &Path, versions: &[SchemaVersion]) -> std::io::Result<()> {
+    let data = serde_json::to_string_pretty(versions)?;
+    let tmp = file.with_extension("json.tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, file)
+}
@@ -1,16 +1,42 @@
 use std::env;
 
+use crate::brokers::store::domain::eviction::EvictionPolicy;
+
 #[derive(Debug, Clone)]
 pub struct StoreConfig {
+    /// Holds the intent log of store-and-publish writes
+    pub persistence_path: String,
     pub cleanup_interval_secs: u64,
+    /// TTL of keys set without one (0 = kept until deleted)
     pub default_ttl_secs: u64,
+    pub max_payload_bytes: usize,
+    /// Keys starting with one of these prefixes keep a version history
+    pub versioned_prefixes: Vec<String>,
+    /// Previous versions kept per versioned key
+    pub max_versions: usize,
+    /// Versions replaced longer ago than this are pruned (0 = count limit only)
+    pub version_retention_secs: u64,
+    /// Keys one MGET, MSET or MDEL may name
+    pub max_batch_keys: usize,
+    /// Estimated bytes the map may hold (0 = no cap)
+    pub max_memory_bytes: u64,
+    /// What a write does at the cap
+    pub eviction_policy: EvictionPolicy,
 }
 
 impl Default for StoreConfig {
     fn default() -> Self {
         Self {
+            persistence_path: "./data/store".to_string(),
             cleanup_interval_secs: 60,
             default_ttl_secs: 3600,
+            max_payload_bytes: 10485760, // 10MB
+            versioned_prefixes: Vec::new(),
+            max_versions: 10,
+            version_retention_secs: 0,
+            max_batch_keys: 1000,
+            max_memory_bytes: 0,
+            eviction_policy: EvictionPolicy::NoEviction,
         }
     }
 }
@@ -19,8 +45,20 @@ impl StoreConfig {
     pub fn load() -> Self {
         let default = Self::default();
         Self {
+            persistence_path: env::var("STORE_ROOT_PERSISTENCE_PATH").unwrap_or(default.persistence_path),
             cleanup_interval_secs: get_env("STORE_CLEANUP_INTERVAL_SECS", default.cleanup_interval_secs),
             default_ttl_secs: get_env("STORE_TTL_SECS", default.default_ttl_secs),
+            max_payload_bytes: get_env("STORE_MAX_PAYLOAD_BYTES", default.max_payload_bytes),
+            versioned_prefixes: env::var("STORE_VERSIONED_PREFIXES")
+                .map(|list| list.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+                .unwrap_or(default.versioned_prefixes),
+            max_versions: get_env("STORE_MAX_VERSIONS", default.max_versions),
+            version_retention_secs: get_env("STORE_VERSION_RETENTION_SECS", default.version_retention_secs),
+            max_batch_keys: get_env("STORE_MAX_BATCH_KEYS", default.max_batch_keys),
+            max_memory_bytes: get_env("STORE_MAX_MEMORY_BYTES", default.max_memory_bytes),
+            eviction_policy: env::var("STORE_EVICTION_POLICY")
+                .map(|policy| policy.parse().unwrap_or_else(|e| panic!("Config error: STORE_EVICTION_POLICY: {}", e)))
+                .unwrap_or(default.eviction_policy),
         }
     }
 }
@@ -0,0 +1,174 @@
+//! Store memory cap (`STORE_MAX_MEMORY_BYTES`) and what a write does when it
+//! would go past it (`STORE_EVICTION_POLICY`):
+//!
+//! - `noeviction`: the write is refused with `Busy` until keys expire or are
+//!   deleted.
+//! - `allkeys-lru`: least recently read or written keys go first.
+//! - `allkeys-lfu`: least often read or written keys go first (counted since
+//!   the key was created), least recent among equals.
+//! - `volatile-ttl`: only keys with an expiry go, soonest to expire first.
+//!   With none left, the write is refused as with `noeviction`.
+//!
+//! Keys are picked by sampling, as Redis does: of every `EVICTION_SAMPLES`
+//! keys looked at, the first in the policy's order goes, so the order above
+//! is approximate and a write never scans the whole map.
+//!
+//! Sizes are estimates: key and value bytes (every retained version of a
+//! versioned key) plus a fixed overhead per entry. An eviction frees down to
+//! `EVICT_TO_PERCENT` of the cap at once, so a full cache doesn't evict on
+//! every write.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::brokers::store::domain::map::Entry;
+
+/// Map slot, entry and allocator bookkeeping of one key
+pub const ENTRY_OVERHEAD: u64 = 96;
+/// Per retained version of a versioned key
+pub const VERSION_OVERHEAD: u64 = 24;
+pub const EVICT_TO_PERCENT: u64 = 95;
+/// Keys looked at per evicted key (Redis' `maxmemory-samples`)
+pub const EVICTION_SAMPLES: usize = 16;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    AllKeysLfu,
+    VolatileTtl,
+}
+
+impl EvictionPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+            Self::AllKeysLfu => "allkeys-lfu",
+            Self::VolatileTtl => "volatile-ttl",
+        }
+    }
+}
+
+impl std::str::FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "noeviction" => Ok(Self::NoEviction),
+            "allkeys-lru" => Ok(Self::AllKeysLru),
+            "allkeys-lfu" => Ok(Self::AllKeysLfu),
+            "volatile-ttl" => Ok(Self::VolatileTtl),
+            other => Err(format!("unknown eviction policy '{}' (noeviction, allkeys-lru, allkeys-lfu, volatile-ttl)", other)),
+        }
+    }
+}
+
+/// When a key was last used and how often, updated on reads without a write lock.
+#[derive(Debug)]
+pub struct Access {
+    last_ms: AtomicU64,
+    hits: AtomicU32,
+}
+
+impl Access {
+    pub fn new(now_ms: u64) -> Self {
+        Self { last_ms: AtomicU64::new(now_ms), hits: AtomicU32::new(1) }
+    }
+
+    pub fn touch(&self, now_ms: u64) {
+        self.last_ms.store(now_ms, Ordering::Relaxed);
+        let _ = self.hits.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |hits| hits.checked_add(1));
+    }
+
+    pub fn last_ms(&self) -> u64 {
+        self.last_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn hits(&self) -> u32 {
+        self.hits.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for Access {
+    fn clone(&self) -> Self {
+        Self { last_ms: AtomicU64::new(self.last_ms()), hits: AtomicU32::new(self.hits()) }
+    }
+}
+
+/// Estimated bytes `entry` holds under a key of `key_len` bytes.
+pub fn entry_size(key_len: usize, entry: &Entry) -> u64 {
+    let versions = entry.versions.as_ref()
+        .map(|versions| versions.iter().map(|v| v.value.len() as u64 + VERSION_OVERHEAD).sum::<u64>())
+        .unwrap_or(0);
+    // A versioned key's current value is also its newest version
+    let value = match versions {
+        0 => entry.value.0.len() as u64,
+        _ => 0,
+    };
+    ENTRY_OVERHEAD + key_len as u64 + value + versions
+}
+
+/// Eviction order under `policy`, lowest first; `None` = never evicted.
+/// `remaining_ms` is the time left before the key expires, if it does.
+pub fn rank(policy: EvictionPolicy, entry: &Entry, remaining_ms: Option<u64>) -> Option<(u64, u64)> {
+    match policy {
+        EvictionPolicy::NoEviction => None,
+        EvictionPolicy::AllKeysLru => Some((entry.access.last_ms(), 0)),
+        EvictionPolicy::AllKeysLfu => Some((entry.access.hits() as u64, entry.access.last_ms())),
+        EvictionPolicy::VolatileTtl => remaining_ms.map(|ms| (ms, 0)),
+    }
+}
+
+/// Bytes held and eviction counters, shared by the clones of a map.
+#[derive(Debug, Default)]
+pub struct Usage {
+    bytes: AtomicU64,
+    evicted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Usage {
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Account for an entry going from `old` to `new` bytes.
+    pub fn resize(&self, old: u64, new: u64) {
+        match new >= old {
+            true => self.bytes.fetch_add(new - old, Ordering::Relaxed),
+            false => self.bytes.fetch_sub(old - new, Ordering::Relaxed),
+        };
+    }
+
+    pub fn record_evicted(&self) {
+        self.evicted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, max_bytes: u64, policy: EvictionPolicy) -> StoreMemory {
+        StoreMemory {
+            used_bytes: self.bytes(),
+            max_bytes,
+            policy,
+            evicted_keys: self.evicted.load(Ordering::Relaxed),
+            rejected_writes: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StoreMemory {
+    /// Estimated, see `entry_size`
+    pub used_bytes: u64,
+    /// 0 = no cap
+    pub max_bytes: u64,
+    pub policy: EvictionPolicy,
+    /// Since start
+    pub evicted_keys: u64,
+    pub rejected_writes: u64,
+}
+
@@ -0,0 +1,180 @@
+//! Expirable exclusive locks with fencing tokens.
+//!
+//! Every successful acquisition gets a token from a counter owned by the lock
+//! name, so tokens only ever grow for a given lock. A holder passes its token
+//! to the resource it protects; the resource rejects writes carrying a token
+//! lower than one it has already seen, which fences off a holder whose lock
+//! expired while it was paused. Counters outlive releases and expiries, but
+//! not a restart: the store is in memory.
+
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use crate::brokers::clock::SharedClock;
+use crate::brokers::error::{ErrorCode, NexoError};
+
+/// Longest lease a lock (or semaphore slot) may be taken or extended for: one
+/// day. Holders needing longer keep extending.
+pub const MAX_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Default)]
+struct LockSlot {
+    /// Last token handed out for this lock
+    fence: u64,
+    holder: Option<Holder>,
+}
+
+#[derive(Debug, Clone)]
+struct Holder {
+    owner: String,
+    token: u64,
+    acquired_at: u64,
+    expires_at: Instant,
+}
+
+impl Holder {
+    fn is_live(&self, now: Instant) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// Result of a successful LOCK.
+#[derive(Debug, Clone, Copy)]
+pub struct LockGrant {
+    pub token: u64,
+    /// ms since epoch
+    pub expires_at: u64,
+}
+
+/// A currently held lock, for introspection.
+#[derive(Debug, Clone)]
+pub struct HeldLock {
+    pub name: String,
+    pub owner: String,
+    pub token: u64,
+    /// ms since epoch
+    pub acquired_at: u64,
+    /// ms since epoch
+    pub expires_at: u64,
+}
+
+#[derive(Clone)]
+pub struct LockStore {
+    inner: Arc<DashMap<String, LockSlot>>,
+    clock: SharedClock,
+}
+
+impl LockStore {
+    pub fn new(cleanup_interval_secs: u64, clock: SharedClock) -> Self {
+        let inner: Arc<DashMap<String, LockSlot>> = Arc::new(DashMap::new());
+
+        // Same sweep as the map TTL cleanup: expired holders are cleared, the slot
+        // (and its fence counter) stays
+        let weak_inner = Arc::downgrade(&inner);
+        let sweep_clock = clock.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(cleanup_interval_secs));
+            loop {
+                interval.tick().await;
+
+                match weak_inner.upgrade() {
+                    Some(locks) => {
+                        let now = sweep_clock.now();
+                        for mut slot in locks.iter_mut() {
+                            if slot.holder.as_ref().is_some_and(|h| !h.is_live(now)) {
+                                slot.holder = None;
+                            }
+                        }
+                    }
+                    None => {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self { inner, clock }
+    }
+
+    /// Take the lock for `ttl_ms`. `None` while another holder's lease is live;
+    /// the lock is not re-entrant, the current holder must EXTEND instead.
+    pub fn acquire(&self, name: &str, owner: &str, ttl_ms: u64) -> Result<Option<LockGrant>, NexoError> {
+        let ttl = lease(ttl_ms)?;
+        let now = self.clock.now();
+        let mut slot = self.inner.entry(name.to_string()).or_default();
+        if slot.holder.as_ref().is_some_and(|h| h.is_live(now)) {
+            return Ok(None);
+        }
+
+        slot.fence += 1;
+        let token = slot.fence;
+        slot.holder = Some(Holder {
+            owner: owner.to_string(),
+            token,
+            acquired_at: self.clock.now_ms(),
+            expires_at: now + ttl,
+        });
+        Ok(Some(LockGrant { token, expires_at: self.clock.now_ms().saturating_add(ttl_ms) }))
+    }
+
+    /// Release the lock, only if `token` is the live holder's. A lease that already
+    /// expired cannot be released: someone else may hold the lock by now.
+    pub fn release(&self, name: &str, token: u64) -> Result<(), NexoError> {
+        let mut slot = self.inner.get_mut(name).ok_or_else(|| not_holder(name))?;
+        match &slot.holder {
+            Some(holder) if holder.token == token && holder.is_live(self.clock.now()) => {
+                slot.holder = None;
+                Ok(())
+            }
+            _ => Err(not_holder(name)),
+        }
+    }
+
+    /// Push the live holder's expiry to `ttl_ms` from now; the token is unchanged.
+    pub fn extend(&self, name: &str, token: u64, ttl_ms: u64) -> Result<u64, NexoError> {
+        let ttl = lease(ttl_ms)?;
+        let now = self.clock.now();
+        let mut slot = self.inner.get_mut(name).ok_or_else(|| not_holder(name))?;
+        match slot.holder.as_mut() {
+            Some(holder) if holder.token == token && holder.is_live(now) => {
+                holder.expires_at = now + ttl;
+                Ok(self.clock.now_ms().saturating_add(ttl_ms))
+            }
+            _ => Err(not_holder(name)),
+        }
+    }
+
+    /// Locks with a live holder, sorted by name.
+    pub fn held(&self) -> Vec<HeldLock> {
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        let mut locks: Vec<HeldLock> = self.inner.iter()
+            .filter_map(|slot| {
+                let holder = slot.holder.as_ref().filter(|h| h.is_live(now))?;
+                Some(HeldLock {
+                    name: slot.key().clone(),
+                    owner: holder.owner.clone(),
+                    token: holder.token,
+                    acquired_at: holder.acquired_at,
+                    expires_at: now_ms + holder.expires_at.saturating_duration_since(now).as_millis() as u64,
+                })
+            })
+            .collect();
+        locks.sort_by(|a, b| a.name.cmp(&b.name));
+        locks
+    }
+}
+
+fn lease(ttl_ms: u64) -> Result<Duration, NexoError> {
+    match ttl_ms {
+        0 => Err(NexoError::invalid("Lock TTL must be greater than 0")),
+        ms if ms > MAX_TTL_MS => Err(NexoError::invalid(format!("Lock TTL must be at most {} ms", MAX_TTL_MS))),
+        ms => Ok(Duration::from_millis(ms)),
+    }
+}
+
+fn not_holder(name: &str) -> NexoError {
+    NexoError::new(ErrorCode::NotOwner, format!("Lock '{}' is not held with this token", name))
+}
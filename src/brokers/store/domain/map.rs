@@ -1,33 +1,66 @@
+use dashmap::mapref::entry::Entry as Slot;
 use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time;
+use crate::brokers::clock::SharedClock;
 use crate::brokers::store::config::StoreConfig;
+use crate::brokers::store::domain::eviction::{self, Access, EvictionPolicy, StoreMemory, Usage};
+use crate::brokers::error::{ErrorCode, NexoError};
 use bytes::Bytes;
 
 #[derive(Clone, Debug)]
 pub struct Entry {
     pub value: MapValue,
     pub expires_at: Option<Instant>,
+    /// Set only for keys under a versioned prefix: oldest first, the current value last
+    pub versions: Option<VecDeque<Version>>,
+    pub access: Access,
+    /// Estimated bytes, as counted in the map's usage
+    size: u64,
+}
+
+impl Entry {
+    /// Expired from its expiry instant on: every check uses this boundary.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expiry| expiry <= now)
+    }
+}
+
+/// A value as it was written by one SET, with its write time (ms since epoch).
+#[derive(Clone, Debug)]
+pub struct Version {
+    pub value: Bytes,
+    pub set_at: u64,
 }
 
 #[derive(Clone)]
 pub struct MapStore {
     inner: Arc<DashMap<String, Entry>>,
     config: Arc<StoreConfig>,
+    clock: SharedClock,
+    usage: Arc<Usage>,
+    /// One eviction at a time; where the last one stopped sampling
+    evicting: Arc<Mutex<usize>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct MapValue(pub Bytes);
 
 impl MapStore {
-    pub fn new(config: Arc<StoreConfig>) -> Self {
+    pub fn new(config: Arc<StoreConfig>, clock: SharedClock) -> Self {
         let inner = Arc::new(DashMap::new());
 
         // Weak reference for the cleanup thread
         // This prevents the thread from keeping the store domain alive if the StoreManager is dropped
         let weak_inner = Arc::downgrade(&inner);
         let cleanup_interval = config.cleanup_interval_secs;
+        let version_retention_ms = config.version_retention_secs * 1000;
+        let sweep_clock = clock.clone();
+        let usage = Arc::new(Usage::default());
+        let sweep_usage = usage.clone();
 
         tokio::spawn(async move {
             let mut interval = time::interval(Duration::from_secs(cleanup_interval));
@@ -36,10 +69,16 @@ impl MapStore {
 
                 match weak_inner.upgrade() {
                     Some(map) => {
-                        let now = Instant::now();
-                        map.retain(|_, entry: &mut Entry| {
-                            if let Some(expiry) = entry.expires_at {
-                                return expiry > now;
+                        let now = sweep_clock.now();
+                        let now_ms = sweep_clock.now_ms();
+                        map.retain(|key: &String, entry: &mut Entry| {
+                            if entry.is_expired(now) {
+                                sweep_usage.resize(entry.size, 0);
+                                return false;
+                            }
+                            if let (Some(versions), true) = (entry.versions.as_mut(), version_retention_ms > 0) {
+                                prune_by_age(versions, now_ms.saturating_sub(version_retention_ms));
+                                resize(&sweep_usage, key.len(), entry);
                             }
                             true
                         });
@@ -51,38 +90,296 @@ impl MapStore {
             }
         });
 
-        Self { inner, config }
+        Self { inner, config, clock, usage, evicting: Arc::new(Mutex::new(0)) }
     }
 
-    pub fn set(&self, key: String, value: Bytes, ttl: Option<u64>) {
+    /// Set the key for `ttl` seconds, or the default TTL without one (a
+    /// default of 0 keeps it until deleted). Fails only when the memory cap
+    /// is reached and nothing may be evicted.
+    pub fn set(&self, key: String, value: Bytes, ttl: Option<u64>) -> Result<(), NexoError> {
         let expires_at = match ttl {
-            Some(0) | None => {
-                Some(Instant::now() + Duration::from_secs(self.config.default_ttl_secs))
+            Some(0) | None => match self.config.default_ttl_secs {
+                0 => None,
+                secs => Some(self.clock.now() + Duration::from_secs(secs)),
+            },
+            Some(secs) => Some(self.clock.now() + Duration::from_secs(secs)),
+        };
+        self.write(key, value, expires_at)
+    }
+
+    /// Set the key with no expiry, whatever the default TTL.
+    pub fn set_persistent(&self, key: String, value: Bytes) -> Result<(), NexoError> {
+        self.write(key, value, None)
+    }
+
+    fn write(&self, key: String, value: Bytes, expires_at: Option<Instant>) -> Result<(), NexoError> {
+        self.admit(&key, value.len())?;
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        let versioned = self.is_versioned(&key);
+        let version = Version { value: value.clone(), set_at: now_ms };
+        let max_versions = self.config.max_versions;
+
+        match self.inner.entry(key) {
+            Slot::Occupied(mut slot) => {
+                let key_len = slot.key().len();
+                let entry = slot.get_mut();
+                // An expired key starts afresh
+                let expired = entry.is_expired(now);
+                if versioned {
+                    let versions = entry.versions.get_or_insert_with(VecDeque::new);
+                    if expired {
+                        versions.clear();
+                    }
+                    versions.push_back(version);
+                    while versions.len() > max_versions + 1 {
+                        versions.pop_front();
+                    }
+                }
+                entry.value = MapValue(value);
+                entry.expires_at = expires_at;
+                match expired {
+                    true => entry.access = Access::new(now_ms),
+                    false => entry.access.touch(now_ms),
+                }
+                resize(&self.usage, key_len, entry);
             }
-            Some(secs) => Some(Instant::now() + Duration::from_secs(secs)),
+            Slot::Vacant(slot) => {
+                let mut entry = Entry {
+                    value: MapValue(value),
+                    expires_at,
+                    versions: versioned.then(|| VecDeque::from([version])),
+                    access: Access::new(now_ms),
+                    size: 0,
+                };
+                resize(&self.usage, slot.key().len(), &mut entry);
+                slot.insert(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Make room for a write of about this size under the memory cap,
+    /// evicting keys as the policy allows.
+    pub fn admit(&self, key: &str, value_len: usize) -> Result<(), NexoError> {
+        let max = self.config.max_memory_bytes;
+        if max == 0 {
+            return Ok(());
+        }
+        // Overwriting a plain key frees what it held; a versioned one keeps it
+        let replaced = match self.is_versioned(key) {
+            true => 0,
+            false => self.inner.get(key).map_or(0, |entry| entry.size),
         };
+        let needed = (eviction::ENTRY_OVERHEAD + (key.len() + value_len) as u64).saturating_sub(replaced);
+        if self.usage.bytes() + needed <= max {
+            return Ok(());
+        }
+        let policy = self.config.eviction_policy;
+        if policy != EvictionPolicy::NoEviction {
+            let mut cursor = self.evicting.lock();
+            if self.usage.bytes() + needed > max {
+                self.evict(&mut cursor, (max * eviction::EVICT_TO_PERCENT / 100).saturating_sub(needed), policy);
+            }
+        }
+        if self.usage.bytes() + needed <= max {
+            return Ok(());
+        }
+        self.usage.record_rejected();
+        Err(NexoError::new(ErrorCode::Busy, format!(
+            "Store memory limit reached ({} bytes, policy {}): the write is refused until keys expire or are deleted",
+            max, policy.as_str()
+        )))
+    }
 
-        self.inner.insert(key, Entry {
-            value: MapValue(value),
-            expires_at,
-        });
+    /// Remove sampled keys until usage is down to `target`, resuming the
+    /// sampling at `cursor`. Stops early when no key may go.
+    fn evict(&self, cursor: &mut usize, target: u64, policy: EvictionPolicy) {
+        while self.usage.bytes() > target {
+            let victims = self.sample_victims(cursor, self.usage.bytes() - target, policy);
+            if victims.is_empty() {
+                return;
+            }
+            for (live, key) in victims {
+                if let Some((_, entry)) = self.inner.remove(&key) {
+                    self.usage.resize(entry.size, 0);
+                    if live {
+                        self.usage.record_evicted();
+                    }
+                }
+            }
+        }
     }
 
-    pub fn get(&self, key: &str) -> Option<Bytes> {
-        if let Some(entry) = self.inner.get(key) {
-            if let Some(expiry) = entry.expires_at {
-                if Instant::now() > expiry {
-                    return None;
+    /// Walk the map from `cursor` in groups of `EVICTION_SAMPLES` keys,
+    /// taking the first of each group in the policy's order, until they add
+    /// up to `excess` bytes or every key was looked at once. Expired keys not
+    /// swept yet are all taken, and flagged as not live (not counted as evicted).
+    fn sample_victims(&self, cursor: &mut usize, excess: u64, policy: EvictionPolicy) -> Vec<(bool, String)> {
+        let len = self.inner.len();
+        if len == 0 {
+            return Vec::new();
+        }
+        let now = self.clock.now();
+        let start = *cursor % len;
+        let mut victims = Vec::new();
+        let mut freed = 0;
+        let mut best: Option<((u64, u64), String, u64)> = None;
+        let mut seen = 0;
+        for item in self.inner.iter().skip(start).chain(self.inner.iter().take(start)) {
+            seen += 1;
+            let entry = item.value();
+            if entry.is_expired(now) {
+                victims.push((false, item.key().clone()));
+                freed += entry.size;
+            } else {
+                let remaining = entry.expires_at.map(|expiry| expiry.saturating_duration_since(now).as_millis() as u64);
+                if let Some(rank) = eviction::rank(policy, entry, remaining) {
+                    if best.as_ref().is_none_or(|(first, _, _)| rank < *first) {
+                        best = Some((rank, item.key().clone(), entry.size));
+                    }
+                }
+            }
+            if seen % eviction::EVICTION_SAMPLES == 0 {
+                if let Some((_, key, size)) = best.take() {
+                    victims.push((true, key));
+                    freed += size;
                 }
             }
-            let MapValue(val) = &entry.value;
-            return Some(val.clone());
+            if freed >= excess {
+                break;
+            }
+        }
+        if let Some((_, key, _)) = best {
+            victims.push((true, key));
         }
-        None
+        *cursor = start + seen;
+        victims
+    }
+
+    pub fn memory(&self) -> StoreMemory {
+        self.usage.snapshot(self.config.max_memory_bytes, self.config.eviction_policy)
+    }
+
+    pub fn is_versioned(&self, key: &str) -> bool {
+        self.config.versioned_prefixes.iter().any(|prefix| key.starts_with(prefix.as_str()))
+    }
+
+    /// The value the key held at `at` (ms since epoch). `None` if the key did
+    /// not exist yet, or the version live at that time was pruned.
+    pub fn get_at(&self, key: &str, at: u64) -> Option<Bytes> {
+        let entry = self.live_entry(key)?;
+        entry.access.touch(self.clock.now_ms());
+        let versions = entry.versions.as_ref()?;
+        versions.iter().rev().find(|v| v.set_at <= at).map(|v| v.value.clone())
+    }
+
+    /// Retained versions of a key, newest first.
+    pub fn history(&self, key: &str, limit: usize) -> Vec<Version> {
+        let Some(entry) = self.live_entry(key) else {
+            return Vec::new();
+        };
+        entry.access.touch(self.clock.now_ms());
+        entry.versions.as_ref()
+            .map(|versions| versions.iter().rev().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn live_entry(&self, key: &str) -> Option<dashmap::mapref::one::Ref<'_, String, Entry>> {
+        let entry = self.inner.get(key)?;
+        match entry.is_expired(self.clock.now()) {
+            true => None,
+            false => Some(entry),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Bytes> {
+        let entry = self.live_entry(key)?;
+        entry.access.touch(self.clock.now_ms());
+        let MapValue(val) = &entry.value;
+        Some(val.clone())
     }
 
     pub fn del(&self, key: &str) -> bool {
-        self.inner.remove(key).is_some()
+        match self.inner.remove(key) {
+            Some((_, entry)) => {
+                self.usage.resize(entry.size, 0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Time left before the key expires: `None` if it does not exist,
+    /// `Some(None)` if it never expires.
+    pub fn ttl(&self, key: &str) -> Option<Option<Duration>> {
+        let entry = self.live_entry(key)?;
+        Some(entry.expires_at.map(|expiry| expiry.saturating_duration_since(self.clock.now())))
+    }
+
+    /// Make the key expire `ttl` from now, keeping its value (and history).
+    /// False if it does not exist.
+    pub fn expire(&self, key: &str, ttl: Duration) -> bool {
+        self.set_expiry(key, Some(self.clock.now() + ttl))
+    }
+
+    /// Drop the key's expiry. False if it does not exist.
+    pub fn persist(&self, key: &str) -> bool {
+        self.set_expiry(key, None)
+    }
+
+    fn set_expiry(&self, key: &str, expires_at: Option<Instant>) -> bool {
+        let Some(mut entry) = self.inner.get_mut(key) else {
+            return false;
+        };
+        if entry.is_expired(self.clock.now()) {
+            return false;
+        }
+        entry.expires_at = expires_at;
+        true
+    }
+
+    /// Values of `keys`, in order (`None` for missing or expired keys).
+    pub fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Bytes>>, NexoError> {
+        self.check_batch(keys.len())?;
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        Ok(keys.iter().map(|key| {
+            let entry = self.inner.get(key)?;
+            match entry.is_expired(now) {
+                true => None,
+                false => {
+                    entry.access.touch(now_ms);
+                    Some(entry.value.0.clone())
+                }
+            }
+        }).collect())
+    }
+
+    /// Set every pair with the same TTL, as many `set` (or `set_persistent`)
+    /// calls would: a write refused by the memory cap stops the rest.
+    pub fn set_many(&self, entries: Vec<(String, Bytes)>, ttl: Option<u64>, keep_forever: bool) -> Result<(), NexoError> {
+        self.check_batch(entries.len())?;
+        for (key, value) in entries {
+            match keep_forever {
+                true => self.set_persistent(key, value)?,
+                false => self.set(key, value, ttl)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// For each key, in order, whether it existed.
+    pub fn del_many(&self, keys: &[String]) -> Result<Vec<bool>, NexoError> {
+        self.check_batch(keys.len())?;
+        Ok(keys.iter().map(|key| self.del(key)).collect())
+    }
+
+    fn check_batch(&self, len: usize) -> Result<(), NexoError> {
+        match len > self.config.max_batch_keys {
+            true => Err(NexoError::invalid(format!("Too many keys in one request: {} (max {})", len, self.config.max_batch_keys))),
+            false => Ok(()),
+        }
     }
 
     pub fn iter(&self) -> dashmap::iter::Iter<'_, String, Entry> {
@@ -93,3 +390,18 @@ impl MapStore {
         self.inner.len()
     }
 }
+
+/// Count `entry` at its current estimated size.
+fn resize(usage: &Usage, key_len: usize, entry: &mut Entry) {
+    let size = eviction::entry_size(key_len, entry);
+    usage.resize(entry.size, size);
+    entry.size = size;
+}
+
+/// Drop versions already replaced before `cutoff_ms`, so reads at any time
+/// after the cutoff still resolve. The current value always stays.
+fn prune_by_age(versions: &mut VecDeque<Version>, cutoff_ms: u64) {
+    while versions.get(1).is_some_and(|next| next.set_at <= cutoff_ms) {
+        versions.pop_front();
+    }
+}
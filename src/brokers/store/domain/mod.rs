@@ -1 +1,4 @@
 pub mod map;
+pub mod eviction;
+pub mod lock;
+pub mod semaphore;
@@ -0,0 +1,216 @@
+//! Counting semaphores: up to `max` concurrent leases per name.
+//!
+//! Leases belong to the connection that took them and end on RELEASE, on TTL
+//! expiry or when that connection goes away. A semaphore exists only while it
+//! has holders; its `max` is fixed by the first acquirer until then.
+
+use dashmap::DashMap;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use crate::brokers::clock::SharedClock;
+use crate::brokers::error::{ErrorCode, NexoError};
+use crate::brokers::store::domain::lock::MAX_TTL_MS;
+
+#[derive(Debug)]
+struct Semaphore {
+    max: u32,
+    next_lease: u64,
+    leases: BTreeMap<u64, Lease>,
+}
+
+#[derive(Debug, Clone)]
+struct Lease {
+    holder: String,
+    acquired_at: u64,
+    expires_at: Instant,
+}
+
+impl Semaphore {
+    fn drop_expired(&mut self, now: Instant) {
+        self.leases.retain(|_, lease| lease.expires_at > now);
+    }
+}
+
+/// Result of a successful ACQUIRE.
+#[derive(Debug, Clone, Copy)]
+pub struct SemaphoreGrant {
+    pub lease: u64,
+    /// ms since epoch
+    pub expires_at: u64,
+}
+
+/// A semaphore with live leases, for introspection.
+#[derive(Debug, Clone)]
+pub struct SemaphoreSnapshot {
+    pub name: String,
+    pub max: u32,
+    pub holders: Vec<LeaseSnapshot>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaseSnapshot {
+    pub lease: u64,
+    pub holder: String,
+    /// ms since epoch
+    pub acquired_at: u64,
+    /// ms since epoch
+    pub expires_at: u64,
+}
+
+#[derive(Clone)]
+pub struct SemaphoreStore {
+    inner: Arc<DashMap<String, Semaphore>>,
+    clock: SharedClock,
+}
+
+impl SemaphoreStore {
+    pub fn new(cleanup_interval_secs: u64, clock: SharedClock) -> Self {
+        let inner: Arc<DashMap<String, Semaphore>> = Arc::new(DashMap::new());
+
+        let weak_inner = Arc::downgrade(&inner);
+        let sweep_clock = clock.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(cleanup_interval_secs));
+            loop {
+                interval.tick().await;
+
+                match weak_inner.upgrade() {
+                    Some(semaphores) => {
+                        let now = sweep_clock.now();
+                        semaphores.retain(|_, sem: &mut Semaphore| {
+                            sem.drop_expired(now);
+                            !sem.leases.is_empty()
+                        });
+                    }
+                    None => {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self { inner, clock }
+    }
+
+    /// Take one of `max` slots for `ttl_ms`. `None` while all slots are leased.
+    pub fn acquire(&self, name: &str, holder: &str, max: u32, ttl_ms: u64) -> Result<Option<SemaphoreGrant>, NexoError> {
+        if max == 0 {
+            return Err(NexoError::invalid("Semaphore max must be greater than 0"));
+        }
+        check_ttl(ttl_ms)?;
+
+        let now = self.clock.now();
+        let mut sem = self.inner.entry(name.to_string()).or_insert_with(|| Semaphore {
+            max,
+            next_lease: 0,
+            leases: BTreeMap::new(),
+        });
+        sem.drop_expired(now);
+        if sem.leases.is_empty() {
+            sem.max = max;
+        } else if sem.max != max {
+            return Err(NexoError::invalid(format!(
+                "Semaphore '{}' is held with max {}, not {}", name, sem.max, max
+            )));
+        }
+        if sem.leases.len() >= sem.max as usize {
+            return Ok(None);
+        }
+
+        sem.next_lease += 1;
+        let lease = sem.next_lease;
+        sem.leases.insert(lease, Lease {
+            holder: holder.to_string(),
+            acquired_at: self.clock.now_ms(),
+            expires_at: now + Duration::from_millis(ttl_ms),
+        });
+        Ok(Some(SemaphoreGrant { lease, expires_at: self.clock.now_ms().saturating_add(ttl_ms) }))
+    }
+
+    /// End a live lease taken by `holder`.
+    pub fn release(&self, name: &str, holder: &str, lease: u64) -> Result<(), NexoError> {
+        let mut sem = self.inner.get_mut(name).ok_or_else(|| not_holder(name))?;
+        sem.drop_expired(self.clock.now());
+        match sem.leases.get(&lease) {
+            Some(held) if held.holder == holder => {
+                sem.leases.remove(&lease);
+            }
+            _ => return Err(not_holder(name)),
+        }
+        let empty = sem.leases.is_empty();
+        drop(sem);
+        if empty {
+            self.inner.remove_if(name, |_, sem| sem.leases.is_empty());
+        }
+        Ok(())
+    }
+
+    /// Push a live lease's expiry to `ttl_ms` from now.
+    pub fn extend(&self, name: &str, holder: &str, lease: u64, ttl_ms: u64) -> Result<u64, NexoError> {
+        check_ttl(ttl_ms)?;
+        let now = self.clock.now();
+        let mut sem = self.inner.get_mut(name).ok_or_else(|| not_holder(name))?;
+        sem.drop_expired(now);
+        match sem.leases.get_mut(&lease) {
+            Some(held) if held.holder == holder => {
+                held.expires_at = now + Duration::from_millis(ttl_ms);
+                Ok(self.clock.now_ms().saturating_add(ttl_ms))
+            }
+            _ => Err(not_holder(name)),
+        }
+    }
+
+    /// Whether `holder` still has a lease on the semaphore.
+    pub fn holds(&self, name: &str, holder: &str) -> bool {
+        let now = self.clock.now();
+        self.inner.get(name)
+            .is_some_and(|sem| sem.leases.values().any(|l| l.holder == holder && l.expires_at > now))
+    }
+
+    /// End every lease `holder` has on the given semaphores (connection closed).
+    pub fn release_holder(&self, holder: &str, names: &[String]) {
+        for name in names {
+            self.inner.remove_if_mut(name, |_, sem| {
+                sem.leases.retain(|_, lease| lease.holder != holder);
+                sem.leases.is_empty()
+            });
+        }
+    }
+
+    /// Semaphores with live leases, sorted by name.
+    pub fn list(&self) -> Vec<SemaphoreSnapshot> {
+        let now = self.clock.now();
+        let now_ms = self.clock.now_ms();
+        let mut list: Vec<SemaphoreSnapshot> = self.inner.iter()
+            .filter_map(|sem| {
+                let holders: Vec<LeaseSnapshot> = sem.leases.iter()
+                    .filter(|(_, lease)| lease.expires_at > now)
+                    .map(|(id, lease)| LeaseSnapshot {
+                        lease: *id,
+                        holder: lease.holder.clone(),
+                        acquired_at: lease.acquired_at,
+                        expires_at: now_ms + lease.expires_at.saturating_duration_since(now).as_millis() as u64,
+                    })
+                    .collect();
+                (!holders.is_empty()).then(|| SemaphoreSnapshot { name: sem.key().clone(), max: sem.max, holders })
+            })
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+}
+
+fn check_ttl(ttl_ms: u64) -> Result<(), NexoError> {
+    match ttl_ms {
+        0 => Err(NexoError::invalid("Semaphore TTL must be greater than 0")),
+        ms if ms > MAX_TTL_MS => Err(NexoError::invalid(format!("Semaphore TTL must be at most {} ms", MAX_TTL_MS))),
+        _ => Ok(()),
+    }
+}
+
+fn not_holder(name: &str) -> NexoError {
+    NexoError::new(ErrorCode::NotOwner, format!("No such lease on semaphore '{}'", name))
+}
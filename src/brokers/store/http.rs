@@ -7,8 +7,9 @@ use axum::Router;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::brokers::store::domain::eviction::StoreMemory;
 use crate::brokers::store::snapshot::StoreSnapshot;
-use crate::transport::http::payload::payload_to_json_value;
+use crate::brokers::payload::render_payload;
 use crate::NexoEngine;
 
 // ==========================================
@@ -21,13 +22,64 @@ pub struct StoreBrokerSnapshot {
     pub total: usize,
     pub offset: usize,
     pub limit: usize,
+    pub memory: StoreMemoryDetail,
+}
+
+#[derive(Serialize)]
+pub struct StoreMemoryDetail {
+    /// Estimated
+    pub used_bytes: u64,
+    /// `None` without a cap
+    pub max_bytes: Option<u64>,
+    pub policy: &'static str,
+    pub evicted_keys: u64,
+    pub rejected_writes: u64,
+}
+
+impl From<StoreMemory> for StoreMemoryDetail {
+    fn from(m: StoreMemory) -> Self {
+        Self {
+            used_bytes: m.used_bytes,
+            max_bytes: (m.max_bytes > 0).then_some(m.max_bytes),
+            policy: m.policy.as_str(),
+            evicted_keys: m.evicted_keys,
+            rejected_writes: m.rejected_writes,
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct KeyDetail {
     pub key: String,
     pub value: Value,
-    pub exp_at: String,
+    pub content_type: &'static str,
+    /// `None` for a key kept until deleted
+    pub exp_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LockDetail {
+    pub name: String,
+    pub owner: String,
+    pub token: u64,
+    pub acquired_at: String,
+    pub expires_at: String,
+}
+
+#[derive(Serialize)]
+pub struct SemaphoreDetail {
+    pub name: String,
+    pub max: u32,
+    pub holders: Vec<LeaseDetail>,
+}
+
+#[derive(Serialize)]
+pub struct LeaseDetail {
+    pub lease: u64,
+    /// Connection id
+    pub holder: String,
+    pub acquired_at: String,
+    pub expires_at: String,
 }
 
 #[derive(Deserialize)]
@@ -49,30 +101,73 @@ async fn get_store(
     let offset = params.offset.unwrap_or(0);
     let filter = params.search;
 
-    let StoreSnapshot { entries, total } = engine.store.scan(limit, offset, filter);
-    let now = std::time::Instant::now();
+    let StoreSnapshot { entries, total, memory } = engine.store.scan(limit, offset, filter);
+    let clock = engine.store.clock();
+    let now = clock.now();
 
     let keys = entries
         .into_iter()
         .map(|entry| {
-            let exp_at = match entry.expires_at {
-                Some(expiry) => {
-                    let sys_now = std::time::SystemTime::now();
-                    let dur = expiry.saturating_duration_since(now);
-                    let future_sys = sys_now + dur;
-                    chrono::DateTime::<chrono::Utc>::from(future_sys).to_rfc3339()
-                }
-                None => "Never".to_string(),
-            };
+            let exp_at = entry.expires_at.map(|expiry| {
+                let dur = expiry.saturating_duration_since(now);
+                to_rfc3339(clock.now_ms() + dur.as_millis() as u64)
+            });
+            let (value, content_type) = render_payload(&entry.payload);
             KeyDetail {
                 key: entry.key,
-                value: payload_to_json_value(&entry.payload),
+                value,
+                content_type,
                 exp_at,
             }
         })
         .collect();
 
-    axum::Json(StoreBrokerSnapshot { keys, total, offset, limit })
+    axum::Json(StoreBrokerSnapshot { keys, total, offset, limit, memory: memory.into() })
+}
+
+async fn get_locks(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    let locks: Vec<LockDetail> = engine
+        .store
+        .locks
+        .held()
+        .into_iter()
+        .map(|lock| LockDetail {
+            name: lock.name,
+            owner: lock.owner,
+            token: lock.token,
+            acquired_at: to_rfc3339(lock.acquired_at),
+            expires_at: to_rfc3339(lock.expires_at),
+        })
+        .collect();
+
+    axum::Json(locks)
+}
+
+async fn get_semaphores(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    let semaphores: Vec<SemaphoreDetail> = engine
+        .store
+        .semaphores
+        .list()
+        .into_iter()
+        .map(|sem| SemaphoreDetail {
+            name: sem.name,
+            max: sem.max,
+            holders: sem.holders.into_iter().map(|lease| LeaseDetail {
+                lease: lease.lease,
+                holder: lease.holder,
+                acquired_at: to_rfc3339(lease.acquired_at),
+                expires_at: to_rfc3339(lease.expires_at),
+            }).collect(),
+        })
+        .collect();
+
+    axum::Json(semaphores)
+}
+
+fn to_rfc3339(ms: u64) -> String {
+    chrono::DateTime::<chrono::Utc>::from_timestamp_millis(ms as i64)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_default()
 }
 
 // ==========================================
@@ -80,5 +175,8 @@ async fn get_store(
 // ==========================================
 
 pub fn routes() -> Router<NexoEngine> {
-    Router::new().route("/api/store", get(get_store))
+    Router::new()
+        .route("/api/store", get(get_store))
+        .route("/api/store/locks", get(get_locks))
+        .route("/api/store/semaphores", get(get_semaphores))
 }
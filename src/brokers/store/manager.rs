@@ -1,25 +1,42 @@
 //! Store Manager: In-memory data store orchestrator
 
+use crate::brokers::clock::{self, SharedClock};
+use crate::brokers::store::domain::lock::LockStore;
 use crate::brokers::store::domain::map::{MapStore, MapValue};
+use crate::brokers::store::domain::semaphore::SemaphoreStore;
 use crate::brokers::store::config::StoreConfig;
 use crate::brokers::store::snapshot::{KeyEntry, StoreSnapshot};
 use std::sync::Arc;
-use std::time::Instant;
 
 pub struct StoreManager {
     pub map: MapStore,
+    pub locks: LockStore,
+    pub semaphores: SemaphoreStore,
+    clock: SharedClock,
 }
 
 impl StoreManager {
     pub fn new(config: Arc<StoreConfig>) -> Self {
+        Self::with_clock(config, clock::system())
+    }
+
+    /// Store whose TTLs and leases run on `clock` (a `MockClock` in tests).
+    pub fn with_clock(config: Arc<StoreConfig>, clock: SharedClock) -> Self {
         Self {
-            map: MapStore::new(config),
+            locks: LockStore::new(config.cleanup_interval_secs, clock.clone()),
+            semaphores: SemaphoreStore::new(config.cleanup_interval_secs, clock.clone()),
+            map: MapStore::new(config, clock.clone()),
+            clock,
         }
     }
 
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
     pub fn scan(&self, limit: usize, offset: usize, filter: Option<String>) -> StoreSnapshot {
         let total = self.map.len();
-        let now = Instant::now();
+        let now = self.clock.now();
 
         let entries = self.map.iter()
             .filter(|entry| {
@@ -33,10 +50,8 @@ impl StoreManager {
             .take(limit)
             .filter_map(|entry| {
                 let val = entry.value();
-                if let Some(expiry) = val.expires_at {
-                    if expiry <= now {
-                        return None;
-                    }
+                if val.is_expired(now) {
+                    return None;
                 }
                 let MapValue(payload) = val.value.clone();
                 Some(KeyEntry {
@@ -47,6 +62,6 @@ impl StoreManager {
             })
             .collect();
 
-        StoreSnapshot { entries, total }
+        StoreSnapshot { entries, total, memory: self.map.memory() }
     }
 }
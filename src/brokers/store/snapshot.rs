@@ -4,9 +4,12 @@
 use bytes::Bytes;
 use std::time::Instant;
 
+use crate::brokers::store::domain::eviction::StoreMemory;
+
 pub struct StoreSnapshot {
     pub entries: Vec<KeyEntry>,
     pub total: usize,
+    pub memory: StoreMemory,
 }
 
 pub struct KeyEntry {
@@ -1,10 +1,16 @@
 //! Store broker TCP surface: opcodes, command parsing, dispatch entry point.
 
-use bytes::Bytes;
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
 use serde::Deserialize;
 
+use crate::brokers::names::{self, NameKind};
+use crate::brokers::intent::PublishTarget;
+use crate::brokers::pub_sub::ClientId;
+use crate::brokers::store::domain::map::Version;
 use crate::transport::tcp::protocol::cursor::PayloadCursor;
-use crate::transport::tcp::protocol::{ParseError, Response};
+use crate::transport::tcp::protocol::{NexoError, ParseError, Response, ToWire};
 use crate::NexoEngine;
 
 // ==========================================
@@ -13,10 +19,33 @@ use crate::NexoEngine;
 
 pub const OPCODE_MIN: u8 = 0x02;
 pub const OPCODE_MAX: u8 = 0x0F;
+// 0x02..=0x0F is full: later store commands continue in 0x60..=0x6F
+pub const OPCODE_EXT_MIN: u8 = 0x60;
+pub const OPCODE_EXT_MAX: u8 = 0x6F;
+
+/// True if `opcode` belongs to the store broker.
+pub fn owns(opcode: u8) -> bool {
+    (OPCODE_MIN..=OPCODE_MAX).contains(&opcode) || (OPCODE_EXT_MIN..=OPCODE_EXT_MAX).contains(&opcode)
+}
 
 pub const OP_MAP_SET: u8 = 0x02;
 pub const OP_MAP_GET: u8 = 0x03;
 pub const OP_MAP_DEL: u8 = 0x04;
+pub const OP_MAP_GET_AT: u8 = 0x05;
+pub const OP_MAP_HISTORY: u8 = 0x06;
+pub const OP_LOCK: u8 = 0x07;
+pub const OP_UNLOCK: u8 = 0x08;
+pub const OP_LOCK_EXTEND: u8 = 0x09;
+pub const OP_SEM_ACQUIRE: u8 = 0x0A;
+pub const OP_SEM_RELEASE: u8 = 0x0B;
+pub const OP_SEM_EXTEND: u8 = 0x0C;
+pub const OP_MAP_SET_PUBLISH: u8 = 0x0D;
+pub const OP_MAP_MGET: u8 = 0x0E;
+pub const OP_MAP_MSET: u8 = 0x0F;
+pub const OP_MAP_MDEL: u8 = 0x60;
+pub const OP_MAP_TTL: u8 = 0x61;
+pub const OP_MAP_EXPIRE: u8 = 0x62;
+pub const OP_MAP_PERSIST: u8 = 0x63;
 
 // ==========================================
 // COMMANDS
@@ -26,13 +55,76 @@ pub const OP_MAP_DEL: u8 = 0x04;
 #[serde(rename_all = "camelCase")]
 struct MapSetOptions {
     pub ttl: Option<u64>,
+    /// No expiry at all, instead of the default TTL
+    #[serde(default)]
+    pub keep_forever: bool,
+}
+
+impl MapSetOptions {
+    fn parse(json: &str) -> Result<Self, ParseError> {
+        let options: Self = serde_json::from_str(json)
+            .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?;
+        if options.keep_forever && options.ttl.is_some_and(|ttl| ttl > 0) {
+            return Err(ParseError::Invalid("'ttl' and 'keepForever' cannot be combined".to_string()));
+        }
+        Ok(options)
+    }
+}
+
+/// Where a store-and-publish sends its event: exactly one of `pubsub`, `stream`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MapSetPublishOptions {
+    pub ttl: Option<u64>,
+    pub pubsub: Option<String>,
+    pub stream: Option<String>,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+impl MapSetPublishOptions {
+    fn target(self) -> Result<PublishTarget, String> {
+        match (self.pubsub, self.stream) {
+            (Some(topic), None) => Ok(PublishTarget::PubSub { topic, retain: self.retain }),
+            (None, Some(topic)) => Ok(PublishTarget::Stream { topic }),
+            _ => Err("Exactly one of 'pubsub' or 'stream' must be set".to_string()),
+        }
+    }
 }
 
 #[derive(Debug)]
 enum StoreCommand {
     MapSet { key: String, options: MapSetOptions, value: Bytes },
+    // [Key:String][Options:String][ValueLen:4][Value...][Event...]
+    MapSetPublish { key: String, ttl: Option<u64>, target: PublishTarget, value: Bytes, event: Bytes },
     MapGet { key: String },
     MapDel { key: String },
+    // [Count:4][Key:String]...
+    MapMget { keys: Vec<String> },
+    // [Options:String][Count:4] then per key: [Key:String][ValueLen:4][Value...]
+    MapMset { options: MapSetOptions, entries: Vec<(String, Bytes)> },
+    // [Count:4][Key:String]...
+    MapMdel { keys: Vec<String> },
+    MapTtl { key: String },
+    // [Key:String][TtlMs:8]
+    MapExpire { key: String, ttl_ms: u64 },
+    MapPersist { key: String },
+    // [Key:String][At:8] (ms since epoch)
+    MapGetAt { key: String, at: u64 },
+    // [Key:String][Limit:4]
+    MapHistory { key: String, limit: u32 },
+    // [Name:String][Owner:String][TtlMs:8]
+    Lock { name: String, owner: String, ttl_ms: u64 },
+    // [Name:String][Token:8]
+    Unlock { name: String, token: u64 },
+    // [Name:String][Token:8][TtlMs:8]
+    LockExtend { name: String, token: u64, ttl_ms: u64 },
+    // [Name:String][Max:4][TtlMs:8]
+    SemAcquire { name: String, max: u32, ttl_ms: u64 },
+    // [Name:String][Lease:8]
+    SemRelease { name: String, lease: u64 },
+    // [Name:String][Lease:8][TtlMs:8]
+    SemExtend { name: String, lease: u64, ttl_ms: u64 },
 }
 
 impl StoreCommand {
@@ -40,12 +132,24 @@ impl StoreCommand {
         match opcode {
             OP_MAP_SET => {
                 let key = cursor.read_string()?;
-                let json_str = cursor.read_string()?;
-                let options: MapSetOptions = serde_json::from_str(&json_str)
-                    .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?;
+                let options = MapSetOptions::parse(&cursor.read_string()?)?;
                 let value = cursor.read_remaining();
                 Ok(Self::MapSet { key, options, value })
             }
+            OP_MAP_SET_PUBLISH => {
+                let key = cursor.read_string()?;
+                let json_str = cursor.read_string()?;
+                let options: MapSetPublishOptions = serde_json::from_str(&json_str)
+                    .map_err(|e| ParseError::Invalid(format!("Invalid JSON options: {}", e)))?;
+                let ttl = options.ttl;
+                let target = options.target().map_err(ParseError::Invalid)?;
+                if let PublishTarget::PubSub { topic, .. } = &target {
+                    names::check(NameKind::PubSubTopic, topic).map_err(ParseError::InvalidName)?;
+                }
+                let value = cursor.read_bytes()?;
+                let event = cursor.read_remaining();
+                Ok(Self::MapSetPublish { key, ttl, target, value, event })
+            }
             OP_MAP_GET => {
                 let key = cursor.read_string()?;
                 Ok(Self::MapGet { key })
@@ -54,25 +158,204 @@ impl StoreCommand {
                 let key = cursor.read_string()?;
                 Ok(Self::MapDel { key })
             }
+            OP_MAP_MGET => Ok(Self::MapMget { keys: read_keys(cursor)? }),
+            OP_MAP_MSET => {
+                let options = MapSetOptions::parse(&cursor.read_string()?)?;
+                let count = cursor.read_u32()?;
+                let mut entries = Vec::new();
+                for _ in 0..count {
+                    let key = cursor.read_string()?;
+                    let value = cursor.read_bytes()?;
+                    entries.push((key, value));
+                }
+                Ok(Self::MapMset { options, entries })
+            }
+            OP_MAP_MDEL => Ok(Self::MapMdel { keys: read_keys(cursor)? }),
+            OP_MAP_TTL => {
+                let key = cursor.read_string()?;
+                Ok(Self::MapTtl { key })
+            }
+            OP_MAP_EXPIRE => {
+                let key = cursor.read_string()?;
+                let ttl_ms = cursor.read_u64()?;
+                Ok(Self::MapExpire { key, ttl_ms })
+            }
+            OP_MAP_PERSIST => {
+                let key = cursor.read_string()?;
+                Ok(Self::MapPersist { key })
+            }
+            OP_MAP_GET_AT => {
+                let key = cursor.read_string()?;
+                let at = cursor.read_u64()?;
+                Ok(Self::MapGetAt { key, at })
+            }
+            OP_MAP_HISTORY => {
+                let key = cursor.read_string()?;
+                let limit = cursor.read_u32()?;
+                Ok(Self::MapHistory { key, limit })
+            }
+            OP_LOCK => {
+                let name = cursor.read_string()?;
+                let owner = cursor.read_string()?;
+                let ttl_ms = cursor.read_u64()?;
+                Ok(Self::Lock { name, owner, ttl_ms })
+            }
+            OP_UNLOCK => {
+                let name = cursor.read_string()?;
+                let token = cursor.read_u64()?;
+                Ok(Self::Unlock { name, token })
+            }
+            OP_LOCK_EXTEND => {
+                let name = cursor.read_string()?;
+                let token = cursor.read_u64()?;
+                let ttl_ms = cursor.read_u64()?;
+                Ok(Self::LockExtend { name, token, ttl_ms })
+            }
+            OP_SEM_ACQUIRE => {
+                let name = cursor.read_string()?;
+                let max = cursor.read_u32()?;
+                let ttl_ms = cursor.read_u64()?;
+                Ok(Self::SemAcquire { name, max, ttl_ms })
+            }
+            OP_SEM_RELEASE => {
+                let name = cursor.read_string()?;
+                let lease = cursor.read_u64()?;
+                Ok(Self::SemRelease { name, lease })
+            }
+            OP_SEM_EXTEND => {
+                let name = cursor.read_string()?;
+                let lease = cursor.read_u64()?;
+                let ttl_ms = cursor.read_u64()?;
+                Ok(Self::SemExtend { name, lease, ttl_ms })
+            }
             _ => Err(ParseError::Invalid(format!("Unknown Store opcode: 0x{:02X}", opcode))),
         }
     }
 }
 
+fn read_keys(cursor: &mut PayloadCursor) -> Result<Vec<String>, ParseError> {
+    let count = cursor.read_u32()?;
+    (0..count).map(|_| cursor.read_string()).collect()
+}
+
+// ==========================================
+// WIRE RESPONSES
+// ==========================================
+
+/// [Count:4] then per version, newest first: [SetAt:8][Len:4][Value...]
+struct HistoryResponse { versions: Vec<Version> }
+
+impl ToWire for HistoryResponse {
+    fn to_wire(&self) -> Bytes {
+        let size = 4 + self.versions.iter().map(|v| 12 + v.value.len()).sum::<usize>();
+        let mut buf = BytesMut::with_capacity(size);
+        buf.put_u32(self.versions.len() as u32);
+        for version in &self.versions {
+            buf.put_u64(version.set_at);
+            buf.put_u32(version.value.len() as u32);
+            buf.put_slice(&version.value);
+        }
+        buf.freeze()
+    }
+}
+
+/// [Count:4] then per key, in request order: [Found:1] and, if found, [Len:4][Value...]
+struct MgetResponse { values: Vec<Option<Bytes>> }
+
+impl ToWire for MgetResponse {
+    fn to_wire(&self) -> Bytes {
+        let size = 4 + self.values.iter().map(|v| v.as_ref().map_or(1, |v| 5 + v.len())).sum::<usize>();
+        let mut buf = BytesMut::with_capacity(size);
+        buf.put_u32(self.values.len() as u32);
+        for value in &self.values {
+            match value {
+                Some(value) => {
+                    buf.put_u8(1);
+                    buf.put_u32(value.len() as u32);
+                    buf.put_slice(value);
+                }
+                None => buf.put_u8(0),
+            }
+        }
+        buf.freeze()
+    }
+}
+
+/// [Count:4] then per key, in request order: [Deleted:1]
+struct MdelResponse { deleted: Vec<bool> }
+
+impl ToWire for MdelResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(4 + self.deleted.len());
+        buf.put_u32(self.deleted.len() as u32);
+        for deleted in &self.deleted {
+            buf.put_u8(*deleted as u8);
+        }
+        buf.freeze()
+    }
+}
+
+/// [Expires:1][RemainingMs:8]: RemainingMs is 0 for a key without expiry
+struct TtlResponse { remaining: Option<Duration> }
+
+impl ToWire for TtlResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(9);
+        buf.put_u8(self.remaining.is_some() as u8);
+        buf.put_u64(self.remaining.map_or(0, |r| r.as_millis() as u64));
+        buf.freeze()
+    }
+}
+
+/// [Id:8][ExpiresAt:8]: a lock's fencing token or a semaphore lease
+struct GrantResponse { id: u64, expires_at: u64 }
+
+impl ToWire for GrantResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(16);
+        buf.put_u64(self.id);
+        buf.put_u64(self.expires_at);
+        buf.freeze()
+    }
+}
+
+/// [ExpiresAt:8]
+struct ExtendResponse { expires_at: u64 }
+
+impl ToWire for ExtendResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(8);
+        buf.put_u64(self.expires_at);
+        buf.freeze()
+    }
+}
+
 // ==========================================
 // DISPATCH ENTRY POINT
 // ==========================================
 
-pub fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine) -> Response {
+pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine, client_id: &ClientId) -> Response {
     let cmd = match StoreCommand::parse(opcode, cursor) {
         Ok(c) => c,
-        Err(e) => return Response::Error(e.to_string()),
+        Err(e) => return Response::Error(e.into()),
     };
 
     match cmd {
         StoreCommand::MapSet { key, options, value } => {
-            engine.store.map.set(key, value, options.ttl);
-            Response::Ok
+            let written = match options.keep_forever {
+                true => engine.store.map.set_persistent(key, value),
+                false => engine.store.map.set(key, value, options.ttl),
+            };
+            match written {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::Error(e),
+            }
+        }
+        StoreCommand::MapSetPublish { key, ttl, target, value, event } => {
+            match engine.store_and_publish(key, value, ttl, target, event).await {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::Error(e),
+            }
         }
         StoreCommand::MapGet { key } => engine
             .store
@@ -84,5 +367,77 @@ pub fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine) -> Re
             engine.store.map.del(&key);
             Response::Ok
         }
+        StoreCommand::MapMget { keys } => match engine.store.map.get_many(&keys) {
+            Ok(values) => Response::Data(MgetResponse { values }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::MapMset { options, entries } => match engine.store.map.set_many(entries, options.ttl, options.keep_forever) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::MapMdel { keys } => match engine.store.map.del_many(&keys) {
+            Ok(deleted) => Response::Data(MdelResponse { deleted }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::MapTtl { key } => match engine.store.map.ttl(&key) {
+            Some(remaining) => Response::Data(TtlResponse { remaining }.to_wire()),
+            None => Response::Null,
+        },
+        StoreCommand::MapExpire { ttl_ms: 0, .. } => Response::Error(NexoError::invalid("TTL must be greater than 0")),
+        StoreCommand::MapExpire { key, ttl_ms } => match engine.store.map.expire(&key, Duration::from_millis(ttl_ms)) {
+            true => Response::Ok,
+            false => Response::Null,
+        },
+        StoreCommand::MapPersist { key } => match engine.store.map.persist(&key) {
+            true => Response::Ok,
+            false => Response::Null,
+        },
+        StoreCommand::MapGetAt { key, .. } | StoreCommand::MapHistory { key, .. } if !engine.store.map.is_versioned(&key) => {
+            Response::Error(NexoError::invalid(format!("Key '{}' is not under a versioned prefix", key)))
+        }
+        StoreCommand::MapGetAt { key, at } => engine
+            .store
+            .map
+            .get_at(&key, at)
+            .map(Response::Data)
+            .unwrap_or(Response::Null),
+        StoreCommand::MapHistory { key, limit } => {
+            let versions = engine.store.map.history(&key, limit as usize);
+            Response::Data(HistoryResponse { versions }.to_wire())
+        }
+        StoreCommand::Lock { name, owner, ttl_ms } => match engine.store.locks.acquire(&name, &owner, ttl_ms) {
+            Ok(Some(grant)) => Response::Data(GrantResponse { id: grant.token, expires_at: grant.expires_at }.to_wire()),
+            Ok(None) => Response::Null,
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::Unlock { name, token } => match engine.store.locks.release(&name, token) {
+            Ok(()) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::LockExtend { name, token, ttl_ms } => match engine.store.locks.extend(&name, token, ttl_ms) {
+            Ok(expires_at) => Response::Data(ExtendResponse { expires_at }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::SemAcquire { name, max, ttl_ms } => match engine.store.semaphores.acquire(&name, &client_id.0, max, ttl_ms) {
+            Ok(Some(grant)) => {
+                engine.connections.add_semaphore(&client_id.0, &name);
+                Response::Data(GrantResponse { id: grant.lease, expires_at: grant.expires_at }.to_wire())
+            }
+            Ok(None) => Response::Null,
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::SemRelease { name, lease } => match engine.store.semaphores.release(&name, &client_id.0, lease) {
+            Ok(()) => {
+                if !engine.store.semaphores.holds(&name, &client_id.0) {
+                    engine.connections.remove_semaphore(&client_id.0, &name);
+                }
+                Response::Ok
+            }
+            Err(e) => Response::Error(e),
+        },
+        StoreCommand::SemExtend { name, lease, ttl_ms } => match engine.store.semaphores.extend(&name, &client_id.0, lease, ttl_ms) {
+            Ok(expires_at) => Response::Data(ExtendResponse { expires_at }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
     }
 }
@@ -0,0 +1,264 @@
+//! Stream Backfill: copy a range of one topic into another, as a background job.
+//!
+//! A job reads the source in batches from `from_seq` up to the last sequence
+//! at start (or `to_seq`), keeps the records matching the optional time range
+//! and key filter, applies the optional transform to JSON payloads and
+//! republishes them into the target (created if missing) with their original
+//! timestamps. Progress is exposed through the stream snapshot. Jobs live in
+//! memory: a restart drops them, and re-running one duplicates what it copied.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::brokers::stream::options::StreamCreateOptions;
+use crate::brokers::stream::snapshot::BackfillSnapshot;
+use crate::brokers::stream::StreamManager;
+use crate::brokers::payload::render_payload;
+use crate::transport::tcp::protocol::DATA_TYPE_JSON;
+
+const BATCH_SIZE: usize = 500;
+
+// ==========================================
+// SPEC
+// ==========================================
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BackfillSpec {
+    pub source: String,
+    pub target: String,
+    /// First sequence to copy (default: the oldest retained)
+    #[serde(default)]
+    pub from_seq: u64,
+    /// Last sequence to copy (default: the last one when the job starts)
+    pub to_seq: Option<u64>,
+    /// Keep records published at or after this time (unix ms)
+    pub from_time: Option<u64>,
+    /// Keep records published before this time (unix ms)
+    pub to_time: Option<u64>,
+    pub key: Option<KeyFilter>,
+    pub transform: Option<Transform>,
+}
+
+/// Keep records whose payload holds `value` at JSON pointer `pointer` (e.g. `/customer/id`).
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeyFilter {
+    pub pointer: String,
+    pub value: Value,
+}
+
+/// Rewrite of top-level fields of JSON object payloads: `remove`, then `rename`
+/// (old -> new), then `set`. Records that aren't JSON objects can't be
+/// transformed and count as failed.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Transform {
+    #[serde(default)]
+    pub remove: Vec<String>,
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+    #[serde(default)]
+    pub set: Map<String, Value>,
+}
+
+impl BackfillSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.source.is_empty() || self.target.is_empty() {
+            return Err("Backfill needs a source and a target topic".to_string());
+        }
+        if self.source == self.target {
+            return Err("Backfill source and target must differ".to_string());
+        }
+        if self.to_seq.is_some_and(|to| to < self.from_seq) {
+            return Err("toSeq is before fromSeq".to_string());
+        }
+        if let Some(key) = &self.key {
+            if !key.pointer.is_empty() && !key.pointer.starts_with('/') {
+                return Err("Key pointer must be a JSON pointer (e.g. /customer/id)".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn in_time_range(&self, timestamp: u64) -> bool {
+        self.from_time.is_none_or(|from| timestamp >= from) && self.to_time.is_none_or(|to| timestamp < to)
+    }
+
+    fn matches_key(&self, payload: &[u8]) -> bool {
+        let Some(key) = &self.key else { return true };
+        render_payload(payload).0.pointer(&key.pointer) == Some(&key.value)
+    }
+}
+
+impl Transform {
+    /// The rewritten payload, or `None` if it isn't a JSON object.
+    fn apply(&self, payload: &[u8]) -> Option<Bytes> {
+        let (&DATA_TYPE_JSON, content) = payload.split_first()? else { return None };
+        let Ok(Value::Object(mut object)) = serde_json::from_slice::<Value>(content) else { return None };
+        for field in &self.remove {
+            object.remove(field);
+        }
+        for (from, to) in &self.rename {
+            if let Some(value) = object.remove(from) {
+                object.insert(to.clone(), value);
+            }
+        }
+        for (field, value) in &self.set {
+            object.insert(field.clone(), value.clone());
+        }
+        let mut out = vec![DATA_TYPE_JSON];
+        out.extend(serde_json::to_vec(&Value::Object(object)).ok()?);
+        Some(Bytes::from(out))
+    }
+}
+
+// ==========================================
+// STATUS
+// ==========================================
+
+pub struct BackfillStatus {
+    id: u64,
+    spec: BackfillSpec,
+    to_seq: u64,
+    next_seq: AtomicU64,
+    copied: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+    done: AtomicBool,
+    error: Mutex<Option<String>>,
+    cancel: CancellationToken,
+}
+
+impl BackfillStatus {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Stop a running job. `false` if it already finished.
+    pub fn cancel(&self) -> bool {
+        if self.done.load(Ordering::Acquire) {
+            return false;
+        }
+        self.cancel.cancel();
+        true
+    }
+
+    fn set_error(&self, error: String) {
+        *self.error.lock().unwrap_or_else(|p| p.into_inner()) = Some(error);
+    }
+
+    pub fn snapshot(&self) -> BackfillSnapshot {
+        let done = self.done.load(Ordering::Acquire);
+        let error = self.error.lock().unwrap_or_else(|p| p.into_inner()).clone();
+        let state = match (done, &error, self.cancel.is_cancelled()) {
+            (false, _, _) => "running",
+            (true, Some(_), _) => "failed",
+            (true, None, true) => "cancelled",
+            (true, None, false) => "completed",
+        };
+        BackfillSnapshot {
+            id: self.id,
+            source: self.spec.source.clone(),
+            target: self.spec.target.clone(),
+            from_seq: self.spec.from_seq,
+            to_seq: self.to_seq,
+            next_seq: self.next_seq.load(Ordering::Relaxed),
+            copied: self.copied.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            state,
+            error,
+        }
+    }
+}
+
+// ==========================================
+// JOB
+// ==========================================
+
+pub fn spawn(manager: StreamManager, id: u64, spec: BackfillSpec, to_seq: u64, cancel: CancellationToken) -> Arc<BackfillStatus> {
+    let status = Arc::new(BackfillStatus {
+        id,
+        to_seq,
+        next_seq: AtomicU64::new(spec.from_seq.max(1)),
+        copied: AtomicU64::new(0),
+        skipped: AtomicU64::new(0),
+        failed: AtomicU64::new(0),
+        done: AtomicBool::new(false),
+        error: Mutex::new(None),
+        cancel,
+        spec,
+    });
+
+    let task_status = status.clone();
+    tokio::spawn(async move {
+        info!("Backfill #{} '{}' -> '{}' started", id, task_status.spec.source, task_status.spec.target);
+        if let Err(e) = run(&manager, &task_status).await {
+            warn!("Backfill #{} failed: {}", id, e);
+            task_status.set_error(e);
+        }
+        task_status.done.store(true, Ordering::Release);
+        info!("Backfill #{} finished: {} copied", id, task_status.copied.load(Ordering::Relaxed));
+    });
+
+    status
+}
+
+async fn run(manager: &StreamManager, status: &BackfillStatus) -> Result<(), String> {
+    let spec = &status.spec;
+    manager.create_topic(spec.target.clone(), StreamCreateOptions::default()).await
+        .map_err(|e| format!("cannot create target topic: {}", e.message))?;
+    let to_seq = status.to_seq;
+
+    let mut next = spec.from_seq.max(1);
+    while next <= to_seq {
+        if status.cancel.is_cancelled() {
+            return Ok(());
+        }
+        let limit = BATCH_SIZE.min((to_seq - next + 1) as usize);
+        let batch = manager.read(&spec.source, next, limit).await;
+        let Some(last) = batch.last() else {
+            // Nothing left from `next` on: the source was deleted or trimmed under us
+            if !manager.exists(&spec.source).await {
+                return Err("source topic was deleted".to_string());
+            }
+            break;
+        };
+        next = last.seq + 1;
+
+        for msg in batch.iter().filter(|m| m.seq <= to_seq) {
+            if !spec.in_time_range(msg.timestamp) || !spec.matches_key(&msg.payload) {
+                status.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let payload = match &spec.transform {
+                Some(transform) => match transform.apply(&msg.payload) {
+                    Some(payload) => payload,
+                    None => {
+                        status.failed.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                },
+                None => msg.payload.clone(),
+            };
+            // Target schemas apply, as for any publish
+            if manager.validate_publish(&spec.target, &payload).is_err() {
+                status.failed.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            manager.publish_with_timestamp(&spec.target, payload, msg.timestamp).await
+                .map_err(|e| format!("publish to '{}' failed: {}", spec.target, e.message))?;
+            status.copied.fetch_add(1, Ordering::Relaxed);
+        }
+        status.next_seq.store(next, Ordering::Relaxed);
+    }
+    Ok(())
+}
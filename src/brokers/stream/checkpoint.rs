@@ -0,0 +1,66 @@
+//! Group checkpoints: a consumer group's committed position as a portable
+//! JSON document, exported from one instance and imported into another
+//! (a mirror, a restored dump) so a consumer fleet can move without
+//! replaying or skipping records.
+//!
+//! A checkpoint carries the ack floor and the publish time of the record
+//! at it. Imported `bySeq`, the floor is taken as is and must fall within
+//! the target's retained range. Imported `byTimestamp`, for targets whose
+//! sequences differ from the source's (mirrors and imports keep timestamps,
+//! not sequences), the group resumes after the last record published at or
+//! before that time.
+
+use serde::{Deserialize, Serialize};
+
+use crate::brokers::error::NexoError;
+
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupCheckpoint {
+    pub version: u32,
+    /// Where it was exported from (informative: any topic can import it)
+    pub topic: String,
+    pub group: String,
+    /// Every record up to here is acked
+    pub ack_floor: u64,
+    /// Publish time of the record at the ack floor, `None` when the floor
+    /// is before the first record or the record is gone
+    pub ack_floor_timestamp: Option<u64>,
+    /// The source's high watermark at export
+    pub last_seq: u64,
+    pub exported_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckpointMatch {
+    #[default]
+    BySeq,
+    ByTimestamp,
+}
+
+impl GroupCheckpoint {
+    pub fn validate(&self) -> Result<(), NexoError> {
+        if self.version != CHECKPOINT_VERSION {
+            return Err(NexoError::invalid(format!("Unsupported checkpoint version {} (expected {})", self.version, CHECKPOINT_VERSION)));
+        }
+        if self.ack_floor > self.last_seq {
+            return Err(NexoError::invalid(format!("Checkpoint ack floor {} is past its last sequence {}", self.ack_floor, self.last_seq)));
+        }
+        Ok(())
+    }
+
+    /// Check a `bySeq` import against the target's `head_seq..=last_seq`.
+    pub fn check_range(&self, head_seq: u64, last_seq: u64) -> Result<(), NexoError> {
+        let lowest = head_seq.max(1) - 1;
+        if self.ack_floor < lowest || self.ack_floor > last_seq {
+            return Err(NexoError::invalid(format!(
+                "Checkpoint ack floor {} is outside the topic's range ({} to {}): import byTimestamp if the sequences differ",
+                self.ack_floor, lowest, last_seq
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use std::env;
 
+use crate::brokers::durability::PersistenceMode;
+
 #[derive(Debug, Clone)]
 pub struct SystemStreamConfig {
     pub persistence_path: String,
@@ -15,6 +17,153 @@ pub struct SystemStreamConfig {
     pub max_open_files: usize,
     pub ack_wait_ms: u64,
     pub max_deliveries: u32,
+    /// How long a disconnected member is kept for its session to resume (0 = it leaves at once)
+    pub session_grace_ms: u64,
+    /// A join resuming a session another live connection still holds
+    pub duplicate_session: DuplicateSession,
+    pub max_payload_bytes: usize,
+    pub mirrors: Vec<MirrorSpec>,
+    pub trace_capacity: usize,
+    /// Decoded blocks (~64KB each) of sealed segments kept for cold reads; 0 disables the cache.
+    pub read_cache_blocks: usize,
+    pub write_backend: WriteBackend,
+    /// Default mode for new topics
+    pub persistence: PersistenceMode,
+    /// Ephemeral engine (`NEXO_EPHEMERAL`): a topic asking for `fileSync` runs `fileAsync`
+    pub ephemeral: bool,
+    /// How long a `FileSync` commit waits for more publishes to share its fsync
+    pub group_commit_ms: u64,
+    /// Default linger of new topics: how long publishes are held to share one storage append (0 = none)
+    pub default_linger_ms: u64,
+    /// Budget of the synchronous flush when the storage actor is dropped without `close()` (0 = none)
+    pub drop_flush_timeout_ms: u64,
+    pub tiering: TieringConfig,
+}
+
+/// Old sealed segments moved to S3-compatible object storage.
+#[derive(Debug, Clone, Default)]
+pub struct TieringConfig {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or a MinIO URL (empty = tiering off)
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// Objects are stored as `<prefix>/<topic>/<start_seq>.log`
+    pub prefix: String,
+    /// Time since a sealed segment's last write before it is uploaded
+    pub age_ms: u64,
+    pub check_interval_ms: u64,
+    pub request_timeout_ms: u64,
+    /// Local directory of segments fetched back for cold reads
+    pub cache_path: String,
+    pub cache_bytes: u64,
+}
+
+impl TieringConfig {
+    pub fn enabled(&self) -> bool {
+        !self.endpoint.is_empty() && !self.bucket.is_empty()
+    }
+
+    fn load() -> Self {
+        Self {
+            endpoint:           get_env_str("STREAM_TIERING_ENDPOINT", ""),
+            bucket:             get_env_str("STREAM_TIERING_BUCKET", ""),
+            region:             get_env_str("STREAM_TIERING_REGION", "us-east-1"),
+            access_key:         get_env_str("STREAM_TIERING_ACCESS_KEY", &get_env_str("AWS_ACCESS_KEY_ID", "")),
+            secret_key:         get_env_str("STREAM_TIERING_SECRET_KEY", &get_env_str("AWS_SECRET_ACCESS_KEY", "")),
+            prefix:             get_env_str("STREAM_TIERING_PREFIX", "streams"),
+            age_ms:             get_env("STREAM_TIERING_AGE_MS", 86400000), // 24 hours
+            check_interval_ms:  get_env("STREAM_TIERING_CHECK_MS", 300000), // 5 minutes
+            request_timeout_ms: get_env("STREAM_TIERING_TIMEOUT_MS", 60000),
+            cache_path:         get_env_str("STREAM_TIERING_CACHE_PATH", "./data/stream-cache"),
+            cache_bytes:        get_env("STREAM_TIERING_CACHE_BYTES", 1073741824), // 1GB
+        }
+    }
+}
+
+/// How the storage actor writes segment files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteBackend {
+    /// A `BufWriter` per open segment, flushed one file at a time
+    #[default]
+    Buffered,
+    /// io_uring (Linux, `io-uring` build feature): all dirty segments flushed in one submission
+    IoUring,
+}
+
+impl std::str::FromStr for WriteBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buffered" => Ok(Self::Buffered),
+            "io_uring" => Ok(Self::IoUring),
+            other => Err(format!("unknown stream write backend '{}'", other)),
+        }
+    }
+}
+
+/// What a join does when its session token belongs to a member another
+/// connection is still attached as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateSession {
+    /// The new connection gets the session and the old one is disconnected
+    #[default]
+    Takeover,
+    /// The join fails with `CONFLICT` until the old connection is gone
+    Reject,
+}
+
+impl std::str::FromStr for DuplicateSession {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "takeover" => Ok(Self::Takeover),
+            "reject" => Ok(Self::Reject),
+            other => Err(format!("unknown duplicate session policy '{}'", other)),
+        }
+    }
+}
+
+/// A remote topic replicated into a local one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorSpec {
+    pub remote_addr: String,
+    pub remote_topic: String,
+    pub local_topic: String,
+}
+
+impl MirrorSpec {
+    /// Parse `host:port/remote_topic[=local_topic]`; the local topic defaults to the remote name.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (source, local) = match spec.split_once('=') {
+            Some((source, local)) => (source.trim(), Some(local.trim())),
+            None => (spec.trim(), None),
+        };
+        let (remote_addr, remote_topic) = source.split_once('/')?;
+        if remote_addr.is_empty() || remote_topic.is_empty() || local == Some("") {
+            return None;
+        }
+        Some(Self {
+            remote_addr: remote_addr.to_string(),
+            remote_topic: remote_topic.to_string(),
+            local_topic: local.unwrap_or(remote_topic).to_string(),
+        })
+    }
+
+    /// Parse a comma-separated list; any invalid entry fails the whole list.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        list.split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| Self::parse(s).ok_or_else(|| format!("invalid stream mirror '{}'", s.trim())))
+            .collect()
+    }
+
+    pub fn source(&self) -> String {
+        format!("{}/{}", self.remote_addr, self.remote_topic)
+    }
 }
 
 impl Default for SystemStreamConfig {
@@ -33,6 +182,19 @@ impl Default for SystemStreamConfig {
             max_open_files: 256,
             ack_wait_ms: 30000, // 30 seconds
             max_deliveries: 5,
+            session_grace_ms: 0, // members leave on disconnect
+            duplicate_session: DuplicateSession::Takeover,
+            max_payload_bytes: 10485760, // 10MB
+            mirrors: Vec::new(),
+            trace_capacity: 10000,
+            read_cache_blocks: 256, // ~16MB
+            write_backend: WriteBackend::Buffered,
+            persistence: PersistenceMode::FileAsync,
+            ephemeral: false,
+            group_commit_ms: 0,
+            default_linger_ms: 0,
+            drop_flush_timeout_ms: 2000,
+            tiering: TieringConfig::default(),
         }
     }
 }
@@ -54,6 +216,20 @@ impl SystemStreamConfig {
             max_open_files:              get_env("STREAM_MAX_OPEN_FILES", default.max_open_files),
             ack_wait_ms:                 get_env("STREAM_ACK_WAIT_MS", default.ack_wait_ms),
             max_deliveries:              get_env("STREAM_MAX_DELIVERIES", default.max_deliveries),
+            session_grace_ms:            get_env("STREAM_SESSION_GRACE_MS", default.session_grace_ms),
+            duplicate_session:           get_env("STREAM_DUPLICATE_SESSION", default.duplicate_session),
+            max_payload_bytes:           get_env("STREAM_MAX_PAYLOAD_BYTES", default.max_payload_bytes),
+            mirrors:                     MirrorSpec::parse_list(&get_env_str("STREAM_MIRRORS", ""))
+                .unwrap_or_else(|e| panic!("Config error: STREAM_MIRRORS must be valid: {}", e)),
+            trace_capacity:              get_env("STREAM_TRACE_CAPACITY", default.trace_capacity),
+            read_cache_blocks:           get_env("STREAM_READ_CACHE_BLOCKS", default.read_cache_blocks),
+            write_backend:               get_env("STREAM_WRITE_BACKEND", default.write_backend),
+            persistence:                 get_env("STREAM_PERSISTENCE", default.persistence),
+            ephemeral:                   default.ephemeral,
+            group_commit_ms:             get_env("STREAM_GROUP_COMMIT_MS", default.group_commit_ms),
+            default_linger_ms:           get_env("STREAM_DEFAULT_LINGER_MS", default.default_linger_ms),
+            drop_flush_timeout_ms:       get_env("STREAM_DROP_FLUSH_TIMEOUT_MS", default.drop_flush_timeout_ms),
+            tiering:                     TieringConfig::load(),
         }
     }
 }
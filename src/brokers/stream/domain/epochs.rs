@@ -0,0 +1,98 @@
+//! Group epochs: the highest generation each consumer group handed out,
+//! persisted per topic in `epochs.json` apart from the ack floors in
+//! `groups.log`.
+//!
+//! Generations live in memory and start over with the process. Before a join
+//! hands one out, the group is moved past the epoch stored by the previous
+//! run and the new generation is written (fsynced) here, on the blocking pool
+//! and outside the topic lock. A client still holding a generation from
+//! before a restart is therefore fenced, even when `groups.log` was lost or
+//! deleted.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::brokers::error::{ErrorCode, NexoError};
+
+pub const EPOCHS_FILE: &str = "epochs.json";
+
+struct TopicEpochs {
+    file: PathBuf,
+    /// As found on disk when the topic was loaded
+    previous: BTreeMap<String, u64>,
+    /// As last written
+    current: BTreeMap<String, u64>,
+    /// Held while writing the file, so each write starts from the last one
+    write: Arc<Mutex<()>>,
+}
+
+#[derive(Default)]
+pub struct GroupEpochs {
+    topics: DashMap<String, TopicEpochs>,
+}
+
+impl GroupEpochs {
+    /// Read the topic's epochs from `topic_dir` (none if the file is missing).
+    pub fn load(&self, topic: &str, topic_dir: &Path) {
+        let file = topic_dir.join(EPOCHS_FILE);
+        let previous: BTreeMap<String, u64> = match std::fs::read(&file) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable group epochs {:?}: {}", file, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        self.topics.insert(topic.to_string(), TopicEpochs { file, current: previous.clone(), previous, write: Arc::default() });
+    }
+
+    /// Last generation `group` handed out before this run (`0` if none).
+    pub fn previous(&self, topic: &str, group: &str) -> u64 {
+        self.topics.get(topic).and_then(|t| t.previous.get(group).copied()).unwrap_or(0)
+    }
+
+    /// Persist `generation` before it is handed out; a no-op unless it is
+    /// higher than the one stored.
+    pub async fn record(&self, topic: &str, group: &str, generation: u64) -> Result<(), NexoError> {
+        let Some(write) = self.topics.get(topic).map(|epochs| epochs.write.clone()) else {
+            return Ok(());
+        };
+        let _write = write.lock().await;
+        let (file, mut current) = match self.topics.get(topic) {
+            Some(epochs) if epochs.current.get(group).is_some_and(|stored| *stored >= generation) => return Ok(()),
+            Some(epochs) => (epochs.file.clone(), epochs.current.clone()),
+            None => return Ok(()),
+        };
+        current.insert(group.to_string(), generation);
+        let written = current.clone();
+        tokio::task::spawn_blocking(move || write_epochs(&file, &written)).await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)))
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to persist group epoch: {}", e)))?;
+        if let Some(mut epochs) = self.topics.get_mut(topic) {
+            epochs.current = current;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, topic: &str) {
+        self.topics.remove(topic);
+    }
+}
+
+/// Temp file + fsync + rename, then fsync of the directory for the rename.
+fn write_epochs(file: &Path, epochs: &BTreeMap<String, u64>) -> std::io::Result<()> {
+    let tmp = file.with_extension("json.tmp");
+    let mut out = std::fs::File::create(&tmp)?;
+    out.write_all(&serde_json::to_vec_pretty(epochs)?)?;
+    out.sync_all()?;
+    std::fs::rename(&tmp, file)?;
+    if let Some(dir) = file.parent() {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
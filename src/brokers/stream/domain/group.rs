@@ -4,14 +4,47 @@
 //! - ack_floor: highest seq such that ALL seqs 1..=ack_floor are acked
 //! - pending: messages delivered but not yet acked
 //! - redeliver: messages that need to be redelivered (nack or timeout)
+//!
+//! Members that drop their connection are detached rather than removed: they
+//! keep their pending messages until the session grace period expires, so a
+//! client resuming with its session token picks up where it left off.
+//!
+//! With priority lanes enabled, high-lane messages in RAM are delivered ahead
+//! of the normal cursor; `ahead` remembers them so the cursor skips them later.
+//!
+//! A record that reaches `max_deliveries` (nacks or ack-wait timeouts) is
+//! parked and reported as a dead letter, which the manager copies to the
+//! group's DLQ topic when enabled. The floor moves past it only once the
+//! manager confirms the copy (`dead_lettered`); a failed copy is retried.
+//!
+//! A member can pause its fetches (backpressure on the consumer side): a paused
+//! member gets empty fetches and moves no cursor, while its pending messages
+//! stay owned and ackable. Pauses belong to the generation and are dropped on
+//! rebalance.
+//!
+//! Generations start over with the process; the manager fences them past the
+//! epoch persisted by the previous run before a join hands one out.
+//!
+//! A group joined through a topic pattern (`orders-*`) remembers it, so the
+//! manager can bind the group to matching topics created later.
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::time::{Duration, Instant};
 
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::brokers::stream::domain::message::Message;
+use crate::brokers::stream::domain::message::{Message, LANE_HIGH};
+use crate::brokers::stream::domain::topic::TopicState;
+use crate::brokers::error::{ErrorCode, NexoError};
+
+/// A record parked after `max_deliveries`, waiting to be copied to the DLQ.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub seq: u64,
+    pub attempts: u32,
+    pub reason: String,
+}
 
 pub struct PendingMsg {
     pub consumer_id: String,
@@ -21,6 +54,11 @@ pub struct PendingMsg {
 
 struct GroupMember {
     connection_client_id: String,
+    session_token: String,
+    /// Set when the owning connection dropped; cleared on resume
+    detached_at: Option<Instant>,
+    /// Fetches return empty until resumed
+    paused: bool,
 }
 
 pub struct ConsumerGroup {
@@ -33,12 +71,23 @@ pub struct ConsumerGroup {
     pub redeliver: VecDeque<u64>,
     pub parked: HashSet<u64>,
     pub delivery_attempts: HashMap<u64, u32>,
+    /// High-lane seqs already delivered beyond `next_deliver_seq`
+    pub ahead: BTreeSet<u64>,
+    /// Next seq to scan for high-lane messages
+    pub high_scan_seq: u64,
+    /// Parked since the manager last drained them
+    dead_letters: Vec<DeadLetter>,
+    /// Dead letters not yet in the DLQ topic: the floor stops below them
+    unwritten: BTreeSet<u64>,
     // === Config ===
     pub max_ack_pending: usize,
     pub ack_wait: Duration,
     pub max_deliveries: u32,
+    pub priority_lanes: bool,
     // === Member tracking (for disconnect cleanup) ===
     members: HashMap<String, GroupMember>,
+    /// Topic patterns this group subscribed through (volatile, re-sent on join)
+    pub patterns: BTreeSet<String>,
     // === Runtime State ===
     pub is_fetching_cold: bool,
     pub generation: u64,
@@ -46,7 +95,7 @@ pub struct ConsumerGroup {
 }
 
 impl ConsumerGroup {
-    pub fn new(id: String, head_seq: u64, max_ack_pending: usize, ack_wait: Duration, max_deliveries: u32) -> Self {
+    pub fn new(id: String, head_seq: u64, max_ack_pending: usize, ack_wait: Duration, max_deliveries: u32, priority_lanes: bool) -> Self {
         let head_seq = head_seq.max(1);
         Self {
             id,
@@ -56,10 +105,16 @@ impl ConsumerGroup {
             redeliver: VecDeque::new(),
             parked: HashSet::new(),
             delivery_attempts: HashMap::new(),
+            ahead: BTreeSet::new(),
+            high_scan_seq: head_seq,
+            dead_letters: Vec::new(),
+            unwritten: BTreeSet::new(),
             max_ack_pending,
             ack_wait,
             max_deliveries,
+            priority_lanes,
             members: HashMap::new(),
+            patterns: BTreeSet::new(),
             is_fetching_cold: false,
             generation: 1,
             cancel: CancellationToken::new(),
@@ -70,20 +125,27 @@ impl ConsumerGroup {
         self.pending.len() >= self.max_ack_pending
     }
 
-    pub fn restore(id: String, ack_floor: u64, head_seq: u64, max_ack_pending: usize, ack_wait: Duration, max_deliveries: u32) -> Self {
+    pub fn restore(id: String, ack_floor: u64, head_seq: u64, max_ack_pending: usize, ack_wait: Duration, max_deliveries: u32, priority_lanes: bool) -> Self {
         let normalized_floor = ack_floor.max(head_seq.saturating_sub(1));
+        let next_deliver_seq = normalized_floor.saturating_add(1).max(head_seq.max(1));
         Self {
             id,
             ack_floor: normalized_floor,
-            next_deliver_seq: normalized_floor.saturating_add(1).max(head_seq.max(1)),
+            next_deliver_seq,
             pending: HashMap::new(),
             redeliver: VecDeque::new(),
             parked: HashSet::new(),
             delivery_attempts: HashMap::new(),
+            ahead: BTreeSet::new(),
+            high_scan_seq: next_deliver_seq,
+            dead_letters: Vec::new(),
+            unwritten: BTreeSet::new(),
             max_ack_pending,
             ack_wait,
             max_deliveries,
+            priority_lanes,
             members: HashMap::new(),
+            patterns: BTreeSet::new(),
             is_fetching_cold: false,
             generation: 1,
             cancel: CancellationToken::new(),
@@ -91,8 +153,12 @@ impl ConsumerGroup {
     }
 
     /// Fetch messages for a client. Serves redeliver queue first, then fresh messages.
-    pub fn fetch(&mut self, consumer_id: &str, generation: u64, limit: usize, log: &VecDeque<Message>, ram_start_seq: u64, head_seq: u64) -> Result<Vec<Message>, String> {
+    pub fn fetch(&mut self, consumer_id: &str, generation: u64, limit: usize, state: &TopicState) -> Result<Vec<Message>, NexoError> {
+        let head_seq = state.head_seq;
         self.ensure_active_consumer(consumer_id, generation)?;
+        if self.is_paused(consumer_id) {
+            return Ok(vec![]);
+        }
         self.clamp_head(head_seq);
 
         if self.pending.len() >= self.max_ack_pending {
@@ -111,7 +177,7 @@ impl ConsumerGroup {
                     continue;
                 }
 
-                if let Some(msg) = Self::read_from_log(log, ram_start_seq, seq) {
+                if let Some(msg) = state.get(seq) {
                     if let Some(msg) = self.issue_delivery(consumer_id, msg) {
                         result.push(msg);
                     }
@@ -125,7 +191,12 @@ impl ConsumerGroup {
             }
         }
 
-        // 2. Fresh messages
+        // 2. High lane (ahead of the normal cursor, RAM only)
+        if self.priority_lanes {
+            self.fetch_high_lane(consumer_id, budget, &mut result, state);
+        }
+
+        // 3. Fresh messages
         while result.len() < budget {
             let seq = self.next_deliver_seq.max(head_seq);
             if self.next_deliver_seq < head_seq {
@@ -133,7 +204,12 @@ impl ConsumerGroup {
                 self.ack_floor = self.ack_floor.max(head_seq.saturating_sub(1));
             }
 
-            if let Some(msg) = Self::read_from_log(log, ram_start_seq, seq) {
+            if self.ahead.remove(&seq) || state.is_discarded(seq) {
+                self.next_deliver_seq = seq + 1;
+                continue;
+            }
+
+            if let Some(msg) = state.get(seq) {
                 self.next_deliver_seq = seq + 1;
                 if let Some(msg) = self.issue_delivery(consumer_id, msg) {
                     result.push(msg);
@@ -146,15 +222,37 @@ impl ConsumerGroup {
         Ok(result)
     }
 
-    /// Acknowledge a message. Removes from pending and tries to advance ack_floor.
-    pub fn ack(&mut self, consumer_id: &str, generation: u64, seq: u64) -> Result<(), String> {
-        self.ensure_active_consumer(consumer_id, generation)?;
+    fn fetch_high_lane(&mut self, consumer_id: &str, budget: usize, result: &mut Vec<Message>, state: &TopicState) {
+        let start = self.high_scan_seq.max(self.next_deliver_seq).max(state.ram_start_seq);
+        let Some(first) = state.log.front().map(|m| m.seq) else { return };
+        let skip = start.saturating_sub(first) as usize;
+        let end = state.visible_end();
 
-        match self.pending.get(&seq) {
-            Some(msg) if msg.consumer_id == consumer_id => {}
-            Some(_) => return Err("NOT_OWNER".to_string()),
-            None => return Err(format!("seq {} not pending", seq)),
+        let mut scanned_to = start;
+        for msg in state.log.iter().skip(skip).take_while(|m| m.seq < end) {
+            if result.len() >= budget {
+                break;
+            }
+            scanned_to = msg.seq + 1;
+            if msg.lane != LANE_HIGH || self.ahead.contains(&msg.seq) || state.is_discarded(msg.seq) {
+                continue;
+            }
+            if msg.seq == self.next_deliver_seq {
+                self.next_deliver_seq += 1;
+            } else {
+                self.ahead.insert(msg.seq);
+            }
+            if let Some(msg) = self.issue_delivery(consumer_id, msg.clone()) {
+                result.push(msg);
+            }
         }
+        self.high_scan_seq = scanned_to;
+    }
+
+    /// Acknowledge a message. Removes from pending and tries to advance ack_floor.
+    pub fn ack(&mut self, consumer_id: &str, generation: u64, seq: u64) -> Result<(), NexoError> {
+        self.ensure_active_consumer(consumer_id, generation)?;
+        self.ensure_owner(consumer_id, seq)?;
 
         self.pending.remove(&seq);
         self.delivery_attempts.remove(&seq);
@@ -163,7 +261,46 @@ impl ConsumerGroup {
         Ok(())
     }
 
-    /// Negative acknowledge: move message back to redeliver queue.
+    /// Negative acknowledge: redeliver right away, or park the record as a
+    /// dead letter once it has been delivered `max_deliveries` times.
+    pub fn nack(&mut self, consumer_id: &str, generation: u64, seq: u64, reason: &str) -> Result<(), NexoError> {
+        self.ensure_active_consumer(consumer_id, generation)?;
+        self.ensure_owner(consumer_id, seq)?;
+
+        if let Some(msg) = self.pending.remove(&seq) {
+            self.release_seq(seq, msg.delivery_count, reason);
+        }
+        self.try_advance_floor();
+        Ok(())
+    }
+
+    pub fn has_dead_letters(&self) -> bool {
+        !self.dead_letters.is_empty()
+    }
+
+    /// Dead letters to copy to the DLQ topic; the floor stays below each one
+    /// until it is confirmed with `dead_lettered` (or handed back with
+    /// `retry_dead_letter`).
+    pub fn take_dead_letters(&mut self) -> Vec<DeadLetter> {
+        std::mem::take(&mut self.dead_letters)
+    }
+
+    /// `seq` is in the DLQ topic, or needs not be (dead letters disabled,
+    /// record gone): the floor may move past it.
+    pub fn dead_lettered(&mut self, seq: u64) {
+        if self.unwritten.remove(&seq) {
+            self.try_advance_floor();
+        }
+    }
+
+    /// Copying `letter` failed: take it again on the next drain.
+    pub fn retry_dead_letter(&mut self, letter: DeadLetter) {
+        if self.unwritten.contains(&letter.seq) {
+            self.dead_letters.push(letter);
+        }
+    }
+
+    /// Move messages whose ack wait expired back to the redeliver queue.
     pub fn check_redelivery(&mut self) -> bool {
         let now = Instant::now();
         let expired: Vec<u64> = self.pending.iter()
@@ -178,7 +315,7 @@ impl ConsumerGroup {
         for seq in expired {
             if let Some(msg) = self.pending.remove(&seq) {
                 tracing::debug!("[Group:{}] Redelivery timeout seq={} (attempts={})", self.id, seq, msg.delivery_count);
-                self.release_seq(seq, msg.delivery_count);
+                self.release_seq(seq, msg.delivery_count, "ack wait expired");
             }
         }
 
@@ -198,6 +335,12 @@ impl ConsumerGroup {
         self.reset_runtime();
     }
 
+    pub fn seek_to(&mut self, ack_floor: u64) {
+        self.ack_floor = ack_floor;
+        self.next_deliver_seq = ack_floor + 1;
+        self.reset_runtime();
+    }
+
     pub fn clamp_head(&mut self, head_seq: u64) -> bool {
         let head_seq = head_seq.max(1);
         let mut changed = false;
@@ -227,6 +370,11 @@ impl ConsumerGroup {
             changed = true;
         }
 
+        self.ahead.retain(|seq| *seq >= head_seq);
+
+        self.unwritten.retain(|seq| *seq >= head_seq);
+        self.dead_letters.retain(|letter| letter.seq >= head_seq);
+
         let before_parked = self.parked.len();
         self.parked.retain(|seq| *seq >= head_seq);
         if self.parked.len() != before_parked {
@@ -256,7 +404,11 @@ impl ConsumerGroup {
         self.pending.clear();
         self.redeliver.clear();
         self.parked.clear();
+        self.dead_letters.clear();
+        self.unwritten.clear();
         self.delivery_attempts.clear();
+        self.ahead.clear();
+        self.high_scan_seq = self.next_deliver_seq;
         self.members.clear();
         self.is_fetching_cold = false;
         self.generation = self.generation.saturating_add(1);
@@ -275,12 +427,75 @@ impl ConsumerGroup {
 
     // --- Members ---
 
-    pub fn add_member(&mut self, connection_client_id: String) -> String {
+    /// Returns `(consumer_id, session_token)` for the new member.
+    pub fn add_member(&mut self, connection_client_id: String) -> (String, String) {
         let consumer_id = Uuid::new_v4().to_string();
+        let session_token = Uuid::new_v4().to_string();
         self.members.insert(consumer_id.clone(), GroupMember {
             connection_client_id,
+            session_token: session_token.clone(),
+            detached_at: None,
+            paused: false,
         });
-        consumer_id
+        (consumer_id, session_token)
+    }
+
+    /// Connection the member owning `session_token` is attached through
+    /// (`None` if there is no such member or it is detached).
+    pub fn session_holder(&self, session_token: &str) -> Option<&str> {
+        self.members.values()
+            .find(|member| member.session_token == session_token && member.detached_at.is_none())
+            .map(|member| member.connection_client_id.as_str())
+    }
+
+    /// Rebind the detached member owning `session_token` to a new connection.
+    /// `None` while the member is still attached: two connections must never
+    /// share it, a takeover detaches it first (`detach_session`).
+    /// Returns `(consumer_id, previous_connection_client_id)`.
+    pub fn resume_member(&mut self, session_token: &str, connection_client_id: String) -> Option<(String, String)> {
+        let (consumer_id, member) = self.members.iter_mut()
+            .find(|(_, member)| member.session_token == session_token && member.detached_at.is_some())?;
+        member.detached_at = None;
+        let previous = std::mem::replace(&mut member.connection_client_id, connection_client_id);
+        Some((consumer_id.clone(), previous))
+    }
+
+    /// Detach the member owning `session_token` from its connection, so that
+    /// another one can resume it. `false` if there is no such member.
+    pub fn detach_session(&mut self, session_token: &str) -> bool {
+        match self.members.values_mut().find(|member| member.session_token == session_token) {
+            Some(member) => {
+                member.detached_at.get_or_insert_with(Instant::now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Keep the member (and its pending messages) until the grace period expires.
+    pub fn detach_member(&mut self, consumer_id: &str) -> bool {
+        match self.members.get_mut(consumer_id) {
+            Some(member) => {
+                member.detached_at.get_or_insert_with(Instant::now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove members detached for longer than `grace`, releasing their pending messages.
+    pub fn expire_detached(&mut self, grace: Duration) -> bool {
+        let now = Instant::now();
+        let expired: Vec<String> = self.members.iter()
+            .filter(|(_, member)| member.detached_at.is_some_and(|at| now.duration_since(at) >= grace))
+            .map(|(consumer_id, _)| consumer_id.clone())
+            .collect();
+
+        for consumer_id in &expired {
+            tracing::debug!("[Group:{}] Session grace expired for consumer {}", self.id, consumer_id);
+            self.remove_member(consumer_id);
+        }
+        !expired.is_empty()
     }
 
     pub fn remove_member(&mut self, consumer_id: &str) -> Option<String> {
@@ -292,19 +507,50 @@ impl ConsumerGroup {
         None
     }
 
+    /// Pause or resume fetching for a member of the current generation.
+    pub fn set_paused(&mut self, consumer_id: &str, generation: u64, paused: bool) -> Result<(), NexoError> {
+        self.ensure_active_consumer(consumer_id, generation)?;
+        if let Some(member) = self.members.get_mut(consumer_id) {
+            member.paused = paused;
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self, consumer_id: &str) -> bool {
+        self.members.get(consumer_id).is_some_and(|member| member.paused)
+    }
+
     pub fn is_member(&self, consumer_id: &str) -> bool {
         self.members.contains_key(consumer_id)
     }
 
+    /// Members, attached or detached.
+    pub fn member_count(&self) -> usize {
+        self.members.len()
+    }
+
     pub fn generation(&self) -> u64 {
         self.generation
     }
 
+    /// Move the generation past `previous`, the last one handed out before
+    /// a restart (see `epochs`).
+    pub fn fence_generation(&mut self, previous: u64) {
+        if self.generation <= previous {
+            self.generation = previous.saturating_add(1);
+            self.invalidate_inflight();
+        }
+    }
+
     // --- Internal ---
 
     /// Specifically registers messages retrieved from disk into the group's pending state.
-    pub fn register_cold_messages(&mut self, consumer_id: &str, generation: u64, messages: Vec<Message>, head_seq: u64) -> Result<Vec<Message>, String> {
+    pub fn register_cold_messages(&mut self, consumer_id: &str, generation: u64, messages: Vec<Message>, state: &TopicState) -> Result<Vec<Message>, NexoError> {
+        let head_seq = state.head_seq;
         self.ensure_active_consumer(consumer_id, generation)?;
+        if self.is_paused(consumer_id) {
+            return Ok(vec![]);
+        }
         self.clamp_head(head_seq);
 
         let mut result = Vec::new();
@@ -331,6 +577,10 @@ impl ConsumerGroup {
                 self.next_deliver_seq = head_seq;
                 self.ack_floor = self.ack_floor.max(head_seq.saturating_sub(1));
             }
+            if seq == next_fresh_seq && (self.ahead.remove(&seq) || state.is_discarded(seq)) {
+                self.next_deliver_seq = seq + 1;
+                continue;
+            }
             let is_fresh = seq == next_fresh_seq;
 
             if is_redelivery || is_fresh {
@@ -351,25 +601,26 @@ impl ConsumerGroup {
         while self.ack_floor + 1 < self.next_deliver_seq
             && !self.pending.contains_key(&(self.ack_floor + 1))
             && !self.redeliver.iter().any(|seq| *seq == self.ack_floor + 1)
+            && !self.unwritten.contains(&(self.ack_floor + 1))
         {
             self.ack_floor += 1;
         }
     }
 
-    fn read_from_log(log: &VecDeque<Message>, ram_start_seq: u64, seq: u64) -> Option<Message> {
-        if seq < ram_start_seq || log.is_empty() {
-            return None; // cold read needed — handled by caller
+    fn ensure_owner(&self, consumer_id: &str, seq: u64) -> Result<(), NexoError> {
+        match self.pending.get(&seq) {
+            Some(msg) if msg.consumer_id == consumer_id => Ok(()),
+            Some(_) => Err(NexoError::new(ErrorCode::NotOwner, "Message is owned by another consumer")),
+            None => Err(NexoError::new(ErrorCode::NotPending, format!("seq {} not pending", seq))),
         }
-        let idx = (seq - ram_start_seq) as usize;
-        log.get(idx).cloned()
     }
 
-    fn ensure_active_consumer(&self, consumer_id: &str, generation: u64) -> Result<(), String> {
+    fn ensure_active_consumer(&self, consumer_id: &str, generation: u64) -> Result<(), NexoError> {
         if generation != self.generation {
-            return Err("FENCED".to_string());
+            return Err(NexoError::new(ErrorCode::Fenced, "Stale group generation, rejoin required"));
         }
         if !self.members.contains_key(consumer_id) {
-            return Err("NOT_MEMBER".to_string());
+            return Err(NexoError::new(ErrorCode::NotMember, "Consumer is not a member of the group"));
         }
         Ok(())
     }
@@ -392,10 +643,12 @@ impl ConsumerGroup {
         Some(msg)
     }
 
-    fn release_seq(&mut self, seq: u64, delivery_count: u32) {
+    fn release_seq(&mut self, seq: u64, delivery_count: u32, reason: &str) {
         if delivery_count >= self.max_deliveries {
             self.parked.insert(seq);
             self.delivery_attempts.remove(&seq);
+            self.dead_letters.push(DeadLetter { seq, attempts: delivery_count, reason: reason.to_string() });
+            self.unwritten.insert(seq);
         } else if !self.redeliver.iter().any(|queued| *queued == seq) {
             self.redeliver.push_back(seq);
         }
@@ -409,7 +662,7 @@ impl ConsumerGroup {
 
         for (seq, delivery_count) in seqs {
             self.pending.remove(&seq);
-            self.release_seq(seq, delivery_count);
+            self.release_seq(seq, delivery_count, "consumer left the group");
         }
 
         self.try_advance_floor();
@@ -1,9 +1,15 @@
 use bytes::Bytes;
 use serde::{Serialize, Deserialize};
 
+/// Default delivery lane.
+pub const LANE_NORMAL: u8 = 0;
+/// High-priority lane: served before `LANE_NORMAL` on topics with priority lanes enabled.
+pub const LANE_HIGH: u8 = 1;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message {
     pub seq: u64,
     pub timestamp: u64,
     pub payload: Bytes,
+    pub lane: u8,
 }
@@ -1,4 +1,9 @@
 pub mod topic;
 pub mod group;
+pub mod epochs;
 pub mod message;
 pub mod persistence;
+pub mod segment_cache;
+pub mod segment_io;
+pub mod object_store;
+pub mod tiering;
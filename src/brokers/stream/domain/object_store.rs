@@ -0,0 +1,143 @@
+//! Minimal S3 client for segment tiering: PUT, GET and DELETE of whole
+//! objects, path-style (`<endpoint>/<bucket>/<key>`) so MinIO and other
+//! S3-compatible stores work unchanged, signed with AWS Signature V4.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use reqwest::{Method, StatusCode, Url};
+use ring::{digest, hmac};
+
+use crate::brokers::stream::config::TieringConfig;
+
+pub struct ObjectStore {
+    http: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    timeout: Duration,
+}
+
+impl ObjectStore {
+    pub fn new(config: &TieringConfig) -> Result<Self, String> {
+        let endpoint = Url::parse(config.endpoint.trim_end_matches('/'))
+            .map_err(|e| format!("invalid tiering endpoint '{}': {}", config.endpoint, e))?;
+        if endpoint.host_str().is_none() {
+            return Err(format!("invalid tiering endpoint '{}': no host", config.endpoint));
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            endpoint,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            timeout: Duration::from_millis(config.request_timeout_ms),
+        })
+    }
+
+    pub async fn put(&self, key: &str, body: Bytes) -> Result<(), String> {
+        let response = self.send(Method::PUT, key, body).await?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            _ => Err(failure(Method::PUT, key, response).await),
+        }
+    }
+
+    /// The object's bytes, `None` if it does not exist.
+    pub async fn get(&self, key: &str) -> Result<Option<Bytes>, String> {
+        let response = self.send(Method::GET, key, Bytes::new()).await?;
+        match response.status() {
+            status if status.is_success() => response.bytes().await.map(Some).map_err(|e| format!("GET {}: {}", key, e)),
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Err(failure(Method::GET, key, response).await),
+        }
+    }
+
+    /// Deleting a missing object succeeds.
+    pub async fn delete(&self, key: &str) -> Result<(), String> {
+        let response = self.send(Method::DELETE, key, Bytes::new()).await?;
+        match response.status() {
+            status if status.is_success() || status == StatusCode::NOT_FOUND => Ok(()),
+            _ => Err(failure(Method::DELETE, key, response).await),
+        }
+    }
+
+    async fn send(&self, method: Method, key: &str, body: Bytes) -> Result<reqwest::Response, String> {
+        let path = format!("/{}/{}", uri_encode(&self.bucket), key.split('/').map(uri_encode).collect::<Vec<_>>().join("/"));
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(digest::digest(&digest::SHA256, &body));
+        let authorization = self.authorization(&method, &url, &path, &amz_date, &payload_hash);
+
+        let response = self.http.request(method.clone(), url)
+            .timeout(self.timeout)
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("{} {}: {}", method, key, e))?;
+        Ok(response)
+    }
+
+    /// `Authorization` header of a request without query string, signing
+    /// host, payload hash and date.
+    fn authorization(&self, method: &Method, url: &Url, path: &str, amz_date: &str, payload_hash: &str) -> String {
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date, scope, hex::encode(digest::digest(&digest::SHA256, canonical_request.as_bytes()))
+        );
+
+        let mut key = sign(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = sign(&key, part.as_bytes());
+        }
+        let signature = hex::encode(sign(&key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+/// Status and the start of the error document the store answered with.
+async fn failure(method: Method, key: &str, response: reqwest::Response) -> String {
+    let status = response.status();
+    let detail: String = response.text().await.unwrap_or_default().chars().take(200).collect();
+    format!("{} {}: HTTP {} {}", method, key, status.as_u16(), detail.trim())
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+/// SigV4 URI encoding of one path segment: unreserved characters stay as they are.
+fn uri_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -2,27 +2,40 @@
 //! 
 //! Responsibilities:
 //! - Offloads disk I/O from individual topic actors.
-//! - Batches writes automatically via `BufWriter` for high throughput.
+//! - Batches writes in memory per segment (`BufWriter`, or io_uring, see `segment_io`).
 //! - Manages an LRU Cache of file descriptors to prevent OS limits exhaustion.
-//! - Executes a global periodic flush to sync bytes to disk and notify topic actors.
+//! - Serves historical reads of sealed segments through a block cache.
+//! - Executes a periodic flush to sync bytes to disk and notify topic actors
+//!   (per topic interval where one is set, see `SetFlushInterval`).
+//! - Group-commits `FileSync` appends: one fsync per window, shared by every waiter.
+//! - Flushes and fsyncs on demand (`Flush`), on a clean stop, and best effort when dropped.
+//! - Moves old sealed segments to object storage and fetches them back for
+//!   cold reads, when tiering is configured (see `tiering`).
 
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
 use lru::LruCache;
 use bytes::Bytes;
 use tokio::fs::{File, OpenOptions};
 use tokio::io::{AsyncWriteExt, BufWriter, AsyncReadExt, BufReader};
 use tokio::sync::{mpsc, oneshot};
-use tracing::{error, info, debug, warn};
+use tokio::time::{sleep_until, Instant};
+use tracing::{error, info, debug, warn, Instrument, Span};
 use crc32fast::Hasher;
 
+use crate::brokers::events::EventHandle;
+use crate::brokers::activity;
+use crate::brokers::stream::config::WriteBackend;
 use crate::brokers::stream::options::RetentionOptions;
-use crate::brokers::stream::domain::message::Message;
+use crate::brokers::stream::domain::message::{Message, LANE_NORMAL};
+use crate::brokers::stream::domain::segment_cache::SegmentCache;
+use crate::brokers::stream::domain::segment_io::{SegmentIo, SegmentWriter};
+use crate::brokers::stream::domain::tiering::{self, TierMiss, TieredSegment, Tiering};
 
 // ==========================================
 // DATA STRUCTURES
@@ -38,12 +51,17 @@ pub struct RecoveredState {
     pub groups_data: HashMap<String, u64>,
     /// First retained sequence on disk
     pub head_seq: u64,
+    /// Total size of all segments on disk
+    pub total_bytes: u64,
 }
 
 #[derive(Debug, Clone)]
 pub struct Segment {
+    /// Where the segment is (or was, if tiered) in the topic directory
     pub path: PathBuf,
     pub start_seq: u64,
+    /// Set when the segment lives in object storage
+    pub tiered: Option<TieredSegment>,
 }
 
 #[derive(Debug)]
@@ -51,11 +69,16 @@ pub struct MessageToAppend {
     pub seq: u64,
     pub timestamp: u64,
     pub payload: Bytes,
+    pub lane: u8,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct RetentionOutcome {
     pub head_seq: u64,
+    pub total_bytes: u64,
+    /// Segments (and their bytes) this pass deleted
+    pub deleted_segments: usize,
+    pub deleted_bytes: u64,
 }
 
 // ==========================================
@@ -68,6 +91,10 @@ pub enum StorageCommand {
         topic_name: String,
         messages: Vec<MessageToAppend>,
         persisted_seq: Arc<AtomicU64>,
+        /// Publisher span, parent of the write.
+        span: Span,
+        /// `FileSync` publishes: each resolved once the write is fsynced (true) or failed.
+        durable: Vec<oneshot::Sender<bool>>,
     },
     
     ColdRead {
@@ -75,6 +102,8 @@ pub enum StorageCommand {
         from_seq: u64,
         limit: usize,
         reply: oneshot::Sender<Vec<Message>>,
+        /// Reader span, parent of the disk read.
+        span: Span,
     },
 
     SaveGroups {
@@ -89,10 +118,39 @@ pub enum StorageCommand {
         reply: oneshot::Sender<RetentionOutcome>,
     },
 
+    /// Forget the topic and delete its directory, or move it to `archive_to`.
     DropTopic {
         topic_name: String,
-        reply: oneshot::Sender<()>,
-    }
+        archive_to: Option<PathBuf>,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+
+    /// Write and fsync everything appended before it; resolves with the outcome.
+    Flush {
+        reply: oneshot::Sender<bool>,
+    },
+
+    /// Flush the topic's writes every `interval` instead of the global
+    /// interval (`None`: back to the global one).
+    SetFlushInterval {
+        topic_name: String,
+        interval: Option<Duration>,
+    },
+
+    /// A background upload landed: index the segment as tiered and delete the local file.
+    SegmentTiered {
+        topic_name: String,
+        local_path: PathBuf,
+        segment: TieredSegment,
+    },
+
+    /// A tiered segment was downloaded to `path` (its size), or could not be;
+    /// the cold reads waiting on it run again.
+    SegmentFetched {
+        key: String,
+        path: PathBuf,
+        result: Result<u64, String>,
+    },
 }
 
 pub struct TopicContext {
@@ -100,6 +158,7 @@ pub struct TopicContext {
     persisted_seq: Arc<AtomicU64>,
     highest_pending_seq: u64,
     current_file_size: u64,
+    last_flush: Instant,
 }
 
 // ==========================================
@@ -109,11 +168,27 @@ pub struct TopicContext {
 pub struct StorageManager {
     base_path: PathBuf,
     rx: mpsc::UnboundedReceiver<StorageCommand>,
-    open_files: LruCache<PathBuf, BufWriter<File>>,
+    open_files: LruCache<PathBuf, SegmentWriter>,
+    io: SegmentIo,
     topics: HashMap<String, TopicContext>,
     flush_interval: Duration,
+    /// Topics flushed on their own interval
+    flush_intervals: HashMap<String, Duration>,
     max_segment_size: u64,
     dirty_topics: HashSet<String>,
+    read_cache: Option<SegmentCache>,
+    group_commit: Duration,
+    /// Segments written by appends waiting in `sync_waiters`
+    sync_paths: HashSet<PathBuf>,
+    sync_waiters: Vec<oneshot::Sender<bool>>,
+    /// Set while waiters are pending: the group commit runs by then
+    commit_deadline: Option<Instant>,
+    /// An fsync owed to the pending waiters failed before the commit
+    sync_failed: bool,
+    /// Budget of the synchronous flush in `Drop` (zero = none)
+    drop_flush_timeout: Duration,
+    tiering: Option<Tiering>,
+    events: EventHandle,
 }
 
 impl StorageManager {
@@ -123,24 +198,67 @@ impl StorageManager {
         max_open_files: usize,
         flush_interval_ms: u64,
         max_segment_size: u64,
+        read_cache: Option<SegmentCache>,
+        write_backend: WriteBackend,
     ) -> Self {
         Self {
             base_path: PathBuf::from(base_path),
             rx,
             open_files: LruCache::new(NonZeroUsize::new(max_open_files).unwrap()),
+            io: SegmentIo::new(write_backend),
             topics: HashMap::new(),
             flush_interval: Duration::from_millis(flush_interval_ms),
+            flush_intervals: HashMap::new(),
             max_segment_size,
             dirty_topics: HashSet::new(),
+            read_cache,
+            group_commit: Duration::ZERO,
+            sync_paths: HashSet::new(),
+            sync_waiters: Vec::new(),
+            commit_deadline: None,
+            sync_failed: false,
+            drop_flush_timeout: Duration::ZERO,
+            tiering: None,
+            events: EventHandle::default(),
         }
     }
 
+    /// Wait up to `group_commit_ms` for more `FileSync` appends before each fsync.
+    pub fn with_group_commit(mut self, group_commit_ms: u64) -> Self {
+        self.group_commit = Duration::from_millis(group_commit_ms);
+        self
+    }
+
+    /// If the actor is dropped before its channel closes (runtime shutdown),
+    /// spend up to `timeout_ms` writing and fsyncing what it still holds.
+    pub fn with_drop_flush_timeout(mut self, timeout_ms: u64) -> Self {
+        self.drop_flush_timeout = Duration::from_millis(timeout_ms);
+        self
+    }
+
+    /// Move old sealed segments to object storage.
+    pub fn with_tiering(mut self, tiering: Tiering) -> Self {
+        self.tiering = Some(tiering);
+        self
+    }
+
+    /// Report write, fsync and open failures as `persistenceError` events.
+    pub fn with_events(mut self, events: EventHandle) -> Self {
+        self.events = events;
+        self
+    }
+
     pub async fn run(mut self) {
-        info!("StorageManager started");
-        let mut flush_timer = tokio::time::interval(self.flush_interval);
-        flush_timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        info!("StorageManager started ({:?} writes)", self.io.backend());
+        let mut flush_timer = Self::flush_timer(self.tick_period());
+        let mut tiering_timer = Self::flush_timer(self.tiering.as_ref().map_or(Duration::from_secs(3600), Tiering::check_interval));
+        let tiering_enabled = self.tiering.is_some();
 
         loop {
+            if flush_timer.period() != self.tick_period() {
+                flush_timer = Self::flush_timer(self.tick_period());
+            }
+            let commit_deadline = self.commit_deadline;
             tokio::select! {
                 cmd_res = self.rx.recv() => {
                     match cmd_res {
@@ -149,60 +267,181 @@ impl StorageManager {
                             while let Ok(next) = self.rx.try_recv() {
                                 self.handle_command(next).await;
                             }
+                            if self.commit_deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+                                self.group_commit().await;
+                            }
                         }
                         None => break,
                     }
                 }
+                _ = sleep_until(commit_deadline.unwrap_or_else(Instant::now)), if commit_deadline.is_some() => {
+                    self.group_commit().await;
+                }
                 _ = flush_timer.tick() => {
-                    self.flush_all().await;
+                    self.flush_due().await;
+                }
+                _ = tiering_timer.tick(), if tiering_enabled => {
+                    self.start_tiering().await;
                 }
             }
         }
         
-        self.flush_all().await;
+        self.sync_all().await;
+        // Everything is on disk: nothing left for `Drop`
+        self.drop_flush_timeout = Duration::ZERO;
         info!("StorageManager stopped");
     }
 
     async fn handle_command(&mut self, cmd: StorageCommand) {
         match cmd {
-            StorageCommand::Append { topic_name, messages, persisted_seq } => {
-                self.handle_append(topic_name, messages, persisted_seq).await;
+            StorageCommand::Append { topic_name, messages, persisted_seq, span, durable } => {
+                let write_span = tracing::info_span!(parent: &span, "stream.persist", topic = %topic_name, messages = messages.len());
+                let written = self.handle_append(topic_name, messages, persisted_seq).instrument(write_span).await;
+                if !durable.is_empty() {
+                    match written {
+                        Some(path) => {
+                            self.sync_paths.insert(path);
+                            self.sync_waiters.extend(durable);
+                            self.commit_deadline.get_or_insert_with(|| Instant::now() + self.group_commit);
+                        }
+                        None => {
+                            for done in durable {
+                                let _ = done.send(false);
+                            }
+                        }
+                    }
+                }
+            }
+            StorageCommand::ColdRead { topic_name, from_seq, limit, reply, span } => {
+                // The reader gave up (client gone, request cancelled): skip the disk
+                if reply.is_closed() {
+                    return;
+                }
+                let read_span = tracing::info_span!(parent: &span, "stream.cold_read", topic = %topic_name, from_seq, limit);
+                match self.cold_read(&topic_name, from_seq, limit).instrument(read_span).await {
+                    Ok(msgs) => { let _ = reply.send(msgs); }
+                    Err(miss) => {
+                        let read = StorageCommand::ColdRead { topic_name, from_seq, limit, reply, span };
+                        if let Some(tiering) = self.tiering.as_mut() {
+                            tiering.wait_for(miss, read);
+                        }
+                    }
+                }
+            }
+            StorageCommand::SegmentTiered { topic_name, local_path, segment } => {
+                self.segment_tiered(&topic_name, &local_path, segment).await;
             }
-            StorageCommand::ColdRead { topic_name, from_seq, limit, reply } => {
-                let msgs = self.cold_read(&topic_name, from_seq, limit).await;
-                let _ = reply.send(msgs);
+            StorageCommand::SegmentFetched { key, path, result } => {
+                let Some(tiering) = self.tiering.as_mut() else { return };
+                let reads = tiering.fetched(&key);
+                match result {
+                    Ok(size) => {
+                        for evicted in tiering.cache.insert(path, size) {
+                            if let Some(cache) = self.read_cache.as_mut() {
+                                cache.invalidate(&evicted);
+                            }
+                        }
+                        for read in reads {
+                            tiering.retry(read);
+                        }
+                    }
+                    Err(e) => {
+                        error!("Tiering: failed to fetch {} for {} cold reads: {}", key, reads.len(), e);
+                        for read in reads {
+                            if let StorageCommand::ColdRead { reply, .. } = read {
+                                let _ = reply.send(Vec::new());
+                            }
+                        }
+                    }
+                }
             }
             StorageCommand::SaveGroups { topic_name, groups_data } => {
                 let base_path = self.base_path.join(&topic_name);
                 if let Err(e) = save_groups_file(&base_path, &groups_data).await {
                     error!("Failed to save groups for {}: {}", topic_name, e);
+                    self.events.persistence_error("stream", Some(&topic_name), format!("save groups: {}", e));
                 }
             }
             StorageCommand::ApplyRetention { topic_name, retention, max_segment_size: _, reply } => {
                 let base_path = self.base_path.join(&topic_name);
-                let outcome = self.apply_retention(&topic_name, &base_path, &retention).await;
+                let mut outcome = self.apply_retention(&topic_name, &base_path, &retention).await;
+                // Count appends still buffered in the active segment's writer (retention never deletes it)
+                if let Some(ctx) = self.topics.get(&topic_name) {
+                    let flushed = tokio::fs::metadata(&ctx.active_path).await.map(|m| m.len()).unwrap_or(0);
+                    outcome.total_bytes = outcome.total_bytes.saturating_sub(flushed) + ctx.current_file_size;
+                }
                 let _ = reply.send(outcome);
             }
-            StorageCommand::DropTopic { topic_name, reply } => {
+            StorageCommand::DropTopic { topic_name, archive_to, reply } => {
+                let topic_path = self.base_path.join(&topic_name);
+                self.flush_intervals.remove(&topic_name);
                 if let Some(ctx) = self.topics.remove(&topic_name) {
-                    self.open_files.pop(&ctx.active_path);
+                    if let Some(writer) = self.open_files.pop(&ctx.active_path) {
+                        // An archived topic keeps everything it acked
+                        if archive_to.is_some() {
+                            self.close_writer(&ctx.active_path, writer).await;
+                        }
+                    }
                 }
-                let topic_path = self.base_path.join(&topic_name);
-                if topic_path.exists() {
-                    let _ = std::fs::remove_dir_all(&topic_path);
+                self.sync_paths.retain(|path| !path.starts_with(&topic_path));
+                if let Some(cache) = self.read_cache.as_mut() {
+                    cache.invalidate_dir(&topic_path);
                 }
-                let _ = reply.send(());
+                if let Some(tiering) = self.tiering.as_mut() {
+                    for copy in tiering.cache.remove_topic(&topic_name) {
+                        if let Some(cache) = self.read_cache.as_mut() {
+                            cache.invalidate(&copy);
+                        }
+                    }
+                    // An archived topic keeps its index, and the objects it points to
+                    if archive_to.is_none() {
+                        tiering.delete_objects(tiering::load_index(&topic_path).await.into_iter().map(|s| s.key).collect());
+                    }
+                }
+                let outcome = match archive_to {
+                    _ if !topic_path.exists() => Ok(()),
+                    Some(target) => activity::move_to_archive(&topic_path, &target)
+                        .map_err(|e| format!("Failed to archive {:?} to {:?}: {}", topic_path, target, e)),
+                    None => {
+                        let _ = std::fs::remove_dir_all(&topic_path);
+                        Ok(())
+                    }
+                };
+                let _ = reply.send(outcome);
+            }
+            StorageCommand::Flush { reply } => {
+                let ok = self.sync_all().await;
+                let _ = reply.send(ok);
+            }
+            StorageCommand::SetFlushInterval { topic_name, interval } => {
+                match interval.filter(|interval| *interval != self.flush_interval && !interval.is_zero()) {
+                    Some(interval) => self.flush_intervals.insert(topic_name, interval),
+                    None => self.flush_intervals.remove(&topic_name),
+                };
             }
         }
     }
 
+    fn flush_timer(period: Duration) -> tokio::time::Interval {
+        let mut timer = tokio::time::interval(period);
+        timer.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        timer
+    }
+
+    /// The timer ticks at the shortest interval in use.
+    fn tick_period(&self) -> Duration {
+        self.flush_intervals.values().copied().fold(self.flush_interval, Duration::min)
+    }
+
+    /// Write `messages` to the topic's active segment; returns the segment path
+    /// once the bytes are handed to its writer.
     async fn handle_append(
         &mut self,
         topic_name: String,
         messages: Vec<MessageToAppend>,
         persisted_seq: Arc<AtomicU64>,
-    ) {
-        if messages.is_empty() { return; }
+    ) -> Option<PathBuf> {
+        if messages.is_empty() { return None; }
 
         let highest_seq = messages.last().unwrap().seq;
         let base_topic_path = self.base_path.join(&topic_name);
@@ -211,7 +450,8 @@ impl StorageManager {
             if !base_topic_path.exists() {
                 if let Err(e) = tokio::fs::create_dir_all(&base_topic_path).await {
                     error!("FATAL: Failed to create topic dir {:?}: {}", base_topic_path, e);
-                    return;
+                    self.events.persistence_error("stream", Some(&topic_name), format!("create {:?}: {}", base_topic_path, e));
+                    return None;
                 }
             }
             
@@ -228,6 +468,7 @@ impl StorageManager {
                 persisted_seq: persisted_seq.clone(),
                 highest_pending_seq: 0,
                 current_file_size: file_size,
+                last_flush: Instant::now(),
             });
         } else {
             self.topics.get_mut(&topic_name).unwrap().persisted_seq = persisted_seq;
@@ -235,85 +476,198 @@ impl StorageManager {
 
         let mut buffer = Vec::new();
         for msg in &messages {
-            serialize_message(&mut buffer, msg.seq, msg.timestamp, &msg.payload);
+            serialize_message(&mut buffer, msg.seq, msg.timestamp, msg.lane, &msg.payload);
         }
         let bytes_len = buffer.len() as u64;
 
-        let path = {
+        let (path, sealed) = {
             let ctx = self.topics.get_mut(&topic_name).unwrap();
+            let mut sealed = None;
             if ctx.current_file_size + bytes_len > self.max_segment_size && ctx.current_file_size > 0 {
-                if let Some(mut old_writer) = self.open_files.pop(&ctx.active_path) {
-                    let _ = old_writer.flush().await;
-                }
+                sealed = self.open_files.pop(&ctx.active_path).map(|writer| (ctx.active_path.clone(), writer));
                 let first_seq = messages.first().unwrap().seq;
                 ctx.active_path = base_topic_path.join(format!("{}.log", first_seq));
                 ctx.current_file_size = 0;
             }
-            ctx.active_path.clone()
+            (ctx.active_path.clone(), sealed)
         };
+        if let Some((sealed_path, writer)) = sealed {
+            self.close_writer(&sealed_path, writer).await;
+        }
 
         match self.get_or_open_writer(&path).await {
             Ok(writer) => {
                 if let Err(e) = writer.write_all(&buffer).await {
                     error!("StorageManager: Failed to write to {:?}: {}", path, e);
+                    self.events.persistence_error("stream", Some(&topic_name), format!("write {:?}: {}", path, e));
                     self.open_files.pop(&path);
-                    return;
+                    return None;
                 }
                 let ctx = self.topics.get_mut(&topic_name).unwrap();
                 ctx.current_file_size += bytes_len;
                 ctx.highest_pending_seq = highest_seq;
                 self.dirty_topics.insert(topic_name.clone());
+                Some(path)
+            }
+            Err(e) => {
+                error!("StorageManager: Failed to open file {:?}: {}", path, e);
+                self.events.persistence_error("stream", Some(&topic_name), format!("open {:?}: {}", path, e));
+                None
             }
-            Err(e) => error!("StorageManager: Failed to open file {:?}: {}", path, e),
         }
     }
 
-    async fn get_or_open_writer(&mut self, path: &PathBuf) -> Result<&mut BufWriter<File>, std::io::Error> {
+    async fn get_or_open_writer(&mut self, path: &PathBuf) -> Result<&mut SegmentWriter, std::io::Error> {
         if !self.open_files.contains(path) {
             if self.open_files.len() == self.open_files.cap().get() {
-                if let Some((_, mut evicted_writer)) = self.open_files.pop_lru() {
-                    let _ = evicted_writer.flush().await;
+                if let Some((evicted_path, evicted_writer)) = self.open_files.pop_lru() {
+                    self.close_writer(&evicted_path, evicted_writer).await;
                 }
             }
-            let file = OpenOptions::new().create(true).append(true).open(path).await?;
-            self.open_files.put(path.clone(), BufWriter::new(file));
+            let writer = self.io.open(path).await?;
+            self.open_files.put(path.clone(), writer);
         }
         Ok(self.open_files.get_mut(path).unwrap())
     }
 
-    async fn flush_all(&mut self) {
-        for (_, writer) in self.open_files.iter_mut() {
+    /// Flush a writer leaving the open-file cache, fsyncing it first if
+    /// pending `FileSync` appends were written to it.
+    async fn close_writer(&mut self, path: &Path, mut writer: SegmentWriter) {
+        if self.sync_paths.remove(path) {
+            if let Err(e) = writer.sync().await {
+                error!("StorageManager: fsync of {:?} failed: {}", path, e);
+                self.events.persistence_error("stream", topic_of(&self.base_path, path).as_deref(), format!("fsync {:?}: {}", path, e));
+                self.sync_failed = true;
+            }
+        } else {
             let _ = writer.flush().await;
         }
+    }
 
-        if !self.dirty_topics.is_empty() {
-            let topics_to_flush: Vec<String> = self.dirty_topics.drain().collect();
-            for name in topics_to_flush {
-                if let Some(ctx) = self.topics.get_mut(&name) {
-                    if ctx.highest_pending_seq > 0 {
-                        ctx.persisted_seq.store(ctx.highest_pending_seq, Ordering::Release);
-                        ctx.highest_pending_seq = 0;
-                    }
+    /// Fsync every segment written by pending `FileSync` appends, then ack
+    /// them all with the combined outcome.
+    async fn group_commit(&mut self) {
+        self.commit_deadline = None;
+        let mut ok = !std::mem::take(&mut self.sync_failed);
+        for path in std::mem::take(&mut self.sync_paths) {
+            if let Some(writer) = self.open_files.peek_mut(&path) {
+                if let Err(e) = writer.sync().await {
+                    error!("StorageManager: fsync of {:?} failed: {}", path, e);
+                    self.events.persistence_error("stream", topic_of(&self.base_path, &path).as_deref(), format!("fsync {:?}: {}", path, e));
+                    ok = false;
                 }
             }
         }
+        for done in self.sync_waiters.drain(..) {
+            let _ = done.send(ok);
+        }
+    }
+
+    async fn flush_all(&mut self) {
+        self.io.flush_all(self.open_files.iter_mut().map(|(_, writer)| writer)).await;
+
+        let now = Instant::now();
+        for ctx in self.topics.values_mut() {
+            ctx.last_flush = now;
+        }
+        let topics_to_flush: Vec<String> = self.dirty_topics.drain().collect();
+        self.mark_persisted(topics_to_flush);
+    }
+
+    /// Timer flush: every topic whose interval elapsed since its last flush.
+    async fn flush_due(&mut self) {
+        if self.flush_intervals.is_empty() {
+            return self.flush_all().await;
+        }
+
+        let now = Instant::now();
+        // Ticks land on a fixed schedule and `last_flush` a little after: half a tick of slack
+        // keeps a topic on the tick's interval from skipping every other tick
+        let slack = self.tick_period() / 2;
+        let (flush_intervals, default) = (&self.flush_intervals, self.flush_interval);
+        let mut due_paths = HashSet::new();
+        let mut due = Vec::new();
+        for (name, ctx) in self.topics.iter_mut() {
+            let interval = flush_intervals.get(name).copied().unwrap_or(default);
+            if now.duration_since(ctx.last_flush) + slack >= interval {
+                ctx.last_flush = now;
+                due_paths.insert(ctx.active_path.clone());
+                due.push(name.clone());
+            }
+        }
+        if due.is_empty() {
+            return;
+        }
+
+        self.io.flush_all(self.open_files.iter_mut().filter(|(path, _)| due_paths.contains(*path)).map(|(_, writer)| writer)).await;
+        let flushed: Vec<String> = due.into_iter().filter(|name| self.dirty_topics.remove(name)).collect();
+        self.mark_persisted(flushed);
     }
 
-    async fn cold_read(&self, topic_name: &str, from_seq: u64, limit: usize) -> Vec<Message> {
+    /// Publish the flushed sequences of `names` to their topics.
+    fn mark_persisted(&mut self, names: Vec<String>) {
+        for name in names {
+            if let Some(ctx) = self.topics.get_mut(&name) {
+                if ctx.highest_pending_seq > 0 {
+                    ctx.persisted_seq.store(ctx.highest_pending_seq, Ordering::Release);
+                    ctx.highest_pending_seq = 0;
+                }
+            }
+        }
+    }
+
+    /// Flush and fsync every open segment, then run the pending group commit.
+    async fn sync_all(&mut self) -> bool {
+        self.flush_all().await;
+        let mut ok = true;
+        for (path, writer) in self.open_files.iter_mut() {
+            if let Err(e) = writer.sync().await {
+                error!("StorageManager: fsync of {:?} failed: {}", path, e);
+                self.events.persistence_error("stream", topic_of(&self.base_path, path).as_deref(), format!("fsync {:?}: {}", path, e));
+                ok = false;
+            }
+        }
+        self.sync_failed |= !ok;
+        self.group_commit().await;
+        ok
+    }
+
+    /// `Err` when the read reaches a tiered segment missing from the local
+    /// cache: it runs again once the segment is downloaded.
+    async fn cold_read(&mut self, topic_name: &str, from_seq: u64, limit: usize) -> Result<Vec<Message>, TierMiss> {
         let base_path = self.base_path.join(topic_name);
         let segments = find_segments(&base_path).await.unwrap_or_default();
         let mut all_msgs = Vec::new();
         let Some(first_segment) = segments.first() else {
-            return all_msgs;
+            return Ok(all_msgs);
         };
 
         let mut current_from_seq = from_seq.max(first_segment.start_seq);
         let mut remaining_limit = limit;
 
         if let Some(idx) = segments.iter().rposition(|s| s.start_seq <= current_from_seq) {
-            for segment in segments.iter().skip(idx) {
+            let last_idx = segments.len() - 1;
+            for (i, segment) in segments.iter().enumerate().skip(idx) {
                 if remaining_limit == 0 { break; }
-                let msgs = read_log_segment(&segment.path, current_from_seq, remaining_limit).await;
+                let path = match (&segment.tiered, self.tiering.as_mut()) {
+                    (None, _) => segment.path.clone(),
+                    (Some(tiered), Some(tiering)) => {
+                        let cached = tiering.cache.path(topic_name, segment.start_seq);
+                        if !tiering.cache.contains(&cached) {
+                            return Err(TierMiss { key: tiered.key.clone(), path: cached });
+                        }
+                        cached
+                    }
+                    (Some(tiered), None) => {
+                        warn!("StorageManager: segment {} of '{}' is tiered but tiering is not configured, skipping", tiered.key, topic_name);
+                        continue;
+                    }
+                };
+                // The last segment is still being appended to, so only sealed ones are cached
+                let msgs = match self.read_cache.as_mut() {
+                    Some(cache) if i < last_idx => cache.read(&path, current_from_seq, remaining_limit).await,
+                    _ => read_log_segment(&path, current_from_seq, remaining_limit).await,
+                };
                 if !msgs.is_empty() {
                     current_from_seq = msgs.last().unwrap().seq + 1;
                     remaining_limit = remaining_limit.saturating_sub(msgs.len());
@@ -321,21 +675,18 @@ impl StorageManager {
                 }
             }
         }
-        all_msgs
+        Ok(all_msgs)
     }
 
-    async fn apply_retention(&mut self, _topic_name: &str, base_path: &PathBuf, retention: &RetentionOptions) -> RetentionOutcome {
+    async fn apply_retention(&mut self, topic_name: &str, base_path: &PathBuf, retention: &RetentionOptions) -> RetentionOutcome {
         if retention.max_age_ms.is_none() && retention.max_bytes.is_none() {
-            return RetentionOutcome {
-                head_seq: find_segments(base_path).await.unwrap_or_default().first().map(|s| s.start_seq).unwrap_or(1),
-            };
+            return retention_outcome(base_path).await;
         }
         let mut segments = find_segments(base_path).await.unwrap_or_default();
         if segments.len() <= 1 {
-            return RetentionOutcome {
-                head_seq: segments.first().map(|s| s.start_seq).unwrap_or(1),
-            };
+            return retention_outcome(base_path).await;
         }
+        let (mut deleted_segments, mut deleted_bytes) = (0, 0);
 
         if let Some(max_age) = retention.max_age_ms {
             let limit = std::time::SystemTime::now() - Duration::from_millis(max_age);
@@ -344,14 +695,15 @@ impl StorageManager {
             for seg in segments {
                 let mut deleted = false;
                 if Some(seg.start_seq) != last_start_seq {
-                    if let Ok(metadata) = tokio::fs::metadata(&seg.path).await {
-                        if let Ok(modified) = metadata.modified() {
-                            if modified < limit {
-                                self.open_files.pop(&seg.path);
-                                let _ = tokio::fs::remove_file(&seg.path).await;
-                                deleted = true;
-                            }
-                        }
+                    let modified = match &seg.tiered {
+                        Some(tiered) => Some(UNIX_EPOCH + Duration::from_millis(tiered.modified_ms)),
+                        None => tokio::fs::metadata(&seg.path).await.and_then(|m| m.modified()).ok(),
+                    };
+                    if modified.is_some_and(|modified| modified < limit) {
+                        deleted_bytes += segment_size(&seg).await;
+                        self.delete_segment(topic_name, base_path, &seg).await;
+                        deleted_segments += 1;
+                        deleted = true;
                     }
                 }
                 if !deleted { survivors.push(seg); }
@@ -360,23 +712,177 @@ impl StorageManager {
         }
 
         if let Some(max_bytes) = retention.max_bytes {
-            let mut current_total: u64 = 0;
-            for seg in &segments {
-                current_total += tokio::fs::metadata(&seg.path).await.map(|m| m.len()).unwrap_or(0);
-            }
+            let mut current_total = segments_size(&segments).await;
             let mut i = 0;
             while current_total > max_bytes && i < segments.len().saturating_sub(1) {
                 let seg = &segments[i];
-                let size = tokio::fs::metadata(&seg.path).await.map(|m| m.len()).unwrap_or(0);
-                self.open_files.pop(&seg.path);
-                let _ = tokio::fs::remove_file(&seg.path).await;
+                let size = segment_size(seg).await;
+                self.delete_segment(topic_name, base_path, seg).await;
                 current_total = current_total.saturating_sub(size);
+                deleted_segments += 1;
+                deleted_bytes += size;
                 i += 1;
             }
         }
 
-        let head_seq = find_segments(base_path).await.unwrap_or_default().first().map(|s| s.start_seq).unwrap_or(1);
-        RetentionOutcome { head_seq }
+        RetentionOutcome { deleted_segments, deleted_bytes, ..retention_outcome(base_path).await }
+    }
+
+    /// Delete a segment dropped by retention, locally or in object storage.
+    async fn delete_segment(&mut self, topic_name: &str, base_path: &Path, seg: &Segment) {
+        let Some(tiered) = &seg.tiered else {
+            self.forget_segment(&seg.path);
+            let _ = tokio::fs::remove_file(&seg.path).await;
+            return;
+        };
+        let mut index = tiering::load_index(base_path).await;
+        index.retain(|s| s.start_seq != tiered.start_seq);
+        if let Err(e) = tiering::save_index(base_path, &index).await {
+            error!("Tiering: failed to update the index of '{}': {}", topic_name, e);
+            return;
+        }
+        if let Some(tiering) = self.tiering.as_mut() {
+            let cached = tiering.cache.path(topic_name, tiered.start_seq);
+            tiering.cache.remove(&cached);
+            if let Some(cache) = self.read_cache.as_mut() {
+                cache.invalidate(&cached);
+            }
+            tiering.delete_objects(vec![tiered.key.clone()]);
+        }
+    }
+
+    /// Upload pass over every topic (see `Tiering::start_upload`).
+    async fn start_tiering(&mut self) {
+        let Some(tiering) = self.tiering.as_ref() else { return };
+        let active: Vec<PathBuf> = self.topics.values().map(|ctx| ctx.active_path.clone()).collect();
+        tiering.start_upload(&self.base_path, &active).await;
+    }
+
+    /// An upload landed: index the segment, then drop the local copy. A
+    /// segment deleted meanwhile (retention, topic removed) is deleted remotely too.
+    async fn segment_tiered(&mut self, topic_name: &str, local_path: &Path, segment: TieredSegment) {
+        let Some(tiering) = self.tiering.as_ref() else { return };
+        let is_active = self.topics.get(topic_name).is_some_and(|ctx| ctx.active_path == local_path);
+        if is_active || !local_path.exists() {
+            tiering.delete_objects(vec![segment.key]);
+            return;
+        }
+        let base_path = self.base_path.join(topic_name);
+        let mut index = tiering::load_index(&base_path).await;
+        index.retain(|s| s.start_seq != segment.start_seq);
+        index.push(segment.clone());
+        index.sort_by_key(|s| s.start_seq);
+        if let Err(e) = tiering::save_index(&base_path, &index).await {
+            error!("Tiering: failed to update the index of '{}', keeping {:?}: {}", topic_name, local_path, e);
+            return;
+        }
+        self.forget_segment(local_path);
+        let _ = tokio::fs::remove_file(local_path).await;
+        info!("Tiering: moved segment {} of '{}' ({} bytes) to {}", segment.start_seq, topic_name, segment.size, segment.key);
+    }
+
+    /// Release the writer and cached blocks of a segment about to be deleted.
+    fn forget_segment(&mut self, path: &Path) {
+        self.open_files.pop(path);
+        self.sync_paths.remove(path);
+        if let Some(cache) = self.read_cache.as_mut() {
+            cache.invalidate(path);
+        }
+    }
+}
+
+impl Drop for StorageManager {
+    /// Best effort when the actor task is dropped mid-flight: write and fsync
+    /// the open segments, then the appends still queued, until the deadline.
+    /// A command the actor was awaiting in is lost with its future.
+    fn drop(&mut self) {
+        if self.drop_flush_timeout.is_zero() {
+            return;
+        }
+        let deadline = std::time::Instant::now() + self.drop_flush_timeout;
+
+        let mut queued: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+        let mut waiters = std::mem::take(&mut self.sync_waiters);
+        while std::time::Instant::now() < deadline {
+            match self.rx.try_recv() {
+                Ok(StorageCommand::Append { topic_name, messages, durable, .. }) => {
+                    let path = match self.topics.get(&topic_name) {
+                        Some(ctx) => ctx.active_path.clone(),
+                        None => active_segment_blocking(&self.base_path.join(&topic_name)),
+                    };
+                    let mut buffer = Vec::new();
+                    for msg in &messages {
+                        serialize_message(&mut buffer, msg.seq, msg.timestamp, msg.lane, &msg.payload);
+                    }
+                    queued.push((path, buffer));
+                    waiters.extend(durable);
+                }
+                Ok(StorageCommand::Flush { reply }) => waiters.push(reply),
+                // Reads, retention and drops answer nobody any more
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        if self.open_files.is_empty() && queued.is_empty() && waiters.is_empty() {
+            return;
+        }
+        warn!("StorageManager dropped with {} open segments and {} queued appends, flushing", self.open_files.len(), queued.len());
+
+        let mut ok = !self.sync_failed;
+        while let Some((path, writer)) = self.open_files.pop_lru() {
+            if std::time::Instant::now() >= deadline {
+                warn!("StorageManager: drop flush timed out, {:?} and later writes may be lost", path);
+                ok = false;
+                break;
+            }
+            if let Err(e) = writer.flush_blocking() {
+                error!("StorageManager: flush of {:?} on drop failed: {}", path, e);
+                ok = false;
+            }
+        }
+        for (path, buffer) in queued {
+            if std::time::Instant::now() >= deadline {
+                ok = false;
+                break;
+            }
+            let written = path.parent().map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::OpenOptions::new().create(true).append(true).open(&path))
+                .and_then(|mut file| {
+                    std::io::Write::write_all(&mut file, &buffer)?;
+                    file.sync_data()
+                });
+            if let Err(e) = written {
+                error!("StorageManager: write of {:?} on drop failed: {}", path, e);
+                ok = false;
+            }
+        }
+        for done in waiters {
+            let _ = done.send(ok);
+        }
+    }
+}
+
+/// The segment appends go to, found without a runtime (see `Drop`).
+fn active_segment_blocking(base_path: &Path) -> PathBuf {
+    let last = std::fs::read_dir(base_path).into_iter().flatten().flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".log")?.parse::<u64>().ok())
+        .max();
+    base_path.join(format!("{}.log", last.unwrap_or(1)))
+}
+
+/// The topic a segment belongs to: its directory under the stream root.
+fn topic_of(base_path: &Path, segment: &Path) -> Option<String> {
+    let dir = segment.parent()?.strip_prefix(base_path).ok()?;
+    dir.to_str().map(str::to_string)
+}
+
+async fn retention_outcome(base_path: &Path) -> RetentionOutcome {
+    let segments = find_segments(base_path).await.unwrap_or_default();
+    RetentionOutcome {
+        head_seq: segments.first().map(|s| s.start_seq).unwrap_or(1),
+        total_bytes: segments_size(&segments).await,
+        deleted_segments: 0,
+        deleted_bytes: 0,
     }
 }
 
@@ -384,23 +890,73 @@ impl StorageManager {
 // HELPERS (Formerly in writer.rs)
 // ==========================================
 
+/// Record length flag: a lane byte follows the timestamp.
+/// Normal-lane records never set it, so they keep the original layout.
+const LANE_FLAG: u32 = 1 << 31;
+
 /// Serialize a message into a buffer (does NOT write to disk).
-pub fn serialize_message(buf: &mut Vec<u8>, seq: u64, timestamp: u64, payload: &[u8]) {
+pub fn serialize_message(buf: &mut Vec<u8>, seq: u64, timestamp: u64, lane: u8, payload: &[u8]) {
     use bytes::BufMut;
-    let len = 8 + 8 + payload.len() as u32;
+    let has_lane = lane != LANE_NORMAL;
+    let mut len = 8 + 8 + payload.len() as u32;
     let mut hasher = Hasher::new();
     hasher.update(&seq.to_be_bytes());
     hasher.update(&timestamp.to_be_bytes());
+    if has_lane {
+        len += 1;
+        hasher.update(&[lane]);
+    }
     hasher.update(payload);
     let crc = hasher.finalize();
 
-    buf.put_u32(len);
+    buf.put_u32(if has_lane { len | LANE_FLAG } else { len });
     buf.put_u32(crc);
     buf.put_u64(seq);
     buf.put_u64(timestamp);
+    if has_lane {
+        buf.put_u8(lane);
+    }
     buf.put_slice(payload);
 }
 
+/// On-disk size of a record as written by `serialize_message`.
+pub fn record_size(lane: u8, payload_len: usize) -> u64 {
+    let lane_len = if lane != LANE_NORMAL { 1 } else { 0 };
+    4 + 4 + 8 + 8 + lane_len + payload_len as u64
+}
+
+/// Split a raw length field into (content length, has lane byte).
+pub(crate) fn decode_record_len(raw: u32) -> (u32, bool) {
+    (raw & !LANE_FLAG, raw & LANE_FLAG != 0)
+}
+
+/// Decode consecutive records from an in-memory slice of a segment,
+/// skipping records whose CRC does not match (same as `read_log_segment`).
+pub(crate) fn decode_records(mut buf: &[u8]) -> Vec<Message> {
+    use bytes::Buf;
+    let mut msgs = Vec::new();
+    while buf.remaining() >= 8 {
+        let (len, has_lane) = decode_record_len(buf.get_u32());
+        let stored_crc = buf.get_u32();
+        if buf.remaining() < len as usize { break; }
+        let (content, rest) = buf.split_at(len as usize);
+        buf = rest;
+
+        let mut hasher = Hasher::new();
+        hasher.update(content);
+        if hasher.finalize() != stored_crc { continue; }
+        if content.len() < 16 + has_lane as usize { continue; }
+
+        let mut cursor = content;
+        let seq = cursor.get_u64();
+        let timestamp = cursor.get_u64();
+        let lane = if has_lane { cursor.get_u8() } else { LANE_NORMAL };
+        let payload = Bytes::copy_from_slice(cursor);
+        msgs.push(Message { seq, timestamp, payload, lane });
+    }
+    msgs
+}
+
 /// Read messages from a log segment file starting at a given seq.
 pub async fn read_log_segment(path: &PathBuf, start_seq: u64, limit: usize) -> Vec<Message> {
     use bytes::Buf;
@@ -414,7 +970,7 @@ pub async fn read_log_segment(path: &PathBuf, start_seq: u64, limit: usize) -> V
     loop {
         let mut len_buf = [0u8; 4];
         if reader.read_exact(&mut len_buf).await.is_err() { break; }
-        let len = u32::from_be_bytes(len_buf);
+        let (len, has_lane) = decode_record_len(u32::from_be_bytes(len_buf));
 
         let mut crc_buf = [0u8; 4];
         if reader.read_exact(&mut crc_buf).await.is_err() { break; }
@@ -427,16 +983,17 @@ pub async fn read_log_segment(path: &PathBuf, start_seq: u64, limit: usize) -> V
         hasher.update(&content_buf);
         if hasher.finalize() != stored_crc { continue; }
 
-        if content_buf.len() < 16 { continue; }
+        if content_buf.len() < 16 + has_lane as usize { continue; }
         
         let mut cursor = std::io::Cursor::new(content_buf);
         let seq = cursor.get_u64();
         let timestamp = cursor.get_u64();
+        let lane = if has_lane { cursor.get_u8() } else { LANE_NORMAL };
         let payload_len = cursor.remaining();
         let payload = Bytes::copy_from_slice(&cursor.copy_to_bytes(payload_len));
 
         if seq >= start_seq {
-            msgs.push(Message { seq, timestamp, payload });
+            msgs.push(Message { seq, timestamp, payload, lane });
             if msgs.len() >= limit { break; }
         }
     }
@@ -454,6 +1011,7 @@ pub async fn recover_topic(topic_name: &str, base_path: PathBuf) -> RecoveredSta
         if let Some(last_segment) = segments.last() {
             state.messages = load_segment_file(&last_segment.path).await;
         }
+        state.total_bytes = segments_size(&segments).await;
         state.segments = segments;
     }
 
@@ -466,8 +1024,24 @@ pub async fn recover_topic(topic_name: &str, base_path: PathBuf) -> RecoveredSta
     state
 }
 
-/// Find all segment files for a topic, sorted by start_seq.
+/// Find all segments of a topic, local or tiered, sorted by start_seq.
+/// A segment both on disk and in the index (upload landed, local delete
+/// did not) is read from disk.
 pub async fn find_segments(base_path: &Path) -> std::io::Result<Vec<Segment>> {
+    let mut segments = find_local_segments(base_path).await?;
+    let local: HashSet<u64> = segments.iter().map(|s| s.start_seq).collect();
+    for tiered in tiering::load_index(base_path).await {
+        if !local.contains(&tiered.start_seq) {
+            let path = base_path.join(format!("{}.log", tiered.start_seq));
+            segments.push(Segment { path, start_seq: tiered.start_seq, tiered: Some(tiered) });
+        }
+    }
+    segments.sort_by_key(|s| s.start_seq);
+    Ok(segments)
+}
+
+/// Segment files in the topic directory, sorted by start_seq.
+pub async fn find_local_segments(base_path: &Path) -> std::io::Result<Vec<Segment>> {
     let mut segments = Vec::new();
     if !base_path.exists() { return Ok(segments); }
 
@@ -479,7 +1053,7 @@ pub async fn find_segments(base_path: &Path) -> std::io::Result<Vec<Segment>> {
         if fname.ends_with(".log") && fname != "groups.log" {
             let name_part = &fname[..fname.len() - 4];
             if let Ok(start_seq) = name_part.parse::<u64>() {
-                segments.push(Segment { path, start_seq });
+                segments.push(Segment { path, start_seq, tiered: None });
             }
         }
     }
@@ -487,6 +1061,22 @@ pub async fn find_segments(base_path: &Path) -> std::io::Result<Vec<Segment>> {
     Ok(segments)
 }
 
+/// Sum of segment sizes, on disk or in object storage.
+pub async fn segments_size(segments: &[Segment]) -> u64 {
+    let mut total = 0;
+    for seg in segments {
+        total += segment_size(seg).await;
+    }
+    total
+}
+
+async fn segment_size(seg: &Segment) -> u64 {
+    match &seg.tiered {
+        Some(tiered) => tiered.size,
+        None => tokio::fs::metadata(&seg.path).await.map(|m| m.len()).unwrap_or(0),
+    }
+}
+
 /// Write the groups.log file (ack_floor for each group). Atomic write via temp file + rename.
 pub async fn save_groups_file(base_path: &Path, groups: &HashMap<String, u64>) -> std::io::Result<()> {
     let tmp_path = base_path.join("groups.log.tmp");
@@ -540,7 +1130,7 @@ async fn load_segment_file(path: &PathBuf) -> VecDeque<Message> {
     loop {
         let mut len_buf = [0u8; 4];
         if reader.read_exact(&mut len_buf).await.is_err() { break; }
-        let len = u32::from_be_bytes(len_buf);
+        let (len, has_lane) = decode_record_len(u32::from_be_bytes(len_buf));
 
         let mut crc_buf = [0u8; 4];
         if reader.read_exact(&mut crc_buf).await.is_err() { break; }
@@ -553,15 +1143,16 @@ async fn load_segment_file(path: &PathBuf) -> VecDeque<Message> {
         hasher.update(&content_buf);
         if hasher.finalize() != stored_crc { break; }
 
-        if content_buf.len() < 16 { break; }
+        if content_buf.len() < 16 + has_lane as usize { break; }
         
         let mut cursor = std::io::Cursor::new(content_buf);
         let seq = cursor.get_u64();
         let timestamp = cursor.get_u64();
+        let lane = if has_lane { cursor.get_u8() } else { LANE_NORMAL };
         let payload_len = cursor.remaining();
         let payload = Bytes::copy_from_slice(&cursor.copy_to_bytes(payload_len));
 
-        msgs.push_back(Message { seq, timestamp, payload });
+        msgs.push_back(Message { seq, timestamp, payload, lane });
     }
     msgs
 }
@@ -0,0 +1,183 @@
+//! Read cache for sealed stream segments.
+//!
+//! Only the tail segment is kept in memory after recovery, so historical reads
+//! (replays, dashboard paging) go to disk. Sealed segments never change: they
+//! are split into record-aligned blocks of roughly `BLOCK_BYTES`, located via
+//! a per-segment index built from a single sequential scan, and decoded blocks
+//! are kept in a bounded LRU keyed by (segment path, block number).
+
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use lru::LruCache;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, BufReader, SeekFrom};
+use tracing::warn;
+
+use crate::brokers::stream::domain::message::Message;
+use crate::brokers::stream::domain::persistence::{decode_record_len, decode_records};
+
+/// Target size of a cached block; a block always ends on a record boundary.
+const BLOCK_BYTES: u64 = 64 * 1024;
+
+/// Hit/miss counters, shared with the manager for snapshots.
+#[derive(Debug, Default)]
+pub struct ReadCacheStats {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    pub blocks: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BlockEntry {
+    first_seq: u64,
+    offset: u64,
+    len: u64,
+}
+
+pub struct SegmentCache {
+    blocks: LruCache<(PathBuf, usize), Arc<Vec<Message>>>,
+    indexes: LruCache<PathBuf, Arc<Vec<BlockEntry>>>,
+    stats: Arc<ReadCacheStats>,
+}
+
+impl SegmentCache {
+    /// `None` when `capacity` (in blocks) is 0: cold reads then decode straight from disk.
+    pub fn new(capacity: usize, stats: Arc<ReadCacheStats>) -> Option<Self> {
+        let capacity = NonZeroUsize::new(capacity)?;
+        Some(Self {
+            blocks: LruCache::new(capacity),
+            // One index per segment; each is a few KB even for large segments
+            indexes: LruCache::new(capacity),
+            stats,
+        })
+    }
+
+    /// Up to `limit` messages with `seq >= from_seq` from a sealed segment.
+    pub async fn read(&mut self, path: &Path, from_seq: u64, limit: usize) -> Vec<Message> {
+        let mut msgs = Vec::new();
+        let Some(index) = self.index(path).await else {
+            return msgs;
+        };
+
+        let start = index.partition_point(|block| block.first_seq <= from_seq).saturating_sub(1);
+        for (block_no, entry) in index.iter().enumerate().skip(start) {
+            let Some(block) = self.block(path, block_no, entry).await else {
+                break;
+            };
+            for msg in block.iter().filter(|m| m.seq >= from_seq) {
+                msgs.push(msg.clone());
+                if msgs.len() >= limit {
+                    return msgs;
+                }
+            }
+        }
+        msgs
+    }
+
+    /// Drop everything cached for a segment (deleted by retention).
+    pub fn invalidate(&mut self, path: &Path) {
+        self.indexes.pop(path);
+        self.retain_blocks(|block_path| block_path != path);
+    }
+
+    /// Drop everything cached under a topic directory (topic deleted).
+    pub fn invalidate_dir(&mut self, dir: &Path) {
+        let paths: Vec<PathBuf> = self.indexes.iter()
+            .filter(|(path, _)| path.starts_with(dir))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in paths {
+            self.indexes.pop(&path);
+        }
+        self.retain_blocks(|block_path| !block_path.starts_with(dir));
+    }
+
+    fn retain_blocks(&mut self, keep: impl Fn(&Path) -> bool) {
+        let keys: Vec<(PathBuf, usize)> = self.blocks.iter()
+            .filter(|((path, _), _)| !keep(path))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            self.blocks.pop(&key);
+        }
+        self.stats.blocks.store(self.blocks.len() as u64, Ordering::Relaxed);
+    }
+
+    async fn index(&mut self, path: &Path) -> Option<Arc<Vec<BlockEntry>>> {
+        if let Some(index) = self.indexes.get(path) {
+            return Some(index.clone());
+        }
+        match build_index(path).await {
+            Ok(index) => {
+                let index = Arc::new(index);
+                self.indexes.put(path.to_path_buf(), index.clone());
+                Some(index)
+            }
+            Err(e) => {
+                warn!("Failed to index segment {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    async fn block(&mut self, path: &Path, block_no: usize, entry: &BlockEntry) -> Option<Arc<Vec<Message>>> {
+        let key = (path.to_path_buf(), block_no);
+        if let Some(block) = self.blocks.get(&key) {
+            self.stats.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(block.clone());
+        }
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+
+        let block = match read_block(path, entry).await {
+            Ok(block) => Arc::new(block),
+            Err(e) => {
+                warn!("Failed to read block {} of segment {:?}: {}", block_no, path, e);
+                return None;
+            }
+        };
+        self.blocks.put(key, block.clone());
+        self.stats.blocks.store(self.blocks.len() as u64, Ordering::Relaxed);
+        Some(block)
+    }
+}
+
+/// Scan a segment once, cutting it into record-aligned blocks. CRCs are
+/// checked later, when a block is decoded; a torn record ends the scan.
+async fn build_index(path: &Path) -> std::io::Result<Vec<BlockEntry>> {
+    let size = tokio::fs::metadata(path).await?.len();
+    let mut reader = BufReader::with_capacity(BLOCK_BYTES as usize, File::open(path).await?);
+    let mut index: Vec<BlockEntry> = Vec::new();
+    let mut content = Vec::new();
+    let mut offset = 0u64;
+
+    while offset + 8 <= size {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).await?;
+        let (len, _) = decode_record_len(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        let record_len = 8 + len as u64;
+        if len < 8 || offset + record_len > size {
+            break;
+        }
+        content.resize(len as usize, 0);
+        reader.read_exact(&mut content).await?;
+        let seq = u64::from_be_bytes(content[..8].try_into().expect("8 bytes"));
+
+        match index.last_mut() {
+            Some(block) if block.len < BLOCK_BYTES => block.len += record_len,
+            _ => index.push(BlockEntry { first_seq: seq, offset, len: record_len }),
+        }
+        offset += record_len;
+    }
+    Ok(index)
+}
+
+async fn read_block(path: &Path, entry: &BlockEntry) -> std::io::Result<Vec<Message>> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(entry.offset)).await?;
+    let mut buf = vec![0u8; entry.len as usize];
+    file.read_exact(&mut buf).await?;
+    Ok(decode_records(&buf))
+}
@@ -0,0 +1,342 @@
+//! Segment file writers used by the storage actor.
+//!
+//! `Buffered` is the portable default: a tokio `BufWriter` per open segment,
+//! each flush being its own blocking write. With the `io-uring` build feature
+//! on Linux, `IoUring` keeps each segment's pending bytes in memory and the
+//! periodic flush writes every dirty segment in a single ring submission, so
+//! many active topics cost one blocking hop instead of one per file.
+
+use std::io;
+use std::path::Path;
+
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tracing::{error, warn};
+
+use crate::brokers::stream::config::WriteBackend;
+
+pub enum SegmentWriter {
+    Buffered(BufWriter<File>),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(uring::UringSegment),
+}
+
+impl SegmentWriter {
+    pub async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => writer.write_all(data).await,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(segment) => segment.write_all(data).await,
+        }
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => writer.flush().await,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(segment) => segment.flush().await,
+        }
+    }
+
+    /// Flush, then fsync the file's data (`FileSync` commits).
+    pub async fn sync(&mut self) -> io::Result<()> {
+        self.flush().await?;
+        match self {
+            Self::Buffered(writer) => writer.get_ref().sync_data().await,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(segment) => segment.sync_data().await,
+        }
+    }
+
+    /// Write what is still buffered and fsync, without a runtime: the
+    /// storage actor's `Drop`. Fails if the file has an operation in flight.
+    pub fn flush_blocking(self) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => {
+                let pending = writer.buffer().to_vec();
+                let mut file = writer.into_inner().try_into_std()
+                    .map_err(|_| io::Error::new(io::ErrorKind::WouldBlock, "file operation in flight"))?;
+                std::io::Write::write_all(&mut file, &pending)?;
+                file.sync_data()
+            }
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(segment) => segment.flush_blocking(),
+        }
+    }
+}
+
+/// Opens segment writers for the configured backend and flushes them.
+pub struct SegmentIo {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ring: Option<uring::Ring>,
+}
+
+impl SegmentIo {
+    /// Falls back to `Buffered` (with a warning) when io_uring is unavailable.
+    pub fn new(backend: WriteBackend) -> Self {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        {
+            let ring = match backend {
+                WriteBackend::Buffered => None,
+                WriteBackend::IoUring => match uring::Ring::new() {
+                    Ok(ring) => Some(ring),
+                    Err(e) => {
+                        warn!("StorageManager: io_uring unavailable ({}), using buffered writes", e);
+                        None
+                    }
+                },
+            };
+            Self { ring }
+        }
+
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        {
+            if backend == WriteBackend::IoUring {
+                warn!("StorageManager: built without the io-uring feature, using buffered writes");
+            }
+            Self {}
+        }
+    }
+
+    pub fn backend(&self) -> WriteBackend {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if self.ring.is_some() {
+            return WriteBackend::IoUring;
+        }
+        WriteBackend::Buffered
+    }
+
+    pub async fn open(&self, path: &Path) -> io::Result<SegmentWriter> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(ring) = &self.ring {
+            return uring::UringSegment::open(path, ring.clone()).await.map(SegmentWriter::Uring);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path).await?;
+        Ok(SegmentWriter::Buffered(BufWriter::new(file)))
+    }
+
+    /// Flush every writer. Errors are logged: the bytes stay unacknowledged
+    /// (`persisted_seq` only moves after a flush round).
+    pub async fn flush_all<'a>(&self, writers: impl Iterator<Item = &'a mut SegmentWriter>) {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if let Some(ring) = &self.ring {
+            let segments = writers.filter_map(|writer| match writer {
+                SegmentWriter::Uring(segment) => Some(segment),
+                SegmentWriter::Buffered(_) => None,
+            });
+            if let Err(e) = uring::flush_batch(ring, segments).await {
+                error!("StorageManager: io_uring flush failed: {}", e);
+            }
+            return;
+        }
+
+        for writer in writers {
+            if let Err(e) = writer.flush().await {
+                error!("StorageManager: flush failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring {
+    use std::collections::VecDeque;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use io_uring::{opcode, types, IoUring};
+
+    const RING_ENTRIES: u32 = 256;
+    /// Failed waits tolerated while draining a batch before it is abandoned
+    const DRAIN_ATTEMPTS: usize = 16;
+    /// Tags each batch's entries, so completions left over from an abandoned
+    /// batch are not taken for the next one's
+    static BATCH: AtomicU64 = AtomicU64::new(0);
+    /// Pending bytes per segment before a write goes out without waiting for the flush tick
+    const MAX_PENDING: usize = 4 * 1024 * 1024;
+
+    #[derive(Clone)]
+    pub struct Ring(Arc<Mutex<IoUring>>);
+
+    impl Ring {
+        pub fn new() -> io::Result<Self> {
+            Ok(Self(Arc::new(Mutex::new(IoUring::new(RING_ENTRIES)?))))
+        }
+    }
+
+    pub struct UringSegment {
+        file: Arc<std::fs::File>,
+        /// File length once everything pending is written
+        offset: u64,
+        pending: Vec<u8>,
+        ring: Ring,
+    }
+
+    impl UringSegment {
+        pub async fn open(path: &Path, ring: Ring) -> io::Result<Self> {
+            let file = tokio::fs::OpenOptions::new().create(true).write(true).truncate(false).open(path).await?;
+            let len = file.metadata().await?.len();
+            Ok(Self { file: Arc::new(file.into_std().await), offset: len, pending: Vec::new(), ring })
+        }
+
+        pub async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+            self.pending.extend_from_slice(data);
+            if self.pending.len() >= MAX_PENDING {
+                self.flush().await?;
+            }
+            Ok(())
+        }
+
+        pub async fn flush(&mut self) -> io::Result<()> {
+            let ring = self.ring.clone();
+            flush_batch(&ring, std::iter::once(self)).await
+        }
+
+        pub async fn sync_data(&self) -> io::Result<()> {
+            let file = self.file.clone();
+            tokio::task::spawn_blocking(move || file.sync_data()).await.map_err(io::Error::other)?
+        }
+
+        pub fn flush_blocking(self) -> io::Result<()> {
+            use std::os::unix::fs::FileExt;
+            self.file.write_all_at(&self.pending, self.offset)?;
+            self.file.sync_data()
+        }
+    }
+
+    struct WriteJob {
+        file: Arc<std::fs::File>,
+        buf: Vec<u8>,
+        offset: u64,
+        written: usize,
+    }
+
+    /// Write the pending bytes of all `segments` in one submission. Segments
+    /// whose write failed keep their bytes and retry on the next flush.
+    pub async fn flush_batch<'a>(ring: &Ring, segments: impl Iterator<Item = &'a mut UringSegment>) -> io::Result<()> {
+        let mut owners = Vec::new();
+        let mut jobs = Vec::new();
+        for segment in segments.filter(|s| !s.pending.is_empty()) {
+            jobs.push(WriteJob {
+                file: segment.file.clone(),
+                buf: std::mem::take(&mut segment.pending),
+                offset: segment.offset,
+                written: 0,
+            });
+            owners.push(segment);
+        }
+        if jobs.is_empty() {
+            return Ok(());
+        }
+
+        let ring = ring.0.clone();
+        let (jobs, results) = tokio::task::spawn_blocking(move || {
+            let mut ring = ring.lock().unwrap_or_else(|e| e.into_inner());
+            let mut jobs = jobs;
+            let results = write_jobs(&mut ring, &mut jobs);
+            (jobs, results)
+        })
+        .await
+        .map_err(io::Error::other)?;
+
+        let mut first_error = None;
+        for ((segment, job), result) in owners.into_iter().zip(jobs).zip(results) {
+            match result {
+                Ok(()) => segment.offset += job.buf.len() as u64,
+                Err(e) => {
+                    // Put the bytes back in front of anything written since
+                    let mut buf = job.buf;
+                    buf.extend_from_slice(&segment.pending);
+                    segment.pending = buf;
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Drive all jobs to completion, resubmitting short writes.
+    ///
+    /// A job's buffer and file must outlive every entry submitted for it. If
+    /// the ring fails, the entries already pushed are still waited for; when
+    /// even that fails, the unfinished jobs' buffers and files are leaked (the
+    /// caller gets copies), since the kernel may still be using them.
+    fn write_jobs(ring: &mut IoUring, jobs: &mut [WriteJob]) -> Vec<io::Result<()>> {
+        let batch = BATCH.fetch_add(1, Ordering::Relaxed) << 32;
+        let mut results: Vec<Option<io::Result<()>>> = (0..jobs.len()).map(|_| None).collect();
+        let mut queued: VecDeque<usize> = (0..jobs.len()).collect();
+        let mut in_flight = 0;
+        let mut failure: Option<io::Error> = None;
+        let mut failed_waits = 0;
+
+        while (failure.is_none() && !queued.is_empty()) || in_flight > 0 {
+            while let Some(&i) = queued.front().filter(|_| failure.is_none()) {
+                let job = &jobs[i];
+                let rest = &job.buf[job.written..];
+                let entry = opcode::Write::new(types::Fd(job.file.as_raw_fd()), rest.as_ptr(), rest.len().min(u32::MAX as usize) as u32)
+                    .offset(job.offset + job.written as u64)
+                    .build()
+                    .user_data(batch | i as u64);
+                // SAFETY: the buffer lives in `jobs`, untouched until its completion is reaped below
+                // or leaked if it never is
+                if unsafe { ring.submission().push(&entry) }.is_err() {
+                    break;
+                }
+                queued.pop_front();
+                in_flight += 1;
+            }
+
+            if let Err(e) = ring.submit_and_wait(1) {
+                if e.kind() != io::ErrorKind::Interrupted {
+                    failed_waits += 1;
+                    failure.get_or_insert(e);
+                    if failed_waits > DRAIN_ATTEMPTS {
+                        break;
+                    }
+                }
+            }
+
+            let completions: Vec<(u64, i32)> = ring.completion().map(|cqe| (cqe.user_data(), cqe.result())).collect();
+            for (user_data, res) in completions {
+                if user_data & !u64::from(u32::MAX) != batch {
+                    continue;
+                }
+                let i = (user_data & u64::from(u32::MAX)) as usize;
+                in_flight -= 1;
+                if res < 0 {
+                    results[i] = Some(Err(io::Error::from_raw_os_error(-res)));
+                } else if res == 0 {
+                    results[i] = Some(Err(io::ErrorKind::WriteZero.into()));
+                } else {
+                    jobs[i].written += res as usize;
+                    if jobs[i].written < jobs[i].buf.len() {
+                        queued.push_back(i);
+                    } else {
+                        results[i] = Some(Ok(()));
+                    }
+                }
+            }
+        }
+
+        let Some(failure) = failure else {
+            return results.into_iter().map(|r| r.unwrap_or(Ok(()))).collect();
+        };
+        if in_flight > 0 {
+            // Gave up draining: some entries may still be read by the kernel
+            for (job, result) in jobs.iter_mut().zip(&results) {
+                if result.is_none() {
+                    let copy = job.buf.clone();
+                    std::mem::forget(std::mem::replace(&mut job.buf, copy));
+                    std::mem::forget(job.file.clone());
+                }
+            }
+        }
+        results.into_iter()
+            .map(|r| r.unwrap_or_else(|| Err(io::Error::new(failure.kind(), failure.to_string()))))
+            .collect()
+    }
+}
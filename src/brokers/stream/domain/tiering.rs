@@ -0,0 +1,306 @@
+//! Tiering of old stream segments to S3-compatible object storage.
+//!
+//! Every `STREAM_TIERING_CHECK_MS` the storage actor looks for sealed
+//! segments not written to for `STREAM_TIERING_AGE_MS` and uploads them from
+//! a background task. Once an upload lands, the actor records the segment in
+//! the topic's `tiered.json` (the location index) and deletes the local file.
+//! A cold read that reaches a tiered segment has it downloaded into a local
+//! cache bounded by `STREAM_TIERING_CACHE_BYTES`, then runs again.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::brokers::stream::config::TieringConfig;
+use crate::brokers::stream::domain::object_store::ObjectStore;
+use crate::brokers::stream::domain::persistence::{find_local_segments, StorageCommand};
+
+/// Location index of a topic's tiered segments, next to its segments.
+pub const INDEX_FILE: &str = "tiered.json";
+
+/// A segment held in object storage instead of the topic directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TieredSegment {
+    pub start_seq: u64,
+    pub key: String,
+    pub size: u64,
+    /// Last write of the local file (ms), for age retention
+    pub modified_ms: u64,
+}
+
+/// The topic's tiered segments, by start sequence (empty if it has none).
+pub async fn load_index(topic_dir: &Path) -> Vec<TieredSegment> {
+    let path = topic_dir.join(INDEX_FILE);
+    let Ok(data) = tokio::fs::read(&path).await else { return Vec::new() };
+    match serde_json::from_slice::<Vec<TieredSegment>>(&data) {
+        Ok(mut segments) => {
+            segments.sort_by_key(|s| s.start_seq);
+            segments
+        }
+        Err(e) => {
+            warn!("Ignoring unreadable tiering index {:?}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Atomic write via temp file + rename; an empty index removes the file.
+pub async fn save_index(topic_dir: &Path, segments: &[TieredSegment]) -> std::io::Result<()> {
+    let path = topic_dir.join(INDEX_FILE);
+    if segments.is_empty() {
+        return match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let tmp_path = topic_dir.join(format!("{}.tmp", INDEX_FILE));
+    tokio::fs::write(&tmp_path, serde_json::to_vec_pretty(segments)?).await?;
+    tokio::fs::rename(&tmp_path, &path).await
+}
+
+/// A cold read waiting for a tiered segment to reach the local cache.
+pub struct TierMiss {
+    pub key: String,
+    pub path: PathBuf,
+}
+
+/// Local copies of tiered segments, least recently read evicted first.
+pub struct TierCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    total_bytes: u64,
+    files: LruCache<PathBuf, u64>,
+}
+
+impl TierCache {
+    /// Pick up the copies a previous run left in `dir`.
+    fn open(dir: PathBuf, max_bytes: u64) -> Self {
+        let mut cache = Self { dir, max_bytes, total_bytes: 0, files: LruCache::unbounded() };
+        for topic in std::fs::read_dir(&cache.dir).into_iter().flatten().flatten() {
+            for file in std::fs::read_dir(topic.path()).into_iter().flatten().flatten() {
+                let path = file.path();
+                match file.metadata() {
+                    Ok(meta) if meta.is_file() && path.extension().is_some_and(|ext| ext == "log") => {
+                        cache.total_bytes += meta.len();
+                        cache.files.put(path, meta.len());
+                    }
+                    // Downloads cut short by a restart
+                    _ => { let _ = std::fs::remove_file(&path); }
+                }
+            }
+        }
+        cache
+    }
+
+    pub fn path(&self, topic_name: &str, start_seq: u64) -> PathBuf {
+        self.dir.join(topic_name).join(format!("{}.log", start_seq))
+    }
+
+    /// Whether `path` is cached, marking it as recently read.
+    pub fn contains(&mut self, path: &Path) -> bool {
+        self.files.get(path).is_some() && path.exists()
+    }
+
+    /// Record a downloaded copy; returns the copies evicted to stay within
+    /// budget (the newest one always stays).
+    pub fn insert(&mut self, path: PathBuf, size: u64) -> Vec<PathBuf> {
+        if let Some(previous) = self.files.put(path, size) {
+            self.total_bytes -= previous;
+        }
+        self.total_bytes += size;
+        let mut evicted = Vec::new();
+        while self.total_bytes > self.max_bytes && self.files.len() > 1 {
+            let Some((path, size)) = self.files.pop_lru() else { break };
+            self.total_bytes -= size;
+            let _ = std::fs::remove_file(&path);
+            evicted.push(path);
+        }
+        evicted
+    }
+
+    /// Drop the copies of a topic's segments; returns their paths.
+    pub fn remove_topic(&mut self, topic_name: &str) -> Vec<PathBuf> {
+        let dir = self.dir.join(topic_name);
+        let paths: Vec<PathBuf> = self.files.iter().map(|(path, _)| path.clone()).filter(|path| path.starts_with(&dir)).collect();
+        for path in &paths {
+            self.remove(path);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+        paths
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Some(size) = self.files.pop(path) {
+            self.total_bytes -= size;
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Tiering state of the storage actor.
+pub struct Tiering {
+    store: Arc<ObjectStore>,
+    config: TieringConfig,
+    pub cache: TierCache,
+    /// Set while an upload pass runs
+    uploading: Arc<AtomicBool>,
+    /// Cold reads waiting on a download, by object key
+    waiting: HashMap<String, Vec<StorageCommand>>,
+    /// Completions go back to the actor; weak, so the actor still stops
+    /// once every manager handle is gone
+    actor: mpsc::WeakUnboundedSender<StorageCommand>,
+}
+
+impl Tiering {
+    pub fn new(config: &TieringConfig, actor: &mpsc::UnboundedSender<StorageCommand>) -> Result<Self, String> {
+        let store = ObjectStore::new(config)?;
+        Ok(Self {
+            store: Arc::new(store),
+            config: config.clone(),
+            cache: TierCache::open(PathBuf::from(&config.cache_path), config.cache_bytes),
+            uploading: Arc::new(AtomicBool::new(false)),
+            waiting: HashMap::new(),
+            actor: actor.downgrade(),
+        })
+    }
+
+    pub fn check_interval(&self) -> Duration {
+        Duration::from_millis(self.config.check_interval_ms.max(1))
+    }
+
+    pub fn object_key(&self, topic_name: &str, start_seq: u64) -> String {
+        let prefix = self.config.prefix.trim_matches('/');
+        match prefix.is_empty() {
+            true => format!("{}/{}.log", topic_name, start_seq),
+            false => format!("{}/{}/{}.log", prefix, topic_name, start_seq),
+        }
+    }
+
+    /// Upload the sealed segments under `base_path` old enough to leave the
+    /// disk, unless a pass is still running. `active` segments are never taken.
+    pub async fn start_upload(&self, base_path: &Path, active: &[PathBuf]) {
+        if self.uploading.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let max_modified = std::time::SystemTime::now() - Duration::from_millis(self.config.age_ms);
+        let mut due = Vec::new();
+        let mut topics = match tokio::fs::read_dir(base_path).await {
+            Ok(entries) => entries,
+            Err(_) => {
+                self.uploading.store(false, Ordering::Release);
+                return;
+            }
+        };
+        while let Ok(Some(entry)) = topics.next_entry().await {
+            let Some(topic_name) = entry.file_name().to_str().map(str::to_string) else { continue };
+            let segments = find_local_segments(&entry.path()).await.unwrap_or_default();
+            // The last segment is the one appends go to
+            for segment in segments.iter().take(segments.len().saturating_sub(1)) {
+                if active.contains(&segment.path) {
+                    continue;
+                }
+                let Ok(meta) = tokio::fs::metadata(&segment.path).await else { continue };
+                let Ok(modified) = meta.modified() else { continue };
+                if modified <= max_modified {
+                    let modified_ms = modified.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+                    let tiered = TieredSegment {
+                        start_seq: segment.start_seq,
+                        key: self.object_key(&topic_name, segment.start_seq),
+                        size: meta.len(),
+                        modified_ms,
+                    };
+                    due.push((topic_name.clone(), segment.path.clone(), tiered));
+                }
+            }
+        }
+        if due.is_empty() {
+            self.uploading.store(false, Ordering::Release);
+            return;
+        }
+
+        let (store, uploading, actor) = (self.store.clone(), self.uploading.clone(), self.actor.clone());
+        tokio::spawn(async move {
+            for (topic_name, local_path, segment) in due {
+                let uploaded = match tokio::fs::read(&local_path).await {
+                    Ok(data) => store.put(&segment.key, data.into()).await,
+                    // Deleted meanwhile (retention, topic removed)
+                    Err(_) => continue,
+                };
+                match uploaded {
+                    Ok(()) => {
+                        let Some(actor) = actor.upgrade() else { break };
+                        let _ = actor.send(StorageCommand::SegmentTiered { topic_name, local_path, segment });
+                    }
+                    Err(e) => warn!("Tiering: upload of {:?} failed, retrying next pass: {}", local_path, e),
+                }
+            }
+            uploading.store(false, Ordering::Release);
+        });
+    }
+
+    /// Park `read` until the segment of `miss` is downloaded, starting the
+    /// download unless one is already running.
+    pub fn wait_for(&mut self, miss: TierMiss, read: StorageCommand) {
+        let waiting = self.waiting.entry(miss.key.clone()).or_default();
+        waiting.push(read);
+        if waiting.len() > 1 {
+            return;
+        }
+        let (store, actor) = (self.store.clone(), self.actor.clone());
+        tokio::spawn(async move {
+            let TierMiss { key, path } = miss;
+            let result = match store.get(&key).await {
+                Ok(Some(data)) => write_cached(&path, &data).await.map(|_| data.len() as u64).map_err(|e| e.to_string()),
+                Ok(None) => Err("object not found".to_string()),
+                Err(e) => Err(e),
+            };
+            if let Some(actor) = actor.upgrade() {
+                let _ = actor.send(StorageCommand::SegmentFetched { key, path, result });
+            }
+        });
+    }
+
+    /// The reads parked on `key`, to run again.
+    pub fn fetched(&mut self, key: &str) -> Vec<StorageCommand> {
+        self.waiting.remove(key).unwrap_or_default()
+    }
+
+    /// Re-queue a read behind the commands already waiting.
+    pub fn retry(&self, read: StorageCommand) {
+        if let Some(actor) = self.actor.upgrade() {
+            let _ = actor.send(read);
+        }
+    }
+
+    /// Delete objects in the background (retention, topic removed).
+    pub fn delete_objects(&self, keys: Vec<String>) {
+        if keys.is_empty() {
+            return;
+        }
+        let store = self.store.clone();
+        tokio::spawn(async move {
+            for key in keys {
+                if let Err(e) = store.delete(&key).await {
+                    warn!("Tiering: delete of {} failed: {}", key, e);
+                }
+            }
+        });
+    }
+}
+
+async fn write_cached(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".part");
+    tokio::fs::write(&tmp_path, data).await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
@@ -1,11 +1,14 @@
 //! Topic: Pure Logic Struct (No Actors, No Channels)
 //! Single append-only log per topic (no partitions).
 
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::labels::Labels;
+use crate::brokers::rate::RateWindow;
 use crate::brokers::stream::domain::message::Message;
-use crate::brokers::stream::options::{StreamCreateOptions, RetentionOptions};
+use crate::brokers::stream::domain::persistence::record_size;
+use crate::brokers::stream::options::{AlterOptions, StreamCreateOptions, RetentionOptions};
 use crate::brokers::stream::config::SystemStreamConfig;
-use std::collections::VecDeque;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
@@ -16,12 +19,30 @@ pub struct TopicConfig {
     pub retention: RetentionOptions,
     pub retention_check_ms: u64,
     pub default_flush_ms: u64,
+    /// Flush interval set by alter (`None`: the server's)
+    #[serde(default)]
+    pub flush_ms: Option<u64>,
+    /// Publishes held this long to share one storage append (0 = none)
+    #[serde(default)]
+    pub linger_ms: u64,
+    /// Messages kept in the RAM window once flushed (0 = tail-only)
     pub ram_soft_limit: usize,
     pub ram_hard_limit: usize,
+    /// Payload bytes kept in the RAM window once flushed (0 = no cap)
+    #[serde(default)]
+    pub ram_max_bytes: u64,
     pub eviction_interval_ms: u64,
     pub max_ack_pending: usize,
     pub ack_wait_ms: u64,
     pub max_deliveries: u32,
+    #[serde(default)]
+    pub priority_lanes: bool,
+    #[serde(default)]
+    pub persistence: PersistenceMode,
+    #[serde(default)]
+    pub dead_letter: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: Labels,
 }
 
 impl TopicConfig {
@@ -49,12 +70,101 @@ impl TopicConfig {
             retention,
             retention_check_ms: sys.retention_check_interval_ms,
             default_flush_ms: sys.default_flush_ms,
-            ram_soft_limit: sys.ram_soft_limit,
+            flush_ms: None,
+            linger_ms: opts.linger_ms.unwrap_or(sys.default_linger_ms),
+            ram_soft_limit: opts.max_memory_messages.unwrap_or(sys.ram_soft_limit),
             ram_hard_limit: sys.ram_hard_limit,
+            ram_max_bytes: opts.max_memory_bytes.unwrap_or(0),
             eviction_interval_ms: sys.eviction_interval_ms,
             max_ack_pending: sys.max_ack_pending,
             ack_wait_ms: sys.ack_wait_ms,
             max_deliveries: sys.max_deliveries,
+            priority_lanes: opts.priority_lanes.unwrap_or(false),
+            persistence: if sys.ephemeral { PersistenceMode::FileAsync } else { opts.persistence.unwrap_or(sys.persistence) },
+            dead_letter: opts.dead_letter.unwrap_or(false),
+            labels: opts.labels.unwrap_or_default(),
+        }
+    }
+
+    /// The settings `opts` states explicitly that differ from this config,
+    /// as `setting: current (requested ...)`. Unstated settings never conflict.
+    pub fn conflicts(&self, opts: &StreamCreateOptions, sys: &SystemStreamConfig) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        let mut check = |setting: &str, current: String, requested: String| {
+            if current != requested {
+                conflicts.push(format!("{}: {} (requested {})", setting, current, requested));
+            }
+        };
+        let limit = |v: Option<u64>| v.map_or("unlimited".to_string(), |v| v.to_string());
+        let mode = |m: PersistenceMode| match m {
+            PersistenceMode::FileAsync => "fileAsync".to_string(),
+            PersistenceMode::FileSync => "fileSync".to_string(),
+        };
+
+        if let Some(r) = &opts.retention {
+            if let Some(age) = r.max_age_ms {
+                check("retention.maxAgeMs", limit(self.retention.max_age_ms), limit(Some(age).filter(|v| *v > 0)));
+            }
+            if let Some(bytes) = r.max_bytes {
+                check("retention.maxBytes", limit(self.retention.max_bytes), limit(Some(bytes).filter(|v| *v > 0)));
+            }
+        }
+        if let Some(lanes) = opts.priority_lanes {
+            check("priorityLanes", self.priority_lanes.to_string(), lanes.to_string());
+        }
+        // Ephemeral mode overrides any requested mode, so there is nothing to contradict
+        if let Some(requested) = opts.persistence.filter(|_| !sys.ephemeral) {
+            check("persistence", mode(self.persistence), mode(requested));
+        }
+        if let Some(dead_letter) = opts.dead_letter {
+            check("deadLetter", self.dead_letter.to_string(), dead_letter.to_string());
+        }
+        if let Some(linger) = opts.linger_ms {
+            check("lingerMs", self.linger_ms.to_string(), linger.to_string());
+        }
+        if let Some(messages) = opts.max_memory_messages {
+            check("maxMemoryMessages", self.ram_soft_limit.to_string(), messages.to_string());
+        }
+        if let Some(bytes) = opts.max_memory_bytes {
+            check("maxMemoryBytes", self.ram_max_bytes.to_string(), bytes.to_string());
+        }
+        if let Some(labels) = &opts.labels {
+            let pairs = |labels: &Labels| format!("{{{}}}", labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(","));
+            check("labels", pairs(&self.labels), pairs(labels));
+        }
+        conflicts
+    }
+
+    /// Apply the settings `opts` states explicitly (alter).
+    pub fn apply(&mut self, opts: &AlterOptions, sys: &SystemStreamConfig) {
+        if let Some(r) = &opts.retention {
+            if let Some(age) = r.max_age_ms {
+                self.retention.max_age_ms = Some(age).filter(|v| *v > 0);
+            }
+            if let Some(bytes) = r.max_bytes {
+                self.retention.max_bytes = Some(bytes).filter(|v| *v > 0);
+            }
+        }
+        if let Some(mode) = opts.persistence.filter(|_| !sys.ephemeral) {
+            self.persistence = mode;
+        }
+        if let Some(dead_letter) = opts.dead_letter {
+            self.dead_letter = dead_letter;
+        }
+        if let Some(flush_ms) = opts.flush_ms {
+            self.flush_ms = Some(flush_ms).filter(|ms| *ms > 0);
+        }
+        if let Some(linger_ms) = opts.linger_ms {
+            self.linger_ms = linger_ms;
+        }
+        if let Some(messages) = opts.max_memory_messages {
+            self.ram_soft_limit = messages;
+        }
+        if let Some(bytes) = opts.max_memory_bytes {
+            self.ram_max_bytes = bytes;
+        }
+        if let Some(labels) = &opts.labels {
+            self.labels = labels.clone();
         }
     }
 }
@@ -66,8 +176,22 @@ pub struct TopicState {
     pub next_seq: u64,
     pub head_seq: u64,
     pub ram_start_seq: u64,   // first seq in RAM window
+    /// Log size in bytes: disk size at the last recovery/retention pass plus appends since
+    pub bytes: u64,
+    /// Payload bytes of the RAM window
+    pub ram_bytes: u64,
     // Config
     pub ram_soft_limit: usize,
+    /// 0 = no byte cap
+    pub ram_max_bytes: u64,
+    /// Appends waiting for their fsync (`FileSync`, `sync`): readers see
+    /// nothing from the first of them on
+    unsynced: BTreeSet<u64>,
+    /// Appends whose fsync failed: their publisher got an error, readers skip them
+    discarded: BTreeSet<u64>,
+    /// Appends and their record bytes over the last minute
+    publishes: RateWindow,
+    published_bytes: RateWindow,
 }
 
 impl TopicState {
@@ -78,13 +202,21 @@ impl TopicState {
             next_seq: 1, // sequences start at 1 (0 = "nothing processed")
             head_seq: 1,
             ram_start_seq: 1,
+            bytes: 0,
+            ram_bytes: 0,
             ram_soft_limit,
+            ram_max_bytes: 0,
+            unsynced: BTreeSet::new(),
+            discarded: BTreeSet::new(),
+            publishes: RateWindow::default(),
+            published_bytes: RateWindow::default(),
         }
     }
 
-    pub fn restore(name: String, ram_soft_limit: usize, head_seq: u64, messages: VecDeque<Message>) -> Self {
+    pub fn restore(name: String, ram_soft_limit: usize, head_seq: u64, messages: VecDeque<Message>, bytes: u64) -> Self {
         let next_seq = messages.back().map(|m| m.seq + 1).unwrap_or(head_seq.max(1));
         let ram_start_seq = messages.front().map(|m| m.seq).unwrap_or(next_seq.max(head_seq));
+        let ram_bytes = messages.iter().map(|m| m.payload.len() as u64).sum();
 
         Self {
             name,
@@ -92,51 +224,115 @@ impl TopicState {
             next_seq,
             head_seq,
             ram_start_seq,
+            bytes,
+            ram_bytes,
             ram_soft_limit,
+            ram_max_bytes: 0,
+            unsynced: BTreeSet::new(),
+            discarded: BTreeSet::new(),
+            publishes: RateWindow::default(),
+            published_bytes: RateWindow::default(),
         }
     }
 
-    pub fn append(&mut self, payload: Bytes) -> (u64, u64) {
+    /// Appends per second and record bytes per second over the minute up to `now`.
+    pub fn publish_rates(&mut self, now: u64) -> (f64, f64) {
+        (self.publishes.per_sec(now), self.published_bytes.per_sec(now))
+    }
+
+    /// Append a record stamped `timestamp` (`now`, or e.g. preserved from a
+    /// mirrored source); `now` feeds the publish rates.
+    pub fn append_at(&mut self, payload: Bytes, lane: u8, timestamp: u64, now: u64) -> (u64, u64) {
         let seq = self.next_seq;
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
 
         if self.log.is_empty() {
             self.ram_start_seq = seq;
         }
 
+        let size = record_size(lane, payload.len());
+        self.publishes.record(now);
+        self.published_bytes.record_many(now, size);
+        self.bytes += size;
+        self.ram_bytes += payload.len() as u64;
         self.log.push_back(Message {
             seq,
             timestamp,
             payload,
+            lane,
         });
         self.next_seq += 1;
 
         (seq, timestamp)
     }
 
+    /// Hide `seq` and every later append from readers until `settle`.
+    pub fn hold(&mut self, seq: u64) {
+        self.unsynced.insert(seq);
+    }
+
+    /// The fsync of a held append is over: it becomes readable on success,
+    /// is skipped for good on failure.
+    pub fn settle(&mut self, seq: u64, synced: bool) {
+        if self.unsynced.remove(&seq) && !synced {
+            self.discarded.insert(seq);
+        }
+    }
+
+    /// First seq readers may not see yet.
+    pub fn visible_end(&self) -> u64 {
+        self.unsynced.first().copied().unwrap_or(self.next_seq)
+    }
+
+    pub fn is_discarded(&self, seq: u64) -> bool {
+        self.discarded.contains(&seq)
+    }
+
+    /// Record `seq` if it is in RAM and readable (discarded ones included,
+    /// see `is_discarded`).
+    pub fn get(&self, seq: u64) -> Option<Message> {
+        if seq < self.ram_start_seq || seq >= self.visible_end() {
+            return None; // cold read needed, or not readable yet
+        }
+        self.log.get((seq - self.ram_start_seq) as usize).cloned()
+    }
+
     pub fn read(&self, from_seq: u64, limit: usize) -> Vec<Message> {
         let from_seq = from_seq.max(self.head_seq).max(1);
+        let end = self.visible_end();
         // Hot read: from RAM
         if from_seq >= self.ram_start_seq && !self.log.is_empty() {
             let idx = (from_seq - self.ram_start_seq) as usize;
             if idx < self.log.len() {
-                return self.log.iter().skip(idx).take(limit).cloned().collect();
+                return self.log.iter().skip(idx)
+                    .take_while(|m| m.seq < end)
+                    .filter(|m| !self.discarded.contains(&m.seq))
+                    .take(limit).cloned().collect();
             }
         }
         // Cold read is handled by the manager via StorageManager
         Vec::new()
     }
 
-    /// Evict old messages from RAM front (only if persisted to disk)
+    /// Evict old messages from RAM front (only if persisted to disk) down to
+    /// the message and byte caps of the window
     pub fn evict(&mut self, persisted_seq: u64) {
-        while self.log.len() > self.ram_soft_limit {
+        self.evict_to(persisted_seq, self.ram_soft_limit, self.ram_max_bytes);
+    }
+
+    /// Evict every persisted message from RAM, whatever the soft limit (memory
+    /// pressure): later reads of them go to disk.
+    pub fn spill(&mut self, persisted_seq: u64) {
+        self.evict_to(persisted_seq, 0, 0);
+    }
+
+    fn evict_to(&mut self, persisted_seq: u64, keep: usize, max_bytes: u64) {
+        while self.log.len() > keep || (max_bytes > 0 && self.ram_bytes > max_bytes) {
             if let Some(front) = self.log.front() {
-                if front.seq <= persisted_seq {
+                // Held appends stay in RAM: a cold read must not serve them either
+                if front.seq <= persisted_seq && front.seq < self.visible_end() {
                     if let Some(removed) = self.log.pop_front() {
                         self.ram_start_seq = (removed.seq + 1).max(self.head_seq);
+                        self.ram_bytes -= removed.payload.len() as u64;
                     }
                 } else {
                     break; // don't evict unpersisted data
@@ -149,9 +345,12 @@ impl TopicState {
 
     pub fn apply_head(&mut self, head_seq: u64) {
         self.head_seq = head_seq.max(1);
+        self.discarded.retain(|seq| *seq >= self.head_seq);
         while let Some(front) = self.log.front() {
             if front.seq < self.head_seq {
-                self.log.pop_front();
+                if let Some(removed) = self.log.pop_front() {
+                    self.ram_bytes -= removed.payload.len() as u64;
+                }
             } else {
                 break;
             }
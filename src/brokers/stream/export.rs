@@ -0,0 +1,228 @@
+//! Topic dumps: a topic's records written to a file in a format other tools
+//! read, for migrating to or from Kafka-like systems and seeding test
+//! environments. Two formats:
+//!
+//! * `jsonl`: one JSON object per line, `{"offset","timestamp","lane","payload"}`
+//!   with the payload in base64. A `key` field is read but not kept: Nexo
+//!   records have none.
+//! * `binary`: the magic `NEXODUMP`, a version byte, then per record a u32
+//!   length followed by offset (u64), timestamp (u64), lane (u8), key length
+//!   (u32), key and payload. Integers are big-endian.
+//!
+//! An import detects the format from the first bytes and appends the records
+//! in file order, keeping their timestamps. Offsets are renumbered from the
+//! target topic's next sequence.
+
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+use crate::brokers::stream::domain::message::Message;
+use crate::brokers::error::{ErrorCode, NexoError};
+use crate::brokers::payload::base64_payload;
+
+pub const BINARY_MAGIC: &[u8; 8] = b"NEXODUMP";
+pub const BINARY_VERSION: u8 = 1;
+/// Records read from the topic per cold read during an export
+pub const EXPORT_BATCH: usize = 500;
+/// offset + timestamp + lane + key length
+const RECORD_HEADER: usize = 8 + 8 + 1 + 4;
+
+#[derive(Debug, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DumpFormat {
+    #[default]
+    Jsonl,
+    Binary,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DumpRecord {
+    pub offset: u64,
+    pub timestamp: u64,
+    #[serde(default)]
+    pub lane: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(with = "base64_payload")]
+    pub payload: Bytes,
+}
+
+impl From<&Message> for DumpRecord {
+    fn from(msg: &Message) -> Self {
+        Self { offset: msg.seq, timestamp: msg.timestamp, lane: msg.lane, key: None, payload: msg.payload.clone() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TopicExportSummary {
+    pub records: u64,
+    pub first_offset: Option<u64>,
+    pub last_offset: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct TopicImportSummary {
+    pub records: u64,
+    /// High-lane records imported on the normal lane (the topic has no priority lanes)
+    pub lane_downgraded: u64,
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> NexoError {
+    NexoError::new(ErrorCode::Storage, format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Writes records to a temp file next to `path`, named per export so two
+/// exports to the same path do not share it; `finish` moves it in place.
+pub struct DumpWriter {
+    format: DumpFormat,
+    path: PathBuf,
+    tmp: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl DumpWriter {
+    pub async fn create(path: &Path, format: DumpFormat) -> Result<Self, NexoError> {
+        let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4().simple()));
+        let file = File::create(&tmp).await.map_err(|e| io_error(path, e))?;
+        let mut writer = Self { format, path: path.to_path_buf(), tmp, out: BufWriter::new(file) };
+        if format == DumpFormat::Binary {
+            writer.out.write_all(BINARY_MAGIC).await.map_err(|e| io_error(path, e))?;
+            writer.out.write_u8(BINARY_VERSION).await.map_err(|e| io_error(path, e))?;
+        }
+        Ok(writer)
+    }
+
+    pub async fn write(&mut self, record: &DumpRecord) -> Result<(), NexoError> {
+        let written = match self.format {
+            DumpFormat::Jsonl => {
+                let mut line = serde_json::to_vec(record).map_err(|e| NexoError::invalid(e.to_string()))?;
+                line.push(b'\n');
+                self.out.write_all(&line).await
+            }
+            DumpFormat::Binary => {
+                let key = record.key.as_deref().unwrap_or("").as_bytes();
+                let len = RECORD_HEADER + key.len() + record.payload.len();
+                let mut frame = Vec::with_capacity(4 + len);
+                frame.extend_from_slice(&(len as u32).to_be_bytes());
+                frame.extend_from_slice(&record.offset.to_be_bytes());
+                frame.extend_from_slice(&record.timestamp.to_be_bytes());
+                frame.push(record.lane);
+                frame.extend_from_slice(&(key.len() as u32).to_be_bytes());
+                frame.extend_from_slice(key);
+                frame.extend_from_slice(&record.payload);
+                self.out.write_all(&frame).await
+            }
+        };
+        written.map_err(|e| io_error(&self.path, e))
+    }
+
+    pub async fn finish(mut self) -> Result<(), NexoError> {
+        let done = async {
+            self.out.flush().await?;
+            self.out.get_ref().sync_all().await?;
+            tokio::fs::rename(&self.tmp, &self.path).await
+        };
+        match done.await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&self.tmp).await;
+                Err(io_error(&self.path, e))
+            }
+        }
+    }
+}
+
+/// Reads a dump of either format, one record at a time.
+pub struct DumpReader {
+    format: DumpFormat,
+    input: BufReader<File>,
+    /// 1-based record (binary) or line (jsonl) number, for errors
+    position: u64,
+}
+
+impl DumpReader {
+    pub async fn open(path: &Path) -> Result<Self, NexoError> {
+        let file = File::open(path).await
+            .map_err(|e| NexoError::invalid(format!("Cannot read {}: {}", path.display(), e)))?;
+        let mut input = BufReader::new(file);
+        let head = input.fill_buf().await
+            .map_err(|e| NexoError::invalid(format!("Cannot read {}: {}", path.display(), e)))?;
+        let format = match head.starts_with(BINARY_MAGIC) {
+            true => DumpFormat::Binary,
+            false => DumpFormat::Jsonl,
+        };
+        if format == DumpFormat::Binary {
+            let mut header = [0u8; 9];
+            input.read_exact(&mut header).await.map_err(|e| NexoError::invalid(format!("Invalid dump header: {}", e)))?;
+            if header[8] == 0 || header[8] > BINARY_VERSION {
+                return Err(NexoError::invalid(format!("Unsupported dump version {}", header[8])));
+            }
+        }
+        Ok(Self { format, input, position: 0 })
+    }
+
+    pub async fn next(&mut self) -> Result<Option<DumpRecord>, NexoError> {
+        match self.format {
+            DumpFormat::Jsonl => self.next_line().await,
+            DumpFormat::Binary => self.next_frame().await,
+        }
+    }
+
+    async fn next_line(&mut self) -> Result<Option<DumpRecord>, NexoError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            self.position += 1;
+            let read = self.input.read_line(&mut line).await
+                .map_err(|e| NexoError::invalid(format!("Line {}: {}", self.position, e)))?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                break;
+            }
+        }
+        serde_json::from_str(line.trim())
+            .map(Some)
+            .map_err(|e| NexoError::invalid(format!("Line {}: {}", self.position, e)))
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<DumpRecord>, NexoError> {
+        self.position += 1;
+        let invalid = |position: u64, e: String| NexoError::invalid(format!("Record {}: {}", position, e));
+        let mut len = [0u8; 4];
+        match self.input.read_exact(&mut len).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(invalid(self.position, e.to_string())),
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        if len < RECORD_HEADER {
+            return Err(invalid(self.position, format!("length {} shorter than a record header", len)));
+        }
+        let max = Config::global().server.max_payload_size;
+        if len - RECORD_HEADER > max {
+            return Err(invalid(self.position, format!("length {} over MAX_PAYLOAD_SIZE ({})", len, max)));
+        }
+        let mut frame = vec![0u8; len];
+        self.input.read_exact(&mut frame).await.map_err(|e| invalid(self.position, e.to_string()))?;
+        let offset = u64::from_be_bytes(frame[0..8].try_into().unwrap());
+        let timestamp = u64::from_be_bytes(frame[8..16].try_into().unwrap());
+        let lane = frame[16];
+        let key_len = u32::from_be_bytes(frame[17..21].try_into().unwrap()) as usize;
+        if RECORD_HEADER + key_len > len {
+            return Err(invalid(self.position, format!("key length {} overruns the record", key_len)));
+        }
+        let key = &frame[RECORD_HEADER..RECORD_HEADER + key_len];
+        let key = (!key.is_empty()).then(|| String::from_utf8_lossy(key).into_owned());
+        let payload = Bytes::copy_from_slice(&frame[RECORD_HEADER + key_len..]);
+        Ok(Some(DumpRecord { offset, timestamp, lane, key, payload }))
+    }
+}
@@ -3,14 +3,21 @@
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::get;
+use axum::routing::{get, post, put};
 use axum::Router;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::brokers::stream::snapshot::{ConsumerGroupSnapshot, StreamSnapshot, TopicSnapshot};
+use crate::brokers::labels::{LabelSelector, Labels};
+use crate::brokers::stream::snapshot::{BackfillSnapshot, ConsumerGroupSnapshot, MirrorSnapshot, ReadCacheSnapshot, StreamSnapshot, TopicSnapshot};
 use crate::brokers::stream::domain::topic::TopicConfig;
-use crate::transport::http::payload::payload_to_json_value;
+use crate::brokers::stream::key_stats::KeyStatsSpec;
+use crate::brokers::stream::checkpoint::{CheckpointMatch, GroupCheckpoint};
+use crate::brokers::stream::export::DumpFormat;
+use crate::brokers::stream::options::{AlterOptions, StreamCreateOptions};
+use crate::transport::http::export_path;
+use crate::brokers::payload::render_payload;
+use crate::transport::tcp::protocol::ErrorCode;
 use crate::NexoEngine;
 
 const STREAM_PAGE_SIZE: usize = 50;
@@ -23,11 +30,91 @@ const STREAM_MAX_PAGE_SIZE: usize = 500;
 #[derive(Serialize)]
 pub struct StreamBrokerSnapshot {
     pub topics: Vec<TopicSummary>,
+    pub mirrors: Vec<MirrorSummary>,
+    pub backfills: Vec<BackfillSummary>,
+    pub read_cache: ReadCacheSummary,
 }
 
 impl From<StreamSnapshot> for StreamBrokerSnapshot {
     fn from(s: StreamSnapshot) -> Self {
-        Self { topics: s.topics.into_iter().map(Into::into).collect() }
+        Self {
+            topics: s.topics.into_iter().map(Into::into).collect(),
+            mirrors: s.mirrors.into_iter().map(Into::into).collect(),
+            backfills: s.backfills.into_iter().map(Into::into).collect(),
+            read_cache: s.read_cache.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReadCacheSummary {
+    pub capacity: usize,
+    pub blocks: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl From<ReadCacheSnapshot> for ReadCacheSummary {
+    fn from(c: ReadCacheSnapshot) -> Self {
+        Self { capacity: c.capacity, blocks: c.blocks, hits: c.hits, misses: c.misses }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MirrorSummary {
+    pub source: String,
+    pub local_topic: String,
+    pub connected: bool,
+    pub mirrored: u64,
+    pub last_seq: u64,
+    pub lag_ms: u64,
+    pub last_error: Option<String>,
+}
+
+impl From<MirrorSnapshot> for MirrorSummary {
+    fn from(m: MirrorSnapshot) -> Self {
+        Self {
+            source: m.source,
+            local_topic: m.local_topic,
+            connected: m.connected,
+            mirrored: m.mirrored,
+            last_seq: m.last_seq,
+            lag_ms: m.lag_ms,
+            last_error: m.last_error,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BackfillSummary {
+    pub id: u64,
+    pub source: String,
+    pub target: String,
+    pub from_seq: u64,
+    pub to_seq: u64,
+    pub next_seq: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub state: &'static str,
+    pub error: Option<String>,
+}
+
+impl From<BackfillSnapshot> for BackfillSummary {
+    fn from(b: BackfillSnapshot) -> Self {
+        Self {
+            id: b.id,
+            source: b.source,
+            target: b.target,
+            from_seq: b.from_seq,
+            to_seq: b.to_seq,
+            next_seq: b.next_seq,
+            copied: b.copied,
+            skipped: b.skipped,
+            failed: b.failed,
+            state: b.state,
+            error: b.error,
+        }
     }
 }
 
@@ -35,7 +122,18 @@ impl From<StreamSnapshot> for StreamBrokerSnapshot {
 pub struct TopicSummary {
     pub name: String,
     pub last_seq: u64,
+    pub head_seq: u64,
+    pub persisted_seq: u64,
+    pub bytes: u64,
+    pub publish_rate: f64,
+    pub byte_rate: f64,
+    pub ram_messages: usize,
+    pub ram_bytes: u64,
+    pub hot_reads: u64,
+    pub cold_reads: u64,
     pub groups: Vec<ConsumerGroupSummary>,
+    pub traced: bool,
+    pub recovered: bool,
     pub config: TopicConfig,
 }
 
@@ -44,7 +142,18 @@ impl From<TopicSnapshot> for TopicSummary {
         Self {
             name: t.name,
             last_seq: t.last_seq,
+            head_seq: t.head_seq,
+            persisted_seq: t.persisted_seq,
+            bytes: t.bytes,
+            publish_rate: t.publish_rate,
+            byte_rate: t.byte_rate,
+            ram_messages: t.ram_messages,
+            ram_bytes: t.ram_bytes,
+            hot_reads: t.hot_reads,
+            cold_reads: t.cold_reads,
             groups: t.groups.into_iter().map(Into::into).collect(),
+            traced: t.traced,
+            recovered: t.recovered,
             config: t.config,
         }
     }
@@ -68,6 +177,7 @@ pub struct MessagePreview {
     pub seq: u64,
     pub timestamp: String,
     pub payload: Value,
+    pub content_type: &'static str,
 }
 
 #[derive(Serialize)]
@@ -84,13 +194,72 @@ pub struct StreamMessagesQuery {
     pub limit: Option<usize>,
 }
 
+/// `label` is a selector: `team=payments,env!=prod`
+#[derive(Deserialize)]
+pub struct StreamListQuery {
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TopicLabelsRequest {
+    pub labels: Labels,
+}
+
+/// `path` is relative to `EXPORT_DIR` on the server.
+#[derive(Deserialize)]
+pub struct TopicExportRequest {
+    pub path: String,
+    #[serde(default)]
+    pub format: DumpFormat,
+}
+
+#[derive(Deserialize)]
+pub struct TopicImportRequest {
+    pub path: String,
+    /// Settings of the topic created by the import
+    #[serde(default)]
+    pub options: StreamCreateOptions,
+}
+
+#[derive(Deserialize)]
+pub struct CheckpointImportRequest {
+    pub checkpoint: GroupCheckpoint,
+    #[serde(default, rename = "match")]
+    pub by: CheckpointMatch,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointImportResult {
+    pub ack_floor: u64,
+}
+
 // ==========================================
 // HANDLERS
 // ==========================================
 
-async fn get_stream(State(engine): State<NexoEngine>) -> impl IntoResponse {
-    let snap = engine.stream.get_snapshot().await;
-    axum::Json(StreamBrokerSnapshot::from(snap))
+async fn get_stream(State(engine): State<NexoEngine>, Query(query): Query<StreamListQuery>) -> impl IntoResponse {
+    let selector = match LabelSelector::parse(query.label.as_deref().unwrap_or("")) {
+        Ok(selector) => selector,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.message).into_response(),
+    };
+    let mut snap = engine.stream.get_snapshot().await;
+    snap.topics.retain(|t| selector.matches(&t.config.labels));
+    axum::Json(StreamBrokerSnapshot::from(snap)).into_response()
+}
+
+async fn set_topic_labels(
+    State(engine): State<NexoEngine>,
+    Path(topic): Path<String>,
+    axum::Json(body): axum::Json<TopicLabelsRequest>,
+) -> impl IntoResponse {
+    let options = AlterOptions { labels: Some(body.labels.clone()), ..Default::default() };
+    match engine.stream.alter_topic(&topic, options).await {
+        Ok(()) => axum::Json(serde_json::json!({ "labels": body.labels })).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) if e.code == ErrorCode::Storage => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
 }
 
 async fn get_stream_messages(
@@ -115,18 +284,87 @@ async fn get_stream_messages(
     let messages = engine.stream.read(&topic, from_seq, limit).await
         .into_iter()
         .rev()
-        .map(|msg| MessagePreview {
-            seq: msg.seq,
-            timestamp: chrono::DateTime::from_timestamp_millis(msg.timestamp as i64)
-                .unwrap_or_default()
-                .to_rfc3339(),
-            payload: payload_to_json_value(&msg.payload),
+        .map(|msg| {
+            let (payload, content_type) = render_payload(&msg.payload);
+            MessagePreview {
+                seq: msg.seq,
+                timestamp: chrono::DateTime::from_timestamp_millis(msg.timestamp as i64)
+                    .unwrap_or_default()
+                    .to_rfc3339(),
+                payload,
+                content_type,
+            }
         })
         .collect();
 
     axum::Json(StreamMessages { messages, from_seq, limit, last_seq }).into_response()
 }
 
+async fn get_key_stats(
+    State(engine): State<NexoEngine>,
+    Path(topic): Path<String>,
+    Query(spec): Query<KeyStatsSpec>,
+) -> impl IntoResponse {
+    match engine.stream.key_stats(&topic, &spec).await {
+        Ok(stats) => axum::Json(stats).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn export_topic(
+    State(engine): State<NexoEngine>,
+    Path(topic): Path<String>,
+    axum::Json(body): axum::Json<TopicExportRequest>,
+) -> impl IntoResponse {
+    let path = match export_path::resolve_for_write(&engine.export_dir, &body.path).await {
+        Ok(path) => path,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match engine.stream.export_topic(&topic, &path, body.format).await {
+        Ok(summary) => axum::Json(summary).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.message).into_response(),
+    }
+}
+
+async fn import_topic(
+    State(engine): State<NexoEngine>,
+    Path(topic): Path<String>,
+    axum::Json(body): axum::Json<TopicImportRequest>,
+) -> impl IntoResponse {
+    let path = match export_path::resolve(&engine.export_dir, &body.path) {
+        Ok(path) => path,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match engine.stream.import_topic(&path, topic, body.options).await {
+        Ok(summary) => axum::Json(summary).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
+async fn export_checkpoint(
+    State(engine): State<NexoEngine>,
+    Path((topic, group)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match engine.stream.export_checkpoint(&topic, &group).await {
+        Ok(checkpoint) => axum::Json(checkpoint).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
+    }
+}
+
+async fn import_checkpoint(
+    State(engine): State<NexoEngine>,
+    Path((topic, group)): Path<(String, String)>,
+    axum::Json(body): axum::Json<CheckpointImportRequest>,
+) -> impl IntoResponse {
+    match engine.stream.import_checkpoint(&topic, &group, &body.checkpoint, body.by).await {
+        Ok(ack_floor) => axum::Json(CheckpointImportResult { ack_floor }).into_response(),
+        Err(e) if e.code == ErrorCode::NotFound => (StatusCode::NOT_FOUND, e.message).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.message).into_response(),
+    }
+}
+
 // ==========================================
 // ROUTES
 // ==========================================
@@ -135,4 +373,9 @@ pub fn routes() -> Router<NexoEngine> {
     Router::new()
         .route("/api/stream", get(get_stream))
         .route("/api/stream/{topic}/messages", get(get_stream_messages))
+        .route("/api/stream/{topic}/labels", put(set_topic_labels))
+        .route("/api/stream/{topic}/keys", get(get_key_stats))
+        .route("/api/stream/{topic}/export", post(export_topic))
+        .route("/api/stream/{topic}/import", post(import_topic))
+        .route("/api/stream/{topic}/groups/{group}/checkpoint", get(export_checkpoint).post(import_checkpoint))
 }
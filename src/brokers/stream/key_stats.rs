@@ -0,0 +1,269 @@
+//! Key distribution of a topic: samples its newest records, reads a key from
+//! each JSON payload (a JSON pointer, as for backfill key filters) and reports
+//! the heaviest keys and an estimate of how many distinct keys there are.
+//!
+//! Memory stays bounded whatever the sample: heavy hitters are tracked with
+//! Space-Saving (`top * 10` counters; a count may overestimate a key by up
+//! to its `error`) and distinct keys with a HyperLogLog (4096 registers,
+//! ~1.6% standard error).
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::brokers::payload::render_payload;
+
+pub const DEFAULT_SAMPLE: usize = 10_000;
+pub const MAX_SAMPLE: usize = 1_000_000;
+pub const DEFAULT_TOP: usize = 10;
+pub const MAX_TOP: usize = 100;
+
+/// Counters kept per reported key
+const COUNTERS_PER_TOP: usize = 10;
+const HLL_BITS: u32 = 12;
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct KeyStatsSpec {
+    /// Where the key is in the payload, e.g. `/customer/id`
+    pub pointer: String,
+    /// Newest records scanned (default `DEFAULT_SAMPLE`)
+    pub sample: Option<usize>,
+    /// Keys reported (default `DEFAULT_TOP`)
+    pub top: Option<usize>,
+}
+
+impl KeyStatsSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.pointer.starts_with('/') {
+            return Err("Key pointer must be a JSON pointer (e.g. /customer/id)".to_string());
+        }
+        if self.sample.is_some_and(|sample| sample == 0 || sample > MAX_SAMPLE) {
+            return Err(format!("sample must be between 1 and {}", MAX_SAMPLE));
+        }
+        if self.top.is_some_and(|top| top == 0 || top > MAX_TOP) {
+            return Err(format!("top must be between 1 and {}", MAX_TOP));
+        }
+        Ok(())
+    }
+
+    pub fn sample(&self) -> usize {
+        self.sample.unwrap_or(DEFAULT_SAMPLE)
+    }
+
+    fn top(&self) -> usize {
+        self.top.unwrap_or(DEFAULT_TOP)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyStats {
+    pub topic: String,
+    /// Sequences scanned (0 and 0 for an empty topic)
+    pub from_seq: u64,
+    pub to_seq: u64,
+    pub sampled: u64,
+    /// Records without a key at the pointer
+    pub missing: u64,
+    /// Estimated distinct keys
+    pub distinct: u64,
+    /// Heaviest keys first
+    pub top: Vec<KeyCount>,
+    /// Share of the heaviest key over the share each key would get if evenly
+    /// spread: 1.0 = uniform, 10.0 = one key takes ten times its fair share
+    pub skew: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeyCount {
+    pub key: String,
+    pub count: u64,
+    /// Most the count may overestimate by
+    pub error: u64,
+    /// Of the records with a key
+    pub share: f64,
+}
+
+/// Feed payloads in with `add`, then `finish`.
+pub struct KeySampler {
+    pointer: String,
+    top: usize,
+    sampled: u64,
+    missing: u64,
+    heavy: SpaceSaving,
+    distinct: HyperLogLog,
+}
+
+impl KeySampler {
+    pub fn new(spec: &KeyStatsSpec) -> Self {
+        Self {
+            pointer: spec.pointer.clone(),
+            top: spec.top(),
+            sampled: 0,
+            missing: 0,
+            heavy: SpaceSaving::new(spec.top() * COUNTERS_PER_TOP),
+            distinct: HyperLogLog::new(),
+        }
+    }
+
+    pub fn add(&mut self, payload: &[u8]) {
+        self.sampled += 1;
+        let key = match render_payload(payload).0.pointer(&self.pointer) {
+            None | Some(Value::Null) => {
+                self.missing += 1;
+                return;
+            }
+            Some(Value::String(key)) => key.clone(),
+            Some(other) => other.to_string(),
+        };
+        self.distinct.add(&key);
+        self.heavy.add(key);
+    }
+
+    pub fn finish(self, topic: String, from_seq: u64, to_seq: u64) -> KeyStats {
+        let keyed = self.sampled - self.missing;
+        // The sketch can't undercount what the exact counters saw
+        let distinct = match keyed {
+            0 => 0,
+            _ => self.distinct.estimate().clamp(self.heavy.len() as u64, keyed),
+        };
+        let share = |count: u64| if keyed == 0 { 0.0 } else { count as f64 / keyed as f64 };
+        let top: Vec<KeyCount> = self.heavy.top(self.top).into_iter()
+            .map(|(key, count, error)| KeyCount { key, count, error, share: share(count) })
+            .collect();
+        let skew = top.first().map(|hottest| hottest.share * distinct as f64).unwrap_or(0.0);
+        KeyStats { topic, from_seq, to_seq, sampled: self.sampled, missing: self.missing, distinct, top, skew }
+    }
+}
+
+/// Space-Saving heavy hitters: a full table replaces its smallest counter,
+/// and the newcomer inherits that count as its error.
+struct SpaceSaving {
+    capacity: usize,
+    /// Key -> (count, error)
+    counters: HashMap<String, (u64, u64)>,
+    /// (count, key), smallest first
+    by_count: BTreeSet<(u64, String)>,
+}
+
+impl SpaceSaving {
+    fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), counters: HashMap::new(), by_count: BTreeSet::new() }
+    }
+
+    fn len(&self) -> usize {
+        self.counters.len()
+    }
+
+    fn add(&mut self, key: String) {
+        if let Some((count, _)) = self.counters.get_mut(&key) {
+            self.by_count.remove(&(*count, key.clone()));
+            *count += 1;
+            self.by_count.insert((*count, key));
+            return;
+        }
+        let (count, error) = match self.counters.len() < self.capacity {
+            true => (1, 0),
+            false => {
+                let Some((min, evicted)) = self.by_count.pop_first() else { return };
+                self.counters.remove(&evicted);
+                (min + 1, min)
+            }
+        };
+        self.by_count.insert((count, key.clone()));
+        self.counters.insert(key, (count, error));
+    }
+
+    /// The `n` heaviest as (key, count, error), ties by key.
+    fn top(&self, n: usize) -> Vec<(String, u64, u64)> {
+        let mut top: Vec<(String, u64, u64)> = self.counters.iter()
+            .map(|(key, (count, error))| (key.clone(), *count, *error))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(n);
+        top
+    }
+}
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self { registers: vec![0; 1 << HLL_BITS] }
+    }
+
+    fn add(&mut self, key: &str) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - HLL_BITS)) as usize;
+        let rank = ((hash << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Small ranges: linear counting is more accurate
+        let estimate = match raw <= 2.5 * m && zeros > 0 {
+            true => m * (m / zeros as f64).ln(),
+            false => raw,
+        };
+        estimate.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(top: usize) -> KeyStatsSpec {
+        KeyStatsSpec { pointer: "/customer".to_string(), sample: None, top: Some(top) }
+    }
+
+    #[test]
+    fn reports_hot_keys_and_skew() {
+        let mut sampler = KeySampler::new(&spec(2));
+        for i in 0..1_000 {
+            // Half of the records go to one customer, the rest spread over 500
+            let customer = if i % 2 == 0 { "hot".to_string() } else { format!("c{}", i % 1_000) };
+            sampler.add(format!(r#"{{"customer":"{}"}}"#, customer).as_bytes());
+        }
+        sampler.add(br#"{"other":1}"#);
+        let stats = sampler.finish("orders".to_string(), 1, 1_001);
+        assert_eq!((stats.sampled, stats.missing), (1_001, 1));
+        assert_eq!(stats.top.len(), 2);
+        assert_eq!((stats.top[0].key.as_str(), stats.top[0].count, stats.top[0].error), ("hot", 500, 0));
+        assert_eq!(stats.top[0].share, 0.5);
+        assert!((480..=520).contains(&stats.distinct), "{}", stats.distinct);
+        assert!(stats.skew > 200.0, "{}", stats.skew);
+    }
+
+    #[test]
+    fn uniform_keys_are_not_skewed() {
+        let mut sampler = KeySampler::new(&spec(3));
+        for i in 0..300 {
+            sampler.add(format!(r#"{{"customer":{}}}"#, i % 3).as_bytes());
+        }
+        let stats = sampler.finish("orders".to_string(), 1, 300);
+        assert_eq!(stats.distinct, 3);
+        assert_eq!(stats.skew, 1.0);
+        let keys: Vec<&str> = stats.top.iter().map(|k| k.key.as_str()).collect();
+        assert_eq!(keys, vec!["0", "1", "2"]);
+    }
+
+    #[test]
+    fn spec_bounds() {
+        assert!(spec(10).validate().is_ok());
+        assert!(spec(MAX_TOP + 1).validate().is_err());
+        assert!(KeyStatsSpec { pointer: "customer".to_string(), sample: None, top: None }.validate().is_err());
+        assert!(KeyStatsSpec { pointer: "/c".to_string(), sample: Some(MAX_SAMPLE + 1), top: None }.validate().is_err());
+    }
+}
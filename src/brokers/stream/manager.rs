@@ -1,28 +1,68 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::time::Duration;
 
 use bytes::Bytes;
 use dashmap::DashMap;
+use futures_util::StreamExt;
 use tokio::sync::{mpsc, oneshot, Notify};
 use tokio::time::{sleep_until, Instant};
 use tokio_util::sync::CancellationToken;
-use tracing::info;
-
-use crate::brokers::stream::options::{SeekTarget, StreamCreateOptions};
-use crate::brokers::stream::config::SystemStreamConfig;
-use crate::brokers::stream::domain::group::ConsumerGroup;
-use crate::brokers::stream::domain::message::Message;
-use crate::brokers::stream::snapshot::{ConsumerGroupSnapshot, StreamSnapshot, TopicSnapshot};
-use crate::brokers::stream::domain::persistence::{recover_topic, MessageToAppend, StorageCommand, StorageManager};
+use tracing::{error, info, warn};
+
+use crate::brokers::activity::ActivityTracker;
+use crate::brokers::clock::{self, SharedClock};
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::events::{EventBus, EventHandle};
+use crate::brokers::hooks::{LifecycleEvent, RebalanceReason};
+use crate::brokers::memory::{BudgetHandle, MemoryBudget};
+use crate::brokers::labels;
+use crate::brokers::names::{self, NameKind};
+use crate::brokers::stream::options::{AlterOptions, SeekTarget, StreamCreateOptions};
+use crate::brokers::stream::config::{DuplicateSession, SystemStreamConfig};
+use crate::brokers::stream::domain::epochs::GroupEpochs;
+use crate::brokers::stream::domain::group::{ConsumerGroup, DeadLetter};
+use crate::brokers::stream::mirror::{self, MirrorStatus};
+use crate::brokers::stream::backfill::{self, BackfillSpec, BackfillStatus};
+use crate::brokers::stream::key_stats::{KeySampler, KeyStats, KeyStatsSpec};
+use crate::brokers::stream::checkpoint::{CheckpointMatch, GroupCheckpoint, CHECKPOINT_VERSION};
+use crate::brokers::stream::export::{DumpFormat, DumpReader, DumpRecord, DumpWriter, TopicExportSummary, EXPORT_BATCH, TopicImportSummary};
+use crate::brokers::schema::{SchemaRegistry, SchemaVersion};
+use crate::brokers::message_trace::{MessageTrace, MessageTracer, TraceEventKind};
+use crate::brokers::stream::domain::message::{Message, LANE_HIGH, LANE_NORMAL};
+use crate::brokers::stream::snapshot::{BackfillSnapshot, ConsumerGroupSnapshot, ReadCacheSnapshot, StreamSnapshot, TopicSnapshot};
+use crate::brokers::stream::domain::persistence::{record_size, recover_topic, MessageToAppend, StorageCommand, StorageManager};
+use crate::brokers::stream::domain::segment_cache::{ReadCacheStats, SegmentCache};
+use crate::brokers::stream::domain::tiering::Tiering;
 use crate::brokers::stream::domain::topic::{TopicConfig, TopicState};
+use crate::brokers::warm_start::WarmStart;
+use crate::brokers::dead_letter::DeadLetterEnvelope;
+use crate::brokers::error::{ErrorCode, NexoError};
+use crate::transport::tcp::protocol::DATA_TYPE_JSON;
 
 struct TopicShared {
     inner: Mutex<TopicInner>,
     notify: Notify,
     persisted_seq: Arc<AtomicU64>,
+    /// Restored from disk at startup rather than created by this process
+    recovered: bool,
+    reads: ReadTiers,
+}
+
+/// Messages served from the RAM window (hot) and from disk (cold) since start.
+#[derive(Default)]
+struct ReadTiers {
+    hot: AtomicU64,
+    cold: AtomicU64,
+}
+
+impl ReadTiers {
+    fn record(&self, cold: bool, messages: usize) {
+        let tier = if cold { &self.cold } else { &self.hot };
+        tier.fetch_add(messages as u64, Ordering::Relaxed);
+    }
 }
 
 #[derive(Clone)]
@@ -37,18 +77,52 @@ struct TopicInner {
     client_map: HashMap<String, Vec<ConsumerBinding>>,
     groups_dirty: bool,
     full_config: TopicConfig,
+    /// Publishes held for the topic's `linger_ms`
+    lingering: Option<LingerBatch>,
+}
+
+/// Publishes sent to storage as one `Append` once the linger ends.
+struct LingerBatch {
+    messages: Vec<MessageToAppend>,
+    durable: Vec<oneshot::Sender<bool>>,
+    /// Span of the publish that opened the batch
+    span: tracing::Span,
 }
 
+/// A lingering batch this large is sent without waiting for the linger to end
+const LINGER_MAX_BATCH: usize = 1024;
+
 enum FetchAttempt {
     Ready(Vec<Message>),
     NeedColdRead { from_seq: u64 },
     Wait,
 }
 
+/// The consumer a cold read is for, and where it starts.
+struct ColdFetch<'a> {
+    topic: &'a str,
+    group: &'a str,
+    consumer_id: &'a str,
+    generation: u64,
+    limit: usize,
+    from_seq: u64,
+}
+
 pub struct JoinGroupResult {
     pub ack_floor: u64,
     pub consumer_id: String,
     pub generation: u64,
+    pub session_token: String,
+    /// Live connection the session was taken over from, for the transport to disconnect
+    pub displaced: Option<String>,
+}
+
+/// One (topic, membership) pair of a multi-topic subscription.
+#[derive(Debug)]
+pub struct TopicMember {
+    pub topic: String,
+    pub consumer_id: String,
+    pub generation: u64,
 }
 
 #[derive(Clone)]
@@ -58,33 +132,85 @@ pub struct StreamManager {
     storage_tx: mpsc::UnboundedSender<StorageCommand>,
     config: Arc<SystemStreamConfig>,
     cancel: CancellationToken,
+    mirrors: Arc<Mutex<Vec<Arc<MirrorStatus>>>>,
+    backfills: Arc<Mutex<Vec<Arc<BackfillStatus>>>>,
+    schemas: Arc<SchemaRegistry>,
+    /// Highest generation per group, persisted apart from `groups.log`
+    epochs: Arc<GroupEpochs>,
+    tracer: Arc<MessageTracer>,
+    read_cache: Arc<ReadCacheStats>,
+    activity: Arc<ActivityTracker>,
+    /// Woken when topics are created or dropped (pattern subscriptions)
+    topology: Arc<Notify>,
+    /// Engine memory budget (see `brokers::memory`)
+    budget: BudgetHandle,
+    events: EventHandle,
+    clock: SharedClock,
 }
 
 impl StreamManager {
     pub async fn new(config: Arc<SystemStreamConfig>) -> Self {
+        Self::with_clock(config, clock::system()).await
+    }
+
+    /// Manager whose timestamps come from `clock` (a `MockClock` in tests).
+    pub async fn with_clock(config: Arc<SystemStreamConfig>, clock: SharedClock) -> Self {
+        Self::with_warm_start(config, clock, &WarmStart::default()).await
+    }
+
+    /// Manager on `clock` restoring the topics on disk `warm_start.concurrency`
+    /// at a time, and counting them in `warm_start`.
+    pub async fn with_warm_start(config: Arc<SystemStreamConfig>, clock: SharedClock, warm_start: &WarmStart) -> Self {
         let topics = Arc::new(DashMap::new());
         let deleted_topics = Arc::new(DashMap::new());
         let (storage_tx, storage_rx) = mpsc::unbounded_channel();
+        let read_cache = Arc::new(ReadCacheStats::default());
+        let events = EventHandle::default();
 
-        let storage_manager = StorageManager::new(
+        let mut storage_manager = StorageManager::new(
             config.persistence_path.clone(),
             storage_rx,
             config.max_open_files,
             config.default_flush_ms,
             config.max_segment_size,
-        );
+            SegmentCache::new(config.read_cache_blocks, read_cache.clone()),
+            config.write_backend,
+        )
+        .with_group_commit(config.group_commit_ms)
+        .with_drop_flush_timeout(config.drop_flush_timeout_ms)
+        .with_events(events.clone());
+        if config.tiering.enabled() {
+            match Tiering::new(&config.tiering, &storage_tx) {
+                Ok(tiering) => storage_manager = storage_manager.with_tiering(tiering),
+                Err(e) => error!("Stream tiering disabled: {}", e),
+            }
+        }
         tokio::spawn(storage_manager.run());
 
+        let tracer = Arc::new(MessageTracer::new("stream", config.trace_capacity, clock.clone()));
+        let activity_path = PathBuf::from(&config.persistence_path).join("activity.json");
         let manager = Self {
             topics,
             deleted_topics,
             storage_tx,
             config,
             cancel: CancellationToken::new(),
+            mirrors: Arc::new(Mutex::new(Vec::new())),
+            backfills: Arc::new(Mutex::new(Vec::new())),
+            schemas: Arc::new(SchemaRegistry::new()),
+            epochs: Arc::new(GroupEpochs::default()),
+            tracer,
+            read_cache,
+            activity: Arc::new(ActivityTracker::load(activity_path, clock.clone())),
+            topology: Arc::new(Notify::new()),
+            budget: BudgetHandle::default(),
+            events,
+            clock,
         };
 
-        manager.bootstrap_from_disk().await;
+        manager.bootstrap_from_disk(warm_start).await;
         manager.spawn_background_tasks();
+        manager.spawn_mirrors();
         manager
     }
 
@@ -92,16 +218,80 @@ impl StreamManager {
         self.cancel.cancel();
     }
 
-    pub async fn create_topic(&self, name: String, options: StreamCreateOptions) -> Result<(), String> {
+    /// Save the group ack floors, then write and fsync every record published
+    /// so far, whatever each topic's persistence mode.
+    pub async fn flush(&self) -> Result<(), NexoError> {
+        for (name, topic_ref) in Self::collect_topics(&self.topics) {
+            Self::send_lingering(&self.storage_tx, &name, &topic_ref, &mut Self::lock_topic(&topic_ref.inner));
+        }
+        Self::save_dirty_groups(&self.topics, &self.storage_tx);
+        let (reply, done) = oneshot::channel();
+        self.storage_tx.send(StorageCommand::Flush { reply })
+            .map_err(|_| NexoError::new(ErrorCode::Storage, "Stream storage stopped"))?;
+        match done.await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(NexoError::new(ErrorCode::Storage, "Failed to flush stream segments")),
+            Err(_) => Err(NexoError::new(ErrorCode::Storage, "Stream storage stopped")),
+        }
+    }
+
+    /// Shed publishes by the engine memory budget from now on.
+    pub fn attach_budget(&self, budget: Arc<MemoryBudget>) {
+        self.budget.attach(budget);
+    }
+
+    /// Post lifecycle events (rebalances, retention, storage errors) to `bus` from now on.
+    pub fn attach_events(&self, bus: Arc<EventBus>) {
+        self.events.attach(bus);
+    }
+
+    /// Payload bytes held in the RAM windows of all topics.
+    pub fn memory_usage(&self) -> u64 {
+        self.topics.iter().map(|entry| Self::lock_topic(&entry.value().inner).state.ram_bytes).sum()
+    }
+
+    /// Flush, then drop every persisted message from the RAM windows (memory
+    /// pressure). Returns the bytes freed.
+    pub async fn spill(&self) -> Result<u64, NexoError> {
+        self.flush().await?;
+        let mut freed = 0;
+        for (_, topic_ref) in Self::collect_topics(&self.topics) {
+            let persisted_seq = topic_ref.persisted_seq.load(Ordering::Acquire);
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            let before = inner.state.ram_bytes;
+            inner.state.spill(persisted_seq);
+            freed += before - inner.state.ram_bytes;
+        }
+        Ok(freed)
+    }
+
+    /// Flush, then stop the background tasks (mirrors, backfills, retention).
+    pub async fn close(&self) -> Result<(), NexoError> {
+        let flushed = self.flush().await;
+        self.shutdown();
+        flushed
+    }
+
+    #[tracing::instrument(name = "stream.create", skip_all, fields(topic = %name))]
+    pub async fn create_topic(&self, name: String, options: StreamCreateOptions) -> Result<(), NexoError> {
         self.deleted_topics.remove(&name);
 
-        if self.topics.contains_key(&name) {
-            return Ok(());
+        if let Some(topic_ref) = self.get_topic(&name) {
+            let config = Self::lock_topic(&topic_ref.inner).full_config.clone();
+            return Self::check_manifest(&name, &config, &options, &self.config);
+        }
+        names::validate_new(NameKind::StreamTopic, &name, self.topic_names())?;
+        if let Some(labels) = &options.labels {
+            labels::validate(labels)?;
         }
 
         let base_path = PathBuf::from(&self.config.persistence_path).join(&name);
         let existed_on_disk = tokio::fs::metadata(&base_path).await.map(|meta| meta.is_dir()).unwrap_or(false);
-        let topic_config = Self::load_topic_config(&base_path, options, &self.config).await;
+        let has_manifest = base_path.join("config.json").is_file();
+        let topic_config = Self::load_topic_config(&base_path, options.clone(), &self.config).await;
+        if has_manifest {
+            Self::check_manifest(&name, &topic_config, &options, &self.config)?;
+        }
 
         info!("[StreamManager] Creating topic '{}'", name);
 
@@ -109,70 +299,368 @@ impl StreamManager {
             if let Err(e) = tokio::fs::create_dir_all(&base_path).await {
                 tracing::error!("Failed to create topic directory at {:?}: {}", base_path, e);
             } else {
-                let config_path = base_path.join("config.json");
-                if let Ok(data) = serde_json::to_string_pretty(&topic_config) {
-                    let _ = tokio::fs::write(&config_path, data).await;
-                }
-            }
-        } else {
-            let config_path = base_path.join("config.json");
-            if !config_path.exists() {
-                if let Ok(data) = serde_json::to_string_pretty(&topic_config) {
-                    let _ = tokio::fs::write(&config_path, data).await;
-                }
+                let _ = Self::write_manifest(&base_path, &topic_config).await;
             }
+        } else if !has_manifest {
+            let _ = Self::write_manifest(&base_path, &topic_config).await;
         }
 
-        let shared = Self::build_topic_shared(name.clone(), topic_config).await;
+        self.schemas.load(&name, &base_path.join("schemas.json"));
+        self.epochs.load(&name, &base_path);
+        if topic_config.flush_ms.is_some() {
+            self.set_flush_interval(&name, &topic_config);
+        }
+        let (shared, _) = Self::build_topic_shared(name.clone(), topic_config, false).await;
+        self.activity.track(&name);
+
+        // Scanned before taking the entry: iterating the map under it would deadlock
+        Self::bind_pattern_groups(&shared, self.pattern_groups_for(&name));
 
         use dashmap::mapref::entry::Entry;
         match self.topics.entry(name) {
             Entry::Occupied(_) => Ok(()),
             Entry::Vacant(v) => {
                 v.insert(shared);
+                self.topology.notify_waiters();
                 Ok(())
             }
         }
     }
 
-    pub async fn delete_topic(&self, name: String) -> Result<(), String> {
+    /// Change the settings of an existing topic and rewrite its manifest.
+    /// Only the settings `options` states are touched; publishes, the
+    /// retention pass and the storage actor pick them up right away.
+    #[tracing::instrument(name = "stream.alter", skip_all, fields(topic = %name))]
+    pub async fn alter_topic(&self, name: &str, options: AlterOptions) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(name).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        if let Some(labels) = &options.labels {
+            labels::validate(labels)?;
+        }
+        let config = {
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            inner.full_config.apply(&options, &self.config);
+            // The next eviction pass trims the window to the new caps
+            inner.state.ram_soft_limit = inner.full_config.ram_soft_limit;
+            inner.state.ram_max_bytes = inner.full_config.ram_max_bytes;
+            inner.full_config.clone()
+        };
+        if options.flush_ms.is_some() {
+            self.set_flush_interval(name, &config);
+        }
+
+        let base_path = PathBuf::from(&self.config.persistence_path).join(name);
+        Self::write_manifest(&base_path, &config).await
+            .map_err(|e| NexoError::new(ErrorCode::Storage, format!("Failed to write the manifest of '{}': {}", name, e)))?;
+        info!("[StreamManager] Altered topic '{}'", name);
+        Ok(())
+    }
+
+    /// A create naming settings that contradict the existing topic would be
+    /// silently ignored: refuse it, pointing at alter.
+    fn check_manifest(name: &str, config: &TopicConfig, options: &StreamCreateOptions, sys: &SystemStreamConfig) -> Result<(), NexoError> {
+        let conflicts = config.conflicts(options, sys);
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+        Err(NexoError::new(
+            ErrorCode::Conflict,
+            format!("Topic '{}' exists with different settings ({}): alter it to change them", name, conflicts.join(", ")),
+        ))
+    }
+
+    fn set_flush_interval(&self, name: &str, config: &TopicConfig) {
+        let _ = self.storage_tx.send(StorageCommand::SetFlushInterval {
+            topic_name: name.to_string(),
+            interval: config.flush_ms.map(Duration::from_millis),
+        });
+    }
+
+    async fn write_manifest(base_path: &Path, config: &TopicConfig) -> std::io::Result<()> {
+        let data = serde_json::to_string_pretty(config).map_err(std::io::Error::other)?;
+        tokio::fs::write(base_path.join("config.json"), data).await
+    }
+
+    /// Groups (with their patterns) subscribed to a pattern matching `topic`.
+    fn pattern_groups_for(&self, topic: &str) -> HashMap<String, Vec<String>> {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        let topic_refs: Vec<Arc<TopicShared>> = self.topics.iter().map(|t| t.value().clone()).collect();
+        for topic_ref in topic_refs {
+            let inner = Self::lock_topic(&topic_ref.inner);
+            for (group_id, group) in &inner.groups {
+                let matching = group.patterns.iter().filter(|p| Self::matches_pattern(p, topic));
+                groups.entry(group_id.clone()).or_default().extend(matching.cloned());
+            }
+        }
+        groups.retain(|_, patterns| !patterns.is_empty());
+        groups
+    }
+
+    /// Create the pattern groups on a new topic, from its first record like any new group.
+    fn bind_pattern_groups(shared: &TopicShared, groups: HashMap<String, Vec<String>>) {
+        if groups.is_empty() {
+            return;
+        }
+        let mut inner = Self::lock_topic(&shared.inner);
+        let head_seq = inner.state.head_seq;
+        let config = &inner.full_config;
+        let (max_ack_pending, ack_wait, max_deliveries, priority_lanes) =
+            (config.max_ack_pending, Duration::from_millis(config.ack_wait_ms), config.max_deliveries, config.priority_lanes);
+        for (group_id, patterns) in groups {
+            info!("[StreamManager] Pattern subscription binds group '{}' to new topic", group_id);
+            let group = inner.groups.entry(group_id.clone())
+                .or_insert_with(|| ConsumerGroup::new(group_id, head_seq, max_ack_pending, ack_wait, max_deliveries, priority_lanes));
+            group.patterns.extend(patterns);
+        }
+        inner.groups_dirty = true;
+    }
+
+    /// `prefix*` matches names starting with `prefix`, except DLQ topics.
+    fn matches_pattern(pattern: &str, topic: &str) -> bool {
+        pattern.strip_suffix('*').is_some_and(|prefix| topic.starts_with(prefix) && !topic.ends_with(".dlq"))
+    }
+
+    #[tracing::instrument(name = "stream.delete", skip_all, fields(topic = %name))]
+    pub async fn delete_topic(&self, name: String) -> Result<(), NexoError> {
+        self.drop_topic(name, None).await
+    }
+
+    /// Remove the topic, moving its directory (segments, groups, schemas) to `target`.
+    #[tracing::instrument(name = "stream.archive", skip_all, fields(topic = %name))]
+    pub async fn archive_topic(&self, name: String, target: PathBuf) -> Result<(), NexoError> {
+        self.drop_topic(name, Some(target)).await
+    }
+
+    async fn drop_topic(&self, name: String, archive_to: Option<PathBuf>) -> Result<(), NexoError> {
         self.deleted_topics.insert(name.clone(), ());
+        self.schemas.remove(&name);
+        self.epochs.remove(&name);
+        self.tracer.forget(&name);
+        self.activity.forget(&name);
 
         let topic_path = PathBuf::from(&self.config.persistence_path).join(&name);
-        if self.topics.remove(&name).is_some() || tokio::fs::metadata(&topic_path).await.map(|meta| meta.is_dir()).unwrap_or(false) {
+        let removed = match self.topics.remove(&name) {
+            Some((_, topic_ref)) => {
+                // Its publishes would recreate the directory
+                Self::lock_topic(&topic_ref.inner).lingering = None;
+                self.topology.notify_waiters();
+                true
+            }
+            None => false,
+        };
+        if removed || tokio::fs::metadata(&topic_path).await.map(|meta| meta.is_dir()).unwrap_or(false) {
             let (del_tx, del_rx) = oneshot::channel();
             let _ = self.storage_tx.send(StorageCommand::DropTopic {
                 topic_name: name,
+                archive_to,
                 reply: del_tx,
             });
-            let _ = del_rx.await;
+            if let Ok(Err(e)) = del_rx.await {
+                return Err(NexoError::new(ErrorCode::Storage, e));
+            }
         }
         Ok(())
     }
 
-    pub async fn publish(&self, topic: &str, payload: Bytes) -> Result<u64, String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
-        let persisted_seq = topic_ref.persisted_seq.clone();
+    /// Last publish/consume per topic.
+    pub fn activity(&self) -> &ActivityTracker {
+        &self.activity
+    }
+
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
 
-        let (seq, timestamp) = {
+    pub fn topic_names(&self) -> Vec<String> {
+        self.topics.iter().map(|t| t.key().clone()).collect()
+    }
+
+    /// Whether the storage task that writes every topic's segments is running.
+    pub fn storage_running(&self) -> bool {
+        !self.storage_tx.is_closed()
+    }
+
+    pub async fn publish(&self, topic: &str, payload: Bytes) -> Result<u64, NexoError> {
+        self.publish_to_lane(topic, payload, LANE_NORMAL).await
+    }
+
+    /// Publish on a delivery lane. `LANE_HIGH` requires a topic created with priority lanes.
+    pub async fn publish_to_lane(&self, topic: &str, payload: Bytes, lane: u8) -> Result<u64, NexoError> {
+        self.schemas.validate(topic, &payload)?;
+        self.admit(topic)?;
+        self.append(topic, payload, lane, None).await
+    }
+
+    /// Publish and resolve once the message is fsynced, whatever the topic's
+    /// persistence mode.
+    pub async fn publish_durable(&self, topic: &str, payload: Bytes) -> Result<u64, NexoError> {
+        self.schemas.validate(topic, &payload)?;
+        self.budget.admit(true)?;
+        self.write(topic, payload, LANE_NORMAL, None, true).await
+    }
+
+    /// Publish a message moved here from elsewhere (queue dead letters): the
+    /// memory budget does not shed it.
+    pub(crate) async fn forward(&self, topic: &str, payload: Bytes) -> Result<u64, NexoError> {
+        self.schemas.validate(topic, &payload)?;
+        self.append(topic, payload, LANE_NORMAL, None).await
+    }
+
+    /// Memory budget check: publishes to `fileSync` topics count as durable.
+    fn admit(&self, topic: &str) -> Result<(), NexoError> {
+        let durable = self.get_topic(topic)
+            .is_some_and(|t| Self::lock_topic(&t.inner).full_config.persistence == PersistenceMode::FileSync);
+        self.budget.admit(durable)
+    }
+
+    /// Check a publish would be accepted (topic exists, payload matches its schema).
+    pub fn validate_publish(&self, topic: &str, payload: &[u8]) -> Result<(), NexoError> {
+        if self.get_topic(topic).is_none() {
+            return Err(NexoError::not_found("Topic not found"));
+        }
+        self.schemas.validate(topic, payload)
+    }
+
+    /// Publish keeping the original message timestamp (used by mirrors and backfills).
+    pub async fn publish_with_timestamp(&self, topic: &str, payload: Bytes, timestamp: u64) -> Result<u64, NexoError> {
+        self.append(topic, payload, LANE_NORMAL, Some(timestamp)).await
+    }
+
+    /// Attach a new schema version to the topic; later publishes must match it.
+    pub fn set_schema(&self, topic: &str, schema: serde_json::Value) -> Result<u32, NexoError> {
+        if self.get_topic(topic).is_none() {
+            return Err(NexoError::not_found("Topic not found"));
+        }
+        let file = PathBuf::from(&self.config.persistence_path).join(topic).join("schemas.json");
+        self.schemas.register(topic, schema, &file)
+    }
+
+    pub fn get_schema(&self, topic: &str, version: Option<u32>) -> Option<SchemaVersion> {
+        self.schemas.get(topic, version)
+    }
+
+    /// Toggle message tracing (debug) for the topic.
+    pub fn set_tracing(&self, topic: &str, enabled: bool) -> Result<(), NexoError> {
+        if self.get_topic(topic).is_none() {
+            return Err(NexoError::not_found("Topic not found"));
+        }
+        self.tracer.set_enabled(topic, enabled);
+        Ok(())
+    }
+
+    /// Recorded lifecycle of a traced message, identified as `<topic>:<seq>`.
+    pub fn trace(&self, message_id: &str) -> Option<MessageTrace> {
+        self.tracer.get(message_id)
+    }
+
+    fn trace_id(topic: &str, seq: u64) -> String {
+        format!("{}:{}", topic, seq)
+    }
+
+    async fn append(&self, topic: &str, payload: Bytes, lane: u8, timestamp: Option<u64>) -> Result<u64, NexoError> {
+        self.write(topic, payload, lane, timestamp, false).await
+    }
+
+    /// Append to the topic; with `sync` the message is fsynced before
+    /// returning even on a `fileAsync` topic.
+    #[tracing::instrument(name = "stream.publish", skip_all, fields(topic = %topic, lane = lane, seq = tracing::field::Empty))]
+    async fn write(&self, topic: &str, payload: Bytes, lane: u8, timestamp: Option<u64>, sync: bool) -> Result<u64, NexoError> {
+        if lane != LANE_NORMAL && lane != LANE_HIGH {
+            return Err(NexoError::invalid(format!("Invalid lane: {}", lane)));
+        }
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+
+        // Sent to storage under the topic lock, so appends arrive in sequence order
+        let (seq, committed) = {
             let mut inner = Self::lock_topic(&topic_ref.inner);
-            inner.state.append(payload.clone())
+            if lane == LANE_HIGH && !inner.full_config.priority_lanes {
+                return Err(NexoError::invalid("Priority lanes not enabled for topic"));
+            }
+            let now = self.clock.now_ms();
+            let (seq, timestamp) = inner.state.append_at(payload.clone(), lane, timestamp.unwrap_or(now), now);
+            let (durable, committed) = match inner.full_config.persistence {
+                PersistenceMode::FileAsync if !sync => (None, None),
+                _ => {
+                    // Readers wait for the fsync: a failed publish is never delivered
+                    inner.state.hold(seq);
+                    let (done_tx, done_rx) = oneshot::channel();
+                    (Some(done_tx), Some(done_rx))
+                }
+            };
+            let message = MessageToAppend { seq, timestamp, lane, payload };
+            match inner.full_config.linger_ms {
+                0 => {
+                    // A batch left over from before an alter goes first
+                    Self::send_lingering(&self.storage_tx, topic, &topic_ref, &mut inner);
+                    let _ = self.storage_tx.send(StorageCommand::Append {
+                        topic_name: topic.to_string(),
+                        messages: vec![message],
+                        persisted_seq: topic_ref.persisted_seq.clone(),
+                        span: tracing::Span::current(),
+                        durable: durable.into_iter().collect(),
+                    });
+                }
+                linger_ms => {
+                    let batch = inner.lingering.get_or_insert_with(|| {
+                        self.linger(topic, &topic_ref, linger_ms);
+                        LingerBatch { messages: Vec::new(), durable: Vec::new(), span: tracing::Span::current() }
+                    });
+                    batch.messages.push(message);
+                    batch.durable.extend(durable);
+                    if batch.messages.len() >= LINGER_MAX_BATCH {
+                        Self::send_lingering(&self.storage_tx, topic, &topic_ref, &mut inner);
+                    }
+                }
+            }
+            (seq, committed)
         };
+        tracing::Span::current().record("seq", seq);
+        self.activity.published(topic);
+        if self.tracer.is_enabled(topic) {
+            self.tracer.record(topic, Self::trace_id(topic, seq), TraceEventKind::Published, (lane == LANE_HIGH).then(|| "high lane".to_string()));
+        }
 
-        let _ = self.storage_tx.send(StorageCommand::Append {
-            topic_name: topic.to_string(),
-            messages: vec![MessageToAppend {
-                seq,
-                timestamp,
-                payload,
-            }],
-            persisted_seq,
+        let Some(committed) = committed else {
+            topic_ref.notify.notify_waiters();
+            return Ok(seq);
+        };
+        // FileSync (or `sync`): the message is readable, and the publisher
+        // acked, once the group commit has fsynced it. Settled in a task so a
+        // publisher going away does not leave the topic held.
+        let settled = tokio::spawn(async move {
+            let synced = committed.await == Ok(true);
+            Self::lock_topic(&topic_ref.inner).state.settle(seq, synced);
+            topic_ref.notify.notify_waiters();
+            synced
         });
-
-        topic_ref.notify.notify_waiters();
+        if !settled.await.unwrap_or(false) {
+            return Err(NexoError::new(ErrorCode::Storage, format!("Failed to persist message {} on '{}'", seq, topic)));
+        }
         Ok(seq)
     }
 
+    /// Send the topic's lingering batch once `linger_ms` is over (unless a
+    /// flush or a full batch sent it before).
+    fn linger(&self, topic: &str, topic_ref: &Arc<TopicShared>, linger_ms: u64) {
+        let (storage_tx, topic, topic_ref) = (self.storage_tx.clone(), topic.to_string(), topic_ref.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(linger_ms)).await;
+            Self::send_lingering(&storage_tx, &topic, &topic_ref, &mut Self::lock_topic(&topic_ref.inner));
+        });
+    }
+
+    /// Append the lingering publishes as one batch; called with the topic locked.
+    fn send_lingering(storage_tx: &mpsc::UnboundedSender<StorageCommand>, topic: &str, topic_ref: &TopicShared, inner: &mut TopicInner) {
+        let Some(batch) = inner.lingering.take() else { return };
+        let _ = storage_tx.send(StorageCommand::Append {
+            topic_name: topic.to_string(),
+            messages: batch.messages,
+            persisted_seq: topic_ref.persisted_seq.clone(),
+            span: batch.span,
+            durable: batch.durable,
+        });
+    }
+
+    #[tracing::instrument(name = "stream.read", skip_all, fields(topic = %topic, from_seq = from_seq))]
     pub async fn read(&self, topic: &str, from_seq: u64, limit: usize) -> Vec<Message> {
         let Some(topic_ref) = self.get_topic(topic) else {
             return Vec::new();
@@ -189,6 +677,7 @@ impl StreamManager {
         };
 
         if !messages.is_empty() || !need_cold {
+            topic_ref.reads.record(false, messages.len());
             return messages;
         }
 
@@ -198,25 +687,52 @@ impl StreamManager {
             from_seq: effective_from_seq,
             limit,
             reply: tx,
+            span: tracing::Span::current(),
         });
-        rx.await.unwrap_or_default()
+        let messages = rx.await.unwrap_or_default();
+        topic_ref.reads.record(true, messages.len());
+        messages
     }
 
-    pub async fn fetch(&self, group: &str, consumer_id: &str, generation: u64, limit: usize, topic: &str, wait_ms: u64) -> Result<Vec<Message>, String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
+    pub async fn fetch(&self, group: &str, consumer_id: &str, generation: u64, limit: usize, topic: &str, wait_ms: u64) -> Result<Vec<Message>, NexoError> {
+        self.fetch_cancellable(group, consumer_id, generation, limit, topic, wait_ms, &CancellationToken::new()).await
+    }
+
+    /// `fetch` for a request `cancel` aborts (the client went away): a long
+    /// poll ends early and a pending cold read is abandoned, its records
+    /// never assigned to the consumer.
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(name = "stream.fetch", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id))]
+    pub async fn fetch_cancellable(&self, group: &str, consumer_id: &str, generation: u64, limit: usize, topic: &str, wait_ms: u64, cancel: &CancellationToken) -> Result<Vec<Message>, NexoError> {
+        let messages = self.fetch_messages(group, consumer_id, generation, limit, topic, wait_ms, cancel).await?;
+        if self.tracer.is_enabled(topic) {
+            for msg in &messages {
+                self.tracer.record(topic, Self::trace_id(topic, msg.seq), TraceEventKind::Dispatched, Some(format!("{} ({})", group, consumer_id)));
+            }
+        }
+        Ok(messages)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_messages(&self, group: &str, consumer_id: &str, generation: u64, limit: usize, topic: &str, wait_ms: u64, cancel: &CancellationToken) -> Result<Vec<Message>, NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        self.activity.consumed(topic);
 
         let group_cancel = {
             let inner = Self::lock_topic(&topic_ref.inner);
             match inner.groups.get(group) {
                 Some(g) => g.cancel_token(),
-                None => return Err("Group not found".to_string()),
+                None => return Err(NexoError::not_found("Group not found")),
             }
         };
 
         if wait_ms == 0 {
             return match self.try_fetch_once(&topic_ref, group, consumer_id, generation, limit)? {
                 FetchAttempt::Ready(messages) => Ok(messages),
-                FetchAttempt::NeedColdRead { from_seq } => self.cold_fetch(&topic_ref, topic, group, consumer_id, generation, limit, from_seq, &group_cancel).await,
+                FetchAttempt::NeedColdRead { from_seq } => {
+                    let read = ColdFetch { topic, group, consumer_id, generation, limit, from_seq };
+                    self.cold_fetch(&topic_ref, read, &group_cancel, cancel).await
+                }
                 FetchAttempt::Wait => Ok(Vec::new()),
             };
         }
@@ -226,13 +742,19 @@ impl StreamManager {
         loop {
             let notified = topic_ref.notify.notified();
 
+            // Seek/leave may wake us through `notify` before the cancel branch is polled
+            if group_cancel.is_cancelled() {
+                return Ok(Vec::new());
+            }
+
             match self.try_fetch_once(&topic_ref, group, consumer_id, generation, limit) {
                 Ok(FetchAttempt::Ready(messages)) => return Ok(messages),
                 Ok(FetchAttempt::NeedColdRead { from_seq }) => {
-                    return self.cold_fetch(&topic_ref, topic, group, consumer_id, generation, limit, from_seq, &group_cancel).await;
+                    let read = ColdFetch { topic, group, consumer_id, generation, limit, from_seq };
+                    return self.cold_fetch(&topic_ref, read, &group_cancel, cancel).await;
                 }
                 Ok(FetchAttempt::Wait) => {}
-                Err(e) if e == "NOT_MEMBER" && !self.is_active_member(&topic_ref, group, consumer_id) => {
+                Err(e) if e.code == ErrorCode::NotMember && !self.is_active_member(&topic_ref, group, consumer_id) => {
                     return Ok(Vec::new());
                 }
                 Err(e) => return Err(e),
@@ -246,6 +768,7 @@ impl StreamManager {
                 _ = notified => {}
                 _ = sleep_until(deadline) => return Ok(Vec::new()),
                 _ = group_cancel.cancelled() => return Ok(Vec::new()),
+                _ = cancel.cancelled() => return Ok(Vec::new()),
             }
         }
     }
@@ -255,16 +778,20 @@ impl StreamManager {
         inner.groups.get(group).map_or(false, |g| g.is_member(consumer_id))
     }
 
-    pub async fn ack(&self, group: &str, topic: &str, consumer_id: &str, generation: u64, seq: u64) -> Result<(), String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
+    #[tracing::instrument(name = "stream.ack", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id, seq = seq))]
+    pub async fn ack(&self, group: &str, topic: &str, consumer_id: &str, generation: u64, seq: u64) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
         let mut inner = Self::lock_topic(&topic_ref.inner);
         let head_seq = inner.state.head_seq;
         let Some(group_ref) = inner.groups.get_mut(group) else {
-            return Err("Group not found".to_string());
+            return Err(NexoError::not_found("Group not found"));
         };
 
         let was_clamped = group_ref.clamp_head(head_seq);
         group_ref.ack(consumer_id, generation, seq)?;
+        if self.tracer.is_enabled(topic) {
+            self.tracer.record(topic, Self::trace_id(topic, seq), TraceEventKind::Acked, Some(group.to_string()));
+        }
         if was_clamped {
             inner.groups_dirty = true;
         }
@@ -272,43 +799,246 @@ impl StreamManager {
         Ok(())
     }
 
-    pub async fn seek(&self, group: &str, topic: &str, target: SeekTarget) -> Result<(), String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
-        {
+    /// Negative ack: the record is redelivered, or dead-lettered once the
+    /// group has delivered it `max_deliveries` times.
+    #[tracing::instrument(name = "stream.nack", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id, seq = seq))]
+    pub async fn nack(&self, group: &str, topic: &str, consumer_id: &str, generation: u64, seq: u64, reason: &str) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let dead_letters = {
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            let head_seq = inner.state.head_seq;
+            let dead_letter = inner.full_config.dead_letter;
+            let Some(group_ref) = inner.groups.get_mut(group) else {
+                return Err(NexoError::not_found("Group not found"));
+            };
+
+            group_ref.clamp_head(head_seq);
+            group_ref.nack(consumer_id, generation, seq, reason)?;
+            let dead_letters = Self::drain_dead_letters(group_ref, dead_letter);
+            inner.groups_dirty = true;
+            dead_letters
+        };
+        if self.tracer.is_enabled(topic) {
+            self.tracer.record(topic, Self::trace_id(topic, seq), TraceEventKind::Nacked, Some(format!("{}: {}", group, reason)));
+        }
+        topic_ref.notify.notify_waiters();
+
+        self.dead_letter(topic, group, dead_letters).await;
+        Ok(())
+    }
+
+    /// DLQ topic of `group` on `topic`.
+    pub fn dlq_topic(topic: &str, group: &str) -> String {
+        format!("{}.{}.dlq", topic, group)
+    }
+
+    /// The group's new dead letters, to copy to its DLQ topic. With dead
+    /// letters disabled there is nothing to copy: the floor moves past them.
+    fn drain_dead_letters(group: &mut ConsumerGroup, dead_letter: bool) -> Vec<DeadLetter> {
+        let letters = group.take_dead_letters();
+        if dead_letter {
+            return letters;
+        }
+        for letter in letters {
+            group.dead_lettered(letter.seq);
+        }
+        Vec::new()
+    }
+
+    /// Copy parked records to the group's DLQ topic, wrapped with their
+    /// failure metadata (see `brokers::dead_letter`), then let the group's
+    /// floor move past them. Records already removed by retention are
+    /// skipped; a record that could not be copied stays below the floor and
+    /// is retried, with those after it, on the next reaper tick.
+    async fn dead_letter(&self, topic: &str, group: &str, letters: Vec<DeadLetter>) {
+        if letters.is_empty() {
+            return;
+        }
+        let dlq = Self::dlq_topic(topic, group);
+        let created = self.create_topic(dlq.clone(), StreamCreateOptions::default()).await;
+        if let Err(e) = &created {
+            tracing::error!("Failed to create DLQ topic '{}': {}", dlq, e.message);
+        }
+
+        let mut written = Vec::with_capacity(letters.len());
+        let mut failed = Vec::new();
+        for letter in letters {
+            // Keep the DLQ in order: after a failure, the rest go again too
+            if created.is_err() || !failed.is_empty() {
+                failed.push(letter);
+                continue;
+            }
+            let Some(record) = self.read(topic, letter.seq, 1).await.into_iter().find(|m| m.seq == letter.seq) else {
+                tracing::warn!("[StreamManager] Record {} of '{}' is gone, not dead-lettered", letter.seq, topic);
+                written.push(letter.seq);
+                continue;
+            };
+            let envelope = DeadLetterEnvelope {
+                source: topic.to_string(),
+                group: Some(group.to_string()),
+                id: record.seq.to_string(),
+                priority: 0,
+                attempts: letter.attempts,
+                reason: letter.reason.clone(),
+                created_at: record.timestamp,
+                failed_at: self.clock.now_ms(),
+                payload: record.payload,
+            };
+            if let Err(e) = self.append(&dlq, envelope.encode(), LANE_NORMAL, None).await {
+                tracing::error!("Failed to dead-letter record {} of '{}': {}", record.seq, topic, e.message);
+                failed.push(letter);
+                continue;
+            }
+            if self.tracer.is_enabled(topic) {
+                self.tracer.record(topic, Self::trace_id(topic, record.seq), TraceEventKind::DeadLettered, Some(dlq.clone()));
+            }
+            written.push(record.seq);
+        }
+
+        let Some(topic_ref) = self.get_topic(topic) else { return };
+        let mut inner = Self::lock_topic(&topic_ref.inner);
+        let Some(group_ref) = inner.groups.get_mut(group) else { return };
+        for seq in written {
+            group_ref.dead_lettered(seq);
+        }
+        for letter in failed {
+            group_ref.retry_dead_letter(letter);
+        }
+        inner.groups_dirty = true;
+    }
+
+    #[tracing::instrument(name = "stream.seek", skip_all, fields(topic = %topic, group = %group))]
+    pub async fn seek(&self, group: &str, topic: &str, target: SeekTarget) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let generation = {
             let mut inner = Self::lock_topic(&topic_ref.inner);
             let last_seq = inner.state.next_seq.saturating_sub(1);
             let head_seq = inner.state.head_seq;
             let max_ack_pending = inner.full_config.max_ack_pending;
             let ack_wait = Duration::from_millis(inner.full_config.ack_wait_ms);
             let max_deliveries = inner.full_config.max_deliveries;
+            let priority_lanes = inner.full_config.priority_lanes;
             let group_ref = inner.groups.entry(group.to_string())
-                .or_insert_with(|| ConsumerGroup::new(group.to_string(), head_seq, max_ack_pending, ack_wait, max_deliveries));
+                .or_insert_with(|| ConsumerGroup::new(group.to_string(), head_seq, max_ack_pending, ack_wait, max_deliveries, priority_lanes));
 
             match target {
                 SeekTarget::Beginning => group_ref.seek_beginning(head_seq),
                 SeekTarget::End => group_ref.seek_end(last_seq),
+                SeekTarget::AckFloor(ack_floor) => group_ref.seek_to(ack_floor),
             }
+            let generation = group_ref.generation();
             inner.groups_dirty = true;
-        }
+            generation
+        };
         topic_ref.notify.notify_waiters();
+        self.rebalanced(topic, group, generation, 0, RebalanceReason::Seek);
+        Ok(())
+    }
+
+    /// The committed position of `group`, portable to another instance (see
+    /// `stream::checkpoint`).
+    pub async fn export_checkpoint(&self, topic: &str, group: &str) -> Result<GroupCheckpoint, NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let (ack_floor, last_seq) = {
+            let inner = Self::lock_topic(&topic_ref.inner);
+            let group_ref = inner.groups.get(group).ok_or_else(|| NexoError::not_found("Group not found"))?;
+            (group_ref.ack_floor, inner.state.next_seq.saturating_sub(1))
+        };
+        let ack_floor_timestamp = match ack_floor {
+            0 => None,
+            _ => self.read(topic, ack_floor, 1).await.into_iter().find(|m| m.seq == ack_floor).map(|m| m.timestamp),
+        };
+        Ok(GroupCheckpoint {
+            version: CHECKPOINT_VERSION,
+            topic: topic.to_string(),
+            group: group.to_string(),
+            ack_floor,
+            ack_floor_timestamp,
+            last_seq,
+            exported_at: self.clock.now_ms(),
+        })
+    }
+
+    /// Move `group` on `topic` (created if missing) to the position of
+    /// `checkpoint`, like a seek. Returns the ack floor it resumes after.
+    #[tracing::instrument(name = "stream.import_checkpoint", skip_all, fields(topic = %topic, group = %group))]
+    pub async fn import_checkpoint(&self, topic: &str, group: &str, checkpoint: &GroupCheckpoint, by: CheckpointMatch) -> Result<u64, NexoError> {
+        checkpoint.validate()?;
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let (head_seq, last_seq) = {
+            let inner = Self::lock_topic(&topic_ref.inner);
+            (inner.state.head_seq.max(1), inner.state.next_seq.saturating_sub(1))
+        };
+        let ack_floor = match (by, checkpoint.ack_floor_timestamp) {
+            (CheckpointMatch::BySeq, _) => {
+                checkpoint.check_range(head_seq, last_seq)?;
+                checkpoint.ack_floor
+            }
+            (CheckpointMatch::ByTimestamp, None) if checkpoint.ack_floor == 0 => head_seq - 1,
+            (CheckpointMatch::ByTimestamp, None) => {
+                return Err(NexoError::invalid("Checkpoint has no timestamp to match: its ack floor record was gone at export"));
+            }
+            (CheckpointMatch::ByTimestamp, Some(timestamp)) => {
+                // Last record published at or before `timestamp`: timestamps grow with sequences
+                let (mut lo, mut hi) = (head_seq, last_seq + 1);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    match self.read(topic, mid, 1).await.into_iter().next() {
+                        Some(msg) if msg.timestamp <= timestamp => lo = msg.seq + 1,
+                        _ => hi = mid,
+                    }
+                }
+                lo - 1
+            }
+        };
+        self.seek(group, topic, SeekTarget::AckFloor(ack_floor)).await?;
+        Ok(ack_floor)
+    }
+
+    /// Stop delivering to `consumer_id` until resumed; its pending messages stay owned.
+    #[tracing::instrument(name = "stream.pause", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id))]
+    pub async fn pause(&self, group: &str, topic: &str, consumer_id: &str, generation: u64) -> Result<(), NexoError> {
+        self.set_paused(group, topic, consumer_id, generation, true)
+    }
+
+    #[tracing::instrument(name = "stream.resume", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id))]
+    pub async fn resume(&self, group: &str, topic: &str, consumer_id: &str, generation: u64) -> Result<(), NexoError> {
+        self.set_paused(group, topic, consumer_id, generation, false)
+    }
+
+    fn set_paused(&self, group: &str, topic: &str, consumer_id: &str, generation: u64, paused: bool) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        {
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            let Some(group_ref) = inner.groups.get_mut(group) else {
+                return Err(NexoError::not_found("Group not found"));
+            };
+            group_ref.set_paused(consumer_id, generation, paused)?;
+        }
+        if !paused {
+            // Wake the member's long-poll
+            topic_ref.notify.notify_waiters();
+        }
         Ok(())
     }
 
-    pub async fn leave_group(&self, group: &str, topic: &str, consumer_id: &str, generation: u64) -> Result<(), String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
-        let should_notify = {
+    #[tracing::instrument(name = "stream.leave", skip_all, fields(topic = %topic, group = %group, consumer_id = %consumer_id))]
+    pub async fn leave_group(&self, group: &str, topic: &str, consumer_id: &str, generation: u64) -> Result<(), NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let members = {
             let mut inner = Self::lock_topic(&topic_ref.inner);
             let Some(group_ref) = inner.groups.get_mut(group) else {
-                return Err("Group not found".to_string());
+                return Err(NexoError::not_found("Group not found"));
             };
 
             if group_ref.generation() != generation {
-                return Err("FENCED".to_string());
+                return Err(NexoError::new(ErrorCode::Fenced, "Stale group generation, rejoin required"));
             }
 
             let Some(connection_client_id) = group_ref.remove_member(consumer_id) else {
-                return Err("NOT_MEMBER".to_string());
+                return Err(NexoError::new(ErrorCode::NotMember, "Consumer is not a member of the group"));
             };
+            let members = group_ref.member_count();
 
             let mut remove_client_key = false;
             if let Some(bindings) = inner.client_map.get_mut(&connection_client_id) {
@@ -320,69 +1050,253 @@ impl StreamManager {
             }
 
             inner.groups_dirty = true;
-            true
+            members
         };
-        if should_notify {
-            topic_ref.notify.notify_waiters();
-        }
+        topic_ref.notify.notify_waiters();
+        self.rebalanced(topic, group, generation, members, RebalanceReason::Left);
         Ok(())
     }
 
-    pub async fn join_group(&self, group: &str, topic: &str, connection_client_id: &str) -> Result<JoinGroupResult, String> {
-        let topic_ref = self.get_topic(topic).ok_or("Topic not found")?;
-        let mut inner = Self::lock_topic(&topic_ref.inner);
-        let head_seq = inner.state.head_seq;
-        let max_ack_pending = inner.full_config.max_ack_pending;
-        let ack_wait = Duration::from_millis(inner.full_config.ack_wait_ms);
-        let max_deliveries = inner.full_config.max_deliveries;
-        let client_id = connection_client_id.to_string();
-        let group_id = group.to_string();
-        let group_exists = inner.groups.contains_key(&group_id);
-
-        let (ack_floor, consumer_id, generation, was_clamped) = {
-            let group_ref = inner.groups.entry(group_id.clone())
-                .or_insert_with(|| ConsumerGroup::new(group_id.clone(), head_seq, max_ack_pending, ack_wait, max_deliveries));
-            let was_clamped = group_ref.clamp_head(head_seq);
-            let consumer_id = group_ref.add_member(client_id.clone());
-            (group_ref.ack_floor, consumer_id, group_ref.generation(), was_clamped)
-        };
+    /// Join a group, or resume a detached membership when `session_token` matches one.
+    #[tracing::instrument(name = "stream.join", skip_all, fields(topic = %topic, group = %group))]
+    pub async fn join_group(&self, group: &str, topic: &str, connection_client_id: &str, session_token: Option<&str>) -> Result<JoinGroupResult, NexoError> {
+        let topic_ref = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let (consumer_id, generation, ack_floor, session_token, displaced, resumed, members) = {
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            let head_seq = inner.state.head_seq;
+            let max_ack_pending = inner.full_config.max_ack_pending;
+            let ack_wait = Duration::from_millis(inner.full_config.ack_wait_ms);
+            let max_deliveries = inner.full_config.max_deliveries;
+            let priority_lanes = inner.full_config.priority_lanes;
+            let client_id = connection_client_id.to_string();
+            let group_id = group.to_string();
+            let group_exists = inner.groups.contains_key(&group_id);
+            if !group_exists {
+                // The group names its DLQ topic, which must be a valid topic too
+                names::validate(NameKind::StreamGroup, group)?;
+                names::validate(NameKind::StreamTopic, &Self::dlq_topic(topic, group))?;
+            }
 
-        inner.client_map.entry(client_id).or_default().push(ConsumerBinding {
-            group_id: group_id.clone(),
-            consumer_id: consumer_id.clone(),
-        });
+            let (ack_floor, consumer_id, session_token, generation, was_clamped, previous_client, members, displaced) = {
+                let group_ref = inner.groups.entry(group_id.clone())
+                    .or_insert_with(|| ConsumerGroup::new(group_id.clone(), head_seq, max_ack_pending, ack_wait, max_deliveries, priority_lanes));
+                // Two live connections must not share a member: its cleanup would follow either
+                let holder = session_token.and_then(|token| group_ref.session_holder(token)).map(str::to_string);
+                let displaced = holder.clone().filter(|holder| *holder != client_id);
+                if displaced.is_some() && self.config.duplicate_session == DuplicateSession::Reject {
+                    return Err(NexoError::new(ErrorCode::Conflict, format!("Session of group '{}' on '{}' is held by another connection", group, topic)));
+                }
+                // Takeover, or the holder joining again: the member lets go of its connection first
+                if let (Some(token), Some(_)) = (session_token, &holder) {
+                    group_ref.detach_session(token);
+                }
+                // The generation handed out must outlive a restart before the client gets it
+                // (persisted below, once the topic is unlocked)
+                group_ref.fence_generation(self.epochs.previous(topic, &group_id));
+                let was_clamped = group_ref.clamp_head(head_seq);
+                let resumed = session_token.and_then(|token| {
+                    group_ref.resume_member(token, client_id.clone()).map(|(consumer_id, previous)| (consumer_id, token.to_string(), previous))
+                });
+                let (consumer_id, session_token, previous_client) = match resumed {
+                    Some((consumer_id, token, previous)) => (consumer_id, token, Some(previous)),
+                    None => {
+                        let (consumer_id, token) = group_ref.add_member(client_id.clone());
+                        (consumer_id, token, None)
+                    }
+                };
+                (group_ref.ack_floor, consumer_id, session_token, group_ref.generation(), was_clamped, previous_client, group_ref.member_count(), displaced)
+            };
 
-        if !group_exists || was_clamped {
-            inner.groups_dirty = true;
+            let resumed = previous_client.is_some();
+            if let Some(previous_client) = previous_client {
+                let mut remove_client_key = false;
+                if let Some(bindings) = inner.client_map.get_mut(&previous_client) {
+                    bindings.retain(|binding| !(binding.group_id == group_id && binding.consumer_id == consumer_id));
+                    remove_client_key = bindings.is_empty();
+                }
+                if remove_client_key {
+                    inner.client_map.remove(&previous_client);
+                }
+            }
+
+            inner.client_map.entry(client_id).or_default().push(ConsumerBinding {
+                group_id: group_id.clone(),
+                consumer_id: consumer_id.clone(),
+            });
+
+            if !group_exists || was_clamped {
+                inner.groups_dirty = true;
+            }
+            (consumer_id, generation, ack_floor, session_token, displaced, resumed, members)
+        };
+        if let Err(e) = self.epochs.record(topic, group, generation).await {
+            // Not handed out: the member goes again
+            let _ = self.leave_group(group, topic, &consumer_id, generation).await;
+            return Err(e);
+        }
+        if !resumed {
+            self.rebalanced(topic, group, generation, members, RebalanceReason::Joined);
         }
 
         Ok(JoinGroupResult {
             ack_floor,
             consumer_id,
             generation,
+            session_token,
+            displaced,
+        })
+    }
+
+    /// Topics matched by `selectors`: exact names, or prefixes ending in `*`.
+    /// Patterns never match DLQ topics. Sorted, without duplicates.
+    pub fn resolve_topics(&self, selectors: &[String]) -> Result<Vec<String>, NexoError> {
+        let mut topics = Vec::new();
+        for selector in selectors {
+            match selector.ends_with('*') {
+                true => topics.extend(self.topics.iter()
+                    .map(|t| t.key().clone())
+                    .filter(|name| Self::matches_pattern(selector, name))),
+                false if self.topics.contains_key(selector) => topics.push(selector.clone()),
+                false => return Err(NexoError::not_found(format!("Topic '{}' not found", selector))),
+            }
+        }
+        topics.sort();
+        topics.dedup();
+        if topics.is_empty() {
+            return Err(NexoError::not_found("No topic matches the subscription"));
+        }
+        Ok(topics)
+    }
+
+    /// Join `group` on every topic matched by `selectors`. Each topic keeps its own
+    /// membership and ack floor; `session_tokens` (topic -> token) resumes them.
+    /// All or nothing: on failure the memberships taken so far are released.
+    /// Patterns are stored on the group, which then binds to matching new topics.
+    pub async fn join_topics(&self, group: &str, selectors: &[String], connection_client_id: &str, session_tokens: &HashMap<String, String>) -> Result<Vec<(String, JoinGroupResult)>, NexoError> {
+        let topics = self.resolve_topics(selectors)?;
+        let mut joined: Vec<(String, JoinGroupResult)> = Vec::with_capacity(topics.len());
+        for topic in topics {
+            match self.join_group(group, &topic, connection_client_id, session_tokens.get(&topic).map(String::as_str)).await {
+                Ok(result) => {
+                    self.store_patterns(group, &topic, selectors);
+                    joined.push((topic, result));
+                }
+                Err(e) => {
+                    for (topic, result) in &joined {
+                        let _ = self.leave_group(group, topic, &result.consumer_id, result.generation).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(joined)
+    }
+
+    fn store_patterns(&self, group: &str, topic: &str, selectors: &[String]) {
+        let Some(topic_ref) = self.get_topic(topic) else { return };
+        let mut inner = Self::lock_topic(&topic_ref.inner);
+        if let Some(g) = inner.groups.get_mut(group) {
+            g.patterns.extend(selectors.iter().filter(|s| Self::matches_pattern(s, topic)).cloned());
+        }
+    }
+
+    /// Whether the topics behind `members` changed: one was dropped, or a topic
+    /// matching one of the group's patterns isn't part of the subscription yet.
+    fn topics_changed(&self, group: &str, members: &[TopicMember]) -> bool {
+        let mut patterns = std::collections::BTreeSet::new();
+        for member in members {
+            let Some(topic_ref) = self.get_topic(&member.topic) else { return true };
+            let inner = Self::lock_topic(&topic_ref.inner);
+            if let Some(g) = inner.groups.get(group) {
+                patterns.extend(g.patterns.iter().cloned());
+            }
+        }
+        if patterns.is_empty() {
+            return false;
+        }
+        self.topics.iter().any(|t| {
+            !members.iter().any(|m| &m.topic == t.key()) && patterns.iter().any(|p| Self::matches_pattern(p, t.key()))
         })
     }
 
+    /// Fetch across the topics of a multi-topic subscription, tagging each record
+    /// with its topic. Every topic first gets an even share of `limit`, the rest
+    /// goes to whoever still has records. Long-polls until any topic has data.
+    /// Fails with `Rebalance` once the subscribed topics change (see `topics_changed`).
+    pub async fn fetch_topics(&self, group: &str, members: &[TopicMember], limit: usize, wait_ms: u64) -> Result<Vec<(String, Message)>, NexoError> {
+        self.fetch_topics_cancellable(group, members, limit, wait_ms, &CancellationToken::new()).await
+    }
+
+    /// `fetch_topics` for a request `cancel` aborts, as in `fetch_cancellable`.
+    pub async fn fetch_topics_cancellable(&self, group: &str, members: &[TopicMember], limit: usize, wait_ms: u64, cancel: &CancellationToken) -> Result<Vec<(String, Message)>, NexoError> {
+        if members.is_empty() {
+            return Err(NexoError::invalid("No topic to fetch from"));
+        }
+        let rebalance = || NexoError::new(ErrorCode::Rebalance, "Subscribed topics changed, rejoin required");
+        let topic_refs = members.iter()
+            .map(|m| self.get_topic(&m.topic).ok_or_else(rebalance))
+            .collect::<Result<Vec<_>, _>>()?;
+        let share = (limit / members.len()).max(1);
+        let deadline = Instant::now() + Duration::from_millis(wait_ms);
+
+        loop {
+            let topology_changed = self.topology.notified();
+            let notified: Vec<_> = topic_refs.iter().map(|t| Box::pin(t.notify.notified())).collect();
+            if self.topics_changed(group, members) {
+                return Err(rebalance());
+            }
+
+            let mut out = Vec::new();
+            for budget in [share, limit] {
+                for member in members {
+                    let remaining = budget.min(limit - out.len());
+                    if remaining == 0 {
+                        break;
+                    }
+                    let messages = self.fetch_cancellable(group, &member.consumer_id, member.generation, remaining, &member.topic, 0, cancel).await?;
+                    out.extend(messages.into_iter().map(|msg| (member.topic.clone(), msg)));
+                }
+            }
+            if !out.is_empty() || Instant::now() >= deadline {
+                return Ok(out);
+            }
+
+            tokio::select! {
+                _ = futures_util::future::select_all(notified) => {}
+                _ = topology_changed => {}
+                _ = sleep_until(deadline) => return Ok(Vec::new()),
+                _ = cancel.cancelled() => return Ok(Vec::new()),
+            }
+        }
+    }
+
+    /// Detach the client's memberships; they are released once the session grace expires.
     pub async fn disconnect(&self, client_id: String) {
         info!("[StreamManager] Disconnecting client: {}", client_id);
-        for (_, topic_ref) in Self::collect_topics(&self.topics) {
-            let mut should_notify = false;
+        let detach = self.config.session_grace_ms > 0;
+        for (topic, topic_ref) in Self::collect_topics(&self.topics) {
+            let mut left = Vec::new();
             {
                 let mut inner = Self::lock_topic(&topic_ref.inner);
                 if let Some(bindings) = inner.client_map.remove(&client_id) {
                     for binding in bindings {
                         if let Some(group_ref) = inner.groups.get_mut(&binding.group_id) {
-                            if group_ref.remove_member(&binding.consumer_id).is_some() {
-                                should_notify = true;
+                            if detach {
+                                group_ref.detach_member(&binding.consumer_id);
+                            } else if group_ref.remove_member(&binding.consumer_id).is_some() {
+                                left.push((binding.group_id, group_ref.generation(), group_ref.member_count()));
                                 inner.groups_dirty = true;
                             }
                         }
                     }
                 }
             }
-            if should_notify {
+            if !left.is_empty() {
                 topic_ref.notify.notify_waiters();
             }
+            for (group, generation, members) in left {
+                self.rebalanced(&topic, &group, generation, members, RebalanceReason::Left);
+            }
         }
     }
 
@@ -390,7 +1304,8 @@ impl StreamManager {
         let mut topics = Vec::new();
 
         for (_, topic_ref) in Self::collect_topics(&self.topics) {
-            let inner = Self::lock_topic(&topic_ref.inner);
+            let mut inner = Self::lock_topic(&topic_ref.inner);
+            let (publish_rate, byte_rate) = inner.state.publish_rates(self.clock.now_ms());
             let groups = inner.groups.values().map(|group| ConsumerGroupSnapshot {
                 id: group.id.clone(),
                 ack_floor: group.ack_floor,
@@ -403,12 +1318,31 @@ impl StreamManager {
             topics.push(TopicSnapshot {
                 name: inner.state.name.clone(),
                 last_seq: inner.state.next_seq.saturating_sub(1),
+                head_seq: inner.state.head_seq,
+                persisted_seq: topic_ref.persisted_seq.load(Ordering::Acquire),
+                bytes: inner.state.bytes,
+                publish_rate,
+                byte_rate,
+                ram_messages: inner.state.log.len(),
+                ram_bytes: inner.state.ram_bytes,
+                hot_reads: topic_ref.reads.hot.load(Ordering::Relaxed),
+                cold_reads: topic_ref.reads.cold.load(Ordering::Relaxed),
                 groups,
+                traced: self.tracer.is_enabled(&inner.state.name),
+                recovered: topic_ref.recovered,
                 config: safe_config,
             });
         }
 
-        StreamSnapshot { topics }
+        let mirrors = self.mirrors.lock().unwrap_or_else(|p| p.into_inner()).iter().map(|m| m.snapshot()).collect();
+        let read_cache = ReadCacheSnapshot {
+            capacity: self.config.read_cache_blocks,
+            blocks: self.read_cache.blocks.load(Ordering::Relaxed),
+            hits: self.read_cache.hits.load(Ordering::Relaxed),
+            misses: self.read_cache.misses.load(Ordering::Relaxed),
+        };
+        let backfills = self.backfills().iter().map(|b| b.snapshot()).collect();
+        StreamSnapshot { topics, mirrors, backfills, read_cache }
     }
 
     pub async fn exists(&self, name: &str) -> bool {
@@ -424,6 +1358,16 @@ impl StreamManager {
         self.topics.get(topic).map(|entry| entry.value().clone())
     }
 
+    fn rebalanced(&self, topic: &str, group: &str, generation: u64, members: usize, reason: RebalanceReason) {
+        self.events.post(LifecycleEvent::GroupRebalanced {
+            topic: topic.to_string(),
+            group: group.to_string(),
+            generation,
+            members,
+            reason,
+        });
+    }
+
     fn lock_topic<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
         mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
     }
@@ -434,14 +1378,25 @@ impl StreamManager {
 
     async fn load_topic_config(base_path: &PathBuf, options: StreamCreateOptions, config: &SystemStreamConfig) -> TopicConfig {
         let config_path = base_path.join("config.json");
-        if let Ok(data) = tokio::fs::read_to_string(&config_path).await {
+        let mut topic_config = if let Ok(data) = tokio::fs::read_to_string(&config_path).await {
             serde_json::from_str(&data).unwrap_or_else(|_| TopicConfig::from_options(options, config))
         } else {
             TopicConfig::from_options(options, config)
-        }
+        };
+        // The manifest records the root it was written under: follow the data directory if it moved
+        topic_config.persistence_path = config.persistence_path.clone();
+        topic_config
     }
 
-    async fn bootstrap_from_disk(&self) {
+    /// A topic directory holds its manifest (`config.json`) or segments; anything
+    /// else under the root (`lost+found`, a copy left by an operator) is not a topic.
+    fn is_topic_dir(path: &Path) -> bool {
+        path.join("config.json").is_file() || std::fs::read_dir(path).map(|entries| {
+            entries.flatten().any(|entry| entry.file_name().to_str().is_some_and(|name| name.ends_with(".log") && name != "groups.log"))
+        }).unwrap_or(false)
+    }
+
+    async fn bootstrap_from_disk(&self, warm_start: &WarmStart) {
         let persistence_path = PathBuf::from(&self.config.persistence_path);
         if !persistence_path.exists() {
             return;
@@ -452,43 +1407,76 @@ impl StreamManager {
             Err(_) => return,
         };
 
+        let mut found = Vec::new();
         while let Ok(Some(entry)) = entries.next_entry().await {
             let path = entry.path();
             if !path.is_dir() {
                 continue;
             }
+            if !Self::is_topic_dir(&path) {
+                warn!("[StreamManager] Skipping {:?}: no topic manifest or segments", path);
+                continue;
+            }
 
             let Some(topic_name) = path.file_name().and_then(|name| name.to_str()) else {
                 continue;
             };
 
             let name = topic_name.to_string();
-            if self.deleted_topics.contains_key(&name) {
-                continue;
+            if !self.deleted_topics.contains_key(&name) {
+                found.push((name, path));
             }
+        }
+        warm_start.topics.discovered(found.len());
+
+        // Segment reads are async file IO: topics recover concurrently
+        let restored = futures_util::stream::iter(found)
+            .map(|(name, path)| self.restore_topic(name, path))
+            .buffer_unordered(warm_start.concurrency)
+            .fold(0, |restored, messages| async move {
+                warm_start.topics.recovered(messages.unwrap_or(0));
+                restored + messages.is_some() as usize
+            })
+            .await;
+        if restored > 0 {
+            info!("[StreamManager] Warm start: {} topics restored from {:?}", restored, persistence_path);
+        }
+    }
 
-            let topic_config = Self::load_topic_config(&path, StreamCreateOptions::default(), &self.config).await;
-            let topic_ref = Self::build_topic_shared(name.clone(), topic_config).await;
+    /// Restore one topic found on disk; returns the records loaded into its
+    /// RAM window, `None` if a topic of that name already exists.
+    async fn restore_topic(&self, name: String, path: PathBuf) -> Option<usize> {
+        let topic_config = Self::load_topic_config(&path, StreamCreateOptions::default(), &self.config).await;
+        self.schemas.load(&name, &path.join("schemas.json"));
+        self.epochs.load(&name, &path);
+        if topic_config.flush_ms.is_some() {
+            self.set_flush_interval(&name, &topic_config);
+        }
+        let (topic_ref, messages) = Self::build_topic_shared(name.clone(), topic_config, true).await;
 
-            use dashmap::mapref::entry::Entry;
-            match self.topics.entry(name.clone()) {
-                Entry::Occupied(_) => {}
-                Entry::Vacant(v) => {
-                    v.insert(topic_ref);
-                    info!("[StreamManager] Restored topic '{}'", name);
-                }
+        use dashmap::mapref::entry::Entry;
+        match self.topics.entry(name.clone()) {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(v) => {
+                v.insert(topic_ref);
+                self.activity.track(&name);
+                info!("[StreamManager] Restored topic '{}'", name);
+                Some(messages)
             }
         }
     }
 
-    async fn build_topic_shared(name: String, config: TopicConfig) -> Arc<TopicShared> {
+    /// Also returns the number of records recovered into the RAM window.
+    async fn build_topic_shared(name: String, config: TopicConfig, from_disk: bool) -> (Arc<TopicShared>, usize) {
         let base_path = PathBuf::from(&config.persistence_path).join(&name);
         if let Err(e) = tokio::fs::create_dir_all(&base_path).await {
             tracing::error!("Failed to create topic directory at {:?}: {}", base_path, e);
         }
 
         let recovered = recover_topic(&name, PathBuf::from(config.persistence_path.clone())).await;
-        let state = TopicState::restore(name.clone(), config.ram_soft_limit, recovered.head_seq.max(1), recovered.messages);
+        let loaded = recovered.messages.len();
+        let mut state = TopicState::restore(name.clone(), config.ram_soft_limit, recovered.head_seq.max(1), recovered.messages, recovered.total_bytes);
+        state.ram_max_bytes = config.ram_max_bytes;
 
         let ack_wait = Duration::from_millis(config.ack_wait_ms);
         let persisted_seq = Arc::new(AtomicU64::new(state.next_seq.saturating_sub(1)));
@@ -496,21 +1484,177 @@ impl StreamManager {
         for (group_id, ack_floor) in recovered.groups_data {
             groups.insert(
                 group_id.clone(),
-                ConsumerGroup::restore(group_id, ack_floor, state.head_seq, config.max_ack_pending, ack_wait, config.max_deliveries),
+                ConsumerGroup::restore(group_id, ack_floor, state.head_seq, config.max_ack_pending, ack_wait, config.max_deliveries, config.priority_lanes),
             );
         }
 
-        Arc::new(TopicShared {
+        let shared = Arc::new(TopicShared {
             inner: Mutex::new(TopicInner {
                 state,
                 groups,
                 client_map: HashMap::new(),
                 groups_dirty: false,
                 full_config: config,
+                lingering: None,
             }),
             notify: Notify::new(),
             persisted_seq,
-        })
+            recovered: from_disk,
+            reads: ReadTiers::default(),
+        });
+        (shared, loaded)
+    }
+
+    /// Start copying a range of `spec.source` into `spec.target` in the background.
+    /// The range ends at `to_seq`, or at the source's last sequence right now.
+    pub async fn start_backfill(&self, spec: BackfillSpec) -> Result<u64, NexoError> {
+        spec.validate().map_err(NexoError::invalid)?;
+        let source = self.get_topic(&spec.source).ok_or_else(|| NexoError::not_found("Source topic not found"))?;
+        let last_seq = Self::lock_topic(&source.inner).state.next_seq.saturating_sub(1);
+        let to_seq = spec.to_seq.map_or(last_seq, |to| to.min(last_seq));
+
+        let mut backfills = self.backfills.lock().unwrap_or_else(|p| p.into_inner());
+        let id = backfills.last().map_or(1, |b| b.id() + 1);
+        backfills.push(backfill::spawn(self.clone(), id, spec, to_seq, self.cancel.child_token()));
+        Ok(id)
+    }
+
+    /// Write the topic's retained records, oldest first, to a dump at `path`
+    /// (see `export`). Records published meanwhile are not included.
+    #[tracing::instrument(name = "stream.export", skip_all, fields(topic = %topic))]
+    pub async fn export_topic(&self, topic: &str, path: &Path, format: DumpFormat) -> Result<TopicExportSummary, NexoError> {
+        let shared = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let last_seq = Self::lock_topic(&shared.inner).state.next_seq.saturating_sub(1);
+
+        let mut writer = DumpWriter::create(path, format).await?;
+        let mut summary = TopicExportSummary::default();
+        let mut next = 1;
+        while next <= last_seq {
+            let batch = self.read(topic, next, EXPORT_BATCH.min((last_seq - next + 1) as usize)).await;
+            let Some(last) = batch.last() else { break };
+            next = last.seq + 1;
+            for msg in batch.iter().filter(|m| m.seq <= last_seq) {
+                writer.write(&DumpRecord::from(msg)).await?;
+                summary.first_offset.get_or_insert(msg.seq);
+                summary.last_offset = Some(msg.seq);
+                summary.records += 1;
+            }
+        }
+        writer.finish().await?;
+
+        info!("Topic '{}': exported {} records to {}", topic, summary.records, path.display());
+        Ok(summary)
+    }
+
+    /// Key distribution over the newest `spec.sample` records (see
+    /// `key_stats`). Reads in batches without holding the topic between them.
+    #[tracing::instrument(name = "stream.key_stats", skip_all, fields(topic = %topic))]
+    pub async fn key_stats(&self, topic: &str, spec: &KeyStatsSpec) -> Result<KeyStats, NexoError> {
+        spec.validate().map_err(NexoError::invalid)?;
+        let shared = self.get_topic(topic).ok_or_else(|| NexoError::not_found("Topic not found"))?;
+        let (head_seq, last_seq) = {
+            let inner = Self::lock_topic(&shared.inner);
+            (inner.state.head_seq, inner.state.next_seq.saturating_sub(1))
+        };
+        let mut sampler = KeySampler::new(spec);
+        if last_seq == 0 {
+            return Ok(sampler.finish(topic.to_string(), 0, 0));
+        }
+        let from_seq = last_seq.saturating_sub(spec.sample() as u64 - 1).max(head_seq).max(1);
+        let mut next = from_seq;
+        while next <= last_seq {
+            let batch = self.read(topic, next, EXPORT_BATCH.min((last_seq - next + 1) as usize)).await;
+            let Some(last) = batch.last() else { break };
+            next = last.seq + 1;
+            for msg in batch.iter().filter(|m| m.seq <= last_seq) {
+                sampler.add(&msg.payload);
+            }
+            tokio::task::yield_now().await;
+        }
+        Ok(sampler.finish(topic.to_string(), from_seq, last_seq))
+    }
+
+    /// Create `topic` from a dump written by `export_topic` (or another tool
+    /// using the same format), appending its records in file order with their
+    /// original timestamps. The topic must not exist yet; it is deleted again
+    /// if the import fails partway, so a retry starts clean.
+    #[tracing::instrument(name = "stream.import", skip_all, fields(topic = %topic))]
+    pub async fn import_topic(&self, path: &Path, topic: String, options: StreamCreateOptions) -> Result<TopicImportSummary, NexoError> {
+        if self.exists(&topic).await {
+            return Err(NexoError::invalid(format!("Topic '{}' already exists", topic)));
+        }
+        let mut reader = DumpReader::open(path).await?;
+        let priority_lanes = options.priority_lanes.unwrap_or(false);
+        self.create_topic(topic.clone(), options).await?;
+
+        let mut summary = TopicImportSummary::default();
+        let imported: Result<(), NexoError> = async {
+            while let Some(record) = reader.next().await? {
+                let lane = match record.lane {
+                    LANE_HIGH if priority_lanes => LANE_HIGH,
+                    LANE_HIGH => {
+                        summary.lane_downgraded += 1;
+                        LANE_NORMAL
+                    }
+                    _ => LANE_NORMAL,
+                };
+                self.append(&topic, record.payload, lane, Some(record.timestamp)).await?;
+                summary.records += 1;
+            }
+            Ok(())
+        }.await;
+        if let Err(e) = imported {
+            if let Err(cleanup) = self.delete_topic(topic.clone()).await {
+                warn!("Topic '{}': failed to delete after a failed import: {}", topic, cleanup.message);
+            }
+            return Err(e);
+        }
+
+        info!("Topic '{}': imported {} records from {}", topic, summary.records, path.display());
+        Ok(summary)
+    }
+
+    /// Backfill jobs since startup, oldest first.
+    pub fn backfills(&self) -> Vec<Arc<BackfillStatus>> {
+        self.backfills.lock().unwrap_or_else(|p| p.into_inner()).clone()
+    }
+
+    pub fn backfill(&self, id: u64) -> Option<BackfillSnapshot> {
+        self.backfills().iter().find(|b| b.id() == id).map(|b| b.snapshot())
+    }
+
+    /// Stop a running backfill. `false` if unknown or already finished.
+    pub fn cancel_backfill(&self, id: u64) -> bool {
+        self.backfills().iter().find(|b| b.id() == id).is_some_and(|b| b.cancel())
+    }
+
+    fn spawn_mirrors(&self) {
+        let statuses = self.config.mirrors.iter()
+            .map(|spec| mirror::spawn(self.clone(), spec.clone(), &self.config.persistence_path, self.cancel.clone()))
+            .collect();
+        *self.mirrors.lock().unwrap_or_else(|p| p.into_inner()) = statuses;
+    }
+
+    /// Queue a save of every topic's group ack floors that changed since the last one.
+    fn save_dirty_groups(topics: &Arc<DashMap<String, Arc<TopicShared>>>, storage_tx: &mpsc::UnboundedSender<StorageCommand>) {
+        for (topic_name, topic_ref) in StreamManager::collect_topics(topics) {
+            let groups_data = {
+                let mut inner = StreamManager::lock_topic(&topic_ref.inner);
+                if !inner.groups_dirty {
+                    None
+                } else {
+                    inner.groups_dirty = false;
+                    Some(inner.groups.iter().map(|(id, group)| (id.clone(), group.ack_floor)).collect::<HashMap<_, _>>())
+                }
+            };
+
+            if let Some(groups_data) = groups_data {
+                let _ = storage_tx.send(StorageCommand::SaveGroups {
+                    topic_name,
+                    groups_data,
+                });
+            }
+        }
     }
 
     fn spawn_background_tasks(&self) {
@@ -549,24 +1693,7 @@ impl StreamManager {
                         _ = cancel.cancelled() => break,
                         _ = timer.tick() => {}
                     }
-                    for (topic_name, topic_ref) in StreamManager::collect_topics(&topics) {
-                        let groups_data = {
-                            let mut inner = StreamManager::lock_topic(&topic_ref.inner);
-                            if !inner.groups_dirty {
-                                None
-                            } else {
-                                inner.groups_dirty = false;
-                                Some(inner.groups.iter().map(|(id, group)| (id.clone(), group.ack_floor)).collect::<HashMap<_, _>>())
-                            }
-                        };
-
-                        if let Some(groups_data) = groups_data {
-                            let _ = storage_tx.send(StorageCommand::SaveGroups {
-                                topic_name,
-                                groups_data,
-                            });
-                        }
-                    }
+                    StreamManager::save_dirty_groups(&topics, &storage_tx);
                 }
             }
         });
@@ -574,6 +1701,7 @@ impl StreamManager {
         let topics = self.topics.clone();
         let storage_tx = self.storage_tx.clone();
         let retention_check_ms = self.config.retention_check_interval_ms;
+        let events = self.events.clone();
         tokio::spawn({
             let cancel = cancel.clone();
             async move {
@@ -585,29 +1713,43 @@ impl StreamManager {
                         _ = timer.tick() => {}
                     }
                     for (topic_name, topic_ref) in StreamManager::collect_topics(&topics) {
-                        let (retention, max_segment_size) = {
+                        let (reply_tx, reply_rx) = oneshot::channel();
+                        // Sent under the topic lock: appends sent before it are measured on disk,
+                        // the bytes counted from here on (and those still lingering) are not
+                        let bytes_sent = {
                             let inner = StreamManager::lock_topic(&topic_ref.inner);
-                            (inner.full_config.retention.clone(), inner.full_config.max_segment_size)
+                            if storage_tx.send(StorageCommand::ApplyRetention {
+                                topic_name: topic_name.clone(),
+                                retention: inner.full_config.retention.clone(),
+                                max_segment_size: inner.full_config.max_segment_size,
+                                reply: reply_tx,
+                            }).is_err() {
+                                continue;
+                            }
+                            let lingering: u64 = inner.lingering.iter()
+                                .flat_map(|batch| &batch.messages)
+                                .map(|m| record_size(m.lane, m.payload.len()))
+                                .sum();
+                            inner.state.bytes.saturating_sub(lingering)
                         };
 
-                        let (reply_tx, reply_rx) = oneshot::channel();
-                        if storage_tx.send(StorageCommand::ApplyRetention {
-                            topic_name,
-                            retention,
-                            max_segment_size,
-                            reply: reply_tx,
-                        }).is_err() {
-                            continue;
-                        }
-
                         let Ok(outcome) = reply_rx.await else {
                             continue;
                         };
+                        if outcome.deleted_segments > 0 {
+                            events.post(LifecycleEvent::SegmentsDeleted {
+                                topic: topic_name,
+                                segments: outcome.deleted_segments,
+                                bytes: outcome.deleted_bytes,
+                                head_seq: outcome.head_seq,
+                            });
+                        }
 
                         let mut should_notify = false;
                         {
                             let mut inner = StreamManager::lock_topic(&topic_ref.inner);
                             let mut groups_changed = false;
+                            inner.state.bytes = outcome.total_bytes + inner.state.bytes.saturating_sub(bytes_sent);
                             if outcome.head_seq != inner.state.head_seq {
                                 inner.state.apply_head(outcome.head_seq);
                                 for group in inner.groups.values_mut() {
@@ -630,6 +1772,8 @@ impl StreamManager {
         });
 
         let topics = self.topics.clone();
+        let session_grace = Duration::from_millis(self.config.session_grace_ms);
+        let manager = self.clone();
         tokio::spawn({
             let cancel = cancel.clone();
             async move {
@@ -640,16 +1784,31 @@ impl StreamManager {
                         _ = cancel.cancelled() => break,
                         _ = timer.tick() => {}
                     }
-                    for (_, topic_ref) in StreamManager::collect_topics(&topics) {
+                    for (topic_name, topic_ref) in StreamManager::collect_topics(&topics) {
                         let mut should_notify = false;
+                        let mut dead_letters = Vec::new();
+                        let mut expired = Vec::new();
                         {
                             let mut inner = StreamManager::lock_topic(&topic_ref.inner);
                             let mut groups_changed = false;
+                            let dead_letter = inner.full_config.dead_letter;
                             for group in inner.groups.values_mut() {
                                 if group.check_redelivery() {
                                     groups_changed = true;
                                     should_notify = true;
                                 }
+                                if group.expire_detached(session_grace) {
+                                    expired.push((group.id.clone(), group.generation(), group.member_count()));
+                                    groups_changed = true;
+                                    should_notify = true;
+                                }
+                                if group.has_dead_letters() {
+                                    groups_changed = true;
+                                    let letters = StreamManager::drain_dead_letters(group, dead_letter);
+                                    if !letters.is_empty() {
+                                        dead_letters.push((group.id.clone(), letters));
+                                    }
+                                }
                             }
                             if groups_changed {
                                 inner.groups_dirty = true;
@@ -658,12 +1817,18 @@ impl StreamManager {
                         if should_notify {
                             topic_ref.notify.notify_waiters();
                         }
+                        for (group, generation, members) in expired {
+                            manager.rebalanced(&topic_name, &group, generation, members, RebalanceReason::Expired);
+                        }
+                        for (group, letters) in dead_letters {
+                            manager.dead_letter(&topic_name, &group, letters).await;
+                        }
                     }
                 }
             }
         });
     }
-    fn try_fetch_once(&self, topic_ref: &Arc<TopicShared>, group: &str, consumer_id: &str, generation: u64, limit: usize) -> Result<FetchAttempt, String> {
+    fn try_fetch_once(&self, topic_ref: &Arc<TopicShared>, group: &str, consumer_id: &str, generation: u64, limit: usize) -> Result<FetchAttempt, NexoError> {
         let mut inner = Self::lock_topic(&topic_ref.inner);
         let TopicInner {
             state,
@@ -679,16 +1844,21 @@ impl StreamManager {
         let (was_clamped, messages, is_fetching_cold, from_seq) = {
             let group_ref = match groups.get_mut(group) {
                 Some(g) => g,
-                None => return Err("Group not found".to_string()),
+                None => return Err(NexoError::not_found("Group not found")),
             };
 
+            // Paused: long-poll like an idle topic until resumed
+            if group_ref.generation() == generation && group_ref.is_paused(consumer_id) {
+                return Ok(FetchAttempt::Wait);
+            }
+
             let was_clamped = group_ref.clamp_head(head_seq);
 
             if group_ref.is_backpressured() {
                 return Ok(FetchAttempt::Ready(Vec::new()));
             }
 
-            let messages = group_ref.fetch(consumer_id, generation, limit, &state.log, ram_start_seq, head_seq)?;
+            let messages = group_ref.fetch(consumer_id, generation, limit, state)?;
             let is_fetching_cold = group_ref.is_fetching_cold;
             let from_seq = group_ref.next_fetch_seq(head_seq);
             (was_clamped, messages, is_fetching_cold, from_seq)
@@ -699,6 +1869,7 @@ impl StreamManager {
         }
 
         if !messages.is_empty() {
+            topic_ref.reads.record(false, messages.len());
             return Ok(FetchAttempt::Ready(messages));
         }
 
@@ -718,47 +1889,58 @@ impl StreamManager {
         Ok(FetchAttempt::Wait)
     }
 
-    async fn cold_fetch(&self, topic_ref: &Arc<TopicShared>, topic: &str, group: &str, consumer_id: &str, generation: u64, limit: usize, from_seq: u64, group_cancel: &CancellationToken) -> Result<Vec<Message>, String> {
+    async fn cold_fetch(&self, topic_ref: &Arc<TopicShared>, read: ColdFetch<'_>, group_cancel: &CancellationToken, cancel: &CancellationToken) -> Result<Vec<Message>, NexoError> {
+        let ColdFetch { topic, group, consumer_id, generation, limit, from_seq } = read;
         let (tx, rx) = oneshot::channel();
         if self.storage_tx.send(StorageCommand::ColdRead {
             topic_name: topic.to_string(),
             from_seq,
             limit,
             reply: tx,
+            span: tracing::Span::current(),
         }).is_err() {
             let mut inner = Self::lock_topic(&topic_ref.inner);
             if let Some(group_ref) = inner.groups.get_mut(group) {
                 group_ref.is_fetching_cold = false;
             }
-            return Err("Disk read failed".to_string());
+            return Err(NexoError::new(ErrorCode::Storage, "Disk read failed"));
         }
 
-        let messages = match rx.await {
-            Ok(messages) => messages,
-            Err(_) => {
+        // Dropping `rx` tells the storage actor to skip the read if not started
+        let read = tokio::select! {
+            read = rx => Some(read),
+            _ = cancel.cancelled() => None,
+        };
+        let messages = match read {
+            Some(Ok(messages)) => messages,
+            outcome => {
                 let mut inner = Self::lock_topic(&topic_ref.inner);
                 if let Some(group_ref) = inner.groups.get_mut(group) {
                     group_ref.is_fetching_cold = false;
                 }
-                return Err("Disk read failed".to_string());
+                return match outcome {
+                    None => Ok(Vec::new()),
+                    _ => Err(NexoError::new(ErrorCode::Storage, "Disk read failed")),
+                };
             }
         };
 
         let mut inner = Self::lock_topic(&topic_ref.inner);
-        let head_seq = inner.state.head_seq;
-        if let Some(group_ref) = inner.groups.get_mut(group) {
+        let TopicInner { state, groups, groups_dirty, .. } = &mut *inner;
+        if let Some(group_ref) = groups.get_mut(group) {
             group_ref.is_fetching_cold = false;
-            if group_cancel.is_cancelled() {
+            if group_cancel.is_cancelled() || cancel.is_cancelled() {
                 return Ok(Vec::new());
             }
-            let was_clamped = group_ref.clamp_head(head_seq);
-            let registered = group_ref.register_cold_messages(consumer_id, generation, messages, head_seq)?;
+            let was_clamped = group_ref.clamp_head(state.head_seq);
+            let registered = group_ref.register_cold_messages(consumer_id, generation, messages, state)?;
             if was_clamped {
-                inner.groups_dirty = true;
+                *groups_dirty = true;
             }
+            topic_ref.reads.record(true, registered.len());
             Ok(registered)
         } else {
-            Err("Group disappeared during cold read".to_string())
+            Err(NexoError::new(ErrorCode::Internal, "Group disappeared during cold read"))
         }
     }
 }
@@ -0,0 +1,360 @@
+//! Stream Mirror: replicate a topic from a remote Nexo into a local topic.
+//!
+//! Each mirror joins a consumer group on the remote instance over the native
+//! protocol, republishes fetched messages locally with their original
+//! timestamps, then acks them remotely. The remote group's ack floor is the
+//! resume point across restarts; a local checkpoint of the last mirrored
+//! sequence skips messages republished but not yet acked when we stopped.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::brokers::stream::config::MirrorSpec;
+use crate::brokers::stream::options::StreamCreateOptions;
+use crate::brokers::stream::snapshot::MirrorSnapshot;
+use crate::brokers::stream::tcp::{OP_S_ACK, OP_S_FETCH, OP_S_JOIN};
+use crate::brokers::stream::StreamManager;
+use crate::transport::tcp::protocol::cursor::PayloadCursor;
+use crate::transport::tcp::protocol::frame::{FrameHeader, STATUS_ERR, TYPE_CHUNK, TYPE_CHUNK_BEGIN, TYPE_CHUNK_END, TYPE_REQUEST, TYPE_RESPONSE};
+use crate::transport::tcp::protocol::ParseError;
+
+const FETCH_LIMIT: u32 = 100;
+const FETCH_WAIT_MS: u32 = 1000;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// ==========================================
+// STATUS
+// ==========================================
+
+pub struct MirrorStatus {
+    spec: MirrorSpec,
+    connected: AtomicBool,
+    mirrored: AtomicU64,
+    last_seq: AtomicU64,
+    lag_ms: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl MirrorStatus {
+    fn new(spec: MirrorSpec) -> Self {
+        Self {
+            spec,
+            connected: AtomicBool::new(false),
+            mirrored: AtomicU64::new(0),
+            last_seq: AtomicU64::new(0),
+            lag_ms: AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    fn set_error(&self, error: Option<String>) {
+        *self.last_error.lock().unwrap_or_else(|p| p.into_inner()) = error;
+    }
+
+    pub fn snapshot(&self) -> MirrorSnapshot {
+        MirrorSnapshot {
+            source: self.spec.source(),
+            local_topic: self.spec.local_topic.clone(),
+            connected: self.connected.load(Ordering::Relaxed),
+            mirrored: self.mirrored.load(Ordering::Relaxed),
+            last_seq: self.last_seq.load(Ordering::Relaxed),
+            lag_ms: self.lag_ms.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().unwrap_or_else(|p| p.into_inner()).clone(),
+        }
+    }
+}
+
+// ==========================================
+// MIRROR TASK
+// ==========================================
+
+pub fn spawn(manager: StreamManager, spec: MirrorSpec, persistence_path: &str, cancel: CancellationToken) -> Arc<MirrorStatus> {
+    let status = Arc::new(MirrorStatus::new(spec.clone()));
+    let checkpoint_path = PathBuf::from(persistence_path)
+        .join(&spec.local_topic)
+        .join(format!("mirror.{}.checkpoint", sanitize(&spec.source())));
+
+    let task_status = status.clone();
+    tokio::spawn(async move {
+        let mut mirror = Mirror { manager, spec, status: task_status, checkpoint_path, session_token: None };
+        mirror.run(cancel).await;
+    });
+
+    status
+}
+
+struct Mirror {
+    manager: StreamManager,
+    spec: MirrorSpec,
+    status: Arc<MirrorStatus>,
+    checkpoint_path: PathBuf,
+    session_token: Option<String>,
+}
+
+impl Mirror {
+    fn group(&self) -> String {
+        format!("nexo-mirror.{}", self.spec.local_topic)
+    }
+
+    async fn run(&mut self, cancel: CancellationToken) {
+        if let Err(e) = self.manager.create_topic(self.spec.local_topic.clone(), StreamCreateOptions::default()).await {
+            warn!("Mirror {}: cannot create local topic '{}': {}", self.spec.source(), self.spec.local_topic, e);
+            return;
+        }
+        let checkpoint = read_checkpoint(&self.checkpoint_path).await;
+        self.status.last_seq.store(checkpoint, Ordering::Relaxed);
+        info!("Mirror {} -> '{}' starting from checkpoint {}", self.spec.source(), self.spec.local_topic, checkpoint);
+
+        loop {
+            let result = tokio::select! {
+                _ = cancel.cancelled() => return,
+                result = self.session() => result,
+            };
+            self.status.connected.store(false, Ordering::Relaxed);
+            if let Err(e) = result {
+                warn!("Mirror {}: {}", self.spec.source(), e);
+                self.status.set_error(Some(e));
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+            }
+        }
+    }
+
+    /// One connection lifetime: join the remote group, then fetch/republish/ack until an error.
+    async fn session(&mut self) -> Result<(), String> {
+        let mut remote = RemoteClient::connect(&self.spec.remote_addr).await?;
+        let group = self.group();
+        let topic = self.spec.remote_topic.clone();
+
+        let join = remote.join(&group, &topic, self.session_token.as_deref()).await?;
+        self.session_token = Some(join.session_token.clone());
+        self.status.connected.store(true, Ordering::Relaxed);
+        self.status.set_error(None);
+
+        loop {
+            let messages = remote.fetch(&topic, &group, &join.consumer_id, join.generation, FETCH_LIMIT, FETCH_WAIT_MS).await?;
+            let Some(newest) = messages.last() else {
+                self.status.lag_ms.store(0, Ordering::Relaxed);
+                continue;
+            };
+            let newest_timestamp = newest.timestamp;
+
+            let checkpoint = self.status.last_seq.load(Ordering::Relaxed);
+            let mut last_seq = checkpoint;
+            for msg in &messages {
+                if msg.seq <= checkpoint {
+                    continue; // republished before a restart, only the ack was lost
+                }
+                self.manager.publish_with_timestamp(&self.spec.local_topic, msg.payload.clone(), msg.timestamp).await
+                    .map_err(|e| format!("local publish failed: {}", e))?;
+                self.status.mirrored.fetch_add(1, Ordering::Relaxed);
+                last_seq = msg.seq;
+            }
+
+            if last_seq > checkpoint {
+                self.status.last_seq.store(last_seq, Ordering::Relaxed);
+                write_checkpoint(&self.checkpoint_path, last_seq).await;
+            }
+
+            for msg in &messages {
+                remote.ack(&topic, &group, &join.consumer_id, join.generation, msg.seq).await?;
+            }
+
+            self.status.lag_ms.store(self.manager.clock().now_ms().saturating_sub(newest_timestamp), Ordering::Relaxed);
+        }
+    }
+}
+
+// ==========================================
+// REMOTE CLIENT (native protocol, one request in flight)
+// ==========================================
+
+struct JoinResult {
+    generation: u64,
+    consumer_id: String,
+    session_token: String,
+}
+
+struct RemoteMessage {
+    seq: u64,
+    timestamp: u64,
+    payload: Bytes,
+}
+
+struct RemoteClient {
+    socket: TcpStream,
+    next_id: u32,
+}
+
+impl RemoteClient {
+    async fn connect(addr: &str) -> Result<Self, String> {
+        let socket = TcpStream::connect(addr).await.map_err(|e| format!("connect to {} failed: {}", addr, e))?;
+        let _ = socket.set_nodelay(true);
+        Ok(Self { socket, next_id: 1 })
+    }
+
+    async fn request(&mut self, opcode: u8, payload: Bytes) -> Result<PayloadCursor, String> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let mut frame = BytesMut::with_capacity(FrameHeader::SIZE + payload.len());
+        frame.put_u8(TYPE_REQUEST);
+        frame.put_u8(opcode);
+        frame.put_u32(id);
+        frame.put_u32(payload.len() as u32);
+        frame.put_slice(&payload);
+        self.socket.write_all(&frame).await.map_err(|e| format!("write failed: {}", e))?;
+
+        let (header, body) = self.read_frame().await?;
+        let (status, body) = match header.frame_type {
+            TYPE_RESPONSE if header.id() == id => (header.meta, body),
+            TYPE_CHUNK_BEGIN if header.id() == id => (header.meta, self.read_chunks(id, body).await?),
+            _ => return Err(format!("unexpected frame (type 0x{:02X}, id {})", header.frame_type, header.id())),
+        };
+        let mut cursor = PayloadCursor::new(body);
+
+        if status == STATUS_ERR {
+            let _code = cursor.read_u16().map_err(|e| format!("{:?}", e))?;
+            let _retryable = cursor.read_u8().map_err(|e| format!("{:?}", e))?;
+            let message = cursor.read_string().unwrap_or_default();
+            return Err(format!("remote error: {}", message));
+        }
+        Ok(cursor)
+    }
+
+    async fn read_frame(&mut self) -> Result<(FrameHeader, Bytes), String> {
+        let mut header = [0u8; FrameHeader::SIZE];
+        self.socket.read_exact(&mut header).await.map_err(|e| format!("read failed: {}", e))?;
+        let header: FrameHeader = bytemuck::cast(header);
+        let len = header.payload_len() as usize;
+        let max = Config::global().server.max_payload_size;
+        if len > max {
+            return Err(format!("frame of {} bytes over MAX_PAYLOAD_SIZE ({})", len, max));
+        }
+        let mut body = vec![0u8; len];
+        self.socket.read_exact(&mut body).await.map_err(|e| format!("read failed: {}", e))?;
+        Ok((header, Bytes::from(body)))
+    }
+
+    /// The rest of a response too large for one frame, after its BEGIN `begin`
+    /// (see `protocol::chunk`). Grown as chunks arrive, not to the declared size.
+    async fn read_chunks(&mut self, id: u32, mut begin: Bytes) -> Result<Bytes, String> {
+        if begin.len() != 1 + 4 || begin.get_u8() != TYPE_RESPONSE {
+            return Err(format!("malformed chunked response {}", id));
+        }
+        let total_len = begin.get_u32() as usize;
+        let max = Config::global().server.max_message_size;
+        if total_len > max {
+            return Err(format!("chunked response of {} bytes over MAX_MESSAGE_SIZE ({})", total_len, max));
+        }
+        let mut data = BytesMut::new();
+        loop {
+            let (header, mut body) = self.read_frame().await?;
+            if header.id() != id || body.len() < 4 {
+                return Err(format!("unexpected frame in chunked response {} (type 0x{:02X}, id {})", id, header.frame_type, header.id()));
+            }
+            let checksum = body.get_u32();
+            match header.frame_type {
+                TYPE_CHUNK if crc32fast::hash(&body) == checksum && data.len() + body.len() <= total_len => {
+                    data.extend_from_slice(&body);
+                }
+                TYPE_CHUNK_END if data.len() == total_len && crc32fast::hash(&data) == checksum => {
+                    return Ok(data.freeze());
+                }
+                _ => return Err(format!("corrupted chunked response {}", id)),
+            }
+        }
+    }
+
+    async fn join(&mut self, group: &str, topic: &str, session_token: Option<&str>) -> Result<JoinResult, String> {
+        let mut buf = BytesMut::new();
+        put_string(&mut buf, group);
+        put_string(&mut buf, topic);
+        if let Some(token) = session_token {
+            put_string(&mut buf, token);
+        }
+        let mut cursor = self.request(OP_S_JOIN, buf.freeze()).await?;
+        let parse = |cursor: &mut PayloadCursor| -> Result<JoinResult, ParseError> {
+            let _ack_floor = cursor.read_u64()?;
+            let generation = cursor.read_u64()?;
+            let consumer_id = cursor.read_string()?;
+            let session_token = cursor.read_string()?;
+            Ok(JoinResult { generation, consumer_id, session_token })
+        };
+        parse(&mut cursor).map_err(|e| format!("malformed join response: {:?}", e))
+    }
+
+    async fn fetch(&mut self, topic: &str, group: &str, consumer_id: &str, generation: u64, limit: u32, wait_ms: u32) -> Result<Vec<RemoteMessage>, String> {
+        let mut buf = BytesMut::new();
+        put_string(&mut buf, topic);
+        put_string(&mut buf, group);
+        put_string(&mut buf, consumer_id);
+        buf.put_u64(generation);
+        buf.put_u32(limit);
+        buf.put_u32(wait_ms);
+        let mut cursor = self.request(OP_S_FETCH, buf.freeze()).await?;
+        let parse = |cursor: &mut PayloadCursor| -> Result<Vec<RemoteMessage>, ParseError> {
+            let count = cursor.read_u32()?;
+            let mut messages = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let seq = cursor.read_u64()?;
+                let timestamp = cursor.read_u64()?;
+                let payload = cursor.read_bytes()?;
+                messages.push(RemoteMessage { seq, timestamp, payload });
+            }
+            Ok(messages)
+        };
+        parse(&mut cursor).map_err(|e| format!("malformed fetch response: {:?}", e))
+    }
+
+    async fn ack(&mut self, topic: &str, group: &str, consumer_id: &str, generation: u64, seq: u64) -> Result<(), String> {
+        let mut buf = BytesMut::new();
+        put_string(&mut buf, topic);
+        put_string(&mut buf, group);
+        put_string(&mut buf, consumer_id);
+        buf.put_u64(generation);
+        buf.put_u64(seq);
+        self.request(OP_S_ACK, buf.freeze()).await.map(|_| ())
+    }
+}
+
+// ==========================================
+// HELPERS
+// ==========================================
+
+fn put_string(buf: &mut BytesMut, s: &str) {
+    buf.put_u32(s.len() as u32);
+    buf.put_slice(s.as_bytes());
+}
+
+fn sanitize(source: &str) -> String {
+    source.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+async fn read_checkpoint(path: &PathBuf) -> u64 {
+    tokio::fs::read_to_string(path).await.ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+async fn write_checkpoint(path: &PathBuf, seq: u64) {
+    let tmp = path.with_extension("checkpoint.tmp");
+    if let Err(e) = tokio::fs::write(&tmp, seq.to_string()).await {
+        warn!("Failed to write mirror checkpoint {:?}: {}", tmp, e);
+        return;
+    }
+    if let Err(e) = tokio::fs::rename(&tmp, path).await {
+        warn!("Failed to commit mirror checkpoint {:?}: {}", path, e);
+    }
+}
@@ -1,5 +1,10 @@
+pub mod backfill;
+pub mod checkpoint;
 pub mod domain;
+pub mod export;
+pub mod key_stats;
 pub mod manager;
+pub mod mirror;
 pub mod config;
 pub mod options;
 pub mod snapshot;
@@ -3,6 +3,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::labels::Labels;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RetentionOptions {
@@ -14,6 +17,38 @@ pub struct RetentionOptions {
 #[serde(rename_all = "camelCase")]
 pub struct StreamCreateOptions {
     pub retention: Option<RetentionOptions>,
+    pub priority_lanes: Option<bool>,
+    /// `fileSync` acks publishes only once they are fsynced
+    pub persistence: Option<PersistenceMode>,
+    /// Copy records parked after `max_deliveries` to a per-group DLQ topic
+    pub dead_letter: Option<bool>,
+    /// Cap of the RAM window (`0`: tail-only, only unflushed records stay in RAM)
+    pub max_memory_messages: Option<usize>,
+    /// Byte cap of the RAM window (`0`: none)
+    pub max_memory_bytes: Option<u64>,
+    /// Publishes held this long to be appended together (`0`: none)
+    pub linger_ms: Option<u64>,
+    /// `key=value` pairs for ownership and cost attribution
+    pub labels: Option<Labels>,
+}
+
+/// Settings `alter` changes on an existing topic; unset ones are kept.
+/// Priority lanes shape the consumer groups and are fixed at creation,
+/// hence no field (an unknown field is an error, not ignored).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AlterOptions {
+    pub retention: Option<RetentionOptions>,
+    /// Flush interval of the topic's writes (`0`: back to `STREAM_DEFAULT_FLUSH_MS`)
+    pub flush_ms: Option<u64>,
+    /// Publishes held this long to be appended together (`0`: none)
+    pub linger_ms: Option<u64>,
+    pub persistence: Option<PersistenceMode>,
+    pub dead_letter: Option<bool>,
+    pub max_memory_messages: Option<usize>,
+    pub max_memory_bytes: Option<u64>,
+    /// Replaces the whole set (`{}` clears it)
+    pub labels: Option<Labels>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,4 +56,7 @@ pub struct StreamCreateOptions {
 pub enum SeekTarget {
     Beginning,
     End,
+    /// Resume after this sequence (checkpoint import)
+    #[serde(skip)]
+    AckFloor(u64),
 }
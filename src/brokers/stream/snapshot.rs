@@ -5,12 +5,35 @@ use crate::brokers::stream::domain::topic::TopicConfig;
 
 pub struct StreamSnapshot {
     pub topics: Vec<TopicSnapshot>,
+    pub mirrors: Vec<MirrorSnapshot>,
+    pub backfills: Vec<BackfillSnapshot>,
+    pub read_cache: ReadCacheSnapshot,
 }
 
 pub struct TopicSnapshot {
     pub name: String,
+    /// High watermark: the newest sequence
     pub last_seq: u64,
+    /// Oldest sequence retention kept
+    pub head_seq: u64,
+    /// Newest sequence the storage actor flushed to disk
+    pub persisted_seq: u64,
+    /// Size on disk, as of the last retention pass plus appends since
+    pub bytes: u64,
+    /// Appends and record bytes per second over the last minute
+    pub publish_rate: f64,
+    pub byte_rate: f64,
+    /// Size of the RAM window
+    pub ram_messages: usize,
+    pub ram_bytes: u64,
+    /// Messages served from the RAM window and from disk since start
+    pub hot_reads: u64,
+    pub cold_reads: u64,
     pub groups: Vec<ConsumerGroupSnapshot>,
+    /// Message tracing (debug) enabled
+    pub traced: bool,
+    /// Restored from disk at startup (warm start)
+    pub recovered: bool,
     pub config: TopicConfig,
 }
 
@@ -19,3 +42,40 @@ pub struct ConsumerGroupSnapshot {
     pub ack_floor: u64,
     pub pending_count: usize,
 }
+
+pub struct MirrorSnapshot {
+    pub source: String,
+    pub local_topic: String,
+    pub connected: bool,
+    pub mirrored: u64,
+    pub last_seq: u64,
+    pub lag_ms: u64,
+    pub last_error: Option<String>,
+}
+
+pub struct BackfillSnapshot {
+    pub id: u64,
+    pub source: String,
+    pub target: String,
+    pub from_seq: u64,
+    pub to_seq: u64,
+    /// Next source sequence to read
+    pub next_seq: u64,
+    pub copied: u64,
+    /// Outside the time range or not matching the key filter
+    pub skipped: u64,
+    /// Not transformable, or rejected by the target schema
+    pub failed: u64,
+    /// `running`, `completed`, `cancelled` or `failed`
+    pub state: &'static str,
+    pub error: Option<String>,
+}
+
+/// Block cache for cold reads of sealed segments.
+pub struct ReadCacheSnapshot {
+    /// Maximum number of cached blocks (0 = disabled)
+    pub capacity: usize,
+    pub blocks: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
@@ -1,13 +1,18 @@
 //! Stream broker TCP surface: opcodes, command parsing, response wire
 //! encoding and the dispatch entry point `handle(...)`.
 
+use std::collections::HashMap;
+
 use bytes::{Bytes, BufMut, BytesMut};
+use tokio_util::sync::CancellationToken;
 
+use crate::brokers::names::{self, NameKind};
 use crate::brokers::pub_sub::ClientId;
 use crate::brokers::stream::domain::message::Message;
-use crate::brokers::stream::options::{SeekTarget, StreamCreateOptions};
+use crate::brokers::stream::manager::TopicMember;
+use crate::brokers::stream::options::{AlterOptions, SeekTarget, StreamCreateOptions};
 use crate::transport::tcp::protocol::cursor::PayloadCursor;
-use crate::transport::tcp::protocol::{ParseError, Response, ToWire};
+use crate::transport::tcp::protocol::{NexoError, ParseError, Response, ToWire};
 use crate::NexoEngine;
 
 // ==========================================
@@ -24,8 +29,15 @@ pub const OP_S_JOIN: u8 = 0x33;
 pub const OP_S_ACK: u8 = 0x34;
 pub const OP_S_EXISTS: u8 = 0x35;
 pub const OP_S_DELETE: u8 = 0x36;
+pub const OP_S_JOIN_MULTI: u8 = 0x37;
 pub const OP_S_SEEK: u8 = 0x38;
 pub const OP_S_LEAVE: u8 = 0x39;
+pub const OP_S_PUB_LANE: u8 = 0x3A;
+pub const OP_S_PAUSE: u8 = 0x3B;
+pub const OP_S_RESUME: u8 = 0x3C;
+pub const OP_S_NACK: u8 = 0x3D;
+pub const OP_S_FETCH_MULTI: u8 = 0x3E;
+pub const OP_S_ALTER: u8 = 0x3F;
 
 // ==========================================
 // COMMANDS
@@ -34,14 +46,21 @@ pub const OP_S_LEAVE: u8 = 0x39;
 #[derive(Debug)]
 enum StreamCommand {
     Create { topic: String, options: StreamCreateOptions },
+    Alter { topic: String, options: AlterOptions },
     Publish { topic: String, payload: Bytes },
+    PublishLane { topic: String, lane: u8, payload: Bytes },
     Fetch { topic: String, group: String, consumer_id: String, generation: u64, limit: u32, wait_ms: u32 },
-    Join { group: String, topic: String },
+    Join { group: String, topic: String, session_token: Option<String> },
+    JoinMulti { group: String, selectors: Vec<String>, session_tokens: HashMap<String, String> },
+    FetchMulti { group: String, members: Vec<TopicMember>, limit: u32, wait_ms: u32 },
     Ack { topic: String, group: String, consumer_id: String, generation: u64, seq: u64 },
+    Nack { topic: String, group: String, consumer_id: String, generation: u64, seq: u64, reason: String },
     Seek { topic: String, group: String, target: SeekTarget },
     Exists { topic: String },
     Delete { topic: String },
     Leave { topic: String, group: String, consumer_id: String, generation: u64 },
+    Pause { topic: String, group: String, consumer_id: String, generation: u64 },
+    Resume { topic: String, group: String, consumer_id: String, generation: u64 },
 }
 
 impl StreamCommand {
@@ -49,16 +68,30 @@ impl StreamCommand {
         match opcode {
             OP_S_CREATE => {
                 let topic = cursor.read_string()?;
+                names::check(NameKind::StreamTopic, &topic).map_err(ParseError::InvalidName)?;
                 let json_str = cursor.read_string()?;
                 let options: StreamCreateOptions = serde_json::from_str(&json_str)
                     .map_err(|e| ParseError::Invalid(format!("Invalid JSON config: {}", e)))?;
                 Ok(Self::Create { topic, options })
             }
+            OP_S_ALTER => {
+                let topic = cursor.read_string()?;
+                let json_str = cursor.read_string()?;
+                let options: AlterOptions = serde_json::from_str(&json_str)
+                    .map_err(|e| ParseError::Invalid(format!("Invalid JSON config: {}", e)))?;
+                Ok(Self::Alter { topic, options })
+            }
             OP_S_PUB => {
                 let topic = cursor.read_string()?;
                 let payload = cursor.read_remaining();
                 Ok(Self::Publish { topic, payload })
             }
+            OP_S_PUB_LANE => {
+                let topic = cursor.read_string()?;
+                let lane = cursor.read_u8()?;
+                let payload = cursor.read_remaining();
+                Ok(Self::PublishLane { topic, lane, payload })
+            }
             OP_S_FETCH => {
                 let topic = cursor.read_string()?;
                 let group = cursor.read_string()?;
@@ -71,7 +104,36 @@ impl StreamCommand {
             OP_S_JOIN => {
                 let group = cursor.read_string()?;
                 let topic = cursor.read_string()?;
-                Ok(Self::Join { group, topic })
+                // Optional trailing session token: resume a detached membership
+                let session_token = if cursor.len() > 0 { Some(cursor.read_string()?) } else { None };
+                Ok(Self::Join { group, topic, session_token })
+            }
+            OP_S_JOIN_MULTI => {
+                // [Group][Count:4]{[Selector]}[Tokens JSON, optional]
+                let group = cursor.read_string()?;
+                let count = cursor.read_u32()?;
+                let selectors = (0..count).map(|_| cursor.read_string()).collect::<Result<Vec<_>, _>>()?;
+                let session_tokens = if cursor.len() > 0 {
+                    let json_str = cursor.read_string()?;
+                    serde_json::from_str(&json_str)
+                        .map_err(|e| ParseError::Invalid(format!("Invalid session tokens: {}", e)))?
+                } else {
+                    HashMap::new()
+                };
+                Ok(Self::JoinMulti { group, selectors, session_tokens })
+            }
+            OP_S_FETCH_MULTI => {
+                // [Group][Limit:4][WaitMs:4][Count:4]{[Topic][ConsumerId][Generation:8]}
+                let group = cursor.read_string()?;
+                let limit = cursor.read_u32()?;
+                let wait_ms = cursor.read_u32()?;
+                let count = cursor.read_u32()?;
+                let members = (0..count).map(|_| Ok(TopicMember {
+                    topic: cursor.read_string()?,
+                    consumer_id: cursor.read_string()?,
+                    generation: cursor.read_u64()?,
+                })).collect::<Result<Vec<_>, ParseError>>()?;
+                Ok(Self::FetchMulti { group, members, limit, wait_ms })
             }
             OP_S_ACK => {
                 let topic = cursor.read_string()?;
@@ -81,6 +143,15 @@ impl StreamCommand {
                 let seq = cursor.read_u64()?;
                 Ok(Self::Ack { topic, group, consumer_id, generation, seq })
             }
+            OP_S_NACK => {
+                let topic = cursor.read_string()?;
+                let group = cursor.read_string()?;
+                let consumer_id = cursor.read_string()?;
+                let generation = cursor.read_u64()?;
+                let seq = cursor.read_u64()?;
+                let reason = cursor.read_string()?;
+                Ok(Self::Nack { topic, group, consumer_id, generation, seq, reason })
+            }
             OP_S_SEEK => {
                 let topic = cursor.read_string()?;
                 let group = cursor.read_string()?;
@@ -107,6 +178,17 @@ impl StreamCommand {
                 let generation = cursor.read_u64()?;
                 Ok(Self::Leave { topic, group, consumer_id, generation })
             }
+            OP_S_PAUSE | OP_S_RESUME => {
+                let topic = cursor.read_string()?;
+                let group = cursor.read_string()?;
+                let consumer_id = cursor.read_string()?;
+                let generation = cursor.read_u64()?;
+                if opcode == OP_S_PAUSE {
+                    Ok(Self::Pause { topic, group, consumer_id, generation })
+                } else {
+                    Ok(Self::Resume { topic, group, consumer_id, generation })
+                }
+            }
             _ => Err(ParseError::Invalid(format!("Unknown Stream opcode: 0x{:02X}", opcode))),
         }
     }
@@ -142,19 +224,55 @@ impl ToWire for FetchResponse {
     }
 }
 
+struct MultiFetchResponse { messages: Vec<(String, Message)> }
+
+impl ToWire for MultiFetchResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u32(self.messages.len() as u32);
+        for (topic, msg) in &self.messages {
+            buf.put_u32(topic.len() as u32);
+            buf.put_slice(topic.as_bytes());
+            buf.put_u64(msg.seq);
+            buf.put_u64(msg.timestamp);
+            buf.put_u32(msg.payload.len() as u32);
+            buf.put_slice(&msg.payload);
+        }
+        buf.freeze()
+    }
+}
+
 struct JoinGroupResponse {
     ack_floor: u64,
     generation: u64,
     consumer_id: String,
+    session_token: String,
 }
 
 impl ToWire for JoinGroupResponse {
     fn to_wire(&self) -> Bytes {
-        let mut buf = BytesMut::with_capacity(16 + 4 + self.consumer_id.len());
+        let mut buf = BytesMut::with_capacity(16 + 8 + self.consumer_id.len() + self.session_token.len());
         buf.put_u64(self.ack_floor);
         buf.put_u64(self.generation);
         buf.put_u32(self.consumer_id.len() as u32);
         buf.put_slice(self.consumer_id.as_bytes());
+        buf.put_u32(self.session_token.len() as u32);
+        buf.put_slice(self.session_token.as_bytes());
+        buf.freeze()
+    }
+}
+
+struct MultiJoinResponse { memberships: Vec<(String, JoinGroupResponse)> }
+
+impl ToWire for MultiJoinResponse {
+    fn to_wire(&self) -> Bytes {
+        let mut buf = BytesMut::new();
+        buf.put_u32(self.memberships.len() as u32);
+        for (topic, membership) in &self.memberships {
+            buf.put_u32(topic.len() as u32);
+            buf.put_slice(topic.as_bytes());
+            buf.put_slice(&membership.to_wire());
+        }
         buf.freeze()
     }
 }
@@ -168,10 +286,11 @@ pub async fn handle(
     cursor: &mut PayloadCursor,
     engine: &NexoEngine,
     client_id: &ClientId,
+    cancel: &CancellationToken,
 ) -> Response {
     let cmd = match StreamCommand::parse(opcode, cursor) {
         Ok(c) => c,
-        Err(e) => return Response::Error(e.to_string()),
+        Err(e) => return Response::Error(e.into()),
     };
 
     let stream = &engine.stream;
@@ -182,39 +301,93 @@ pub async fn handle(
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
+        StreamCommand::Alter { topic, options } => match stream.alter_topic(&topic, options).await {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
         StreamCommand::Publish { topic, payload } => match stream.publish(&topic, payload).await {
             Ok(seq) => Response::Data(PublishResponse { seq }.to_wire()),
             Err(e) => Response::Error(e),
         },
+        StreamCommand::PublishLane { topic, lane, payload } => match stream.publish_to_lane(&topic, payload, lane).await {
+            Ok(seq) => Response::Data(PublishResponse { seq }.to_wire()),
+            Err(e) => Response::Error(e),
+        },
         StreamCommand::Fetch { topic, group, consumer_id, generation, limit, wait_ms } => {
-            match stream.fetch(&group, &consumer_id, generation, limit as usize, &topic, wait_ms as u64).await {
+            match stream.fetch_cancellable(&group, &consumer_id, generation, limit as usize, &topic, wait_ms as u64, cancel).await {
                 Ok(messages) => Response::Data(FetchResponse { messages }.to_wire()),
                 Err(e) => Response::Error(e),
             }
         }
-        StreamCommand::Join { group, topic } => match stream.join_group(&group, &topic, &client).await {
-            Ok(result) => Response::Data(JoinGroupResponse {
-                ack_floor: result.ack_floor,
-                generation: result.generation,
-                consumer_id: result.consumer_id,
-            }.to_wire()),
+        StreamCommand::Join { group, topic, session_token } => match stream.join_group(&group, &topic, &client, session_token.as_deref()).await {
+            Ok(result) => {
+                engine.connections.add_group(&client, &topic, &group);
+                if let Some(displaced) = &result.displaced {
+                    engine.connections.kick(displaced);
+                }
+                Response::Data(JoinGroupResponse {
+                    ack_floor: result.ack_floor,
+                    generation: result.generation,
+                    consumer_id: result.consumer_id,
+                    session_token: result.session_token,
+                }.to_wire())
+            }
+            Err(e) => Response::Error(e),
+        },
+        StreamCommand::JoinMulti { group, selectors, session_tokens } => match stream.join_topics(&group, &selectors, &client, &session_tokens).await {
+            Ok(joined) => {
+                let memberships = joined.into_iter().map(|(topic, result)| {
+                    engine.connections.add_group(&client, &topic, &group);
+                    if let Some(displaced) = &result.displaced {
+                        engine.connections.kick(displaced);
+                    }
+                    (topic, JoinGroupResponse {
+                        ack_floor: result.ack_floor,
+                        generation: result.generation,
+                        consumer_id: result.consumer_id,
+                        session_token: result.session_token,
+                    })
+                }).collect();
+                Response::Data(MultiJoinResponse { memberships }.to_wire())
+            }
             Err(e) => Response::Error(e),
         },
+        StreamCommand::FetchMulti { group, members, limit, wait_ms } => {
+            match stream.fetch_topics_cancellable(&group, &members, limit as usize, wait_ms as u64, cancel).await {
+                Ok(messages) => Response::Data(MultiFetchResponse { messages }.to_wire()),
+                Err(e) => Response::Error(e),
+            }
+        }
         StreamCommand::Ack { topic, group, consumer_id, generation, seq } => match stream.ack(&group, &topic, &consumer_id, generation, seq).await {
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
+        StreamCommand::Nack { topic, group, consumer_id, generation, seq, reason } => match stream.nack(&group, &topic, &consumer_id, generation, seq, &reason).await {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
         StreamCommand::Seek { topic, group, target } => match stream.seek(&group, &topic, target).await {
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
         StreamCommand::Leave { topic, group, consumer_id, generation } => match stream.leave_group(&group, &topic, &consumer_id, generation).await {
+            Ok(_) => {
+                engine.connections.remove_group(&client, &topic, &group);
+                Response::Ok
+            }
+            Err(e) => Response::Error(e),
+        },
+        StreamCommand::Pause { topic, group, consumer_id, generation } => match stream.pause(&group, &topic, &consumer_id, generation).await {
+            Ok(_) => Response::Ok,
+            Err(e) => Response::Error(e),
+        },
+        StreamCommand::Resume { topic, group, consumer_id, generation } => match stream.resume(&group, &topic, &consumer_id, generation).await {
             Ok(_) => Response::Ok,
             Err(e) => Response::Error(e),
         },
         StreamCommand::Exists { topic } => match stream.exists(&topic).await {
             true => Response::Ok,
-            false => Response::Error("Stream not found".to_string()),
+            false => Response::Error(NexoError::not_found("Stream not found")),
         },
         StreamCommand::Delete { topic } => match stream.delete_topic(topic).await {
             Ok(_) => Response::Ok,
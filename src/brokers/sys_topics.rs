@@ -0,0 +1,94 @@
+//! System topics: broker internals published on pub/sub every
+//! `SYS_TOPICS_INTERVAL_MS`, so clients and dashboards can subscribe to
+//! `$nexo/sys/#` instead of polling snapshots.
+//!
+//! Each tick publishes one JSON message per topic: `connections`, `memory`,
+//! `queues/<queue>` and `streams/<topic>`. Messages are not retained (a new
+//! subscriber waits for the next tick) and deleted resources simply stop
+//! being published.
+
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::brokers::pub_sub::PubSubManager;
+use crate::brokers::queue::snapshot::QueueSnapshot;
+use crate::brokers::stream::snapshot::TopicSnapshot;
+
+pub const SYS_PREFIX: &str = "$nexo/sys/";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStats {
+    pub connections: usize,
+}
+
+/// Bytes held in RAM, as counted by the memory budget
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub queue: u64,
+    pub stream: u64,
+    pub pubsub: u64,
+    pub total: u64,
+    /// `None` without a memory budget
+    pub budget: Option<u64>,
+    pub pressure: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStats<'a> {
+    pub queue: &'a str,
+    pub pending: usize,
+    pub inflight: usize,
+    pub scheduled: usize,
+    pub dlq: usize,
+    pub bytes: usize,
+    pub consumers: usize,
+}
+
+impl<'a> From<&'a QueueSnapshot> for QueueStats<'a> {
+    fn from(q: &'a QueueSnapshot) -> Self {
+        Self {
+            queue: &q.name,
+            pending: q.pending,
+            inflight: q.inflight,
+            scheduled: q.scheduled,
+            dlq: q.dlq,
+            bytes: q.bytes,
+            consumers: q.consumers.len(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStats<'a> {
+    pub topic: &'a str,
+    /// High watermark
+    pub last_seq: u64,
+    pub head_seq: u64,
+    pub persisted_seq: u64,
+    pub bytes: u64,
+    pub publish_rate: f64,
+}
+
+impl<'a> From<&'a TopicSnapshot> for StreamStats<'a> {
+    fn from(t: &'a TopicSnapshot) -> Self {
+        Self {
+            topic: &t.name,
+            last_seq: t.last_seq,
+            head_seq: t.head_seq,
+            persisted_seq: t.persisted_seq,
+            bytes: t.bytes,
+            publish_rate: t.publish_rate,
+        }
+    }
+}
+
+/// Publish `stats` on `$nexo/sys/<path>`.
+pub fn publish<T: Serialize>(pubsub: &PubSubManager, path: &str, stats: &T) {
+    if let Ok(json) = serde_json::to_vec(stats) {
+        let _ = pubsub.publish(&format!("{}{}", SYS_PREFIX, path), Bytes::from(json), false, None);
+    }
+}
@@ -2,7 +2,13 @@ use crate::brokers::stream::config::SystemStreamConfig;
 use crate::brokers::queue::config::SystemQueueConfig;
 use crate::brokers::pub_sub::config::PubSubConfig;
 use crate::brokers::store::config::StoreConfig;
+use crate::brokers::activity::{ExcludeRule, IdleAction};
+use crate::brokers::durability::PersistenceMode;
+use crate::brokers::ephemeral;
+use crate::transport::tcp::admission::{self, AdmissionConfig};
+use crate::transport::tcp::listener::{self, ListenerConfig};
 use std::env;
+use std::path::PathBuf;
 use std::sync::OnceLock;
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
@@ -16,6 +22,11 @@ pub struct Config {
     pub queue: SystemQueueConfig,
     pub pubsub: PubSubConfig,
     pub stream: SystemStreamConfig,
+    pub idle: IdleConfig,
+    pub memory: MemoryConfig,
+    pub hooks: HooksConfig,
+    pub sys_topics: SysTopicsConfig,
+    pub events: EventsConfig,
 }
 
 impl Config {
@@ -25,13 +36,46 @@ impl Config {
 
     fn load() -> Self {
         dotenv::dotenv().ok();
-        Self {
+        let mut config = Self {
             server: ServerConfig::load(),
             store: StoreConfig::load(),
             queue: SystemQueueConfig::load(),
             pubsub: PubSubConfig::load(),
             stream: SystemStreamConfig::load(),
+            idle: IdleConfig::load(),
+            memory: MemoryConfig::load(),
+            hooks: HooksConfig::load(),
+            sys_topics: SysTopicsConfig::load(),
+            events: EventsConfig::load(),
+        };
+        if get_env::<bool>("NEXO_EPHEMERAL", "false") {
+            let dir = ephemeral::scratch_dir()
+                .unwrap_or_else(|e| panic!("Config error: NEXO_EPHEMERAL needs a scratch directory: {}", e));
+            config.make_ephemeral(dir);
         }
+        config
+    }
+
+    /// Run every broker out of `dir` (see `brokers::ephemeral`), and without
+    /// fsyncs: `fileSync` asked by config or per resource runs `fileAsync`.
+    pub fn make_ephemeral(&mut self, dir: PathBuf) {
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        self.store.persistence_path = path("store");
+        self.queue.persistence_path = path("queues");
+        if !self.queue.blob_path.is_empty() {
+            self.queue.blob_path = path("blobs");
+        }
+        self.pubsub.persistence_path = path("pubsub");
+        self.stream.persistence_path = path("streams");
+        self.idle.archive_path = path("archive");
+        self.server.export_dir = path("exports");
+        self.hooks.dead_letter_path = dir.join("hooks").join("dead-letter.jsonl").to_string_lossy().into_owned();
+
+        self.queue.persistence = PersistenceMode::FileAsync;
+        self.queue.ephemeral = true;
+        self.stream.persistence = PersistenceMode::FileAsync;
+        self.stream.ephemeral = true;
+        self.server.ephemeral = Some(dir);
     }
 }
 
@@ -42,30 +86,204 @@ impl Config {
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Listeners (`SERVER_LISTENERS`, default: one on host:port with every role;
+    /// plus `SERVER_UNIX_SOCKET` when set)
+    pub listeners: Vec<ListenerConfig>,
+    pub dashboard_host: String,
     pub dashboard_port: u16,
     pub log_level: String,
     pub dashboard_enabled: bool,
     pub max_payload_size: usize,
+    /// Largest message accepted as chunked frames (BEGIN/CHUNK/END)
+    pub max_message_size: usize,
+    /// Outbound responses and pushes above this size are sent as chunks
+    pub chunk_size: usize,
     pub channel_capacity_socket_write: usize,
+    /// Retired read buffers kept per connection for reuse (0 = no pooling)
+    pub read_pool_buffers: usize,
+    pub read_buffer_size: usize,
+    pub otlp_endpoint: String,
+    pub otlp_service_name: String,
+    pub otlp_filter: String,
+    /// Scratch directory holding all data in ephemeral mode (`NEXO_EPHEMERAL`)
+    pub ephemeral: Option<PathBuf>,
+    /// Queues (and, separately, stream topics) restored at once on warm start
+    pub recovery_concurrency: usize,
+    /// TCP connection limits and address lists, checked at accept
+    pub admission: AdmissionConfig,
+    /// Requests taking longer are logged with their connection and resource (0 = off)
+    pub slow_request_ms: u64,
+    /// Dashboard exports and imports read and write only below this directory
+    pub export_dir: String,
 }
 
 impl ServerConfig {
     fn load() -> Self {
         let env_mode = get_env::<String>("NEXO_ENV", "dev");
+        let host: String = get_env("SERVER_HOST", "0.0.0.0");
+        let port: u16 = get_env("SERVER_SOCKET_TCP_PORT", "7654");
+        let mut listeners = listener::parse_listeners(&get_env::<String>("SERVER_LISTENERS", ""), &format!("{}:{}", host, port))
+            .unwrap_or_else(|e| panic!("Config error: SERVER_LISTENERS must be valid: {}", e));
+        let unix = listener::unix_listener(
+            &get_env::<String>("SERVER_UNIX_SOCKET", ""),
+            &get_env::<String>("SERVER_UNIX_SOCKET_ROLES", ""),
+            &get_env::<String>("SERVER_UNIX_SOCKET_UIDS", ""),
+        ).unwrap_or_else(|e| panic!("Config error: SERVER_UNIX_SOCKET_* must be valid: {}", e));
+        listeners.extend(unix);
 
         Self {
-            host:           get_env("SERVER_HOST", "0.0.0.0"),
-            port:           get_env("SERVER_SOCKET_TCP_PORT", "7654"),
+            host,
+            port,
+            listeners,
+            dashboard_host: get_env("SERVER_DASHBOARD_HOST", "0.0.0.0"),
             dashboard_port: get_env("SERVER_DASHBOARD_HTTP_PORT", "8080"),
             log_level:      get_env("NEXO_LOG", "error"),
             dashboard_enabled: env_mode != "prod",
             max_payload_size: get_env("MAX_PAYLOAD_SIZE", "10485760"), // 10MB
+            max_message_size: get_env("MAX_MESSAGE_SIZE", "67108864"), // 64MB
+            chunk_size:     get_env("CHUNK_SIZE", "1048576"), // 1MB
             channel_capacity_socket_write: get_env("CHANNEL_CAPACITY_SOCKET_WRITE", "1024"),
+            read_pool_buffers: get_env("SOCKET_READ_POOL_BUFFERS", "16"),
+            read_buffer_size: get_env("SOCKET_READ_BUFFER_SIZE", "8192"),
+            otlp_endpoint:  get_env("NEXO_OTLP_ENDPOINT", ""), // empty = export disabled
+            otlp_service_name: get_env("NEXO_OTLP_SERVICE_NAME", "nexo"),
+            otlp_filter:    get_env("NEXO_OTLP_FILTER", "nexo=info"),
+            ephemeral:      None,
+            recovery_concurrency: get_env("RECOVERY_CONCURRENCY", "4"),
+            admission: AdmissionConfig {
+                max_connections: get_env("SERVER_MAX_CONNECTIONS", "0"), // 0 = unlimited
+                max_per_ip: get_env("SERVER_MAX_CONNECTIONS_PER_IP", "0"),
+                allow: admission::parse_nets(&get_env::<String>("SERVER_ALLOW_CIDRS", ""))
+                    .unwrap_or_else(|e| panic!("Config error: SERVER_ALLOW_CIDRS must be valid: {}", e)),
+                deny: admission::parse_nets(&get_env::<String>("SERVER_DENY_CIDRS", ""))
+                    .unwrap_or_else(|e| panic!("Config error: SERVER_DENY_CIDRS must be valid: {}", e)),
+            },
+            slow_request_ms: get_env("SLOW_REQUEST_MS", "1000"),
+            export_dir:     get_env("EXPORT_DIR", "./data/exports"),
         }
     }
 }
 
+// IDLE RESOURCES
+#[derive(Debug, Clone)]
+pub struct IdleConfig {
+    /// Days without publish or consume before a resource is idle (0 = policy disabled)
+    pub after_days: u64,
+    pub action: IdleAction,
+    /// Never reaped (`IDLE_EXCLUDE`, see `brokers::activity`)
+    pub exclude: Vec<ExcludeRule>,
+    pub archive_path: String,
+    /// How often activity stats are saved and the policy runs
+    pub check_interval_secs: u64,
+}
+
+impl IdleConfig {
+    fn load() -> Self {
+        Self {
+            after_days: get_env("IDLE_AFTER_DAYS", "0"),
+            action: get_env::<String>("IDLE_ACTION", "report").parse()
+                .unwrap_or_else(|e| panic!("Config error: IDLE_ACTION must be valid: {}", e)),
+            exclude: ExcludeRule::parse_list(&get_env::<String>("IDLE_EXCLUDE", "")),
+            archive_path: get_env("IDLE_ARCHIVE_PATH", "./data/archive"),
+            check_interval_secs: get_env("IDLE_CHECK_INTERVAL_SECS", "3600"),
+        }
+    }
+
+    pub fn after_ms(&self) -> u64 {
+        self.after_days.saturating_mul(24 * 60 * 60 * 1000)
+    }
 
+    pub fn is_excluded(&self, broker: &str, name: &str) -> bool {
+        self.exclude.iter().any(|rule| rule.matches(broker, name))
+    }
+}
+
+// MEMORY BUDGET
+#[derive(Debug, Clone)]
+pub struct MemoryConfig {
+    /// RAM the brokers may hold before publishes are shed (0 = no budget, see `brokers::memory`)
+    pub budget_bytes: u64,
+    /// Share of the budget above which non-durable publishes are refused
+    pub high_water_percent: u8,
+    pub sample_interval_ms: u64,
+}
+
+impl MemoryConfig {
+    fn load() -> Self {
+        Self {
+            budget_bytes: get_env("MEMORY_BUDGET_BYTES", "0"),
+            high_water_percent: get_env("MEMORY_HIGH_WATER_PERCENT", "80"),
+            sample_interval_ms: get_env("MEMORY_SAMPLE_INTERVAL_MS", "500"),
+        }
+    }
+}
+
+// LIFECYCLE HOOKS
+#[derive(Debug, Clone)]
+pub struct HooksConfig {
+    /// Endpoints every lifecycle event is POSTed to (empty = hooks off, see `brokers::hooks`)
+    pub urls: Vec<String>,
+    /// Events sent, by name (empty = all)
+    pub events: Vec<String>,
+    pub timeout_ms: u64,
+    /// Deliveries per event and URL, the first included
+    pub max_attempts: u32,
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Events waiting per URL; beyond, new ones go to the dead-letter file
+    pub buffer: usize,
+    pub dead_letter_path: String,
+}
+
+impl HooksConfig {
+    fn load() -> Self {
+        let list = |key: &str| get_env::<String>(key, "").split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect::<Vec<_>>();
+        let urls = list("HOOK_URLS");
+        if let Some(url) = urls.iter().find(|url| !(url.starts_with("http://") || url.starts_with("https://"))) {
+            panic!("Config error: HOOK_URLS must be http(s) URLs, got '{}'", url);
+        }
+        Self {
+            urls,
+            events: list("HOOK_EVENTS"),
+            timeout_ms: get_env("HOOK_TIMEOUT_MS", "5000"),
+            max_attempts: get_env("HOOK_MAX_ATTEMPTS", "5"),
+            backoff_ms: get_env("HOOK_BACKOFF_MS", "500"),
+            max_backoff_ms: get_env("HOOK_MAX_BACKOFF_MS", "30000"),
+            buffer: get_env("HOOK_BUFFER", "1024"),
+            dead_letter_path: get_env("HOOK_DEAD_LETTER_PATH", "./data/hooks/dead-letter.jsonl"),
+        }
+    }
+}
+
+// SYSTEM TOPICS
+#[derive(Debug, Clone)]
+pub struct SysTopicsConfig {
+    /// How often broker stats go to `$nexo/sys/...` (0 = off, see `brokers::sys_topics`)
+    pub interval_ms: u64,
+}
+
+impl SysTopicsConfig {
+    fn load() -> Self {
+        Self {
+            interval_ms: get_env("SYS_TOPICS_INTERVAL_MS", "0"),
+        }
+    }
+}
+
+// EVENT LOG
+#[derive(Debug, Clone)]
+pub struct EventsConfig {
+    /// Lifecycle events kept for the activity feed (see `brokers::events`)
+    pub capacity: usize,
+}
+
+impl EventsConfig {
+    fn load() -> Self {
+        Self {
+            capacity: get_env("EVENT_LOG_CAPACITY", "1000"),
+        }
+    }
+}
 
 // --- PRIVATE HELPER ---
 
@@ -3,14 +3,29 @@
 pub mod transport;
 pub mod brokers;
 pub mod config;
+pub mod telemetry;
 
+use bytes::Bytes;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use crate::brokers::activity::{ActivityTracker, IdleAction, IdleResource};
+use crate::brokers::clock::{self, SharedClock};
+use crate::brokers::health::{DiskProbe, HealthCheck, Readiness};
+use crate::brokers::events::EventBus;
+use crate::brokers::hooks::HookDispatcher;
+use crate::brokers::warm_start::WarmStart;
+use crate::brokers::intent::{Intent, IntentLog, PublishTarget};
+use crate::brokers::memory::{MemoryBudget, MemorySnapshot, MemoryUsage, Pressure};
+use crate::brokers::sys_topics::{self, ConnectionStats, MemoryStats, QueueStats, StreamStats};
 use crate::brokers::store::StoreManager;
 use crate::brokers::queue::QueueManager;
 use crate::brokers::pub_sub::PubSubManager;
 use crate::brokers::stream::StreamManager;
-use crate::config::Config;
+use crate::transport::tcp::admission::{Admission, AdmissionSnapshot};
+use crate::transport::tcp::registry::ConnectionRegistry;
+use crate::transport::tcp::protocol::NexoError;
+use crate::config::{Config, IdleConfig};
 
 // ========================================
 // ENGINE (The Singleton)
@@ -22,21 +37,373 @@ pub struct NexoEngine {
     pub queue: Arc<QueueManager>,
     pub pubsub: Arc<PubSubManager>,
     pub stream: Arc<StreamManager>,
+    pub intents: Arc<IntentLog>,
+    pub idle: Arc<IdleConfig>,
+    pub connections: Arc<ConnectionRegistry>,
+    /// TCP connection limits and address lists (see `transport::tcp::admission`)
+    pub admission: Arc<Admission>,
+    /// Engine-wide memory budget and load shedding (see `brokers::memory`)
+    pub memory: Arc<MemoryBudget>,
+    /// Lifecycle events: activity feed and hooks (see `brokers::events`)
+    pub events: Arc<EventBus>,
+    /// Data directories the readiness check writes to
+    pub disk: Arc<DiskProbe>,
+    /// Progress of the recovery this engine started with
+    pub warm_start: Arc<WarmStart>,
     pub start_time: Instant,
+    /// Time source of every broker (see `brokers::clock`)
+    pub clock: SharedClock,
+    /// Scratch directory of ephemeral mode (see `brokers::ephemeral`)
+    pub ephemeral: Option<PathBuf>,
+    /// Root of the files the dashboard exports to and imports from
+    pub export_dir: PathBuf,
+}
+
+/// Engine-wide facts, for read-only adapters (dashboard HTTP, ...).
+pub struct SystemSnapshot {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// `Some` when nothing outlives the process
+    pub ephemeral_dir: Option<PathBuf>,
+    /// `Some` when a memory budget is set
+    pub memory: Option<MemorySnapshot>,
+    pub admission: AdmissionSnapshot,
 }
 
 impl NexoEngine {
     pub async fn new(config: &Config) -> Self {
-        let pubsub = Arc::new(PubSubManager::new(Arc::new(config.pubsub.clone())));
-        
-        Self {
-            store: Arc::new(StoreManager::new(Arc::new(config.store.clone()))),
-            queue: Arc::new(QueueManager::new(Arc::new(config.queue.clone()))),
+        Self::with_warm_start(config, Arc::new(WarmStart::new(config.server.recovery_concurrency))).await
+    }
+
+    /// Start the engine, reporting recovery progress to `warm_start` (which
+    /// the caller may already serve, e.g. on the health check port).
+    pub async fn with_warm_start(config: &Config, warm_start: Arc<WarmStart>) -> Self {
+        let clock = clock::system();
+        let pubsub = Arc::new(PubSubManager::with_clock(Arc::new(config.pubsub.clone()), clock.clone()));
+        let queue = Arc::new(QueueManager::with_warm_start(Arc::new(config.queue.clone()), clock.clone(), &warm_start));
+        queue.attach_alerts(pubsub.clone());
+        let intent_path = Path::new(&config.store.persistence_path).join("intents.db");
+        let intents = IntentLog::open(&intent_path)
+            .unwrap_or_else(|e| panic!("Failed to open intent log {}: {}", intent_path.display(), e));
+
+        let stream = Arc::new(StreamManager::with_warm_start(Arc::new(config.stream.clone()), clock.clone(), &warm_start).await);
+        queue.attach_streams(stream.clone());
+
+        let memory = Arc::new(MemoryBudget::new(&config.memory));
+        queue.attach_budget(memory.clone());
+        stream.attach_budget(memory.clone());
+        pubsub.attach_budget(memory.clone());
+
+        let events = EventBus::new(config.events.capacity, HookDispatcher::start(&config.hooks, clock.clone()), clock.clone());
+        queue.attach_events(events.clone());
+        stream.attach_events(events.clone());
+
+        let engine = Self {
+            store: Arc::new(StoreManager::with_clock(Arc::new(config.store.clone()), clock.clone())),
+            queue,
             pubsub,
-            stream: Arc::new(StreamManager::new(Arc::new(config.stream.clone())).await),
+            stream,
+            intents: Arc::new(intents),
+            idle: Arc::new(config.idle.clone()),
+            connections: Arc::new(ConnectionRegistry::new()),
+            admission: Admission::new(config.server.admission.clone()),
+            memory,
+            events,
+            disk: Arc::new(DiskProbe::from_config(config)),
+            warm_start,
             start_time: Instant::now(),
+            clock,
+            ephemeral: config.server.ephemeral.clone(),
+            export_dir: PathBuf::from(&config.server.export_dir),
+        };
+        engine.recover_intents().await;
+        engine.warm_start.finish();
+        engine.spawn_idle_monitor();
+        engine.spawn_subscription_sweeper();
+        if engine.memory.is_enabled() {
+            engine.spawn_memory_sampler(Duration::from_millis(config.memory.sample_interval_ms.max(1)));
+        }
+        if config.sys_topics.interval_ms > 0 {
+            engine.spawn_sys_publisher(Duration::from_millis(config.sys_topics.interval_ms));
+        }
+        engine
+    }
+
+    /// Set a store key and publish an event about it as one operation (see
+    /// `brokers::intent`). Fails without applying anything if the stream
+    /// would reject the publish.
+    pub async fn store_and_publish(&self, key: String, value: Bytes, ttl: Option<u64>, target: PublishTarget, payload: Bytes) -> Result<(), NexoError> {
+        if let PublishTarget::Stream { topic } = &target {
+            self.stream.validate_publish(topic, &payload)?;
+        }
+        // Refused now rather than left pending and applied at the next start
+        self.memory.admit(matches!(target, PublishTarget::Stream { .. }))?;
+        let intent = Intent { key, value, ttl, target, payload, created_at: self.clock.now_ms() };
+        let id = self.intents.record(&intent).await?;
+        self.apply_intent(&intent).await?;
+        self.intents.resolve(id).await
+    }
+
+    async fn apply_intent(&self, intent: &Intent) -> Result<(), NexoError> {
+        // Store first: whoever reacts to the event must find the new value.
+        // A replayed intent whose TTL already ran out only owes the event.
+        if let Some(ttl) = intent.remaining_ttl(self.clock.now_ms()) {
+            self.store.map.set(intent.key.clone(), intent.value.clone(), ttl)?;
+        }
+        match &intent.target {
+            PublishTarget::PubSub { topic, retain } => {
+                self.pubsub.publish(topic, intent.payload.clone(), *retain, None)?;
+            }
+            PublishTarget::Stream { topic } => {
+                self.stream.publish_durable(topic, intent.payload.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply again the intents a crash left unresolved.
+    async fn recover_intents(&self) {
+        let pending = match self.intents.pending().await {
+            Ok(pending) => pending,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to read the intent log");
+                return;
+            }
+        };
+        for (id, intent) in pending {
+            match self.apply_intent(&intent).await {
+                Ok(()) => tracing::info!(key = %intent.key, "Recovered store-and-publish intent"),
+                Err(e) => tracing::warn!(key = %intent.key, error = %e, "Dropped store-and-publish intent that can no longer be applied"),
+            }
+            if let Err(e) = self.intents.resolve(id).await {
+                tracing::error!(error = %e, "Failed to resolve intent");
+            }
+        }
+    }
+
+    /// Live resources untouched for at least `min_idle_ms`, longest idle first.
+    pub fn idle_resources(&self, min_idle_ms: u64) -> Vec<IdleResource> {
+        let queues = self.queue.queue_names();
+        let topics = self.stream.topic_names();
+        let mut idle = Vec::new();
+        // Pub/sub roots have no registry: the tracker is the list of known roots
+        let mut collect = |broker: &'static str, tracker: &ActivityTracker, live: Option<&[String]>| {
+            for (name, activity, idle_ms) in tracker.idle_since(min_idle_ms) {
+                if live.is_some_and(|live| !live.contains(&name)) {
+                    continue;
+                }
+                idle.push(IdleResource {
+                    broker,
+                    excluded: self.idle.is_excluded(broker, &name),
+                    name,
+                    last_publish: activity.last_publish,
+                    last_consume: activity.last_consume,
+                    idle_ms,
+                });
+            }
+        };
+        collect("queue", self.queue.activity(), Some(&queues));
+        collect("stream", self.stream.activity(), Some(&topics));
+        collect("pubsub", self.pubsub.activity(), None);
+        idle.sort_by_key(|r| std::cmp::Reverse(r.idle_ms));
+        idle
+    }
+
+    /// Run the idle policy once: archive or delete every idle resource not
+    /// excluded. Returns what was reaped.
+    pub async fn reap_idle(&self) -> Vec<IdleResource> {
+        if self.idle.after_days == 0 || self.idle.action == IdleAction::Report {
+            return Vec::new();
+        }
+        let stamp = self.clock.now_ms();
+        let mut reaped = Vec::new();
+        for resource in self.idle_resources(self.idle.after_ms()) {
+            if resource.excluded {
+                continue;
+            }
+            let archive_to = (self.idle.action == IdleAction::Archive)
+                .then(|| Path::new(&self.idle.archive_path).join(resource.broker).join(format!("{}-{}", resource.name, stamp)));
+            let result = match (resource.broker, archive_to) {
+                ("queue", Some(target)) => self.queue.archive_queue(resource.name.clone(), &target).await,
+                ("queue", None) => self.queue.delete_queue(resource.name.clone()).await,
+                ("stream", Some(target)) => self.stream.archive_topic(resource.name.clone(), target).await,
+                ("stream", None) => self.stream.delete_topic(resource.name.clone()).await,
+                // Retained messages are all a root persists: archive drops them too
+                _ => {
+                    self.pubsub.clear_root(&resource.name);
+                    Ok(())
+                }
+            };
+            match result {
+                Ok(()) => {
+                    tracing::info!(broker = resource.broker, name = %resource.name, idle_ms = resource.idle_ms, action = ?self.idle.action, "Reaped idle resource");
+                    reaped.push(resource);
+                }
+                Err(e) => tracing::warn!(broker = resource.broker, name = %resource.name, error = %e, "Failed to reap idle resource"),
+            }
+        }
+        reaped
+    }
+
+    pub fn system_snapshot(&self) -> SystemSnapshot {
+        SystemSnapshot {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_secs: self.start_time.elapsed().as_secs(),
+            ephemeral_dir: self.ephemeral.clone(),
+            memory: self.memory.is_enabled().then(|| self.memory.snapshot()),
+            admission: self.admission.snapshot(),
+        }
+    }
+
+    /// Readiness for traffic (see `brokers::health`).
+    pub async fn readiness(&self) -> Readiness {
+        let mut stopped: Vec<String> = self.queue.stopped_writers().into_iter().map(|q| format!("queue '{}'", q)).collect();
+        if !self.stream.storage_running() {
+            stopped.push("stream storage".to_string());
+        }
+        let persistence = match stopped.is_empty() {
+            true => HealthCheck::new("persistence", true, "all writers running"),
+            false => HealthCheck::new("persistence", false, format!("stopped: {}", stopped.join(", "))),
+        };
+
+        let memory = match self.memory.is_enabled() {
+            false => HealthCheck::new("memory", true, "no budget"),
+            true => {
+                let m = self.memory.snapshot();
+                let detail = format!("{} of {} bytes high water, pressure {}", m.usage.total(), m.high_water, m.pressure.as_str());
+                HealthCheck::new("memory", m.pressure == Pressure::Normal, detail)
+            }
+        };
+
+        Readiness::from_checks(vec![
+            Readiness::recovery(&self.warm_start.snapshot()),
+            persistence,
+            self.disk.check().await,
+            memory,
+        ])
+    }
+
+    /// Sample what the brokers hold in RAM and update the memory pressure
+    /// level; entering `High` spills the stream RAM windows to disk.
+    pub async fn sample_memory(&self) -> Pressure {
+        let usage = MemoryUsage {
+            queue: self.queue.memory_usage(),
+            stream: self.stream.memory_usage(),
+            pubsub: self.pubsub.memory_usage(),
+        };
+        let previous = self.memory.pressure();
+        let pressure = self.memory.record(usage);
+        if pressure != previous {
+            tracing::warn!(pressure = pressure.as_str(), used = usage.total(), "Memory pressure changed");
         }
+        if pressure >= Pressure::High && usage.stream > 0 {
+            match self.stream.spill().await {
+                Ok(freed) => {
+                    tracing::info!(freed, "Spilled stream RAM windows to disk");
+                    return self.memory.record(MemoryUsage { stream: self.stream.memory_usage(), ..usage });
+                }
+                Err(e) => tracing::error!(error = %e.message, "Stream spill failed"),
+            }
+        }
+        pressure
     }
 
+    /// Publish connection, memory, queue and stream stats on the system
+    /// topics (see `brokers::sys_topics`).
+    pub async fn publish_sys_stats(&self) {
+        sys_topics::publish(&self.pubsub, "connections", &ConnectionStats { connections: self.connections.len() });
+
+        let usage = MemoryUsage {
+            queue: self.queue.memory_usage(),
+            stream: self.stream.memory_usage(),
+            pubsub: self.pubsub.memory_usage(),
+        };
+        let budget = self.memory.is_enabled().then(|| self.memory.snapshot());
+        sys_topics::publish(&self.pubsub, "memory", &MemoryStats {
+            queue: usage.queue,
+            stream: usage.stream,
+            pubsub: usage.pubsub,
+            total: usage.total(),
+            budget: budget.as_ref().map(|m| m.limit),
+            pressure: budget.as_ref().map(|m| m.pressure.as_str()),
+        });
 
+        for queue in self.queue.get_snapshot().await {
+            sys_topics::publish(&self.pubsub, &format!("queues/{}", queue.name), &QueueStats::from(&queue));
+        }
+        for topic in self.stream.get_snapshot().await.topics {
+            sys_topics::publish(&self.pubsub, &format!("streams/{}", topic.name), &StreamStats::from(&topic));
+        }
+    }
+
+    /// Save the access stats of every broker.
+    pub fn save_activity(&self) {
+        self.queue.activity().save();
+        self.stream.activity().save();
+        self.pubsub.activity().save();
+    }
+
+    /// Graceful stop: queue writers drain and fsync, stream segments and
+    /// group offsets are flushed and fsynced, access stats are saved.
+    pub async fn close(&self) {
+        self.queue.close().await;
+        if let Err(e) = self.stream.close().await {
+            tracing::error!(error = %e.message, "Stream flush on close failed");
+        }
+        self.save_activity();
+    }
+
+    fn spawn_memory_sampler(&self, period: Duration) {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                engine.sample_memory().await;
+            }
+        });
+    }
+
+    fn spawn_sys_publisher(&self, period: Duration) {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                engine.publish_sys_stats().await;
+            }
+        });
+    }
+
+    /// Drop the pub/sub subscriptions whose idle TTL ran out.
+    fn spawn_subscription_sweeper(&self) {
+        let engine = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                for (client_id, pattern) in engine.pubsub.expire_idle() {
+                    engine.connections.remove_subscription(&client_id.0, &pattern);
+                }
+            }
+        });
+    }
+
+    fn spawn_idle_monitor(&self) {
+        let engine = self.clone();
+        let period = Duration::from_secs(self.idle.check_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                engine.save_activity();
+                engine.reap_idle().await;
+            }
+        });
+    }
 }
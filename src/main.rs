@@ -1,9 +1,15 @@
 #![allow(dead_code, unused_imports, unused_variables)]
 
+use nexo::brokers::data_lock::DataLock;
+use nexo::brokers::ephemeral;
+use nexo::brokers::integrity::{self, DataDirs};
+use nexo::brokers::warm_start::WarmStart;
 use nexo::config::Config;
 use nexo::NexoEngine;
+use nexo::telemetry;
 use nexo::transport::{tcp, http};
-use tokio::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
 
 // ========================================
 // MAIN ENTRY POINT
@@ -13,56 +19,103 @@ use tokio::net::TcpListener;
 async fn main() {
     let config = Config::global();
 
-    // Init Tracing (logging)
-    tracing_subscriber::fmt()
-        .with_env_filter(&config.server.log_level)
-        .compact()
-        .with_target(false)
-        .without_time()
-        .init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let dirs = DataDirs::from_config(config);
+    let takeover = args.iter().any(|a| a == "--force-takeover");
+    let lock_data_dirs = || DataLock::acquire(&dirs, takeover).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+
+    // `--check [--repair]`: verify persisted data and exit without serving
+    if args.iter().any(|a| a == "--check") {
+        let repair = args.iter().any(|a| a == "--repair");
+        // A read-only scan may run next to the server, a repair may not
+        let lock = repair.then(lock_data_dirs);
+        let report = integrity::check(&dirs, repair);
+        println!("{}", serde_json::to_string_pretty(&report).expect("report serializes"));
+        if let Some(lock) = lock {
+            lock.release();
+        }
+        if let Some(dir) = &config.server.ephemeral {
+            ephemeral::remove(dir);
+        }
+        std::process::exit(if report.ok { 0 } else { 1 });
+    }
+
+    // Init Tracing (logging + optional OTLP export)
+    let _tracer_provider = telemetry::init(&config.server);
+    if let Some(dir) = &config.server.ephemeral {
+        tracing::warn!("Ephemeral mode: data lives in {:?} and is deleted on shutdown", dir);
+    }
 
     tracing::debug!("--- CONFIGURATION LOADED ---");
     tracing::debug!("{:#?}", config);
     tracing::debug!("----------------------------");
 
-    let engine = NexoEngine::new(&config).await;
-    
-    let addr = format!("{}:{}", config.server.host, config.server.port);
+    // One process per data directory: a second one would corrupt it
+    let data_lock = lock_data_dirs();
 
-    let engine_clone_for_dashboard = engine.clone();
+    // The dashboard port answers health probes while warm start runs
+    let (engine_for_dashboard, dashboard_engine) = tokio::sync::oneshot::channel();
+    let warm_start = Arc::new(WarmStart::new(config.server.recovery_concurrency));
+    warm_start.spawn_reporter(Duration::from_secs(1));
 
     if config.server.dashboard_enabled {
         tracing::info!(port = config.server.dashboard_port, "📊 Dashboard enabled");
-        tokio::spawn(async move {
-            http::router::start_http_server(engine_clone_for_dashboard, config.server.dashboard_port).await;
-        });
     } else {
-        tracing::info!("🚫 Dashboard disabled by config");
+        tracing::info!(port = config.server.dashboard_port, "🚫 Dashboard disabled by config, serving health checks only");
     }
+    let progress = warm_start.clone();
+    tokio::spawn(async move {
+        let server = &config.server;
+        http::router::start_http_server(dashboard_engine, progress, &server.dashboard_host, server.dashboard_port, server.dashboard_enabled).await;
+    });
 
-    tracing::info!(host = %config.server.host, port = %config.server.port, "🚀 Nexo Server Starting...");
-
-    let listener = TcpListener::bind(&addr)
-        .await
-        .expect("Failed to bind");
-
-    tracing::info!(address = %addr, "Nexo listening");
+    let engine = NexoEngine::with_warm_start(&config, warm_start).await;
+    let _ = engine_for_dashboard.send(engine.clone());
 
-    loop {
-        let (socket, client_addr) = listener
-            .accept()
-            .await
-            .expect("Failed to accept connection");
+    tracing::info!(listeners = config.server.listeners.len(), "🚀 Nexo Server Starting...");
 
-        let engine_clone = engine.clone();
+    let listeners: Vec<_> = config.server.listeners.iter()
+        .map(|listener| tokio::spawn(tcp::listener::serve(listener.clone(), engine.clone())))
+        .collect();
 
-        tracing::info!(client = %client_addr, "New connection accepted");
-
-        tokio::spawn(async move {
-            if let Err(e) = tcp::connection::handle_connection(socket, engine_clone).await {
-                tracing::error!(client = %client_addr, error = %e, "Connection error");
+    // Listeners run until the process exits: one stopping (e.g. a failed bind) takes the server down
+    tokio::select! {
+        (result, _, _) = futures_util::future::select_all(listeners) => {
+            if let Err(e) = result {
+                tracing::error!(error = %e, "Listener stopped");
+            }
+            engine.close().await;
+            if let Some(dir) = &config.server.ephemeral {
+                ephemeral::remove(dir);
+            }
+            std::process::exit(1);
+        }
+        _ = shutdown_signal() => {
+            tracing::info!("🛑 Shutting down, flushing brokers...");
+            engine.close().await;
+            data_lock.release();
+            if let Some(dir) = &config.server.ephemeral {
+                ephemeral::remove(dir);
             }
-            tracing::debug!(client = %client_addr, "Connection closed");
-        });
+            std::process::exit(0);
+        }
+    }
+}
+
+/// Ctrl-C, or SIGTERM (`docker stop`) on Unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("SIGTERM handler installs");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
     }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
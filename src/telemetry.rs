@@ -0,0 +1,72 @@
+//! Telemetry: log output plus optional OTLP trace export.
+//!
+//! Logs always go to stdout, filtered by `NEXO_LOG`. When `NEXO_OTLP_ENDPOINT`
+//! is set, spans matching `NEXO_OTLP_FILTER` are also batched and exported
+//! over OTLP/gRPC.
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::ServerConfig;
+
+/// Install the global subscriber. Keep the returned provider alive for the
+/// lifetime of the process: dropping it stops the exporter.
+pub fn init(config: &ServerConfig) -> Option<SdkTracerProvider> {
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .compact()
+        .with_target(false)
+        .without_time()
+        .with_filter(EnvFilter::new(&config.log_level));
+
+    let provider = build_provider(config);
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer("nexo"))
+            .with_filter(EnvFilter::new(&config.otlp_filter))
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    if provider.is_some() {
+        tracing::info!(endpoint = %config.otlp_endpoint, "OTLP trace export enabled");
+    }
+
+    provider
+}
+
+fn build_provider(config: &ServerConfig) -> Option<SdkTracerProvider> {
+    if config.otlp_endpoint.is_empty() {
+        return None;
+    }
+
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.otlp_endpoint)
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("Failed to build OTLP exporter for {}: {}", config.otlp_endpoint, e);
+            return None;
+        }
+    };
+
+    let resource = Resource::builder()
+        .with_service_name(config.otlp_service_name.clone())
+        .build();
+
+    Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource)
+            .build(),
+    )
+}
@@ -0,0 +1,90 @@
+//! Connection registry HTTP surface.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::Router;
+use serde::Serialize;
+
+use crate::transport::tcp::registry::ConnectionSnapshot;
+use crate::NexoEngine;
+
+// ==========================================
+// DTOs
+// ==========================================
+
+#[derive(Serialize)]
+pub struct ConnectionSummary {
+    pub id: String,
+    pub peer_addr: String,
+    pub connected_at: String,
+    pub frames_in: u64,
+    pub frames_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Requests answered with an error
+    pub errors: u64,
+    /// Slowest request so far, long polls aside
+    pub max_latency_ms: f64,
+    /// Requests over `SLOW_REQUEST_MS`
+    pub slow_requests: u64,
+    /// Read buffers reused from the connection's pool
+    pub read_buffers_reused: u64,
+    /// Read buffers allocated because none was free
+    pub read_buffers_allocated: u64,
+    pub subscriptions: Vec<String>,
+    pub groups: Vec<String>,
+    pub semaphores: Vec<String>,
+}
+
+impl From<ConnectionSnapshot> for ConnectionSummary {
+    fn from(c: ConnectionSnapshot) -> Self {
+        Self {
+            id: c.id,
+            peer_addr: c.peer_addr,
+            connected_at: chrono::DateTime::<chrono::Utc>::from(c.connected_at).to_rfc3339(),
+            frames_in: c.frames_in,
+            frames_out: c.frames_out,
+            bytes_in: c.bytes_in,
+            bytes_out: c.bytes_out,
+            errors: c.errors,
+            max_latency_ms: c.max_latency_us as f64 / 1000.0,
+            slow_requests: c.slow_requests,
+            read_buffers_reused: c.read_pool.hits,
+            read_buffers_allocated: c.read_pool.misses,
+            subscriptions: c.subscriptions,
+            groups: c.groups,
+            semaphores: c.semaphores,
+        }
+    }
+}
+
+// ==========================================
+// HANDLERS
+// ==========================================
+
+async fn get_connections(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    let connections: Vec<ConnectionSummary> = engine.connections.list().into_iter().map(Into::into).collect();
+    axum::Json(connections)
+}
+
+async fn kick_connection(
+    State(engine): State<NexoEngine>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match engine.connections.kick(&id) {
+        true => StatusCode::NO_CONTENT.into_response(),
+        false => (StatusCode::NOT_FOUND, "Connection not found").into_response(),
+    }
+}
+
+// ==========================================
+// ROUTES
+// ==========================================
+
+pub fn routes() -> Router<NexoEngine> {
+    Router::new()
+        .route("/api/connections", get(get_connections))
+        .route("/api/connections/{id}/kick", post(kick_connection))
+}
@@ -0,0 +1,43 @@
+//! Activity feed HTTP surface: the engine's recent lifecycle events, newest
+//! first. The dashboard polls with `after` = the highest id it has seen.
+
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::NexoEngine;
+
+pub const DEFAULT_LIMIT: usize = 100;
+
+// ==========================================
+// DTOs
+// ==========================================
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Only events with a higher id
+    pub after: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+// ==========================================
+// HANDLERS
+// ==========================================
+
+async fn get_events(
+    State(engine): State<NexoEngine>,
+    Query(query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    Json(engine.events.recent(query.after.unwrap_or(0), query.limit.unwrap_or(DEFAULT_LIMIT)))
+}
+
+// ==========================================
+// ROUTES
+// ==========================================
+
+pub fn routes() -> Router<NexoEngine> {
+    Router::new()
+        .route("/api/events", get(get_events))
+}
@@ -0,0 +1,47 @@
+//! Where dashboard exports and imports may go: a relative path below
+//! `EXPORT_DIR`. The dashboard has no authentication, so a client must not
+//! choose where on the server's filesystem a file is written or read.
+
+use std::path::{Component, Path, PathBuf};
+
+/// `name` under `dir`, refused when absolute or climbing out with `..`.
+pub fn resolve(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    let mut parts = 0;
+    for component in relative.components() {
+        match component {
+            Component::Normal(_) => parts += 1,
+            Component::CurDir => {}
+            _ => return Err(format!("Invalid path '{}': must be relative to the export directory, without '..'", name)),
+        }
+    }
+    if parts == 0 {
+        return Err("Invalid path: a file name is required".to_string());
+    }
+    Ok(dir.join(relative))
+}
+
+/// `resolve`, creating the directories an export will be written to.
+pub async fn resolve_for_write(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = resolve(dir, name)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await
+            .map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_stay_below_the_export_dir() {
+        let dir = Path::new("/data/exports");
+        assert_eq!(resolve(dir, "orders.json").unwrap(), Path::new("/data/exports/orders.json"));
+        assert_eq!(resolve(dir, "./daily/orders.json").unwrap(), Path::new("/data/exports/daily/orders.json"));
+        for refused in ["/etc/passwd", "../orders.json", "daily/../../etc/passwd", "", "."] {
+            assert!(resolve(dir, refused).is_err(), "{}", refused);
+        }
+    }
+}
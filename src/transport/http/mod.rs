@@ -1,3 +1,7 @@
 pub mod router;
 pub mod assets;
-pub mod payload;
+pub mod connections;
+pub mod trace;
+pub mod events;
+pub mod export_path;
+pub mod system;
@@ -1,22 +1,62 @@
+use std::future::IntoFuture;
+use std::sync::Arc;
+
 use axum::Router;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
 use tower_http::compression::CompressionLayer;
+use crate::brokers::warm_start::WarmStart;
 use crate::NexoEngine;
 use crate::transport::http::assets::static_handler;
 
-pub async fn start_http_server(engine: NexoEngine, port: u16) {
+/// Bind the dashboard port right away and serve it once `engine` arrives.
+/// Until then only `/healthz`, `/readyz` (503) and `/api/recovery` answer,
+/// so orchestrators can tell a recovering server from a dead one and
+/// operators can follow `warm_start`. With the dashboard disabled the port
+/// keeps serving just the health checks.
+pub async fn start_http_server(engine: oneshot::Receiver<NexoEngine>, warm_start: Arc<WarmStart>, host: &str, port: u16, dashboard: bool) {
+    let addr = format!("{}:{}", host, port);
+    let listener = std::net::TcpListener::bind(&addr).expect("Failed to bind dashboard port");
+    listener.set_nonblocking(true).expect("Failed to configure dashboard socket");
+    let startup_listener = listener.try_clone()
+        .and_then(TcpListener::from_std)
+        .expect("Failed to share dashboard socket");
+
+    let startup = crate::transport::http::system::startup_routes(warm_start);
+    let engine = tokio::select! {
+        engine = engine => match engine {
+            Ok(engine) => engine,
+            Err(_) => return,
+        },
+        result = axum::serve(startup_listener, startup).into_future() => {
+            tracing::error!(error = ?result.err(), "Dashboard stopped during warm start");
+            return;
+        }
+    };
+
+    if !dashboard {
+        let app = crate::transport::http::system::health_routes().with_state(engine);
+        let listener = TcpListener::from_std(listener).expect("Failed to bind health check port");
+        axum::serve(listener, app).await.expect("Failed to start health check server");
+        return;
+    }
+
     let app = Router::new()
         .merge(crate::brokers::store::http::routes())
         .merge(crate::brokers::queue::http::routes())
         .merge(crate::brokers::stream::http::routes())
         .merge(crate::brokers::pub_sub::http::routes())
+        .merge(crate::transport::http::connections::routes())
+        .merge(crate::transport::http::trace::routes())
+        .merge(crate::transport::http::events::routes())
+        .merge(crate::transport::http::system::routes())
         .layer(CompressionLayer::new())
         .fallback(static_handler)
         .with_state(engine);
 
-    let addr = format!("0.0.0.0:{}", port);
     tracing::info!("🌐 Dashboard available at http://{}", addr);
 
-    let listener = tokio::net::TcpListener::bind(&addr).await.expect("Failed to bind dashboard port");
+    let listener = TcpListener::from_std(listener).expect("Failed to bind dashboard port");
 
     axum::serve(listener, app).await.expect("Failed to start dashboard server");
 }
@@ -0,0 +1,151 @@
+//! Engine-wide HTTP surface.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+
+use crate::brokers::health::Readiness;
+use crate::brokers::memory::MemorySnapshot;
+use crate::brokers::warm_start::WarmStart;
+use crate::transport::tcp::admission::AdmissionSnapshot;
+use crate::{NexoEngine, SystemSnapshot};
+
+// ==========================================
+// DTOs
+// ==========================================
+
+#[derive(Serialize)]
+pub struct SystemSummary {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// Nothing is kept across restarts (`NEXO_EPHEMERAL`)
+    pub ephemeral: bool,
+    pub ephemeral_dir: Option<String>,
+    /// Absent without a memory budget (`MEMORY_BUDGET_BYTES`)
+    pub memory: Option<MemorySummary>,
+    pub connections: ConnectionsSummary,
+}
+
+#[derive(Serialize)]
+pub struct MemorySummary {
+    pub limit_bytes: u64,
+    pub high_water_bytes: u64,
+    pub used_bytes: u64,
+    pub queue_bytes: u64,
+    pub stream_bytes: u64,
+    pub pubsub_bytes: u64,
+    /// `normal`, `high` (non-durable publishes shed) or `critical` (all shed)
+    pub pressure: &'static str,
+    pub rejections: u64,
+}
+
+impl From<MemorySnapshot> for MemorySummary {
+    fn from(m: MemorySnapshot) -> Self {
+        Self {
+            limit_bytes: m.limit,
+            high_water_bytes: m.high_water,
+            used_bytes: m.usage.total(),
+            queue_bytes: m.usage.queue,
+            stream_bytes: m.usage.stream,
+            pubsub_bytes: m.usage.pubsub,
+            pressure: m.pressure.as_str(),
+            rejections: m.rejections,
+        }
+    }
+}
+
+/// TCP connections and those refused at accept (see `SERVER_MAX_CONNECTIONS`)
+#[derive(Serialize)]
+pub struct ConnectionsSummary {
+    pub open: usize,
+    /// Over the server or per-address limit
+    pub rejected_limit: u64,
+    /// By `SERVER_ALLOW_CIDRS` / `SERVER_DENY_CIDRS`
+    pub rejected_denied: u64,
+}
+
+impl From<AdmissionSnapshot> for ConnectionsSummary {
+    fn from(a: AdmissionSnapshot) -> Self {
+        Self { open: a.connections, rejected_limit: a.rejected_limit, rejected_denied: a.rejected_denied }
+    }
+}
+
+impl From<SystemSnapshot> for SystemSummary {
+    fn from(s: SystemSnapshot) -> Self {
+        Self {
+            version: s.version,
+            uptime_secs: s.uptime_secs,
+            ephemeral: s.ephemeral_dir.is_some(),
+            ephemeral_dir: s.ephemeral_dir.map(|dir| dir.display().to_string()),
+            memory: s.memory.map(MemorySummary::from),
+            connections: ConnectionsSummary::from(s.admission),
+        }
+    }
+}
+
+// ==========================================
+// HANDLERS
+// ==========================================
+
+async fn get_system(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    axum::Json(SystemSummary::from(engine.system_snapshot()))
+}
+
+/// Liveness: the process answers.
+async fn get_healthz() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Warm-start progress; final counts once recovery is over.
+async fn get_recovery(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    Json(engine.warm_start.snapshot())
+}
+
+async fn get_readyz(State(engine): State<NexoEngine>) -> impl IntoResponse {
+    readiness_response(engine.readiness().await)
+}
+
+/// 200 when ready, 503 otherwise; the body lists every check either way.
+fn readiness_response(readiness: Readiness) -> Response {
+    let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(readiness)).into_response()
+}
+
+// ==========================================
+// ROUTES
+// ==========================================
+
+pub fn routes() -> Router<NexoEngine> {
+    Router::new()
+        .route("/api/system", get(get_system))
+        .route("/api/recovery", get(get_recovery))
+        .merge(health_routes())
+}
+
+/// Liveness and readiness probes; all the port serves with the dashboard disabled.
+pub fn health_routes() -> Router<NexoEngine> {
+    Router::new()
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+}
+
+/// Served during warm start, before the engine exists: the process is alive
+/// but not ready. Connections are closed after each response, so no probe
+/// stays on this router once the full one takes over.
+pub fn startup_routes(warm_start: Arc<WarmStart>) -> Router {
+    let progress = warm_start.clone();
+    Router::new()
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(move || async move { readiness_response(Readiness::recovering(&warm_start.snapshot())) }))
+        .route("/api/recovery", get(move || async move { Json(progress.snapshot()) }))
+        .fallback(|| async { (StatusCode::SERVICE_UNAVAILABLE, "Nexo is starting").into_response() })
+        .layer(axum::middleware::map_response(|mut res: Response| async move {
+            res.headers_mut().insert(header::CONNECTION, HeaderValue::from_static("close"));
+            res
+        }))
+}
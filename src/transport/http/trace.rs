@@ -0,0 +1,67 @@
+//! Message tracing HTTP surface: per queue/topic toggles and lookup by message id.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::NexoEngine;
+
+// ==========================================
+// DTOs
+// ==========================================
+
+#[derive(Deserialize)]
+pub struct TraceToggle {
+    pub enabled: bool,
+}
+
+// ==========================================
+// HANDLERS
+// ==========================================
+
+/// Queue messages are traced by UUID, stream messages by `<topic>:<seq>`.
+async fn get_trace(
+    State(engine): State<NexoEngine>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match engine.queue.trace(&id).or_else(|| engine.stream.trace(&id)) {
+        Some(trace) => Json(trace).into_response(),
+        None => (StatusCode::NOT_FOUND, "No trace recorded for this message").into_response(),
+    }
+}
+
+async fn toggle_queue_trace(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<TraceToggle>,
+) -> impl IntoResponse {
+    match engine.queue.set_tracing(&name, body.enabled) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
+    }
+}
+
+async fn toggle_stream_trace(
+    State(engine): State<NexoEngine>,
+    Path(name): Path<String>,
+    Json(body): Json<TraceToggle>,
+) -> impl IntoResponse {
+    match engine.stream.set_tracing(&name, body.enabled) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, e.message).into_response(),
+    }
+}
+
+// ==========================================
+// ROUTES
+// ==========================================
+
+pub fn routes() -> Router<NexoEngine> {
+    Router::new()
+        .route("/api/trace/{id}", get(get_trace))
+        .route("/api/trace/queue/{name}", post(toggle_queue_trace))
+        .route("/api/trace/stream/{name}", post(toggle_stream_trace))
+}
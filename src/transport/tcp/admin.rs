@@ -0,0 +1,280 @@
+//! Admin TCP surface: connection listing, stats and kick, payload schemas, integrity check, idle resources,
+//! stream backfills, readiness, stream key distribution.
+
+use std::time::UNIX_EPOCH;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::transport::tcp::protocol::cursor::PayloadCursor;
+use crate::transport::tcp::protocol::{ErrorCode, NexoError, ParseError, Response, ToWire};
+use crate::brokers::integrity::{self, DataDirs};
+use crate::brokers::pub_sub::ClientId;
+use crate::brokers::schema::SchemaVersion;
+use crate::brokers::stream::backfill::BackfillSpec;
+use crate::brokers::stream::http::BackfillSummary;
+use crate::brokers::stream::key_stats::KeyStatsSpec;
+use crate::config::Config;
+use crate::transport::http::connections::ConnectionSummary;
+use crate::transport::tcp::registry::ConnectionSnapshot;
+use crate::NexoEngine;
+
+// ==========================================
+// OPCODES
+// ==========================================
+
+pub const OPCODE_MIN: u8 = 0x40;
+pub const OPCODE_MAX: u8 = 0x4F;
+
+pub const OP_ADMIN_LIST_CONNECTIONS: u8 = 0x40;
+pub const OP_ADMIN_KICK: u8 = 0x41;
+pub const OP_ADMIN_SET_SCHEMA: u8 = 0x42;
+pub const OP_ADMIN_GET_SCHEMA: u8 = 0x43;
+pub const OP_ADMIN_CHECK: u8 = 0x44;
+pub const OP_ADMIN_IDLE: u8 = 0x45;
+pub const OP_ADMIN_BACKFILL: u8 = 0x46;
+pub const OP_ADMIN_BACKFILL_STATUS: u8 = 0x47;
+pub const OP_ADMIN_BACKFILL_CANCEL: u8 = 0x48;
+pub const OP_ADMIN_PING: u8 = 0x49;
+pub const OP_ADMIN_KEY_STATS: u8 = 0x4A;
+pub const OP_ADMIN_CONNECTION_STATS: u8 = 0x4B;
+
+// Schema subject kinds
+pub const SCHEMA_KIND_QUEUE: u8 = 0x01;
+pub const SCHEMA_KIND_STREAM: u8 = 0x02;
+
+// ==========================================
+// COMMANDS
+// ==========================================
+
+#[derive(Debug)]
+enum AdminCommand {
+    ListConnections,
+    Kick { connection_id: String },
+    // [Kind:1][Name:String][Schema:String (JSON)]
+    SetSchema { kind: u8, name: String, schema: String },
+    // [Kind:1][Name:String][Version:4] (0 = latest)
+    GetSchema { kind: u8, name: String, version: u32 },
+    Check,
+    // [MinIdleSecs:8] (0 = IDLE_AFTER_DAYS)
+    Idle { min_idle_secs: u64 },
+    // [Spec:String (JSON)]
+    Backfill { spec: String },
+    // [Id:8] (0 = all jobs)
+    BackfillStatus { id: u64 },
+    // [Id:8]
+    BackfillCancel { id: u64 },
+    Ping,
+    // [Topic:String][Spec:String (JSON)]
+    KeyStats { topic: String, spec: String },
+    // [ConnectionId:String] (empty = the calling connection)
+    ConnectionStats { connection_id: String },
+}
+
+impl AdminCommand {
+    fn parse(opcode: u8, cursor: &mut PayloadCursor) -> Result<Self, ParseError> {
+        match opcode {
+            OP_ADMIN_LIST_CONNECTIONS => Ok(Self::ListConnections),
+            OP_ADMIN_KICK => {
+                let connection_id = cursor.read_string()?;
+                Ok(Self::Kick { connection_id })
+            }
+            OP_ADMIN_SET_SCHEMA => {
+                let kind = cursor.read_u8()?;
+                let name = cursor.read_string()?;
+                let schema = cursor.read_string()?;
+                Ok(Self::SetSchema { kind, name, schema })
+            }
+            OP_ADMIN_GET_SCHEMA => {
+                let kind = cursor.read_u8()?;
+                let name = cursor.read_string()?;
+                let version = cursor.read_u32()?;
+                Ok(Self::GetSchema { kind, name, version })
+            }
+            OP_ADMIN_CHECK => Ok(Self::Check),
+            OP_ADMIN_IDLE => {
+                let min_idle_secs = cursor.read_u64()?;
+                Ok(Self::Idle { min_idle_secs })
+            }
+            OP_ADMIN_BACKFILL => {
+                let spec = cursor.read_string()?;
+                Ok(Self::Backfill { spec })
+            }
+            OP_ADMIN_BACKFILL_STATUS => {
+                let id = cursor.read_u64()?;
+                Ok(Self::BackfillStatus { id })
+            }
+            OP_ADMIN_BACKFILL_CANCEL => {
+                let id = cursor.read_u64()?;
+                Ok(Self::BackfillCancel { id })
+            }
+            OP_ADMIN_PING => Ok(Self::Ping),
+            OP_ADMIN_KEY_STATS => {
+                let topic = cursor.read_string()?;
+                let spec = cursor.read_string()?;
+                Ok(Self::KeyStats { topic, spec })
+            }
+            OP_ADMIN_CONNECTION_STATS => {
+                let connection_id = cursor.read_string()?;
+                Ok(Self::ConnectionStats { connection_id })
+            }
+            _ => Err(ParseError::Invalid(format!("Unknown Admin opcode: 0x{:02X}", opcode))),
+        }
+    }
+}
+
+// ==========================================
+// WIRE RESPONSES
+// ==========================================
+
+struct ConnectionListResponse { connections: Vec<ConnectionSnapshot> }
+
+impl ToWire for ConnectionListResponse {
+    fn to_wire(&self) -> Bytes {
+        fn put_string(buf: &mut BytesMut, s: &str) {
+            buf.put_u32(s.len() as u32);
+            buf.put_slice(s.as_bytes());
+        }
+
+        let mut buf = BytesMut::new();
+        buf.put_u32(self.connections.len() as u32);
+        for conn in &self.connections {
+            put_string(&mut buf, &conn.id);
+            put_string(&mut buf, &conn.peer_addr);
+            let connected_at_ms = conn.connected_at.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+            buf.put_u64(connected_at_ms);
+            buf.put_u64(conn.frames_in);
+            buf.put_u64(conn.frames_out);
+            buf.put_u64(conn.bytes_in);
+            buf.put_u64(conn.bytes_out);
+            buf.put_u32(conn.subscriptions.len() as u32);
+            for sub in &conn.subscriptions {
+                put_string(&mut buf, sub);
+            }
+            buf.put_u32(conn.groups.len() as u32);
+            for group in &conn.groups {
+                put_string(&mut buf, group);
+            }
+        }
+        buf.freeze()
+    }
+}
+
+/// [Version:4][CreatedAt:8][Schema:String (JSON)]
+struct SchemaResponse { schema: SchemaVersion }
+
+impl ToWire for SchemaResponse {
+    fn to_wire(&self) -> Bytes {
+        let json = self.schema.schema.to_string();
+        let mut buf = BytesMut::with_capacity(16 + json.len());
+        buf.put_u32(self.schema.version);
+        buf.put_u64(self.schema.created_at);
+        buf.put_u32(json.len() as u32);
+        buf.put_slice(json.as_bytes());
+        buf.freeze()
+    }
+}
+
+// ==========================================
+// DISPATCH ENTRY POINT
+// ==========================================
+
+pub async fn handle(opcode: u8, cursor: &mut PayloadCursor, engine: &NexoEngine, client_id: &ClientId) -> Response {
+    let cmd = match AdminCommand::parse(opcode, cursor) {
+        Ok(c) => c,
+        Err(e) => return Response::Error(e.into()),
+    };
+
+    match cmd {
+        AdminCommand::ListConnections => {
+            Response::Data(ConnectionListResponse { connections: engine.connections.list() }.to_wire())
+        }
+        AdminCommand::Kick { connection_id } => match engine.connections.kick(&connection_id) {
+            true => Response::Ok,
+            false => Response::Error(NexoError::not_found("Connection not found")),
+        },
+        AdminCommand::SetSchema { kind, name, schema } => {
+            let schema: serde_json::Value = match serde_json::from_str(&schema) {
+                Ok(schema) => schema,
+                Err(e) => return Response::Error(NexoError::invalid(format!("Schema is not valid JSON: {}", e))),
+            };
+            let result = match kind {
+                SCHEMA_KIND_QUEUE => engine.queue.set_schema(&name, schema),
+                SCHEMA_KIND_STREAM => engine.stream.set_schema(&name, schema),
+                _ => return Response::Error(NexoError::invalid(format!("Unknown schema kind: 0x{:02X}", kind))),
+            };
+            match result {
+                Ok(version) => Response::Data(Bytes::copy_from_slice(&version.to_be_bytes())),
+                Err(e) => Response::Error(e),
+            }
+        }
+        AdminCommand::GetSchema { kind, name, version } => {
+            let version = (version != 0).then_some(version);
+            let schema = match kind {
+                SCHEMA_KIND_QUEUE => engine.queue.get_schema(&name, version),
+                SCHEMA_KIND_STREAM => engine.stream.get_schema(&name, version),
+                _ => return Response::Error(NexoError::invalid(format!("Unknown schema kind: 0x{:02X}", kind))),
+            };
+            match schema {
+                Some(schema) => Response::Data(SchemaResponse { schema }.to_wire()),
+                None => Response::Null,
+            }
+        }
+        AdminCommand::Check => {
+            // Read-only while live: repairing files the brokers hold open is offline-only (`--check --repair`)
+            let dirs = DataDirs::from_config(Config::global());
+            match tokio::task::spawn_blocking(move || integrity::check(&dirs, false)).await {
+                Ok(report) => Response::Data(Bytes::from(serde_json::to_vec(&report).unwrap_or_default())),
+                Err(e) => Response::Error(NexoError::new(ErrorCode::Internal, format!("Integrity check failed: {}", e))),
+            }
+        }
+        AdminCommand::Idle { min_idle_secs } => {
+            let min_idle_ms = match min_idle_secs {
+                0 => engine.idle.after_ms(),
+                secs => secs.saturating_mul(1000),
+            };
+            Response::Data(Bytes::from(serde_json::to_vec(&engine.idle_resources(min_idle_ms)).unwrap_or_default()))
+        }
+        AdminCommand::Backfill { spec } => {
+            let spec: BackfillSpec = match serde_json::from_str(&spec) {
+                Ok(spec) => spec,
+                Err(e) => return Response::Error(NexoError::invalid(format!("Invalid backfill spec: {}", e))),
+            };
+            match engine.stream.start_backfill(spec).await {
+                Ok(id) => Response::Data(Bytes::copy_from_slice(&id.to_be_bytes())),
+                Err(e) => Response::Error(e),
+            }
+        }
+        AdminCommand::BackfillStatus { id: 0 } => {
+            let jobs: Vec<BackfillSummary> = engine.stream.backfills().iter().map(|b| b.snapshot().into()).collect();
+            Response::Data(Bytes::from(serde_json::to_vec(&jobs).unwrap_or_default()))
+        }
+        AdminCommand::BackfillStatus { id } => match engine.stream.backfill(id) {
+            Some(job) => Response::Data(Bytes::from(serde_json::to_vec(&BackfillSummary::from(job)).unwrap_or_default())),
+            None => Response::Error(NexoError::not_found("Backfill not found")),
+        },
+        AdminCommand::BackfillCancel { id } => match engine.stream.cancel_backfill(id) {
+            true => Response::Ok,
+            false => Response::Error(NexoError::not_found("No running backfill with this id")),
+        },
+        AdminCommand::Ping => {
+            Response::Data(Bytes::from(serde_json::to_vec(&engine.readiness().await).unwrap_or_default()))
+        }
+        AdminCommand::KeyStats { topic, spec } => {
+            let spec: KeyStatsSpec = match serde_json::from_str(&spec) {
+                Ok(spec) => spec,
+                Err(e) => return Response::Error(NexoError::invalid(format!("Invalid key stats spec: {}", e))),
+            };
+            match engine.stream.key_stats(&topic, &spec).await {
+                Ok(stats) => Response::Data(Bytes::from(serde_json::to_vec(&stats).unwrap_or_default())),
+                Err(e) => Response::Error(e),
+            }
+        }
+        AdminCommand::ConnectionStats { connection_id } => {
+            let connection_id = if connection_id.is_empty() { &client_id.0 } else { &connection_id };
+            match engine.connections.get(connection_id) {
+                Some(stats) => Response::Data(Bytes::from(serde_json::to_vec(&ConnectionSummary::from(stats)).unwrap_or_default())),
+                None => Response::Error(NexoError::not_found("Connection not found")),
+            }
+        }
+    }
+}
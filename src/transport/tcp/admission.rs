@@ -0,0 +1,233 @@
+//! Connection admission: who may open a TCP connection, and how many.
+//!
+//! Checked at accept time, before a session is set up. A peer matching
+//! `SERVER_DENY_CIDRS`, or missing from a non-empty `SERVER_ALLOW_CIDRS`,
+//! is refused with `FORBIDDEN`. Past `SERVER_MAX_CONNECTIONS` in all, or
+//! `SERVER_MAX_CONNECTIONS_PER_IP` from one address, it is refused with
+//! `TOO_MANY_CONNECTIONS` (retryable). Either way the peer gets one error
+//! frame (correlation id 0) and the socket is closed. Unix socket peers are
+//! not counted: they are filtered by uid instead.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::transport::tcp::protocol::{ErrorCode, NexoError};
+
+/// An address range, `10.0.0.0/8` or a bare address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| format!("invalid address in '{}'", s))?;
+        let addr = canonical(addr);
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>().ok().filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix length in '{}' (0 to {})", s, max))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// IPv4 peers of a dual-stack socket show up as `::ffff:a.b.c.d`
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+/// Comma-separated ranges, as in `SERVER_ALLOW_CIDRS`.
+pub fn parse_nets(value: &str) -> Result<Vec<IpNet>, String> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::parse).collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    /// 0 = unlimited
+    pub max_connections: usize,
+    /// 0 = unlimited
+    pub max_per_ip: usize,
+    /// Empty = every address not denied
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdmissionSnapshot {
+    /// TCP connections currently admitted
+    pub connections: usize,
+    /// Refused since start: over a limit, or by the address lists
+    pub rejected_limit: u64,
+    pub rejected_denied: u64,
+}
+
+#[derive(Default)]
+struct Counts {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+pub struct Admission {
+    config: AdmissionConfig,
+    counts: Mutex<Counts>,
+    rejected_limit: AtomicU64,
+    rejected_denied: AtomicU64,
+}
+
+impl Admission {
+    pub fn new(config: AdmissionConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            counts: Mutex::new(Counts::default()),
+            rejected_limit: AtomicU64::new(0),
+            rejected_denied: AtomicU64::new(0),
+        })
+    }
+
+    /// Count a connection from `ip` for as long as the guard lives, or say
+    /// why it is refused.
+    pub fn admit(self: &Arc<Self>, ip: IpAddr) -> Result<AdmissionGuard, NexoError> {
+        let ip = canonical(ip);
+        let denied = self.config.deny.iter().any(|net| net.contains(ip))
+            || (!self.config.allow.is_empty() && !self.config.allow.iter().any(|net| net.contains(ip)));
+        if denied {
+            self.rejected_denied.fetch_add(1, Ordering::Relaxed);
+            return Err(NexoError::new(ErrorCode::Forbidden, format!("Connections from {} are not allowed", ip)));
+        }
+
+        let mut counts = self.counts.lock();
+        let from_ip = counts.per_ip.get(&ip).copied().unwrap_or(0);
+        let refusal = if self.config.max_connections > 0 && counts.total >= self.config.max_connections {
+            Some(format!("Server connection limit reached ({})", self.config.max_connections))
+        } else if self.config.max_per_ip > 0 && from_ip >= self.config.max_per_ip {
+            Some(format!("Connection limit per address reached ({})", self.config.max_per_ip))
+        } else {
+            None
+        };
+        if let Some(message) = refusal {
+            self.rejected_limit.fetch_add(1, Ordering::Relaxed);
+            return Err(NexoError::new(ErrorCode::TooManyConnections, message));
+        }
+        counts.total += 1;
+        counts.per_ip.insert(ip, from_ip + 1);
+        Ok(AdmissionGuard { admission: self.clone(), ip })
+    }
+
+    pub fn snapshot(&self) -> AdmissionSnapshot {
+        AdmissionSnapshot {
+            connections: self.counts.lock().total,
+            rejected_limit: self.rejected_limit.load(Ordering::Relaxed),
+            rejected_denied: self.rejected_denied.load(Ordering::Relaxed),
+        }
+    }
+
+    fn release(&self, ip: IpAddr) {
+        let mut counts = self.counts.lock();
+        counts.total = counts.total.saturating_sub(1);
+        if let Some(from_ip) = counts.per_ip.get_mut(&ip) {
+            *from_ip -= 1;
+            if *from_ip == 0 {
+                counts.per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+/// An admitted connection; dropping it frees its slot.
+pub struct AdmissionGuard {
+    admission: Arc<Admission>,
+    ip: IpAddr,
+}
+
+impl Drop for AdmissionGuard {
+    fn drop(&mut self) {
+        self.admission.release(self.ip);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges_match_their_addresses() {
+        let net: IpNet = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.1.200.3")));
+        assert!(net.contains(ip("::ffff:10.1.0.1")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!("0.0.0.0/0".parse::<IpNet>().unwrap().contains(ip("192.168.1.1")));
+        assert!("fd00::/8".parse::<IpNet>().unwrap().contains(ip("fd12::1")));
+        assert!("127.0.0.1".parse::<IpNet>().unwrap().contains(ip("127.0.0.1")));
+        for invalid in ["10.0.0.0/33", "fd00::/129", "host/8", "10.0.0.0/x"] {
+            assert!(invalid.parse::<IpNet>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn limits_free_up_when_connections_close() {
+        let admission = Admission::new(AdmissionConfig { max_connections: 3, max_per_ip: 2, ..Default::default() });
+        let a1 = admission.admit(ip("10.0.0.1")).unwrap();
+        let _a2 = admission.admit(ip("10.0.0.1")).unwrap();
+        let per_ip = admission.admit(ip("10.0.0.1")).err().unwrap();
+        assert_eq!(per_ip.code, ErrorCode::TooManyConnections);
+        assert!(per_ip.retryable);
+        let _b = admission.admit(ip("10.0.0.2")).unwrap();
+        assert!(admission.admit(ip("10.0.0.3")).is_err(), "Server limit");
+
+        drop(a1);
+        let _a3 = admission.admit(ip("10.0.0.1")).unwrap();
+        let snapshot = admission.snapshot();
+        assert_eq!((snapshot.connections, snapshot.rejected_limit, snapshot.rejected_denied), (3, 2, 0));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let admission = Admission::new(AdmissionConfig {
+            allow: parse_nets("10.0.0.0/8, 127.0.0.1").unwrap(),
+            deny: parse_nets("10.66.0.0/16").unwrap(),
+            ..Default::default()
+        });
+        assert!(admission.admit(ip("10.1.2.3")).is_ok());
+        assert!(admission.admit(ip("127.0.0.1")).is_ok());
+        for refused in ["10.66.1.1", "192.168.1.1"] {
+            assert_eq!(admission.admit(ip(refused)).err().unwrap().code, ErrorCode::Forbidden);
+        }
+        assert_eq!(admission.snapshot().rejected_denied, 2);
+    }
+}
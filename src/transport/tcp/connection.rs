@@ -1,20 +1,55 @@
 //! Connection Session Layer: lifecycle + routing for a single client session.
-//! Owns broker registration, push bridge, and request dispatch.
+//! Owns broker registration, push bridges, and request dispatch.
+//! Sessions run over any byte stream (TCP, Unix domain sockets).
+use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
 use std::sync::Arc;
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::Instrument;
 use uuid::Uuid;
 
 use crate::brokers::pub_sub::{ClientId, PubSubMessage};
+use crate::brokers::queue;
+use crate::brokers::queue::push::QueueDelivery;
 use crate::config::Config;
-use crate::transport::tcp::dispatcher::Dispatcher;
-use crate::transport::tcp::protocol::{InboundFrame, OutboundFrame, ParseError, Response, TYPE_REQUEST, NexoCodec};
+use crate::transport::tcp::dispatcher::{self, Dispatcher};
+use crate::transport::tcp::listener::Roles;
+use crate::transport::tcp::registry::ConnectionHandle;
+use crate::transport::tcp::protocol::chunk::{self, Reassembler};
+use crate::transport::tcp::protocol::pool::BufferPool;
+use crate::transport::tcp::protocol::{FrameHeader, InboundFrame, NexoError, OutboundFrame, ParseError, Response, TYPE_REQUEST, NexoCodec};
 use crate::NexoEngine;
 
+/// How long a closed connection waits for its cancelled requests
+const REQUEST_DRAIN: Duration = Duration::from_secs(1);
+
 pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<(), String> {
+    handle_listener_connection(socket, engine, Roles::ALL).await
+}
+
+/// A TCP session accepted by a listener serving only `roles`.
+pub async fn handle_listener_connection(socket: TcpStream, engine: NexoEngine, roles: Roles) -> Result<(), String> {
+    let peer_addr = socket.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    handle_stream(socket, peer_addr, engine, roles).await
+}
+
+/// Refuse a TCP connection before any session: one error frame (id 0), then close.
+pub async fn reject(socket: TcpStream, err: NexoError) {
+    let mut framed = FramedWrite::new(socket, NexoCodec::new());
+    let _ = framed.send(OutboundFrame::Response { id: 0, response: Response::Error(err) }).await;
+    let _ = framed.get_mut().shutdown().await;
+}
+
+/// A session over any byte stream; `peer_addr` is what the connection registry shows.
+pub async fn handle_stream<S>(socket: S, peer_addr: String, engine: NexoEngine, roles: Roles) -> Result<(), String>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
     let config = Config::global();
     let engine = Arc::new(engine); // Wrapped in Arc once for all tasks
 
@@ -22,14 +57,19 @@ pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<
     // ACT 1: SESSION SETUP & SOCKET CHANNELS
     // ==========================================
     let client_id = ClientId(Uuid::new_v4().to_string());
+    let connection = engine.connections.register(client_id.0.clone(), peer_addr);
+    let kicked = connection.kicked();
+    // Cancelled once the client is gone: requests still running stop waiting
+    // and give back what they took (see `drain_requests`)
+    let cancel = CancellationToken::new();
 
     // Channels to communicate with the raw TCP socket
     let (inbound_tx, mut inbound_rx) = mpsc::channel(config.server.channel_capacity_socket_write);
     let (outbound_tx, outbound_rx) = mpsc::channel(config.server.channel_capacity_socket_write);
 
     // Spawn the raw I/O task
-    let (reader, writer) = socket.into_split();
-    let mut socket_task = tokio::spawn(run_socket(reader, writer, inbound_tx, outbound_rx));
+    let (reader, writer) = tokio::io::split(socket);
+    let mut socket_task = tokio::spawn(run_socket(reader, writer, inbound_tx, outbound_rx, connection.clone()));
 
     // ==========================================
     // ACT 2: PUBSUB PUSH BRIDGE
@@ -39,15 +79,44 @@ pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<
     engine.pubsub.connect(client_id.clone(), push_tx);
 
     // Background task: forwards PubSub pushes to the socket's outbound channel
+    // A full socket channel means the client stopped reading: the time it
+    // stays full counts against the idle TTL of its subscriptions
     let outbound_bridge = outbound_tx.clone();
+    let (bridge_engine, bridge_client) = (Arc::clone(&engine), client_id.clone());
     let bridge_handle = tokio::spawn(async move {
         while let Some(msg_arc) = push_rx.recv().await {
+            // Late by now: the client would rather not have it
+            if msg_arc.is_expired() {
+                continue;
+            }
             let payload = msg_arc.get_network_packet().clone();
-            let frame = OutboundFrame::PushPubSub { id: 0, payload };
+            let frame = OutboundFrame::PushPubSub { id: 0, flags: msg_arc.push_flags(), payload };
 
+            let frame = match outbound_bridge.try_send(frame) {
+                Ok(()) => continue,
+                Err(mpsc::error::TrySendError::Full(frame)) => frame,
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            };
+            bridge_engine.pubsub.set_stalled(&bridge_client, true);
             if outbound_bridge.send(frame).await.is_err() {
                 break; // Socket closed, exit bridge
             }
+            bridge_engine.pubsub.set_stalled(&bridge_client, false);
+        }
+    });
+
+    // Same for queue push subscriptions: deliveries from the Queue Engine
+    let (queue_push_tx, mut queue_push_rx) = mpsc::unbounded_channel::<QueueDelivery>();
+    engine.queue.connect(&client_id.0, queue_push_tx);
+
+    let outbound_bridge = outbound_tx.clone();
+    let queue_bridge_handle = tokio::spawn(async move {
+        while let Some(delivery) = queue_push_rx.recv().await {
+            let frame = OutboundFrame::PushQueue { id: 0, flags: delivery.push_flags(), payload: queue::tcp::encode_delivery(&delivery) };
+
+            if outbound_bridge.send(frame).await.is_err() {
+                break;
+            }
         }
     });
 
@@ -63,18 +132,31 @@ pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<
                 let tx_clone = outbound_tx.clone();
                 let engine_clone = Arc::clone(&engine);
                 let client_id_clone = client_id.clone();
+                let cancel = cancel.child_token();
+                let span = tracing::info_span!(
+                    "nexo.request",
+                    connection = %client_id.0,
+                    opcode = frame.header.meta,
+                    correlation_id = frame.header.id(),
+                    payload_len = frame.payload.len(),
+                );
 
+                let stats = connection.clone();
                 request_set.spawn(async move {
                     let id = frame.header.id();
-                    let response = match frame.header.frame_type {
-                        TYPE_REQUEST => {
-                            let dispatcher = Dispatcher::new(&engine_clone, &client_id_clone);
-                            dispatcher.dispatch(frame.header.meta, frame.payload).await
+                    let (opcode, started) = (frame.header.meta, Instant::now());
+                    let payload = frame.payload.clone();
+                    let response = match (frame.header.frame_type, frame.rejection) {
+                        (_, Some(reason)) => Response::Error(reason),
+                        (TYPE_REQUEST, None) => {
+                            let dispatcher = Dispatcher::new(&engine_clone, &client_id_clone, roles, &cancel);
+                            dispatcher.dispatch(opcode, frame.payload).await
                         }
-                        _ => Response::Error("Unsupported frame type".into()),
+                        _ => Response::Error(NexoError::invalid("Unsupported frame type")),
                     };
+                    record_request(&stats, opcode, &payload, started.elapsed(), &response);
                     let _ = tx_clone.send(OutboundFrame::Response { id, response }).await;
-                });
+                }.instrument(span));
             }
 
             // EVENT B: The TCP Socket crashed or disconnected
@@ -88,6 +170,13 @@ pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<
 
             // EVENT C: A background request finished, clean up its memory
             _ = request_set.join_next(), if !request_set.is_empty() => {}
+
+            // EVENT D: An admin kicked this connection
+            _ = kicked.cancelled() => {
+                tracing::info!("Client {:?} kicked by admin", client_id);
+                socket_task.abort();
+                break;
+            }
         }
     }
 
@@ -96,23 +185,69 @@ pub async fn handle_connection(socket: TcpStream, engine: NexoEngine) -> Result<
     // ==========================================
     tracing::debug!("Client {:?} disconnected", client_id);
 
-    request_set.abort_all();
+    cancel.cancel();
+    drain_requests(&mut request_set).await;
     bridge_handle.abort();
+    queue_bridge_handle.abort();
+    let semaphores = engine.connections.semaphores(&client_id.0);
+    engine.store.semaphores.release_holder(&client_id.0, &semaphores);
+    engine.connections.unregister(&client_id.0);
     engine.pubsub.disconnect(&client_id);
+    engine.queue.disconnect(&client_id.0);
     engine.stream.disconnect(client_id.0.clone()).await;
 
     Ok(())
 }
 
+/// Count a request in the connection stats, and log it if it took longer
+/// than `SLOW_REQUEST_MS` (long polls aside).
+fn record_request(connection: &ConnectionHandle, opcode: u8, payload: &Bytes, elapsed: Duration, response: &Response) {
+    let failed = matches!(response, Response::Error(_));
+    if dispatcher::waits(opcode) {
+        connection.record_request(None, failed, false);
+        return;
+    }
+    let threshold = Config::global().server.slow_request_ms;
+    let slow = threshold > 0 && elapsed >= Duration::from_millis(threshold);
+    connection.record_request(Some(elapsed), failed, slow);
+    if slow {
+        tracing::warn!(
+            connection = %connection.id,
+            peer = %connection.peer_addr,
+            broker = dispatcher::broker_of(opcode),
+            opcode = format_args!("0x{:02X}", opcode),
+            resource = dispatcher::resource_of(opcode, payload).as_deref().unwrap_or("-"),
+            elapsed_ms = elapsed.as_millis() as u64,
+            failed,
+            "Slow request"
+        );
+    }
+}
+
+/// Give cancelled requests a moment to return what they took (in-flight
+/// queue messages, pending cold reads), then abort the rest.
+async fn drain_requests(request_set: &mut tokio::task::JoinSet<()>) {
+    let drained = async { while request_set.join_next().await.is_some() {} };
+    let _ = tokio::time::timeout(REQUEST_DRAIN, drained).await;
+    request_set.abort_all();
+}
 
-async fn run_socket(
-    reader: OwnedReadHalf,
-    writer: OwnedWriteHalf,
+async fn run_socket<R, W>(
+    reader: R,
+    writer: W,
     inbound_tx: mpsc::Sender<InboundFrame>,
     mut outbound_rx: mpsc::Receiver<OutboundFrame>,
-) -> Result<(), ParseError> {
-    let mut framed_reader = FramedRead::new(reader, NexoCodec::new());
+    connection: Arc<ConnectionHandle>,
+) -> Result<(), ParseError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let config = &Config::global().server;
+    let pool = BufferPool::new(config.read_pool_buffers, config.read_buffer_size, connection.read_pool());
+    let mut framed_reader = FramedRead::with_capacity(reader, NexoCodec::pooled(pool), config.read_buffer_size);
     let mut framed_writer = FramedWrite::new(writer, NexoCodec::new());
+    let mut reassembler = Reassembler::new();
 
     loop {
         tokio::select! {
@@ -120,6 +255,8 @@ async fn run_socket(
             frame = framed_reader.next() => {
                 match frame {
                     Some(Ok(frame)) => {
+                        connection.record_inbound(FrameHeader::SIZE + frame.header.payload_len() as usize);
+                        let Some(frame) = reassembler.accept(frame) else { continue };
                         if inbound_tx.send(frame).await.is_err() {
                             break;
                         }
@@ -130,11 +267,22 @@ async fn run_socket(
             }
             outbound = outbound_rx.recv() => {
                 match outbound {
-                    Some(message) => {
-                        if let Err(err) = framed_writer.send(message).await {
-                            return Err(err);
+                    Some(message) => match chunk::split(&message, config.chunk_size) {
+                        // Fed one at a time: the writer flushes as its buffer fills
+                        Some(parts) => {
+                            for part in parts {
+                                connection.record_outbound(part.wire_len());
+                                framed_writer.feed(part).await?;
+                            }
+                            framed_writer.flush().await?;
                         }
-                    }
+                        None => {
+                            connection.record_outbound(message.wire_len());
+                            if let Err(err) = framed_writer.send(message).await {
+                                return Err(err);
+                            }
+                        }
+                    },
                     None => break,
                 }
             }
@@ -2,43 +2,97 @@
 
 use crate::brokers::pub_sub::ClientId;
 use crate::brokers::{pub_sub, queue, store, stream};
+use crate::config::Config;
+use crate::transport::tcp::admin;
+use crate::transport::tcp::listener::Roles;
 use crate::transport::tcp::protocol::cursor::PayloadCursor;
-use crate::transport::tcp::protocol::Response;
+use crate::transport::tcp::protocol::{ErrorCode, NexoError, Response};
 use crate::NexoEngine;
 use bytes::Bytes;
+use tokio_util::sync::CancellationToken;
 
 pub const OP_DEBUG_ECHO: u8 = 0x00;
 
+/// Requests that wait for data by design (long polls): left out of latency
+/// stats and the slow-request log.
+pub fn waits(opcode: u8) -> bool {
+    matches!(opcode, queue::tcp::OP_Q_CONSUME | stream::tcp::OP_S_FETCH | stream::tcp::OP_S_FETCH_MULTI)
+}
+
+/// Broker owning `opcode`, for logs.
+pub fn broker_of(opcode: u8) -> &'static str {
+    match opcode {
+        op if store::tcp::owns(op) => "store",
+        op if queue::tcp::owns(op) => "queue",
+        op if (pub_sub::tcp::OPCODE_MIN..=pub_sub::tcp::OPCODE_MAX).contains(&op) => "pubsub",
+        op if (stream::tcp::OPCODE_MIN..=stream::tcp::OPCODE_MAX).contains(&op) => "stream",
+        op if (admin::OPCODE_MIN..=admin::OPCODE_MAX).contains(&op) => "admin",
+        _ => "unknown",
+    }
+}
+
+/// The queue, topic, key or lock a request is about: the first string of its
+/// payload, past the fields a few opcodes put before it (multi-topic stream
+/// requests name their group).
+pub fn resource_of(opcode: u8, payload: &Bytes) -> Option<String> {
+    let mut cursor = PayloadCursor::new(payload.clone());
+    match opcode {
+        queue::tcp::OP_Q_ACK | queue::tcp::OP_Q_NACK => {
+            cursor.read_uuid_bytes().ok()?;
+        }
+        pub_sub::tcp::OP_ALIAS => {
+            cursor.read_u16().ok()?;
+        }
+        pub_sub::tcp::OP_PUB_ALIAS | store::tcp::OP_MAP_MSET => return None,
+        store::tcp::OP_MAP_MGET | store::tcp::OP_MAP_MDEL => {
+            cursor.read_u32().ok()?;
+        }
+        op if (admin::OPCODE_MIN..=admin::OPCODE_MAX).contains(&op) => return None,
+        _ => {}
+    }
+    cursor.read_string().ok()
+}
+
 pub struct Dispatcher<'a> {
     engine: &'a NexoEngine,
     client_id: &'a ClientId,
+    /// Roles of the listener the connection came in on
+    roles: Roles,
+    /// Fires when the client goes away mid-request
+    cancel: &'a CancellationToken,
 }
 
 impl<'a> Dispatcher<'a> {
-    pub fn new(engine: &'a NexoEngine, client_id: &'a ClientId) -> Self {
-        Self { engine, client_id }
+    pub fn new(engine: &'a NexoEngine, client_id: &'a ClientId, roles: Roles, cancel: &'a CancellationToken) -> Self {
+        Self { engine, client_id, roles, cancel }
     }
 
     pub async fn dispatch(&self, opcode: u8, payload: Bytes) -> Response {
+        if !self.roles.allows(opcode) {
+            return Response::Error(NexoError::new(ErrorCode::Forbidden, format!("Opcode 0x{:02X} is not served on this listener", opcode)));
+        }
         let mut cursor = PayloadCursor::new(payload);
 
         match opcode {
             OP_DEBUG_ECHO => Response::Data(cursor.read_remaining()),
 
-            op if (store::tcp::OPCODE_MIN..=store::tcp::OPCODE_MAX).contains(&op) => {
-                store::tcp::handle(op, &mut cursor, self.engine)
+            op if store::tcp::owns(op) => {
+                store::tcp::handle(op, &mut cursor, self.engine, self.client_id).await
             }
-            op if (queue::tcp::OPCODE_MIN..=queue::tcp::OPCODE_MAX).contains(&op) => {
-                queue::tcp::handle(op, &mut cursor, self.engine).await
+            op if queue::tcp::owns(op) => {
+                queue::tcp::handle(op, &mut cursor, self.engine, self.client_id, self.cancel).await
             }
             op if (pub_sub::tcp::OPCODE_MIN..=pub_sub::tcp::OPCODE_MAX).contains(&op) => {
                 pub_sub::tcp::handle(op, &mut cursor, self.engine, self.client_id).await
             }
             op if (stream::tcp::OPCODE_MIN..=stream::tcp::OPCODE_MAX).contains(&op) => {
-                stream::tcp::handle(op, &mut cursor, self.engine, self.client_id).await
+                stream::tcp::handle(op, &mut cursor, self.engine, self.client_id, self.cancel).await
+            }
+            op if (admin::OPCODE_MIN..=admin::OPCODE_MAX).contains(&op) => {
+                admin::handle(op, &mut cursor, self.engine, self.client_id).await
             }
 
-            _ => Response::Error(format!("Unknown opcode: 0x{:02X}", opcode)),
+            _ => Response::Error(NexoError::new(ErrorCode::UnknownOpcode, format!("Unknown opcode: 0x{:02X}", opcode))),
         }
     }
 }
@@ -0,0 +1,342 @@
+//! TCP listeners: the server can bind several addresses, each serving its own
+//! set of roles (brokers, admin). A request for an opcode outside the
+//! listener's roles is answered with `FORBIDDEN`.
+//!
+//! Configured through `SERVER_LISTENERS`, a `;`-separated list of
+//! `name=host:port[/role,role,...]`, e.g.
+//! `clients=0.0.0.0:7654/store,queue,pubsub,stream;tools=127.0.0.1:7655/all`.
+//! Roles: `store`, `queue`, `pubsub`, `stream`, `admin`, `all` (the default).
+//! Listing and kicking other clients also needs `connections`, which `all`
+//! does not include: it has to be granted by name.
+//!
+//! `SERVER_UNIX_SOCKET` adds a Unix domain socket listener for same-host
+//! clients (sidecars, CLI). It runs the same protocol stack; its peers are
+//! identified by their credentials, and `SERVER_UNIX_SOCKET_UIDS` restricts
+//! which users may connect.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::net::TcpListener;
+
+use crate::brokers::{pub_sub, queue, store, stream};
+use crate::transport::tcp::{admin, connection};
+use crate::NexoEngine;
+
+/// Refused connections are logged at most once per interval
+const REJECT_LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// How long a refused client gets to take its error frame
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Aggregates the warnings for refused connections: a flood of them (e.g. at
+/// the connection limit) logs one line per `REJECT_LOG_INTERVAL`.
+#[derive(Default)]
+struct RejectLog {
+    last: Option<Instant>,
+    suppressed: u64,
+}
+
+impl RejectLog {
+    /// `Some(refused since the last line)` when this refusal is to be logged.
+    fn record(&mut self, now: Instant) -> Option<u64> {
+        match self.last {
+            Some(last) if now.duration_since(last) < REJECT_LOG_INTERVAL => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roles {
+    pub store: bool,
+    pub queue: bool,
+    pub pubsub: bool,
+    pub stream: bool,
+    pub admin: bool,
+    /// Admin list connections and kick; never implied by `all`
+    pub connections: bool,
+}
+
+impl Roles {
+    pub const ALL: Roles = Roles { store: true, queue: true, pubsub: true, stream: true, admin: true, connections: false };
+    pub const NONE: Roles = Roles { store: false, queue: false, pubsub: false, stream: false, admin: false, connections: false };
+
+    /// Whether a request with `opcode` may be served. Opcodes owned by no role
+    /// (debug echo, unknown) are left to the dispatcher.
+    pub fn allows(&self, opcode: u8) -> bool {
+        match opcode {
+            admin::OP_ADMIN_LIST_CONNECTIONS | admin::OP_ADMIN_KICK => self.admin && self.connections,
+            op if store::tcp::owns(op) => self.store,
+            op if queue::tcp::owns(op) => self.queue,
+            op if (pub_sub::tcp::OPCODE_MIN..=pub_sub::tcp::OPCODE_MAX).contains(&op) => self.pubsub,
+            op if (stream::tcp::OPCODE_MIN..=stream::tcp::OPCODE_MAX).contains(&op) => self.stream,
+            op if (admin::OPCODE_MIN..=admin::OPCODE_MAX).contains(&op) => self.admin,
+            _ => true,
+        }
+    }
+}
+
+impl std::str::FromStr for Roles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut roles = Roles::NONE;
+        for role in s.split(',').map(str::trim) {
+            match role {
+                "all" => roles = Roles { connections: roles.connections, ..Roles::ALL },
+                "store" => roles.store = true,
+                "queue" => roles.queue = true,
+                "pubsub" => roles.pubsub = true,
+                "stream" => roles.stream = true,
+                "admin" => roles.admin = true,
+                "connections" => roles.connections = true,
+                other => return Err(format!("unknown listener role '{}'", other)),
+            }
+        }
+        Ok(roles)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Transport {
+    #[default]
+    Tcp,
+    /// `address` is the socket path
+    Unix {
+        /// Users allowed to connect (empty = any)
+        allowed_uids: Vec<u32>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerConfig {
+    pub name: String,
+    pub address: String,
+    pub roles: Roles,
+    pub transport: Transport,
+}
+
+/// Parse `SERVER_LISTENERS`. An empty value yields the single default
+/// listener on `default_address` with every role.
+pub fn parse_listeners(value: &str, default_address: &str) -> Result<Vec<ListenerConfig>, String> {
+    let entries: Vec<&str> = value.split(';').map(str::trim).filter(|e| !e.is_empty()).collect();
+    if entries.is_empty() {
+        return Ok(vec![ListenerConfig {
+            name: "default".to_string(),
+            address: default_address.to_string(),
+            roles: Roles::ALL,
+            transport: Transport::Tcp,
+        }]);
+    }
+
+    let mut listeners: Vec<ListenerConfig> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let (name, rest) = entry.split_once('=')
+            .ok_or_else(|| format!("listener '{}' must be name=host:port[/roles]", entry))?;
+        let (address, roles) = match rest.split_once('/') {
+            Some((address, roles)) => (address, roles.parse()?),
+            None => (rest, Roles::ALL),
+        };
+        let name = name.trim();
+        if listeners.iter().any(|l| l.name == name) {
+            return Err(format!("duplicate listener '{}'", name));
+        }
+        listeners.push(ListenerConfig { name: name.to_string(), address: address.trim().to_string(), roles, transport: Transport::Tcp });
+    }
+    Ok(listeners)
+}
+
+/// The `SERVER_UNIX_SOCKET` listener, `None` when `path` is empty.
+pub fn unix_listener(path: &str, roles: &str, uids: &str) -> Result<Option<ListenerConfig>, String> {
+    if path.is_empty() {
+        return Ok(None);
+    }
+    let allowed_uids = uids.split(',').map(str::trim).filter(|u| !u.is_empty())
+        .map(|u| u.parse().map_err(|_| format!("invalid uid '{}'", u)))
+        .collect::<Result<Vec<u32>, String>>()?;
+    Ok(Some(ListenerConfig {
+        name: "unix".to_string(),
+        address: path.to_string(),
+        roles: if roles.is_empty() { Roles::ALL } else { roles.parse()? },
+        transport: Transport::Unix { allowed_uids },
+    }))
+}
+
+/// Bind `listener` and serve connections until the process exits.
+pub async fn serve(listener: ListenerConfig, engine: NexoEngine) {
+    match listener.transport {
+        Transport::Tcp => serve_tcp(listener, engine).await,
+        #[cfg(unix)]
+        Transport::Unix { .. } => serve_unix(listener, engine).await,
+        #[cfg(not(unix))]
+        Transport::Unix { .. } => panic!("Listener '{}': Unix domain sockets are not supported on this platform", listener.name),
+    }
+}
+
+async fn serve_tcp(listener: ListenerConfig, engine: NexoEngine) {
+    let socket = TcpListener::bind(&listener.address)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to bind listener '{}' on {}: {}", listener.name, listener.address, e));
+
+    tracing::info!(listener = %listener.name, address = %listener.address, roles = ?listener.roles, "Nexo listening");
+    let listener = Arc::new(listener);
+    let mut rejections = RejectLog::default();
+
+    loop {
+        let (socket, client_addr) = match socket.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::error!(listener = %listener.name, error = %e, "Failed to accept connection");
+                continue;
+            }
+        };
+
+        let admitted = match engine.admission.admit(client_addr.ip()) {
+            Ok(admitted) => admitted,
+            Err(e) => {
+                if let Some(suppressed) = rejections.record(Instant::now()) {
+                    tracing::warn!(client = %client_addr, listener = %listener.name, reason = %e.message, suppressed, "Rejected connection");
+                }
+                // A client that does not read must not pin the task
+                tokio::spawn(async move {
+                    let _ = tokio::time::timeout(REJECT_TIMEOUT, connection::reject(socket, e)).await;
+                });
+                continue;
+            }
+        };
+
+        let engine_clone = engine.clone();
+        let listener = listener.clone();
+
+        tracing::info!(client = %client_addr, listener = %listener.name, "New connection accepted");
+
+        tokio::spawn(async move {
+            let _admitted = admitted;
+            if let Err(e) = connection::handle_listener_connection(socket, engine_clone, listener.roles).await {
+                tracing::error!(client = %client_addr, error = %e, "Connection error");
+            }
+            tracing::debug!(client = %client_addr, "Connection closed");
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(listener: ListenerConfig, engine: NexoEngine) {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::net::UnixListener;
+
+    let Transport::Unix { allowed_uids } = &listener.transport else { unreachable!() };
+    // A socket file left behind by a previous run would fail the bind
+    if std::fs::metadata(&listener.address).is_ok_and(|m| m.file_type().is_socket()) {
+        let _ = std::fs::remove_file(&listener.address);
+    }
+    let socket = UnixListener::bind(&listener.address)
+        .unwrap_or_else(|e| panic!("Failed to bind listener '{}' on {}: {}", listener.name, listener.address, e));
+
+    tracing::info!(listener = %listener.name, path = %listener.address, roles = ?listener.roles, "Nexo listening");
+
+    loop {
+        let stream = match socket.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::error!(listener = %listener.name, error = %e, "Failed to accept connection");
+                continue;
+            }
+        };
+
+        let Ok(cred) = stream.peer_cred() else {
+            tracing::warn!(listener = %listener.name, "Rejected connection without peer credentials");
+            continue;
+        };
+        if !allowed_uids.is_empty() && !allowed_uids.contains(&cred.uid()) {
+            tracing::warn!(listener = %listener.name, uid = cred.uid(), "Rejected connection from unauthorized user");
+            continue;
+        }
+        let peer_addr = match cred.pid() {
+            Some(pid) => format!("unix:uid={},pid={}", cred.uid(), pid),
+            None => format!("unix:uid={}", cred.uid()),
+        };
+
+        let engine_clone = engine.clone();
+        let roles = listener.roles;
+
+        tracing::info!(client = %peer_addr, listener = %listener.name, "New connection accepted");
+
+        tokio::spawn(async move {
+            if let Err(e) = connection::handle_stream(stream, peer_addr.clone(), engine_clone, roles).await {
+                tracing::error!(client = %peer_addr, error = %e, "Connection error");
+            }
+            tracing::debug!(client = %peer_addr, "Connection closed");
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_value_is_the_default_listener() {
+        let listeners = parse_listeners("", "0.0.0.0:7654").unwrap();
+        assert_eq!(listeners, vec![ListenerConfig {
+            name: "default".to_string(),
+            address: "0.0.0.0:7654".to_string(),
+            roles: Roles::ALL,
+            transport: Transport::Tcp,
+        }]);
+    }
+
+    #[test]
+    fn listeners_are_parsed_with_roles() {
+        let listeners = parse_listeners("clients=0.0.0.0:9000/queue,stream; tools=127.0.0.1:9001", "unused").unwrap();
+        assert_eq!(listeners.len(), 2);
+        assert_eq!(listeners[0].address, "0.0.0.0:9000");
+        assert_eq!(listeners[0].roles, Roles { queue: true, stream: true, ..Roles::NONE });
+        assert_eq!((listeners[1].name.as_str(), listeners[1].roles), ("tools", Roles::ALL));
+
+        assert!(!listeners[0].roles.allows(admin::OP_ADMIN_KICK));
+        assert!(listeners[0].roles.allows(queue::tcp::OP_Q_PUSH));
+
+        // Connection management is opt-in, even on a listener with every role
+        assert!(listeners[1].roles.allows(admin::OP_ADMIN_PING));
+        assert!(!listeners[1].roles.allows(admin::OP_ADMIN_LIST_CONNECTIONS));
+        assert!(!listeners[1].roles.allows(admin::OP_ADMIN_KICK));
+        let tools = parse_listeners("tools=127.0.0.1:9001/connections,all", "unused").unwrap();
+        assert_eq!(tools[0].roles, Roles { connections: true, ..Roles::ALL });
+        assert!(tools[0].roles.allows(admin::OP_ADMIN_KICK));
+        // ...and still needs the admin role
+        assert!(!"connections".parse::<Roles>().unwrap().allows(admin::OP_ADMIN_KICK));
+    }
+
+    #[test]
+    fn invalid_listeners_are_rejected() {
+        assert!(parse_listeners("0.0.0.0:9000", "unused").is_err());
+        assert!(parse_listeners("a=0.0.0.0:9000/mqtt", "unused").is_err());
+        assert!(parse_listeners("a=0.0.0.0:9000;a=0.0.0.0:9001", "unused").is_err());
+    }
+
+    #[test]
+    fn rejections_are_logged_once_per_interval() {
+        let mut log = RejectLog::default();
+        let start = Instant::now();
+        assert_eq!(log.record(start), Some(0));
+        assert_eq!(log.record(start + Duration::from_secs(1)), None);
+        assert_eq!(log.record(start + Duration::from_secs(2)), None);
+        assert_eq!(log.record(start + REJECT_LOG_INTERVAL), Some(2));
+    }
+
+    #[test]
+    fn unix_listener_is_optional() {
+        assert_eq!(unix_listener("", "", "").unwrap(), None);
+        let listener = unix_listener("/run/nexo.sock", "admin", "0, 1000").unwrap().unwrap();
+        assert_eq!(listener.roles, Roles { admin: true, ..Roles::NONE });
+        assert_eq!(listener.transport, Transport::Unix { allowed_uids: vec![0, 1000] });
+        assert!(unix_listener("/run/nexo.sock", "", "root").is_err());
+    }
+}
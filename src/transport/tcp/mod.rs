@@ -1,3 +1,7 @@
+pub mod admin;
+pub mod admission;
 pub mod connection;
 pub mod dispatcher;
+pub mod listener;
 pub mod protocol;
+pub mod registry;
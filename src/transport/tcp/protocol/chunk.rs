@@ -0,0 +1,298 @@
+//! Chunked messages: payloads larger than one frame, sent as BEGIN/CHUNK/END
+//! frames sharing the message's correlation id (layout in `frame.rs`).
+//!
+//! Inbound, `Reassembler` buffers the chunks of each request and hands the
+//! connection one ordinary request frame once END arrives and both checksums
+//! match. Outbound, `split` turns a large response or push into a chunked
+//! message; the socket writer feeds the chunks one at a time so the output
+//! buffer never holds the whole payload.
+
+use std::collections::HashMap;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::config::Config;
+use crate::transport::tcp::protocol::limits::max_message_size;
+use super::errors::{ErrorCode, NexoError};
+use super::frame::{
+    FrameHeader, InboundFrame, OutboundFrame, Response, STATUS_DATA, TYPE_CHUNK, TYPE_CHUNK_BEGIN,
+    TYPE_CHUNK_END, TYPE_PUSH_PUBSUB, TYPE_PUSH_QUEUE, TYPE_REQUEST, TYPE_RESPONSE,
+};
+
+/// Chunked requests a connection may have open at once
+const MAX_PENDING_MESSAGES: usize = 16;
+/// Reassembly buffers start this small and grow as chunks arrive: the size a
+/// BEGIN declares is only a claim until the data is there
+const INITIAL_BUFFER: usize = 64 * 1024;
+
+/// Splits a response or push whose payload exceeds `chunk_size` into a chunked
+/// message. Returns `None` when the frame fits and can be sent as is.
+pub fn split(frame: &OutboundFrame, chunk_size: usize) -> Option<Vec<OutboundFrame>> {
+    let (id, frame_type, meta, payload) = match frame {
+        OutboundFrame::Response { id, response: Response::Data(data) } => (*id, TYPE_RESPONSE, STATUS_DATA, data),
+        OutboundFrame::PushPubSub { id, flags, payload } => (*id, TYPE_PUSH_PUBSUB, *flags, payload),
+        OutboundFrame::PushQueue { id, flags, payload } => (*id, TYPE_PUSH_QUEUE, *flags, payload),
+        _ => return None,
+    };
+    let chunk_size = chunk_size.max(1);
+    if payload.len() <= chunk_size {
+        return None;
+    }
+
+    let mut parts = Vec::with_capacity(payload.len().div_ceil(chunk_size) + 2);
+    parts.push(OutboundFrame::ChunkBegin { id, frame_type, meta, total_len: payload.len() as u32 });
+    let mut offset = 0;
+    while offset < payload.len() {
+        let end = (offset + chunk_size).min(payload.len());
+        parts.push(OutboundFrame::Chunk { id, data: payload.slice(offset..end) });
+        offset = end;
+    }
+    parts.push(OutboundFrame::ChunkEnd { id, checksum: crc32fast::hash(payload) });
+    Some(parts)
+}
+
+struct PartialMessage {
+    opcode: u8,
+    total_len: usize,
+    data: BytesMut,
+    hasher: crc32fast::Hasher,
+    /// Already answered with an error: remaining chunks are dropped until END
+    failed: bool,
+}
+
+/// Per-connection reassembly of chunked requests.
+#[derive(Default)]
+pub struct Reassembler {
+    pending: HashMap<u32, PartialMessage>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one decoded frame. Plain frames pass through; chunk frames are
+    /// buffered and END yields the reassembled request. A broken message
+    /// yields a single rejection frame (answered with its id), and the rest of
+    /// its chunks are dropped.
+    pub fn accept(&mut self, frame: InboundFrame) -> Option<InboundFrame> {
+        let id = frame.header.id();
+        match frame.header.frame_type {
+            TYPE_CHUNK_BEGIN => self.begin(frame),
+            TYPE_CHUNK => self.chunk(frame),
+            TYPE_CHUNK_END => {
+                let partial = self.pending.remove(&id)?;
+                Self::end(frame, partial)
+            }
+            _ => Some(frame),
+        }
+    }
+
+    fn begin(&mut self, mut frame: InboundFrame) -> Option<InboundFrame> {
+        let id = frame.header.id();
+        let opcode = frame.header.meta;
+        if let Some(reason) = frame.rejection.take() {
+            return Some(rejection(id, opcode, reason));
+        }
+        if self.pending.contains_key(&id) {
+            return Some(rejection(id, opcode, NexoError::invalid(format!("Chunked message {} already open", id))));
+        }
+        if self.pending.len() >= MAX_PENDING_MESSAGES {
+            return Some(rejection(id, opcode, NexoError::invalid(format!(
+                "Too many chunked messages in flight (max: {})", MAX_PENDING_MESSAGES
+            ))));
+        }
+        if frame.payload.len() != 1 + 4 {
+            return Some(rejection(id, opcode, NexoError::invalid("Malformed chunk BEGIN frame")));
+        }
+
+        let frame_type = frame.payload.get_u8();
+        let total_len = frame.payload.get_u32() as usize;
+        let max = max_message_size(Config::global(), opcode);
+        let (data, failed, reply) = if frame_type != TYPE_REQUEST {
+            (BytesMut::new(), true, Some(NexoError::invalid("Only requests can be chunked")))
+        } else if total_len > max {
+            let reason = NexoError::new(
+                ErrorCode::PayloadTooLarge,
+                format!("Message too large: {} bytes (max: {})", total_len, max),
+            );
+            (BytesMut::new(), true, Some(reason))
+        } else {
+            (BytesMut::with_capacity(total_len.min(INITIAL_BUFFER)), false, None)
+        };
+
+        self.pending.insert(id, PartialMessage { opcode, total_len, data, hasher: crc32fast::Hasher::new(), failed });
+        reply.map(|reason| rejection(id, opcode, reason))
+    }
+
+    fn chunk(&mut self, frame: InboundFrame) -> Option<InboundFrame> {
+        let id = frame.header.id();
+        let Some(partial) = self.pending.get_mut(&id) else {
+            return Some(rejection(id, 0, NexoError::invalid(format!("Unknown chunked message {}", id))));
+        };
+        if partial.failed {
+            return None;
+        }
+
+        let opcode = partial.opcode;
+        let reason = if let Some(reason) = frame.rejection {
+            Some(reason)
+        } else if frame.payload.len() < 4 {
+            Some(NexoError::invalid("Malformed chunk frame"))
+        } else {
+            let mut payload = frame.payload;
+            let checksum = payload.get_u32();
+            if crc32fast::hash(&payload) != checksum {
+                Some(NexoError::invalid(format!("Chunk checksum mismatch in message {}", id)))
+            } else if partial.data.len() + payload.len() > partial.total_len {
+                Some(NexoError::invalid(format!(
+                    "Chunked message {} exceeds its declared size of {} bytes", id, partial.total_len
+                )))
+            } else {
+                partial.hasher.update(&payload);
+                partial.data.extend_from_slice(&payload);
+                None
+            }
+        };
+
+        let reason = reason?;
+        partial.failed = true;
+        partial.data = BytesMut::new();
+        Some(rejection(id, opcode, reason))
+    }
+
+    fn end(frame: InboundFrame, partial: PartialMessage) -> Option<InboundFrame> {
+        let id = frame.header.id();
+        if partial.failed {
+            return None;
+        }
+        if partial.data.len() != partial.total_len {
+            return Some(rejection(id, partial.opcode, NexoError::invalid(format!(
+                "Chunked message {} ended after {} of {} bytes", id, partial.data.len(), partial.total_len
+            ))));
+        }
+        let checksum = frame.payload.get(..4).map(|mut b| b.get_u32());
+        if checksum != Some(partial.hasher.finalize()) {
+            return Some(rejection(id, partial.opcode, NexoError::invalid(format!("Checksum mismatch in message {}", id))));
+        }
+
+        Some(InboundFrame {
+            header: FrameHeader::new(TYPE_REQUEST, partial.opcode, id, partial.total_len as u32),
+            payload: partial.data.freeze(),
+            rejection: None,
+        })
+    }
+}
+
+fn rejection(id: u32, opcode: u8, reason: NexoError) -> InboundFrame {
+    InboundFrame {
+        header: FrameHeader::new(TYPE_REQUEST, opcode, id, 0),
+        payload: Bytes::new(),
+        rejection: Some(reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::transport::tcp::protocol::codec::NexoCodec;
+
+    const TEST_ID: u32 = 7;
+    const OPCODE: u8 = 0x21;
+
+    /// Encodes `frames` and decodes them back, as the server would read them.
+    fn wire(frames: Vec<OutboundFrame>) -> Vec<InboundFrame> {
+        let mut codec = NexoCodec::new();
+        let mut buf = BytesMut::new();
+        for frame in frames {
+            codec.encode(frame, &mut buf).unwrap();
+        }
+        std::iter::from_fn(|| codec.decode(&mut buf).unwrap()).collect()
+    }
+
+    fn chunked_request(id: u32, payload: &Bytes, chunk_size: usize) -> Vec<OutboundFrame> {
+        let push = OutboundFrame::PushPubSub { id, flags: 0, payload: payload.clone() };
+        let mut parts = split(&push, chunk_size).expect("payload should be chunked");
+        parts[0] = OutboundFrame::ChunkBegin { id, frame_type: TYPE_REQUEST, meta: OPCODE, total_len: payload.len() as u32 };
+        parts
+    }
+
+    #[test]
+    fn small_frames_are_not_split() {
+        let frame = OutboundFrame::PushPubSub { id: 0, flags: 0, payload: Bytes::from_static(b"small") };
+        assert!(split(&frame, 1024).is_none());
+        let frame = OutboundFrame::Response { id: TEST_ID, response: Response::Ok };
+        assert!(split(&frame, 0).is_none());
+    }
+
+    #[test]
+    fn chunked_request_is_reassembled() {
+        let payload: Bytes = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>().into();
+        let mut reassembler = Reassembler::new();
+
+        let mut out = Vec::new();
+        for frame in wire(chunked_request(TEST_ID, &payload, 1024)) {
+            out.extend(reassembler.accept(frame));
+        }
+
+        assert_eq!(out.len(), 1);
+        let request = &out[0];
+        assert!(request.rejection.is_none());
+        assert_eq!(request.header.frame_type, TYPE_REQUEST);
+        assert_eq!(request.header.meta, OPCODE);
+        assert_eq!(request.header.id(), TEST_ID);
+        assert_eq!(request.payload, payload);
+        assert!(reassembler.pending.is_empty());
+    }
+
+    #[test]
+    fn corrupted_chunk_is_rejected_once() {
+        let payload = Bytes::from(vec![1u8; 4096]);
+        let mut frames = wire(chunked_request(TEST_ID, &payload, 1024));
+        // Flip a data byte of the second chunk after its checksum was written
+        let mut corrupted = BytesMut::from(&frames[2].payload[..]);
+        corrupted[10] ^= 0xFF;
+        frames[2].payload = corrupted.freeze();
+
+        let mut reassembler = Reassembler::new();
+        let out: Vec<_> = frames.into_iter().filter_map(|f| reassembler.accept(f)).collect();
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].header.id(), TEST_ID);
+        assert!(out[0].rejection.as_ref().unwrap().message.contains("checksum"));
+        assert!(reassembler.pending.is_empty());
+    }
+
+    #[test]
+    fn oversized_message_is_rejected_at_begin() {
+        let max = max_message_size(Config::global(), OPCODE);
+        let begin = OutboundFrame::ChunkBegin { id: TEST_ID, frame_type: TYPE_REQUEST, meta: OPCODE, total_len: (max + 1) as u32 };
+        let chunk = OutboundFrame::Chunk { id: TEST_ID, data: Bytes::from_static(b"ignored") };
+        let end = OutboundFrame::ChunkEnd { id: TEST_ID, checksum: 0 };
+
+        let mut reassembler = Reassembler::new();
+        let out: Vec<_> = wire(vec![begin, chunk, end]).into_iter().filter_map(|f| reassembler.accept(f)).collect();
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].rejection.as_ref().unwrap().code, ErrorCode::PayloadTooLarge);
+    }
+
+    #[test]
+    fn declared_size_is_not_allocated_up_front() {
+        let max = max_message_size(Config::global(), OPCODE);
+        let begin = OutboundFrame::ChunkBegin { id: TEST_ID, frame_type: TYPE_REQUEST, meta: OPCODE, total_len: max as u32 };
+        let mut reassembler = Reassembler::new();
+        assert!(wire(vec![begin]).into_iter().filter_map(|f| reassembler.accept(f)).next().is_none());
+        assert!(reassembler.pending[&TEST_ID].data.capacity() <= INITIAL_BUFFER);
+    }
+
+    #[test]
+    fn plain_frames_pass_through() {
+        let mut frames = wire(vec![OutboundFrame::Response { id: TEST_ID, response: Response::Data(Bytes::from_static(b"x")) }]);
+        let mut reassembler = Reassembler::new();
+        let frame = reassembler.accept(frames.remove(0)).expect("plain frame passes through");
+        assert_eq!(frame.payload, Bytes::from_static(b"x"));
+    }
+}
@@ -1,19 +1,54 @@
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
 use crate::config::Config;
-use super::errors::ParseError;
+use crate::transport::tcp::protocol::limits::max_payload_size;
+use super::errors::{wire_code, ErrorCode, NexoError, ParseError};
+use super::pool::BufferPool;
 use super::frame::{
     FrameHeader, InboundFrame, OutboundFrame, Response, STATUS_DATA, STATUS_ERR, STATUS_NULL,
-    STATUS_OK, TYPE_PUSH_PUBSUB, TYPE_RESPONSE,
+    STATUS_OK, TYPE_CHUNK, TYPE_CHUNK_BEGIN, TYPE_CHUNK_END, TYPE_PUSH_PUBSUB, TYPE_PUSH_QUEUE, TYPE_RESPONSE,
 };
 
-#[derive(Debug, Default)]
-pub struct NexoCodec;
+/// Free space below which the read buffer is swapped for a pooled one
+const MIN_READ_SPACE: usize = 1024;
+
+#[derive(Default)]
+pub struct NexoCodec {
+    /// Bytes of a rejected oversized payload still to be dropped from the socket
+    discard_remaining: usize,
+    pool: Option<BufferPool>,
+    /// The current read buffer was sized for one large frame: dropped, not pooled, once retired
+    oversized: bool,
+}
 
 impl NexoCodec {
     pub fn new() -> Self {
-        Self
+        Self::default()
+    }
+
+    /// Decoder that reads into buffers recycled through `pool`.
+    pub fn pooled(pool: BufferPool) -> Self {
+        Self { pool: Some(pool), ..Self::default() }
+    }
+
+    /// Before asking for more bytes: if `src` cannot hold the rest of the next
+    /// frame (`frame_len` bytes, header included), move its unread bytes into
+    /// a pooled buffer so the reader does not allocate.
+    fn make_room(&mut self, src: &mut BytesMut, frame_len: usize) {
+        let Some(pool) = self.pool.as_mut() else { return };
+        if src.capacity() >= frame_len && src.capacity() - src.len() >= MIN_READ_SPACE {
+            return;
+        }
+
+        let want = frame_len + MIN_READ_SPACE;
+        let mut fresh = pool.take(want);
+        fresh.extend_from_slice(src);
+        let retired = std::mem::replace(src, fresh);
+        if !self.oversized {
+            pool.give(retired);
+        }
+        self.oversized = want > pool.buffer_size();
     }
 }
 
@@ -22,7 +57,18 @@ impl Decoder for NexoCodec {
     type Error = ParseError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.discard_remaining > 0 {
+            let skipped = self.discard_remaining.min(src.len());
+            src.advance(skipped);
+            self.discard_remaining -= skipped;
+            if self.discard_remaining > 0 {
+                self.make_room(src, FrameHeader::SIZE);
+                return Ok(None);
+            }
+        }
+
         if src.len() < FrameHeader::SIZE {
+            self.make_room(src, FrameHeader::SIZE);
             return Ok(None);
         }
 
@@ -36,16 +82,28 @@ impl Decoder for NexoCodec {
         };
 
         let payload_len = header_ref.payload_len() as usize;
-        let max_payload_size = Config::global().server.max_payload_size;
+        let max_payload_size = max_payload_size(Config::global(), header_ref.meta);
         if payload_len > max_payload_size {
-            return Err(ParseError::Invalid(format!(
-                "Payload too large: {} bytes (max: {})", payload_len, max_payload_size
-            )));
+            // Drop the payload without buffering it and answer with an error frame
+            let header = *header_ref;
+            src.advance(FrameHeader::SIZE);
+            let skipped = payload_len.min(src.len());
+            src.advance(skipped);
+            self.discard_remaining = payload_len - skipped;
+            return Ok(Some(InboundFrame {
+                header,
+                payload: Bytes::new(),
+                rejection: Some(NexoError::new(
+                    ErrorCode::PayloadTooLarge,
+                    format!("Payload too large: {} bytes (max: {})", payload_len, max_payload_size),
+                )),
+            }));
         }
 
         let total_len = FrameHeader::SIZE + payload_len;
 
         if src.len() < total_len {
+            self.make_room(src, total_len);
             return Ok(None);
         }
 
@@ -53,7 +111,7 @@ impl Decoder for NexoCodec {
         let frame_bytes = src.split_to(total_len).freeze();
         let payload = frame_bytes.slice(FrameHeader::SIZE..);
 
-        Ok(Some(InboundFrame { header, payload }))
+        Ok(Some(InboundFrame { header, payload, rejection: None }))
     }
 }
 
@@ -66,15 +124,23 @@ impl Encoder<OutboundFrame> for NexoCodec {
                 let (status, payload) = match response {
                     Response::Ok => (STATUS_OK, Bytes::new()),
                     Response::Null => (STATUS_NULL, Bytes::new()),
-                    Response::Error(msg) => {
-                        let mut buf = BytesMut::with_capacity(4 + msg.len());
-                        buf.put_u32(msg.len() as u32);
-                        buf.put_slice(msg.as_bytes());
-                        (STATUS_ERR, buf.freeze())
+                    Response::Error(err) => {
+                        // Written straight into the output buffer, no intermediate payload
+                        dst.reserve(FrameHeader::SIZE + 2 + 1 + 4 + err.message.len());
+                        dst.put_u8(TYPE_RESPONSE);
+                        dst.put_u8(STATUS_ERR);
+                        dst.put_u32(id);
+                        dst.put_u32((2 + 1 + 4 + err.message.len()) as u32);
+                        dst.put_u16(wire_code(err.code));
+                        dst.put_u8(err.retryable as u8);
+                        dst.put_u32(err.message.len() as u32);
+                        dst.put_slice(err.message.as_bytes());
+                        return Ok(());
                     }
                     Response::Data(data) => (STATUS_DATA, data),
                 };
 
+                dst.reserve(FrameHeader::SIZE + payload.len());
                 dst.put_u8(TYPE_RESPONSE);
                 dst.put_u8(status);
                 dst.put_u32(id);
@@ -83,14 +149,50 @@ impl Encoder<OutboundFrame> for NexoCodec {
             }
             OutboundFrame::PushPubSub {
                 id,
+                flags,
                 payload,
             } => {
+                dst.reserve(FrameHeader::SIZE + payload.len());
                 dst.put_u8(TYPE_PUSH_PUBSUB);
-                dst.put_u8(0); // meta byte unused for now
+                dst.put_u8(flags);
+                dst.put_u32(id);
+                dst.put_u32(payload.len() as u32);
+                dst.extend_from_slice(&payload);
+            }
+            OutboundFrame::PushQueue { id, flags, payload } => {
+                dst.reserve(FrameHeader::SIZE + payload.len());
+                dst.put_u8(TYPE_PUSH_QUEUE);
+                dst.put_u8(flags);
                 dst.put_u32(id);
                 dst.put_u32(payload.len() as u32);
                 dst.extend_from_slice(&payload);
             }
+            OutboundFrame::ChunkBegin { id, frame_type, meta, total_len } => {
+                dst.reserve(FrameHeader::SIZE + 1 + 4);
+                dst.put_u8(TYPE_CHUNK_BEGIN);
+                dst.put_u8(meta);
+                dst.put_u32(id);
+                dst.put_u32(1 + 4);
+                dst.put_u8(frame_type);
+                dst.put_u32(total_len);
+            }
+            OutboundFrame::Chunk { id, data } => {
+                dst.reserve(FrameHeader::SIZE + 4 + data.len());
+                dst.put_u8(TYPE_CHUNK);
+                dst.put_u8(0);
+                dst.put_u32(id);
+                dst.put_u32((4 + data.len()) as u32);
+                dst.put_u32(crc32fast::hash(&data));
+                dst.extend_from_slice(&data);
+            }
+            OutboundFrame::ChunkEnd { id, checksum } => {
+                dst.reserve(FrameHeader::SIZE + 4);
+                dst.put_u8(TYPE_CHUNK_END);
+                dst.put_u8(0);
+                dst.put_u32(id);
+                dst.put_u32(4);
+                dst.put_u32(checksum);
+            }
         }
 
         Ok(())
@@ -100,7 +202,7 @@ impl Encoder<OutboundFrame> for NexoCodec {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transport::tcp::protocol::frame::{STATUS_DATA, TYPE_RESPONSE};
+    use crate::transport::tcp::protocol::frame::{STATUS_DATA, TYPE_REQUEST, TYPE_RESPONSE};
 
     const TEST_ID: u32 = 42;
     const TEST_PAYLOAD: &[u8] = b"nexo-test-payload";
@@ -169,4 +271,110 @@ mod tests {
 
         assert!(parsed.is_none());
     }
+
+    #[test]
+    fn oversized_payload_is_rejected_and_skipped() {
+        const OPCODE: u8 = 0x31;
+        let max = max_payload_size(Config::global(), OPCODE);
+        let mut codec = NexoCodec::new();
+
+        let mut buf = BytesMut::new();
+        buf.put_u8(TYPE_REQUEST);
+        buf.put_u8(OPCODE);
+        buf.put_u32(TEST_ID);
+        buf.put_u32((max + 1) as u32);
+
+        let rejected = codec
+            .decode(&mut buf)
+            .expect("oversized frame must not be a protocol error")
+            .expect("rejection should be reported immediately");
+        assert_eq!(rejected.header.id(), TEST_ID);
+        assert!(rejected.rejection.is_some());
+        assert!(rejected.payload.is_empty());
+
+        // The oversized payload arrives later and must be dropped before the next frame
+        let mut buf = BytesMut::from(&vec![0u8; max + 1][..]);
+        buf.put_u8(TYPE_REQUEST);
+        buf.put_u8(OPCODE);
+        buf.put_u32(TEST_ID + 1);
+        buf.put_u32(TEST_PAYLOAD.len() as u32);
+        buf.put_slice(TEST_PAYLOAD);
+
+        let frame = codec
+            .decode(&mut buf)
+            .expect("decode should succeed")
+            .expect("frame should be complete");
+        assert_eq!(frame.header.id(), TEST_ID + 1);
+        assert!(frame.rejection.is_none());
+        assert_eq!(frame.payload, TEST_PAYLOAD);
+    }
+
+    fn request_frame(id: u32, payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u8(TYPE_REQUEST);
+        buf.put_u8(0x21);
+        buf.put_u32(id);
+        buf.put_u32(payload.len() as u32);
+        buf.put_slice(payload);
+        buf
+    }
+
+    #[test]
+    fn pooled_decoder_reuses_read_buffers_once_frames_are_dropped() {
+        use std::sync::Arc;
+        use crate::transport::tcp::protocol::pool::{BufferPool, PoolStats};
+
+        let stats = Arc::new(PoolStats::default());
+        let mut codec = NexoCodec::pooled(BufferPool::new(4, 4096, stats.clone()));
+        let mut src = BytesMut::new();
+        let wire = request_frame(TEST_ID, &[7u8; 500]);
+
+        // Reads land in the spare room the decoder made: the socket never has to grow the buffer
+        let read_frame = |codec: &mut NexoCodec, src: &mut BytesMut| {
+            assert!(codec.decode(src).unwrap().is_none());
+            assert!(src.capacity() - src.len() >= wire.len());
+            src.extend_from_slice(&wire);
+            codec.decode(src).unwrap().expect("frame should be complete")
+        };
+
+        // Frames kept alive pin their buffers, so new ones are allocated
+        let held: Vec<InboundFrame> = (0..40).map(|_| read_frame(&mut codec, &mut src)).collect();
+        let pinned = stats.snapshot();
+        assert_eq!(pinned.hits, 0);
+        assert!(pinned.misses >= 4);
+        drop(held);
+
+        // Once dropped, the retired buffers come back
+        for _ in 0..200 {
+            let frame = read_frame(&mut codec, &mut src);
+            assert_eq!(frame.payload.len(), 500);
+        }
+        let reused = stats.snapshot();
+        assert!(reused.hits > 0);
+        assert_eq!(reused.misses, pinned.misses, "no allocation once buffers are free");
+    }
+
+    #[test]
+    fn error_response_carries_code_and_retryable_flag() {
+        let mut codec = NexoCodec::new();
+        let mut encoded = BytesMut::new();
+        let err = NexoError::new(ErrorCode::Fenced, "stale");
+
+        codec
+            .encode(
+                OutboundFrame::Response {
+                    id: TEST_ID,
+                    response: Response::Error(err),
+                },
+                &mut encoded,
+            )
+            .expect("encode should succeed");
+
+        let mut payload = encoded.split_off(FrameHeader::SIZE);
+        assert_eq!(encoded[1], STATUS_ERR);
+        assert_eq!(payload.get_u16(), wire_code(ErrorCode::Fenced));
+        assert_eq!(payload.get_u8(), 1);
+        assert_eq!(payload.get_u32(), 5);
+        assert_eq!(&payload[..], b"stale");
+    }
 }
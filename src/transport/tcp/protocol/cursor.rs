@@ -21,6 +21,13 @@ impl PayloadCursor {
         Ok(self.data.get_u8())
     }
 
+    pub fn read_u16(&mut self) -> Result<u16, ParseError> {
+        if !self.has_remaining(2) {
+            return Err(ParseError::Invalid("Payload too short for u16".into()));
+        }
+        Ok(self.data.get_u16())
+    }
+
     pub fn read_u32(&mut self) -> Result<u32, ParseError> {
         if !self.has_remaining(4) {
             return Err(ParseError::Invalid("Payload too short for u32".into()));
@@ -45,6 +52,15 @@ impl PayloadCursor {
         Ok(s.to_string())
     }
 
+    /// Read a `u32`-length-prefixed byte blob.
+    pub fn read_bytes(&mut self) -> Result<Bytes, ParseError> {
+        let len = self.read_u32()? as usize;
+        if !self.has_remaining(len) {
+            return Err(ParseError::Invalid(format!("Incomplete bytes: expected {} bytes", len)));
+        }
+        Ok(self.data.copy_to_bytes(len))
+    }
+
     pub fn read_remaining(&mut self) -> Bytes {
         let len = self.data.remaining();
         self.data.copy_to_bytes(len)
@@ -1,12 +1,14 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParseError {
     Invalid(String),
+    /// A resource name breaking `brokers::names` rules
+    InvalidName(String),
 }
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParseError::Invalid(msg) => write!(f, "{}", msg),
+            ParseError::Invalid(msg) | ParseError::InvalidName(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -18,3 +20,42 @@ impl From<std::io::Error> for ParseError {
         ParseError::Invalid(error.to_string())
     }
 }
+
+// ========================================
+// ERROR CODES (shared by all brokers)
+// ========================================
+
+pub use crate::brokers::error::{ErrorCode, NexoError};
+
+/// Numeric code of `code` in error frames:
+/// [Code: u16] [Retryable: u8] [MessageLen: u32] [Message...]
+pub fn wire_code(code: ErrorCode) -> u16 {
+    match code {
+        ErrorCode::InvalidRequest => 0x0001,
+        ErrorCode::UnknownOpcode => 0x0002,
+        ErrorCode::PayloadTooLarge => 0x0003,
+        ErrorCode::SchemaViolation => 0x0004,
+        ErrorCode::Forbidden => 0x0005,
+        ErrorCode::InvalidName => 0x0006,
+        ErrorCode::NotFound => 0x0100,
+        ErrorCode::Conflict => 0x0101,
+        ErrorCode::Fenced => 0x0200,
+        ErrorCode::NotMember => 0x0201,
+        ErrorCode::NotOwner => 0x0202,
+        ErrorCode::NotPending => 0x0203,
+        ErrorCode::Rebalance => 0x0204,
+        ErrorCode::Storage => 0x0300,
+        ErrorCode::Busy => 0x0301,
+        ErrorCode::TooManyConnections => 0x0302,
+        ErrorCode::Internal => 0x03FF,
+    }
+}
+
+impl From<ParseError> for NexoError {
+    fn from(error: ParseError) -> Self {
+        match error {
+            ParseError::Invalid(msg) => NexoError::invalid(msg),
+            ParseError::InvalidName(msg) => NexoError::new(ErrorCode::InvalidName, msg),
+        }
+    }
+}
@@ -9,21 +9,38 @@
 //! Payload: [Data...]
 //!
 //! Push Frame (Total Header: 10 bytes):
-//! [FrameType: 1 byte] [Meta/PushType: 1 byte] [CorrelationID: 4 bytes (BE)] [PayloadLen: 4 bytes (BE)]
-//! Payload: [Data...]
+//! [FrameType: 1 byte] [Meta/PushFlags: 1 byte] [CorrelationID: 4 bytes (BE)] [PayloadLen: 4 bytes (BE)]
+//! Payload: [SubscriptionIds (if flagged)] [TopicLen: 4 bytes (BE)] [Topic] [Data...]
+//! SubscriptionIds: [Count: 2 bytes (BE)] [Id: 4 bytes (BE)]...
+//!
+//! Queue Push Frame (Total Header: 10 bytes), delivering to a push subscription:
+//! [0x07] [0] [CorrelationID: 0] [PayloadLen: 4 bytes (BE)]
+//! Payload: [QNameLen: 4 bytes (BE)] [QName] [Count: 4 bytes (BE)] {[Id: 16] [Len: 4 bytes (BE)] [Data...]}
 //!
 //! Data Structure (auto-contained):
 //! [DataType: 1 byte] [Data...]
+//!
+//! Chunked Message (a request, response or push too large for one frame), every
+//! frame carrying the CorrelationID of the message:
+//! BEGIN: [0x04] [Meta of the message] Payload: [FrameType of the message: 1 byte] [TotalLen: 4 bytes (BE)]
+//! CHUNK: [0x05] [0]                   Payload: [CRC32 of Data: 4 bytes (BE)] [Data...]
+//! END:   [0x06] [0]                   Payload: [CRC32 of the whole message: 4 bytes (BE)]
 
 use bytes::Bytes;
 use bytemuck::{Pod, Zeroable};
 
+use super::errors::NexoError;
+
 // ========================================
 // FRAME TYPES
 // ========================================
 pub const TYPE_REQUEST: u8 = 0x01;
 pub const TYPE_RESPONSE: u8 = 0x02;
 pub const TYPE_PUSH_PUBSUB: u8 = 0x03;
+pub const TYPE_CHUNK_BEGIN: u8 = 0x04;
+pub const TYPE_CHUNK: u8 = 0x05;
+pub const TYPE_CHUNK_END: u8 = 0x06;
+pub const TYPE_PUSH_QUEUE: u8 = 0x07;
 
 // ========================================
 // RESPONSE STATUS (Meta byte for Response frames)
@@ -33,12 +50,28 @@ pub const STATUS_ERR: u8 = 0x01;
 pub const STATUS_NULL: u8 = 0x02;
 pub const STATUS_DATA: u8 = 0x03;
 
+// ========================================
+// PUSH FLAGS (Meta byte for push frames)
+// ========================================
+/// The message is retained (replayed on subscribe, or retain-as-published)
+pub const PUSH_FLAG_RETAIN: u8 = 0x01;
+/// The payload starts with the ids of the subscriptions that matched
+pub const PUSH_FLAG_SUBSCRIPTION_IDS: u8 = 0x02;
+/// Not a message: the server dropped a subscription (idle TTL), payload
+/// `[Pattern or QName:string][Reason:string]` (pub/sub and queue pushes)
+pub const PUSH_FLAG_UNSUBSCRIBED: u8 = 0x04;
+
 // ========================================
 // DATA TYPE FLAGS (First byte of data payload)
 // ========================================
 pub const DATA_TYPE_RAW: u8 = 0x00;
 pub const DATA_TYPE_STRING: u8 = 0x01;
 pub const DATA_TYPE_JSON: u8 = 0x02;
+/// Queue payload offloaded to the blob store (see `brokers::queue::blob`)
+pub const DATA_TYPE_BLOB_REF: u8 = 0x03;
+/// Opaque to the brokers: only the dashboard and schema validation decode these
+pub const DATA_TYPE_MSGPACK: u8 = 0x04;
+pub const DATA_TYPE_PROTOBUF: u8 = 0x05;
 
 // ========================================
 // FRAME HEADER
@@ -64,6 +97,10 @@ impl FrameHeader {
     pub fn payload_len(&self) -> u32 {
         u32::from_be_bytes(self.payload_len)
     }
+
+    pub fn new(frame_type: u8, meta: u8, id: u32, payload_len: u32) -> Self {
+        Self { frame_type, meta, id: id.to_be_bytes(), payload_len: payload_len.to_be_bytes() }
+    }
 }
 
 // ========================================
@@ -75,13 +112,38 @@ impl FrameHeader {
 pub struct InboundFrame {
     pub header: FrameHeader,
     pub payload: Bytes,
+    /// Set when the payload exceeded its size limit and was dropped unread
+    pub rejection: Option<NexoError>,
 }
 
 /// Outbound frame: encoded by NexoCodec to the socket
 #[derive(Debug)]
 pub enum OutboundFrame {
     Response { id: u32, response: Response },
-    PushPubSub { id: u32, payload: Bytes },
+    PushPubSub { id: u32, flags: u8, payload: Bytes },
+    PushQueue { id: u32, flags: u8, payload: Bytes },
+    /// Opens a chunked message: `frame_type`/`meta` are those of the frame it replaces
+    ChunkBegin { id: u32, frame_type: u8, meta: u8, total_len: u32 },
+    Chunk { id: u32, data: Bytes },
+    ChunkEnd { id: u32, checksum: u32 },
+}
+
+impl OutboundFrame {
+    /// Encoded size on the wire, header included.
+    pub fn wire_len(&self) -> usize {
+        let payload_len = match self {
+            OutboundFrame::Response { response, .. } => match response {
+                Response::Ok | Response::Null => 0,
+                Response::Data(data) => data.len(),
+                Response::Error(err) => 2 + 1 + 4 + err.message.len(),
+            },
+            OutboundFrame::PushPubSub { payload, .. } | OutboundFrame::PushQueue { payload, .. } => payload.len(),
+            OutboundFrame::ChunkBegin { .. } => 1 + 4,
+            OutboundFrame::Chunk { data, .. } => 4 + data.len(),
+            OutboundFrame::ChunkEnd { .. } => 4,
+        };
+        FrameHeader::SIZE + payload_len
+    }
 }
 
 /// Represents a response to be sent back
@@ -89,6 +151,6 @@ pub enum OutboundFrame {
 pub enum Response {
     Ok,
     Data(Bytes),
-    Error(String),
+    Error(NexoError),
     Null,
 }
@@ -0,0 +1,27 @@
+//! Frame size limits: how large a request payload, or a chunked message, may
+//! be for a given opcode.
+
+use crate::brokers::{pub_sub, queue, store, stream};
+use crate::config::Config;
+
+/// Largest request payload accepted for `opcode`: the owning broker's limit,
+/// capped by the server-wide frame limit.
+pub fn max_payload_size(config: &Config, opcode: u8) -> usize {
+    broker_limit(config, opcode).unwrap_or(usize::MAX).min(config.server.max_payload_size)
+}
+
+/// Largest request accepted for `opcode` as a chunked message: the owning
+/// broker's limit, capped by the server-wide message limit.
+pub fn max_message_size(config: &Config, opcode: u8) -> usize {
+    broker_limit(config, opcode).unwrap_or(config.server.max_payload_size).min(config.server.max_message_size)
+}
+
+fn broker_limit(config: &Config, opcode: u8) -> Option<usize> {
+    match opcode {
+        op if store::tcp::owns(op) => Some(config.store.max_payload_bytes),
+        op if queue::tcp::owns(op) => Some(config.queue.max_payload_bytes),
+        op if (pub_sub::tcp::OPCODE_MIN..=pub_sub::tcp::OPCODE_MAX).contains(&op) => Some(config.pubsub.max_payload_bytes),
+        op if (stream::tcp::OPCODE_MIN..=stream::tcp::OPCODE_MAX).contains(&op) => Some(config.stream.max_payload_bytes),
+        _ => None,
+    }
+}
@@ -1,7 +1,10 @@
+pub mod chunk;
 pub mod codec;
 pub mod errors;
 pub mod frame;
+pub mod limits;
 pub mod cursor;
+pub mod pool;
 pub mod traits;
 
 pub use codec::*;
@@ -0,0 +1,94 @@
+//! Per-connection pool of read buffers for `NexoCodec`.
+//!
+//! Decoded payloads are zero-copy slices of the socket read buffer, so a
+//! buffer stays pinned while any of its payloads is alive (a publish being
+//! fanned out, a message waiting in a queue). Left alone, the reader allocates
+//! a fresh buffer every time the current one fills up. The pool keeps a small
+//! ring of retired buffers and hands one back as soon as every frame sliced
+//! from it has been dropped (`BytesMut::try_reclaim`).
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::BytesMut;
+
+/// Counters shared with the connection registry (`/api/connections`).
+#[derive(Debug, Default)]
+pub struct PoolStats {
+    /// Retired buffers reused for a new read
+    hits: AtomicU64,
+    /// Buffers allocated because none in the ring was free
+    misses: AtomicU64,
+    /// Retired buffers dropped because the ring was full
+    discarded: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub discarded: u64,
+}
+
+impl PoolStats {
+    pub fn snapshot(&self) -> PoolSnapshot {
+        PoolSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub struct BufferPool {
+    ring: VecDeque<BytesMut>,
+    max_buffers: usize,
+    buffer_size: usize,
+    stats: Arc<PoolStats>,
+}
+
+impl BufferPool {
+    /// `max_buffers = 0` disables reuse: every buffer is a fresh allocation.
+    pub fn new(max_buffers: usize, buffer_size: usize, stats: Arc<PoolStats>) -> Self {
+        Self {
+            ring: VecDeque::with_capacity(max_buffers),
+            max_buffers,
+            buffer_size: buffer_size.max(1),
+            stats,
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// An empty buffer with room for at least `min` bytes, reclaimed from the
+    /// ring when one of its buffers is no longer referenced.
+    pub fn take(&mut self, min: usize) -> BytesMut {
+        let want = min.max(self.buffer_size);
+        for _ in 0..self.ring.len() {
+            let Some(mut buf) = self.ring.pop_front() else { break };
+            if buf.try_reclaim(want) {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                return buf;
+            }
+            self.ring.push_back(buf);
+        }
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        BytesMut::with_capacity(want)
+    }
+
+    /// Retire a buffer whose unread bytes have been copied out.
+    pub fn give(&mut self, mut buf: BytesMut) {
+        if self.max_buffers == 0 {
+            return;
+        }
+        buf.clear();
+        if self.ring.len() >= self.max_buffers {
+            self.ring.pop_front();
+            self.stats.discarded.fetch_add(1, Ordering::Relaxed);
+        }
+        self.ring.push_back(buf);
+    }
+}
@@ -0,0 +1,223 @@
+//! Connection Registry: live view of every TCP session.
+//!
+//! Each connection registers on accept and unregisters on close. Broker
+//! handlers record subscriptions, consumer groups and semaphore leases through
+//! it, the socket task records traffic and the session records each request's
+//! outcome and latency. Admin surfaces list sessions or kick one.
+
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use dashmap::DashMap;
+use tokio_util::sync::CancellationToken;
+
+use crate::transport::tcp::protocol::pool::{PoolSnapshot, PoolStats};
+
+pub struct ConnectionHandle {
+    pub id: String,
+    pub peer_addr: String,
+    pub connected_at: SystemTime,
+    frames_in: AtomicU64,
+    frames_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    errors: AtomicU64,
+    max_latency_us: AtomicU64,
+    slow_requests: AtomicU64,
+    read_pool: Arc<PoolStats>,
+    subscriptions: Mutex<BTreeSet<String>>,
+    groups: Mutex<BTreeSet<String>>,
+    semaphores: Mutex<BTreeSet<String>>,
+    kick: CancellationToken,
+}
+
+impl ConnectionHandle {
+    pub fn record_inbound(&self, bytes: usize) {
+        self.frames_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_outbound(&self, bytes: usize) {
+        self.frames_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A request answered after `latency`; long polls are left out of the
+    /// latency (they wait by design) but still count their errors.
+    pub fn record_request(&self, latency: Option<Duration>, failed: bool, slow: bool) {
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(latency) = latency {
+            self.max_latency_us.fetch_max(latency.as_micros() as u64, Ordering::Relaxed);
+        }
+        if slow {
+            self.slow_requests.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counters of the connection's read-buffer pool, updated by its codec.
+    pub fn read_pool(&self) -> Arc<PoolStats> {
+        self.read_pool.clone()
+    }
+
+    /// Resolves when an admin kicks this connection.
+    pub fn kicked(&self) -> CancellationToken {
+        self.kick.clone()
+    }
+
+    fn lock(set: &Mutex<BTreeSet<String>>) -> MutexGuard<'_, BTreeSet<String>> {
+        set.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn snapshot(&self) -> ConnectionSnapshot {
+        ConnectionSnapshot {
+            id: self.id.clone(),
+            peer_addr: self.peer_addr.clone(),
+            connected_at: self.connected_at,
+            frames_in: self.frames_in.load(Ordering::Relaxed),
+            frames_out: self.frames_out.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            max_latency_us: self.max_latency_us.load(Ordering::Relaxed),
+            slow_requests: self.slow_requests.load(Ordering::Relaxed),
+            read_pool: self.read_pool.snapshot(),
+            subscriptions: Self::lock(&self.subscriptions).iter().cloned().collect(),
+            groups: Self::lock(&self.groups).iter().cloned().collect(),
+            semaphores: Self::lock(&self.semaphores).iter().cloned().collect(),
+        }
+    }
+}
+
+pub struct ConnectionSnapshot {
+    pub id: String,
+    pub peer_addr: String,
+    pub connected_at: SystemTime,
+    pub frames_in: u64,
+    pub frames_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Requests answered with an error
+    pub errors: u64,
+    /// Slowest request so far, long polls aside
+    pub max_latency_us: u64,
+    /// Requests over `SLOW_REQUEST_MS`
+    pub slow_requests: u64,
+    pub read_pool: PoolSnapshot,
+    pub subscriptions: Vec<String>,
+    pub groups: Vec<String>,
+    pub semaphores: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    connections: DashMap<String, Arc<ConnectionHandle>>,
+}
+
+impl ConnectionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, id: String, peer_addr: String) -> Arc<ConnectionHandle> {
+        let handle = Arc::new(ConnectionHandle {
+            id: id.clone(),
+            peer_addr,
+            connected_at: SystemTime::now(),
+            frames_in: AtomicU64::new(0),
+            frames_out: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            max_latency_us: AtomicU64::new(0),
+            slow_requests: AtomicU64::new(0),
+            read_pool: Arc::new(PoolStats::default()),
+            subscriptions: Mutex::new(BTreeSet::new()),
+            groups: Mutex::new(BTreeSet::new()),
+            semaphores: Mutex::new(BTreeSet::new()),
+            kick: CancellationToken::new(),
+        });
+        self.connections.insert(id, handle.clone());
+        handle
+    }
+
+    pub fn unregister(&self, id: &str) {
+        self.connections.remove(id);
+    }
+
+    /// Ask the connection to close. Returns false if it is not registered.
+    pub fn kick(&self, id: &str) -> bool {
+        match self.connections.get(id) {
+            Some(handle) => {
+                handle.kick.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn add_subscription(&self, id: &str, pattern: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.subscriptions).insert(pattern.to_string());
+        }
+    }
+
+    pub fn remove_subscription(&self, id: &str, pattern: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.subscriptions).remove(pattern);
+        }
+    }
+
+    /// Groups are recorded as `topic/group`.
+    pub fn add_group(&self, id: &str, topic: &str, group: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.groups).insert(format!("{}/{}", topic, group));
+        }
+    }
+
+    pub fn remove_group(&self, id: &str, topic: &str, group: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.groups).remove(&format!("{}/{}", topic, group));
+        }
+    }
+
+    pub fn add_semaphore(&self, id: &str, name: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.semaphores).insert(name.to_string());
+        }
+    }
+
+    pub fn remove_semaphore(&self, id: &str, name: &str) {
+        if let Some(handle) = self.connections.get(id) {
+            ConnectionHandle::lock(&handle.semaphores).remove(name);
+        }
+    }
+
+    /// Semaphores the connection has taken leases on, so they can be released on close.
+    pub fn semaphores(&self, id: &str) -> Vec<String> {
+        self.connections.get(id)
+            .map(|handle| ConnectionHandle::lock(&handle.semaphores).iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    pub fn get(&self, id: &str) -> Option<ConnectionSnapshot> {
+        self.connections.get(id).map(|handle| handle.snapshot())
+    }
+
+    pub fn list(&self) -> Vec<ConnectionSnapshot> {
+        let mut list: Vec<ConnectionSnapshot> = self.connections.iter().map(|entry| entry.value().snapshot()).collect();
+        list.sort_by_key(|c| c.connected_at);
+        list
+    }
+}
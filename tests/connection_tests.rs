@@ -0,0 +1,462 @@
+use nexo::transport::tcp::registry::ConnectionRegistry;
+use nexo::transport::tcp::protocol::{
+    wire_code, ErrorCode, InboundFrame, NexoCodec, OutboundFrame, STATUS_DATA, STATUS_ERR, STATUS_OK, TYPE_CHUNK,
+    TYPE_CHUNK_BEGIN, TYPE_CHUNK_END, TYPE_PUSH_PUBSUB, TYPE_REQUEST, TYPE_RESPONSE,
+};
+use bytes::{Buf, BufMut, BytesMut};
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Encoder, FramedRead};
+
+#[cfg(test)]
+mod connection_tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_tracks_session_info() {
+        let registry = ConnectionRegistry::new();
+        let handle = registry.register("conn-1".to_string(), "127.0.0.1:5000".to_string());
+
+        handle.record_inbound(30);
+        handle.record_outbound(12);
+        handle.record_outbound(8);
+        registry.add_subscription("conn-1", "sensors/#");
+        registry.add_group("conn-1", "orders", "billing");
+
+        let list = registry.list();
+        assert_eq!(list.len(), 1);
+        let conn = &list[0];
+        assert_eq!(conn.peer_addr, "127.0.0.1:5000");
+        assert_eq!((conn.frames_in, conn.bytes_in), (1, 30));
+        assert_eq!((conn.frames_out, conn.bytes_out), (2, 20));
+        assert_eq!(conn.subscriptions, vec!["sensors/#".to_string()]);
+        assert_eq!(conn.groups, vec!["orders/billing".to_string()]);
+
+        handle.record_request(Some(std::time::Duration::from_millis(3)), false, false);
+        handle.record_request(Some(std::time::Duration::from_millis(40)), true, true);
+        handle.record_request(None, true, false);
+        let conn = registry.get("conn-1").unwrap();
+        assert_eq!((conn.errors, conn.max_latency_us, conn.slow_requests), (2, 40_000, 1));
+        assert!(registry.get("unknown").is_none());
+
+        registry.remove_subscription("conn-1", "sensors/#");
+        registry.remove_group("conn-1", "orders", "billing");
+        let conn = &registry.list()[0];
+        assert!(conn.subscriptions.is_empty());
+        assert!(conn.groups.is_empty());
+
+        registry.unregister("conn-1");
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_kick_cancels_connection_token() {
+        let registry = ConnectionRegistry::new();
+        let handle = registry.register("conn-1".to_string(), "127.0.0.1:5000".to_string());
+        let kicked = handle.kicked();
+
+        assert!(!registry.kick("unknown"));
+        assert!(!kicked.is_cancelled());
+        assert!(registry.kick("conn-1"));
+        assert!(kicked.is_cancelled());
+    }
+
+    async fn start_server() -> (std::net::SocketAddr, tempfile::TempDir) {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().to_str().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.store.persistence_path = format!("{}/store", path);
+        config.pubsub.persistence_path = format!("{}/pubsub", path);
+        config.queue.persistence_path = format!("{}/queue", path);
+        config.stream.persistence_path = format!("{}/stream", path);
+        let engine = nexo::NexoEngine::new(&config).await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(nexo::transport::tcp::connection::handle_connection(socket, engine.clone()));
+            }
+        });
+        (addr, tmp)
+    }
+
+    fn string_field(buf: &mut BytesMut, value: &str) {
+        buf.put_u32(value.len() as u32);
+        buf.put_slice(value.as_bytes());
+    }
+
+    async fn next_frame<R: tokio::io::AsyncRead + Unpin>(reader: &mut FramedRead<R, NexoCodec>) -> InboundFrame {
+        reader.next().await.expect("connection closed").expect("valid frame")
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_large_publish_travels_as_chunks_both_ways() {
+        use nexo::brokers::pub_sub::tcp::{OP_PUB, OP_SUB};
+        let (addr, _tmp) = start_server().await;
+        let topic = "chunked/big";
+        // Above both CHUNK_SIZE and a 1MB frame, below the broker limit
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i: usize| (i % 251) as u8).collect();
+
+        let (sub_read, mut sub_write) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut sub_read = FramedRead::new(sub_read, NexoCodec::new());
+        let mut sub = BytesMut::new();
+        string_field(&mut sub, topic);
+        let mut frame = BytesMut::new();
+        frame.put_u8(TYPE_REQUEST);
+        frame.put_u8(OP_SUB);
+        frame.put_u32(1);
+        frame.put_u32(sub.len() as u32);
+        frame.put_slice(&sub);
+        sub_write.write_all(&frame).await.unwrap();
+        assert_eq!(next_frame(&mut sub_read).await.header.meta, STATUS_OK);
+
+        // Publisher sends the request as BEGIN / CHUNK... / END
+        let mut request = BytesMut::new();
+        string_field(&mut request, topic);
+        string_field(&mut request, "{}");
+        request.put_slice(&data);
+        let request = request.freeze();
+
+        let mut codec = NexoCodec::new();
+        let mut wire = BytesMut::new();
+        let id = 9;
+        codec.encode(OutboundFrame::ChunkBegin { id, frame_type: TYPE_REQUEST, meta: OP_PUB, total_len: request.len() as u32 }, &mut wire).unwrap();
+        for part in request.chunks(256 * 1024) {
+            codec.encode(OutboundFrame::Chunk { id, data: request.slice_ref(part) }, &mut wire).unwrap();
+        }
+        codec.encode(OutboundFrame::ChunkEnd { id, checksum: crc32fast::hash(&request) }, &mut wire).unwrap();
+
+        let (pub_read, mut pub_write) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut pub_read = FramedRead::new(pub_read, NexoCodec::new());
+        pub_write.write_all(&wire).await.unwrap();
+        let ack = next_frame(&mut pub_read).await;
+        assert_eq!((ack.header.frame_type, ack.header.meta, ack.header.id()), (TYPE_RESPONSE, STATUS_OK, id));
+
+        // Subscriber gets the push back as a chunked message
+        let mut begin = next_frame(&mut sub_read).await;
+        assert_eq!(begin.header.frame_type, TYPE_CHUNK_BEGIN);
+        assert_eq!(begin.payload.get_u8(), TYPE_PUSH_PUBSUB);
+        let total_len = begin.payload.get_u32() as usize;
+
+        let mut push = BytesMut::new();
+        let mut chunks = 0;
+        loop {
+            let mut frame = next_frame(&mut sub_read).await;
+            match frame.header.frame_type {
+                TYPE_CHUNK => {
+                    let checksum = frame.payload.get_u32();
+                    assert_eq!(crc32fast::hash(&frame.payload), checksum);
+                    push.extend_from_slice(&frame.payload);
+                    chunks += 1;
+                }
+                TYPE_CHUNK_END => {
+                    assert_eq!(frame.payload.get_u32(), crc32fast::hash(&push));
+                    break;
+                }
+                other => panic!("unexpected frame type 0x{:02X}", other),
+            }
+        }
+        assert!(chunks > 1);
+        assert_eq!(push.len(), total_len);
+        assert_eq!(push.get_u32() as usize, topic.len());
+        assert_eq!(&push[..topic.len()], topic.as_bytes());
+        assert_eq!(&push[topic.len()..], &data[..]);
+    }
+
+    #[tokio::test]
+    async fn test_listener_serves_only_its_roles() {
+        use nexo::transport::tcp::dispatcher::OP_DEBUG_ECHO;
+        use nexo::transport::tcp::listener::Roles;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().to_str().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.store.persistence_path = format!("{}/store", path);
+        config.pubsub.persistence_path = format!("{}/pubsub", path);
+        config.queue.persistence_path = format!("{}/queue", path);
+        config.stream.persistence_path = format!("{}/stream", path);
+        let engine = nexo::NexoEngine::new(&config).await;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let roles = Roles { queue: true, ..Roles::NONE };
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(nexo::transport::tcp::connection::handle_listener_connection(socket, engine.clone(), roles));
+            }
+        });
+
+        let (read, mut write) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut read = FramedRead::new(read, NexoCodec::new());
+        let request = |opcode: u8, id: u32, payload: &[u8]| {
+            let mut frame = BytesMut::new();
+            frame.put_u8(TYPE_REQUEST);
+            frame.put_u8(opcode);
+            frame.put_u32(id);
+            frame.put_u32(payload.len() as u32);
+            frame.put_slice(payload);
+            frame
+        };
+
+        // A store opcode is refused on a queue-only listener
+        let mut exists = BytesMut::new();
+        string_field(&mut exists, "anything");
+        write.write_all(&request(nexo::brokers::store::tcp::OPCODE_MIN, 1, &exists)).await.unwrap();
+        let mut refused = next_frame(&mut read).await;
+        assert_eq!((refused.header.meta, refused.header.id()), (STATUS_ERR, 1));
+        assert_eq!(refused.payload.get_u16(), wire_code(ErrorCode::Forbidden));
+
+        // Queue opcodes and the debug echo are served
+        write.write_all(&request(nexo::brokers::queue::tcp::OP_Q_EXISTS, 2, &exists)).await.unwrap();
+        let mut missing = next_frame(&mut read).await;
+        assert_eq!(missing.header.meta, STATUS_ERR);
+        assert_eq!(missing.payload.get_u16(), wire_code(ErrorCode::NotFound));
+
+        write.write_all(&request(OP_DEBUG_ECHO, 3, b"ping")).await.unwrap();
+        let echo = next_frame(&mut read).await;
+        assert_eq!((echo.header.meta, &echo.payload[..]), (STATUS_DATA, &b"ping"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_admin_ping_reports_readiness() {
+        use nexo::brokers::queue::options::QueueCreateOptions;
+        use nexo::transport::tcp::admin::OP_ADMIN_PING;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().to_str().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.store.persistence_path = format!("{}/store", path);
+        config.pubsub.persistence_path = format!("{}/pubsub", path);
+        config.queue.persistence_path = format!("{}/queue", path);
+        config.stream.persistence_path = format!("{}/stream", path);
+        let engine = nexo::NexoEngine::new(&config).await;
+        engine.queue.create_queue("jobs".to_string(), QueueCreateOptions::default()).await.unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = engine.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(nexo::transport::tcp::connection::handle_connection(socket, served.clone()));
+            }
+        });
+
+        let (read, mut write) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut read = FramedRead::new(read, NexoCodec::new());
+        let mut frame = BytesMut::new();
+        frame.put_u8(TYPE_REQUEST);
+        frame.put_u8(OP_ADMIN_PING);
+        frame.put_u32(1);
+        frame.put_u32(0);
+        write.write_all(&frame).await.unwrap();
+        let reply = next_frame(&mut read).await;
+        assert_eq!(reply.header.meta, STATUS_DATA);
+        let report: serde_json::Value = serde_json::from_slice(&reply.payload).unwrap();
+        assert_eq!(report["ready"], true, "{}", report);
+        let checks: Vec<&str> = report["checks"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+        assert_eq!(checks, ["recovery", "persistence", "disk", "memory"]);
+
+        // A stopped queue writer makes the engine unready
+        engine.queue.close().await;
+        let readiness = engine.readiness().await;
+        assert!(!readiness.ready);
+        let persistence = readiness.checks.iter().find(|c| c.name == "persistence").unwrap();
+        assert!(!persistence.ok && persistence.detail.contains("'jobs'"), "{}", persistence.detail);
+    }
+
+    #[tokio::test]
+    async fn test_admin_connection_stats() {
+        use nexo::transport::tcp::admin::OP_ADMIN_CONNECTION_STATS;
+
+        let (addr, _tmp) = start_server().await;
+        let (read, mut write) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut read = FramedRead::new(read, NexoCodec::new());
+        let request = |opcode: u8, id: u32, payload: &[u8]| {
+            let mut frame = BytesMut::new();
+            frame.put_u8(TYPE_REQUEST);
+            frame.put_u8(opcode);
+            frame.put_u32(id);
+            frame.put_u32(payload.len() as u32);
+            frame.put_slice(payload);
+            frame
+        };
+
+        let mut missing = BytesMut::new();
+        string_field(&mut missing, "missing");
+        write.write_all(&request(nexo::brokers::queue::tcp::OP_Q_EXISTS, 1, &missing)).await.unwrap();
+        assert_eq!(next_frame(&mut read).await.header.meta, STATUS_ERR);
+
+        // Empty id: the calling connection
+        let mut own = BytesMut::new();
+        string_field(&mut own, "");
+        write.write_all(&request(OP_ADMIN_CONNECTION_STATS, 2, &own)).await.unwrap();
+        let reply = next_frame(&mut read).await;
+        assert_eq!(reply.header.meta, STATUS_DATA);
+        let stats: serde_json::Value = serde_json::from_slice(&reply.payload).unwrap();
+        assert_eq!((stats["errors"].as_u64(), stats["frames_in"].as_u64()), (Some(1), Some(2)), "{}", stats);
+        assert!(stats["max_latency_ms"].as_f64().is_some());
+
+        write.write_all(&request(OP_ADMIN_CONNECTION_STATS, 3, &missing)).await.unwrap();
+        let mut unknown = next_frame(&mut read).await;
+        assert_eq!(unknown.header.meta, STATUS_ERR);
+        assert_eq!(unknown.payload.get_u16(), wire_code(ErrorCode::NotFound));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_warm_start_reports_recovery_progress() {
+        use nexo::brokers::queue::options::QueueCreateOptions;
+        use nexo::brokers::stream::options::StreamCreateOptions;
+        use nexo::brokers::warm_start::WarmStart;
+        use std::sync::Arc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().to_str().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.store.persistence_path = format!("{}/store", path);
+        config.pubsub.persistence_path = format!("{}/pubsub", path);
+        config.queue.persistence_path = format!("{}/queue", path);
+        config.stream.persistence_path = format!("{}/stream", path);
+        config.server.recovery_concurrency = 3;
+        {
+            let engine = nexo::NexoEngine::new(&config).await;
+            for i in 0..5 {
+                let queue = format!("jobs-{}", i);
+                engine.queue.create_queue(queue.clone(), QueueCreateOptions::default()).await.unwrap();
+                for _ in 0..2 {
+                    engine.queue.push(queue.clone(), bytes::Bytes::from("job"), 0).await.unwrap();
+                }
+            }
+            for i in 0..2 {
+                let topic = format!("events-{}", i);
+                engine.stream.create_topic(topic.clone(), StreamCreateOptions::default()).await.unwrap();
+                for _ in 0..3 {
+                    engine.stream.publish(&topic, bytes::Bytes::from("event")).await.unwrap();
+                }
+            }
+            engine.close().await;
+        }
+
+        let warm_start = Arc::new(WarmStart::new(config.server.recovery_concurrency));
+        assert!(!warm_start.is_done());
+        let engine = nexo::NexoEngine::with_warm_start(&config, warm_start.clone()).await;
+        let progress = warm_start.snapshot();
+        assert!(progress.done);
+        assert_eq!((progress.queues.discovered, progress.queues.recovered, progress.queues.messages), (5, 5, 10));
+        assert_eq!((progress.topics.discovered, progress.topics.recovered, progress.topics.messages), (2, 2, 6));
+        assert_eq!(engine.queue.queue_names().len(), 5);
+
+        let readiness = engine.readiness().await;
+        let recovery = readiness.checks.iter().find(|c| c.name == "recovery").unwrap();
+        assert!(recovery.ok, "{}", recovery.detail);
+        assert!(recovery.detail.contains("5/5 queues, 2/2 topics, 16 messages"), "{}", recovery.detail);
+        engine.close().await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_listener_runs_the_protocol() {
+        use nexo::transport::tcp::dispatcher::OP_DEBUG_ECHO;
+        use nexo::transport::tcp::listener::{self, unix_listener};
+        use tokio::net::UnixStream;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().to_str().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.store.persistence_path = format!("{}/store", path);
+        config.pubsub.persistence_path = format!("{}/pubsub", path);
+        config.queue.persistence_path = format!("{}/queue", path);
+        config.stream.persistence_path = format!("{}/stream", path);
+        let engine = nexo::NexoEngine::new(&config).await;
+
+        let socket_path = tmp.path().join("nexo.sock");
+        let unix = unix_listener(socket_path.to_str().unwrap(), "", "").unwrap().unwrap();
+        tokio::spawn(listener::serve(unix, engine.clone()));
+
+        let stream = loop {
+            match UnixStream::connect(&socket_path).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        let (read, mut write) = stream.into_split();
+        let mut read = FramedRead::new(read, NexoCodec::new());
+
+        let mut frame = BytesMut::new();
+        frame.put_u8(TYPE_REQUEST);
+        frame.put_u8(OP_DEBUG_ECHO);
+        frame.put_u32(1);
+        frame.put_u32(4);
+        frame.put_slice(b"ping");
+        write.write_all(&frame).await.unwrap();
+        let echo = next_frame(&mut read).await;
+        assert_eq!((echo.header.meta, &echo.payload[..]), (STATUS_DATA, &b"ping"[..]));
+
+        // Peers are identified by their credentials
+        let connections = engine.connections.list();
+        assert_eq!(connections.len(), 1);
+        assert!(connections[0].peer_addr.starts_with("unix:uid="), "{}", connections[0].peer_addr);
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_mode_runs_every_broker_from_its_scratch_dir() {
+        use nexo::brokers::durability::PersistenceMode;
+        use nexo::brokers::queue::options::QueueCreateOptions;
+        use nexo::brokers::queue::QueueConfig;
+        use nexo::brokers::stream::domain::topic::TopicConfig;
+        use nexo::brokers::stream::options::StreamCreateOptions;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.make_ephemeral(tmp.path().to_path_buf());
+        let engine = nexo::NexoEngine::new(&config).await;
+
+        engine.queue.create_queue("jobs".to_string(), QueueCreateOptions::default()).await.unwrap();
+        engine.stream.create_topic("events".to_string(), StreamCreateOptions::default()).await.unwrap();
+        assert!(tmp.path().join("queues/jobs.db").is_file());
+        assert!(tmp.path().join("streams/events").is_dir());
+        assert!(tmp.path().join("store/intents.db").is_file());
+        assert_eq!(engine.system_snapshot().ephemeral_dir.as_deref(), Some(tmp.path()));
+
+        // Per-resource `fileSync` is overridden: there is nothing to fsync for
+        let queue = QueueConfig::from_options(QueueCreateOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() }, &config.queue);
+        let topic = TopicConfig::from_options(StreamCreateOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() }, &config.stream);
+        assert_eq!((queue.persistence, topic.persistence), (PersistenceMode::FileAsync, PersistenceMode::FileAsync));
+
+        engine.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_sys_topics_publish_broker_stats() {
+        use bytes::Bytes;
+        use nexo::brokers::pub_sub::ClientId;
+        use nexo::brokers::queue::options::QueueCreateOptions;
+        use nexo::brokers::stream::options::StreamCreateOptions;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = nexo::config::Config::global().clone();
+        config.make_ephemeral(tmp.path().to_path_buf());
+        let engine = nexo::NexoEngine::new(&config).await;
+        engine.queue.create_queue("jobs".to_string(), QueueCreateOptions::default()).await.unwrap();
+        engine.queue.push("jobs".to_string(), Bytes::from("a"), 0).await.unwrap();
+        engine.stream.create_topic("events".to_string(), StreamCreateOptions::default()).await.unwrap();
+        engine.stream.publish("events", Bytes::from("e")).await.unwrap();
+
+        let client = ClientId("dashboard".to_string());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        engine.pubsub.connect(client.clone(), tx);
+        engine.pubsub.subscribe(&client, "$nexo/sys/#").unwrap();
+        engine.publish_sys_stats().await;
+
+        let mut stats = std::collections::HashMap::new();
+        while let Ok(msg) = rx.try_recv() {
+            stats.insert(msg.topic.clone(), serde_json::from_slice::<serde_json::Value>(&msg.payload).unwrap());
+        }
+        assert_eq!(stats["$nexo/sys/connections"]["connections"], 0);
+        assert!(stats["$nexo/sys/memory"]["budget"].is_null());
+        assert_eq!(stats["$nexo/sys/queues/jobs"]["pending"], 1);
+        assert_eq!(stats["$nexo/sys/streams/events"]["lastSeq"], 1);
+        engine.close().await;
+    }
+}
@@ -3,6 +3,7 @@ use nexo::brokers::queue::QueueManager;
 use nexo::brokers::stream::StreamManager;
 use nexo::brokers::store::StoreManager;
 use nexo::brokers::pub_sub::PubSubManager;
+use nexo::brokers::clock::MockClock;
 use nexo::config::Config;
 use tempfile::TempDir;
 use std::time::{Duration, Instant};
@@ -22,6 +23,19 @@ pub async fn setup_queue_manager() -> (QueueManager, TempDir) {
     (manager, temp_dir)
 }
 
+/// Queue manager on virtual time: advance the clock instead of sleeping.
+pub async fn setup_queue_manager_with_clock() -> (QueueManager, Arc<MockClock>, TempDir) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().to_str().unwrap().to_string();
+
+    let mut config = Config::global().queue.clone();
+    config.persistence_path = path;
+
+    let clock = MockClock::new();
+    let manager = QueueManager::with_clock(Arc::new(config), clock.clone());
+    (manager, clock, temp_dir)
+}
+
 pub async fn setup_pubsub_manager() -> (Arc<PubSubManager>, TempDir) {
     let temp_dir = tempfile::tempdir().unwrap();
     let path = temp_dir.path().to_str().unwrap().to_string();
@@ -33,6 +47,16 @@ pub async fn setup_pubsub_manager() -> (Arc<PubSubManager>, TempDir) {
     (manager, temp_dir)
 }
 
+pub async fn setup_pubsub_manager_with_clock() -> (Arc<PubSubManager>, Arc<MockClock>, TempDir) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut config = Config::global().pubsub.clone();
+    config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let clock = MockClock::new();
+    let manager = Arc::new(PubSubManager::with_clock(Arc::new(config), clock.clone()));
+    (manager, clock, temp_dir)
+}
+
 pub async fn setup_stream_manager() -> (StreamManager, TempDir) {
     let temp_dir = tempfile::tempdir().unwrap();
     let path = temp_dir.path().to_str().unwrap().to_string();
@@ -51,6 +75,14 @@ pub async fn setup_store_manager() -> (StoreManager, TempDir) {
     (manager, temp_dir)
 }
 
+/// Store manager on virtual time: advance the clock instead of sleeping.
+pub async fn setup_store_manager_with_clock() -> (StoreManager, Arc<MockClock>) {
+    let config = Config::global().store.clone();
+    let clock = MockClock::new();
+    let manager = StoreManager::with_clock(Arc::new(config), clock.clone());
+    (manager, clock)
+}
+
 // ==========================================
 // BENCHMARK UTILITY
 // ==========================================
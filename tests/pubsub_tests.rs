@@ -1,11 +1,13 @@
-use nexo::brokers::pub_sub::{PubSubManager, ClientId};
+use nexo::brokers::pub_sub::{PubSubManager, ClientId, RetainHandling, SubscriptionOptions};
+use nexo::brokers::pub_sub::config::RetainedEviction;
+use nexo::transport::tcp::protocol::ErrorCode;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use bytes::Bytes;
 use std::time::{Duration, Instant};
 
 mod helpers;
-use helpers::{setup_pubsub_manager, Benchmark};
+use helpers::{setup_pubsub_manager, setup_pubsub_manager_with_clock, Benchmark};
 
 
 
@@ -31,11 +33,11 @@ mod pubsub_tests {
 
             // 2. Subscribe
             let topic = "sensors/temp";
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             // 3. Publish
             let payload = Bytes::from("24.5");
-            let count = manager.publish(topic, payload.clone(), false, None);
+            let count = manager.publish(topic, payload.clone(), false, None).unwrap();
             assert_eq!(count, 1, "Should deliver to 1 subscriber");
 
             // 4. Verify Receipt
@@ -52,19 +54,19 @@ mod pubsub_tests {
             manager.connect(client_id.clone(), tx);
 
             // Subscribe to "home/+/status"
-            manager.subscribe(&client_id, "home/+/status");
+            manager.subscribe(&client_id, "home/+/status").unwrap();
 
             // MATCH: "home/kitchen/status"
-            manager.publish("home/kitchen/status", Bytes::from("on"), false, None);
+            manager.publish("home/kitchen/status", Bytes::from("on"), false, None).unwrap();
             let msg = rx.recv().await.expect("Should match + wildcard");
             assert_eq!(msg.topic, "home/kitchen/status");
 
             // NO MATCH: "home/kitchen/fridge/status" (too deep)
-            let count = manager.publish("home/kitchen/fridge/status", Bytes::from("off"), false, None);
+            let count = manager.publish("home/kitchen/fridge/status", Bytes::from("off"), false, None).unwrap();
             assert_eq!(count, 0, "Should not match nested levels");
 
             // NO MATCH: "home/status" (too shallow)
-            let count = manager.publish("home/status", Bytes::from("err"), false, None);
+            let count = manager.publish("home/status", Bytes::from("err"), false, None).unwrap();
             assert_eq!(count, 0);
         }
 
@@ -76,14 +78,14 @@ mod pubsub_tests {
             manager.connect(client_id.clone(), tx);
 
             // Subscribe to "logs/#"
-            manager.subscribe(&client_id, "logs/#");
+            manager.subscribe(&client_id, "logs/#").unwrap();
 
             // MATCH: "logs/error"
-            manager.publish("logs/error", Bytes::from("e1"), false, None);
+            manager.publish("logs/error", Bytes::from("e1"), false, None).unwrap();
             assert_eq!(rx.recv().await.unwrap().topic, "logs/error");
 
             // MATCH: "logs/app/backend/error" (deep)
-            manager.publish("logs/app/backend/error", Bytes::from("e2"), false, None);
+            manager.publish("logs/app/backend/error", Bytes::from("e2"), false, None).unwrap();
             assert_eq!(rx.recv().await.unwrap().topic, "logs/app/backend/error");
         }
 
@@ -93,14 +95,14 @@ mod pubsub_tests {
             let topic = "config/settings";
 
             // 1. Publish Retained (No subscribers yet)
-            manager.publish(topic, Bytes::from("dark_mode"), true, None);
+            manager.publish(topic, Bytes::from("dark_mode"), true, None).unwrap();
 
             // 2. New Client Connects & Subscribes
             let client_id = ClientId("late_joiner".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
 
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             // 3. Should receive retained message immediately
             let msg = rx.recv().await.expect("Should receive retained message");
@@ -121,33 +123,80 @@ mod pubsub_tests {
             let (tx, _rx) = mpsc::unbounded_channel();
 
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, "chat/room1");
+            manager.subscribe(&client_id, "chat/room1").unwrap();
 
             // Verify subscription exists (indirectly via publish count)
-            let count = manager.publish("chat/room1", Bytes::from("hi"), false, None);
+            let count = manager.publish("chat/room1", Bytes::from("hi"), false, None).unwrap();
             assert_eq!(count, 1);
 
             // Explicit disconnect (simulates socket close)
             manager.disconnect(&client_id);
 
             // Publish again -> Should be 0 subscribers
-            let count = manager.publish("chat/room1", Bytes::from("anyone?"), false, None);
+            let count = manager.publish("chat/room1", Bytes::from("anyone?"), false, None).unwrap();
             assert_eq!(count, 0, "Client should be unsubscribed after disconnect");
         }
 
+        #[tokio::test]
+        async fn test_topic_alias_resolves_per_connection() {
+            let (manager, _tmp) = setup_pubsub_manager().await;
+            let publisher = ClientId("alias_pub".to_string());
+            let other = ClientId("alias_other".to_string());
+            let (tx_pub, _rx_pub) = mpsc::unbounded_channel();
+            let (tx_other, _rx_other) = mpsc::unbounded_channel();
+            manager.connect(publisher.clone(), tx_pub);
+            manager.connect(other.clone(), tx_other);
+
+            let topic = "factory/line-7/sensors/vibration/axis-x";
+            manager.register_alias(&publisher, 1, topic).unwrap();
+            assert_eq!(manager.resolve_alias(&publisher, 1).as_deref(), Some(topic));
+
+            // Aliases are scoped to the connection that registered them
+            assert_eq!(manager.resolve_alias(&other, 1), None);
+
+            // Re-binding replaces the mapping
+            manager.register_alias(&publisher, 1, "factory/line-8").unwrap();
+            assert_eq!(manager.resolve_alias(&publisher, 1).as_deref(), Some("factory/line-8"));
+
+            // Wildcards cannot be aliased
+            assert!(manager.register_alias(&publisher, 2, "factory/+").is_err());
+
+            // Disconnect drops the aliases
+            manager.disconnect(&publisher);
+            assert_eq!(manager.resolve_alias(&publisher, 1), None);
+        }
+
+        #[tokio::test]
+        async fn test_topic_alias_limit() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = nexo::config::Config::global().pubsub.clone();
+            config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            config.max_topic_aliases = 2;
+            let manager = PubSubManager::new(Arc::new(config));
+
+            let client_id = ClientId("alias_limit".to_string());
+            let (tx, _rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+
+            manager.register_alias(&client_id, 1, "a").unwrap();
+            manager.register_alias(&client_id, 2, "b").unwrap();
+            assert!(manager.register_alias(&client_id, 3, "c").is_err(), "Limit should reject new aliases");
+            manager.register_alias(&client_id, 2, "c").unwrap();
+        }
+
         #[tokio::test]
         async fn test_retained_with_custom_ttl() {
             let (manager, _tmp) = setup_pubsub_manager().await;
             let topic = "sensors/temp";
 
             // Publish retained with custom TTL (2 seconds)
-            manager.publish(topic, Bytes::from("23.5"), true, Some(2));
+            manager.publish(topic, Bytes::from("23.5"), true, Some(2)).unwrap();
 
             // Subscribe immediately - should receive retained
             let client_id = ClientId("sub1".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             let msg = rx.recv().await.expect("Should receive retained message");
             assert_eq!(msg.payload, Bytes::from("23.5"));
@@ -159,7 +208,7 @@ mod pubsub_tests {
             let client_id2 = ClientId("sub2".to_string());
             let (tx2, mut rx2) = mpsc::unbounded_channel();
             manager.connect(client_id2.clone(), tx2);
-            manager.subscribe(&client_id2, topic);
+            manager.subscribe(&client_id2, topic).unwrap();
 
             // Should timeout (no retained message)
             let result = tokio::time::timeout(Duration::from_millis(100), rx2.recv()).await;
@@ -172,25 +221,25 @@ mod pubsub_tests {
             let topic = "config/theme";
 
             // 1. Publish retained
-            manager.publish(topic, Bytes::from("dark"), true, None);
+            manager.publish(topic, Bytes::from("dark"), true, None).unwrap();
 
             // 2. Verify retained exists
             let client_id = ClientId("sub1".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             let msg = rx.recv().await.expect("Should receive retained");
             assert_eq!(msg.payload, Bytes::from("dark"));
 
             // 3. Clear retained with empty payload (MQTT standard)
-            manager.publish(topic, Bytes::from(""), true, None);
+            manager.publish(topic, Bytes::from(""), true, None).unwrap();
 
             // 4. New subscriber should NOT receive retained
             let client_id2 = ClientId("sub2".to_string());
             let (tx2, mut rx2) = mpsc::unbounded_channel();
             manager.connect(client_id2.clone(), tx2);
-            manager.subscribe(&client_id2, topic);
+            manager.subscribe(&client_id2, topic).unwrap();
 
             let result = tokio::time::timeout(Duration::from_millis(100), rx2.recv()).await;
             assert!(result.is_err(), "Should not receive cleared retained message");
@@ -210,7 +259,7 @@ mod pubsub_tests {
                 config.persistence_path = path.clone();
                 let manager = Arc::new(PubSubManager::new(Arc::new(config)));
                 
-                manager.publish(topic, payload.clone(), true, None);
+                manager.publish(topic, payload.clone(), true, None).unwrap();
 
                 // Wait for async save to disk
                 tokio::time::sleep(Duration::from_millis(200)).await;
@@ -232,7 +281,7 @@ mod pubsub_tests {
                 let client_id = ClientId("after_restart".to_string());
                 let (tx, mut rx) = mpsc::unbounded_channel();
                 manager2.connect(client_id.clone(), tx);
-                manager2.subscribe(&client_id, topic);
+                manager2.subscribe(&client_id, topic).unwrap();
 
                 let msg = rx.recv().await.expect("Should receive retained after restart");
                 assert_eq!(msg.payload, payload);
@@ -247,13 +296,13 @@ mod pubsub_tests {
             let topic = "temp/sensor";
 
             // Publish retained with 1 second TTL
-            manager.publish(topic, Bytes::from("old_value"), true, Some(1));
+            manager.publish(topic, Bytes::from("old_value"), true, Some(1)).unwrap();
 
             // Verify retained exists
             let client_id = ClientId("sub1".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             let msg = rx.recv().await.expect("Should receive retained");
             assert_eq!(msg.payload, Bytes::from("old_value"));
@@ -266,11 +315,116 @@ mod pubsub_tests {
             let client_id2 = ClientId("sub2".to_string());
             let (tx2, mut rx2) = mpsc::unbounded_channel();
             manager.connect(client_id2.clone(), tx2);
-            manager.subscribe(&client_id2, topic);
+            manager.subscribe(&client_id2, topic).unwrap();
 
             let result = tokio::time::timeout(Duration::from_millis(100), rx2.recv()).await;
             assert!(result.is_err(), "Should not receive expired retained");
         }
+
+        #[tokio::test]
+        async fn test_retained_limits_per_root() {
+            let tmp = tempfile::tempdir().unwrap();
+            let manager_with = |dir: &str, eviction: RetainedEviction| {
+                let mut config = nexo::config::Config::global().pubsub.clone();
+                config.persistence_path = tmp.path().join(dir).to_str().unwrap().to_string();
+                config.max_retained_per_root = 2;
+                config.max_retained_payload_bytes = 4;
+                config.retained_eviction = eviction;
+                PubSubManager::new(Arc::new(config))
+            };
+
+            // reject: a full root refuses new topics, other roots and replacements are fine
+            let manager = manager_with("reject", RetainedEviction::Reject);
+            manager.publish("a/1", Bytes::from("v"), true, None).unwrap();
+            manager.publish("a/2", Bytes::from("v"), true, None).unwrap();
+            assert_eq!(manager.publish("a/3", Bytes::from("v"), true, None).unwrap_err().code, ErrorCode::InvalidRequest);
+            manager.publish("b/1", Bytes::from("v"), true, None).unwrap();
+            manager.publish("a/1", Bytes::from("w"), true, None).unwrap();
+            manager.publish("a/1", Bytes::new(), true, None).unwrap();
+            manager.publish("a/3", Bytes::from("v"), true, None).unwrap();
+
+            assert_eq!(manager.publish("c/1", Bytes::from("12345"), true, None).unwrap_err().code, ErrorCode::PayloadTooLarge);
+            manager.publish("c/1", Bytes::from("12345"), false, None).expect("Only retaining is limited");
+
+            let retained = manager.scan_topics(10, 0, None).retained;
+            assert_eq!((retained.topics, retained.rejections, retained.evictions), (3, 2, 0));
+
+            // lru: the root's oldest retained topic makes room
+            let manager = manager_with("lru", RetainedEviction::Lru);
+            for topic in ["a/1", "a/2", "a/3"] {
+                manager.publish(topic, Bytes::from("v"), true, None).unwrap();
+            }
+            let client_id = ClientId("sub".to_string());
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+            manager.subscribe(&client_id, "a/#").unwrap();
+
+            let mut topics = Vec::new();
+            while let Ok(msg) = rx.try_recv() {
+                topics.push(msg.topic.clone());
+            }
+            topics.sort();
+            assert_eq!(topics, vec!["a/2", "a/3"]);
+            assert_eq!(manager.scan_topics(10, 0, None).retained.evictions, 1);
+        }
+
+        #[tokio::test]
+        async fn test_message_expiry() {
+            let (manager, _tmp) = setup_pubsub_manager().await;
+            let client_id = ClientId("sensor-reader".to_string());
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+            manager.subscribe(&client_id, "sensors/+").unwrap();
+
+            manager.publish_from(None, "sensors/temp", Bytes::from("21"), true, None, Some(Duration::from_millis(30))).unwrap();
+            manager.publish("sensors/humidity", Bytes::from("40"), false, None).unwrap();
+            let stale = rx.try_recv().unwrap();
+            let timeless = rx.try_recv().unwrap();
+            assert!(!stale.is_expired());
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            // The connection drops it on dequeue instead of sending it late
+            assert!(stale.is_expired());
+            assert!(!timeless.is_expired());
+
+            // The retained copy keeps its own TTL
+            let late = ClientId("late".to_string());
+            let (tx2, mut rx2) = mpsc::unbounded_channel();
+            manager.connect(late.clone(), tx2);
+            manager.subscribe(&late, "sensors/temp").unwrap();
+            let replayed = rx2.try_recv().unwrap();
+            assert!(replayed.retain && !replayed.is_expired());
+        }
+
+        #[tokio::test]
+        async fn test_idle_subscription_expires() {
+            let (manager, clock, _tmp) = setup_pubsub_manager_with_clock().await;
+            let client_id = ClientId("slow".to_string());
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+            manager.subscribe(&client_id, "metrics/#").unwrap();
+            manager.subscribe(&client_id, "alerts/#").unwrap();
+            manager.set_idle_ttl(&client_id, "metrics/#", Some(Duration::from_millis(50)));
+
+            // Stalled, but not for long enough
+            manager.set_stalled(&client_id, true);
+            assert!(manager.expire_idle().is_empty());
+            // Reading again resets the clock
+            clock.advance(Duration::from_millis(80));
+            manager.set_stalled(&client_id, false);
+            manager.set_stalled(&client_id, true);
+            assert!(manager.expire_idle().is_empty());
+
+            clock.advance(Duration::from_millis(80));
+            assert_eq!(manager.expire_idle(), vec![(client_id.clone(), "metrics/#".to_string())]);
+            let notice = rx.try_recv().unwrap();
+            assert!(notice.unsubscribed);
+            assert_eq!(notice.topic, "metrics/#");
+            assert_ne!(notice.push_flags() & nexo::transport::tcp::protocol::PUSH_FLAG_UNSUBSCRIBED, 0);
+
+            // Only the subscription with a TTL goes
+            assert_eq!(manager.publish("metrics/cpu", Bytes::from("1"), false, None).unwrap(), 0);
+            assert_eq!(manager.publish("alerts/cpu", Bytes::from("1"), false, None).unwrap(), 1);
+        }
     }
 
     // =========================================================================================
@@ -287,7 +441,7 @@ mod pubsub_tests {
             let (tx, mut rx) = mpsc::unbounded_channel();
             
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, "test/topic");
+            manager.subscribe(&client_id, "test/topic").unwrap();
             
             // Spawn consumer to drain channel
             tokio::spawn(async move {
@@ -303,7 +457,7 @@ mod pubsub_tests {
             
             // Publish 100 messages while disconnect is happening
             for _ in 0..100 {
-                manager.publish("test/topic", Bytes::from("data"), false, None);
+                manager.publish("test/topic", Bytes::from("data"), false, None).unwrap();
             }
             
             // Should complete without deadlock
@@ -317,12 +471,12 @@ mod pubsub_tests {
             let (tx, mut rx) = mpsc::unbounded_channel();
             
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, "#");
+            manager.subscribe(&client_id, "#").unwrap();
             
             // Should receive ALL messages from any topic
-            manager.publish("sensors/temp", Bytes::from("1"), false, None);
-            manager.publish("logs/error", Bytes::from("2"), false, None);
-            manager.publish("any/random/topic", Bytes::from("3"), false, None);
+            manager.publish("sensors/temp", Bytes::from("1"), false, None).unwrap();
+            manager.publish("logs/error", Bytes::from("2"), false, None).unwrap();
+            manager.publish("any/random/topic", Bytes::from("3"), false, None).unwrap();
             
             let msg1 = rx.recv().await.expect("Should receive message 1");
             let msg2 = rx.recv().await.expect("Should receive message 2");
@@ -340,15 +494,15 @@ mod pubsub_tests {
             let (manager, _tmp) = setup_pubsub_manager().await;
             
             // Publish retained on specific topics
-            manager.publish("sensors/temp", Bytes::from("20"), true, None);
-            manager.publish("sensors/humidity", Bytes::from("60"), true, None);
-            manager.publish("sensors/pressure", Bytes::from("1013"), true, None);
+            manager.publish("sensors/temp", Bytes::from("20"), true, None).unwrap();
+            manager.publish("sensors/humidity", Bytes::from("60"), true, None).unwrap();
+            manager.publish("sensors/pressure", Bytes::from("1013"), true, None).unwrap();
             
             // Subscribe with wildcard AFTER retained messages exist
             let client_id = ClientId("wildcard_late".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, "sensors/+");
+            manager.subscribe(&client_id, "sensors/+").unwrap();
             
             // Should receive ALL 3 retained messages
             let mut received = vec![
@@ -379,12 +533,12 @@ mod pubsub_tests {
                 let client_id = ClientId(format!("client_{}", i));
                 let (tx, rx) = mpsc::unbounded_channel();
                 manager.connect(client_id.clone(), tx);
-                manager.subscribe(&client_id, topic);
+                manager.subscribe(&client_id, topic).unwrap();
                 receivers.push(rx);
             }
             
             // Publish one message
-            let count = manager.publish(topic, Bytes::from("breaking_news"), false, None);
+            let count = manager.publish(topic, Bytes::from("breaking_news"), false, None).unwrap();
             assert_eq!(count, 3, "Should deliver to all 3 subscribers");
             
             // All 3 clients should receive the message
@@ -404,11 +558,11 @@ mod pubsub_tests {
             manager.connect(client_id.clone(), tx);
             
             // Subscribe to same topic twice (should deduplicate)
-            manager.subscribe(&client_id, "sensors/temp");
-            manager.subscribe(&client_id, "sensors/temp");
+            manager.subscribe(&client_id, "sensors/temp").unwrap();
+            manager.subscribe(&client_id, "sensors/temp").unwrap();
             
             // Publish
-            manager.publish("sensors/temp", Bytes::from("data"), false, None);
+            manager.publish("sensors/temp", Bytes::from("data"), false, None).unwrap();
             
             // Should receive only 1 message (not 2)
             let msg1 = rx.recv().await.expect("Should receive message");
@@ -431,10 +585,29 @@ mod pubsub_tests {
             manager.unsubscribe(&client_id, "sensors/temp");
             
             // Publish should work normally
-            let count = manager.publish("sensors/temp", Bytes::from("data"), false, None);
+            let count = manager.publish("sensors/temp", Bytes::from("data"), false, None).unwrap();
             assert_eq!(count, 0, "Should have no subscribers");
         }
 
+        #[tokio::test]
+        async fn test_invalid_wildcard_filters_are_rejected() {
+            let (manager, _tmp) = setup_pubsub_manager().await;
+            let client_id = ClientId("bad_filters".to_string());
+            let (tx, _rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+
+            // '#' only as the whole last level, '+' only as a whole level
+            for filter in ["a/#/b", "#/a", "a/b#", "sp+rts/x", ""] {
+                let err = manager.subscribe(&client_id, filter).unwrap_err();
+                assert_eq!(err.code, ErrorCode::InvalidName, "{:?} must be rejected", filter);
+            }
+            assert_eq!(manager.publish("a/x/b", Bytes::from("data"), false, None).unwrap(), 0, "Nothing was half-subscribed");
+
+            for filter in ["#", "+", "a/+/#", "+/+"] {
+                manager.subscribe(&client_id, filter).unwrap();
+            }
+        }
+
         #[tokio::test]
         async fn test_disconnect_during_subscribe() {
             let (manager, _tmp) = setup_pubsub_manager().await;
@@ -448,7 +621,7 @@ mod pubsub_tests {
             let client_clone = client_id.clone();
             let subscribe_task = tokio::spawn(async move {
                 for _ in 0..10 {
-                    manager_clone.subscribe(&client_clone, "test/topic");
+                    manager_clone.subscribe(&client_clone, "test/topic").unwrap();
                 }
             });
             
@@ -470,15 +643,15 @@ mod pubsub_tests {
             let topic = "config/setting";
             
             // Publish retained 3 times on same topic
-            manager.publish(topic, Bytes::from("v1"), true, None);
-            manager.publish(topic, Bytes::from("v2"), true, None);
-            manager.publish(topic, Bytes::from("v3"), true, None);
+            manager.publish(topic, Bytes::from("v1"), true, None).unwrap();
+            manager.publish(topic, Bytes::from("v2"), true, None).unwrap();
+            manager.publish(topic, Bytes::from("v3"), true, None).unwrap();
             
             // New subscriber should receive only latest (v3)
             let client_id = ClientId("late".to_string());
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
             
             let msg = rx.recv().await.expect("Should receive retained");
             assert_eq!(msg.payload, Bytes::from("v3"), "Should receive only latest retained");
@@ -497,11 +670,11 @@ mod pubsub_tests {
             let (tx, mut rx) = mpsc::unbounded_channel();
             manager.connect(client_id.clone(), tx);
             
-            manager.subscribe(&client_id, "sensors/+/temp");
-            manager.subscribe(&client_id, "sensors/kitchen/+");
+            manager.subscribe(&client_id, "sensors/+/temp").unwrap();
+            manager.subscribe(&client_id, "sensors/kitchen/+").unwrap();
             
             // Publish to topic that matches BOTH patterns
-            manager.publish("sensors/kitchen/temp", Bytes::from("data"), false, None);
+            manager.publish("sensors/kitchen/temp", Bytes::from("data"), false, None).unwrap();
             
             // Should receive message only once (deduplicated by client_id)
             let msg1 = rx.recv().await.expect("Should receive message");
@@ -511,6 +684,154 @@ mod pubsub_tests {
             let result = tokio::time::timeout(Duration::from_millis(100), rx.recv()).await;
             assert!(result.is_err(), "Should not receive duplicate from overlapping patterns");
         }
+
+        #[tokio::test]
+        async fn test_subscription_ids_and_options() {
+            let (manager, _tmp) = setup_pubsub_manager().await;
+            let client_id = ClientId("sub_options".to_string());
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            manager.connect(client_id.clone(), tx);
+            manager.publish("rooms/a/temp", Bytes::from("19"), true, None).unwrap();
+
+            // Overlapping filters deliver once, with the ids of every matching subscription
+            let with_id = |id| SubscriptionOptions { id: Some(id), ..Default::default() };
+            manager.subscribe_with(&client_id, "rooms/+/temp", with_id(1)).unwrap();
+            let replayed = rx.recv().await.unwrap();
+            assert_eq!((replayed.retain, replayed.subscription_ids.as_slice()), (true, &[1][..]));
+            manager.subscribe_with(&client_id, "rooms/#", SubscriptionOptions {
+                id: Some(2),
+                retain_handling: RetainHandling::DontSend,
+                ..Default::default()
+            }).unwrap();
+            assert!(rx.try_recv().is_err(), "DontSend must skip retained messages");
+
+            manager.publish("rooms/a/temp", Bytes::from("20"), true, None).unwrap();
+            let msg = rx.recv().await.unwrap();
+            assert_eq!(msg.subscription_ids, vec![1, 2]);
+            assert!(!msg.retain, "Retain flag is cleared without retain-as-published");
+            assert!(rx.try_recv().is_err());
+
+            // Re-subscribing replaces the options; SendIfNew skips the replay
+            manager.subscribe_with(&client_id, "rooms/#", SubscriptionOptions {
+                id: Some(3),
+                no_local: true,
+                retain_as_published: true,
+                retain_handling: RetainHandling::SendIfNew,
+            }).unwrap();
+            assert!(rx.try_recv().is_err(), "SendIfNew must skip an existing subscription");
+
+            manager.publish("rooms/b/temp", Bytes::from("21"), true, None).unwrap();
+            let msg = rx.recv().await.unwrap();
+            assert_eq!((msg.retain, msg.subscription_ids.as_slice()), (true, &[1, 3][..]));
+
+            // Own publishes only reach the subscriptions without no_local
+            assert_eq!(manager.publish_from(Some(&client_id), "rooms/c/temp", Bytes::from("22"), false, None, None).unwrap(), 1);
+            assert_eq!(rx.recv().await.unwrap().subscription_ids, vec![1]);
+            assert_eq!(manager.publish_from(Some(&client_id), "rooms/c/humidity", Bytes::from("40"), false, None, None).unwrap(), 0);
+            assert!(rx.try_recv().is_err());
+        }
+
+        #[tokio::test]
+        async fn test_match_cache_follows_subscriptions() {
+            let (manager, _tmp) = setup_pubsub_manager().await;
+            let (tx_a, mut rx_a) = mpsc::unbounded_channel();
+            let (tx_b, mut rx_b) = mpsc::unbounded_channel();
+            let client_a = ClientId("cache_a".to_string());
+            let client_b = ClientId("cache_b".to_string());
+            manager.connect(client_a.clone(), tx_a);
+            manager.connect(client_b.clone(), tx_b);
+
+            manager.subscribe(&client_a, "metrics/+/cpu").unwrap();
+            assert_eq!(manager.publish("metrics/host1/cpu", Bytes::from("1"), false, None).unwrap(), 1);
+            assert_eq!(manager.publish("metrics/host1/cpu", Bytes::from("2"), false, None).unwrap(), 1);
+            let cache = manager.scan_topics(10, 0, None).match_cache;
+            assert_eq!((cache.hits, cache.misses, cache.entries), (1, 1, 1));
+
+            // A new subscriber must not be hidden by the cached route
+            manager.subscribe(&client_b, "metrics/#").unwrap();
+            assert_eq!(manager.publish("metrics/host1/cpu", Bytes::from("3"), false, None).unwrap(), 2);
+            assert_eq!(rx_b.recv().await.unwrap().topic, "metrics/host1/cpu");
+
+            manager.unsubscribe(&client_a, "metrics/+/cpu");
+            assert_eq!(manager.publish("metrics/host1/cpu", Bytes::from("4"), false, None).unwrap(), 1);
+            manager.disconnect(&client_b);
+            assert_eq!(manager.publish("metrics/host1/cpu", Bytes::from("5"), false, None).unwrap(), 0);
+
+            for expected in ["1", "2", "3"] {
+                assert_eq!(rx_a.recv().await.unwrap().payload, Bytes::from(expected));
+            }
+            assert!(rx_a.try_recv().is_err(), "Unsubscribed client got a cached route");
+        }
+
+        #[tokio::test]
+        async fn test_parallel_fanout_keeps_order() {
+            let tmp = tempfile::tempdir().unwrap();
+            let mut config = nexo::config::Config::global().pubsub.clone();
+            config.persistence_path = tmp.path().to_str().unwrap().to_string();
+            config.fanout_threshold = 10;
+            config.fanout_workers = 4;
+            let manager = PubSubManager::new(Arc::new(config));
+
+            let mut receivers = Vec::new();
+            for i in 0..50 {
+                let client_id = ClientId(format!("fan_{}", i));
+                let (tx, rx) = mpsc::unbounded_channel();
+                manager.connect(client_id.clone(), tx);
+                manager.subscribe(&client_id, "fan/big").unwrap();
+                receivers.push(rx);
+            }
+            // A small topic shared by one of them: its inline sends must not overtake queued ones
+            manager.subscribe(&ClientId("fan_0".to_string()), "fan/small").unwrap();
+
+            for n in 0..100u32 {
+                assert_eq!(manager.publish("fan/big", Bytes::from(n.to_string()), false, None).unwrap(), 50);
+                manager.publish("fan/small", Bytes::from(format!("s{}", n)), false, None).unwrap();
+            }
+
+            for (i, rx) in receivers.iter_mut().enumerate() {
+                for n in 0..100u32 {
+                    let msg = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await
+                        .expect("Fan-out timed out").unwrap();
+                    assert_eq!(msg.payload, Bytes::from(n.to_string()), "subscriber {} out of order", i);
+                    if i == 0 {
+                        let small = rx.recv().await.unwrap();
+                        assert_eq!(small.payload, Bytes::from(format!("s{}", n)));
+                    }
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_publish_confirm_waits_for_fanout() {
+            let tmp = tempfile::tempdir().unwrap();
+            let mut config = nexo::config::Config::global().pubsub.clone();
+            config.persistence_path = tmp.path().to_str().unwrap().to_string();
+            config.fanout_threshold = 10;
+            config.fanout_workers = 4;
+            let manager = PubSubManager::new(Arc::new(config));
+
+            let mut receivers = Vec::new();
+            for i in 0..20 {
+                let client_id = ClientId(format!("confirm_{}", i));
+                let (tx, rx) = mpsc::unbounded_channel();
+                manager.connect(client_id.clone(), tx);
+                manager.subscribe(&client_id, "confirm/big").unwrap();
+                receivers.push(rx);
+            }
+            manager.subscribe(&ClientId("confirm_0".to_string()), "confirm/small").unwrap();
+
+            let first = manager.publish_confirmed(None, "confirm/big", Bytes::from("a"), false, None, None).await.unwrap();
+            assert_eq!(first.delivered, 20);
+            // Confirmed means already in every channel, no waiting needed
+            for rx in receivers.iter_mut() {
+                assert_eq!(rx.try_recv().unwrap().payload, Bytes::from("a"));
+            }
+
+            let second = manager.publish_confirmed(None, "confirm/small", Bytes::from("b"), false, None, None).await.unwrap();
+            assert_eq!(second.delivered, 1);
+            assert!(second.publish_id > first.publish_id);
+            assert_eq!(manager.publish_confirmed(None, "confirm/none", Bytes::from("c"), false, None, None).await.unwrap().delivered, 0);
+        }
     }
 
     // =========================================================================================
@@ -530,7 +851,7 @@ mod pubsub_tests {
             manager.connect(client_id.clone(), tx);
 
             let topic = "bench/speed";
-            manager.subscribe(&client_id, topic);
+            manager.subscribe(&client_id, topic).unwrap();
 
             let payload = Bytes::from("fast_data");
 
@@ -543,7 +864,7 @@ mod pubsub_tests {
 
             for _ in 0..MSG_COUNT {
                 let start = Instant::now();
-                manager.publish(topic, payload.clone(), false, None);
+                manager.publish(topic, payload.clone(), false, None).unwrap();
                 bench.record(start.elapsed());
             }
 
@@ -558,7 +879,7 @@ mod pubsub_tests {
             manager.connect(client_id.clone(), tx);
 
             // Subscribe with wildcard
-            manager.subscribe(&client_id, "bench/+/metric");
+            manager.subscribe(&client_id, "bench/+/metric").unwrap();
             let payload = Bytes::from("data");
 
             tokio::spawn(async move {
@@ -569,13 +890,49 @@ mod pubsub_tests {
 
             for _ in 0..MSG_COUNT {
                 let start = Instant::now();
-                manager.publish("bench/server1/metric", payload.clone(), false, None);
+                manager.publish("bench/server1/metric", payload.clone(), false, None).unwrap();
                 bench.record(start.elapsed());
             }
 
             bench.stop();
         }
 
+        #[tokio::test]
+        async fn bench_pubsub_wildcard_match_cache() {
+            // Wildcard-heavy routing: 200 '+' patterns, publishes spread over 100 topics
+            let count = 200_000;
+            for cache_size in [0, 4096] {
+                let tmp = tempfile::tempdir().unwrap();
+                let mut config = nexo::config::Config::global().pubsub.clone();
+                config.persistence_path = tmp.path().to_str().unwrap().to_string();
+                config.match_cache_size = cache_size;
+                let manager = PubSubManager::new(Arc::new(config));
+
+                let client_id = ClientId("bench_cache".to_string());
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                manager.connect(client_id.clone(), tx);
+                for i in 0..200 {
+                    manager.subscribe(&client_id, &format!("bench/+/metric{}", i)).unwrap();
+                }
+                tokio::spawn(async move {
+                    while rx.recv().await.is_some() {}
+                });
+
+                let topics: Vec<String> = (0..100).map(|i| format!("bench/server{}/metric{}", i, i)).collect();
+                let payload = Bytes::from("data");
+                let label = if cache_size > 0 { "cached" } else { "uncached" };
+                let mut bench = Benchmark::start(&format!("PUBSUB - Wildcard Match ({})", label), count);
+
+                for i in 0..count {
+                    let start = Instant::now();
+                    manager.publish(&topics[i % topics.len()], payload.clone(), false, None).unwrap();
+                    bench.record(start.elapsed());
+                }
+
+                bench.stop();
+            }
+        }
+
         #[tokio::test]
         async fn bench_pubsub_fanout() {
             let (manager, _tmp) = setup_pubsub_manager().await;
@@ -587,7 +944,7 @@ mod pubsub_tests {
                 let client_id = ClientId(format!("sub_{}", i));
                 let (tx, mut rx) = mpsc::unbounded_channel();
                 manager.connect(client_id.clone(), tx);
-                manager.subscribe(&client_id, topic);
+                manager.subscribe(&client_id, topic).unwrap();
 
                 tokio::spawn(async move {
                     while let Some(_) = rx.recv().await {}
@@ -601,11 +958,120 @@ mod pubsub_tests {
 
             for _ in 0..count {
                 let start = Instant::now();
-                manager.publish(topic, payload.clone(), false, None);
+                manager.publish(topic, payload.clone(), false, None).unwrap();
                 bench.record(start.elapsed());
             }
 
             bench.stop();
         }
+
+        use nexo::brokers::pub_sub::tcp::{OP_PUB, OP_SUB};
+        use nexo::transport::tcp::protocol::{TYPE_PUSH_PUBSUB, TYPE_REQUEST, TYPE_RESPONSE};
+
+        fn request_frame(opcode: u8, id: u32, payload: &[u8]) -> Vec<u8> {
+            let mut frame = Vec::with_capacity(10 + payload.len());
+            frame.push(TYPE_REQUEST);
+            frame.push(opcode);
+            frame.extend_from_slice(&id.to_be_bytes());
+            frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+            frame.extend_from_slice(payload);
+            frame
+        }
+
+        fn string_field(buf: &mut Vec<u8>, value: &str) {
+            buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            buf.extend_from_slice(value.as_bytes());
+        }
+
+        /// Reads frames off `socket` until `count` frames of `frame_type` have arrived.
+        async fn drain_frames(socket: &mut tokio::net::tcp::OwnedReadHalf, frame_type: u8, count: usize) {
+            use tokio::io::AsyncReadExt;
+            let mut buf = bytes::BytesMut::with_capacity(64 * 1024);
+            let mut seen = 0;
+            while seen < count {
+                if socket.read_buf(&mut buf).await.unwrap() == 0 {
+                    panic!("socket closed after {} frames", seen);
+                }
+                while buf.len() >= 10 {
+                    let len = u32::from_be_bytes(buf[6..10].try_into().unwrap()) as usize;
+                    if buf.len() < 10 + len {
+                        break;
+                    }
+                    if buf[0] == frame_type {
+                        seen += 1;
+                    }
+                    let _ = buf.split_to(10 + len);
+                }
+            }
+        }
+
+        /// Fan-out over real sockets: exercises the codec read path (and its
+        /// buffer pool) on the publisher connection.
+        #[tokio::test(flavor = "multi_thread")]
+        async fn bench_pubsub_fanout_tcp() {
+            use tokio::io::AsyncWriteExt;
+            use tokio::net::{TcpListener, TcpStream};
+
+            let tmp = tempfile::tempdir().unwrap();
+            let path = tmp.path().to_str().unwrap();
+            let mut config = nexo::config::Config::global().clone();
+            config.store.persistence_path = format!("{}/store", path);
+            config.pubsub.persistence_path = format!("{}/pubsub", path);
+            config.queue.persistence_path = format!("{}/queue", path);
+            config.stream.persistence_path = format!("{}/stream", path);
+            let engine = nexo::NexoEngine::new(&config).await;
+
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server_engine = engine.clone();
+            tokio::spawn(async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    tokio::spawn(nexo::transport::tcp::connection::handle_connection(socket, server_engine.clone()));
+                }
+            });
+
+            let topic = "fanout/tcp";
+            let num_subs = 20;
+            let count = 20_000;
+
+            let mut sub_payload = Vec::new();
+            string_field(&mut sub_payload, topic);
+            let mut receivers = Vec::new();
+            for _ in 0..num_subs {
+                let (mut reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+                writer.write_all(&request_frame(OP_SUB, 1, &sub_payload)).await.unwrap();
+                drain_frames(&mut reader, TYPE_RESPONSE, 1).await; // SUB ack
+                receivers.push(tokio::spawn(async move {
+                    drain_frames(&mut reader, TYPE_PUSH_PUBSUB, count).await;
+                    writer
+                }));
+            }
+
+            let (mut pub_reader, mut pub_writer) = TcpStream::connect(addr).await.unwrap().into_split();
+            let acks = tokio::spawn(async move { drain_frames(&mut pub_reader, TYPE_RESPONSE, count).await });
+
+            let mut pub_payload = Vec::new();
+            string_field(&mut pub_payload, topic);
+            string_field(&mut pub_payload, "{}");
+            pub_payload.extend_from_slice(&[0u8; 256]);
+
+            let mut bench = Benchmark::start(&format!("PUBSUB - TCP Fanout 1->{}", num_subs), count);
+            for i in 0..count {
+                let start = Instant::now();
+                pub_writer.write_all(&request_frame(OP_PUB, i as u32, &pub_payload)).await.unwrap();
+                bench.record(start.elapsed());
+            }
+            acks.await.unwrap();
+            for receiver in receivers {
+                receiver.await.unwrap();
+            }
+            bench.stop();
+
+            let publisher = engine.connections.list().into_iter()
+                .max_by_key(|conn| conn.frames_in)
+                .unwrap();
+            let pool = publisher.read_pool;
+            println!("   Read pool:   {} reused | {} allocated | {} discarded\n", pool.hits, pool.misses, pool.discarded);
+        }
     }
-}
\ No newline at end of file
+}
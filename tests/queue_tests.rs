@@ -1,11 +1,23 @@
 use nexo::brokers::queue::{QueueManager};
-use nexo::brokers::queue::options::QueueCreateOptions;
+use nexo::brokers::queue::domain::dlq::DlqFilter;
+use nexo::brokers::queue::options::{DeadLetterTarget, QueueCreateOptions, QueuePushOptions, QueueSubscribeOptions, ReplayOptions, ReplayTransform, WebhookOptions};
 use bytes::Bytes;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use nexo::transport::tcp::protocol::ErrorCode;
+use nexo::brokers::message_trace::TraceEventKind;
+use nexo::brokers::pub_sub::ClientId;
+use nexo::brokers::durability::PersistenceMode;
+use nexo::brokers::dead_letter::DeadLetterEnvelope;
+use nexo::brokers::labels::Labels;
+use nexo::brokers::events::EventBus;
+use nexo::brokers::hooks::HookDispatcher;
 use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
 mod helpers;
-use helpers::{setup_queue_manager, Benchmark};
+use helpers::{setup_queue_manager, setup_queue_manager_with_clock, Benchmark};
 
 
 
@@ -115,7 +127,7 @@ mod queue_tests {
 
         #[tokio::test]
         async fn test_retry_and_dlq() {
-            let (manager, _tmp) = setup_queue_manager().await;
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
             let q = format!("feature_dlq_{}", Uuid::new_v4());
 
             let visibility_timeout = 100;
@@ -127,6 +139,7 @@ mod queue_tests {
                 // Pop 1 (att=1). Timeout. 1 < 3 -> Requeue.
                 // Pop 2 (att=2). Timeout. 2 < 3 -> Requeue.
                 // Pop 3 (att=3). Timeout. 3 >= 3 -> DLQ.
+                ..Default::default()
             };
             manager.create_queue(q.clone(), config).await.unwrap();
 
@@ -136,24 +149,25 @@ mod queue_tests {
             let m1 = manager.pop(&q).await.unwrap();
             assert_eq!(m1.attempts, 1);
 
-            // Wait for visibility timeout + buffer
-            tokio::time::sleep(Duration::from_millis(visibility_timeout + 50)).await;
+            // Move past the visibility timeout
+            clock.advance(Duration::from_millis(visibility_timeout + 50));
+            manager.expire_inflight();
 
             // Attempt 2
             let m2 = manager.pop(&q).await.unwrap();
             assert_eq!(m2.attempts, 2);
-            tokio::time::sleep(Duration::from_millis(visibility_timeout + 50)).await;
+            clock.advance(Duration::from_millis(visibility_timeout + 50));
+            manager.expire_inflight();
 
             // Attempt 3 (Last)
             let m3 = manager.pop(&q).await.unwrap();
             assert_eq!(m3.attempts, 3);
             let msg_id = m3.id;
             
-            // Wait for visibility timeout + buffer for actor to wake up and process
-            tokio::time::sleep(Duration::from_millis(visibility_timeout + 150)).await;
+            clock.advance(Duration::from_millis(visibility_timeout + 50));
+            manager.expire_inflight();
 
             // Attempt 4 -> Should be gone from Main Queue (moved to internal DLQ)
-            // The actor loop should have woken up automatically and moved to DLQ
             assert!(manager.pop(&q).await.is_none(), "Should be moved to DLQ");
 
             // Verify message is in DLQ using new DLQ methods
@@ -177,6 +191,595 @@ mod queue_tests {
             manager.ack(&q, replayed.id).await;
         }
 
+        #[tokio::test]
+        async fn test_dlq_replay_options_and_bulk_replay() {
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("feature_dlq_replay_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            let mut failed = Vec::new();
+            for (payload, reason) in [("a", "timeout"), ("b", "bad input"), ("c", "timeout"), ("d", "timeout")] {
+                manager.push(q.clone(), Bytes::from(payload), 1).await.unwrap();
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.nack(&q, msg.id, reason.to_string()).await);
+                failed.push(msg.id);
+            }
+            manager.push(q.clone(), Bytes::from("backlog"), 1).await.unwrap();
+
+            // Front of its priority bucket, ahead of the backlog
+            let front = ReplayOptions { front: Some(true), ..Default::default() };
+            assert!(manager.move_to_queue_with(&q, failed[1], &front).await.unwrap());
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("b"));
+
+            // Priority override and delay
+            let bump = ReplayOptions { priority: Some(9), delay_ms: Some(1_000), ..Default::default() };
+            assert!(manager.move_to_queue_with(&q, failed[0], &bump).await.unwrap());
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("backlog"), "Delayed replay is not visible yet");
+            clock.advance(Duration::from_millis(1_500));
+            manager.expire_inflight();
+            let bumped = manager.pop(&q).await.unwrap();
+            assert_eq!((bumped.payload, bumped.priority), (Bytes::from("a"), 9));
+
+            let invalid = ReplayOptions { front: Some(true), delay_ms: Some(10), ..Default::default() };
+            assert!(manager.move_to_queue_with(&q, failed[2], &invalid).await.is_err());
+
+            // Bulk replay by failure reason keeps the failure order
+            manager.push(q.clone(), Bytes::from("bad input again"), 1).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.nack(&q, msg.id, "bad input".to_string()).await);
+            assert_eq!(manager.move_all_to_queue(&q, Some("timeout"), &front).await.unwrap(), 2);
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("c"));
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("d"));
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 1);
+            assert_eq!(manager.move_all_to_queue(&q, None, &ReplayOptions::default()).await.unwrap(), 1);
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 0);
+        }
+
+        #[tokio::test]
+        async fn test_dlq_replay_transform() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_dlq_transform_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            for payload in [r#"{"routing":"eu","order":1}"#, r#"{"routing":"us","order":2}"#] {
+                manager.push(q.clone(), Bytes::from(payload), 0).await.unwrap();
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.nack(&q, msg.id, "timeout".to_string()).await);
+            }
+            let (_, dlq) = manager.peek_dlq(&q, 10, 0).await.unwrap();
+            let first = dlq.iter().find(|m| m.payload.ends_with(b"1}")).unwrap().id;
+
+            let strip = ReplayOptions {
+                transform: Some(ReplayTransform { strip_fields: vec!["routing".into()], wrap: false }),
+                ..Default::default()
+            };
+            assert!(manager.move_to_queue_with(&q, first, &strip).await.unwrap());
+            let replayed = manager.pop(&q).await.unwrap();
+            assert_eq!((replayed.id, replayed.payload.clone()), (first, Bytes::from(r#"{"order":1}"#)));
+            manager.ack(&q, replayed.id).await;
+
+            let wrap = ReplayOptions { transform: Some(ReplayTransform { strip_fields: Vec::new(), wrap: true }), ..Default::default() };
+            assert_eq!(manager.move_all_to_queue(&q, None, &wrap).await.unwrap(), 1);
+            let replayed = manager.pop(&q).await.unwrap();
+            let value: serde_json::Value = serde_json::from_slice(&replayed.payload).unwrap();
+            assert_eq!(value["payload"], serde_json::json!({ "routing": "us", "order": 2 }));
+            assert_eq!(value["replay"]["failureReason"], "timeout");
+            assert_eq!(value["replay"]["attempts"], 1);
+            assert!(value["replay"]["replayedAt"].as_u64().unwrap() >= value["replay"]["failedAt"].as_u64().unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_dlq_filtered_peek_and_bulk_operations() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_dlq_filter_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            let fail = |payload: &'static str, reason: &'static str| {
+                let (manager, q) = (&manager, &q);
+                async move {
+                    manager.push(q.clone(), Bytes::from(payload), 1).await.unwrap();
+                    let msg = manager.pop(q).await.unwrap();
+                    assert!(manager.nack(q, msg.id, reason.to_string()).await);
+                }
+            };
+            fail("order-1", "timeout").await;
+            tokio::time::sleep(Duration::from_millis(80)).await;
+            fail("order-2", "timeout").await;
+            fail("invoice-3", "bad input").await;
+            fail("order-4", "bad input").await;
+
+            // Criteria combine; results are most recent failure first
+            let by_reason = DlqFilter { reason: Some("input".into()), ..Default::default() };
+            let (total, msgs) = manager.peek_dlq_filtered(&q, &by_reason, 10, 0).await.unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(msgs[0].payload, Bytes::from("order-4"));
+            let both = DlqFilter { payload: Some("order".into()), ..by_reason.clone() };
+            assert_eq!(manager.peek_dlq_filtered(&q, &both, 10, 0).await.unwrap().0, 1);
+            let old = DlqFilter { older_than_ms: Some(50), ..Default::default() };
+            let (total, msgs) = manager.peek_dlq_filtered(&q, &old, 10, 0).await.unwrap();
+            assert_eq!((total, msgs[0].payload.clone()), (1, Bytes::from("order-1")));
+            let recent = DlqFilter { newer_than_ms: Some(50), ..Default::default() };
+            assert_eq!(manager.peek_dlq_filtered(&q, &recent, 10, 0).await.unwrap().0, 3);
+
+            // Bulk replay of the matches, oldest failure first, with progress
+            let mut updates = Vec::new();
+            let timeouts = DlqFilter { reason: Some("timeout".into()), ..Default::default() };
+            let replayed = manager.replay_dlq_matching(&q, &timeouts, &ReplayOptions::default(), |done, total| updates.push((done, total))).await.unwrap();
+            assert_eq!(replayed, 2);
+            assert_eq!(updates.last(), Some(&(2, 2)));
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("order-1"));
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("order-2"));
+
+            // Bulk delete of the matches leaves the rest
+            let invoices = DlqFilter { payload: Some("invoice".into()), ..Default::default() };
+            assert_eq!(manager.delete_dlq_matching(&q, &invoices, |_, _| {}).await.unwrap(), 1);
+            let (total, msgs) = manager.peek_dlq(&q, 10, 0).await.unwrap();
+            assert_eq!((total, msgs[0].payload.clone()), (1, Bytes::from("order-4")));
+            assert_eq!(manager.delete_dlq_matching(&q, &DlqFilter::default(), |_, _| {}).await.unwrap(), 1);
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 0);
+            assert!(manager.replay_dlq_matching("missing", &timeouts, &ReplayOptions::default(), |_, _| {}).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_search_index_over_pending_and_dlq() {
+            let tmp = tempfile::tempdir().unwrap();
+            let mut config = nexo::config::Config::global().queue.clone();
+            config.persistence_path = tmp.path().to_str().unwrap().to_string();
+            config.search_index = true;
+            let manager = QueueManager::new(Arc::new(config));
+            let q = format!("feature_search_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions { max_retries: Some(1), ..Default::default() }).await.unwrap();
+
+            for (order, reason) in [("A-1", "card declined"), ("A-2", "Timeout"), ("B-7", "card expired")] {
+                manager.push(q.clone(), Bytes::from(format!(r#"{{"order":"{}"}}"#, order)), 0).await.unwrap();
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.nack(&q, msg.id, reason.to_string()).await);
+            }
+            manager.push(q.clone(), Bytes::from(r#"{"order":"C-9","note":"gift wrap"}"#), 0).await.unwrap();
+
+            // Every word must match, in the payload or the failure reason, any case
+            let (total, found) = manager.search_dlq(&q, "CARD", 10, 0).await.unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(found[0].failure_reason, "card expired", "Most recent failure first");
+            let (total, found) = manager.search_dlq(&q, "order timeout", 10, 0).await.unwrap();
+            assert_eq!((total, found[0].failure_reason.as_str()), (1, "Timeout"));
+            assert_eq!(manager.search_dlq(&q, "declined expired", 10, 0).await.unwrap().0, 0);
+
+            let (total, _) = manager.get_messages(q.clone(), "pending".to_string(), None, 0, 10, Some("Gift".to_string())).await.unwrap();
+            assert_eq!(total, 1);
+
+            // Removed messages leave the index
+            let id = manager.search_dlq(&q, "declined", 10, 0).await.unwrap().1[0].id;
+            assert!(manager.delete_dlq(&q, id).await.unwrap());
+            assert_eq!(manager.search_dlq(&q, "declined", 10, 0).await.unwrap().0, 0);
+            assert_eq!(manager.move_all_to_queue(&q, Some("card"), &ReplayOptions::default()).await.unwrap(), 1);
+            let (total, _) = manager.get_messages(q.clone(), "pending".to_string(), None, 0, 10, Some("order".to_string())).await.unwrap();
+            assert_eq!(total, 2);
+        }
+
+        #[tokio::test]
+        async fn test_delayed_push_list_and_reschedule() {
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("feature_scheduled_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            manager.push_delayed(q.clone(), Bytes::from("later"), 0, 2_000).await.unwrap();
+            manager.push_delayed(q.clone(), Bytes::from("sooner"), 0, 1_000).await.unwrap();
+            assert!(manager.pop(&q).await.is_none(), "Scheduled messages are invisible");
+
+            // Calendar view: activation order, bounded by the window and the limit
+            let all = manager.list_scheduled(&q, 0, u64::MAX, 10).unwrap();
+            assert_eq!(all.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("sooner"), Bytes::from("later")]);
+            assert!(all[0].scheduled_at < all[1].scheduled_at);
+            let window = manager.list_scheduled(&q, 0, all[0].scheduled_at, 10).unwrap();
+            assert_eq!(window.len(), 1);
+            assert_eq!(manager.list_scheduled(&q, 0, u64::MAX, 1).unwrap().len(), 1);
+
+            // Push "later" out; "sooner" is released on time
+            let later = all[1].id;
+            assert!(manager.reschedule(&q, later, all[1].scheduled_at + 10_000).unwrap());
+            clock.advance(Duration::from_millis(2_500));
+            manager.expire_inflight();
+
+            let released = manager.pop(&q).await.expect("due message is released");
+            assert_eq!(released.payload, Bytes::from("sooner"));
+            manager.ack(&q, released.id).await;
+            assert!(manager.pop(&q).await.is_none(), "Rescheduled message is still waiting");
+
+            // Rescheduling into the past releases it right away
+            assert!(manager.reschedule(&q, later, 0).unwrap());
+            assert!(!manager.reschedule(&q, later, 0).unwrap(), "No longer scheduled");
+            assert!(manager.list_scheduled(&q, 0, u64::MAX, 10).unwrap().is_empty());
+            let released = manager.pop(&q).await.expect("rescheduled message is released");
+            assert_eq!(released.id, later);
+        }
+
+        #[tokio::test]
+        async fn test_precise_delays_release_without_the_pulse() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_precise_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions { precise_delays: Some(true), ..Default::default() }).await.unwrap();
+
+            manager.push_delayed(q.clone(), Bytes::from("later"), 0, 60_000).await.unwrap();
+            manager.push_delayed(q.clone(), Bytes::from("sooner"), 0, 30).await.unwrap();
+            let scheduled = manager.list_scheduled(&q, 0, u64::MAX, 10).unwrap();
+            assert_eq!(scheduled.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("sooner"), Bytes::from("later")]);
+
+            // Released by the scheduler thread, close to its activation time
+            let start = Instant::now();
+            let batch = manager.consume_batch(q.clone(), Some(10), Some(2_000)).await.unwrap();
+            assert_eq!(batch.len(), 1);
+            assert_eq!(batch[0].payload, Bytes::from("sooner"));
+            assert!(start.elapsed() < Duration::from_millis(30 + 25), "released {:?} after the push", start.elapsed());
+
+            // Moving a message earlier wakes the scheduler for it
+            let later = scheduled[1].id;
+            assert!(manager.reschedule(&q, later, scheduled[1].scheduled_at - 59_950).unwrap());
+            let batch = manager.consume_batch(q.clone(), Some(10), Some(2_000)).await.unwrap();
+            assert_eq!(batch.iter().map(|m| m.id).collect::<Vec<_>>(), vec![later]);
+
+            let fanout = QueueCreateOptions { fanout: Some(true), precise_delays: Some(true), ..Default::default() };
+            let err = manager.create_queue(format!("{}_fanout", q), fanout).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+        }
+
+        #[tokio::test]
+        async fn test_fanout_queue_delivers_to_every_group() {
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("feature_fanout_{}", Uuid::new_v4());
+            let visibility_timeout = 100;
+            let config = QueueCreateOptions {
+                visibility_timeout_ms: Some(visibility_timeout),
+                max_retries: Some(2),
+                fanout: Some(true),
+                ..Default::default()
+            };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            // Messages pushed before any group binds wait for the first consumers
+            manager.push(q.clone(), Bytes::from("invalidate:user:1"), 0).await.unwrap();
+            manager.push(q.clone(), Bytes::from("invalidate:user:2"), 0).await.unwrap();
+
+            let err = manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+
+            let a = manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap();
+            let b = manager.consume_group(&q, "cache-b", Some(10), Some(0)).await.unwrap();
+            assert_eq!(a.len(), 2);
+            assert_eq!(b.len(), 2);
+            assert_eq!(a[0].id, b[0].id, "Groups see the same messages");
+
+            // Settled for group A only: the message stays for group B
+            assert!(manager.ack_group(&q, "cache-a", a[0].id).await);
+            assert!(manager.ack_group(&q, "cache-a", a[1].id).await);
+            assert!(!manager.ack_group(&q, "cache-a", a[1].id).await, "Double ack is rejected");
+            assert!(manager.ack_group(&q, "cache-b", b[0].id).await);
+            assert_eq!(manager.get_snapshot().await[0].pending, 1);
+
+            // Retries are per group
+            assert!(manager.nack_group(&q, "cache-b", b[1].id, "redis down".to_string()).await);
+            assert!(manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap().is_empty());
+            let retry = manager.consume_group(&q, "cache-b", Some(10), Some(0)).await.unwrap();
+            assert_eq!(retry.len(), 1);
+            assert_eq!(retry[0].attempts, 2);
+
+            // Out of retries: group B dead-letters a copy and the ring is trimmed
+            clock.advance(Duration::from_millis(visibility_timeout + 50));
+            manager.expire_inflight();
+            let (total, dlq) = manager.peek_dlq(&q, 10, 0).await.unwrap();
+            assert_eq!(total, 1);
+            assert_eq!(dlq[0].payload, Bytes::from("invalidate:user:2"));
+            assert_ne!(dlq[0].id, retry[0].id);
+            assert!(dlq[0].failure_reason.contains("cache-b"));
+            let err = manager.move_to_queue(&q, dlq[0].id).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+
+            let snapshot = manager.get_snapshot().await;
+            assert_eq!(snapshot[0].pending, 0);
+            assert_eq!(snapshot[0].groups.len(), 2);
+
+            // An abandoned group holds messages back until it is unbound
+            manager.push(q.clone(), Bytes::from("invalidate:user:3"), 0).await.unwrap();
+            let a = manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap();
+            assert!(manager.ack_group(&q, "cache-a", a[0].id).await);
+            assert_eq!(manager.get_snapshot().await[0].pending, 1);
+            manager.unbind_group(&q, "cache-b").await.unwrap();
+            let snapshot = manager.get_snapshot().await;
+            assert_eq!((snapshot[0].pending, snapshot[0].groups.len()), (0, 1));
+            assert_eq!(manager.unbind_group(&q, "cache-b").await.unwrap_err().code, ErrorCode::NotFound);
+
+            // Groups are only for fanout queues
+            let plain = format!("feature_plain_{}", Uuid::new_v4());
+            manager.create_queue(plain.clone(), QueueCreateOptions::default()).await.unwrap();
+            let err = manager.consume_group(&plain, "cache-a", Some(10), Some(0)).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+        }
+
+        #[tokio::test]
+        async fn test_blob_offload_lifecycle() {
+            use nexo::brokers::queue::blob;
+            use nexo::brokers::queue::options::ImportConflict;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let blob_root = temp_dir.path().join("blobs");
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            sys_config.blob_path = blob_root.to_str().unwrap().to_string();
+            sys_config.blob_threshold_bytes = 16;
+            let manager = QueueManager::new(Arc::new(sys_config));
+
+            let q = format!("feature_blob_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+            let blob_dir = blob_root.join(&q);
+            let blob_count = || std::fs::read_dir(&blob_dir).map(|d| d.count()).unwrap_or(0);
+            let large = Bytes::from(vec![7u8; 64]);
+
+            // Small payloads stay inline
+            manager.push(q.clone(), Bytes::from("small"), 0).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            assert_eq!(msg.payload, Bytes::from("small"));
+            manager.ack(&q, msg.id).await;
+
+            // Large ones are replaced by a reference, resolved with fetch_blob
+            manager.push(q.clone(), large.clone(), 0).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            let (blob_id, size) = blob::parse_reference(&msg.payload).expect("payload should be a blob reference");
+            assert_eq!((blob_id, size), (msg.id, 64));
+            assert_eq!(manager.fetch_blob(&q, blob_id).await.unwrap(), Some(large.clone()));
+
+            // Ack collects the blob
+            assert!(manager.ack(&q, msg.id).await);
+            assert_eq!(manager.fetch_blob(&q, blob_id).await.unwrap(), None);
+            assert_eq!(blob_count(), 0);
+
+            // So does purging the DLQ
+            manager.push(q.clone(), large.clone(), 0).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.nack(&q, msg.id, "boom".to_string()).await);
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 1);
+            assert_eq!(blob_count(), 1);
+            assert_eq!(manager.purge_dlq(&q).await.unwrap(), 1);
+            assert_eq!(blob_count(), 0);
+
+            // Orphans (no message points to them) are swept, live blobs are kept
+            manager.push(q.clone(), large.clone(), 0).await.unwrap();
+            std::fs::write(blob_dir.join(format!("{}.blob", Uuid::now_v7())), b"orphan").unwrap();
+            assert_eq!(manager.sweep_blobs(&q, Duration::ZERO), 1);
+            assert_eq!(blob_count(), 1);
+
+            // A forged reference to that blob is refused, so it can't fetch or delete it
+            let live_id = std::fs::read_dir(&blob_dir).unwrap().next().unwrap().unwrap().path();
+            let live_id = Uuid::parse_str(live_id.file_stem().unwrap().to_str().unwrap()).unwrap();
+            let err = manager.push(q.clone(), blob::reference(&live_id, 64), 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+            assert_eq!(blob_count(), 1);
+
+            // Exports carry the payload itself, not the reference
+            let file = temp_dir.path().join("blob_export.json");
+            manager.export_queue(&q, &file).await.unwrap();
+            let copy = format!("{}_copy", q);
+            manager.import_queue(&file, copy.clone(), ImportConflict::Fail).await.unwrap();
+            assert_eq!(manager.pop(&copy).await.unwrap().payload, large);
+
+            manager.delete_queue(q.clone()).await.unwrap();
+            assert!(!blob_dir.exists());
+        }
+
+        #[tokio::test]
+        async fn test_dlq_alerts() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let (pubsub, _tmp2) = helpers::setup_pubsub_manager().await;
+            manager.attach_alerts(pubsub.clone());
+            let q = format!("feature_dlq_alerts_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), dlq_alert_threshold: Some(2), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            let client = ClientId(format!("ops_{}", Uuid::new_v4()));
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            pubsub.connect(client.clone(), tx);
+            pubsub.subscribe(&client, "$nexo/alerts/dlq/+").unwrap();
+
+            for i in 0..3 {
+                manager.push(q.clone(), Bytes::from(format!("m{}", i)), 0).await.unwrap();
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.nack(&q, msg.id, format!("boom {}", i)).await);
+            }
+
+            let mut events = Vec::new();
+            while let Ok(msg) = rx.try_recv() {
+                assert_eq!(msg.topic, format!("$nexo/alerts/dlq/{}", q));
+                events.push(serde_json::from_slice::<serde_json::Value>(&msg.payload).unwrap());
+            }
+            let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+            // The threshold fires once, when the depth reaches it
+            assert_eq!(kinds, vec!["deadLettered", "deadLettered", "threshold", "deadLettered"]);
+            assert_eq!(events[1]["reason"], "boom 1");
+            assert_eq!(events[1]["attempts"], 1);
+            assert_eq!(events[1]["dlqDepth"], 2);
+            assert_eq!(events[2]["threshold"], 2);
+        }
+
+        /// Starts a lifecycle hook receiver answering `status`; returns its URL and the bodies received.
+        async fn start_hook_receiver(status: u16) -> (String, Arc<std::sync::Mutex<Vec<serde_json::Value>>>) {
+            let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let sink = received.clone();
+            let app = axum::Router::new().route("/events", axum::routing::post(move |body: Bytes| {
+                let sink = sink.clone();
+                async move {
+                    sink.lock().unwrap().push(serde_json::from_slice(&body).unwrap());
+                    axum::http::StatusCode::from_u16(status).unwrap()
+                }
+            }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            (format!("http://{}/events", addr), received)
+        }
+
+        #[tokio::test]
+        async fn test_lifecycle_hooks() {
+            let (manager, tmp) = setup_queue_manager().await;
+            let (url, received) = start_hook_receiver(200).await;
+            let mut hooks = nexo::config::Config::global().hooks.clone();
+            hooks.urls = vec![url];
+            hooks.events = vec!["queueCreated".into(), "queueDeleted".into(), "dlqThreshold".into()];
+            hooks.dead_letter_path = tmp.path().join("hooks.jsonl").to_str().unwrap().to_string();
+            let clock = nexo::brokers::clock::system();
+            let bus = EventBus::new(100, HookDispatcher::start(&hooks, clock.clone()), clock);
+            manager.attach_events(bus.clone());
+
+            let q = format!("feature_hooks_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), dlq_alert_threshold: Some(1), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+            manager.push(q.clone(), Bytes::from("m"), 0).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.nack(&q, msg.id, "boom".to_string()).await);
+            manager.delete_queue(q.clone()).await.unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while received.lock().unwrap().len() < 3 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let events = received.lock().unwrap().clone();
+            let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+            // Delivered in order, filtered by `events`
+            assert_eq!(kinds, vec!["queueCreated", "dlqThreshold", "queueDeleted"]);
+            assert!(events.iter().all(|e| e["queue"] == q.as_str() && e["id"].is_string()));
+            assert_eq!(events[1]["dlqDepth"], 1);
+            assert_eq!(events[1]["threshold"], 1);
+            assert!(!tmp.path().join("hooks.jsonl").exists());
+
+            // The activity feed has them too, newest first
+            let feed: Vec<&str> = bus.recent(0, 10).iter().map(|r| r.event.name()).collect();
+            assert_eq!(feed, vec!["queueDeleted", "dlqThreshold", "queueCreated"]);
+        }
+
+        #[tokio::test]
+        async fn test_lifecycle_hook_dead_letters() {
+            let (manager, tmp) = setup_queue_manager().await;
+            let (url, received) = start_hook_receiver(500).await;
+            let dead_letters = tmp.path().join("hooks").join("dead-letter.jsonl");
+            let mut hooks = nexo::config::Config::global().hooks.clone();
+            hooks.urls = vec![url.clone()];
+            hooks.events = Vec::new();
+            hooks.max_attempts = 2;
+            hooks.backoff_ms = 10;
+            hooks.dead_letter_path = dead_letters.to_str().unwrap().to_string();
+            let clock = nexo::brokers::clock::system();
+            manager.attach_events(EventBus::new(0, HookDispatcher::start(&hooks, clock.clone()), clock));
+
+            let q = format!("feature_hooks_dl_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let written = || std::fs::read_to_string(&dead_letters).is_ok_and(|content| content.ends_with('\n'));
+            while !written() && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let content = std::fs::read_to_string(&dead_letters).unwrap();
+            let lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+            assert_eq!(lines.len(), 1);
+            assert_eq!(lines[0]["url"], url.as_str());
+            assert_eq!(lines[0]["attempts"], 2);
+            assert_eq!(lines[0]["error"], "HTTP 500 Internal Server Error");
+            assert_eq!(lines[0]["body"]["event"], "queueCreated");
+            assert_eq!(lines[0]["body"]["queue"], q.as_str());
+            // Both attempts carried the same event id
+            let attempts = received.lock().unwrap().clone();
+            assert_eq!(attempts.len(), 2);
+            assert_eq!(attempts[0]["id"], attempts[1]["id"]);
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_custom_dead_letter_targets() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let (stream, _tmp2) = helpers::setup_stream_manager().await;
+            let stream = Arc::new(stream);
+            manager.attach_streams(stream.clone());
+
+            let fail_once = |q: &str| {
+                let (manager, q) = (manager.clone(), q.to_string());
+                async move {
+                    manager.push(q.clone(), Bytes::from("{\"order\":1}"), 3).await.unwrap();
+                    let msg = manager.pop(&q).await.unwrap();
+                    assert!(manager.nack(&q, msg.id, "boom".to_string()).await);
+                    msg.id
+                }
+            };
+            let create = |target: DeadLetterTarget| QueueCreateOptions { max_retries: Some(1), dead_letter: Some(target), ..Default::default() };
+
+            // Another queue, created on first use: the envelope carries the failure metadata
+            let q = format!("feature_dl_queue_{}", Uuid::new_v4());
+            let target = format!("{}_failed", q);
+            manager.create_queue(q.clone(), create(DeadLetterTarget::Queue { name: target.clone() })).await.unwrap();
+            let id = fail_once(&q).await;
+            let mut forwarded = None;
+            for _ in 0..50 {
+                forwarded = manager.pop(&target).await;
+                if forwarded.is_some() { break; }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            let forwarded = forwarded.expect("dead letter reaches the target queue");
+            assert_eq!(forwarded.priority, 3);
+            let letter = DeadLetterEnvelope::decode(&forwarded.payload).unwrap();
+            assert_eq!((letter.source.as_str(), letter.id), (q.as_str(), id.to_string()));
+            assert_eq!((letter.attempts, letter.reason.as_str()), (1, "boom"));
+            assert_eq!(letter.payload, Bytes::from("{\"order\":1}"));
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 0, "Nothing kept in the own DLQ");
+
+            // A letter the target refuses stays in the own DLQ
+            let q = format!("feature_dl_refused_{}", Uuid::new_v4());
+            let target = format!("{}_failed", q);
+            manager.create_queue(target.clone(), QueueCreateOptions::default()).await.unwrap();
+            manager.set_schema(&target, serde_json::json!({ "type": "object", "required": ["never"] })).unwrap();
+            manager.create_queue(q.clone(), create(DeadLetterTarget::Queue { name: target.clone() })).await.unwrap();
+            let id = fail_once(&q).await;
+            let mut kept = Vec::new();
+            for _ in 0..50 {
+                kept = manager.peek_dlq(&q, 10, 0).await.unwrap().1;
+                if !kept.is_empty() { break; }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(kept.iter().map(|m| m.id).collect::<Vec<_>>(), vec![id]);
+            assert!(manager.pop(&target).await.is_none());
+
+            // Stream topic
+            let q = format!("feature_dl_stream_{}", Uuid::new_v4());
+            let topic = format!("{}-dead", q);
+            manager.create_queue(q.clone(), create(DeadLetterTarget::Stream { topic: topic.clone() })).await.unwrap();
+            fail_once(&q).await;
+            let mut records = Vec::new();
+            for _ in 0..50 {
+                records = stream.read(&topic, 0, 10).await;
+                if !records.is_empty() { break; }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            assert_eq!(records.len(), 1);
+            assert_eq!(DeadLetterEnvelope::decode(&records[0].payload).unwrap().source, q);
+
+            // Disabled: dropped
+            let q = format!("feature_dl_off_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), create(DeadLetterTarget::Disabled)).await.unwrap();
+            fail_once(&q).await;
+            assert_eq!(manager.peek_dlq(&q, 10, 0).await.unwrap().0, 0);
+            assert!(manager.pop(&q).await.is_none());
+
+            // A queue can't dead-letter into itself
+            let q = format!("feature_dl_self_{}", Uuid::new_v4());
+            let err = manager.create_queue(q.clone(), create(DeadLetterTarget::Queue { name: q.clone() })).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+            // Nor into a target it could never create
+            let err = manager.create_queue(q.clone(), create(DeadLetterTarget::Queue { name: "no/such queue".to_string() })).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidName);
+            let err = manager.create_queue(q.clone(), create(DeadLetterTarget::Stream { topic: String::new() })).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidName);
+        }
+
         #[tokio::test]
         async fn test_delete_queue() {
             let (manager, _tmp) = setup_queue_manager().await;
@@ -204,6 +807,176 @@ mod queue_tests {
             manager2.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
             assert!(manager2.pop(&q).await.is_none(), "Queue should be empty after delete and recreation");
         }
+
+        #[tokio::test]
+        async fn test_message_ids_are_time_ordered() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_ids_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            let before = nexo::brokers::queue::current_time_ms();
+            manager.push(q.clone(), Bytes::from("first"), 0).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(2)).await;
+            manager.push(q.clone(), Bytes::from("second"), 0).await.unwrap();
+
+            let m1 = manager.pop(&q).await.unwrap();
+            let m2 = manager.pop(&q).await.unwrap();
+            assert_eq!(m1.id.get_version_num(), 7);
+            assert!(m1.id < m2.id, "Ids should sort by creation time");
+
+            let ts = nexo::brokers::queue::id_timestamp_ms(&m1.id).expect("v7 id carries a timestamp");
+            assert!(ts >= before && ts <= m2.created_at);
+            assert_eq!(nexo::brokers::queue::id_timestamp_ms(&Uuid::new_v4()), None);
+        }
+
+        #[tokio::test]
+        async fn test_bytes_accounting_and_compaction() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_bytes_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            manager.push(q.clone(), Bytes::from("12345"), 0).await.unwrap();
+            manager.push(q.clone(), Bytes::from("123"), 5).await.unwrap();
+
+            let bytes_of = |snap: Vec<nexo::brokers::queue::snapshot::QueueSnapshot>| {
+                snap.into_iter().find(|s| s.name == q).map(|s| (s.bytes, s.index_drift)).unwrap()
+            };
+            assert_eq!(bytes_of(manager.get_snapshot().await), (8, 0));
+
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.ack(&q, msg.id).await);
+            assert_eq!(bytes_of(manager.get_snapshot().await), (5, 0));
+
+            // A consistent state has nothing to repair
+            assert_eq!(manager.compact(&q).await.unwrap(), 0);
+            assert_eq!(bytes_of(manager.get_snapshot().await), (5, 0));
+        }
+
+        #[tokio::test]
+        async fn test_message_trace_lifecycle() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_trace_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(2), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            // Untraced queues record nothing
+            manager.push(q.clone(), Bytes::from("quiet"), 0).await.unwrap();
+            let quiet = manager.pop(&q).await.unwrap();
+            assert!(manager.trace(&quiet.id.to_string()).is_none());
+            manager.ack(&q, quiet.id).await;
+
+            manager.set_tracing(&q, true).unwrap();
+            manager.push(q.clone(), Bytes::from("traced"), 0).await.unwrap();
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.nack(&q, msg.id, "boom".to_string()).await);
+            let msg = manager.consume_batch(q.clone(), Some(1), Some(0)).await.unwrap().remove(0);
+            assert!(manager.nack(&q, msg.id, "boom again".to_string()).await);
+            assert!(manager.move_to_queue(&q, msg.id).await.unwrap());
+            let msg = manager.pop(&q).await.unwrap();
+            assert!(manager.ack(&q, msg.id).await);
+
+            let trace = manager.trace(&msg.id.to_string()).expect("Traced message should be recorded");
+            assert_eq!(trace.subject, q);
+            let kinds: Vec<TraceEventKind> = trace.events.iter().map(|e| e.kind).collect();
+            assert_eq!(kinds, vec![
+                TraceEventKind::Pushed,
+                TraceEventKind::Dispatched,
+                TraceEventKind::Nacked,
+                TraceEventKind::Dispatched,
+                TraceEventKind::Nacked,
+                TraceEventKind::DeadLettered,
+                TraceEventKind::Replayed,
+                TraceEventKind::Dispatched,
+                TraceEventKind::Acked,
+            ]);
+            assert_eq!(trace.events[2].detail.as_deref(), Some("boom"));
+
+            // Deleting the queue drops its traces
+            manager.delete_queue(q.clone()).await.unwrap();
+            assert!(manager.trace(&msg.id.to_string()).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_schema_validation_on_push() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("feature_schema_{}", Uuid::new_v4());
+            let json = |s: &str| Bytes::from([&[0x02u8][..], s.as_bytes()].concat());
+
+            assert!(manager.set_schema(&q, serde_json::json!({})).is_err(), "Unknown queue should be rejected");
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            let schema = serde_json::json!({
+                "type": "object",
+                "properties": { "user": { "type": "object", "properties": { "age": { "type": "integer" } } } },
+                "required": ["user"]
+            });
+            assert_eq!(manager.set_schema(&q, schema).unwrap(), 1);
+            assert!(manager.set_schema(&q, serde_json::json!({ "type": 42 })).is_err(), "Invalid schema should be rejected");
+
+            manager.push(q.clone(), json(r#"{"user":{"age":30}}"#), 0).await.unwrap();
+
+            let err = manager.push(q.clone(), json(r#"{"user":{"age":"thirty"}}"#), 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::SchemaViolation);
+            assert!(err.message.contains("/user/age"), "Error should name the failing path: {}", err.message);
+            assert!(manager.push(q.clone(), Bytes::from_static(&[0x00, 0xFF]), 0).await.is_err(), "Raw payloads can't match a schema");
+
+            // MessagePack payloads are decoded and validated like JSON: {"user": {"age": 30 | "x"}}
+            let msgpack_user = |age: &[u8]| Bytes::from([&[0x04u8, 0x81, 0xA4][..], b"user", &[0x81, 0xA3], b"age", age].concat());
+            manager.push(q.clone(), msgpack_user(&[0x1E]), 0).await.unwrap();
+            let err = manager.push(q.clone(), msgpack_user(&[0xA1, b'x']), 0).await.unwrap_err();
+            assert!(err.message.contains("/user/age"), "MessagePack payload should be validated: {}", err.message);
+
+            // A new version replaces the active schema, older versions stay retrievable
+            assert_eq!(manager.set_schema(&q, serde_json::json!({ "type": "object" })).unwrap(), 2);
+            manager.push(q.clone(), json(r#"{"user":{"age":"thirty"}}"#), 0).await.unwrap();
+            assert_eq!(manager.get_schema(&q, None).unwrap().version, 2);
+            assert_eq!(manager.get_schema(&q, Some(1)).unwrap().schema["required"][0], "user");
+            assert!(manager.get_schema(&q, Some(3)).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_idle_queue_is_reported_and_archived() {
+            let (manager, clock, tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("feature_idle_{}", Uuid::new_v4());
+            let day = Duration::from_secs(24 * 60 * 60);
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            clock.advance(day * 2);
+            manager.push(q.clone(), Bytes::from("last"), 0).await.unwrap();
+            let activity = manager.activity().get(&q).unwrap();
+            assert!(activity.last_publish > activity.tracked_since);
+            assert_eq!(activity.last_consume, 0);
+            assert!(manager.activity().idle_since(day.as_millis() as u64).is_empty(), "Just published");
+
+            clock.advance(day * 3);
+            let idle = manager.activity().idle_since(day.as_millis() as u64 * 3);
+            assert_eq!(idle.len(), 1);
+            assert_eq!(idle[0].0, q);
+            assert_eq!(idle[0].2, (day * 3).as_millis() as u64);
+
+            let target = tmp.path().join("archive").join(&q);
+            manager.archive_queue(q.clone(), &target).await.unwrap();
+            assert!(target.join(format!("{}.db", q)).is_file(), "Database moved to the archive");
+            assert!(!tmp.path().join(format!("{}.db", q)).exists());
+            assert!(!manager.queue_names().contains(&q));
+            assert!(manager.activity().get(&q).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_invalid_queue_names_are_rejected() {
+            let (manager, _tmp) = setup_queue_manager().await;
+
+            for name in ["../escape", "a/b", ".hidden", "with space", ""] {
+                let err = manager.create_queue(name.to_string(), QueueCreateOptions::default()).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::InvalidName, "{:?} must be rejected", name);
+            }
+
+            manager.create_queue("orders".to_string(), QueueCreateOptions::default()).await.unwrap();
+            let err = manager.create_queue("Orders".to_string(), QueueCreateOptions::default()).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidName);
+            assert!(err.message.contains("only in case"), "{}", err.message);
+            assert!(manager.create_queue("orders".to_string(), QueueCreateOptions::default()).await.is_ok(), "Re-creating is still a no-op");
+        }
     }
 
     // =========================================================================================
@@ -239,6 +1012,138 @@ mod queue_tests {
             assert!(batch3.is_empty());
         }
 
+        #[tokio::test]
+        async fn test_batch_consume_priority_quotas() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_quota_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            for i in 0..10 {
+                manager.push(q.clone(), Bytes::from(format!("high_{}", i)), 10).await.unwrap();
+            }
+            for i in 0..5 {
+                manager.push(q.clone(), Bytes::from(format!("low_{}", i)), 0).await.unwrap();
+            }
+
+            let quotas = std::collections::HashMap::from([(10u8, 0.6)]);
+            let payloads = |msgs: Vec<nexo::brokers::queue::Message>| msgs.into_iter().map(|m| m.payload).collect::<Vec<_>>();
+
+            // At most 60% high priority while low priority messages are waiting
+            let batch = manager.consume_batch_with_quotas(q.clone(), Some(5), Some(0), Some(&quotas)).await.unwrap();
+            assert_eq!(payloads(batch), vec!["high_0", "high_1", "high_2", "low_0", "low_1"]);
+
+            // Slots low priority can't fill go back to high priority, FIFO within each bucket
+            let batch = manager.consume_batch_with_quotas(q.clone(), Some(10), Some(0), Some(&quotas)).await.unwrap();
+            assert_eq!(payloads(batch), vec!["high_3", "high_4", "high_5", "high_6", "high_7", "high_8", "high_9", "low_2", "low_3", "low_4"]);
+
+            let invalid = std::collections::HashMap::from([(10u8, 1.5)]);
+            assert!(manager.consume_batch_with_quotas(q.clone(), Some(5), Some(0), Some(&invalid)).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_consumer_stats() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_consumers_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            for i in 0..5 {
+                manager.push(q.clone(), Bytes::from(format!("m{}", i)), 0).await.unwrap();
+            }
+
+            let live = CancellationToken::new();
+            let a = manager.consume_batch_as(q.clone(), Some(3), Some(0), None, Some("conn-a"), &live).await.unwrap();
+            let b = manager.consume_batch_as(q.clone(), Some(10), Some(0), None, Some("conn-b"), &live).await.unwrap();
+            assert_eq!((a.len(), b.len()), (3, 2));
+            assert!(manager.ack(&q, a[0].id).await);
+            assert!(manager.ack(&q, a[1].id).await);
+            assert!(manager.nack(&q, b[0].id, "boom".to_string()).await);
+            // Anonymous consumers (webhooks, HTTP) are not tracked
+            assert!(manager.consume_batch(q.clone(), Some(1), Some(0)).await.unwrap().len() == 1);
+
+            let consumers = |snapshot: Vec<nexo::brokers::queue::snapshot::QueueSnapshot>| {
+                snapshot.into_iter().find(|s| s.name == q).unwrap().consumers
+            };
+            let stats = consumers(manager.get_snapshot().await);
+            assert_eq!(stats.len(), 2);
+            let conn_a = stats.iter().find(|c| c.id == "conn-a").unwrap();
+            assert_eq!((conn_a.prefetch, conn_a.delivered, conn_a.acked, conn_a.in_flight), (3, 3, 2, 1));
+            assert!(conn_a.ack_rate > 0.0);
+            let conn_b = stats.iter().find(|c| c.id == "conn-b").unwrap();
+            assert_eq!((conn_b.prefetch, conn_b.delivered, conn_b.nacked, conn_b.in_flight), (10, 2, 1, 1));
+
+            manager.disconnect("conn-a");
+            let stats = consumers(manager.get_snapshot().await);
+            assert_eq!(stats.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), vec!["conn-b"]);
+        }
+
+        #[tokio::test]
+        async fn test_cancelled_consume_returns_messages() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_cancel_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            // A long poll ends as soon as its client goes away
+            let cancel = CancellationToken::new();
+            let waiting = {
+                let (manager, q, cancel) = (manager.clone(), q.clone(), cancel.clone());
+                tokio::spawn(async move { manager.consume_batch_as(q, Some(5), Some(30_000), None, Some("conn-a"), &cancel).await })
+            };
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let started = Instant::now();
+            cancel.cancel();
+            assert!(waiting.await.unwrap().unwrap().is_empty());
+            assert!(started.elapsed() < Duration::from_secs(1));
+
+            // Messages taken for a cancelled request go back untouched
+            manager.push(q.clone(), Bytes::from("m"), 0).await.unwrap();
+            assert!(manager.consume_batch_as(q.clone(), Some(5), Some(0), None, Some("conn-a"), &cancel).await.unwrap().is_empty());
+            let msgs = manager.consume_batch_as(q.clone(), Some(5), Some(0), None, Some("conn-b"), &CancellationToken::new()).await.unwrap();
+            assert_eq!((msgs.len(), msgs[0].attempts), (1, 1));
+
+            let snapshot = manager.get_snapshot().await.into_iter().find(|s| s.name == q).unwrap();
+            let conn_a = snapshot.consumers.iter().find(|c| c.id == "conn-a").unwrap();
+            assert_eq!((conn_a.delivered, conn_a.in_flight), (0, 0));
+        }
+
+        #[tokio::test]
+        async fn test_priority_lanes_and_purge() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_lanes_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            for i in 0..3 {
+                manager.push(q.clone(), Bytes::from(format!("bulk{}", i)), 0).await.unwrap();
+            }
+            manager.push(q.clone(), Bytes::from("urgent"), 9).await.unwrap();
+            manager.push_delayed(q.clone(), Bytes::from("bulk-later"), 0, 60_000).await.unwrap();
+
+            let urgent = manager.pop(&q).await.unwrap();
+            assert!(manager.ack(&q, urgent.id).await);
+            let bulk = manager.pop(&q).await.unwrap();
+
+            let lanes = |snapshot: Vec<nexo::brokers::queue::snapshot::QueueSnapshot>| {
+                snapshot.into_iter().find(|s| s.name == q).unwrap().priorities
+            };
+            let stats = lanes(manager.get_snapshot().await);
+            assert_eq!(stats.iter().map(|l| l.priority).collect::<Vec<_>>(), vec![9, 0], "Highest priority first");
+            assert_eq!((stats[0].pending, stats[0].pushed, stats[0].acked), (0, 1, 1));
+            assert!(stats[0].ack_rate > 0.0);
+            assert_eq!((stats[1].pending, stats[1].inflight, stats[1].scheduled, stats[1].pushed), (2, 1, 1, 4));
+
+            let (total, _) = manager.get_messages(q.clone(), "pending".to_string(), Some(0), 0, 10, None).await.unwrap();
+            assert_eq!(total, 2);
+            let (total, _) = manager.get_messages(q.clone(), "pending".to_string(), Some(9), 0, 10, None).await.unwrap();
+            assert_eq!(total, 0);
+
+            // Pending and scheduled go, the in-flight message stays with its consumer
+            assert_eq!(manager.purge_priority(&q, 0).await.unwrap(), 3);
+            let stats = lanes(manager.get_snapshot().await);
+            assert_eq!((stats[1].pending, stats[1].inflight, stats[1].scheduled), (0, 1, 0));
+            assert!(manager.ack(&q, bulk.id).await);
+            assert!(manager.pop(&q).await.is_none());
+
+            let err = manager.purge_priority("missing", 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotFound);
+        }
+
         #[tokio::test]
         async fn test_long_polling() {
             let (manager, _tmp) = setup_queue_manager().await;
@@ -378,6 +1283,379 @@ mod queue_tests {
             assert!(manager.ack(&q, batch_b[0].id).await);
         }
 
+        /// Starts a webhook receiver answering `status` and returns its URL and hit counter.
+        async fn start_webhook(status: u16) -> (String, Arc<AtomicUsize>) {
+            let hits = Arc::new(AtomicUsize::new(0));
+            let counter = hits.clone();
+            let app = axum::Router::new().route("/hook", axum::routing::post(move |body: Bytes| {
+                let counter = counter.clone();
+                async move {
+                    assert_eq!(body, Bytes::from("pushed"));
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    axum::http::StatusCode::from_u16(status).unwrap()
+                }
+            }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            (format!("http://{}/hook", addr), hits)
+        }
+
+        #[tokio::test]
+        async fn test_webhook_delivery_acks_on_success() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_webhook_ok_{}", Uuid::new_v4());
+            let (url, hits) = start_webhook(200).await;
+
+            let config = QueueCreateOptions {
+                webhook: Some(WebhookOptions { url, timeout_ms: None, concurrency: Some(2) }),
+                ..Default::default()
+            };
+            manager.create_queue(q.clone(), config).await.unwrap();
+            manager.push(q.clone(), Bytes::from("pushed"), 0).await.unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while hits.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            assert_eq!(hits.load(Ordering::SeqCst), 1, "Message should be delivered exactly once");
+            assert!(manager.pop(&q).await.is_none(), "Delivered message should be acked");
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_webhook_failures_end_in_dlq() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_webhook_fail_{}", Uuid::new_v4());
+            let (url, hits) = start_webhook(500).await;
+
+            let config = QueueCreateOptions {
+                max_retries: Some(2),
+                webhook: Some(WebhookOptions { url, timeout_ms: None, concurrency: None }),
+                ..Default::default()
+            };
+            manager.create_queue(q.clone(), config).await.unwrap();
+            manager.push(q.clone(), Bytes::from("pushed"), 0).await.unwrap();
+
+            // Attempt 1 fails, backs off, attempt 2 fails and dead-letters
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let (total, dlq) = manager.peek_dlq(&q, 10, 0).await.unwrap();
+                if total == 1 {
+                    assert!(dlq[0].failure_reason.contains("500"));
+                    break;
+                }
+                assert!(Instant::now() < deadline, "Message should reach the DLQ");
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert_eq!(hits.load(Ordering::SeqCst), 2);
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_webhook_rejects_invalid_url() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let config = QueueCreateOptions {
+                webhook: Some(WebhookOptions { url: "ftp://example".to_string(), timeout_ms: None, concurrency: None }),
+                ..Default::default()
+            };
+            assert!(manager.create_queue(format!("adv_webhook_bad_{}", Uuid::new_v4()), config).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_message_groups_deliver_in_order() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_groups_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            let grouped = |group: &str| QueuePushOptions { message_group: Some(group.to_string()), ..Default::default() };
+            for payload in ["a1", "a2", "a3"] {
+                manager.push_with(q.clone(), Bytes::from(payload), grouped("a")).await.unwrap();
+            }
+            manager.push_with(q.clone(), Bytes::from("b1"), grouped("b")).await.unwrap();
+            manager.push(q.clone(), Bytes::from("free"), 0).await.unwrap();
+
+            // One message per group at a time, groups in parallel
+            let batch = manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap();
+            let payloads: Vec<Bytes> = batch.iter().map(|m| m.payload.clone()).collect();
+            assert_eq!(payloads, vec![Bytes::from("a1"), Bytes::from("b1"), Bytes::from("free")]);
+            assert!(manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap().is_empty());
+            let pending = manager.get_snapshot().await.into_iter().find(|s| s.name == q).unwrap().pending;
+            assert_eq!(pending, 2, "Held group messages still count as pending");
+
+            // A nacked message is retried before the rest of its group
+            assert!(manager.nack(&q, batch[0].id, "retry".to_string()).await);
+            let retry = manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap();
+            assert_eq!(retry.len(), 1);
+            assert_eq!(retry[0].id, batch[0].id);
+
+            // Acking lets the next one through, waking a long-poll
+            let waiter = {
+                let manager = manager.clone();
+                let q = q.clone();
+                tokio::spawn(async move { manager.consume_batch(q, Some(10), Some(2_000)).await.unwrap() })
+            };
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(manager.ack(&q, retry[0].id).await);
+            let next = tokio::time::timeout(Duration::from_millis(500), waiter).await.unwrap().unwrap();
+            assert_eq!(next.len(), 1);
+            assert_eq!(next[0].payload, Bytes::from("a2"));
+
+            // Groups can't mix with fanout, and ids are bounded
+            assert_eq!(manager.push_with(q.clone(), Bytes::from("x"), grouped("")).await.unwrap_err().code, ErrorCode::InvalidRequest);
+            assert!(manager.push_with(q.clone(), Bytes::from("x"), grouped(&"g".repeat(200))).await.is_err());
+            let fanout = format!("adv_groups_fanout_{}", Uuid::new_v4());
+            manager.create_queue(fanout.clone(), QueueCreateOptions { fanout: Some(true), ..Default::default() }).await.unwrap();
+            assert!(manager.push_with(fanout, Bytes::from("x"), grouped("a")).await.is_err());
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_idempotency_key_drops_duplicates_within_window() {
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("adv_dedup_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            let keyed = |key: &str| QueuePushOptions { idempotency_key: Some(key.to_string()), ..Default::default() };
+
+            // Two producers emit the same event: the second push is accepted and dropped
+            manager.push_with(q.clone(), Bytes::from("order-1"), keyed("evt-1")).await.unwrap();
+            manager.push_with(q.clone(), Bytes::from("order-1"), keyed("evt-1")).await.unwrap();
+            manager.push_with(q.clone(), Bytes::from("order-2"), keyed("evt-2")).await.unwrap();
+            let batch = manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap();
+            assert_eq!(batch.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("order-1"), Bytes::from("order-2")]);
+
+            // Still a duplicate after the original was consumed, until the window ends
+            for msg in &batch {
+                assert!(manager.ack(&q, msg.id).await);
+            }
+            manager.push_with(q.clone(), Bytes::from("order-1"), keyed("evt-1")).await.unwrap();
+            assert!(manager.pop(&q).await.is_none());
+
+            let window = nexo::config::Config::global().queue.dedup_window_ms;
+            clock.advance(Duration::from_millis(window));
+            manager.push_with(q.clone(), Bytes::from("order-1"), keyed("evt-1")).await.unwrap();
+            assert_eq!(manager.pop(&q).await.unwrap().payload, Bytes::from("order-1"));
+
+            // Keys are per queue
+            let other = format!("adv_dedup_other_{}", Uuid::new_v4());
+            manager.create_queue(other.clone(), QueueCreateOptions::default()).await.unwrap();
+            manager.push_with(other.clone(), Bytes::from("order-2"), keyed("evt-2")).await.unwrap();
+            assert!(manager.pop(&other).await.is_some());
+
+            assert_eq!(manager.push_with(q.clone(), Bytes::from("x"), keyed("")).await.unwrap_err().code, ErrorCode::InvalidRequest);
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_archive_keeps_acked_messages_for_replay() {
+            use nexo::brokers::queue::options::ArchiveOptions;
+
+            let (manager, clock, _tmp) = setup_queue_manager_with_clock().await;
+            let q = format!("adv_archive_{}", Uuid::new_v4());
+            let archive = ArchiveOptions { max_age_ms: Some(60_000), max_bytes: None };
+            manager.create_queue(q.clone(), QueueCreateOptions { archive: Some(archive.clone()), ..Default::default() }).await.unwrap();
+            for payload in ["a", "b", "c"] {
+                manager.push(q.clone(), Bytes::from(payload), 3).await.unwrap();
+            }
+            for _ in 0..2 {
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.ack(&q, msg.id).await);
+            }
+
+            // Newest first, with what the message looked like when acked
+            let (total, archived) = manager.peek_archive(&q, 10, 0).await.unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(archived.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("b"), Bytes::from("a")]);
+            assert_eq!((archived[1].priority, archived[1].attempts), (3, 1));
+            assert_eq!(manager.peek_archive(&q, 1, 1).await.unwrap().1[0].payload, Bytes::from("a"));
+
+            // Replay pushes a copy; the archive keeps its record
+            assert!(manager.replay_archived(&q, archived[1].id).await.unwrap());
+            assert!(!manager.replay_archived(&q, Uuid::new_v4()).await.unwrap());
+            let batch = manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap();
+            assert_eq!(batch.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("c"), Bytes::from("a")]);
+            assert_ne!(batch[1].id, archived[1].id);
+            assert_eq!(manager.peek_archive(&q, 10, 0).await.unwrap().0, 2);
+
+            // Age retention
+            clock.advance(Duration::from_millis(60_000));
+            assert_eq!(manager.peek_archive(&q, 10, 0).await.unwrap().0, 0);
+            assert!(!manager.replay_archived(&q, archived[0].id).await.unwrap());
+
+            let plain = format!("adv_archive_off_{}", Uuid::new_v4());
+            manager.create_queue(plain.clone(), QueueCreateOptions::default()).await.unwrap();
+            assert_eq!(manager.peek_archive(&plain, 10, 0).await.unwrap_err().code, ErrorCode::InvalidRequest);
+            let fanout = QueueCreateOptions { fanout: Some(true), archive: Some(archive), ..Default::default() };
+            assert_eq!(manager.create_queue(format!("adv_archive_fanout_{}", Uuid::new_v4()), fanout).await.unwrap_err().code, ErrorCode::InvalidRequest);
+        }
+
+        #[tokio::test]
+        async fn test_archive_size_retention_drops_oldest() {
+            use nexo::brokers::queue::options::ArchiveOptions;
+
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_archive_size_{}", Uuid::new_v4());
+            let archive = ArchiveOptions { max_age_ms: None, max_bytes: Some(400) };
+            manager.create_queue(q.clone(), QueueCreateOptions { archive: Some(archive), ..Default::default() }).await.unwrap();
+            for i in 0..10 {
+                manager.push(q.clone(), Bytes::from(format!("{:0>100}", i)), 0).await.unwrap();
+                let msg = manager.pop(&q).await.unwrap();
+                assert!(manager.ack(&q, msg.id).await);
+            }
+
+            let (total, archived) = manager.peek_archive(&q, 10, 0).await.unwrap();
+            assert_eq!(total, 2, "Two ~155 byte records fit in 400 bytes");
+            assert_eq!(archived[0].payload, Bytes::from(format!("{:0>100}", 9)));
+            assert_eq!(archived[0].seq, 9);
+            let stats = manager.get_snapshot().await.into_iter().find(|s| s.name == q).unwrap().archive.unwrap();
+            assert_eq!(stats.records, 2);
+            assert!(stats.bytes <= 400);
+        }
+
+        #[tokio::test]
+        async fn test_memory_budget_sheds_non_durable_pushes() {
+            let tmp = tempfile::tempdir().unwrap();
+            let path = tmp.path().to_str().unwrap();
+            let mut config = nexo::config::Config::global().clone();
+            config.store.persistence_path = format!("{}/store", path);
+            config.pubsub.persistence_path = format!("{}/pubsub", path);
+            config.queue.persistence_path = format!("{}/queue", path);
+            config.stream.persistence_path = format!("{}/stream", path);
+            config.memory.budget_bytes = 10_000;
+            config.memory.high_water_percent = 50;
+            config.memory.sample_interval_ms = 3_600_000; // sampled by hand
+            let engine = nexo::NexoEngine::new(&config).await;
+            let queue = &engine.queue;
+
+            let persistence = |mode| QueueCreateOptions { persistence: Some(mode), ..Default::default() };
+            queue.create_queue("shed-async".into(), persistence(PersistenceMode::FileAsync)).await.unwrap();
+            queue.create_queue("shed-sync".into(), persistence(PersistenceMode::FileSync)).await.unwrap();
+            for _ in 0..6 {
+                queue.push("shed-async".into(), Bytes::from(vec![0u8; 1000]), 0).await.unwrap();
+            }
+            assert_eq!(engine.sample_memory().await, nexo::brokers::memory::Pressure::High);
+
+            // High: only fsynced pushes get through
+            let err = queue.push("shed-async".into(), Bytes::from("x"), 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::Busy);
+            assert!(err.retryable);
+            assert!(engine.pubsub.publish("shed/topic", Bytes::from("x"), false, None).is_err());
+            for _ in 0..4 {
+                queue.push("shed-sync".into(), Bytes::from(vec![0u8; 1000]), 0).await.unwrap();
+            }
+
+            // Critical: nothing gets through
+            assert_eq!(engine.sample_memory().await, nexo::brokers::memory::Pressure::Critical);
+            let err = queue.push("shed-sync".into(), Bytes::from("x"), 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::Busy);
+
+            // Consumers drain the queues: pushes are accepted again
+            for name in ["shed-async", "shed-sync"] {
+                while let Some(msg) = queue.pop(name).await {
+                    assert!(queue.ack(name, msg.id).await);
+                }
+            }
+            assert_eq!(engine.sample_memory().await, nexo::brokers::memory::Pressure::Normal);
+            queue.push("shed-async".into(), Bytes::from("x"), 0).await.unwrap();
+            assert_eq!(engine.memory.snapshot().rejections, 3);
+        }
+
+        #[tokio::test]
+        async fn test_push_subscription_respects_prefetch() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_push_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            for i in 0..5 {
+                manager.push(q.clone(), Bytes::from(format!("m{}", i)), 0).await.unwrap();
+            }
+
+            let options = QueueSubscribeOptions { prefetch: Some(2), ..Default::default() };
+            // Push needs a connection to deliver to
+            assert!(manager.subscribe(&q, "conn-push", QueueSubscribeOptions::default()).is_err());
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            manager.connect("conn-push", tx);
+            let zero = QueueSubscribeOptions { prefetch: Some(0), ..Default::default() };
+            assert_eq!(manager.subscribe(&q, "conn-push", zero).unwrap_err().code, ErrorCode::InvalidRequest);
+            manager.subscribe(&q, "conn-push", options).unwrap();
+
+            let recv = |rx: &mut tokio::sync::mpsc::UnboundedReceiver<_>| {
+                let mut messages = Vec::new();
+                while let Ok(delivery) = rx.try_recv() {
+                    let delivery: nexo::brokers::queue::push::QueueDelivery = delivery;
+                    assert_eq!(delivery.queue, q);
+                    messages.extend(delivery.messages);
+                }
+                messages
+            };
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let first = recv(&mut rx);
+            assert_eq!(first.len(), 2, "Only the prefetch window is delivered");
+
+            // Settling one message makes room for exactly one more
+            assert!(manager.ack(&q, first[0].id).await);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let refill = recv(&mut rx);
+            assert_eq!(refill.len(), 1);
+            assert_eq!(refill[0].payload, Bytes::from("m2"));
+
+            // New messages wait for room too, then arrive without polling
+            assert!(manager.nack(&q, first[1].id, "retry".to_string()).await);
+            assert!(manager.ack(&q, refill[0].id).await);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(recv(&mut rx).len(), 2);
+
+            let stats = manager.get_snapshot().await.into_iter().find(|s| s.name == q).unwrap().consumers;
+            let conn = stats.iter().find(|c| c.id == "conn-push").unwrap();
+            assert!(conn.push);
+            assert_eq!((conn.prefetch, conn.in_flight), (2, 2));
+
+            assert!(manager.unsubscribe(&q, "conn-push"));
+            assert!(!manager.unsubscribe(&q, "conn-push"));
+            manager.push(q.clone(), Bytes::from("after"), 0).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(recv(&mut rx).is_empty(), "Unsubscribed connection gets nothing");
+            manager.delete_queue(q).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_push_subscription_idle_ttl() {
+            let (manager, _tmp) = setup_queue_manager().await;
+            let q = format!("adv_push_idle_{}", Uuid::new_v4());
+            let empty = format!("adv_push_empty_{}", Uuid::new_v4());
+            for name in [&q, &empty] {
+                manager.create_queue(name.clone(), QueueCreateOptions::default()).await.unwrap();
+            }
+            for i in 0..3 {
+                manager.push(q.clone(), Bytes::from(format!("m{}", i)), 0).await.unwrap();
+            }
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<nexo::brokers::queue::push::QueueDelivery>();
+            manager.connect("conn-idle", tx);
+            let options = QueueSubscribeOptions { prefetch: Some(2), idle_ttl_ms: Some(300), ..Default::default() };
+            manager.subscribe(&q, "conn-idle", options.clone()).unwrap();
+            manager.subscribe(&empty, "conn-idle", options).unwrap();
+
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let first = rx.try_recv().unwrap();
+            assert_eq!(first.messages.len(), 2);
+            // An ack restarts the clock
+            assert!(manager.ack(&q, first.messages[0].id).await);
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(rx.try_recv().unwrap().messages.len(), 1);
+
+            tokio::time::sleep(Duration::from_millis(400)).await;
+            let notice = rx.try_recv().unwrap();
+            assert_eq!(notice.queue, q);
+            assert!(notice.messages.is_empty());
+            assert!(notice.unsubscribed.unwrap().starts_with("no ack or nack for"));
+            assert!(!manager.unsubscribe(&q, "conn-idle"), "Already dropped");
+            let stats = manager.get_snapshot().await.into_iter().find(|s| s.name == q).unwrap().consumers;
+            assert!(!stats.iter().find(|c| c.id == "conn-idle").unwrap().push);
+
+            // Nothing delivered, nothing to settle: never idle
+            assert!(manager.unsubscribe(&empty, "conn-idle"));
+            assert!(rx.try_recv().is_err());
+        }
     }
 
     // =========================================================================================
@@ -387,6 +1665,119 @@ mod queue_tests {
     mod persistence {
         use super::*;
 
+        #[tokio::test]
+        async fn test_wal_checkpoint_reports_health() {
+            let tmp = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = tmp.path().to_str().unwrap().to_string();
+            sys_config.wal_checkpoint_interval_ms = 50;
+            let manager = QueueManager::new(Arc::new(sys_config));
+
+            let q = format!("persist_wal_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            for i in 0..100 {
+                manager.push(q.clone(), Bytes::from(format!("msg_{}", i)), 0).await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(400)).await;
+
+            let snapshot = manager.get_snapshot().await;
+            let health = snapshot.iter().find(|s| s.name == q).unwrap().persistence;
+            assert!(health.wal_bytes > 0, "Writes go through the WAL");
+            assert!(health.last_checkpoint_at > 0, "Checkpoint task ran");
+            assert_eq!(health.checkpoint_lag_frames, 0, "Nothing blocks a checkpoint");
+            assert_eq!(health.checkpoint_failures, 0);
+        }
+
+        #[tokio::test]
+        async fn test_export_import_queue() {
+            use nexo::brokers::queue::options::ImportConflict;
+
+            let (source, _tmp) = setup_queue_manager().await;
+            let q = format!("persist_export_{}", Uuid::new_v4());
+            let config = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+            source.create_queue(q.clone(), config).await.unwrap();
+
+            source.push(q.clone(), Bytes::from("failed"), 0).await.unwrap();
+            let failed = source.pop(&q).await.unwrap();
+            assert!(source.nack(&q, failed.id, "boom".to_string()).await);
+
+            source.push(q.clone(), Bytes::from("low"), 0).await.unwrap();
+            source.push(q.clone(), Bytes::from("high"), 9).await.unwrap();
+            source.push(q.clone(), Bytes::from("inflight"), 5).await.unwrap();
+            let inflight = source.pop(&q).await.unwrap();
+            assert_eq!(inflight.payload, Bytes::from("high"));
+
+            let export_dir = tempfile::tempdir().unwrap();
+            let file = export_dir.path().join("queue.json");
+            let exported = source.export_queue(&q, &file).await.unwrap();
+            assert_eq!(exported.messages, 3);
+            assert_eq!(exported.dlq, 1);
+
+            // Import into another environment under a new name
+            let (target, _tmp2) = setup_queue_manager().await;
+            let imported = target.import_queue(&file, "restored".to_string(), ImportConflict::Fail).await.unwrap();
+            assert_eq!((imported.messages, imported.dlq, imported.skipped), (3, 1, 0));
+
+            // The in-flight message comes back as pending, priorities are kept
+            let order: Vec<Bytes> = vec![
+                target.pop("restored").await.unwrap().payload,
+                target.pop("restored").await.unwrap().payload,
+                target.pop("restored").await.unwrap().payload,
+            ];
+            assert_eq!(order, vec![Bytes::from("high"), Bytes::from("inflight"), Bytes::from("low")]);
+            let (dlq_total, dlq) = target.peek_dlq("restored", 10, 0).await.unwrap();
+            assert_eq!(dlq_total, 1);
+            assert_eq!(dlq[0].failure_reason, "boom");
+
+            // Conflicts
+            let err = target.import_queue(&file, "restored".to_string(), ImportConflict::Fail).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::InvalidRequest);
+
+            let merged = target.import_queue(&file, "restored".to_string(), ImportConflict::Merge).await.unwrap();
+            assert_eq!(merged.messages, 0);
+            assert_eq!(merged.skipped, 4, "every id is still in flight or in the DLQ");
+
+            let replaced = target.import_queue(&file, "restored".to_string(), ImportConflict::Replace).await.unwrap();
+            assert_eq!((replaced.messages, replaced.dlq, replaced.skipped), (3, 1, 0));
+        }
+
+        #[tokio::test]
+        async fn test_integrity_check_queue_files() {
+            use nexo::brokers::integrity::{self, DataDirs, FileKind, FileStatus};
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let q = format!("persist_check_{}", Uuid::new_v4());
+
+            {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+                manager.push(q.clone(), Bytes::from("payload"), 0).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            let config_path = temp_dir.path().join(format!("{}.config.json", q));
+            std::fs::write(&config_path, "{ not json").unwrap();
+
+            let dirs = DataDirs {
+                queue: temp_dir.path().to_path_buf(),
+                stream: temp_dir.path().join("streams"),
+                pubsub: temp_dir.path().join("pubsub"),
+            };
+            let report = integrity::check(&dirs, false);
+            assert!(!report.ok);
+            let db = report.files.iter().find(|f| f.kind == FileKind::Sqlite).unwrap();
+            assert_eq!(db.status, FileStatus::Ok);
+            let json = report.files.iter().find(|f| f.kind == FileKind::Json).unwrap();
+            assert_eq!(json.status, FileStatus::Corrupt);
+
+            let report = integrity::check(&dirs, true);
+            assert!(report.ok);
+            assert!(!config_path.exists());
+            assert!(config_path.with_extension("json.corrupt").exists());
+        }
+
         #[tokio::test]
         async fn test_crash_recovery_messages() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -424,6 +1815,208 @@ mod queue_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_message_groups_survive_restart() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let q = format!("persist_groups_{}", Uuid::new_v4());
+            let grouped = || QueuePushOptions { message_group: Some("orders-42".to_string()), ..Default::default() };
+
+            {
+                let manager1 = QueueManager::new(Arc::new(sys_config.clone()));
+                manager1.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+                manager1.push_with(q.clone(), Bytes::from("first"), grouped()).await.unwrap();
+                manager1.push_with(q.clone(), Bytes::from("second"), grouped()).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            let manager2 = QueueManager::new(Arc::new(sys_config.clone()));
+            manager2.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            let batch = manager2.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap();
+            assert_eq!(batch.len(), 1, "The group is still delivered one at a time");
+            assert_eq!(batch[0].payload, Bytes::from("first"));
+            assert_eq!(batch[0].message_group.as_deref(), Some("orders-42"));
+        }
+
+        fn spill_config(dir: &tempfile::TempDir) -> nexo::brokers::queue::config::SystemQueueConfig {
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = dir.path().to_str().unwrap().to_string();
+            sys_config.spill_threshold = 50;
+            sys_config.spill_chunk = 10;
+            sys_config
+        }
+
+        async fn drain(manager: &QueueManager, q: &str, expected: usize) -> Vec<Bytes> {
+            let mut payloads = Vec::new();
+            for _ in 0..expected {
+                let batch = manager.consume_batch(q.to_string(), Some(7), Some(0)).await.unwrap();
+                for msg in batch {
+                    assert!(manager.ack(q, msg.id).await);
+                    payloads.push(msg.payload);
+                }
+                if payloads.len() >= expected {
+                    break;
+                }
+            }
+            payloads
+        }
+
+        #[tokio::test]
+        async fn test_backlog_spills_to_disk_in_order() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let manager = QueueManager::new(Arc::new(spill_config(&temp_dir)));
+            let q = format!("persist_spill_{}", Uuid::new_v4());
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+
+            for i in 0..200 {
+                manager.push(q.clone(), Bytes::from(format!("low_{}", i)), 0).await.unwrap();
+            }
+            for i in 0..5 {
+                manager.push(q.clone(), Bytes::from(format!("high_{}", i)), 5).await.unwrap();
+            }
+            let snapshot = manager.get_snapshot().await;
+            let s = snapshot.iter().find(|s| s.name == q).unwrap();
+            assert_eq!(s.pending, 205, "Paged-out messages still count as pending");
+            assert!(s.spilled >= 150, "Backlog beyond the head is on disk, got {}", s.spilled);
+            assert!(s.bytes < 60 * "low_199".len(), "Only the head stays in RAM");
+
+            // A requeued message waits behind the ones on disk
+            let first = manager.pop(&q).await.unwrap();
+            assert_eq!(first.payload, Bytes::from("high_0"));
+            assert!(manager.nack(&q, first.id, "retry".to_string()).await);
+
+            let expected: Vec<Bytes> = (1..5).map(|i| format!("high_{}", i))
+                .chain(["high_0".to_string()])
+                .chain((0..200).map(|i| format!("low_{}", i)))
+                .map(Bytes::from)
+                .collect();
+            assert_eq!(drain(&manager, &q, 205).await, expected, "FIFO per priority across spill and reload");
+
+            let snapshot = manager.get_snapshot().await;
+            let s = snapshot.iter().find(|s| s.name == q).unwrap();
+            assert_eq!((s.pending, s.spilled), (0, 0));
+
+            // Purging a priority drops its messages on disk too
+            for i in 0..100 {
+                manager.push(q.clone(), Bytes::from(format!("purged_{}", i)), 3).await.unwrap();
+            }
+            assert_eq!(manager.purge_priority(&q, 3).await.unwrap(), 100);
+            assert!(manager.consume_batch(q.clone(), Some(10), Some(0)).await.unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_spilled_backlog_survives_restart() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let q = format!("persist_spill_restart_{}", Uuid::new_v4());
+
+            {
+                let manager1 = QueueManager::new(Arc::new(spill_config(&temp_dir)));
+                manager1.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+                for i in 0..120 {
+                    manager1.push(q.clone(), Bytes::from(format!("msg_{}", i)), 0).await.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            let manager2 = QueueManager::new(Arc::new(spill_config(&temp_dir)));
+            manager2.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            let snapshot = manager2.get_snapshot().await;
+            let s = snapshot.iter().find(|s| s.name == q).unwrap();
+            assert_eq!(s.pending, 120);
+            assert!(s.spilled > 0, "Paged-out messages stay on disk after recovery");
+
+            let expected: Vec<Bytes> = (0..120).map(|i| Bytes::from(format!("msg_{}", i))).collect();
+            assert_eq!(drain(&manager2, &q, 120).await, expected);
+        }
+
+        #[tokio::test]
+        async fn test_log_backend_survives_restart() {
+            use nexo::brokers::queue::domain::backend::BackendKind;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = spill_config(&temp_dir);
+            sys_config.storage_backend = BackendKind::Log;
+            let q = format!("persist_log_{}", Uuid::new_v4());
+            let dlq_options = QueueCreateOptions { max_retries: Some(1), ..Default::default() };
+
+            {
+                let manager1 = QueueManager::new(Arc::new(sys_config.clone()));
+                manager1.create_queue(q.clone(), dlq_options.clone()).await.unwrap();
+                for i in 0..120 {
+                    manager1.push(q.clone(), Bytes::from(format!("msg_{}", i)), 0).await.unwrap();
+                }
+                let acked = manager1.pop(&q).await.unwrap();
+                assert!(manager1.ack(&q, acked.id).await);
+                let failed = manager1.pop(&q).await.unwrap();
+                assert!(manager1.nack(&q, failed.id, "boom".to_string()).await);
+                let replayed = manager1.pop(&q).await.unwrap();
+                assert!(manager1.nack(&q, replayed.id, "again".to_string()).await);
+                assert!(manager1.move_to_queue(&q, replayed.id).await.unwrap());
+                manager1.close().await;
+            }
+            assert!(temp_dir.path().join(format!("{}.qlog", q)).exists());
+            assert!(!temp_dir.path().join(format!("{}.db", q)).exists());
+
+            // The format on disk wins over the configured default
+            sys_config.storage_backend = BackendKind::Sqlite;
+            let manager2 = QueueManager::new(Arc::new(sys_config));
+            manager2.create_queue(q.clone(), dlq_options).await.unwrap();
+            let snapshot = manager2.get_snapshot().await;
+            let s = snapshot.iter().find(|s| s.name == q).unwrap();
+            assert_eq!(s.pending, 118);
+            assert!(s.spilled > 0, "Paged-out messages stay in the log after recovery");
+            let (dlq_total, dlq) = manager2.peek_dlq(&q, 10, 0).await.unwrap();
+            assert_eq!(dlq_total, 1);
+            assert_eq!(dlq[0].payload, Bytes::from("msg_1"));
+            assert_eq!(dlq[0].failure_reason, "boom");
+
+            let mut expected: Vec<Bytes> = (2..120).map(|i| Bytes::from(format!("msg_{}", i))).collect();
+            let mut drained = drain(&manager2, &q, 118).await;
+            expected.sort();
+            drained.sort();
+            assert_eq!(drained, expected, "Replayed and paged-out messages are all recovered");
+            assert!(!temp_dir.path().join(format!("{}.db", q)).exists());
+        }
+
+        #[tokio::test]
+        async fn test_archive_survives_restart() {
+            use nexo::brokers::queue::options::ArchiveOptions;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let q = format!("persist_archive_{}", Uuid::new_v4());
+            let options = || QueueCreateOptions { archive: Some(ArchiveOptions::default()), ..Default::default() };
+
+            {
+                let manager1 = QueueManager::new(Arc::new(sys_config.clone()));
+                manager1.create_queue(q.clone(), options()).await.unwrap();
+                for payload in ["first", "second"] {
+                    manager1.push(q.clone(), Bytes::from(payload), 0).await.unwrap();
+                    let msg = manager1.pop(&q).await.unwrap();
+                    assert!(manager1.ack(&q, msg.id).await);
+                }
+                manager1.close().await;
+            }
+
+            let manager2 = QueueManager::new(Arc::new(sys_config.clone()));
+            manager2.push(q.clone(), Bytes::from("third"), 0).await.unwrap();
+            let msg = manager2.pop(&q).await.unwrap();
+            assert!(manager2.ack(&q, msg.id).await);
+
+            let (total, archived) = manager2.peek_archive(&q, 10, 0).await.unwrap();
+            assert_eq!(total, 3);
+            assert_eq!(archived.iter().map(|m| (m.seq, m.payload.clone())).collect::<Vec<_>>(), vec![
+                (2, Bytes::from("third")),
+                (1, Bytes::from("second")),
+                (0, Bytes::from("first")),
+            ]);
+
+            manager2.delete_queue(q.clone()).await.unwrap();
+            assert!(!temp_dir.path().join(format!("{}.archive", q)).exists());
+        }
+
         #[tokio::test]
         async fn test_acked_persistence() {
             let q = format!("persist_acked_{}", Uuid::new_v4());
@@ -463,6 +2056,217 @@ mod queue_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_fanout_groups_survive_restart() {
+            let q = format!("persist_fanout_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let config = QueueCreateOptions { fanout: Some(true), ..Default::default() };
+
+            {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q.clone(), config.clone()).await.unwrap();
+                manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap();
+                manager.consume_group(&q, "cache-b", Some(10), Some(0)).await.unwrap();
+
+                manager.push(q.clone(), Bytes::from("m1"), 0).await.unwrap();
+                manager.push(q.clone(), Bytes::from("m2"), 0).await.unwrap();
+                let a = manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap();
+                let b = manager.consume_group(&q, "cache-b", Some(10), Some(0)).await.unwrap();
+                // m1 settled by both groups, m2 only by A
+                manager.ack_group(&q, "cache-a", a[0].id).await;
+                manager.ack_group(&q, "cache-a", a[1].id).await;
+                manager.ack_group(&q, "cache-b", b[0].id).await;
+
+                manager.flush().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+
+            {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q.clone(), config).await.unwrap();
+                let snapshot = manager.get_snapshot().await;
+                assert_eq!(snapshot[0].groups.len(), 2, "Bound groups are restored");
+
+                // Each group resumes where it left off: B still owes m2, in flight at shutdown
+                assert!(manager.consume_group(&q, "cache-a", Some(10), Some(0)).await.unwrap().is_empty());
+                let msgs = manager.consume_group(&q, "cache-b", Some(10), Some(0)).await.unwrap();
+                let payloads: Vec<Bytes> = msgs.iter().map(|m| m.payload.clone()).collect();
+                assert_eq!(payloads, vec![Bytes::from("m2")]);
+                assert_eq!(msgs[0].attempts, 2);
+
+                // Newer messages go to both
+                manager.push(q.clone(), Bytes::from("m3"), 0).await.unwrap();
+                for group in ["cache-a", "cache-b"] {
+                    let msgs = manager.consume_group(&q, group, Some(10), Some(0)).await.unwrap();
+                    assert_eq!(msgs.into_iter().map(|m| m.payload).collect::<Vec<_>>(), vec![Bytes::from("m3")]);
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn test_file_sync_push_is_durable_on_ack() {
+            let q = format!("persist_sync_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            // The periodic flush never fires during the test: only group commits write
+            sys_config.default_flush_ms = 60_000;
+            sys_config.group_commit_ms = 5;
+
+            let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+            let config = QueueCreateOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() };
+            manager.create_queue(q.clone(), config).await.unwrap();
+
+            let handles: Vec<_> = (0..20).map(|i| {
+                let manager = manager.clone();
+                let q = q.clone();
+                tokio::spawn(async move { manager.push(q, Bytes::from(format!("sync-{}", i)), 0).await })
+            }).collect();
+            for handle in handles {
+                handle.await.unwrap().unwrap();
+            }
+
+            // Every acked push is already on disk, while the first manager is still running
+            let manager2 = QueueManager::new(std::sync::Arc::new(sys_config));
+            let batch = manager2.consume_batch(q.clone(), Some(100), Some(0)).await.unwrap();
+            assert_eq!(batch.len(), 20);
+        }
+
+        #[tokio::test]
+        async fn test_flush_and_close_persist_buffered_writes() {
+            let q = format!("persist_flush_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            // Neither the periodic flush nor a full batch writes during the test
+            sys_config.default_flush_ms = 60_000;
+            sys_config.writer_batch_size = 10_000;
+
+            let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            for i in 0..10 {
+                manager.push(q.clone(), Bytes::from(format!("flushed-{}", i)), 0).await.unwrap();
+            }
+
+            // flush() commits what the writer buffers, the manager keeps running
+            manager.flush().await.unwrap();
+            let reader = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+            assert_eq!(reader.get_snapshot().await[0].pending, 10);
+            reader.close().await;
+
+            manager.push(q.clone(), Bytes::from("after-flush"), 0).await.unwrap();
+            manager.close().await;
+            assert!(manager.flush().await.is_err(), "Closed writers refuse flushes");
+
+            let manager2 = QueueManager::new(std::sync::Arc::new(sys_config));
+            let batch = manager2.consume_batch(q.clone(), Some(100), Some(0)).await.unwrap();
+            assert_eq!(batch.len(), 11, "close() drains the writer");
+        }
+
+        #[test]
+        fn test_dropped_runtime_flushes_queue_writers() {
+            let q = format!("persist_drop_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            sys_config.default_flush_ms = 60_000;
+            sys_config.writer_batch_size = 10_000;
+
+            // No close(): the writer task is dropped with the runtime, holding the pushes
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+                for i in 0..10 {
+                    manager.push(q.clone(), Bytes::from(format!("dropped-{}", i)), 0).await.unwrap();
+                }
+            });
+            drop(runtime);
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let recovered = runtime.block_on(async {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.consume_batch(q.clone(), Some(100), Some(0)).await.unwrap().len()
+            });
+            assert_eq!(recovered, 10);
+
+            // With no budget, the same drop loses them
+            let q2 = format!("persist_drop_off_{}", Uuid::new_v4());
+            sys_config.drop_flush_timeout_ms = 0;
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q2.clone(), QueueCreateOptions::default()).await.unwrap();
+                manager.push(q2.clone(), Bytes::from("lost"), 0).await.unwrap();
+            });
+            drop(runtime);
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let recovered = runtime.block_on(async {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config));
+                manager.consume_batch(q2.clone(), Some(100), Some(0)).await.map(|b| b.len()).unwrap_or(0)
+            });
+            assert_eq!(recovered, 0);
+        }
+
+        #[tokio::test]
+        async fn test_schema_persistence() {
+            let q = format!("persist_schema_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+
+            {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+                manager.set_schema(&q, serde_json::json!({ "type": "string" })).unwrap();
+                manager.set_schema(&q, serde_json::json!({ "type": "integer" })).unwrap();
+            }
+
+            let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+            assert_eq!(manager.get_schema(&q, None).unwrap().version, 2);
+            assert_eq!(manager.get_schema(&q, Some(1)).unwrap().schema["type"], "string");
+            assert!(manager.push(q.clone(), Bytes::from("\x02\"text\""), 0).await.is_err());
+            manager.push(q.clone(), Bytes::from("\x0242"), 0).await.unwrap();
+
+            // Deleting the queue drops its schemas
+            manager.delete_queue(q.clone()).await.unwrap();
+            manager.create_queue(q.clone(), QueueCreateOptions::default()).await.unwrap();
+            assert!(manager.get_schema(&q, None).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_labels_persistence() {
+            let q = format!("persist_labels_{}", Uuid::new_v4());
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut sys_config = nexo::config::Config::global().queue.clone();
+            sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let labels = |pairs: &[(&str, &str)]| -> Labels { pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect() };
+            let labels_of = |snapshot: Vec<nexo::brokers::queue::snapshot::QueueSnapshot>| {
+                snapshot.into_iter().find(|s| s.name == q).unwrap().config.labels
+            };
+
+            {
+                let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+                let options = QueueCreateOptions { labels: Some(labels(&[("team", "payments")])), ..Default::default() };
+                manager.create_queue(q.clone(), options).await.unwrap();
+                assert_eq!(labels_of(manager.get_snapshot().await), labels(&[("team", "payments")]));
+
+                let invalid = QueueCreateOptions { labels: Some(labels(&[("team name", "x")])), ..Default::default() };
+                let err = manager.create_queue(format!("{}_bad", q), invalid).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::InvalidRequest);
+                assert!(!manager.exists(&format!("{}_bad", q)).await);
+
+                manager.set_labels(&q, labels(&[("team", "payments"), ("env", "prod")])).unwrap();
+                assert!(manager.set_labels(&q, labels(&[("", "x")])).is_err());
+                assert_eq!(manager.set_labels("missing", Labels::new()).unwrap_err().code, ErrorCode::NotFound);
+            }
+
+            let manager = QueueManager::new(std::sync::Arc::new(sys_config.clone()));
+            assert_eq!(labels_of(manager.get_snapshot().await), labels(&[("team", "payments"), ("env", "prod")]));
+        }
+
         #[tokio::test]
         async fn test_inflight_recovery_timeout() {
             let q = format!("persist_inflight_{}", Uuid::new_v4());
@@ -713,6 +2517,125 @@ mod queue_tests {
             bench.stop();
         }
 
+        #[tokio::test]
+        async fn bench_queue_group_commit() {
+            // cargo test --release bench_queue_group_commit -- --test-threads=1 --nocapture
+            const PRODUCERS: usize = 32;
+            const PER_PRODUCER: usize = 100;
+
+            for (mode, window_ms) in [(PersistenceMode::FileAsync, 0), (PersistenceMode::FileSync, 0), (PersistenceMode::FileSync, 2)] {
+                let temp_dir = tempfile::tempdir().unwrap();
+                let mut sys_config = nexo::config::Config::global().queue.clone();
+                sys_config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+                sys_config.group_commit_ms = window_ms;
+                let manager = QueueManager::new(Arc::new(sys_config));
+                let q = "bench_group_commit".to_string();
+                manager.create_queue(q.clone(), QueueCreateOptions { persistence: Some(mode), ..Default::default() }).await.unwrap();
+
+                let mut bench = Benchmark::start(&format!("PUSH - {:?}, {}ms window ({} producers)", mode, window_ms, PRODUCERS), PRODUCERS * PER_PRODUCER);
+                let handles: Vec<_> = (0..PRODUCERS).map(|_| {
+                    let manager = manager.clone();
+                    let q = q.clone();
+                    tokio::spawn(async move {
+                        let mut samples = Vec::with_capacity(PER_PRODUCER);
+                        for _ in 0..PER_PRODUCER {
+                            let start = Instant::now();
+                            manager.push(q.clone(), Bytes::from("data"), 0).await.unwrap();
+                            samples.push(start.elapsed());
+                        }
+                        samples
+                    })
+                }).collect();
+                for handle in handles {
+                    for sample in handle.await.unwrap() {
+                        bench.record(sample);
+                    }
+                }
+                bench.stop();
+            }
+        }
+
+        #[test]
+        fn bench_timer_indexes() {
+            // cargo test --release bench_timer_indexes -- --test-threads=1 --nocapture
+            // Scheduled and in-flight messages in sorted maps (default) vs timing wheels (QUEUE_TIMING_WHEEL)
+            use nexo::brokers::clock::{Clock, MockClock};
+            use nexo::brokers::queue::domain::queue::{Message, MessageState, QueueState};
+
+            const TIMERS: usize = 200_000;
+            const SPAN_MS: u64 = 180_000;
+            const PULSE_MS: u64 = 50;
+
+            for timing_wheel in [false, true] {
+                let clock = MockClock::new();
+                let mut state = QueueState::new(clock.clone());
+                if timing_wheel {
+                    state.enable_timing_wheel(true);
+                }
+                let label = if timing_wheel { "timing wheels" } else { "sorted maps" };
+
+                // Activations spread over the next 3 minutes
+                let now = clock.now_ms();
+                let mut bench = Benchmark::start(&format!("TIMERS - Schedule ({})", label), TIMERS);
+                for i in 0..TIMERS {
+                    let mut msg = Message::new(Bytes::from_static(b"data"), 0);
+                    msg.visible_at = now + 1 + (i as u64 * 7_919) % SPAN_MS;
+                    msg.state = MessageState::Scheduled(msg.visible_at);
+                    let start = Instant::now();
+                    state.push(msg);
+                    bench.record(start.elapsed());
+                }
+                bench.stop();
+
+                // Each pulse: release due messages, dispatch them with varied
+                // visibility timeouts, ack half, expire the rest as they time out
+                let pulses = (SPAN_MS / PULSE_MS) as usize;
+                let mut bench = Benchmark::start(&format!("TIMERS - Pulse: release, dispatch, ack, expire ({})", label), pulses);
+                for pulse in 0..pulses {
+                    clock.advance(Duration::from_millis(PULSE_MS));
+                    let start = Instant::now();
+                    state.promote_due();
+                    let (batch, _) = state.take_batch(1_000, 5_000 + (pulse as u64 % 10) * 1_000, None);
+                    for msg in batch.iter().step_by(2) {
+                        state.ack(msg.id);
+                    }
+                    state.process_expired(3);
+                    bench.record(start.elapsed());
+                }
+                bench.stop();
+                assert_eq!(state.scheduled_count(), 0);
+            }
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn bench_delay_precision() {
+            // cargo test --release bench_delay_precision -- --test-threads=1 --nocapture
+            // Latency = how late after its activation time a delayed message reaches a waiting consumer
+            const DELAYED: usize = 200;
+
+            for precise in [false, true] {
+                let (manager, _tmp) = setup_queue_manager().await;
+                let q = format!("bench_delay_{}", Uuid::new_v4());
+                manager.create_queue(q.clone(), QueueCreateOptions { precise_delays: Some(precise), ..Default::default() }).await.unwrap();
+
+                let name = match precise {
+                    true => "DELAY - Precise (timing wheel + scheduler thread)",
+                    false => "DELAY - Standard (50ms pulse)",
+                };
+                let mut bench = Benchmark::start(name, DELAYED);
+                for i in 0..DELAYED {
+                    let delay = Duration::from_millis(5 + (i % 7) as u64);
+                    let due = Instant::now() + delay;
+                    manager.push_delayed(q.clone(), Bytes::from("data"), 0, delay.as_millis() as u64).await.unwrap();
+                    let batch = manager.consume_batch(q.clone(), Some(1), Some(1_000)).await.unwrap();
+                    assert_eq!(batch.len(), 1);
+                    bench.record(Instant::now().saturating_duration_since(due));
+                    manager.ack(&q, batch[0].id).await;
+                }
+                bench.stop();
+            }
+        }
+
     }
 
 }
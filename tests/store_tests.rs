@@ -1,8 +1,11 @@
 mod helpers;
-use helpers::{setup_store_manager, Benchmark};
+use helpers::{setup_store_manager, setup_store_manager_with_clock, Benchmark};
 use bytes::Bytes;
 use std::time::Duration;
 use uuid::Uuid;
+use nexo::brokers::clock::MockClock;
+use nexo::brokers::store::domain::eviction::{EvictionPolicy, ENTRY_OVERHEAD};
+use nexo::brokers::store::StoreManager;
 
 
 #[cfg(test)]
@@ -23,7 +26,7 @@ mod store_tests {
             let val = Bytes::from("value");
 
             // PUT
-            manager.map.set(key.clone(), val.clone(), None);
+            manager.map.set(key.clone(), val.clone(), None).unwrap();
 
             // GET
             let retrieved = manager.map.get(&key).expect("Key should exist");
@@ -43,8 +46,8 @@ mod store_tests {
             let (manager, _tmp) = setup_store_manager().await;
             let key = format!("key_ovr_{}", Uuid::new_v4());
 
-            manager.map.set(key.clone(), Bytes::from("v1"), None);
-            manager.map.set(key.clone(), Bytes::from("v2"), None);
+            manager.map.set(key.clone(), Bytes::from("v1"), None).unwrap();
+            manager.map.set(key.clone(), Bytes::from("v2"), None).unwrap();
 
             let val = manager.map.get(&key).unwrap();
             assert_eq!(val, Bytes::from("v2"));
@@ -52,24 +55,352 @@ mod store_tests {
 
         #[tokio::test]
         async fn test_ttl_expiration() {
-            let (manager, _tmp) = setup_store_manager().await;
+            let (manager, clock) = setup_store_manager_with_clock().await;
             let key = format!("key_ttl_{}", Uuid::new_v4());
 
             let ttl_sec = 1;
-            manager.map.set(key.clone(), Bytes::from("temp"), Some(ttl_sec));
+            manager.map.set(key.clone(), Bytes::from("temp"), Some(ttl_sec)).unwrap();
 
             let retrieved = manager.map.get(&key);
             assert!(retrieved.is_some());
 
-            // Wait > TTL
-            tokio::time::sleep(Duration::from_millis((ttl_sec * 1000) + 100)).await;
+            // Move past the TTL
+            clock.advance(Duration::from_millis((ttl_sec * 1000) + 100));
 
             let after_ttl = manager.map.get(&key);
             // Should expire (either by lazy check or background, new implementation has lazy check!)
             assert!(after_ttl.is_none(), "Key should have expired");
         }
+
+        #[tokio::test]
+        async fn test_ttl_inspection_and_changes() {
+            let (manager, clock) = setup_store_manager_with_clock().await;
+            let key = format!("key_expire_{}", Uuid::new_v4());
+
+            assert!(manager.map.ttl(&key).is_none());
+            assert!(!manager.map.expire(&key, Duration::from_secs(5)));
+            assert!(!manager.map.persist(&key));
+
+            manager.map.set(key.clone(), Bytes::from("v"), Some(10)).unwrap();
+            assert_eq!(manager.map.ttl(&key), Some(Some(Duration::from_secs(10))));
+
+            // Shorten it: the value stays, the key goes at the new deadline
+            assert!(manager.map.expire(&key, Duration::from_secs(2)));
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(manager.map.ttl(&key), Some(Some(Duration::from_secs(1))));
+            assert_eq!(manager.map.get(&key), Some(Bytes::from("v")));
+
+            // Persisted: outlives any TTL
+            assert!(manager.map.persist(&key));
+            assert_eq!(manager.map.ttl(&key), Some(None));
+            clock.advance(Duration::from_secs(3600 * 24));
+            assert_eq!(manager.map.get(&key), Some(Bytes::from("v")));
+
+            // Expired at its deadline exactly, for every check alike; an
+            // expired key can't be brought back
+            assert!(manager.map.expire(&key, Duration::from_secs(1)));
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(manager.map.get(&key), None);
+            assert_eq!(manager.map.get_many(std::slice::from_ref(&key)).unwrap(), vec![None]);
+            assert!(!manager.map.persist(&key));
+            assert!(manager.map.ttl(&key).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_keys_kept_forever() {
+            let (manager, clock) = setup_store_manager_with_clock().await;
+            let key = format!("key_forever_{}", Uuid::new_v4());
+            manager.map.set_persistent(key.clone(), Bytes::from("v")).unwrap();
+            assert_eq!(manager.map.ttl(&key), Some(None));
+
+            // A default TTL of 0: keys set without a TTL never expire
+            let mut config = nexo::config::Config::global().store.clone();
+            config.default_ttl_secs = 0;
+            let clock2 = nexo::brokers::clock::MockClock::new();
+            let no_default = nexo::brokers::store::StoreManager::with_clock(std::sync::Arc::new(config), clock2.clone());
+            no_default.map.set(key.clone(), Bytes::from("v"), None).unwrap();
+            no_default.map.set(format!("{}_ttl", key), Bytes::from("v"), Some(1)).unwrap();
+            assert_eq!(no_default.map.ttl(&key), Some(None));
+
+            for clock in [&clock, &clock2] {
+                clock.advance(Duration::from_secs(3600 * 24 * 365));
+            }
+            assert_eq!(manager.map.get(&key), Some(Bytes::from("v")));
+            assert_eq!(no_default.map.get(&key), Some(Bytes::from("v")));
+            assert!(no_default.map.get(&format!("{}_ttl", key)).is_none(), "An explicit TTL still applies");
+        }
+
+        /// Room for five 100-byte values under two-byte keys
+        fn capped(policy: EvictionPolicy) -> (StoreManager, std::sync::Arc<MockClock>) {
+            let mut config = nexo::config::Config::global().store.clone();
+            config.max_memory_bytes = 5 * (ENTRY_OVERHEAD + 2 + 100);
+            config.eviction_policy = policy;
+            let clock = MockClock::new();
+            (StoreManager::with_clock(std::sync::Arc::new(config), clock.clone()), clock)
+        }
+
+        fn fill(manager: &StoreManager, clock: &MockClock, keys: std::ops::Range<usize>, ttls: &[Option<u64>]) {
+            for i in keys {
+                manager.map.set(format!("k{}", i), Bytes::from(vec![0u8; 100]), ttls.get(i).copied().flatten()).unwrap();
+                clock.advance(Duration::from_millis(1));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_memory_cap_without_eviction() {
+            let (manager, clock) = capped(EvictionPolicy::NoEviction);
+            fill(&manager, &clock, 0..5, &[]);
+            assert_eq!(manager.map.memory().used_bytes, 5 * (ENTRY_OVERHEAD + 102));
+
+            let refused = manager.map.set("k5".to_string(), Bytes::from(vec![0u8; 100]), None).unwrap_err();
+            assert_eq!(refused.code, nexo::transport::tcp::protocol::ErrorCode::Busy);
+            // Overwriting in place needs no extra room
+            manager.map.set("k0".to_string(), Bytes::from(vec![1u8; 100]), None).unwrap();
+
+            manager.map.del("k1");
+            manager.map.set("k5".to_string(), Bytes::from(vec![0u8; 100]), None).unwrap();
+            let memory = manager.map.memory();
+            assert_eq!((memory.evicted_keys, memory.rejected_writes), (0, 1));
+        }
+
+        #[tokio::test]
+        async fn test_lru_and_lfu_eviction() {
+            let (manager, clock) = capped(EvictionPolicy::AllKeysLru);
+            fill(&manager, &clock, 0..5, &[]);
+            manager.map.get("k0");
+            // Down to 95% of the cap: the two least recently used go
+            fill(&manager, &clock, 5..6, &[]);
+            let present: Vec<bool> = (0..6).map(|i| manager.map.get(&format!("k{}", i)).is_some()).collect();
+            assert_eq!(present, vec![true, false, false, true, true, true]);
+            assert_eq!(manager.map.memory().evicted_keys, 2);
+
+            // Past one sample: keys go one per sampled group, and the cap holds
+            let mut config = nexo::config::Config::global().store.clone();
+            config.max_memory_bytes = 64 * (ENTRY_OVERHEAD + 3 + 100);
+            config.eviction_policy = EvictionPolicy::AllKeysLru;
+            let clock = MockClock::new();
+            let manager = StoreManager::with_clock(std::sync::Arc::new(config), clock.clone());
+            fill(&manager, &clock, 10..74, &[]);
+            fill(&manager, &clock, 74..90, &[]);
+            assert!(manager.map.memory().evicted_keys >= 16);
+            assert_eq!(manager.map.memory().rejected_writes, 0);
+            assert!(manager.map.memory().used_bytes <= 64 * (ENTRY_OVERHEAD + 103));
+
+            let (manager, clock) = capped(EvictionPolicy::AllKeysLfu);
+            fill(&manager, &clock, 0..5, &[]);
+            for i in [3, 3, 0, 0, 1, 1, 2, 4, 4] {
+                manager.map.get(&format!("k{}", i));
+                clock.advance(Duration::from_millis(1));
+            }
+            // k2 is the least used, then k3 the least recent of the rest
+            fill(&manager, &clock, 5..6, &[]);
+            let present: Vec<bool> = (0..5).map(|i| manager.map.get(&format!("k{}", i)).is_some()).collect();
+            assert_eq!(present, vec![true, true, false, false, true]);
+        }
+
+        #[tokio::test]
+        async fn test_volatile_ttl_eviction() {
+            let (manager, clock) = capped(EvictionPolicy::VolatileTtl);
+            fill(&manager, &clock, 0..5, &[None, None, Some(100), Some(10), Some(50)]);
+            // Soonest to expire first; keys without a TTL stay
+            fill(&manager, &clock, 5..6, &[]);
+            let present: Vec<bool> = (0..5).map(|i| manager.map.get(&format!("k{}", i)).is_some()).collect();
+            assert_eq!(present, vec![true, true, true, false, false]);
+
+            // Nothing left that may go: refused
+            let mut config = nexo::config::Config::global().store.clone();
+            config.max_memory_bytes = 2 * (ENTRY_OVERHEAD + 102);
+            config.eviction_policy = EvictionPolicy::VolatileTtl;
+            let manager = StoreManager::new(std::sync::Arc::new(config));
+            manager.map.set_persistent("p0".to_string(), Bytes::from(vec![0u8; 100])).unwrap();
+            manager.map.set_persistent("p1".to_string(), Bytes::from(vec![0u8; 100])).unwrap();
+            assert!(manager.map.set("p2".to_string(), Bytes::from(vec![0u8; 100]), Some(10)).is_err());
+            assert!("allkeys-lru".parse::<EvictionPolicy>().is_ok() && "lru".parse::<EvictionPolicy>().is_err());
+        }
+
+        #[tokio::test]
+        async fn test_versioned_keys() {
+            let mut config = nexo::config::Config::global().store.clone();
+            config.versioned_prefixes = vec!["config:".to_string()];
+            config.max_versions = 2;
+            let manager = nexo::brokers::store::StoreManager::new(std::sync::Arc::new(config));
+            let key = format!("config:{}", Uuid::new_v4());
+
+            let mut times = Vec::new();
+            for v in ["v1", "v2", "v3", "v4"] {
+                manager.map.set(key.clone(), Bytes::from(v), None).unwrap();
+                times.push(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+
+            assert_eq!(manager.map.get(&key).unwrap(), Bytes::from("v4"));
+            assert_eq!(manager.map.get_at(&key, times[2]).unwrap(), Bytes::from("v3"));
+            assert_eq!(manager.map.get_at(&key, times[1]).unwrap(), Bytes::from("v2"));
+            // Current + 2 previous versions are kept: v1 was pruned
+            assert!(manager.map.get_at(&key, times[0]).is_none());
+
+            let history = manager.map.history(&key, 10);
+            let values: Vec<Bytes> = history.iter().map(|v| v.value.clone()).collect();
+            assert_eq!(values, vec![Bytes::from("v4"), Bytes::from("v3"), Bytes::from("v2")]);
+            assert!(history[0].set_at >= history[1].set_at);
+
+            // Keys outside the prefix keep no history
+            let plain = format!("plain:{}", Uuid::new_v4());
+            manager.map.set(plain.clone(), Bytes::from("x"), None).unwrap();
+            assert!(!manager.map.is_versioned(&plain));
+            assert!(manager.map.history(&plain, 10).is_empty());
+
+            // History goes away with the key
+            manager.map.del(&key);
+            assert!(manager.map.history(&key, 10).is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_bulk_operations() {
+            let mut config = nexo::config::Config::global().store.clone();
+            config.max_batch_keys = 3;
+            let manager = nexo::brokers::store::StoreManager::new(std::sync::Arc::new(config));
+            let keys: Vec<String> = (0..3).map(|i| format!("bulk_{}_{}", i, Uuid::new_v4())).collect();
+
+            let entries = vec![(keys[0].clone(), Bytes::from("a")), (keys[2].clone(), Bytes::from("c"))];
+            manager.map.set_many(entries, None, false).unwrap();
+
+            // Per-key results, in request order
+            let values = manager.map.get_many(&keys).unwrap();
+            assert_eq!(values, vec![Some(Bytes::from("a")), None, Some(Bytes::from("c"))]);
+            assert_eq!(manager.map.del_many(&keys).unwrap(), vec![true, false, true]);
+            assert_eq!(manager.map.get_many(&keys).unwrap(), vec![None, None, None]);
+
+            // Over the key limit: refused as a whole
+            let too_many: Vec<String> = (0..4).map(|i| format!("bulk_{}", i)).collect();
+            assert!(manager.map.get_many(&too_many).is_err());
+            assert!(manager.map.del_many(&too_many).is_err());
+            let entries = too_many.iter().map(|k| (k.clone(), Bytes::from("x"))).collect();
+            assert!(manager.map.set_many(entries, None, false).is_err());
+            assert!(manager.map.get(&too_many[0]).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_locks_with_fencing_tokens() {
+            let (manager, clock) = setup_store_manager_with_clock().await;
+            let name = format!("lock_{}", Uuid::new_v4());
+
+            let first = manager.locks.acquire(&name, "worker-a", 60_000).unwrap().expect("Lock should be free");
+            assert!(manager.locks.acquire(&name, "worker-b", 60_000).unwrap().is_none(), "Lock is held");
+            assert!(manager.locks.held().iter().any(|l| l.name == name && l.owner == "worker-a"));
+
+            // Only the holder's token releases
+            assert!(manager.locks.release(&name, first.token + 1).is_err());
+            manager.locks.release(&name, first.token).unwrap();
+            assert!(manager.locks.release(&name, first.token).is_err(), "Already released");
+
+            // Tokens keep growing across acquisitions
+            let second = manager.locks.acquire(&name, "worker-b", 50).unwrap().unwrap();
+            assert!(second.token > first.token);
+            let extended = manager.locks.extend(&name, second.token, 50).unwrap();
+            assert!(extended >= second.expires_at);
+
+            // An expired lease can neither be extended nor released, and the lock is free again
+            clock.advance(Duration::from_millis(120));
+            assert!(manager.locks.extend(&name, second.token, 1000).is_err());
+            assert!(manager.locks.release(&name, second.token).is_err());
+            assert!(!manager.locks.held().iter().any(|l| l.name == name));
+            let third = manager.locks.acquire(&name, "worker-c", 1000).unwrap().unwrap();
+            assert!(third.token > second.token);
+
+            assert!(manager.locks.acquire("zero_ttl", "worker-a", 0).is_err());
+            assert!(manager.locks.acquire("huge_ttl", "worker-a", u64::MAX).is_err());
+            assert!(manager.locks.extend(&name, third.token, u64::MAX).is_err());
+        }
+
+        #[tokio::test]
+        async fn test_semaphore_leases() {
+            let (manager, clock) = setup_store_manager_with_clock().await;
+            let sems = &manager.semaphores;
+            let name = format!("sem_{}", Uuid::new_v4());
+
+            let a = sems.acquire(&name, "conn-a", 2, 60_000).unwrap().expect("Slot free");
+            let b = sems.acquire(&name, "conn-b", 2, 50).unwrap().expect("Slot free");
+            assert!(sems.acquire(&name, "conn-c", 2, 60_000).unwrap().is_none(), "All slots leased");
+            assert!(sems.acquire(&name, "conn-c", 3, 60_000).is_err(), "Max is fixed while held");
+
+            // Leases are released by their holder only
+            assert!(sems.release(&name, "conn-b", a.lease).is_err());
+            assert!(sems.holds(&name, "conn-b"));
+
+            // An expired lease frees its slot
+            clock.advance(Duration::from_millis(120));
+            assert!(sems.release(&name, "conn-b", b.lease).is_err());
+            let c = sems.acquire(&name, "conn-c", 2, 60_000).unwrap().expect("Expired slot reused");
+            assert!(c.lease > b.lease);
+            let listed = sems.list().into_iter().find(|s| s.name == name).unwrap();
+            assert_eq!(listed.max, 2);
+            assert_eq!(listed.holders.len(), 2);
+
+            // Closing a connection drops its leases
+            sems.release_holder("conn-c", std::slice::from_ref(&name));
+            assert!(!sems.holds(&name, "conn-c"));
+            sems.release(&name, "conn-a", a.lease).unwrap();
+            assert!(sems.list().iter().all(|s| s.name != name), "Empty semaphore is removed");
+
+            // Once empty, the next acquirer sets a new max
+            let d = sems.acquire(&name, "conn-d", 5, 1000).unwrap().unwrap();
+
+            assert!(sems.acquire(&name, "conn-e", 5, u64::MAX).is_err());
+            assert!(sems.extend(&name, "conn-d", d.lease, u64::MAX).is_err());
+        }
+
+        #[tokio::test]
+        async fn test_store_and_publish_recovers_pending_intents() {
+            use nexo::brokers::intent::{Intent, PublishTarget};
+            use nexo::brokers::stream::options::StreamCreateOptions;
+            use nexo::transport::tcp::protocol::ErrorCode;
+
+            let tmp = tempfile::tempdir().unwrap();
+            let mut config = nexo::config::Config::global().clone();
+            config.store.persistence_path = tmp.path().join("store").to_str().unwrap().to_string();
+            config.queue.persistence_path = tmp.path().join("queue").to_str().unwrap().to_string();
+            config.pubsub.persistence_path = tmp.path().join("pubsub").to_str().unwrap().to_string();
+            config.stream.persistence_path = tmp.path().join("stream").to_str().unwrap().to_string();
+            let topic = "order-events";
+            let target = PublishTarget::Stream { topic: topic.to_string() };
+
+            let engine = nexo::NexoEngine::new(&config).await;
+            engine.stream.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+
+            engine.store_and_publish("order:1".to_string(), Bytes::from("paid"), None, target.clone(), Bytes::from("order:1 paid")).await.unwrap();
+            assert_eq!(engine.store.map.get("order:1").unwrap(), Bytes::from("paid"));
+            assert_eq!(engine.stream.read(topic, 1, 10).await.len(), 1);
+            assert!(engine.intents.pending().await.unwrap().is_empty());
+
+            // A rejected publish applies neither side
+            let missing = PublishTarget::Stream { topic: "missing".to_string() };
+            let err = engine.store_and_publish("order:2".to_string(), Bytes::from("paid"), None, missing, Bytes::from("x")).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotFound);
+            assert!(engine.store.map.get("order:2").is_none());
+
+            // Crash after the intent was logged, before it was applied
+            let intent = Intent {
+                key: "order:3".to_string(),
+                value: Bytes::from("shipped"),
+                ttl: None,
+                target,
+                payload: Bytes::from("order:3 shipped"),
+                created_at: engine.store.clock().now_ms(),
+            };
+            engine.intents.record(&intent).await.unwrap();
+            drop(engine);
+
+            let engine = nexo::NexoEngine::new(&config).await;
+            assert_eq!(engine.store.map.get("order:3").unwrap(), Bytes::from("shipped"));
+            let events = engine.stream.read(topic, 1, 10).await;
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[1].payload, Bytes::from("order:3 shipped"));
+            assert!(engine.intents.pending().await.unwrap().is_empty());
+        }
     }
 
+
     // =========================================================================================
     // 2. PERFORMANCE BENCHMARKS
     // =========================================================================================
@@ -88,7 +419,7 @@ mod store_tests {
             for i in 0..COUNT {
                 let start = Instant::now();
                 let key = i.to_string();
-                manager.map.set(key, Bytes::from("data"), None);
+                manager.map.set(key, Bytes::from("data"), None).unwrap();
                 bench.record(start.elapsed());
             }
             bench.stop();
@@ -101,7 +432,7 @@ mod store_tests {
             // Pre-fill
             for i in 0..COUNT {
                 let key = i.to_string();
-                manager.map.set(key, Bytes::from("data"), None);
+                manager.map.set(key, Bytes::from("data"), None).unwrap();
             }
 
             let mut bench = Benchmark::start("STORE - Read (GET)", COUNT);
@@ -1,4 +1,4 @@
-use nexo::brokers::stream::options::StreamCreateOptions;
+use nexo::brokers::stream::options::{AlterOptions, StreamCreateOptions};
 use nexo::config::Config;
 use bytes::Bytes;
 use std::time::{Duration, Instant};
@@ -8,9 +8,16 @@ use helpers::Benchmark;
 #[cfg(test)]
 mod stream_tests {
     use super::*;
-    use nexo::brokers::stream::domain::message::Message;
-    use nexo::brokers::stream::manager::JoinGroupResult;
+    use nexo::brokers::stream::domain::message::{Message, LANE_HIGH, LANE_NORMAL};
+    use nexo::brokers::stream::manager::{JoinGroupResult, TopicMember};
+    use nexo::brokers::stream::backfill::BackfillSpec;
+    use nexo::brokers::stream::export::DumpFormat;
+    use nexo::brokers::stream::config::{MirrorSpec, WriteBackend};
+    use nexo::brokers::durability::PersistenceMode;
     use nexo::brokers::stream::StreamManager;
+    use nexo::transport::tcp::protocol::ErrorCode;
+    use nexo::brokers::message_trace::TraceEventKind;
+    use nexo::brokers::dead_letter::DeadLetterEnvelope;
     use std::sync::Arc;
 
     fn get_test_config(path: Option<&str>) -> nexo::brokers::stream::config::SystemStreamConfig {
@@ -25,8 +32,40 @@ mod stream_tests {
         StreamManager::new(Arc::new(config)).await
     }
 
+    /// Serve a full Nexo engine over TCP, as a mirror source.
+    async fn start_remote(path: &std::path::Path) -> (nexo::NexoEngine, String) {
+        let mut config = Config::global().clone();
+        config.store.persistence_path = path.join("store").to_str().unwrap().to_string();
+        config.stream.persistence_path = path.join("streams").to_str().unwrap().to_string();
+        config.queue.persistence_path = path.join("queues").to_str().unwrap().to_string();
+        config.pubsub.persistence_path = path.join("pubsub").to_str().unwrap().to_string();
+        let engine = nexo::NexoEngine::new(&config).await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server_engine = engine.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let engine = server_engine.clone();
+                tokio::spawn(nexo::transport::tcp::connection::handle_connection(socket, engine));
+            }
+        });
+        (engine, addr)
+    }
+
+    async fn wait_for_messages(manager: &StreamManager, topic: &str, count: usize) -> Vec<Message> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let msgs = manager.read(topic, 1, 1000).await;
+            if msgs.len() >= count || Instant::now() >= deadline {
+                return msgs;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
     async fn join_session(manager: &StreamManager, group: &str, topic: &str, client: &str) -> JoinGroupResult {
-        manager.join_group(group, topic, client).await.unwrap()
+        manager.join_group(group, topic, client, None).await.unwrap()
     }
 
     async fn fetch_messages(manager: &StreamManager, group: &str, topic: &str, consumer: &JoinGroupResult, limit: usize, wait_ms: u64) -> Vec<Message> {
@@ -61,6 +100,60 @@ mod stream_tests {
             assert_eq!(msgs[0].seq, 1);
         }
 
+        #[tokio::test]
+        async fn test_stream_message_trace() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let manager = build_manager(get_test_config(Some(temp_dir.path().to_str().unwrap()))).await;
+            let topic = "trace-topic";
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            assert!(manager.set_tracing("missing-topic", true).is_err());
+            manager.set_tracing(topic, true).unwrap();
+
+            let seq = manager.publish(topic, Bytes::from("traced")).await.unwrap();
+            let join = manager.join_group("billing", topic, "client-1", None).await.unwrap();
+            let msgs = manager.fetch("billing", &join.consumer_id, join.generation, 10, topic, 0).await.unwrap();
+            assert_eq!(msgs.len(), 1);
+            manager.ack("billing", topic, &join.consumer_id, join.generation, seq).await.unwrap();
+
+            let trace = manager.trace(&format!("{}:{}", topic, seq)).expect("Traced message should be recorded");
+            let kinds: Vec<TraceEventKind> = trace.events.iter().map(|e| e.kind).collect();
+            assert_eq!(kinds, vec![TraceEventKind::Published, TraceEventKind::Dispatched, TraceEventKind::Acked]);
+            assert_eq!(trace.events[2].detail.as_deref(), Some("billing"));
+
+            manager.set_tracing(topic, false).unwrap();
+            let seq = manager.publish(topic, Bytes::from("quiet")).await.unwrap();
+            assert!(manager.trace(&format!("{}:{}", topic, seq)).is_none());
+        }
+
+        #[tokio::test]
+        async fn test_stream_schema_validation() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let path = temp_dir.path().to_str().unwrap().to_string();
+            let topic = "schema-topic";
+            let json = |s: &str| Bytes::from([&[0x02u8][..], s.as_bytes()].concat());
+
+            {
+                let manager = build_manager(get_test_config(Some(&path))).await;
+                assert_eq!(manager.set_schema(topic, serde_json::json!({})).unwrap_err().code, ErrorCode::NotFound);
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+
+                let schema = serde_json::json!({ "type": "array", "items": { "type": "number" } });
+                assert_eq!(manager.set_schema(topic, schema).unwrap(), 1);
+
+                assert_eq!(manager.publish(topic, json("[1, 2.5]")).await.unwrap(), 1);
+                let err = manager.publish(topic, json(r#"[1, "two"]"#)).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::SchemaViolation);
+                assert!(err.message.contains("/1"), "Error should name the failing path: {}", err.message);
+                assert_eq!(manager.read(topic, 1, 10).await.len(), 1, "Rejected payload must not be appended");
+                manager.shutdown();
+            }
+
+            // Schemas survive a restart
+            let manager = build_manager(get_test_config(Some(&path))).await;
+            assert_eq!(manager.get_schema(topic, None).unwrap().version, 1);
+            assert!(manager.publish(topic, json(r#"{"not":"an array"}"#)).await.is_err());
+        }
+
         #[tokio::test]
         async fn test_stream_ordering() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -169,6 +262,53 @@ mod stream_tests {
             assert_eq!(probe.ack_floor, 2);
         }
 
+        #[tokio::test]
+        async fn test_nack_dead_letters_poison_record() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.max_deliveries = 2;
+            let manager = build_manager(config).await;
+            let topic = "nack-dlq-flow";
+            let group = "g-nack";
+
+            let options = StreamCreateOptions { dead_letter: Some(true), ..Default::default() };
+            manager.create_topic(topic.to_string(), options).await.unwrap();
+            manager.publish(topic, Bytes::from("\x02{\"poison\":true}")).await.unwrap();
+            manager.publish(topic, Bytes::from("msg-2")).await.unwrap();
+
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let first = fetch_messages(&manager, group, topic, &consumer, 1, 0).await;
+            assert_eq!(first[0].seq, 1);
+
+            // A stale generation is fenced
+            let err = manager.nack(group, topic, &consumer.consumer_id, consumer.generation + 1, 1, "boom").await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::Fenced);
+
+            // First nack redelivers right away, the second exhausts max_deliveries
+            manager.nack(group, topic, &consumer.consumer_id, consumer.generation, 1, "boom 1").await.unwrap();
+            let second = fetch_messages(&manager, group, topic, &consumer, 1, 0).await;
+            assert_eq!(second[0].seq, 1);
+            manager.nack(group, topic, &consumer.consumer_id, consumer.generation, 1, "boom 2").await.unwrap();
+
+            // The offset moved past the poison record
+            let next = fetch_messages(&manager, group, topic, &consumer, 10, 0).await;
+            assert_eq!(next.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![2]);
+            ack_message(&manager, group, topic, &consumer, 2).await;
+            assert_eq!(join_session(&manager, group, topic, "client-B").await.ack_floor, 2);
+
+            let dlq = StreamManager::dlq_topic(topic, group);
+            let letters = wait_for_messages(&manager, &dlq, 1).await;
+            assert_eq!(letters.len(), 1);
+            let envelope = DeadLetterEnvelope::decode(&letters[0].payload).unwrap();
+            assert_eq!((envelope.source.as_str(), envelope.group.as_deref(), envelope.id.as_str()), (topic, Some(group), "1"));
+            assert_eq!((envelope.attempts, envelope.reason.as_str()), (2, "boom 2"));
+            assert_eq!(envelope.payload, Bytes::from("\x02{\"poison\":true}"));
+
+            // A group whose DLQ topic would be an invalid name is refused
+            let err = manager.join_group("g nack", topic, "client-C", None).await.err().unwrap();
+            assert_eq!(err.code, ErrorCode::InvalidName);
+        }
+
         #[tokio::test]
         async fn test_ack_floor_advancement() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -228,7 +368,7 @@ mod stream_tests {
             manager.seek(group, topic, SeekTarget::Beginning).await.unwrap();
 
             let fenced = manager.fetch(group, &consumer.consumer_id, consumer.generation, 10, topic, 0).await;
-            assert!(matches!(fenced, Err(ref e) if e == "FENCED"));
+            assert!(matches!(fenced, Err(ref e) if e.code == ErrorCode::Fenced));
 
             let replay = join_session(&manager, group, topic, "client-A").await;
             let from_start = fetch_messages(&manager, group, topic, &replay, 10, 0).await;
@@ -290,6 +430,50 @@ mod stream_tests {
             assert_eq!(from_start[0].seq, 1);
         }
 
+        #[tokio::test]
+        async fn test_group_checkpoint_export_import() {
+            use nexo::brokers::stream::checkpoint::CheckpointMatch;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let (source, mirror, short) = ("ckpt-src", "ckpt-mirror", "ckpt-short");
+            for topic in [source, mirror, short] {
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            }
+            // The mirror holds older records first: same timestamps, other sequences
+            for i in 1..=3 {
+                manager.publish_with_timestamp(mirror, Bytes::from("older"), 500 + i).await.unwrap();
+            }
+            for i in 1..=10 {
+                manager.publish_with_timestamp(source, Bytes::from(format!("m{}", i)), 1_000 + i).await.unwrap();
+                manager.publish_with_timestamp(mirror, Bytes::from(format!("m{}", i)), 1_000 + i).await.unwrap();
+            }
+            manager.publish(short, Bytes::from("x")).await.unwrap();
+
+            let consumer = join_session(&manager, "fleet", source, "client-A").await;
+            for msg in fetch_messages(&manager, "fleet", source, &consumer, 4, 0).await {
+                ack_message(&manager, "fleet", source, &consumer, msg.seq).await;
+            }
+            let checkpoint = manager.export_checkpoint(source, "fleet").await.unwrap();
+            assert_eq!((checkpoint.ack_floor, checkpoint.ack_floor_timestamp, checkpoint.last_seq), (4, Some(1_004), 10));
+            let missing = manager.export_checkpoint(source, "nobody").await.err().unwrap();
+            assert_eq!(missing.code, ErrorCode::NotFound);
+
+            // Matched by publish time, the fleet resumes at "m5" on the mirror
+            assert_eq!(manager.import_checkpoint(mirror, "fleet", &checkpoint, CheckpointMatch::ByTimestamp).await.unwrap(), 7);
+            let resumed = join_session(&manager, "fleet", mirror, "client-B").await;
+            let next = fetch_messages(&manager, "fleet", mirror, &resumed, 1, 0).await;
+            assert_eq!((next[0].seq, &next[0].payload[..]), (8, &b"m5"[..]));
+
+            // By sequence, the floor must exist on the target
+            assert_eq!(manager.import_checkpoint(source, "copy", &checkpoint, CheckpointMatch::BySeq).await.unwrap(), 4);
+            assert!(manager.import_checkpoint(short, "fleet", &checkpoint, CheckpointMatch::BySeq).await.is_err());
+            let mut newer = checkpoint.clone();
+            newer.version += 1;
+            assert!(manager.import_checkpoint(source, "fleet", &newer, CheckpointMatch::BySeq).await.is_err());
+        }
+
         #[tokio::test]
         async fn test_leave_cancels_inflight_fetch() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -320,6 +504,177 @@ mod stream_tests {
             assert!(result.unwrap().is_empty(), "Cancelled fetch should return empty");
         }
 
+        #[tokio::test]
+        async fn test_pause_resume_consumer() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let topic = "pause-resume";
+            let group = "g-pause";
+
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            let consumer_a = join_session(&manager, group, topic, "client-A").await;
+            let consumer_b = join_session(&manager, group, topic, "client-B").await;
+            for i in 1..=3 {
+                manager.publish(topic, Bytes::from(format!("m{}", i))).await.unwrap();
+            }
+
+            // A owns m1, then pauses: its pending message stays ackable
+            let msgs = fetch_messages(&manager, group, topic, &consumer_a, 1, 0).await;
+            assert_eq!(msgs[0].seq, 1);
+            manager.pause(group, topic, &consumer_a.consumer_id, consumer_a.generation).await.unwrap();
+            assert!(fetch_messages(&manager, group, topic, &consumer_a, 10, 0).await.is_empty());
+            ack_message(&manager, group, topic, &consumer_a, 1).await;
+
+            // The paused fetch advanced nothing: B continues at m2
+            let msgs = fetch_messages(&manager, group, topic, &consumer_b, 1, 0).await;
+            assert_eq!(msgs[0].seq, 2);
+
+            let err = manager.pause(group, topic, &consumer_a.consumer_id, consumer_a.generation + 1).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::Fenced);
+            let err = manager.pause(group, topic, "ghost", consumer_a.generation).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotMember);
+
+            // Resume wakes a long-poll that started while paused
+            let fetch_manager = manager.clone();
+            let consumer_id = consumer_a.consumer_id.clone();
+            let generation = consumer_a.generation;
+            let fetch_handle = tokio::spawn(async move {
+                let start = Instant::now();
+                let result = fetch_manager.fetch(group, &consumer_id, generation, 10, topic, 5_000).await;
+                (start.elapsed(), result)
+            });
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.resume(group, topic, &consumer_a.consumer_id, consumer_a.generation).await.unwrap();
+
+            let (elapsed, result) = fetch_handle.await.unwrap();
+            assert!(elapsed < Duration::from_millis(1_000), "Resume should wake the fetch, took {:?}", elapsed);
+            let seqs: Vec<u64> = result.unwrap().iter().map(|m| m.seq).collect();
+            assert_eq!(seqs, vec![3]);
+        }
+
+        #[tokio::test]
+        async fn test_session_resume_keeps_pending_within_grace() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.session_grace_ms = 10_000;
+            let manager = build_manager(config).await;
+            let topic = "session-resume";
+            let group = "g-resume";
+
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            manager.publish(topic, Bytes::from("m1")).await.unwrap();
+            manager.publish(topic, Bytes::from("m2")).await.unwrap();
+
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let msgs = fetch_messages(&manager, group, topic, &consumer, 1, 0).await;
+            assert_eq!(msgs[0].seq, 1);
+
+            manager.disconnect("client-A".to_string()).await;
+
+            let resumed = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.unwrap();
+            assert_eq!(resumed.consumer_id, consumer.consumer_id);
+            assert_eq!(resumed.generation, consumer.generation);
+            assert_eq!(resumed.session_token, consumer.session_token);
+
+            // Pending seq 1 is still owned by the resumed consumer, so it can be acked
+            ack_message(&manager, group, topic, &resumed, 1).await;
+            let next = fetch_messages(&manager, group, topic, &resumed, 10, 0).await;
+            assert_eq!(next.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![2]);
+
+            // The old connection going away again must not detach the resumed member
+            manager.disconnect("client-A".to_string()).await;
+            ack_message(&manager, group, topic, &resumed, 2).await;
+        }
+
+        #[tokio::test]
+        async fn test_session_grace_expiry_releases_pending() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.session_grace_ms = 200;
+            let manager = build_manager(config).await;
+            let topic = "session-expiry";
+            let group = "g-expiry";
+
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            manager.publish(topic, Bytes::from("m1")).await.unwrap();
+
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            assert_eq!(fetch_messages(&manager, group, topic, &consumer, 1, 0).await.len(), 1);
+
+            manager.disconnect("client-A".to_string()).await;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+
+            let rejoined = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.unwrap();
+            assert_ne!(rejoined.consumer_id, consumer.consumer_id, "Expired session must not be resumed");
+
+            let msgs = fetch_messages(&manager, group, topic, &rejoined, 10, 0).await;
+            assert_eq!(msgs.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![1], "Expired member's pending should be redelivered");
+        }
+
+        #[tokio::test]
+        async fn test_disconnect_leaves_group_without_grace() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            assert_eq!(config.session_grace_ms, 0, "Grace period is opt-in");
+            let manager = build_manager(config).await;
+            let topic = "session-no-grace";
+            let group = "g-no-grace";
+
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            manager.publish(topic, Bytes::from("m1")).await.unwrap();
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let other = join_session(&manager, group, topic, "client-B").await;
+            assert_eq!(fetch_messages(&manager, group, topic, &consumer, 1, 0).await.len(), 1);
+
+            // The member is gone at once and its pending message goes to the rest of the group
+            manager.disconnect("client-A".to_string()).await;
+            let other = manager.join_group(group, topic, "client-B", Some(&other.session_token)).await.unwrap();
+            let msgs = fetch_messages(&manager, group, topic, &other, 10, 0).await;
+            assert_eq!(msgs.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![1]);
+            let rejoined = manager.join_group(group, topic, "client-C", Some(&consumer.session_token)).await.unwrap();
+            assert_ne!(rejoined.consumer_id, consumer.consumer_id);
+        }
+
+        #[tokio::test]
+        async fn test_duplicate_session_policy() {
+            use nexo::brokers::stream::config::DuplicateSession;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.session_grace_ms = 10_000;
+            let topic = "duplicate-session";
+            let group = "g-dup";
+
+            // Takeover (default): the session moves and the old connection is reported
+            let manager = build_manager(config.clone()).await;
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            assert!(consumer.displaced.is_none());
+            let taken = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.unwrap();
+            assert_eq!(taken.consumer_id, consumer.consumer_id);
+            assert_eq!(taken.displaced.as_deref(), Some("client-A"));
+            // Same connection again: nothing to displace
+            let again = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.unwrap();
+            assert!(again.displaced.is_none());
+            manager.shutdown();
+
+            config.persistence_path = temp_dir.path().join("reject").to_str().unwrap().to_string();
+            config.duplicate_session = DuplicateSession::Reject;
+            let manager = build_manager(config).await;
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let err = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.err().unwrap();
+            assert_eq!(err.code, ErrorCode::Conflict);
+
+            // Once the holder is gone the session resumes as usual
+            manager.disconnect("client-A".to_string()).await;
+            let resumed = manager.join_group(group, topic, "client-B", Some(&consumer.session_token)).await.unwrap();
+            assert_eq!(resumed.consumer_id, consumer.consumer_id);
+            assert!(resumed.displaced.is_none());
+        }
+
         #[tokio::test]
         async fn test_multi_consumer_parallel_fetch() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -350,6 +705,236 @@ mod stream_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_group_subscribes_to_multiple_topics() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let group = "g-aggregate";
+
+            for topic in ["orders.created", "orders.paid", "billing", "orders.created.g-other.dlq", "audit"] {
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            }
+
+            let selectors = vec!["orders.*".to_string(), "billing".to_string()];
+            let joined = manager.join_topics(group, &selectors, "client-A", &Default::default()).await.unwrap();
+            let topics: Vec<&str> = joined.iter().map(|(t, _)| t.as_str()).collect();
+            assert_eq!(topics, vec!["billing", "orders.created", "orders.paid"], "Patterns skip DLQ topics");
+            let members: Vec<TopicMember> = joined.iter().map(|(topic, r)| TopicMember {
+                topic: topic.clone(),
+                consumer_id: r.consumer_id.clone(),
+                generation: r.generation,
+            }).collect();
+
+            for i in 1..=2 {
+                manager.publish("orders.created", Bytes::from(format!("created-{}", i))).await.unwrap();
+                manager.publish("orders.paid", Bytes::from(format!("paid-{}", i))).await.unwrap();
+            }
+            manager.publish("billing", Bytes::from("invoice-1")).await.unwrap();
+            manager.publish("audit", Bytes::from("not subscribed")).await.unwrap();
+
+            // Every topic gets a share of the batch before any takes the rest
+            let first = manager.fetch_topics(group, &members, 3, 0).await.unwrap();
+            let mut first_topics: Vec<&str> = first.iter().map(|(t, _)| t.as_str()).collect();
+            first_topics.sort();
+            assert_eq!(first_topics, vec!["billing", "orders.created", "orders.paid"]);
+
+            let second = manager.fetch_topics(group, &members, 10, 0).await.unwrap();
+            assert_eq!(second.len(), 2);
+            assert!(second.iter().all(|(t, m)| t.starts_with("orders.") && m.seq == 2));
+
+            // Acks stay keyed by (topic, seq)
+            for (topic, msg) in first.iter().chain(second.iter()) {
+                let member = members.iter().find(|m| &m.topic == topic).unwrap();
+                manager.ack(group, topic, &member.consumer_id, member.generation, msg.seq).await.unwrap();
+            }
+            assert!(manager.fetch_topics(group, &members, 10, 0).await.unwrap().is_empty());
+
+            // Long-poll wakes on a publish to any subscribed topic
+            let poller = manager.clone();
+            let handle = tokio::spawn(async move {
+                poller.fetch_topics(group, &members, 10, 5000).await.unwrap()
+            });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.publish("billing", Bytes::from("invoice-2")).await.unwrap();
+            let woken = tokio::time::timeout(Duration::from_secs(2), handle).await.unwrap().unwrap();
+            assert_eq!(woken.len(), 1);
+            assert_eq!(woken[0].0, "billing");
+            assert_eq!(woken[0].1.payload, Bytes::from("invoice-2"));
+
+            let missing = manager.join_topics(group, &["nope".to_string()], "client-B", &Default::default()).await;
+            assert_eq!(missing.err().unwrap().code, ErrorCode::NotFound);
+            let unmatched = manager.join_topics(group, &["payments.*".to_string()], "client-B", &Default::default()).await;
+            assert_eq!(unmatched.err().unwrap().code, ErrorCode::NotFound);
+        }
+
+        #[tokio::test]
+        async fn test_pattern_subscription_follows_new_topics() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let group = "g-pattern";
+            let selectors = vec!["orders-*".to_string()];
+            let members_of = |joined: &[(String, JoinGroupResult)]| -> Vec<TopicMember> {
+                joined.iter().map(|(topic, r)| TopicMember {
+                    topic: topic.clone(),
+                    consumer_id: r.consumer_id.clone(),
+                    generation: r.generation,
+                }).collect()
+            };
+
+            manager.create_topic("orders-eu".to_string(), StreamCreateOptions::default()).await.unwrap();
+            let joined = manager.join_topics(group, &selectors, "client-A", &Default::default()).await.unwrap();
+            let members = members_of(&joined);
+            let tokens: std::collections::HashMap<String, String> = joined.iter()
+                .map(|(topic, r)| (topic.clone(), r.session_token.clone()))
+                .collect();
+
+            // Unrelated topics leave the subscription alone
+            manager.create_topic("billing".to_string(), StreamCreateOptions::default()).await.unwrap();
+            assert!(manager.fetch_topics(group, &members, 10, 0).await.unwrap().is_empty());
+
+            // A matching topic wakes the long-poll with a rebalance
+            let poller = manager.clone();
+            let poll_members = members_of(&joined);
+            let handle = tokio::spawn(async move { poller.fetch_topics(group, &poll_members, 10, 5000).await });
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            manager.create_topic("orders-us".to_string(), StreamCreateOptions::default()).await.unwrap();
+            let woken = tokio::time::timeout(Duration::from_secs(2), handle).await.unwrap().unwrap();
+            assert_eq!(woken.err().unwrap().code, ErrorCode::Rebalance);
+
+            // The group is bound to the new topic right away: nothing published before the rejoin is lost
+            manager.publish("orders-us", Bytes::from("us-1")).await.unwrap();
+            let rejoined = manager.join_topics(group, &selectors, "client-A", &tokens).await.unwrap();
+            let topics: Vec<&str> = rejoined.iter().map(|(t, _)| t.as_str()).collect();
+            assert_eq!(topics, vec!["orders-eu", "orders-us"]);
+            assert_eq!(rejoined[0].1.consumer_id, joined[0].1.consumer_id, "Existing membership resumed");
+
+            let members = members_of(&rejoined);
+            let msgs = manager.fetch_topics(group, &members, 10, 0).await.unwrap();
+            assert_eq!(msgs.len(), 1);
+            assert_eq!(msgs[0].0, "orders-us");
+
+            // A dropped topic rebalances too
+            manager.delete_topic("orders-eu".to_string()).await.unwrap();
+            let dropped = manager.fetch_topics(group, &members, 10, 0).await;
+            assert_eq!(dropped.err().unwrap().code, ErrorCode::Rebalance);
+        }
+
+        #[tokio::test]
+        async fn test_backfill_copies_filtered_range_with_transform() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let json = |value: serde_json::Value| {
+                let mut payload = vec![nexo::transport::tcp::protocol::DATA_TYPE_JSON];
+                payload.extend(value.to_string().into_bytes());
+                Bytes::from(payload)
+            };
+
+            manager.create_topic("orders-v1".to_string(), StreamCreateOptions::default()).await.unwrap();
+            for i in 1..=8u64 {
+                let region = if i % 2 == 0 { "eu" } else { "us" };
+                manager.publish_with_timestamp("orders-v1", json(serde_json::json!({ "id": i, "region": region, "name": format!("o{}", i), "legacy": true })), 1_000 + i).await.unwrap();
+            }
+            manager.publish("orders-v1", Bytes::from("raw, not json")).await.unwrap();
+
+            let spec: BackfillSpec = serde_json::from_value(serde_json::json!({
+                "source": "orders-v1",
+                "target": "orders-v2",
+                "fromSeq": 3,
+                "key": { "pointer": "/region", "value": "eu" },
+                "transform": { "remove": ["legacy"], "rename": { "name": "title" }, "set": { "version": 2 } }
+            })).unwrap();
+            let id = manager.start_backfill(spec).await.unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let job = loop {
+                let job = manager.backfill(id).unwrap();
+                if job.state != "running" || Instant::now() >= deadline {
+                    break job;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            };
+            assert_eq!(job.state, "completed");
+            assert_eq!((job.to_seq, job.copied, job.skipped), (9, 3, 4), "seqs 4, 6 and 8 copied; odd ones and the raw record filtered out");
+            assert!(!manager.cancel_backfill(id), "Finished jobs can't be cancelled");
+
+            let copied = manager.read("orders-v2", 1, 10).await;
+            assert_eq!(copied.len(), 3);
+            let first = nexo::brokers::payload::payload_to_json_value(&copied[0].payload);
+            assert_eq!(first, serde_json::json!({ "id": 4, "region": "eu", "title": "o4", "version": 2 }));
+            assert_eq!(copied[0].timestamp, 1_004, "Original timestamps are kept");
+
+            let invalid: BackfillSpec = serde_json::from_value(serde_json::json!({ "source": "orders-v1", "target": "orders-v1" })).unwrap();
+            assert_eq!(manager.start_backfill(invalid).await.unwrap_err().code, ErrorCode::InvalidRequest);
+            let missing: BackfillSpec = serde_json::from_value(serde_json::json!({ "source": "nope", "target": "x" })).unwrap();
+            assert_eq!(manager.start_backfill(missing).await.unwrap_err().code, ErrorCode::NotFound);
+        }
+
+        #[tokio::test]
+        async fn test_topic_bytes_accounting() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let topic = "bytes-accounting";
+
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            let bytes_of = |snapshot: nexo::brokers::stream::snapshot::StreamSnapshot| {
+                snapshot.topics.into_iter().find(|t| t.name == topic).map(|t| t.bytes).unwrap()
+            };
+            assert_eq!(bytes_of(manager.get_snapshot().await), 0);
+
+            manager.publish(topic, Bytes::from("12345")).await.unwrap();
+            let one = bytes_of(manager.get_snapshot().await);
+            assert!(one > 5, "Record overhead should be counted");
+
+            manager.publish(topic, Bytes::from("1234567890")).await.unwrap();
+            assert_eq!(bytes_of(manager.get_snapshot().await), one * 2 + 5);
+        }
+
+        #[tokio::test]
+        async fn test_mirror_replicates_remote_topic() {
+            let remote_dir = tempfile::tempdir().unwrap();
+            let (remote, addr) = start_remote(remote_dir.path()).await;
+            remote.stream.create_topic("edge-sensors".to_string(), StreamCreateOptions::default()).await.unwrap();
+            for i in 0..3 {
+                remote.stream.publish("edge-sensors", Bytes::from(format!("reading-{}", i))).await.unwrap();
+            }
+            let source = remote.stream.read("edge-sensors", 1, 10).await;
+
+            let local_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(local_dir.path().to_str().unwrap()));
+            config.mirrors = MirrorSpec::parse_list(&format!("{}/edge-sensors=cloud-sensors", addr)).unwrap();
+            let manager = build_manager(config).await;
+
+            let mirrored = wait_for_messages(&manager, "cloud-sensors", 3).await;
+            assert_eq!(mirrored.len(), 3);
+            for (local, remote) in mirrored.iter().zip(source.iter()) {
+                assert_eq!(local.payload, remote.payload);
+                assert_eq!(local.timestamp, remote.timestamp, "Mirror should preserve timestamps");
+            }
+
+            let snapshot = manager.get_snapshot().await;
+            let status = &snapshot.mirrors[0];
+            assert_eq!(status.source, format!("{}/edge-sensors", addr));
+            assert_eq!(status.local_topic, "cloud-sensors");
+            assert!(status.connected);
+            assert_eq!((status.mirrored, status.last_seq), (3, 3));
+        }
+
+        #[test]
+        fn test_mirror_spec_parsing() {
+            let spec = MirrorSpec::parse("10.0.0.5:7654/sensors").unwrap();
+            assert_eq!((spec.remote_addr.as_str(), spec.remote_topic.as_str(), spec.local_topic.as_str()), ("10.0.0.5:7654", "sensors", "sensors"));
+
+            let list = MirrorSpec::parse_list("edge-1:7654/logs=logs-edge1, ,edge-2:7654/logs=logs-edge2").unwrap();
+            assert_eq!(list.len(), 2);
+            assert_eq!(list[1].local_topic, "logs-edge2");
+            assert!(MirrorSpec::parse_list("edge-1:7654/logs=logs-edge1, bad-entry").is_err());
+            assert!(MirrorSpec::parse("edge:7654/topic=").is_none());
+        }
+
         #[tokio::test]
         async fn test_delete_topic() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -377,6 +962,40 @@ mod stream_tests {
             let msgs = manager.read(topic, 1, 10).await;
             assert!(msgs.is_empty());
         }
+
+        #[tokio::test]
+        async fn test_priority_lanes_deliver_high_first() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let manager = build_manager(config).await;
+            let topic = "priority-lanes";
+            let group = "g-lanes";
+
+            let options = StreamCreateOptions { priority_lanes: Some(true), ..Default::default() };
+            manager.create_topic(topic.to_string(), options).await.unwrap();
+            manager.create_topic("no-lanes".to_string(), StreamCreateOptions::default()).await.unwrap();
+            assert!(manager.publish_to_lane("no-lanes", Bytes::from("x"), LANE_HIGH).await.is_err());
+
+            manager.publish(topic, Bytes::from("n1")).await.unwrap();
+            manager.publish(topic, Bytes::from("n2")).await.unwrap();
+            manager.publish_to_lane(topic, Bytes::from("h1"), LANE_HIGH).await.unwrap();
+            manager.publish(topic, Bytes::from("n3")).await.unwrap();
+            manager.publish_to_lane(topic, Bytes::from("h2"), LANE_HIGH).await.unwrap();
+
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let first = fetch_messages(&manager, group, topic, &consumer, 3, 0).await;
+            let payloads: Vec<_> = first.iter().map(|m| m.payload.clone()).collect();
+            assert_eq!(payloads, vec![Bytes::from("h1"), Bytes::from("h2"), Bytes::from("n1")]);
+
+            let rest = fetch_messages(&manager, group, topic, &consumer, 10, 0).await;
+            let payloads: Vec<_> = rest.iter().map(|m| m.payload.clone()).collect();
+            assert_eq!(payloads, vec![Bytes::from("n2"), Bytes::from("n3")]);
+
+            for msg in first.iter().chain(rest.iter()) {
+                ack_message(&manager, group, topic, &consumer, msg.seq).await;
+            }
+            assert!(fetch_messages(&manager, group, topic, &consumer, 10, 0).await.is_empty());
+        }
     }
 
     mod persistence {
@@ -414,6 +1033,207 @@ mod stream_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_file_sync_publish_is_durable_on_ack() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().to_str());
+            // The periodic flush never fires during the test: only group commits write
+            config.default_flush_ms = 60_000;
+            config.group_commit_ms = 5;
+            let topic = "persist-sync";
+
+            let manager = build_manager(config.clone()).await;
+            let options = StreamCreateOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() };
+            manager.create_topic(topic.to_string(), options).await.unwrap();
+
+            let handles: Vec<_> = (0..20).map(|i| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.publish(topic, Bytes::from(format!("sync-{}", i))).await })
+            }).collect();
+            for handle in handles {
+                handle.await.unwrap().unwrap();
+            }
+
+            // Every acked publish is already on disk, while the first manager is still running
+            let manager2 = build_manager(config).await;
+            let msgs = manager2.read(topic, 1, 100).await;
+            assert_eq!(msgs.len(), 20);
+            assert_eq!(msgs.iter().map(|m| m.seq).collect::<Vec<_>>(), (1..=20).collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn test_flush_persists_records_and_ack_floors() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().to_str());
+            // The periodic flush never fires during the test: only flush() writes
+            config.default_flush_ms = 60_000;
+            let topic = "persist-flush";
+            let group = "g-flush";
+
+            let manager = build_manager(config.clone()).await;
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            for i in 0..3 {
+                manager.publish(topic, Bytes::from(format!("flushed-{}", i))).await.unwrap();
+            }
+            let consumer = join_session(&manager, group, topic, "client-A").await;
+            let msgs = fetch_messages(&manager, group, topic, &consumer, 2, 0).await;
+            for msg in &msgs {
+                ack_message(&manager, group, topic, &consumer, msg.seq).await;
+            }
+
+            manager.flush().await.unwrap();
+            let manager2 = build_manager(config.clone()).await;
+            assert_eq!(manager2.read(topic, 1, 100).await.len(), 3);
+            assert_eq!(join_session(&manager2, group, topic, "client-B").await.ack_floor, 2);
+
+            manager.close().await.unwrap();
+        }
+
+        #[test]
+        fn test_dropped_runtime_flushes_stream_segments() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().to_str());
+            config.default_flush_ms = 60_000;
+            let topic = "persist-drop";
+
+            // No close(): the storage actor is dropped with the runtime, holding the records
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                for i in 0..10 {
+                    manager.publish(topic, Bytes::from(format!("dropped-{}", i))).await.unwrap();
+                }
+                // Let the actor take the appends: one it is in the middle of is lost with the task
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            });
+            drop(runtime);
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let msgs = runtime.block_on(async {
+                let manager = build_manager(config.clone()).await;
+                manager.read(topic, 1, 100).await
+            });
+            assert_eq!(msgs.iter().map(|m| m.seq).collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+        }
+
+        #[tokio::test]
+        async fn test_io_uring_backend_write_and_recover() {
+            // Falls back to buffered writes without the io-uring feature, so this runs either way
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().to_str());
+            config.write_backend = WriteBackend::IoUring;
+            config.max_segment_size = 256;
+            let topic = "persist-uring";
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                for i in 0..50 {
+                    manager.publish(topic, Bytes::from(format!("msg-{}", i))).await.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            {
+                let manager = build_manager(config.clone()).await;
+                let msgs = manager.read(topic, 1, 100).await;
+                assert_eq!(msgs.len(), 50);
+                for (i, msg) in msgs.iter().enumerate() {
+                    assert_eq!(msg.seq, i as u64 + 1);
+                    assert_eq!(msg.payload, Bytes::from(format!("msg-{}", i)));
+                }
+                // Appends keep going at the end of the recovered segment
+                manager.publish(topic, Bytes::from("after-restart")).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            let manager = build_manager(config).await;
+            let msgs = manager.read(topic, 50, 10).await;
+            assert_eq!(msgs.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("msg-49"), Bytes::from("after-restart")]);
+        }
+
+        #[tokio::test]
+        async fn test_priority_lane_survives_restart() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = Config::global().stream.clone();
+            config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            let topic = "persist-lanes";
+
+            {
+                let manager = build_manager(config.clone()).await;
+                let options = StreamCreateOptions { priority_lanes: Some(true), ..Default::default() };
+                manager.create_topic(topic.to_string(), options).await.unwrap();
+                manager.publish(topic, Bytes::from("normal")).await.unwrap();
+                manager.publish_to_lane(topic, Bytes::from("urgent"), LANE_HIGH).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            {
+                let manager = build_manager(config.clone()).await;
+                let msgs = manager.read(topic, 1, 10).await;
+                assert_eq!(msgs.len(), 2);
+                assert_eq!(msgs[0].lane, LANE_NORMAL);
+                assert_eq!(msgs[1].lane, LANE_HIGH);
+
+                let consumer = join_session(&manager, "g", topic, "client-A").await;
+                let fetched = fetch_messages(&manager, "g", topic, &consumer, 1, 0).await;
+                assert_eq!(fetched[0].payload, Bytes::from("urgent"));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_integrity_check_repairs_segment() {
+            use nexo::brokers::integrity::{self, DataDirs, FileKind, FileStatus};
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(temp_dir.path().join("streams").to_str());
+            let topic = "persist-integrity";
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                for i in 1..=3 {
+                    manager.publish(topic, Bytes::from(format!("msg{}", i))).await.unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(150)).await;
+            }
+
+            // Flip the last payload byte of the third record
+            let segment = temp_dir.path().join("streams").join(topic).join("1.log");
+            let mut data = std::fs::read(&segment).unwrap();
+            let last = data.len() - 1;
+            data[last] ^= 0xFF;
+            std::fs::write(&segment, &data).unwrap();
+
+            let dirs = DataDirs {
+                queue: temp_dir.path().join("queues"),
+                stream: temp_dir.path().join("streams"),
+                pubsub: temp_dir.path().join("pubsub"),
+            };
+
+            let report = integrity::check(&dirs, false);
+            assert!(!report.ok);
+            let file = report.files.iter().find(|f| f.kind == FileKind::Segment).unwrap();
+            assert_eq!(file.status, FileStatus::Corrupt);
+            assert_eq!(file.records, Some(2));
+            assert!(file.issues[0].contains("CRC mismatch"));
+            assert_eq!(std::fs::metadata(&segment).unwrap().len(), data.len() as u64, "check alone must not modify files");
+
+            let report = integrity::check(&dirs, true);
+            assert!(report.ok);
+            assert_eq!(report.repaired, 1);
+            let valid_bytes = report.files.iter().find(|f| f.kind == FileKind::Segment).unwrap().valid_bytes.unwrap();
+            assert_eq!(std::fs::metadata(&segment).unwrap().len(), valid_bytes);
+
+            assert!(integrity::check(&dirs, false).ok);
+
+            let manager = build_manager(config).await;
+            let msgs = manager.read(topic, 1, 10).await;
+            assert_eq!(msgs.len(), 2);
+            assert_eq!(msgs[1].payload, Bytes::from("msg2"));
+        }
+
         #[tokio::test]
         async fn test_ack_floor_persistence() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -449,6 +1269,48 @@ mod stream_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_generation_stays_fenced_across_restarts() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(temp_dir.path().to_str());
+            let topic = "fenced-epochs";
+            let group = "g-epochs";
+            let topic_dir = temp_dir.path().join(topic);
+
+            let first = {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                manager.publish(topic, Bytes::from("msg1")).await.unwrap();
+                let consumer = join_session(&manager, group, topic, "client-A").await;
+                let msgs = fetch_messages(&manager, group, topic, &consumer, 10, 0).await;
+                ack_message(&manager, group, topic, &consumer, msgs[0].seq).await;
+                tokio::time::sleep(Duration::from_millis(600)).await;
+                consumer
+            };
+            assert!(topic_dir.join("epochs.json").exists());
+
+            // Restart with the commit log gone: the group comes back from scratch
+            std::fs::remove_file(topic_dir.join("groups.log")).unwrap();
+            let manager = build_manager(config.clone()).await;
+            manager.publish(topic, Bytes::from("msg2")).await.unwrap();
+            assert!(manager.ack(group, topic, &first.consumer_id, first.generation, 1).await.is_err());
+            let second = join_session(&manager, group, topic, "client-B").await;
+            assert!(second.generation > first.generation, "generation regressed: {} -> {}", first.generation, second.generation);
+
+            // A client holding the pre-restart generation is fenced on commit
+            let fetched = fetch_messages(&manager, group, topic, &second, 10, 0).await;
+            assert!(!fetched.is_empty());
+            let err = manager.ack(group, topic, &second.consumer_id, first.generation, fetched[0].seq).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::Fenced);
+            ack_message(&manager, group, topic, &second, fetched[0].seq).await;
+            drop(manager);
+
+            // Each run hands out a generation above every earlier one
+            let manager = build_manager(config).await;
+            let third = join_session(&manager, group, topic, "client-C").await;
+            assert!(third.generation > second.generation);
+        }
+
         #[tokio::test]
         async fn test_corruption_integrity() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -584,6 +1446,166 @@ mod stream_tests {
             assert_eq!(fetched.first().map(|msg| msg.seq), retained.first().map(|msg| msg.seq));
         }
 
+        #[tokio::test]
+        async fn test_cold_reads_use_segment_cache() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().to_str());
+            config.max_segment_size = 200;
+            let topic = "cold_read_cache";
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                for i in 1..=10 {
+                    manager.publish(topic, Bytes::from(format!("msg{:02}-padding-0000000000", i))).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+
+            // Only the tail segment is loaded on restart: older seqs are cold reads
+            let manager = build_manager(config).await;
+            let first = manager.read(topic, 1, 5).await;
+            let seqs: Vec<u64> = first.iter().map(|m| m.seq).collect();
+            assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+            assert_eq!(first[0].payload, Bytes::from("msg01-padding-0000000000"));
+
+            let cache = manager.get_snapshot().await.read_cache;
+            assert!(cache.misses > 0);
+            assert_eq!(cache.hits, 0);
+
+            let second = manager.read(topic, 1, 5).await;
+            assert_eq!(second.iter().map(|m| m.seq).collect::<Vec<_>>(), seqs);
+            let cache = manager.get_snapshot().await.read_cache;
+            assert!(cache.hits > 0);
+            assert!(cache.blocks > 0);
+        }
+
+        #[tokio::test]
+        async fn test_topic_export_import_roundtrip() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(temp_dir.path().join("streams").to_str());
+            config.max_segment_size = 200;
+            let manager = build_manager(config).await;
+            let topic = "export_source";
+            manager.create_topic(topic.to_string(), StreamCreateOptions { priority_lanes: Some(true), ..Default::default() }).await.unwrap();
+            for i in 1..=20 {
+                let lane = if i % 5 == 0 { LANE_HIGH } else { LANE_NORMAL };
+                manager.publish_to_lane(topic, Bytes::from(format!("record-{:02}", i)), lane).await.unwrap();
+            }
+            let source = manager.read(topic, 1, 100).await;
+
+            for (format, file) in [(DumpFormat::Jsonl, "dump.jsonl"), (DumpFormat::Binary, "dump.bin")] {
+                let path = temp_dir.path().join(file);
+                let exported = manager.export_topic(topic, &path, format).await.unwrap();
+                assert_eq!(exported.records, 20);
+                assert_eq!((exported.first_offset, exported.last_offset), (Some(1), Some(20)));
+
+                let target = format!("imported_{}", file.replace('.', "_"));
+                let imported = manager.import_topic(&path, target.clone(), StreamCreateOptions::default()).await.unwrap();
+                assert_eq!(imported.records, 20);
+                assert_eq!(imported.lane_downgraded, 4);
+                let copy = manager.read(&target, 1, 100).await;
+                assert_eq!(copy.iter().map(|m| &m.payload).collect::<Vec<_>>(), source.iter().map(|m| &m.payload).collect::<Vec<_>>());
+                assert_eq!(copy.iter().map(|m| m.timestamp).collect::<Vec<_>>(), source.iter().map(|m| m.timestamp).collect::<Vec<_>>());
+
+                let err = manager.import_topic(&path, target, StreamCreateOptions::default()).await.unwrap_err();
+                assert!(err.message.contains("already exists"));
+            }
+
+            // A dump from another tool: keys are dropped, blank lines skipped
+            let foreign = temp_dir.path().join("foreign.jsonl");
+            std::fs::write(&foreign, "{\"offset\":7,\"timestamp\":1000,\"key\":\"k1\",\"payload\":\"aGVsbG8=\"}\n\n{\"offset\":9,\"timestamp\":2000,\"payload\":\"d29ybGQ=\"}\n").unwrap();
+            manager.import_topic(&foreign, "foreign".to_string(), StreamCreateOptions::default()).await.unwrap();
+            let records = manager.read("foreign", 1, 10).await;
+            assert_eq!(records.iter().map(|m| (m.seq, m.timestamp, m.payload.clone())).collect::<Vec<_>>(),
+                vec![(1, 1000, Bytes::from("hello")), (2, 2000, Bytes::from("world"))]);
+
+            // A dump broken partway leaves no topic behind: the retry is not refused
+            let broken = temp_dir.path().join("broken.jsonl");
+            std::fs::write(&broken, "{\"offset\":1,\"timestamp\":1000,\"payload\":\"aGVsbG8=\"}\nnot json\n").unwrap();
+            let err = manager.import_topic(&broken, "broken".to_string(), StreamCreateOptions::default()).await.unwrap_err();
+            assert!(err.message.contains("Line 2"), "{}", err.message);
+            assert!(!manager.exists("broken").await);
+            std::fs::write(&broken, "{\"offset\":1,\"timestamp\":1000,\"payload\":\"aGVsbG8=\"}\n").unwrap();
+            assert_eq!(manager.import_topic(&broken, "broken".to_string(), StreamCreateOptions::default()).await.unwrap().records, 1);
+        }
+
+        /// In-memory S3: objects by path, whatever the signature.
+        async fn start_fake_s3() -> (String, Arc<std::sync::Mutex<std::collections::HashMap<String, Bytes>>>) {
+            use axum::http::{Method, StatusCode, Uri};
+            let objects = Arc::new(std::sync::Mutex::new(std::collections::HashMap::<String, Bytes>::new()));
+            let store = objects.clone();
+            let app = axum::Router::new().fallback(move |method: Method, uri: Uri, body: Bytes| {
+                let store = store.clone();
+                async move {
+                    let mut objects = store.lock().unwrap();
+                    let key = uri.path().to_string();
+                    match method {
+                        Method::PUT => { objects.insert(key, body); (StatusCode::OK, Bytes::new()) }
+                        Method::GET => match objects.get(&key) {
+                            Some(data) => (StatusCode::OK, data.clone()),
+                            None => (StatusCode::NOT_FOUND, Bytes::new()),
+                        },
+                        Method::DELETE => { objects.remove(&key); (StatusCode::NO_CONTENT, Bytes::new()) }
+                        _ => (StatusCode::METHOD_NOT_ALLOWED, Bytes::new()),
+                    }
+                }
+            });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint = format!("http://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            (endpoint, objects)
+        }
+
+        #[tokio::test]
+        async fn test_tiered_segments_read_back_from_object_store() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let (endpoint, objects) = start_fake_s3().await;
+            let mut config = get_test_config(temp_dir.path().join("streams").to_str());
+            config.max_segment_size = 200;
+            config.tiering.endpoint = endpoint;
+            config.tiering.bucket = "nexo".to_string();
+            config.tiering.access_key = "test".to_string();
+            config.tiering.secret_key = "test".to_string();
+            config.tiering.age_ms = 0;
+            config.tiering.check_interval_ms = 100;
+            config.tiering.cache_path = temp_dir.path().join("cache").to_str().unwrap().to_string();
+            let topic = "tiered_topic";
+            let topic_dir = temp_dir.path().join("streams").join(topic);
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+                for i in 1..=10 {
+                    manager.publish(topic, Bytes::from(format!("msg{:02}-padding-0000000000", i))).await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+
+            let local: Vec<_> = std::fs::read_dir(&topic_dir).unwrap().flatten()
+                .filter(|e| e.file_name().to_str().is_some_and(|n| n.ends_with(".log") && n != "groups.log"))
+                .collect();
+            assert_eq!(local.len(), 1, "only the active segment stays on disk");
+            assert!(topic_dir.join("tiered.json").exists());
+            assert!(objects.lock().unwrap().keys().all(|key| key.starts_with("/nexo/streams/tiered_topic/")));
+            assert!(!objects.lock().unwrap().is_empty());
+
+            // Cold reads download the tiered segments, then hit the local cache
+            let manager = build_manager(config).await;
+            let msgs = manager.read(topic, 1, 10).await;
+            assert_eq!(msgs.iter().map(|m| m.seq).collect::<Vec<_>>(), (1..=10).collect::<Vec<u64>>());
+            assert_eq!(msgs[0].payload, Bytes::from("msg01-padding-0000000000"));
+            assert!(temp_dir.path().join("cache").join(topic).exists());
+            let again = manager.read(topic, 1, 3).await;
+            assert_eq!(again.iter().map(|m| m.seq).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+            manager.delete_topic(topic.to_string()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(objects.lock().unwrap().is_empty(), "deleting the topic deletes its objects");
+        }
+
         #[tokio::test]
         async fn test_warm_start_auto_restore() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -636,6 +1658,240 @@ mod stream_tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_manifest_conflicts_and_alter() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let sync = || StreamCreateOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() };
+            let async_opts = || StreamCreateOptions { persistence: Some(PersistenceMode::FileAsync), dead_letter: Some(true), ..Default::default() };
+            let alter_async = AlterOptions { persistence: Some(PersistenceMode::FileAsync), dead_letter: Some(true), ..Default::default() };
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic("orders".to_string(), sync()).await.unwrap();
+                manager.create_topic("orders".to_string(), sync()).await.unwrap();
+                manager.create_topic("orders".to_string(), StreamCreateOptions::default()).await.unwrap();
+
+                let err = manager.create_topic("orders".to_string(), async_opts()).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::Conflict);
+                assert!(err.message.contains("persistence: fileSync (requested fileAsync)"), "{}", err.message);
+
+                manager.alter_topic("orders", alter_async.clone()).await.unwrap();
+                manager.create_topic("orders".to_string(), async_opts()).await.unwrap();
+                assert_eq!(manager.alter_topic("missing", alter_async).await.unwrap_err().code, ErrorCode::NotFound);
+                manager.shutdown();
+            }
+
+            // The altered manifest is what a restart restores
+            let manager = build_manager(config).await;
+            assert_eq!(manager.create_topic("orders".to_string(), sync()).await.unwrap_err().code, ErrorCode::Conflict);
+            manager.create_topic("orders".to_string(), async_opts()).await.unwrap();
+        }
+
+        #[tokio::test]
+        async fn test_topic_labels() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let labels = |pairs: &[(&str, &str)]| Some(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+            let labels_of = |snapshot: &nexo::brokers::stream::snapshot::StreamSnapshot, topic: &str| {
+                snapshot.topics.iter().find(|t| t.name == topic).unwrap().config.labels.clone()
+            };
+
+            {
+                let manager = build_manager(config.clone()).await;
+                let create = StreamCreateOptions { labels: labels(&[("team", "payments"), ("env", "staging")]), ..Default::default() };
+                manager.create_topic("orders".to_string(), create).await.unwrap();
+
+                let err = manager.create_topic("bad".to_string(), StreamCreateOptions { labels: labels(&[("-team", "x")]), ..Default::default() }).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::InvalidRequest);
+                let err = manager.create_topic("orders".to_string(), StreamCreateOptions { labels: labels(&[("team", "ops")]), ..Default::default() }).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::Conflict);
+
+                manager.alter_topic("orders", AlterOptions { labels: labels(&[("team", "payments"), ("env", "prod")]), ..Default::default() }).await.unwrap();
+                assert!(manager.alter_topic("orders", AlterOptions { labels: labels(&[("env", "a\u{7}")]), ..Default::default() }).await.is_err());
+                manager.shutdown();
+            }
+
+            // Labels are part of the manifest
+            let manager = build_manager(config).await;
+            let snapshot = manager.get_snapshot().await;
+            assert_eq!(labels_of(&snapshot, "orders").get("env").map(String::as_str), Some("prod"));
+            assert_eq!(labels_of(&snapshot, "orders").len(), 2);
+        }
+
+        #[tokio::test]
+        async fn test_rebalance_hooks() {
+            use nexo::brokers::events::EventBus;
+            use nexo::brokers::hooks::HookDispatcher;
+            use nexo::brokers::stream::options::SeekTarget;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let manager = build_manager(get_test_config(Some(temp_dir.path().to_str().unwrap()))).await;
+            let received = Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+            let sink = received.clone();
+            let app = axum::Router::new().route("/events", axum::routing::post(move |body: Bytes| {
+                let sink = sink.clone();
+                async move { sink.lock().unwrap().push(serde_json::from_slice(&body).unwrap()) }
+            }));
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+            let mut hooks = Config::global().hooks.clone();
+            hooks.urls = vec![format!("http://{}/events", addr)];
+            hooks.dead_letter_path = temp_dir.path().join("hooks.jsonl").to_str().unwrap().to_string();
+            let clock = manager.clock().clone();
+            manager.attach_events(EventBus::new(0, HookDispatcher::start(&hooks, clock.clone()), clock));
+
+            manager.create_topic("orders".to_string(), StreamCreateOptions::default()).await.unwrap();
+            let first = manager.join_group("billing", "orders", "client-1", None).await.unwrap();
+            // Resuming a detached membership is not a rebalance
+            manager.join_group("billing", "orders", "client-1", Some(&first.session_token)).await.unwrap();
+            let second = manager.join_group("billing", "orders", "client-2", None).await.unwrap();
+            manager.leave_group("billing", "orders", &second.consumer_id, second.generation).await.unwrap();
+            manager.seek("billing", "orders", SeekTarget::End).await.unwrap();
+
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while received.lock().unwrap().len() < 4 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let events = received.lock().unwrap().clone();
+            let summary: Vec<(&str, u64)> = events.iter()
+                .map(|e| (e["reason"].as_str().unwrap(), e["members"].as_u64().unwrap()))
+                .collect();
+            assert_eq!(summary, vec![("joined", 1), ("joined", 2), ("left", 1), ("seek", 0)]);
+            assert!(events.iter().all(|e| e["event"] == "groupRebalanced" && e["topic"] == "orders" && e["group"] == "billing"));
+            assert!(events[3]["generation"].as_u64().unwrap() > events[2]["generation"].as_u64().unwrap());
+        }
+
+        #[tokio::test]
+        async fn test_alter_flush_interval() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.write_backend = WriteBackend::Buffered;
+            let segment = temp_dir.path().join("slow").join("1.log");
+            let on_disk = || std::fs::metadata(&segment).map(|m| m.len()).unwrap_or(0);
+            let flush_ms = |ms| AlterOptions { flush_ms: Some(ms), ..Default::default() };
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic("slow".to_string(), StreamCreateOptions::default()).await.unwrap();
+                manager.alter_topic("slow", flush_ms(60_000)).await.unwrap();
+
+                manager.publish("slow", Bytes::from("buffered")).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                assert_eq!(on_disk(), 0, "Held until the topic's own interval");
+
+                manager.alter_topic("slow", flush_ms(0)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                assert!(on_disk() > 0, "Back on the server interval");
+
+                manager.alter_topic("slow", flush_ms(60_000)).await.unwrap();
+                manager.shutdown();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+
+            // The interval is part of the manifest
+            let manager = build_manager(config).await;
+            let written = on_disk();
+            manager.publish("slow", Bytes::from("buffered again")).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert_eq!(on_disk(), written);
+        }
+
+        #[tokio::test]
+        async fn test_warm_start_from_moved_data_dir() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let old_root = temp_dir.path().join("old");
+            let new_root = temp_dir.path().join("new");
+            let mut config = get_test_config(Some(old_root.to_str().unwrap()));
+
+            {
+                let manager = build_manager(config.clone()).await;
+                manager.create_topic("moved".to_string(), StreamCreateOptions::default()).await.unwrap();
+                manager.publish("moved", Bytes::from("before")).await.unwrap();
+                let snapshot = manager.get_snapshot().await;
+                assert!(!snapshot.topics[0].recovered, "Created in this process");
+                manager.shutdown();
+                tokio::time::sleep(Duration::from_millis(600)).await;
+            }
+
+            // Not a topic: no manifest, no segments
+            std::fs::create_dir_all(old_root.join("lost+found")).unwrap();
+            std::fs::write(old_root.join("lost+found").join("notes.txt"), "x").unwrap();
+            std::fs::rename(&old_root, &new_root).unwrap();
+            config.persistence_path = new_root.to_str().unwrap().to_string();
+
+            let manager = build_manager(config).await;
+            let snapshot = manager.get_snapshot().await;
+            assert_eq!(snapshot.topics.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), vec!["moved"], "Stray directories are not restored");
+            assert!(snapshot.topics[0].recovered);
+
+            manager.publish("moved", Bytes::from("after")).await.unwrap();
+            let msgs = manager.read("moved", 1, 10).await;
+            assert_eq!(msgs.iter().map(|m| m.payload.clone()).collect::<Vec<_>>(), vec![Bytes::from("before"), Bytes::from("after")]);
+            tokio::time::sleep(Duration::from_millis(600)).await;
+            assert!(!old_root.exists(), "Writes follow the moved root, not the path in the manifest");
+        }
+
+        #[tokio::test]
+        async fn test_mirror_resumes_from_checkpoint() {
+            let remote_dir = tempfile::tempdir().unwrap();
+            let (remote, addr) = start_remote(remote_dir.path()).await;
+            remote.stream.create_topic("edge-logs".to_string(), StreamCreateOptions::default()).await.unwrap();
+            for i in 0..3 {
+                remote.stream.publish("edge-logs", Bytes::from(format!("log-{}", i))).await.unwrap();
+            }
+
+            let local_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(local_dir.path().to_str().unwrap()));
+            config.mirrors = MirrorSpec::parse_list(&format!("{}/edge-logs", addr)).unwrap();
+
+            {
+                let manager = build_manager(config.clone()).await;
+                assert_eq!(wait_for_messages(&manager, "edge-logs", 3).await.len(), 3);
+                manager.shutdown();
+                tokio::time::sleep(Duration::from_millis(600)).await;
+            }
+
+            // Rewind the remote group as if acks were lost, then keep producing
+            use nexo::brokers::stream::options::SeekTarget;
+            remote.stream.seek("nexo-mirror.edge-logs", "edge-logs", SeekTarget::Beginning).await.unwrap();
+            for i in 3..5 {
+                remote.stream.publish("edge-logs", Bytes::from(format!("log-{}", i))).await.unwrap();
+            }
+
+            let manager2 = build_manager(config).await;
+            let msgs = wait_for_messages(&manager2, "edge-logs", 5).await;
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let msgs_after = manager2.read("edge-logs", 1, 100).await;
+            assert_eq!(msgs.len(), 5);
+            assert_eq!(msgs_after.len(), 5, "Checkpoint should prevent re-mirroring");
+            let payloads: Vec<_> = msgs_after.iter().map(|m| m.payload.clone()).collect();
+            let expected: Vec<_> = (0..5).map(|i| Bytes::from(format!("log-{}", i))).collect();
+            assert_eq!(payloads, expected);
+        }
+
+        #[tokio::test]
+        async fn test_mirror_reassembles_chunked_batches() {
+            let remote_dir = tempfile::tempdir().unwrap();
+            let (remote, addr) = start_remote(remote_dir.path()).await;
+            remote.stream.create_topic("big-logs".to_string(), StreamCreateOptions::default()).await.unwrap();
+            // One fetch answers over CHUNK_SIZE, so it comes back chunked
+            let large: Vec<Bytes> = (0..3u8).map(|i| Bytes::from(vec![i; 600 * 1024])).collect();
+            for payload in &large {
+                remote.stream.publish("big-logs", payload.clone()).await.unwrap();
+            }
+
+            let local_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(local_dir.path().to_str().unwrap()));
+            config.mirrors = MirrorSpec::parse_list(&format!("{}/big-logs", addr)).unwrap();
+            let manager = build_manager(config).await;
+            let msgs = wait_for_messages(&manager, "big-logs", 3).await;
+            let payloads: Vec<_> = msgs.iter().map(|m| m.payload.clone()).collect();
+            assert_eq!(payloads, large);
+        }
+
         #[tokio::test]
         async fn test_ram_eviction_under_load() {
             let temp_dir = tempfile::tempdir().unwrap();
@@ -708,6 +1964,213 @@ mod stream_tests {
                 ack_message(&manager, group, topic, &consumer, msg.seq).await;
             }
         }
+
+        #[tokio::test]
+        async fn test_topic_memory_caps_and_read_tiers() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = Config::global().stream.clone();
+            config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+            config.eviction_interval_ms = 50;
+            config.default_flush_ms = 20;
+            let manager = build_manager(config).await;
+            let topic = "memory_caps";
+
+            // 100 payloads of 10 bytes, at most 200 bytes in RAM once flushed
+            let options = StreamCreateOptions { max_memory_bytes: Some(200), ..Default::default() };
+            manager.create_topic(topic.to_string(), options).await.unwrap();
+            for i in 0..100 {
+                manager.publish(topic, Bytes::from(format!("msg_{:06}", i))).await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(300)).await;
+
+            let stats = |snapshot: nexo::brokers::stream::snapshot::StreamSnapshot| {
+                let t = snapshot.topics.into_iter().find(|t| t.name == topic).unwrap();
+                (t.ram_messages, t.ram_bytes, t.hot_reads, t.cold_reads)
+            };
+            let (ram_messages, ram_bytes, _, _) = stats(manager.get_snapshot().await);
+            assert_eq!((ram_messages, ram_bytes), (20, 200), "The byte cap bounds the window");
+
+            assert_eq!(manager.read(topic, 1, 10).await.len(), 10);
+            assert_eq!(manager.read(topic, 91, 10).await.len(), 10);
+            let (_, _, hot, cold) = stats(manager.get_snapshot().await);
+            assert_eq!((hot, cold), (10, 10));
+
+            // Tail-only: nothing flushed stays in RAM
+            let tail_only = AlterOptions { max_memory_messages: Some(0), ..Default::default() };
+            manager.alter_topic(topic, tail_only).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let (ram_messages, ram_bytes, _, _) = stats(manager.get_snapshot().await);
+            assert_eq!((ram_messages, ram_bytes), (0, 0));
+
+            let last = manager.read(topic, 100, 10).await;
+            assert_eq!(last[0].payload, Bytes::from("msg_000099"), "Served from disk");
+            let (_, _, hot, cold) = stats(manager.get_snapshot().await);
+            assert_eq!((hot, cold), (10, 11));
+        }
+
+        #[tokio::test]
+        async fn test_activity_runs_on_the_injected_clock() {
+            use nexo::brokers::clock::{Clock, MockClock};
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            let clock = MockClock::new();
+            let manager = StreamManager::with_clock(Arc::new(config), clock.clone()).await;
+            manager.create_topic("clocked".to_string(), StreamCreateOptions::default()).await.unwrap();
+
+            clock.advance(Duration::from_secs(3600));
+            manager.publish("clocked", Bytes::from("m1")).await.unwrap();
+            assert_eq!(manager.activity().get("clocked").unwrap().last_publish, clock.now_ms());
+        }
+
+        #[tokio::test]
+        async fn test_topic_publish_metrics() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.default_flush_ms = 20;
+            let manager = build_manager(config).await;
+            let topic = "publish_metrics";
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            manager.create_topic("quiet".to_string(), StreamCreateOptions::default()).await.unwrap();
+
+            for i in 0..120 {
+                manager.publish(topic, Bytes::from(format!("msg_{:06}", i))).await.unwrap();
+            }
+            manager.flush().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            let snapshot = manager.get_snapshot().await;
+            let t = snapshot.topics.iter().find(|t| t.name == topic).unwrap();
+            assert_eq!((t.head_seq, t.last_seq, t.persisted_seq), (1, 120, 120));
+            // Rates over the last minute: 120 appends of 34-byte records
+            assert_eq!(t.publish_rate, 2.0);
+            assert_eq!(t.byte_rate, 120.0 * 34.0 / 60.0);
+            // On disk: the startup retention pass may have measured the log mid-publish
+            assert_eq!(t.bytes, 120 * 34);
+            let on_disk: u64 = std::fs::read_dir(temp_dir.path().join(topic)).unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                .map(|path| std::fs::metadata(path).unwrap().len())
+                .sum();
+            assert_eq!(t.bytes, on_disk);
+            let quiet = snapshot.topics.iter().find(|t| t.name == "quiet").unwrap();
+            assert_eq!((quiet.publish_rate, quiet.last_seq, quiet.persisted_seq), (0.0, 0, 0));
+        }
+
+        #[tokio::test]
+        async fn test_publish_linger() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.default_flush_ms = 20;
+            let manager = build_manager(config).await;
+            let topic = "lingering";
+            let persisted = || async { manager.get_snapshot().await.topics[0].persisted_seq };
+            manager.create_topic(topic.to_string(), StreamCreateOptions { linger_ms: Some(400), ..Default::default() }).await.unwrap();
+
+            for i in 0..3 {
+                manager.publish(topic, Bytes::from(format!("msg_{}", i))).await.unwrap();
+            }
+            assert_eq!(manager.read(topic, 1, 10).await.len(), 3, "Readable while lingering");
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            assert_eq!(persisted().await, 0, "Held for the linger");
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            assert_eq!(persisted().await, 3);
+
+            // Every fileSync publisher of the batch is acked by its fsync
+            manager.alter_topic(topic, AlterOptions { persistence: Some(PersistenceMode::FileSync), ..Default::default() }).await.unwrap();
+            let publishes: Vec<_> = (0..5).map(|i| {
+                let manager = manager.clone();
+                tokio::spawn(async move { manager.publish(topic, Bytes::from(format!("sync_{}", i))).await })
+            }).collect();
+            let mut seqs = Vec::new();
+            for publish in publishes {
+                seqs.push(publish.await.unwrap().unwrap());
+            }
+            seqs.sort();
+            assert_eq!(seqs, vec![4, 5, 6, 7, 8]);
+
+            // A flush does not wait for the linger
+            manager.alter_topic(topic, AlterOptions { persistence: Some(PersistenceMode::FileAsync), ..Default::default() }).await.unwrap();
+            manager.publish(topic, Bytes::from("flushed")).await.unwrap();
+            manager.flush().await.unwrap();
+            assert_eq!(persisted().await, 9);
+
+            // Turning the linger off sends what is held first, in order
+            manager.publish(topic, Bytes::from("held")).await.unwrap();
+            manager.alter_topic(topic, AlterOptions { linger_ms: Some(0), ..Default::default() }).await.unwrap();
+            manager.publish(topic, Bytes::from("direct")).await.unwrap();
+            manager.flush().await.unwrap();
+            assert_eq!(persisted().await, 11);
+            let payloads: Vec<Bytes> = manager.read(topic, 10, 10).await.into_iter().map(|m| m.payload).collect();
+            assert_eq!(payloads, vec![Bytes::from("held"), Bytes::from("direct")]);
+        }
+
+        #[tokio::test]
+        async fn test_key_stats_samples_newest_records() {
+            use nexo::brokers::stream::key_stats::KeyStatsSpec;
+
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = get_test_config(Some(temp_dir.path().to_str().unwrap()));
+            config.ram_soft_limit = 100; // most of the sample is read from disk
+            let manager = build_manager(config).await;
+            let topic = "keyed";
+            manager.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+            for i in 0..2_000 {
+                // The older half is all one customer, the newer half spread over 4
+                let customer = if i < 1_000 { "old".to_string() } else { format!("c{}", i % 4) };
+                manager.publish(topic, Bytes::from(format!(r#"{{"customer":{{"id":"{}"}}}}"#, customer))).await.unwrap();
+            }
+            manager.publish(topic, Bytes::from("not json")).await.unwrap();
+
+            let spec = |sample: usize| KeyStatsSpec { pointer: "/customer/id".to_string(), sample: Some(sample), top: Some(2) };
+            let stats = manager.key_stats(topic, &spec(1_001)).await.unwrap();
+            assert_eq!((stats.from_seq, stats.to_seq, stats.sampled, stats.missing), (1_001, 2_001, 1_001, 1));
+            assert_eq!(stats.distinct, 4);
+            assert_eq!(stats.skew, 1.0);
+            assert_eq!(stats.top.iter().map(|k| (k.key.as_str(), k.count)).collect::<Vec<_>>(), vec![("c0", 250), ("c1", 250)]);
+
+            let stats = manager.key_stats(topic, &spec(10_000)).await.unwrap();
+            assert_eq!((stats.from_seq, stats.sampled, stats.distinct), (1, 2_001, 5));
+            assert_eq!((stats.top[0].key.as_str(), stats.top[0].count), ("old", 1_000));
+            assert!(stats.skew > 2.0);
+
+            assert_eq!(manager.key_stats("missing", &spec(10)).await.unwrap_err().code, ErrorCode::NotFound);
+            let bad = KeyStatsSpec { pointer: "customer".to_string(), sample: None, top: None };
+            assert_eq!(manager.key_stats(topic, &bad).await.unwrap_err().code, ErrorCode::InvalidRequest);
+        }
+
+        #[tokio::test]
+        async fn test_memory_pressure_spills_ram_window() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let path = temp_dir.path();
+            let mut config = Config::global().clone();
+            config.store.persistence_path = path.join("store").to_str().unwrap().to_string();
+            config.stream.persistence_path = path.join("streams").to_str().unwrap().to_string();
+            config.queue.persistence_path = path.join("queues").to_str().unwrap().to_string();
+            config.pubsub.persistence_path = path.join("pubsub").to_str().unwrap().to_string();
+            config.memory.budget_bytes = 10_000;
+            config.memory.high_water_percent = 50;
+            config.memory.sample_interval_ms = 3_600_000; // sampled by hand
+            let engine = nexo::NexoEngine::new(&config).await;
+            let topic = "spill-test";
+            engine.stream.create_topic(topic.to_string(), StreamCreateOptions::default()).await.unwrap();
+
+            for i in 0..8 {
+                engine.stream.publish(topic, Bytes::from(vec![i as u8; 1000])).await.unwrap();
+            }
+            assert_eq!(engine.stream.memory_usage(), 8000);
+
+            // Over the high-water mark: the RAM window goes to disk instead of shedding
+            assert_eq!(engine.sample_memory().await, nexo::brokers::memory::Pressure::Normal);
+            assert_eq!(engine.stream.memory_usage(), 0);
+            engine.stream.publish(topic, Bytes::from("after")).await.unwrap();
+
+            let spilled = engine.stream.read(topic, 1, 100).await;
+            assert_eq!(spilled.len(), 8, "Spilled messages are read back from disk");
+            assert_eq!(spilled[7].payload, Bytes::from(vec![7u8; 1000]));
+            let recent = engine.stream.read(topic, 9, 100).await;
+            assert_eq!(recent[0].payload, Bytes::from("after"));
+        }
     }
 
     mod performance {
@@ -737,6 +2200,80 @@ mod stream_tests {
             tokio::time::sleep(Duration::from_millis(200)).await;
             bench.stop();
         }
+
+        /// Flush latency with many active topics, per write backend.
+        /// cargo test --release --features io-uring bench_stream_write_backends -- --test-threads=1 --nocapture
+        #[tokio::test]
+        async fn bench_stream_write_backends() {
+            use nexo::brokers::stream::domain::persistence::{MessageToAppend, StorageCommand, StorageManager};
+            use std::sync::atomic::{AtomicU64, Ordering};
+
+            const TOPICS: usize = 64;
+            const ROUNDS: usize = 500;
+
+            for backend in [WriteBackend::Buffered, WriteBackend::IoUring] {
+                let temp_dir = tempfile::tempdir().unwrap();
+                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                let storage = StorageManager::new(temp_dir.path().to_str().unwrap().to_string(), rx, 256, 1, 104857600, None, backend);
+                tokio::spawn(storage.run());
+
+                let persisted: Vec<Arc<AtomicU64>> = (0..TOPICS).map(|_| Arc::new(AtomicU64::new(0))).collect();
+                let mut bench = Benchmark::start(&format!("STREAM FLUSH ({} topics, {:?})", TOPICS, backend), ROUNDS);
+                for round in 1..=ROUNDS as u64 {
+                    let start = Instant::now();
+                    for (t, seq) in persisted.iter().enumerate() {
+                        tx.send(StorageCommand::Append {
+                            topic_name: format!("topic-{}", t),
+                            messages: vec![MessageToAppend { seq: round, timestamp: 0, payload: Bytes::from(vec![7u8; 512]), lane: LANE_NORMAL }],
+                            persisted_seq: seq.clone(),
+                            span: tracing::Span::none(),
+                            durable: Vec::new(),
+                        }).unwrap();
+                    }
+                    while persisted.iter().any(|seq| seq.load(Ordering::Acquire) < round) {
+                        tokio::task::yield_now().await;
+                    }
+                    bench.record(start.elapsed());
+                }
+                bench.stop();
+            }
+        }
+
+        #[tokio::test]
+        async fn bench_stream_group_commit() {
+            // cargo test --release bench_stream_group_commit -- --test-threads=1 --nocapture
+            const PRODUCERS: usize = 32;
+            const PER_PRODUCER: usize = 100;
+
+            for (mode, window_ms) in [(PersistenceMode::FileAsync, 0), (PersistenceMode::FileSync, 0), (PersistenceMode::FileSync, 2)] {
+                let temp_dir = tempfile::tempdir().unwrap();
+                let mut config = get_test_config(temp_dir.path().to_str());
+                config.group_commit_ms = window_ms;
+                let manager = build_manager(config).await;
+                let options = StreamCreateOptions { persistence: Some(mode), ..Default::default() };
+                manager.create_topic("bench-group-commit".to_string(), options).await.unwrap();
+
+                let mut bench = Benchmark::start(&format!("PUBLISH - {:?}, {}ms window ({} producers)", mode, window_ms, PRODUCERS), PRODUCERS * PER_PRODUCER);
+                let handles: Vec<_> = (0..PRODUCERS).map(|_| {
+                    let manager = manager.clone();
+                    tokio::spawn(async move {
+                        let mut samples = Vec::with_capacity(PER_PRODUCER);
+                        for _ in 0..PER_PRODUCER {
+                            let start = Instant::now();
+                            manager.publish("bench-group-commit", Bytes::from("data")).await.unwrap();
+                            samples.push(start.elapsed());
+                        }
+                        samples
+                    })
+                }).collect();
+                for handle in handles {
+                    for sample in handle.await.unwrap() {
+                        bench.record(sample);
+                    }
+                }
+                bench.stop();
+            }
+        }
     }
 
     mod error_handling {
@@ -753,7 +2290,15 @@ mod stream_tests {
             let result = manager.publish("nonexistent_topic", Bytes::from("msg")).await;
             
             assert!(result.is_err(), "Publishing to nonexistent topic should fail");
-            assert_eq!(result.err().unwrap(), "Topic not found");
+            let err = result.err().unwrap();
+            assert_eq!(err.code, ErrorCode::NotFound);
+            assert_eq!(err.message, "Topic not found");
+            assert!(!err.retryable);
+
+            // Fetching a missing topic leaves no activity record behind
+            let err = manager.fetch("g", "c", 1, 10, "nonexistent_topic", 0).await.unwrap_err();
+            assert_eq!(err.code, ErrorCode::NotFound);
+            assert!(manager.activity().get("nonexistent_topic").is_none());
         }
 
         #[tokio::test]
@@ -797,6 +2342,22 @@ mod stream_tests {
             
             let result = manager.fetch("test_group", "client-A", 1, 10, "test_topic", 0).await;
             assert!(result.is_err(), "Fetch without join should fail");
+            assert_eq!(result.err().unwrap().code, ErrorCode::NotFound);
+        }
+
+        #[tokio::test]
+        async fn test_create_topic_with_invalid_name() {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let mut config = Config::global().stream.clone();
+            config.persistence_path = temp_dir.path().to_str().unwrap().to_string();
+
+            let manager = build_manager(config).await;
+            for name in ["..", "orders/eu", "orders:eu", &"x".repeat(201)] {
+                let err = manager.create_topic(name.to_string(), StreamCreateOptions::default()).await.unwrap_err();
+                assert_eq!(err.code, ErrorCode::InvalidName, "{:?} must be rejected", name);
+            }
+            assert!(manager.topic_names().is_empty());
+            assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().filter_map(|e| e.ok()).filter(|e| e.path().is_dir()).count(), 0, "Nothing created on disk");
         }
     }
 }
\ No newline at end of file